@@ -73,17 +73,367 @@ pub use ocl;
 /// A container that holds information needed for interacting with a GPU using OpenCL.
 ///
 /// You should really only use this if you intend to drop down to low-level OpenCL for maximum performance
-/// Buffers and programs are stored in hash tables. Programs are indexed by their source code.
+/// A `Gpu` is created with [`Gpu::new`](struct.Gpu.html#method.new), which reuses the OpenCL objects of earlier `Gpu`s for the same device.
+/// Buffers and programs are stored in hash tables. Programs are indexed by a hash of their source code, which is computed when the program is generated.
 /// Buffers are indexed by a `*const [f32]`. Given a value `data`, you can get the `*const [f32]` index with `get_buffer_key!(data)`.
 ///
 /// Note that `data` must have an `as_slice()` method defined for its type. As an example `data` could be of type `Vec`.
+/// Data made up of structures (see [`AsFloats`](trait.AsFloats.html)) is stored in buffers as a flat slice of `f32`s.
 pub struct Gpu {
     pub device: ocl::Device,
     pub context: ocl::Context,
     pub queue: ocl::Queue,
     pub buffers: std::collections::HashMap<*const [f32], ocl::Buffer<f32>>,
-    pub programs: std::collections::HashMap<String, ocl::Program>, // TODO cache kernels instead of programs if possible
-                                                                   // kernels can be cached instead of programs, if it is easy to change the dims and args of a kernel
+    pub programs: std::collections::HashMap<u64, ocl::Program>, // TODO cache kernels instead of programs if possible
+                                                                // kernels can be cached instead of programs, if it is easy to change the dims and args of a kernel
+    pub names: std::collections::HashMap<String, ocl::Buffer<f32>>, // buffers given a name with gpu_do!(name(data, "name"))
+    pub modified: std::collections::HashSet<*const [f32]>, // buffers written to by launched code since they were last loaded or read
+    pub cpu_threshold: usize, // launches with less work than this run on the CPU
+    free_buffers: Vec<ocl::Buffer<f32>>, // buffers that can be reused by gpu_do!(load(data)), see Gpu::reuse_buffer
+    cached: std::collections::HashSet<*const [f32]>, // data loaded with gpu_do!(load_cached(data)), see Gpu::cache_buffer
+    cached_buffers: std::collections::HashMap<*const [f32], ocl::Buffer<f32>>, // buffers of data loaded with load_cached by earlier Gpus
+}
+
+impl Drop for Gpu {
+    // the context, queue, programs, and buffers are kept so that the next Gpu created for the device can reuse them
+    fn drop(&mut self) {
+        let mut free_buffers = std::mem::take(&mut self.free_buffers);
+        let mut cached_buffers = std::mem::take(&mut self.cached_buffers);
+        for (key, buffer) in self.buffers.drain() {
+            if self.cached.contains(&key) {
+                cached_buffers.insert(key, buffer);
+            } else {
+                free_buffers.push(buffer);
+            }
+        }
+        // data cached by earlier Gpus that this one didn't use is the first to go
+        let unused = cached_buffers
+            .keys()
+            .filter(|key| !self.cached.contains(key))
+            .copied()
+            .collect::<Vec<_>>();
+        for key in unused {
+            if cached_buffers.len() <= MAX_FREE_BUFFERS {
+                break;
+            }
+            free_buffers.extend(cached_buffers.remove(&key));
+        }
+        if free_buffers.len() > MAX_FREE_BUFFERS {
+            free_buffers.drain(..free_buffers.len() - MAX_FREE_BUFFERS);
+        }
+        let runtime = Runtime {
+            device: self.device,
+            context: self.context.clone(),
+            queue: self.queue.clone(),
+            programs: std::mem::take(&mut self.programs),
+            free_buffers,
+            cached_buffers,
+        };
+        // this could be dropped while the thread is being torn down in which case there is nothing to reuse the runtime
+        let _ = RUNTIMES.try_with(|runtimes| runtimes.borrow_mut().push(runtime));
+    }
+}
+
+/// The amount of work below which a launch runs on the CPU instead of the GPU.
+///
+/// Launching a kernel has overhead (and so does moving data it uses between the CPU and GPU). A launched loop is
+/// run as a plain Rust loop on the CPU instead when the number of iterations plus the number of `f32`s in the arrays
+/// it uses is less than the `cpu_threshold` of the `Gpu`, which starts out as this. The loop then runs on copies of the
+/// arrays read back from the GPU (not on the data on the CPU, which may have changed since it was loaded) and the
+/// arrays it writes to are written back to the GPU. So the result is the same either way. The threshold can be changed
+/// by giving the `Gpu` a name.
+/// ```
+/// # extern crate em;
+/// # use em::*;
+/// #[gpu_use]
+/// fn main() {
+///     let mut data = vec![0.1; 1000];
+///
+///     // always launch on the GPU
+///     gpu_do!(with(gpu));
+///     gpu.cpu_threshold = 0;
+///
+///     gpu_do!(load(data));
+///     gpu_do!(launch());
+///     for i in 0..1000 {
+///         data[i] = data[i] * 10.0;
+///     }
+///     gpu_do!(read(data));
+/// }
+/// ```
+pub const DEFAULT_CPU_THRESHOLD: usize = 1 << 14;
+
+// the most buffers that are kept for each device to be reused by `Gpu`s created later
+const MAX_FREE_BUFFERS: usize = 16;
+
+// the OpenCL objects that `Gpu`s for a device are created from
+//
+// creating a context and a queue and building programs is slow so these are kept (for each thread) after a `Gpu` is dropped
+// and then reused by the next `Gpu` that is created for the same device
+struct Runtime {
+    device: ocl::Device,
+    context: ocl::Context,
+    queue: ocl::Queue,
+    programs: std::collections::HashMap<u64, ocl::Program>,
+    // buffers of dropped `Gpu`s that can be reused for loading data of the same length, oldest first
+    free_buffers: Vec<ocl::Buffer<f32>>,
+    // buffers of data loaded with gpu_do!(load_cached(data)) by dropped `Gpu`s
+    cached_buffers: std::collections::HashMap<*const [f32], ocl::Buffer<f32>>,
+}
+
+thread_local! {
+    static RUNTIMES: std::cell::RefCell<Vec<Runtime>> = std::cell::RefCell::new(vec![]);
+}
+
+impl Gpu {
+    /// Creates a `Gpu` for the given device of the given platform
+    ///
+    /// This is what `#[gpu_use]` does at the start of a tagged function that isn't a helper function. The OpenCL context, queue, and
+    /// programs are only created the first time a `Gpu` is created for a device on a thread. After that, they are reused along with the
+    /// programs built by earlier `Gpu`s for the device (and the buffers they loaded data to, which are reused for loading data of the same length).
+    /// So calling a tagged function in a loop doesn't build its programs over and over again.
+    /// ```
+    /// # extern crate em;
+    /// # use em::*;
+    /// #[gpu_use]
+    /// fn double(mut data: Vec<f32>) -> Vec<f32> {
+    ///     gpu_do!(with(gpu));
+    ///     gpu.cpu_threshold = 0;
+    ///     gpu_do!(load(data));
+    ///     gpu_do!(launch());
+    ///     for i in 0..data.len() {
+    ///         data[i] = data[i] * 2.0;
+    ///     }
+    ///     gpu_do!(read(data));
+    ///     data
+    /// }
+    ///
+    /// fn main() {
+    ///     let mut data = vec![1.0; 1000];
+    ///     // the program is only built the first time
+    ///     for _ in 0..10 {
+    ///         data = double(data);
+    ///     }
+    ///     assert_eq!(data, vec![1024.0; 1000]);
+    /// }
+    /// ```
+    pub fn new(platform: ocl::Platform, device: ocl::Device) -> Self {
+        let runtime = RUNTIMES.with(|runtimes| {
+            let mut runtimes = runtimes.borrow_mut();
+            let i = runtimes.iter().position(|runtime| runtime.device == device);
+            i.map(|i| runtimes.remove(i))
+        });
+        let runtime = runtime.unwrap_or_else(|| {
+            let context = ocl::Context::builder()
+                .platform(platform)
+                .devices(device.clone())
+                .build()
+                .expect("failed to build context for executing on GPU with OpenCL");
+            let queue = ocl::Queue::new(&context, device, None)
+                .expect("failed to create queue of commands to be sent to GPU");
+            Runtime {
+                device,
+                context,
+                queue,
+                programs: std::collections::HashMap::new(),
+                free_buffers: vec![],
+                cached_buffers: std::collections::HashMap::new(),
+            }
+        });
+
+        Gpu {
+            device: runtime.device,
+            context: runtime.context,
+            queue: runtime.queue,
+            buffers: std::collections::HashMap::new(),
+            programs: runtime.programs,
+            names: std::collections::HashMap::new(),
+            modified: std::collections::HashSet::new(),
+            cpu_threshold: DEFAULT_CPU_THRESHOLD,
+            free_buffers: runtime.free_buffers,
+            cached: std::collections::HashSet::new(),
+            cached_buffers: runtime.cached_buffers,
+        }
+    }
+
+    /// Takes a buffer of the given length that was loaded to by a dropped `Gpu` so that it can be loaded to again without allocating
+    ///
+    /// This is used by `gpu_do!(load(data))` when `data` isn't already loaded.
+    pub fn reuse_buffer(&mut self, len: usize) -> Option<ocl::Buffer<f32>> {
+        let i = self
+            .free_buffers
+            .iter()
+            .rposition(|buffer| buffer.len() == len)?;
+        Some(self.free_buffers.remove(i))
+    }
+
+    /// Makes the buffer of the data with the given key (see [`get_buffer_key!`](macro.get_buffer_key.html)) the buffer of that data in this `Gpu`
+    /// if an earlier `Gpu` loaded it with `gpu_do!(load_cached(data))`
+    ///
+    /// Returns whether the data is now loaded without having been uploaded again. This is used by `gpu_do!(load_cached(data))`.
+    pub fn reuse_cached_buffer(&mut self, key: *const [f32]) -> bool {
+        if self.buffers.contains_key(&key) {
+            return true;
+        }
+        match self.cached_buffers.remove(&key) {
+            Some(buffer) => {
+                self.buffers.insert(key, buffer);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Keeps the buffer of the data with the given key alive after this `Gpu` is dropped so that `gpu_do!(load_cached(data))` can reuse it
+    pub fn cache_buffer(&mut self, key: *const [f32]) {
+        self.cached.insert(key);
+    }
+
+    /// Gets the buffer that was given the given name with `gpu_do!(name(data, "name"))`
+    ///
+    /// Like [`get_buffer_key!`](macro.get_buffer_key.html), this is for dropping down to low-level OpenCL.
+    pub fn named(&self, name: &str) -> Option<&ocl::Buffer<f32>> {
+        self.names.get(name)
+    }
+}
+
+/// A type that is made up of only `f32`s and can be loaded to the GPU as a slice of `f32`s.
+///
+/// This is implemented for `f32`. You can implement it for your own structure so that you can load a `Vec` of
+/// that structure to the GPU and use its fields in launched code (e.g. - `particles[i].x = particles[i].x + 1.0`).
+/// ```
+/// # extern crate em;
+/// # use em::*;
+/// #[repr(C)]
+/// struct Particle {
+///     x: f32,
+///     y: f32,
+/// }
+///
+/// unsafe impl AsFloats for Particle {}
+/// ```
+///
+/// This is unsafe to implement because the structure must be `#[repr(C)]` and have only `f32` fields (or other types
+/// that implement `AsFloats`). Otherwise, reinterpreting it as `f32`s would be undefined behavior.
+pub unsafe trait AsFloats {}
+
+unsafe impl AsFloats for f32 {}
+
+/// Views a slice of `T` as a slice of `f32`s.
+///
+/// This is used for loading data to the GPU and getting keys to access buffers.
+pub fn as_floats<T: AsFloats>(slice: &[T]) -> &[f32] {
+    // this is safe because T is made up of only f32s
+    unsafe {
+        std::slice::from_raw_parts(
+            slice.as_ptr() as *const f32,
+            std::mem::size_of_val(slice) / std::mem::size_of::<f32>(),
+        )
+    }
+}
+
+/// Views a mutable slice of `T` as a mutable slice of `f32`s.
+///
+/// This is used for reading data back from the GPU.
+pub fn as_floats_mut<T: AsFloats>(slice: &mut [T]) -> &mut [f32] {
+    // this is safe because T is made up of only f32s
+    unsafe {
+        std::slice::from_raw_parts_mut(
+            slice.as_mut_ptr() as *mut f32,
+            std::mem::size_of_val(slice) / std::mem::size_of::<f32>(),
+        )
+    }
+}
+
+/// Views a slice of `f32`s as a slice of the same type as the given slice.
+///
+/// This is an internal of the code that `#[gpu_use]` generates and isn't meant to be called directly. The given slice is only used for its
+/// type. Launched code that runs on the CPU runs on copies of data read back from the GPU that are viewed like this.
+#[doc(hidden)]
+pub fn as_slice_like<'a, T: AsFloats>(_like: &[T], floats: &'a [f32]) -> &'a [T] {
+    // this is safe because T is made up of only f32s
+    unsafe {
+        std::slice::from_raw_parts(
+            floats.as_ptr() as *const T,
+            std::mem::size_of_val(floats) / std::mem::size_of::<T>(),
+        )
+    }
+}
+
+/// Views a mutable slice of `f32`s as a mutable slice of the same type as the given slice.
+///
+/// This is an internal of the code that `#[gpu_use]` generates and isn't meant to be called directly (see `as_slice_like`).
+#[doc(hidden)]
+pub fn as_slice_like_mut<'a, T: AsFloats>(_like: &[T], floats: &'a mut [f32]) -> &'a mut [T] {
+    // this is safe because T is made up of only f32s
+    unsafe {
+        std::slice::from_raw_parts_mut(
+            floats.as_mut_ptr() as *mut T,
+            std::mem::size_of_val(floats) / std::mem::size_of::<T>(),
+        )
+    }
+}
+
+/// Lists every device of every OpenCL platform along with the platform it belongs to.
+///
+/// This is the list that `#[gpu_use(device = 1)]` indexes into and that `#[gpu_use(device_name = "NVIDIA")]` searches.
+/// ```
+/// # extern crate em;
+/// # use em::*;
+/// for (i, (_platform, device)) in list_devices().iter().enumerate() {
+///     println!("{}: {}", i, device.name().unwrap_or_default());
+/// }
+/// ```
+pub fn list_devices() -> Vec<(ocl::Platform, ocl::Device)> {
+    ocl::Platform::list()
+        .into_iter()
+        .flat_map(|platform| {
+            ocl::Device::list_all(platform)
+                .unwrap_or_default()
+                .into_iter()
+                .map(move |device| (platform, device))
+        })
+        .collect()
+}
+
+/// Returns the number of `f32`s that each element of a slice of `T` is made up of.
+///
+/// This is used for reserving room on the GPU for a number of elements.
+pub fn floats_per_element<T: AsFloats>(_slice: &[T]) -> usize {
+    std::mem::size_of::<T>() / std::mem::size_of::<f32>()
+}
+
+/// Builds an OpenCL program from source code generated by `gpu_do!(launch())`.
+///
+/// If the `EMU_DUMP_KERNELS` environment variable is set to a directory, the source code is first written to a file there
+/// (named by a hash of the code). If the program fails to build, this panics with the source code (with line numbers) and the build log
+/// so that you can see what code was actually generated.
+pub fn build_program(gpu: &Gpu, src: &str) -> ocl::Program {
+    if let Some(dir) = std::env::var_os("EMU_DUMP_KERNELS") {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        src.hash(&mut hasher);
+        let path = std::path::Path::new(&dir).join(format!("{:016x}.cl", hasher.finish()));
+        // this is just for debugging so failing to write doesn't stop us from building
+        let _ = std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(path, src));
+    }
+
+    ocl::Program::builder()
+        .devices(gpu.device)
+        .src(src)
+        .build(&gpu.context)
+        .unwrap_or_else(|error| {
+            let width = src.lines().count().to_string().len();
+            let numbered_src: String = src
+                .lines()
+                .enumerate()
+                .map(|(i, line)| format!("{:>width$} | {}\n", i + 1, line, width = width))
+                .collect();
+            panic!(
+                "failed to compile program to be run on GPU\n\n{}\n\n{}",
+                error, numbered_src
+            )
+        })
 }
 
 /// A macro for getting key to access a `Buffer` in the `buffers` field of a `Gpu`.
@@ -96,10 +446,11 @@ pub struct Gpu {
 /// ```
 /// # extern crate em;
 /// # use em::*;
-/// #[gpu_use] // this inserts a "let gpu = Gpu { ... };" at the start of the main function
+/// #[gpu_use] // this creates a `Gpu` at the start of the main function
 /// fn main() {
 ///     let data = vec![0.0; 1000];
 ///     gpu_do!(load(data));
+///     gpu_do!(with(gpu)); // this gives the `Gpu` the name `gpu`
 ///     let buffer: &ocl::Buffer<f32> = gpu.buffers.get(&get_buffer_key!(data)).unwrap();
 ///
 ///     // do something with buffer...
@@ -108,7 +459,7 @@ pub struct Gpu {
 #[macro_export]
 macro_rules! get_buffer_key {
     ($i:ident) => {
-        ($i.as_slice() as *const [f32])
+        ($crate::as_floats($i.as_slice()) as *const [f32])
     };
 }
 
@@ -137,23 +488,178 @@ macro_rules! get_buffer_key {
 ///     gpu_do!(read(data)); // read data back from GPU
 /// }
 /// ```
-/// Concretely, there are 3 (only 3 at the moment) commands to the GPU that
-/// can be declared.
+/// Concretely, there are 10 commands to the GPU that can be declared.
 /// 1. Loading to the GPU with `gpu_do!(load(data))`
 /// 2. Reading from the GPU with `gpu_do!(read(data))`
-/// 3. Launching on the GPU with `gpu_do!(launch())`
+/// 3. Launching on the GPU with `gpu_do!(launch())` (optionally with options like `gpu_do!(launch(elements_per_thread = 4, unroll = 2))`)
+/// 4. Waiting for everything sent to the GPU so far to finish with `gpu_do!(sync())`
+/// 5. Freeing data on the GPU early with `gpu_do!(unload(data))`
+/// 6. Allocating room for `n` elements of data on the GPU without loading anything with `gpu_do!(reserve(data, n))`
+/// 7. Giving data on the GPU a name that other functions can use to get at it with `gpu_do!(name(data, "weights"))`
+/// 8. Declaring the length data must have with `gpu_do!(assert_len(data, 1024))`
+/// 9. Giving the `Gpu` itself a name to use it directly with `gpu_do!(with(gpu))`
+/// 10. Launching a closure over each element of data to produce new data with `gpu_do!(launch_map(data, |x| x * 2.0))`
+///
+/// A launch that is too small to be worth the overhead of launching runs as a plain Rust loop on the CPU instead (see
+/// [`DEFAULT_CPU_THRESHOLD`](constant.DEFAULT_CPU_THRESHOLD.html)). Either way, what you read back is the same.
+///
+/// By default, data stays on the GPU until the function that created the GPU returns and reads wait for whatever they depend on.
+/// `unload` and `reserve` let you control how much memory is used on the GPU and `sync` lets you control when you wait on the GPU.
+/// Reserving is useful for data that launches only write to. Just make sure you don't read data that you reserved before launching
+/// something that writes to all of it.
+/// ```
+/// # extern crate em;
+/// # use em::*;
+/// #[gpu_use]
+/// fn main() {
+///     let input = vec![1.0; 1000];
+///     let mut output = vec![0.0; 1000];
+///
+///     gpu_do!(load(input));
+///     gpu_do!(reserve(output, 1000)); // output doesn't need to be loaded since it's overwritten
+///     gpu_do!(launch());
+///     for i in 0..1000 {
+///         output[i] = input[i] * 2.0;
+///     }
+///     gpu_do!(sync()); // wait for the launch to finish
+///     gpu_do!(unload(input)); // input isn't needed on the GPU anymore
+///     gpu_do!(read(output));
+/// }
+/// ```
+///
+/// Naming data lets a helper function (see [`gpu_use`](attr.gpu_use.html)) use data that is already on the GPU without the data being
+/// passed to it. If `data` is loaded, `gpu_do!(name(data, "weights"))` gives its buffer the name `"weights"`. If `data` isn't loaded,
+/// it instead makes `data` use the buffer already named `"weights"` (which must hold as many floats as `data`). Reading `data` then reads
+/// from that buffer and launches that use `data` use that buffer. The buffer stays on the GPU as long as it has a name, even if it is unloaded.
+/// ```
+/// # extern crate em;
+/// # use em::*;
+/// #[gpu_use(scale)]
+/// fn main() {
+///     let weights = vec![1.0; 1000];
+///     gpu_do!(load(weights));
+///     gpu_do!(name(weights, "weights"));
+///     scale();
+/// }
+///
+/// #[gpu_use(scale)]
+/// fn scale() {
+///     // this doesn't need to be loaded since it gets the buffer named "weights"
+///     let mut weights = vec![0.0; 1000];
+///     gpu_do!(name(weights, "weights"));
+///     gpu_do!(launch());
+///     for i in 0..1000 {
+///         weights[i] = weights[i] * 2.0;
+///     }
+///     gpu_do!(read(weights));
+///     assert_eq!(weights, vec![2.0; 1000]);
+/// }
+/// ```
+///
+/// Each iteration of a launched for loop is normally run by its own work-item on the GPU. For cheap loops over a lot of data, that can leave
+/// a wide GPU spending more time starting work-items than doing work. A launch can instead have each work-item go over several iterations of
+/// the outermost loop with `gpu_do!(launch(elements_per_thread = 4))`. The iterations a work-item goes over are a whole grid of work-items
+/// apart (a grid-stride loop) so that neighboring work-items still access neighboring elements. Adding `unroll = 2` copies the body of the
+/// loop so that 2 of those iterations are done in each iteration of the grid-stride loop. It can't be more than `elements_per_thread`.
+/// ```
+/// # extern crate em;
+/// # use em::*;
+/// #[gpu_use]
+/// fn main() {
+///     let mut data = vec![1.0; 1 << 20];
+///     gpu_do!(load(data));
+///     gpu_do!(launch(elements_per_thread = 8, unroll = 4));
+///     for i in 0..data.len() {
+///         data[i] = data[i] * 2.0 + 1.0;
+///     }
+///     gpu_do!(read(data));
+///     assert_eq!(data, vec![3.0; 1 << 20]);
+/// }
+/// ```
+///
+/// Launching with `launch_map` doesn't change the data it maps. Instead, it launches the closure out-of-place into new data that is
+/// reserved on the GPU and returns that new data (a `Vec<f32>` as long as the data that is mapped). Like anything written by a launch, the
+/// new data is only brought back to the CPU when it is read. Until then, it stays on the GPU so that it can be mapped or launched over again
+/// without being copied back and forth. This way, a pipeline of pure functions doesn't have to mutate its input in place. The body of the
+/// closure is launched just like the right-hand side of an assignment in a launched for loop so it can use the same subset of Rust.
+/// ```
+/// # extern crate em;
+/// # use em::*;
+/// #[gpu_use]
+/// fn main() {
+///     let data = vec![1.0; 1000];
+///     let offset = 1.0f32;
+///
+///     gpu_do!(load(data));
+///     let doubled = gpu_do!(launch_map(data, |x| x * 2.0));
+///     let mut result = gpu_do!(launch_map(doubled, |x| x + offset));
+///     gpu_do!(read(result));
+///     assert_eq!(result, vec![3.0; 1000]);
+///     assert_eq!(data, vec![1.0; 1000]);
+/// }
+/// ```
+///
+/// Loading with `load_cached` instead of `load` keeps the buffer the data is loaded to alive after the function returns. When the function
+/// is called again (like in a loop) with data at the same address and of the same length, that buffer is reused without allocating or
+/// uploading anything. This is only correct if the data hasn't been changed on the CPU since it was last loaded or read, which is the case
+/// for data that is only changed by launches and read back after them (or not changed at all, like weights). Data that is written on the CPU
+/// in between calls should be loaded with `load`.
+/// ```
+/// # extern crate em;
+/// # use em::*;
+/// #[gpu_use]
+/// fn step(mut state: Vec<f32>) -> Vec<f32> {
+///     gpu_do!(load_cached(state));
+///     gpu_do!(launch());
+///     for i in 0..state.len() {
+///         state[i] = state[i] * 0.5;
+///     }
+///     gpu_do!(read(state));
+///     state
+/// }
+///
+/// fn main() {
+///     let mut state = vec![1024.0; 1000];
+///     for _ in 0..10 {
+///         state = step(state);
+///     }
+///     assert_eq!(state, vec![1.0; 1000]);
+/// }
+/// ```
+///
+/// Declaring the length of data with `assert_len` checks the length right away, every time the data is loaded, and before every launch
+/// that indexes the data. If a launched loop goes past the declared length, the launch panics saying so instead of just saying the data is
+/// too short. And if both the declared length and the range of the loop are literals, going past the declared length is an error at compile time.
+/// ```
+/// # extern crate em;
+/// # use em::*;
+/// #[gpu_use]
+/// fn main() {
+///     let mut data = vec![0.0; 1024];
+///     gpu_do!(assert_len(data, 1024));
+///     gpu_do!(load(data));
+///     gpu_do!(launch());
+///     for i in 0..1024 {
+///         data[i] = data[i] + 1.0;
+///     }
+///     gpu_do!(read(data));
+/// }
+/// ```
 ///
 /// Note that data must be an identifier. The only hard requirement for data is
 /// that it must have the 2 following methods.
-/// - `fn as_slice(&self) -> &[f32]`
-/// - `fn as_mut_slice(&mut self) -> &mut [f32]`
+/// - `fn as_slice(&self) -> &[T]`
+/// - `fn as_mut_slice(&mut self) -> &mut [T]`
 ///
-/// There is a soft requirement that the data should be representing a list of
-/// `f32`s and indexing it with `data[i]` should return an `f32`. But this is
+/// The slice must be of a type that implements [`AsFloats`](trait.AsFloats.html). So
+/// data can either be a list of `f32`s or a list of structures made up of `f32`s.
+/// Indexing it with `data[i]` should return an `f32` or such a structure. This is
 /// really just to ensure that when we lift code from CPU to GPU it is
-/// functionally equivalent in a sane way. Also, note that no invocation of
+/// functionally equivalent in a sane way. Fields of structures can be used in launched
+/// code with `data[i].x`. Also, note that no invocation of
 /// `gpu_do!()` will ever expand to anything, unless the function it's being
-/// used in is tagged with `#[gpu_use]`
+/// used in is tagged with `#[gpu_use]` (except for `launch_map` which
+/// just maps the data on the CPU)
 ///
 /// There is also a requirement that once data is loaded, it should not be
 /// re-allocated on the CPU in-between launches, reads that make use of it.
@@ -161,13 +667,130 @@ macro_rules! get_buffer_key {
 ///
 /// And in case the example doesn't make
 /// this clear, `gpu_do!(launch())` basically attempts to launch the following
-/// expression/piece of code on the GPU. Now, you can't just put any code you
+/// expression/piece of code on the GPU. This must be a for loop (or up to 3 nested for loops)
+/// over a range from 0 to either a literal (like `0..1000`), a variable or constant
+/// holding the size (like `0..width` or `0..N`), an arithmetic expression of those (like `0..2 * N`),
+/// or the length of data (like `0..data.len()`).
+/// Iterating over enumerated data also works and is launched just like a for loop
+/// over `0..data.len()`.
+/// ```
+/// # extern crate em;
+/// # use em::*;
+/// #[gpu_use]
+/// fn main() {
+///     let mut data = vec![0.0; 1000];
+///     gpu_do!(load(data));
+///     gpu_do!(launch());
+///     for (i, x) in data.iter_mut().enumerate() {
+///         *x = *x + 1.0;
+///     }
+///     gpu_do!(launch());
+///     data.iter_mut().enumerate().for_each(|(i, x)| *x = *x * 2.0);
+///     gpu_do!(read(data));
+/// }
+/// ```
+/// Now, you can't just put any code you
 /// want there. There is a very, very small subset of Rust code that can
 /// be launched. Anything outside of this subset will result in a compile-time
 /// error that will explain to you what was outside of the subset.
+///
+/// The exception is a scalar expression that doesn't depend on the loop's variables or read from any arrays (like
+/// `(scale - offset).abs()` or `settings.scale / data.len() as f32`). Any such expression that evaluates to an `f32`
+/// can be used since it is evaluated just once before launching and then passed in.
+///
+/// Expressions can use `+`, `-`, `*`, `/`, `%`, and negation. In indices (like `data[(i << 1) & 511]`), the bitwise operators and shifts
+/// can also be used. Assignments can be compound with `+=`, `-=`, `*=`, `/=`, or `%=`.
+///
+/// Variables can be declared with `let` (and shadowed) and then assigned to. A variable is either an `f32` or an index (`usize` or `i32`).
+/// Its type doesn't need to be written down since it is inferred from what it is initialized with, like `let x = data[i] * 2.0` or
+/// `let j = (i + 1) % 1000`. Casts like `i as f32` or `x as usize` convert between the two. If the type can't be inferred (like for
+/// `let big = data[i] > 1.0`), the error asks for it to be written down.
+///
+/// Besides assignments, launched code can have `while` loops, `loop`s, and `if`s (with `else if` and `else`). Their conditions are comparisons
+/// (like `data[i] > 1.0`) combined with `&&`, `||`, and `!` so they can depend on the data. `break` and `continue` work in loops inside of the
+/// launched loop but can't be used to leave the launched loop itself since every iteration runs on its own.
+///
+/// An iterative kernel that should stop once nothing changes (like relaxation until convergence) can't decide that on the GPU. Instead,
+/// launched code can set a flag in data that the CPU resets before each launch and reads after. Every iteration that sets the flag writes
+/// the same value so it doesn't matter which one is last.
+/// ```
+/// # extern crate em;
+/// # use em::*;
+/// #[gpu_use]
+/// fn main() {
+///     let mut data = vec![100.0; 1000];
+///     let mut changed = vec![0.0; 1];
+///
+///     gpu_do!(load(data));
+///     loop {
+///         changed[0] = 0.0;
+///         gpu_do!(load(changed));
+///         gpu_do!(launch());
+///         for i in 0..1000 {
+///             while data[i] > 1.0 {
+///                 data[i] *= 0.5;
+///                 changed[0] = 1.0;
+///                 if data[i] < 10.0 {
+///                     break;
+///                 }
+///             }
+///         }
+///         gpu_do!(read(changed));
+///         if changed[0] == 0.0 {
+///             break;
+///         }
+///     }
+///     gpu_do!(read(data));
+///     assert!(data.iter().all(|x| *x <= 1.0));
+/// }
+/// ```
+///
+/// Launched code can also call functions defined inside of the tagged function. Such a function must return an `f32` and its body must be
+/// a single expression in the same subset (which may come after some `let`s). Its parameters can be `f32`s, indices (`usize` or `i32`), or
+/// arrays (`&[f32]` or `&mut [f32]`), which are passed in without copying. These functions can call each other so that launched code can be
+/// composed instead of copy-pasted. Constants (`f32`s or indices) defined inside of the tagged function can be used both by launched code
+/// and by these functions. Each function and constant that is used is generated just once, before the launched code that uses it.
+/// ```
+/// # extern crate em;
+/// # use em::*;
+/// #[gpu_use]
+/// fn main() {
+///     let mut data = vec![1.0; 1000];
+///     let mut result = vec![0.0; 1000];
+///
+///     fn at(values: &[f32], i: usize) -> f32 {
+///         values[i]
+///     }
+///     fn doubled(values: &[f32], i: usize) -> f32 {
+///         at(values, i) + at(values, i)
+///     }
+///     const ALPHA: f32 = 0.01;
+///     fn damped(x: f32) -> f32 {
+///         let leak = x * ALPHA;
+///         x - leak
+///     }
+///
+///     gpu_do!(load(data));
+///     gpu_do!(load(result));
+///     gpu_do!(launch());
+///     for i in 0..1000 {
+///         result[i] = damped(doubled(&data, i) * 3.0);
+///     }
+///     gpu_do!(read(result));
+/// }
+/// ```
 #[macro_export]
 macro_rules! gpu_do {
     (load($i:ident)) => {};
+    (load_cached($i:ident)) => {};
     (read($i:ident)) => {};
-    (launch()) => {};
+    (launch($($option:ident = $value:expr),*)) => {};
+    (sync()) => {};
+    (unload($i:ident)) => {};
+    (reserve($i:ident, $n:expr)) => {};
+    (name($i:ident, $name:expr)) => {};
+    (assert_len($i:ident, $n:expr)) => {};
+    (launch_map($i:ident, $f:expr)) => {
+        $i.as_slice().iter().copied().map($f).collect::<Vec<f32>>()
+    };
 }
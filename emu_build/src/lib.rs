@@ -0,0 +1,340 @@
+//! `emu_build` generates typed Rust wrappers for GLSL compute shaders from a build script.
+//!
+//! Instead of declaring the parameters of each kernel by hand (and keeping them in sync with the GLSL), you can keep your compute shaders
+//! in a directory and have a [`Builder`](struct.Builder.html) compile each of them to SPIR-V when your crate is built. The buffers that each
+//! shader takes are reflected from the SPIR-V and a Rust module is generated with one function per shader. Each function takes a
+//! [`Spawner`](../emu_core/spawn/struct.Spawner.html) and a `DeviceBox` of the reflected type for each buffer, and launches the shader. The SPIR-V
+//! is embedded in your program with [`include_spirv!`](../emu_core/macro.include_spirv.html) so the GLSL isn't compiled again at run-time.
+//!
+//! For a shader at `shaders/double.comp` that declares `layout(set = 0, binding = 0) buffer Data { float[] data; };`, your build script
+//! would be the following.
+//! ```no_run
+//! fn main() {
+//!     emu_build::Builder::new("shaders").generate().unwrap();
+//! }
+//! ```
+//! Then you can include the generated module and launch the shader with a `&mut DeviceBox<[f32]>`.
+//! ```ignore
+//! use emu_core::prelude::*;
+//!
+//! mod kernels {
+//!     include!(concat!(env!("OUT_DIR"), "/emu_kernels.rs"));
+//! }
+//!
+//! fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     futures::executor::block_on(assert_device_pool_initialized());
+//!     let mut data: DeviceBox<[f32]> = vec![1.0; 1024].as_device_boxed_mut()?;
+//!     unsafe { kernels::double(&spawn(1024), &mut data)?; }
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Only storage buffers in set 0 that have a single member (like a `float[]` or a `uvec4`) are supported. Types are mapped to Rust types
+//! as they are (a `vec4` is a `[f32; 4]`) so buffers of types that are padded (like an array of `vec3`s) should be avoided. Like `emu_core`,
+//! this compiles GLSL with `shaderc` by default or with `naga` with the `glsl-compile-naga` feature.
+
+use emu_core::compile::*;
+use emu_core::compile_impls::*;
+use emu_core::error::CompileError;
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+pub mod reflect;
+
+use reflect::*;
+
+// the name of the file in OUT_DIR that Builder::generate writes
+const GENERATED_FILE: &str = "emu_kernels.rs";
+
+/// An error in generating wrappers for shaders
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum BuildError {
+    #[error("failed to read or write {}", .path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("failed to compile {}", .path.display())]
+    Compile {
+        path: PathBuf,
+        #[source]
+        source: CompileError,
+    },
+    /// The shader takes something that a typed wrapper can't be generated for
+    #[error("can't generate a wrapper for {}: {}", .path.display(), .message)]
+    Unsupported { path: PathBuf, message: String },
+    /// `OUT_DIR` isn't set, which means that [`generate`](struct.Builder.html#method.generate) wasn't called from a build script
+    #[error("OUT_DIR isn't set (`generate` should be called from a build script)")]
+    NoOutDir,
+}
+
+/// A builder for generating a Rust module with a wrapper for each compute shader in a directory
+pub struct Builder {
+    dir: PathBuf,
+    extensions: Vec<String>,
+    optimization: Optimization,
+}
+
+impl Builder {
+    /// Starts generating wrappers for the shaders in the given directory (relative to the root of the crate being built)
+    ///
+    /// By default, files with the `comp` extension are shaders.
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            extensions: vec![String::from("comp")],
+            optimization: Optimization::None,
+        }
+    }
+
+    /// Also treats files with the given extension (like `"glsl"`) as shaders
+    pub fn extension(mut self, extension: impl Into<String>) -> Self {
+        self.extensions.push(extension.into());
+        self
+    }
+
+    /// Sets how much the shaders should be optimized when compiled to SPIR-V (see [`Optimization`](../emu_core/compile_impls/enum.Optimization.html))
+    pub fn optimization(mut self, optimization: Optimization) -> Self {
+        self.optimization = optimization;
+        self
+    }
+
+    /// Generates the module in `emu_kernels.rs` in `OUT_DIR`
+    ///
+    /// This should be called from a build script. It tells Cargo to build again whenever the directory of shaders changes.
+    pub fn generate(&self) -> Result<(), BuildError> {
+        let out_dir = std::env::var_os("OUT_DIR").ok_or(BuildError::NoOutDir)?;
+        println!("cargo:rerun-if-changed={}", self.dir.display());
+        for shader in self.shaders()? {
+            println!("cargo:rerun-if-changed={}", shader.display());
+        }
+        self.generate_in(out_dir)?;
+        Ok(())
+    }
+
+    /// Generates the module in `emu_kernels.rs` in the given directory and returns the path of the generated module
+    ///
+    /// The SPIR-V of each shader is written next to the module.
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let shaders = std::env::temp_dir().join("emu_build_shaders");
+    /// std::fs::create_dir_all(&shaders)?;
+    /// std::fs::write(
+    ///     shaders.join("double.comp"),
+    ///     r#"
+    /// #version 450
+    /// layout(local_size_x = 1) in;
+    /// layout(set = 0, binding = 0) buffer Data {
+    ///     float[] data;
+    /// };
+    /// layout(set = 0, binding = 1) readonly buffer Scale {
+    ///     float scale;
+    /// };
+    /// void main() {
+    ///     data[gl_GlobalInvocationID.x] *= scale;
+    /// }
+    ///     "#,
+    /// )?;
+    ///
+    /// let module = emu_build::Builder::new(&shaders).generate_in(&shaders)?;
+    /// let module = std::fs::read_to_string(module)?;
+    /// assert!(module.contains("pub unsafe fn double("));
+    /// assert!(module.contains("data: &mut ::emu_core::device::DeviceBox<[f32]>"));
+    /// assert!(module.contains("scale: &::emu_core::device::DeviceBox<f32>"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn generate_in(&self, out_dir: impl AsRef<Path>) -> Result<PathBuf, BuildError> {
+        let out_dir = out_dir.as_ref();
+        let mut module = String::from("// generated by emu_build, do not edit\n");
+        for shader in self.shaders()? {
+            let name = identifier(&shader.file_stem().unwrap_or_default().to_string_lossy());
+            let spirv = self.compile(&shader)?;
+            let interface = reflect(&spirv).map_err(|message| BuildError::Unsupported {
+                path: shader.clone(),
+                message,
+            })?;
+
+            // the SPIR-V is embedded with include_spirv! so it's checked again when the generated module is compiled
+            let spirv_path = out_dir.join(format!("{}.spv", name));
+            let bytes: Vec<u8> = spirv
+                .iter()
+                .flat_map(|word| word.to_ne_bytes().to_vec())
+                .collect();
+            std::fs::write(&spirv_path, bytes).map_err(|source| BuildError::Io {
+                path: spirv_path.clone(),
+                source,
+            })?;
+            module += &wrapper(&shader, &name, &spirv_path, &interface)?;
+        }
+
+        let module_path = out_dir.join(GENERATED_FILE);
+        std::fs::write(&module_path, module).map_err(|source| BuildError::Io {
+            path: module_path.clone(),
+            source,
+        })?;
+        Ok(module_path)
+    }
+
+    // the paths of the shaders in the directory, sorted so that the generated module doesn't change from build to build
+    fn shaders(&self) -> Result<Vec<PathBuf>, BuildError> {
+        let io_error = |source| BuildError::Io {
+            path: self.dir.clone(),
+            source,
+        };
+        let mut shaders = vec![];
+        for entry in std::fs::read_dir(&self.dir).map_err(io_error)? {
+            let path = entry.map_err(io_error)?.path();
+            let is_shader = path.extension().map_or(false, |extension| {
+                self.extensions
+                    .iter()
+                    .any(|shader_extension| extension == shader_extension.as_str())
+            });
+            if path.is_file() && is_shader {
+                shaders.push(path);
+            }
+        }
+        shaders.sort();
+        Ok(shaders)
+    }
+
+    fn compile(&self, shader: &Path) -> Result<Vec<u32>, BuildError> {
+        let code = std::fs::read_to_string(shader).map_err(|source| BuildError::Io {
+            path: shader.to_path_buf(),
+            source,
+        })?;
+        let glsl = Glsl::new()
+            .set_code_with_glsl(code)
+            .set_optimization(self.optimization);
+        <GlslCompile as CompileToSpirv<Glsl, Vec<u32>>>::compile_to_spirv(glsl)
+            .map(|spirv| spirv.code)
+            .map_err(|source| BuildError::Compile {
+                path: shader.to_path_buf(),
+                source,
+            })
+    }
+}
+
+// generates the function that launches the given shader
+fn wrapper(
+    shader: &Path,
+    name: &str,
+    spirv_path: &Path,
+    interface: &Interface,
+) -> Result<String, BuildError> {
+    // arguments are passed to bindings 0, 1, 2, and so on of set 0 in order
+    for (i, param) in interface.params.iter().enumerate() {
+        if param.set != 0 || param.binding != i as u32 {
+            return Err(BuildError::Unsupported {
+                path: shader.to_path_buf(),
+                message: format!(
+                    "buffers should be bound to bindings 0, 1, 2, and so on of set 0 but binding {} of set {} is bound",
+                    param.binding, param.set
+                ),
+            });
+        }
+    }
+    let param_names: Vec<String> = interface
+        .params
+        .iter()
+        .map(|param| match &param.name {
+            Some(name) => identifier(name),
+            None => format!("arg{}", param.binding),
+        })
+        .collect();
+
+    let mut code = String::new();
+    let file_name = shader.file_name().unwrap_or_default().to_string_lossy();
+    writeln!(code).unwrap();
+    writeln!(
+        code,
+        "/// Launches the kernel compiled from `{}`",
+        file_name
+    )
+    .unwrap();
+    writeln!(code, "///").unwrap();
+    writeln!(
+        code,
+        "/// This is unsafe for the same reason launching any kernel is unsafe."
+    )
+    .unwrap();
+    writeln!(code, "#[allow(dead_code)]").unwrap();
+    write!(
+        code,
+        "pub unsafe fn {}(spawner: &::emu_core::spawn::Spawner",
+        name
+    )
+    .unwrap();
+    for (param, param_name) in interface.params.iter().zip(&param_names) {
+        write!(
+            code,
+            ", {}: &{}::emu_core::device::DeviceBox<{}>",
+            param_name,
+            if param.mutable { "mut " } else { "" },
+            param.rust_type
+        )
+        .unwrap();
+    }
+    writeln!(code, ") -> Result<(), ::emu_core::error::KernelError> {{").unwrap();
+    writeln!(
+        code,
+        "    let params = ::emu_core::device::ParamsBuilder::new()"
+    )
+    .unwrap();
+    for param in &interface.params {
+        writeln!(
+            code,
+            "        .param::<{}>(::emu_core::device::Mutability::{})",
+            param.rust_type,
+            if param.mutable { "Mut" } else { "Const" }
+        )
+        .unwrap();
+    }
+    writeln!(code, "        .build();").unwrap();
+    writeln!(
+        code,
+        "    let spirv = ::emu_core::include_spirv!({:?}, params);",
+        spirv_path.display().to_string()
+    )
+    .unwrap();
+    writeln!(code, "    let kernel = ::emu_core::compile::compile::<::emu_core::compile::Spirv<Vec<u32>>, ::emu_core::compile_impls::SpirvCompile, _, ::emu_core::cache::GlobalCache>(spirv)?.finish()?;").unwrap();
+    write!(
+        code,
+        "    let args = ::emu_core::device::ArgsBuilder::new()"
+    )
+    .unwrap();
+    for param_name in &param_names {
+        write!(code, ".arg({})", param_name).unwrap();
+    }
+    writeln!(code, ".build();").unwrap();
+    writeln!(code, "    spawner.launch((kernel, args))?;").unwrap();
+    writeln!(code, "    Ok(())").unwrap();
+    writeln!(code, "}}").unwrap();
+    Ok(code)
+}
+
+// turns the given name (of a file or a buffer) into a Rust identifier
+fn identifier(name: &str) -> String {
+    let mut identifier: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if identifier.is_empty() || identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        identifier.insert(0, '_');
+    }
+    // the names of buffers could be Rust keywords (like `type` or `ref`)
+    const KEYWORDS: &[&str] = &[
+        "as", "box", "break", "const", "continue", "crate", "else", "enum", "extern", "fn", "for",
+        "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+        "self", "static", "struct", "super", "trait", "type", "unsafe", "use", "where", "while",
+        "spawner", "params", "spirv", "kernel", "args",
+    ];
+    if KEYWORDS.contains(&identifier.as_str()) {
+        identifier.push('_');
+    }
+    identifier
+}
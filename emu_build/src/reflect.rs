@@ -0,0 +1,303 @@
+//! Reflecting the buffers that a compute shader compiled to SPIR-V takes
+
+use std::collections::HashMap;
+
+const OP_NAME: u32 = 5;
+const OP_MEMBER_NAME: u32 = 6;
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+const DECORATION_BLOCK: u32 = 2;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_NON_WRITABLE: u32 = 24;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+
+const STORAGE_CLASS_UNIFORM: u32 = 2;
+const STORAGE_CLASS_STORAGE_BUFFER: u32 = 12;
+
+const EXECUTION_MODEL_GL_COMPUTE: u32 = 5;
+
+/// A buffer that a compute shader takes as a parameter
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Param {
+    /// The descriptor set the buffer is bound in
+    pub set: u32,
+    /// The binding of the buffer in its set
+    pub binding: u32,
+    /// The name of the buffer's only member if it has one (or else the name of the buffer)
+    pub name: Option<String>,
+    /// The Rust type that a `DeviceBox` passed in for the buffer holds (like `[f32]` or `[u32; 4]`)
+    pub rust_type: String,
+    /// Whether or not the shader can write to the buffer
+    pub mutable: bool,
+}
+
+/// The interface of a compute shader compiled to SPIR-V
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Interface {
+    /// The name of the first compute entry point
+    pub entry_point: String,
+    /// The buffers the shader takes, in order of set and then binding
+    pub params: Vec<Param>,
+}
+
+// the instructions of a SPIR-V module that describe its interface
+#[derive(Default)]
+struct Module {
+    entry_point: Option<String>,
+    names: HashMap<u32, String>,
+    member_names: HashMap<(u32, u32), String>,
+    decorations: HashMap<u32, Vec<(u32, Option<u32>)>>, // each decoration with its first literal, if it has one
+    member_decorations: HashMap<u32, Vec<u32>>, // the decorations of any member of each struct
+    types: HashMap<u32, (u32, Vec<u32>)>, // the opcode and operands (after the result id) of each type
+    constants: HashMap<u32, u32>,
+    variables: Vec<(u32, u32, u32)>, // the pointer type, id, and storage class of each global variable
+}
+
+/// Reflects the interface of the given SPIR-V
+///
+/// Only storage buffers with a single member are reflected. An error describes anything else (like a uniform buffer or a buffer of structs)
+/// that a wrapper can't be generated for.
+pub fn reflect(code: &[u32]) -> Result<Interface, String> {
+    let module = parse(code)?;
+    let entry_point = module
+        .entry_point
+        .clone()
+        .ok_or_else(|| String::from("there is no compute entry point"))?;
+
+    let mut params = vec![];
+    for &(pointer_type, id, storage_class) in &module.variables {
+        let decoration = |decoration: u32| {
+            module
+                .decorations
+                .get(&id)
+                .and_then(|decorations| decorations.iter().find(|(d, _)| *d == decoration))
+        };
+        let (set, binding) = match (
+            decoration(DECORATION_DESCRIPTOR_SET),
+            decoration(DECORATION_BINDING),
+        ) {
+            (Some((_, Some(set))), Some((_, Some(binding)))) => (*set, *binding),
+            _ => continue, // not a resource (like gl_GlobalInvocationID)
+        };
+        let block = match module.types.get(&pointer_type) {
+            Some((OP_TYPE_POINTER, operands)) if operands.len() == 2 => operands[1],
+            _ => return Err(format!("binding {} isn't a pointer", binding)),
+        };
+        let block_decorations = module.decorations.get(&block);
+        let is_decorated = |decoration: u32| {
+            block_decorations.map_or(false, |decorations| {
+                decorations.iter().any(|(d, _)| *d == decoration)
+            })
+        };
+        let is_storage_buffer = (storage_class == STORAGE_CLASS_STORAGE_BUFFER
+            && is_decorated(DECORATION_BLOCK))
+            || (storage_class == STORAGE_CLASS_UNIFORM && is_decorated(DECORATION_BUFFER_BLOCK));
+        if !is_storage_buffer {
+            return Err(format!(
+                "binding {} isn't a storage buffer (only storage buffers can be passed `DeviceBox`s)",
+                binding
+            ));
+        }
+        let member = match module.types.get(&block) {
+            Some((OP_TYPE_STRUCT, members)) if members.len() == 1 => members[0],
+            _ => {
+                return Err(format!(
+                    "binding {} should have exactly 1 member so that it can be passed a single `DeviceBox`",
+                    binding
+                ))
+            }
+        };
+        let rust_type = match module.types.get(&member) {
+            Some((OP_TYPE_RUNTIME_ARRAY, operands)) => {
+                format!("[{}]", module.rust_type(operands[0], binding)?)
+            }
+            _ => module.rust_type(member, binding)?,
+        };
+        // glslang decorates the members of a readonly buffer and other compilers decorate the variable
+        let mutable = decoration(DECORATION_NON_WRITABLE).is_none()
+            && !module
+                .member_decorations
+                .get(&block)
+                .map_or(false, |decorations| {
+                    decorations.contains(&DECORATION_NON_WRITABLE)
+                });
+        let name = module
+            .member_names
+            .get(&(block, 0))
+            .or_else(|| module.names.get(&id))
+            .filter(|name| !name.is_empty())
+            .cloned();
+        params.push(Param {
+            set,
+            binding,
+            name,
+            rust_type,
+            mutable,
+        });
+    }
+    params.sort_by_key(|param| (param.set, param.binding));
+
+    Ok(Interface {
+        entry_point,
+        params,
+    })
+}
+
+fn parse(code: &[u32]) -> Result<Module, String> {
+    if code.len() < 5 || code[0] != 0x07230203 {
+        return Err(String::from("the compiled shader isn't SPIR-V"));
+    }
+
+    // skip the 5-word header and then walk through instructions
+    // each instruction starts with a word holding its word count (high 16 bits) and opcode (low 16 bits)
+    let mut module = Module::default();
+    let mut i = 5;
+    while i < code.len() {
+        let word_count = (code[i] >> 16) as usize;
+        let opcode = code[i] & 0xffff;
+        if word_count == 0 || i + word_count > code.len() {
+            return Err(String::from(
+                "the compiled shader has a malformed instruction",
+            ));
+        }
+        let operands = &code[i + 1..i + word_count];
+        match opcode {
+            OP_NAME if operands.len() >= 2 => {
+                module.names.insert(operands[0], string(&operands[1..]));
+            }
+            OP_MEMBER_NAME if operands.len() >= 3 => {
+                module
+                    .member_names
+                    .insert((operands[0], operands[1]), string(&operands[2..]));
+            }
+            OP_ENTRY_POINT
+                if operands.len() >= 3
+                    && operands[0] == EXECUTION_MODEL_GL_COMPUTE
+                    && module.entry_point.is_none() =>
+            {
+                module.entry_point = Some(string(&operands[2..]));
+            }
+            OP_TYPE_INT
+            | OP_TYPE_FLOAT
+            | OP_TYPE_VECTOR
+            | OP_TYPE_MATRIX
+            | OP_TYPE_ARRAY
+            | OP_TYPE_RUNTIME_ARRAY
+            | OP_TYPE_STRUCT
+            | OP_TYPE_POINTER
+                if !operands.is_empty() =>
+            {
+                module
+                    .types
+                    .insert(operands[0], (opcode, operands[1..].to_vec()));
+            }
+            OP_CONSTANT if operands.len() >= 3 => {
+                module.constants.insert(operands[1], operands[2]);
+            }
+            OP_VARIABLE if operands.len() >= 3 => {
+                module
+                    .variables
+                    .push((operands[0], operands[1], operands[2]));
+            }
+            OP_DECORATE if operands.len() >= 2 => {
+                module
+                    .decorations
+                    .entry(operands[0])
+                    .or_default()
+                    .push((operands[1], operands.get(2).copied()));
+            }
+            OP_MEMBER_DECORATE if operands.len() >= 3 => {
+                module
+                    .member_decorations
+                    .entry(operands[0])
+                    .or_default()
+                    .push(operands[2]);
+            }
+            _ => {}
+        }
+        i += word_count;
+    }
+    Ok(module)
+}
+
+impl Module {
+    // the Rust type that the given SPIR-V type is laid out like
+    fn rust_type(&self, id: u32, binding: u32) -> Result<String, String> {
+        match self.types.get(&id) {
+            Some((OP_TYPE_INT, operands)) if operands.len() == 2 => {
+                match (operands[0], operands[1]) {
+                    (32, 0) => Ok(String::from("u32")),
+                    (32, _) => Ok(String::from("i32")),
+                    (64, 0) => Ok(String::from("u64")),
+                    (64, _) => Ok(String::from("i64")),
+                    (width, _) => Err(format!(
+                        "binding {} has {}-bit integers, which aren't supported",
+                        binding, width
+                    )),
+                }
+            }
+            Some((OP_TYPE_FLOAT, operands)) if operands.len() == 1 => match operands[0] {
+                32 => Ok(String::from("f32")),
+                64 => Ok(String::from("f64")),
+                width => Err(format!(
+                    "binding {} has {}-bit floats, which aren't supported",
+                    binding, width
+                )),
+            },
+            // vectors and matrices are laid out like arrays of their components and columns
+            Some((OP_TYPE_VECTOR, operands)) | Some((OP_TYPE_MATRIX, operands))
+                if operands.len() == 2 =>
+            {
+                Ok(format!(
+                    "[{}; {}]",
+                    self.rust_type(operands[0], binding)?,
+                    operands[1]
+                ))
+            }
+            Some((OP_TYPE_ARRAY, operands)) if operands.len() == 2 => {
+                let len = self.constants.get(&operands[1]).ok_or_else(|| {
+                    format!(
+                        "binding {} has an array whose length isn't a constant",
+                        binding
+                    )
+                })?;
+                Ok(format!(
+                    "[{}; {}]",
+                    self.rust_type(operands[0], binding)?,
+                    len
+                ))
+            }
+            Some((OP_TYPE_STRUCT, _)) => Err(format!(
+                "binding {} holds a struct, which can't be mapped to a Rust type",
+                binding
+            )),
+            _ => Err(format!(
+                "binding {} holds a type that can't be mapped to a Rust type",
+                binding
+            )),
+        }
+    }
+}
+
+// reads a nul-terminated string packed into little-endian words
+fn string(words: &[u32]) -> String {
+    let bytes: Vec<u8> = words
+        .iter()
+        .flat_map(|word| word.to_le_bytes().to_vec())
+        .take_while(|byte| *byte != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
@@ -12,10 +12,10 @@ struct Shape {
     r: [i32; 2],
 }
 
-#[cfg(not(feature = "glsl-compile"))]
+#[cfg(not(any(feature = "glsl-compile", feature = "glsl-compile-naga")))]
 fn main() {}
 
-#[cfg(feature = "glsl-compile")]
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // ensure that a device pool has been initialized
     // this should be called before every time when you assume you have devices to use
@@ -0,0 +1,70 @@
+#[cfg(not(any(feature = "glsl-compile", feature = "glsl-compile-naga")))]
+fn main() {}
+
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    use emu_core::canvas::*;
+    use emu_core::prelude::*;
+
+    futures::executor::block_on(assert_device_pool_initialized());
+
+    // ray march a scene of signed distance fields (a sphere sitting on a plane)
+    // each pixel is shaded by a compute kernel so there is no render pass or window
+    let mut canvas = ComputeCanvas::new(640, 480)?;
+    canvas.render(
+        r#"
+float sdf(vec3 p) {
+    float sphere = length(p - vec3(0.0, 1.0, 0.0)) - 1.0;
+    float plane = p.y;
+    return min(sphere, plane);
+}
+
+vec3 normal(vec3 p) {
+    vec2 e = vec2(0.001, 0.0);
+    return normalize(vec3(
+        sdf(p + e.xyy) - sdf(p - e.xyy),
+        sdf(p + e.yxy) - sdf(p - e.yxy),
+        sdf(p + e.yyx) - sdf(p - e.yyx)
+    ));
+}
+
+// how far a ray from p toward the light gets before hitting something, for soft shadows
+float shadow(vec3 p, vec3 light) {
+    float result = 1.0;
+    float t = 0.02;
+    for (int i = 0; i < 64 && t < 10.0; i++) {
+        float d = sdf(p + light * t);
+        result = min(result, 8.0 * d / t);
+        t += clamp(d, 0.01, 0.5);
+    }
+    return clamp(result, 0.0, 1.0);
+}
+
+vec4 shade(vec2 uv) {
+    float aspect = float(resolution.x) / float(resolution.y);
+    vec3 origin = vec3(0.0, 1.5, -4.0);
+    vec3 direction = normalize(vec3((uv.x * 2.0 - 1.0) * aspect, 1.0 - uv.y * 2.0 - 0.2, 1.5));
+    vec3 light = normalize(vec3(-0.6, 1.0, -0.4));
+
+    float t = 0.0;
+    for (int i = 0; i < 128 && t < 50.0; i++) {
+        vec3 p = origin + direction * t;
+        float d = sdf(p);
+        if (d < 0.001) {
+            float diffuse = max(dot(normal(p), light), 0.0) * shadow(p, light);
+            return vec4(vec3(0.1 + 0.9 * diffuse), 1.0);
+        }
+        t += d;
+    }
+
+    // sky
+    return vec4(mix(vec3(0.4, 0.6, 1.0), vec3(0.9), uv.y), 1.0);
+}
+"#,
+        0.0,
+    )?;
+
+    futures::executor::block_on(canvas.save_png("compute_render.png"))?;
+    println!("saved compute_render.png");
+    Ok(())
+}
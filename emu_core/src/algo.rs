@@ -0,0 +1,897 @@
+//! Ready-made kernels for common algorithms
+//!
+//! These are launched on the device currently selected from the pool and work on `DeviceBox`s that are already on that device.
+//! Images are stored as a `DeviceBox<[f32]>` of `width * height` pixels in row-major order. This module requires the `glsl-compile` feature.
+//! - [`convolve_2d`](fn.convolve_2d.html) for convolving an image with an arbitrary small filter
+//! - [`gaussian_blur`](fn.gaussian_blur.html) for a separable Gaussian blur
+//! - [`stencil_5`](fn.stencil_5.html) and [`stencil_9`](fn.stencil_9.html) for 5-point and 9-point stencils (like a step of a heat equation solver)
+//! - [`Precision`](enum.Precision.html) for picking whether the filters above add up their terms in half, single, or double precision
+//! - [`histogram`](fn.histogram.html) for counting how many times each value occurs
+//! - [`device_map`](fn.device_map.html) for running a bit of GLSL on each element of an array of structures
+//! - [`DeviceBox::check_finite`](../device/struct.DeviceBox.html#method.check_finite) for finding NaNs and infinities in a `DeviceBox<[f32]>`
+//! - [`DeviceBox::convert`](../device/struct.DeviceBox.html#method.convert) for converting the elements of a `DeviceBox` to another type (like bytes of
+//! an image to `f32`s)
+
+use zerocopy::*;
+
+use crate::boxed::*;
+use crate::cache::*;
+use crate::compile::*;
+use crate::compile_impls::*;
+use crate::device::*;
+use crate::error::*;
+use crate::pool::*;
+use crate::spawn::*;
+
+// the width and height of the tile of pixels that each thread block computes
+const TILE_SIZE: u32 = 16;
+// the largest width or height of a filter
+// the tile and its border must fit in 16 KB of shared memory, which is the least any device has
+const MAX_FILTER_SIZE: u32 = 49;
+// the number of threads in each thread block of the histogram kernels
+const HISTOGRAM_BLOCK_SIZE: u32 = 256;
+// the most thread blocks the histogram kernels launch, each thread block strides through the data
+const MAX_HISTOGRAM_BLOCKS: u32 = 256;
+// the most bins that we privatize in shared memory, this many counts fit in 16 KB
+const MAX_PRIVATIZED_BINS: usize = 4096;
+// the number of threads in each thread block of the kernel for device_map
+const MAP_BLOCK_SIZE: u32 = 64;
+// the number of threads in each thread block of the kernel for check_finite
+const CHECK_BLOCK_SIZE: u32 = 256;
+// the most thread blocks the kernel for check_finite launches, each thread block strides through the data
+const MAX_CHECK_BLOCKS: u32 = 256;
+// the number of threads in each thread block of the kernels for convert
+const CONVERT_BLOCK_SIZE: u32 = 64;
+// the most thread blocks the kernels for convert launch, each thread block strides through the data
+const MAX_CONVERT_BLOCKS: u32 = 1024;
+
+/// Convolves an image with a filter
+///
+/// `filter` has `filter_width * filter_height` weights in row-major order. Both dimensions of the filter must be odd and no more than 49.
+/// The filter is centered on each pixel and pixels past the edges of the image are clamped to the nearest edge. Like most image processing
+/// libraries, the filter is not flipped (so technically this is a cross-correlation). Each thread block loads a tile of the image (and the border
+/// the filter needs around it) into shared memory once so that neighboring threads don't read the same pixels from global memory over and over.
+/// ```
+/// # use {emu_core::prelude::*, emu_core::algo::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # futures::executor::block_on(assert_device_pool_initialized());
+/// let image: DeviceBox<[f32]> = vec![1.0; 64 * 32].as_device_boxed()?;
+/// let mut blurred: DeviceBox<[f32]> = vec![0.0; 64 * 32].as_device_boxed_mut()?;
+/// // a 3x3 box blur
+/// convolve_2d(&image, &mut blurred, 64, 32, &[1.0 / 9.0; 9], 3, 3)?;
+/// let blurred = futures::executor::block_on(blurred.get())?;
+/// assert!(blurred.iter().all(|pixel| (pixel - 1.0).abs() < 1e-5));
+/// # Ok(())
+/// # }
+/// ```
+pub fn convolve_2d(
+    input: &DeviceBox<[f32]>,
+    output: &mut DeviceBox<[f32]>,
+    width: u32,
+    height: u32,
+    filter: &[f32],
+    filter_width: u32,
+    filter_height: u32,
+) -> Result<(), KernelError> {
+    convolve_2d_with_precision(
+        input,
+        output,
+        width,
+        height,
+        filter,
+        filter_width,
+        filter_height,
+        Precision::F32,
+    )
+}
+
+/// Convolves an image with a filter like [`convolve_2d`](fn.convolve_2d.html), adding up the weighted pixels in the given precision
+///
+/// The image and the filter are still `f32`s, only the sum for each pixel is computed in the given precision. If the device doesn't support
+/// the precision, `CompileOrNoDeviceError::UnsupportedF64` is returned (see [`Precision::or_supported`](enum.Precision.html#method.or_supported)).
+#[allow(clippy::too_many_arguments)]
+pub fn convolve_2d_with_precision(
+    input: &DeviceBox<[f32]>,
+    output: &mut DeviceBox<[f32]>,
+    width: u32,
+    height: u32,
+    filter: &[f32],
+    filter_width: u32,
+    filter_height: u32,
+    precision: Precision,
+) -> Result<(), KernelError> {
+    assert_image_size(input, width, height, "input");
+    assert_image_size(output, width, height, "output");
+    assert_eq!(
+        filter.len(),
+        (filter_width * filter_height) as usize,
+        "filter must have `filter_width * filter_height` weights"
+    );
+    assert!(
+        filter_width % 2 == 1 && filter_height % 2 == 1,
+        "filter must have an odd width and height so that it can be centered on each pixel"
+    );
+    assert!(
+        filter_width <= MAX_FILTER_SIZE && filter_height <= MAX_FILTER_SIZE,
+        "filter can't be wider or taller than {}",
+        MAX_FILTER_SIZE
+    );
+    if width == 0 || height == 0 {
+        return Ok(());
+    }
+    if !precision.is_supported()? {
+        return Err(KernelError::Compile(CompileOrNoDeviceError::UnsupportedF64));
+    }
+
+    let (radius_x, radius_y) = (filter_width / 2, filter_height / 2);
+    let (tile_width, tile_height) = (TILE_SIZE + 2 * radius_x, TILE_SIZE + 2 * radius_y);
+    let mut kernel = GlslKernel::new();
+    if precision == Precision::F64 {
+        kernel = kernel.with_f64();
+    }
+    let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+        kernel
+            .spawn(TILE_SIZE)
+            .spawn(TILE_SIZE)
+            .param::<[f32], _>("float[] src_image")
+            .param_mut::<[f32], _>("float[] dst_image")
+            .param::<[f32], _>("float[] weights")
+            .param::<[u32; 2], _>("uvec2 size")
+            .share(format!("float tile[{}][{}]", tile_height, tile_width))
+            .with_helper_code(precision.helper_code())
+            .with_kernel_code(format!(
+                r#"
+ivec2 last = ivec2(size) - 1;
+ivec2 tile_origin = ivec2(gl_WorkGroupID.xy) * {tile} - ivec2({radius_x}, {radius_y});
+
+// (1) load the tile and its border into shared memory
+for (uint y = gl_LocalInvocationID.y; y < {tile_height}; y += {tile}) {{
+    for (uint x = gl_LocalInvocationID.x; x < {tile_width}; x += {tile}) {{
+        ivec2 pixel = clamp(tile_origin + ivec2(x, y), ivec2(0), last);
+        tile[y][x] = src_image[pixel.y * int(size.x) + pixel.x];
+    }}
+}}
+memoryBarrierShared();
+barrier();
+
+// (2) apply the filter
+uvec2 pixel = gl_GlobalInvocationID.xy;
+if (pixel.x < size.x && pixel.y < size.y) {{
+    {sum_type} sum = {sum_type}(0);
+    for (uint fy = 0; fy < {filter_height}; fy++) {{
+        for (uint fx = 0; fx < {filter_width}; fx++) {{
+            sum = {multiply_add};
+        }}
+    }}
+    dst_image[pixel.y * size.x + pixel.x] = float(sum);
+}}
+"#,
+                tile = TILE_SIZE,
+                radius_x = radius_x,
+                radius_y = radius_y,
+                tile_width = tile_width,
+                tile_height = tile_height,
+                filter_width = filter_width,
+                filter_height = filter_height,
+                sum_type = precision.glsl_type(),
+                multiply_add = precision.multiply_add(
+                    "sum",
+                    &format!("weights[fy * {} + fx]", filter_width),
+                    "tile[gl_LocalInvocationID.y + fy][gl_LocalInvocationID.x + fx]"
+                )
+            )),
+    )?
+    .finish()?;
+
+    let filter: DeviceBox<[f32]> = filter.as_device_boxed()?;
+    let size = DeviceBox::new([width, height])?;
+    unsafe {
+        spawn((width + TILE_SIZE - 1) / TILE_SIZE)
+            .spawn((height + TILE_SIZE - 1) / TILE_SIZE)
+            .launch(crate::call!(kernel, input, output, &filter, &size))?;
+    }
+
+    Ok(())
+}
+
+/// Blurs an image with a Gaussian filter with the given standard deviation
+///
+/// The filter extends 3 standard deviations from each pixel so `sigma` can't be more than 8. Since a Gaussian filter is separable, this is done in 2 passes
+/// with [`convolve_2d`](fn.convolve_2d.html) - first horizontally and then vertically. This is much faster than a single pass with a square filter.
+/// ```
+/// # use {emu_core::prelude::*, emu_core::algo::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # futures::executor::block_on(assert_device_pool_initialized());
+/// let image: DeviceBox<[f32]> = vec![1.0; 128 * 128].as_device_boxed()?;
+/// let mut blurred: DeviceBox<[f32]> = vec![0.0; 128 * 128].as_device_boxed_mut()?;
+/// gaussian_blur(&image, &mut blurred, 128, 128, 2.0)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn gaussian_blur(
+    input: &DeviceBox<[f32]>,
+    output: &mut DeviceBox<[f32]>,
+    width: u32,
+    height: u32,
+    sigma: f32,
+) -> Result<(), KernelError> {
+    gaussian_blur_with_precision(input, output, width, height, sigma, Precision::F32)
+}
+
+/// Blurs an image with a Gaussian filter like [`gaussian_blur`](fn.gaussian_blur.html), adding up the weighted pixels in the given precision
+///
+/// See [`convolve_2d_with_precision`](fn.convolve_2d_with_precision.html) for what the precision changes.
+/// ```
+/// # use {emu_core::prelude::*, emu_core::algo::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # futures::executor::block_on(assert_device_pool_initialized());
+/// let image: DeviceBox<[f32]> = vec![1.0; 128 * 128].as_device_boxed()?;
+/// let mut blurred: DeviceBox<[f32]> = vec![0.0; 128 * 128].as_device_boxed_mut()?;
+/// // use doubles where the device has them and floats where it doesn't
+/// let precision = Precision::F64.or_supported()?;
+/// gaussian_blur_with_precision(&image, &mut blurred, 128, 128, 2.0, precision)?;
+/// let blurred = futures::executor::block_on(blurred.get())?;
+/// assert!(blurred.iter().all(|pixel| (pixel - 1.0).abs() < 1e-5));
+/// # Ok(())
+/// # }
+/// ```
+pub fn gaussian_blur_with_precision(
+    input: &DeviceBox<[f32]>,
+    output: &mut DeviceBox<[f32]>,
+    width: u32,
+    height: u32,
+    sigma: f32,
+    precision: Precision,
+) -> Result<(), KernelError> {
+    assert!(
+        sigma > 0.0 && (3.0 * sigma).ceil() as u32 <= MAX_FILTER_SIZE / 2,
+        "sigma must be positive and no more than {}",
+        MAX_FILTER_SIZE / 6
+    );
+    if width == 0 || height == 0 {
+        return Ok(());
+    }
+
+    // the weights of a normalized 1D Gaussian filter
+    let radius = (3.0 * sigma).ceil() as i32;
+    let mut filter = (-radius..=radius)
+        .map(|x| (-(x * x) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect::<Vec<f32>>();
+    let total: f32 = filter.iter().sum();
+    filter.iter_mut().for_each(|weight| *weight /= total);
+    let filter_size = filter.len() as u32;
+
+    let mut horizontally_blurred: DeviceBox<[f32]> =
+        DeviceBox::with_size_mut(width as usize * height as usize * std::mem::size_of::<f32>())?;
+    convolve_2d_with_precision(
+        input,
+        &mut horizontally_blurred,
+        width,
+        height,
+        &filter,
+        filter_size,
+        1,
+        precision,
+    )?;
+    convolve_2d_with_precision(
+        &horizontally_blurred,
+        output,
+        width,
+        height,
+        &filter,
+        1,
+        filter_size,
+        precision,
+    )
+}
+
+/// Applies a 5-point stencil to an image
+///
+/// Each output pixel is the weighted sum of the input pixel and its 4 neighbors. The weights are in the order center, north (the row above),
+/// south (the row below), west, and east. Pixels past the edges of the image are clamped to the nearest edge.
+/// ```
+/// # use {emu_core::prelude::*, emu_core::algo::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # futures::executor::block_on(assert_device_pool_initialized());
+/// let mut temperature: DeviceBox<[f32]> = vec![0.0; 256 * 256].as_device_boxed_mut()?;
+/// let mut next_temperature: DeviceBox<[f32]> = vec![0.0; 256 * 256].as_device_boxed_mut()?;
+/// // a step of explicitly solving the heat equation
+/// let alpha = 0.2;
+/// for _ in 0..10 {
+///     stencil_5(&temperature, &mut next_temperature, 256, 256, [1.0 - 4.0 * alpha, alpha, alpha, alpha, alpha])?;
+///     DeviceBox::swap(&mut temperature, &mut next_temperature);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn stencil_5(
+    input: &DeviceBox<[f32]>,
+    output: &mut DeviceBox<[f32]>,
+    width: u32,
+    height: u32,
+    weights: [f32; 5],
+) -> Result<(), KernelError> {
+    stencil_5_with_precision(input, output, width, height, weights, Precision::F32)
+}
+
+/// Applies a 5-point stencil like [`stencil_5`](fn.stencil_5.html), adding up the weighted pixels in the given precision
+///
+/// See [`convolve_2d_with_precision`](fn.convolve_2d_with_precision.html) for what the precision changes.
+pub fn stencil_5_with_precision(
+    input: &DeviceBox<[f32]>,
+    output: &mut DeviceBox<[f32]>,
+    width: u32,
+    height: u32,
+    weights: [f32; 5],
+    precision: Precision,
+) -> Result<(), KernelError> {
+    let [center, north, south, west, east] = weights;
+    stencil_9_with_precision(
+        input,
+        output,
+        width,
+        height,
+        [[0.0, north, 0.0], [west, center, east], [0.0, south, 0.0]],
+        precision,
+    )
+}
+
+/// Applies a 9-point stencil to an image
+///
+/// Each output pixel is the weighted sum of the 3x3 block of input pixels centered on it. The weights are in row-major order so
+/// `weights[0][1]` is the weight of the pixel directly above. Pixels past the edges of the image are clamped to the nearest edge.
+pub fn stencil_9(
+    input: &DeviceBox<[f32]>,
+    output: &mut DeviceBox<[f32]>,
+    width: u32,
+    height: u32,
+    weights: [[f32; 3]; 3],
+) -> Result<(), KernelError> {
+    stencil_9_with_precision(input, output, width, height, weights, Precision::F32)
+}
+
+/// Applies a 9-point stencil like [`stencil_9`](fn.stencil_9.html), adding up the weighted pixels in the given precision
+///
+/// See [`convolve_2d_with_precision`](fn.convolve_2d_with_precision.html) for what the precision changes.
+pub fn stencil_9_with_precision(
+    input: &DeviceBox<[f32]>,
+    output: &mut DeviceBox<[f32]>,
+    width: u32,
+    height: u32,
+    weights: [[f32; 3]; 3],
+    precision: Precision,
+) -> Result<(), KernelError> {
+    let filter = weights
+        .iter()
+        .flat_map(|row| row.iter().copied())
+        .collect::<Vec<f32>>();
+    convolve_2d_with_precision(input, output, width, height, &filter, 3, 3, precision)
+}
+
+/// The precision that the filters of this module (like [`convolve_2d_with_precision`](fn.convolve_2d_with_precision.html)) add up their terms in
+///
+/// Images are always stored as `f32`s, so this only changes the precision of the arithmetic. Devices can't be assumed to support anything but
+/// single precision. Most consumer GPUs don't support double precision, so code that wants doubles where it can get them should pick the
+/// precision with [`or_supported`](#method.or_supported).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Precision {
+    /// Half precision (16-bit floats)
+    ///
+    /// WebGPU doesn't expose half precision arithmetic, so this is emulated by rounding each product and sum to half precision. This is supported
+    /// everywhere and is useful for seeing how a filter behaves at half precision but it isn't any faster than single precision.
+    F16,
+    /// Single precision (32-bit floats)
+    F32,
+    /// Double precision (64-bit floats), which needs a device that supports them (see [`Device::supports_f64`](../device/struct.Device.html#method.supports_f64))
+    F64,
+}
+
+impl Precision {
+    /// Returns whether or not the device currently selected from the pool supports this precision
+    pub fn is_supported(&self) -> Result<bool, NoDeviceError> {
+        match self {
+            Precision::F64 => Ok(take()?.lock().unwrap().supports_f64()),
+            _ => Ok(true),
+        }
+    }
+
+    /// Returns this precision if the device currently selected from the pool supports it or else the highest lower precision that it does support
+    pub fn or_supported(self) -> Result<Self, NoDeviceError> {
+        if self.is_supported()? {
+            Ok(self)
+        } else {
+            Ok(Precision::F32)
+        }
+    }
+
+    // the GLSL type that sums are added up in
+    fn glsl_type(&self) -> &'static str {
+        match self {
+            Precision::F16 | Precision::F32 => "float",
+            Precision::F64 => "double",
+        }
+    }
+
+    // GLSL for the given sum plus the product of the given floats
+    fn multiply_add(&self, sum: &str, a: &str, b: &str) -> String {
+        match self {
+            Precision::F16 => format!(
+                "round_to_half({} + round_to_half(round_to_half({}) * round_to_half({})))",
+                sum, a, b
+            ),
+            Precision::F32 => format!("{} + {} * {}", sum, a, b),
+            Precision::F64 => format!("{} + double({}) * double({})", sum, a, b),
+        }
+    }
+
+    // GLSL for helper functions that multiply_add uses
+    fn helper_code(&self) -> &'static str {
+        match self {
+            Precision::F16 => {
+                "float round_to_half(float x) { return unpackHalf2x16(packHalf2x16(vec2(x, 0.0))).x; }"
+            }
+            _ => "",
+        }
+    }
+}
+
+impl Default for Precision {
+    fn default() -> Self {
+        Precision::F32
+    }
+}
+
+/// Counts how many times each value in `0..bins` occurs in the given data
+///
+/// This returns a mutable `DeviceBox` of `bins` counts where the count at index `i` is how many elements of `data` are equal to `i`.
+/// Elements that are `bins` or more are ignored. There are 2 ways this can be done and this picks the one that should be faster.
+/// - If there are few enough bins for a histogram to fit in shared memory (at most 4096), each thread block builds its own private histogram with
+/// fast atomics on shared memory and then a second kernel adds up the private histograms. This avoids having every thread contend for the same few
+/// counters in global memory.
+/// - If there are more bins or the device is a CPU (where there is no real shared memory), each thread atomically increments the counts in global memory directly.
+/// ```
+/// # use {emu_core::prelude::*, emu_core::algo::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # futures::executor::block_on(assert_device_pool_initialized());
+/// let data: DeviceBox<[u32]> = (0..10000).map(|i| i % 10).collect::<Vec<u32>>().as_device_boxed()?;
+/// let counts = histogram(&data, 10)?;
+/// assert_eq!(futures::executor::block_on(counts.get())?, vec![1000; 10].into_boxed_slice());
+/// # Ok(())
+/// # }
+/// ```
+pub fn histogram(data: &DeviceBox<[u32]>, bins: usize) -> Result<DeviceBox<[u32]>, KernelError> {
+    assert!(bins > 0, "there must be at least 1 bin");
+    let len = data.size as usize / std::mem::size_of::<u32>();
+    let mut counts: DeviceBox<[u32]> = vec![0u32; bins].as_device_boxed_mut()?;
+    if len == 0 {
+        return Ok(counts);
+    }
+
+    let num_blocks =
+        ((len as u32 + HISTOGRAM_BLOCK_SIZE - 1) / HISTOGRAM_BLOCK_SIZE).min(MAX_HISTOGRAM_BLOCKS);
+    let len_on_device = DeviceBox::new(len as u32)?;
+    let is_cpu = info()?
+        .info
+        .map(|info| info.device_type() == DeviceType::Cpu)
+        .unwrap_or(false);
+
+    if bins <= MAX_PRIVATIZED_BINS && !is_cpu {
+        // (1) each thread block builds its own histogram in shared memory
+        let privatize = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+            GlslKernel::new()
+                .spawn(HISTOGRAM_BLOCK_SIZE)
+                .param::<[u32], _>("uint[] data")
+                .param_mut::<[u32], _>("uint[] partial_counts")
+                .param::<u32, _>("uint len")
+                .share(format!("uint local_counts[{}]", bins))
+                .with_kernel_code(format!(
+                    r#"
+for (uint bin = gl_LocalInvocationID.x; bin < {bins}; bin += {block_size}) {{
+    local_counts[bin] = 0;
+}}
+memoryBarrierShared();
+barrier();
+
+for (uint i = gl_GlobalInvocationID.x; i < len; i += gl_NumWorkGroups.x * {block_size}) {{
+    uint value = data[i];
+    if (value < {bins}) {{
+        atomicAdd(local_counts[value], 1u);
+    }}
+}}
+memoryBarrierShared();
+barrier();
+
+for (uint bin = gl_LocalInvocationID.x; bin < {bins}; bin += {block_size}) {{
+    partial_counts[gl_WorkGroupID.x * {bins} + bin] = local_counts[bin];
+}}
+"#,
+                    bins = bins,
+                    block_size = HISTOGRAM_BLOCK_SIZE
+                )),
+        )?
+        .finish()?;
+
+        // (2) the histograms of all the thread blocks are added up
+        let reduce = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+            GlslKernel::new()
+                .spawn(64)
+                .param::<[u32], _>("uint[] partial_counts")
+                .param_mut::<[u32], _>("uint[] counts")
+                .param::<u32, _>("uint num_blocks")
+                .with_kernel_code(format!(
+                    r#"
+uint bin = gl_GlobalInvocationID.x;
+if (bin < {bins}) {{
+    uint count = 0;
+    for (uint block = 0; block < num_blocks; block++) {{
+        count += partial_counts[block * {bins} + bin];
+    }}
+    counts[bin] = count;
+}}
+"#,
+                    bins = bins
+                )),
+        )?
+        .finish()?;
+
+        let mut partial_counts: DeviceBox<[u32]> =
+            DeviceBox::with_size_mut(num_blocks as usize * bins * std::mem::size_of::<u32>())?;
+        let num_blocks_on_device = DeviceBox::new(num_blocks)?;
+        unsafe {
+            spawn(num_blocks).launch(crate::call!(
+                privatize,
+                data,
+                &mut partial_counts,
+                &len_on_device
+            ))?;
+            spawn((bins as u32 + 63) / 64).launch(crate::call!(
+                reduce,
+                &partial_counts,
+                &mut counts,
+                &num_blocks_on_device
+            ))?;
+        }
+    } else {
+        let count = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+            GlslKernel::new()
+                .spawn(HISTOGRAM_BLOCK_SIZE)
+                .param::<[u32], _>("uint[] data")
+                .param_mut::<[u32], _>("uint[] counts")
+                .param::<u32, _>("uint len")
+                .with_kernel_code(format!(
+                    r#"
+for (uint i = gl_GlobalInvocationID.x; i < len; i += gl_NumWorkGroups.x * {block_size}) {{
+    uint value = data[i];
+    if (value < {bins}) {{
+        atomicAdd(counts[value], 1u);
+    }}
+}}
+"#,
+                    bins = bins,
+                    block_size = HISTOGRAM_BLOCK_SIZE
+                )),
+        )?
+        .finish()?;
+
+        unsafe {
+            spawn(num_blocks).launch(crate::call!(count, data, &mut counts, &len_on_device))?;
+        }
+    }
+
+    Ok(counts)
+}
+
+/// Runs the given GLSL code on each structure in the given array
+///
+/// The code can read and modify the structure through a variable called `s`. The GLSL definition of the structure, the buffer it is stored in,
+/// and the kernel itself are all generated from the Rust structure (which must implement [`GlslStruct`](../compile/trait.GlslStruct.html)).
+/// ```
+/// use {emu_core::prelude::*, emu_core::algo::*, emu_glsl::*, zerocopy::*};
+///
+/// #[repr(C)]
+/// #[derive(AsBytes, FromBytes, Copy, Clone, Default, Debug, GlslStruct, PartialEq)]
+/// struct Ball {
+///     pos: [f32; 2],
+///     radius: f32,
+/// }
+///
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     futures::executor::block_on(assert_device_pool_initialized());
+///     let mut balls: DeviceBox<[Ball]> = vec![Ball { pos: [0.0; 2], radius: 1.0 }; 1000].as_device_boxed_mut()?;
+///     device_map(&mut balls, "s.radius *= 2.0; s.pos += vec2(1.0);")?;
+///     assert_eq!(
+///         futures::executor::block_on(balls.get())?,
+///         vec![Ball { pos: [1.0; 2], radius: 2.0 }; 1000].into_boxed_slice()
+///     );
+///     Ok(())
+/// }
+/// ```
+pub fn device_map<T: GlslStruct>(data: &mut DeviceBox<[T]>, code: &str) -> Result<(), KernelError> {
+    let len = data.size as usize / std::mem::size_of::<T>();
+    if len == 0 {
+        return Ok(());
+    }
+
+    let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+        GlslKernel::new()
+            .spawn(MAP_BLOCK_SIZE)
+            .param_structs_mut::<T, _>("data")
+            .param::<u32, _>("uint len")
+            .with_kernel_code(format!(
+                r#"
+uint i = gl_GlobalInvocationID.x;
+if (i < len) {{
+    {name} s = data[i];
+    {{
+{code}
+    }}
+    data[i] = s;
+}}
+"#,
+                name = T::glsl_name(),
+                code = code
+            )),
+    )?
+    .finish()?;
+
+    let len_on_device = DeviceBox::new(len as u32)?;
+    let num_blocks = (len as u32 + MAP_BLOCK_SIZE - 1) / MAP_BLOCK_SIZE;
+    unsafe {
+        spawn(num_blocks).launch(crate::call!(kernel, data, &len_on_device))?;
+    }
+
+    Ok(())
+}
+
+impl DeviceBox<[f32]> {
+    /// Checks that every element is finite (not NaN or infinite)
+    ///
+    /// This runs a small kernel that finds the first element that isn't finite, so only its index is downloaded. Calling this after each stage of a
+    /// long pipeline makes it practical to find where the numbers first blow up. If an element isn't finite, this returns
+    /// `CheckFiniteError::NotFinite` with the index of the first such element.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_core::algo::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let mut data: DeviceBox<[f32]> = vec![1.0; 1024].as_device_boxed_mut()?;
+    /// futures::executor::block_on(data.check_finite())?;
+    ///
+    /// let mut values = vec![1.0; 1024];
+    /// values[700] = f32::INFINITY;
+    /// values[900] = f32::NAN;
+    /// data.set(values)?;
+    /// match futures::executor::block_on(data.check_finite()) {
+    ///     Err(CheckFiniteError::NotFinite(index)) => assert_eq!(index, 700),
+    ///     _ => panic!("expected element 700 to not be finite"),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn check_finite(&self) -> Result<(), CheckFiniteError> {
+        let first = self.find_first_non_finite()?;
+        match first.get_one().await? {
+            u32::MAX => Ok(()),
+            index => Err(CheckFiniteError::NotFinite(index as usize)),
+        }
+    }
+
+    // launches a kernel that finds the index of the first element that isn't finite, or u32::MAX if they all are
+    fn find_first_non_finite(&self) -> Result<DeviceBox<u32>, KernelError> {
+        let mut first = DeviceBox::new_mut(u32::MAX)?;
+        let len = self.size as usize / std::mem::size_of::<f32>();
+        if len == 0 {
+            return Ok(first);
+        }
+
+        let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+            GlslKernel::new()
+                .spawn(CHECK_BLOCK_SIZE)
+                .param::<[f32], _>("float[] data")
+                .param_mut::<u32, _>("uint first")
+                .param::<u32, _>("uint len")
+                .with_kernel_code(format!(
+                    r#"
+for (uint i = gl_GlobalInvocationID.x; i < len; i += gl_NumWorkGroups.x * {block_size}) {{
+    if (isnan(data[i]) || isinf(data[i])) {{
+        atomicMin(first, i);
+    }}
+}}
+"#,
+                    block_size = CHECK_BLOCK_SIZE
+                )),
+        )?
+        .finish()?;
+
+        let len_on_device = DeviceBox::new(len as u32)?;
+        let num_blocks =
+            ((len as u32 + CHECK_BLOCK_SIZE - 1) / CHECK_BLOCK_SIZE).min(MAX_CHECK_BLOCKS);
+        unsafe {
+            spawn(num_blocks).launch(crate::call!(kernel, self, &mut first, &len_on_device))?;
+        }
+
+        Ok(first)
+    }
+}
+
+/// A 16-bit (half precision) float, stored as its bits
+///
+/// Rust doesn't have a 16-bit float type. This lets half precision data (like the output of a sensor or the weights of a network) be
+/// [`convert`](../device/struct.DeviceBox.html#method.convert)ed to and from `f32` on the device.
+#[repr(transparent)]
+#[derive(AsBytes, FromBytes, Copy, Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub struct F16(pub u16);
+
+/// A type of element that [`DeviceBox::convert`](../device/struct.DeviceBox.html#method.convert) can convert from and to
+///
+/// Elements are converted through an `f32`. Each element is stored in 8, 16, or 32 bits and elements are packed into 32-bit words, so the GLSL
+/// of an implementation works on a `uint` holding the bits of a single element.
+pub trait ConvertElement: AsBytes + FromBytes + Copy {
+    /// The number of bits in an element (8, 16, or 32)
+    const BITS: u32;
+    /// A GLSL expression of the `float` that the element with the bits in the `uint bits` is converted to
+    fn to_float() -> &'static str;
+    /// A GLSL expression of the bits (as a `uint`) of the element that the `float value` is converted to
+    fn from_float() -> &'static str;
+}
+
+/// Bytes are normalized, so 0 converts to 0.0 and 255 converts to 1.0
+///
+/// Floats are clamped to between 0.0 and 1.0 and then rounded to the nearest byte.
+impl ConvertElement for u8 {
+    const BITS: u32 = 8;
+    fn to_float() -> &'static str {
+        "float(bits) / 255.0"
+    }
+    fn from_float() -> &'static str {
+        "uint(round(clamp(value, 0.0, 1.0) * 255.0))"
+    }
+}
+
+impl ConvertElement for F16 {
+    const BITS: u32 = 16;
+    fn to_float() -> &'static str {
+        "unpackHalf2x16(bits).x"
+    }
+    fn from_float() -> &'static str {
+        "packHalf2x16(vec2(value, 0.0))"
+    }
+}
+
+/// Floats are truncated toward zero, just like with `as`, but floats out of the range of `i32` aren't saturated
+impl ConvertElement for i32 {
+    const BITS: u32 = 32;
+    fn to_float() -> &'static str {
+        "float(int(bits))"
+    }
+    fn from_float() -> &'static str {
+        "uint(int(value))"
+    }
+}
+
+/// Floats are truncated toward zero and negative floats convert to 0, just like with `as`, but floats too large for `u32` aren't saturated
+impl ConvertElement for u32 {
+    const BITS: u32 = 32;
+    fn to_float() -> &'static str {
+        "float(bits)"
+    }
+    fn from_float() -> &'static str {
+        "uint(max(value, 0.0))"
+    }
+}
+
+impl ConvertElement for f32 {
+    const BITS: u32 = 32;
+    fn to_float() -> &'static str {
+        "uintBitsToFloat(bits)"
+    }
+    fn from_float() -> &'static str {
+        "floatBitsToUint(value)"
+    }
+}
+
+impl<T: ConvertElement> DeviceBox<[T]> {
+    /// Converts each element to another type in a new `DeviceBox`
+    ///
+    /// This runs a small kernel (compiled once for each pair of types and then cached) so that data doesn't have to be downloaded, converted on the CPU,
+    /// and uploaded again. Elements are converted through an `f32` (see the implementations of [`ConvertElement`](../algo/trait.ConvertElement.html) for
+    /// how each type is converted) so converting between 2 integer types loses precision above 2^24.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_core::algo::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let pixels: DeviceBox<[u8]> = vec![0u8, 51, 255, 255].as_device_boxed()?;
+    /// let normalized: DeviceBox<[f32]> = pixels.convert()?;
+    /// let normalized_on_cpu = futures::executor::block_on(normalized.get())?;
+    /// assert!((normalized_on_cpu[1] - 0.2).abs() < 1e-6);
+    ///
+    /// let halves: DeviceBox<[F16]> = normalized.convert()?;
+    /// let bytes: DeviceBox<[u8]> = halves.convert()?;
+    /// assert_eq!(futures::executor::block_on(bytes.get())?, vec![0, 51, 255, 255].into_boxed_slice());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Elements are packed into 32-bit words on the device so the size in bytes of both this and the converted `DeviceBox` must be a multiple of 4
+    /// (like a `DeviceBox<[u8]>` whose length is a multiple of 4).
+    pub fn convert<U: ConvertElement>(&self) -> Result<DeviceBox<[U]>, KernelError> {
+        let len = self.size as usize / std::mem::size_of::<T>();
+        assert_eq!(
+            self.size % 4,
+            0,
+            "the size in bytes of the `DeviceBox` being converted must be a multiple of 4"
+        );
+        assert_eq!(
+            (len * std::mem::size_of::<U>()) % 4,
+            0,
+            "the size in bytes of the converted `DeviceBox` must be a multiple of 4"
+        );
+        let mut converted: DeviceBox<[U]> =
+            DeviceBox::with_size_mut(len * std::mem::size_of::<U>())?;
+        if len == 0 {
+            return Ok(converted);
+        }
+
+        // each thread writes whole words of the output so that threads don't race on the elements packed in a word
+        let mask = |bits: u32| {
+            if bits == 32 {
+                u32::MAX
+            } else {
+                (1 << bits) - 1
+            }
+        };
+        let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+            GlslKernel::new()
+                .spawn(CONVERT_BLOCK_SIZE)
+                .param::<[T], _>("uint[] data")
+                .param_mut::<[U], _>("uint[] converted")
+                .param::<u32, _>("uint len")
+                .with_kernel_code(format!(
+                    r#"
+uint num_words = (len + {out_per_word}u - 1u) / {out_per_word}u;
+for (uint word = gl_GlobalInvocationID.x; word < num_words; word += gl_NumWorkGroups.x * {block_size}u) {{
+    uint packed = 0u;
+    for (uint k = 0u; k < {out_per_word}u; k++) {{
+        uint i = word * {out_per_word}u + k;
+        if (i < len) {{
+            uint bits = (data[i / {in_per_word}u] >> ({in_bits}u * (i % {in_per_word}u))) & {in_mask}u;
+            float value = {to_float};
+            packed |= (({from_float}) & {out_mask}u) << ({out_bits}u * k);
+        }}
+    }}
+    converted[word] = packed;
+}}
+"#,
+                    block_size = CONVERT_BLOCK_SIZE,
+                    in_bits = T::BITS,
+                    in_per_word = 32 / T::BITS,
+                    in_mask = mask(T::BITS),
+                    out_bits = U::BITS,
+                    out_per_word = 32 / U::BITS,
+                    out_mask = mask(U::BITS),
+                    to_float = T::to_float(),
+                    from_float = U::from_float(),
+                )),
+        )?
+        .finish()?;
+
+        let len_on_device = DeviceBox::new(len as u32)?;
+        let num_words = ((len as u64 * U::BITS as u64 + 31) / 32) as u32;
+        let num_blocks =
+            ((num_words + CONVERT_BLOCK_SIZE - 1) / CONVERT_BLOCK_SIZE).min(MAX_CONVERT_BLOCKS);
+        unsafe {
+            spawn(num_blocks).launch(crate::call!(kernel, self, &mut converted, &len_on_device))?;
+        }
+
+        Ok(converted)
+    }
+}
+
+// asserts that the given DeviceBox holds an image with the given width and height
+fn assert_image_size(image: &DeviceBox<[f32]>, width: u32, height: u32, name: &str) {
+    assert_eq!(
+        image.size as usize / std::mem::size_of::<f32>(),
+        width as usize * height as usize,
+        "`{}` must have `width * height` pixels",
+        name
+    );
+}
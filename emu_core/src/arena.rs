@@ -0,0 +1,150 @@
+//! An arena of scratch buffers for multi-pass algorithms
+//!
+//! Algorithms like reductions and scans run several passes over data and each pass writes intermediate results to a buffer that the next pass
+//! reads. Library code can't know how often it will be called, so creating those buffers on every call can add up. A [`TempArena`](struct.TempArena.html)
+//! instead hands out scratch [`DeviceBox`](../device/struct.DeviceBox.html)s and keeps each one once it is no longer used so that it can be handed out again.
+
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+
+use crate::device::*;
+use crate::error::*;
+use crate::pool::*;
+
+/// An arena of scratch buffers on a single device
+///
+/// A `TempArena` is made for the device [`take`](../pool/fn.take.html) would return and keeps using that device even if another one is selected later.
+/// [`scratch`](#method.scratch) returns a [`Scratch`](struct.Scratch.html), which is a mutable `DeviceBox<[T]>` that goes back to the arena when it is dropped.
+/// Buffers are only recycled for scratch space of the same size in bytes (so that the `length()` a kernel sees is always right) and the contents of a
+/// recycled buffer are whatever the last pass left in it. Since launches on a device run in the order they are submitted, a buffer can be handed out
+/// again as soon as the launches that use it have been submitted.
+/// ```
+/// # use {emu_core::prelude::*, emu_core::arena::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # futures::executor::block_on(assert_device_pool_initialized());
+/// let double = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+///     GlslKernel::new()
+///         .param::<[f32], _>("float[] src")
+///         .param_mut::<[f32], _>("float[] dst")
+///         .with_kernel_code("dst[gl_GlobalInvocationID.x] = 2.0 * src[gl_GlobalInvocationID.x];"),
+/// )?
+/// .finish()?;
+///
+/// let arena = TempArena::new()?;
+/// let data: DeviceBox<[f32]> = vec![1.0; 1024].as_device_boxed()?;
+/// let mut result: DeviceBox<[f32]> = DeviceBox::with_size_mut(1024 * std::mem::size_of::<f32>())?;
+/// for _ in 0..10 {
+///     // the intermediate result of each iteration uses the same buffer
+///     let mut tmp = arena.scratch::<f32>(1024)?;
+///     unsafe {
+///         spawn(1024).launch(call!(double.clone(), &data, &mut tmp))?;
+///         spawn(1024).launch(call!(double.clone(), &tmp, &mut result))?;
+///     }
+/// }
+/// assert_eq!(arena.num_free(), 1);
+/// assert_eq!(futures::executor::block_on(result.get())?, vec![4.0; 1024].into_boxed_slice());
+/// # Ok(())
+/// # }
+/// ```
+pub struct TempArena {
+    device: &'static Mutex<Device>,
+    // buffers that aren't handed out, by their size in bytes
+    free: Mutex<HashMap<u64, Vec<DeviceBox<[u8]>>>>,
+}
+
+impl TempArena {
+    /// Creates an empty arena for the device `take` would return
+    pub fn new() -> Result<Self, NoDeviceError> {
+        Ok(Self {
+            device: take()?,
+            free: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Returns scratch space for the given number of `T`s
+    ///
+    /// A buffer of the same size in bytes is recycled if there is a free one. Otherwise, a new buffer is created.
+    pub fn scratch<T>(&self, len: usize) -> Result<Scratch<'_, T>, NoDeviceError> {
+        let size = len * std::mem::size_of::<T>();
+        let recycled = self
+            .free
+            .lock()
+            .unwrap()
+            .get_mut(&(size as u64))
+            .and_then(|free| free.pop());
+        let device_box = match recycled {
+            Some(device_box) => device_box,
+            None => self.device.lock().unwrap().create_with_size_mut(size),
+        };
+        Ok(Scratch {
+            arena: self,
+            device_box: Some(device_box.retype()),
+        })
+    }
+
+    /// Returns the number of buffers that are waiting to be handed out again
+    pub fn num_free(&self) -> usize {
+        self.free
+            .lock()
+            .unwrap()
+            .values()
+            .map(|free| free.len())
+            .sum()
+    }
+
+    /// Drops all the buffers that are waiting to be handed out again, freeing their memory
+    pub fn clear(&self) {
+        self.free.lock().unwrap().clear();
+    }
+}
+
+/// Scratch space handed out by a [`TempArena`](struct.TempArena.html)
+///
+/// This dereferences to a mutable `DeviceBox<[T]>` and can be passed to [`call!`](../macro.call.html) like one. When it is dropped, its buffer goes
+/// back to the arena.
+pub struct Scratch<'a, T> {
+    arena: &'a TempArena,
+    device_box: Option<DeviceBox<[T]>>, // inv: Some until dropped
+}
+
+impl<'a, T> Deref for Scratch<'a, T> {
+    type Target = DeviceBox<[T]>;
+
+    fn deref(&self) -> &Self::Target {
+        self.device_box.as_ref().unwrap()
+    }
+}
+
+impl<'a, T> DerefMut for Scratch<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.device_box.as_mut().unwrap()
+    }
+}
+
+impl<'a, T> Drop for Scratch<'a, T> {
+    fn drop(&mut self) {
+        if let Some(device_box) = self.device_box.take() {
+            let device_box: DeviceBox<[u8]> = device_box.retype();
+            self.arena
+                .free
+                .lock()
+                .unwrap()
+                .entry(device_box.size)
+                .or_insert_with(Vec::new)
+                .push(device_box);
+        }
+    }
+}
+
+impl<'a, 'b, T> IntoArg<'a> for &'a Scratch<'b, T> {
+    fn add_to(self, builder: ArgsBuilder<'a>) -> ArgsBuilder<'a> {
+        (&**self).add_to(builder)
+    }
+}
+
+impl<'a, 'b, T> IntoArg<'a> for &'a mut Scratch<'b, T> {
+    fn add_to(self, builder: ArgsBuilder<'a>) -> ArgsBuilder<'a> {
+        (&**self).add_to(builder)
+    }
+}
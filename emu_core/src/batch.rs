@@ -0,0 +1,152 @@
+//! Solving many small independent problems with a single launch
+//!
+//! Launching a kernel for each of thousands of tiny problems (like small linear systems) spends almost all of its time on the overhead of each
+//! launch. A [`Batch`](struct.Batch.html) instead packs all of the problems into one buffer of values along with an index buffer of where each
+//! problem starts, and [`batched`](fn.batched.html) runs a bit of GLSL for every problem in a single launch. Problems can have different sizes.
+//! This module requires the `glsl-compile` feature.
+
+use zerocopy::*;
+
+use crate::boxed::*;
+use crate::cache::*;
+use crate::compile::*;
+use crate::compile_impls::*;
+use crate::device::*;
+use crate::error::*;
+use crate::spawn::*;
+
+// the number of threads in each thread block of the kernel for batched
+const BATCH_BLOCK_SIZE: u32 = 64;
+
+/// Many small problems packed into a single mutable `DeviceBox`
+///
+/// The values of all problems are stored one after another in [`values`](#method.values) and [`offsets`](#method.offsets) has 1 more element
+/// than there are problems. The values of problem `i` are at indices `offsets[i]..offsets[i + 1]` of the values. This is the same layout as
+/// [`flatten_jagged_mut`](../boxed/fn.flatten_jagged_mut.html), so a batch can also be passed to a kernel of your own that declares it with
+/// [`param_jagged_mut`](../compile_impls/struct.GlslKernel.html#method.param_jagged_mut) (passing in the values and then the offsets).
+pub struct Batch<T> {
+    values: DeviceBox<[T]>,
+    offsets: DeviceBox<[u32]>,
+    host_offsets: Vec<usize>, // kept on the host so that unpacking only downloads the values
+}
+
+impl<T: AsBytes + FromBytes + Copy> Batch<T> {
+    /// Packs the given problems into a batch on the device currently selected from the pool
+    pub fn pack<P: AsRef<[T]>>(problems: &[P]) -> Result<Self, NoDeviceError> {
+        let mut host_offsets = Vec::with_capacity(problems.len() + 1);
+        host_offsets.push(0);
+        for problem in problems {
+            host_offsets.push(host_offsets[host_offsets.len() - 1] + problem.as_ref().len());
+        }
+        let (values, offsets) = flatten_jagged_as(problems, Mutability::Mut)?;
+        Ok(Self {
+            values,
+            offsets,
+            host_offsets,
+        })
+    }
+
+    /// Returns the number of problems
+    pub fn len(&self) -> usize {
+        self.host_offsets.len() - 1
+    }
+
+    /// Returns whether or not there are no problems
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of values of the given problem
+    pub fn problem_len(&self, problem: usize) -> usize {
+        self.host_offsets[problem + 1] - self.host_offsets[problem]
+    }
+
+    /// The values of all of the problems
+    pub fn values(&self) -> &DeviceBox<[T]> {
+        &self.values
+    }
+
+    /// The index of the first value of each problem, followed by the total number of values
+    pub fn offsets(&self) -> &DeviceBox<[u32]> {
+        &self.offsets
+    }
+
+    /// Downloads the problems and splits them back up into a `Vec` for each problem
+    pub async fn unpack(&self) -> Result<Vec<Vec<T>>, GetError> {
+        let values = self.values.get().await?;
+        Ok(unflatten_jagged(&values, &self.host_offsets))
+    }
+}
+
+/// Runs the given GLSL code on every problem of the given batch with a single launch
+///
+/// Each problem is solved by its own thread. The code can read the index of the problem from `uint problem` and work on the problem's values
+/// with the functions that [`param_jagged_mut`](../compile_impls/struct.GlslKernel.html#method.param_jagged_mut) generates for `problems` - the
+/// number of values is `problems_len(problem)`, value `i` is `problems_get(problem, i)`, and `problems_set(problem, i, value)` changes it.
+/// ```
+/// # use {emu_core::prelude::*, emu_core::batch::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # futures::executor::block_on(assert_device_pool_initialized());
+/// // each problem is a 2x2 system of linear equations, packed as [a, b, c, d, e, f] for
+/// // | a b | x = | e |
+/// // | c d |     | f |
+/// let problems = (0..10000)
+///     .map(|i| vec![2.0, 0.0, 0.0, 4.0, 2.0 * i as f32, 4.0])
+///     .collect::<Vec<Vec<f32>>>();
+/// let mut batch = Batch::pack(&problems)?;
+/// // solve every system with Cramer's rule and write the solution over [e, f]
+/// batched(&mut batch, r#"
+/// float a = problems_get(problem, 0), b = problems_get(problem, 1);
+/// float c = problems_get(problem, 2), d = problems_get(problem, 3);
+/// float e = problems_get(problem, 4), f = problems_get(problem, 5);
+/// float det = a * d - b * c;
+/// problems_set(problem, 4, (e * d - b * f) / det);
+/// problems_set(problem, 5, (a * f - e * c) / det);
+/// "#)?;
+/// let solved = futures::executor::block_on(batch.unpack())?;
+/// assert_eq!(solved[3][4..], [3.0, 1.0]);
+/// # Ok(())
+/// # }
+/// ```
+pub fn batched<T: GlslScalar + AsBytes + FromBytes + Copy>(
+    batch: &mut Batch<T>,
+    code: &str,
+) -> Result<(), KernelError> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+
+    let mut kernel = GlslKernel::new();
+    if T::needs_f64() {
+        kernel = kernel.with_f64();
+    }
+    let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+        kernel
+            .spawn(BATCH_BLOCK_SIZE)
+            .param_jagged_mut::<T, _>(format!("{} problems", T::glsl_type()))
+            .param::<u32, _>("uint num_problems")
+            .with_kernel_code(format!(
+                r#"
+uint problem = gl_GlobalInvocationID.x;
+if (problem < num_problems) {{
+{code}
+}}
+"#,
+                code = code
+            )),
+    )?
+    .finish()?;
+
+    let num_problems = DeviceBox::new(batch.len() as u32)?;
+    let num_blocks = (batch.len() as u32 + BATCH_BLOCK_SIZE - 1) / BATCH_BLOCK_SIZE;
+    unsafe {
+        spawn(num_blocks).launch(crate::call!(
+            kernel,
+            &batch.values,
+            &batch.offsets,
+            &num_problems
+        ))?;
+    }
+
+    Ok(())
+}
@@ -2,7 +2,13 @@
 
 use std::borrow::Borrow;
 use std::iter::FromIterator;
+#[cfg(feature = "extras")]
+use std::marker::PhantomData;
+use std::path::Path;
+use std::time::Duration;
 
+#[cfg(feature = "extras")]
+use crate::compile::*;
 use crate::device::*;
 use crate::error::*;
 use crate::pool::*;
@@ -49,6 +55,73 @@ impl<T: ?Sized> DeviceBox<T> {
     pub fn with_size_mut(size: usize) -> Result<Self, NoDeviceError> {
         Ok(take()?.lock().unwrap().create_with_size_mut(size))
     }
+
+    //
+    // FUNCTIONS TO CREATE BOXES WITH OPTIONS
+    //
+
+    /// Create a `DeviceBox<T>` where `T` has the given number of bytes, with the given usage, label, and mutability
+    pub fn with_size_and_options(
+        size: usize,
+        options: &DeviceBoxOptions,
+    ) -> Result<Self, NoDeviceError> {
+        Ok(take()?.lock().unwrap().create_with_options(size, options))
+    }
+
+    /// Create a `DeviceBox<T>` from a borrow of `T`, with the given usage, label, and mutability
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let lookup_table: DeviceBox<[u32]> = DeviceBox::from_with_options(
+    ///     (0..256).collect::<Vec<u32>>().as_slice(),
+    ///     &DeviceBoxOptions::new().with_label("lookup table"),
+    /// )?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_with_options<B: Borrow<T>>(
+        obj: B,
+        options: &DeviceBoxOptions,
+    ) -> Result<Self, NoDeviceError>
+    where
+        T: AsBytes,
+    {
+        Ok(take()?
+            .lock()
+            .unwrap()
+            .create_from_with_options(obj, options))
+    }
+
+    //
+    // FUNCTIONS TO SWAP BOXES
+    //
+
+    /// Swaps the buffers of 2 `DeviceBox`s without copying any data
+    ///
+    /// This is useful for ping-ponging between an input and an output buffer when running the same kernel over and over.
+    /// The size and mutability of each `DeviceBox` go along with its buffers.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let mut current: DeviceBox<[f32]> = vec![0.0; 1024].as_device_boxed_mut()?;
+    /// let mut next: DeviceBox<[f32]> = vec![1.0; 1024].as_device_boxed_mut()?;
+    /// DeviceBox::swap(&mut current, &mut next);
+    /// assert_eq!(futures::executor::block_on(current.get())?, vec![1.0; 1024].into_boxed_slice());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn swap(a: &mut Self, b: &mut Self) {
+        std::mem::swap(&mut a.staging, &mut b.staging);
+        std::mem::swap(&mut a.storage_buffer, &mut b.storage_buffer);
+        std::mem::swap(&mut a.offset, &mut b.offset);
+        std::mem::swap(&mut a.allocation, &mut b.allocation);
+        std::mem::swap(&mut a.size, &mut b.size);
+        std::mem::swap(&mut a.mutability, &mut b.mutability);
+        std::mem::swap(&mut a.usage, &mut b.usage);
+        std::mem::swap(&mut a.id, &mut b.id);
+    }
 }
 
 /// A trait for creating a `DeviceBox<T>` by consuming an object `T`
@@ -259,11 +332,67 @@ impl<T: AsBytes + ?Sized> DeviceBox<T> {
     /// # Ok(())
     /// # }
     /// ```
-    /// It is expected that the object you pass in is of the same size (in bytes) as what was
-    /// already stored in the `DeviceBox`. For example, you should not upload a vector of different
-    /// length than that of the slice already stored on the device.
-    pub fn set<U: Borrow<T>>(&mut self, obj: U) -> Result<(), NoDeviceError> {
-        Ok(take()?.lock().unwrap().set_from(self, obj.borrow()))
+    /// The object you pass in must be of the same size (in bytes) as what is already stored in the `DeviceBox`. For example, you can't
+    /// upload a vector of different length than that of the slice already stored on the device. If the sizes differ, nothing is uploaded and
+    /// `SetError::SizeMismatch` is returned. A `DeviceBox<[T]>` can be [`resize`](#method.resize)d first to hold a vector of a different length.
+    pub fn set<U: Borrow<T>>(&mut self, obj: U) -> Result<(), SetError> {
+        take()?.lock().unwrap().set_from(self, obj.borrow())
+    }
+
+    /// Sets every byte of self (a `DeviceBox<T>`) to zero
+    ///
+    /// This doesn't upload a host vector of zeros so it's a cheap way to reset accumulators between iterations.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let mut sums: DeviceBox<[f32]> = vec![0.5; 1024].as_device_boxed_mut()?;
+    /// sums.zero()?;
+    /// assert_eq!(futures::executor::block_on(sums.get())?, vec![0.0; 1024].into_boxed_slice());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn zero(&mut self) -> Result<(), NoDeviceError> {
+        Ok(take()?.lock().unwrap().zero(self))
+    }
+}
+
+impl<T: AsBytes + Copy> DeviceBox<[T]> {
+    /// Sets every element of self (a `DeviceBox<[T]>`) to the given value
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let mut data: DeviceBox<[f32]> = vec![0.0; 1024].as_device_boxed_mut()?;
+    /// data.fill(1.0)?;
+    /// assert_eq!(futures::executor::block_on(data.get())?, vec![1.0; 1024].into_boxed_slice());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn fill(&mut self, value: T) -> Result<(), NoDeviceError> {
+        Ok(take()?.lock().unwrap().fill(self, value))
+    }
+
+    /// Changes the number of elements in self (a `DeviceBox<[T]>`), keeping as many of the elements already there as fit
+    ///
+    /// Elements past the old length are zero. This allocates a new buffer so it's for the occasional growing (or shrinking) of a buffer, like
+    /// when a batch gets larger, and not for every upload.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let mut data: DeviceBox<[f32]> = vec![1.0; 1024].as_device_boxed_mut()?;
+    /// data.resize(2048)?;
+    /// assert_eq!(data.len(), 2048);
+    /// data.set(vec![2.0; 2048])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resize(&mut self, len: usize) -> Result<(), NoDeviceError> {
+        Ok(take()?
+            .lock()
+            .unwrap()
+            .resize(self, len * std::mem::size_of::<T>()))
     }
 }
 
@@ -297,6 +426,382 @@ impl<T: FromBytes + Copy> DeviceBox<[T]> {
             .unwrap()
             .get(self)
             .await
-            .map_err(|_| GetError::Completion)
+            .map_err(GetError::Completion)
+    }
+
+    /// Downloads from self (a `DeviceBox<[T]>`) to a `Box<[T]>`, giving up after the given timeout
+    ///
+    /// This is just like [`get`](#method.get) except that it returns `GetError::Timeout` instead of waiting forever
+    /// on a device that never finishes.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*, std::time::Duration};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let data: DeviceBox<[f32]> = vec![0.5; 1024].as_device_boxed_mut()?;
+    /// assert_eq!(futures::executor::block_on(data.get_with_timeout(Duration::from_secs(5)))?,
+    ///     vec![0.5; 1024].into_boxed_slice());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_with_timeout(&self, timeout: Duration) -> Result<Box<[T]>, GetError> {
+        take()
+            .map_err(|_| GetError::NoDevice)?
+            .lock()
+            .unwrap()
+            .get_with_timeout(self, timeout)
+            .await
+    }
+
+    /// Downloads from self (a `DeviceBox<[T]>`) into the given slice
+    ///
+    /// Unlike [`get`](#method.get), this doesn't allocate. So you can reuse the same `Vec` across many downloads.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let data: DeviceBox<[f32]> = vec![0.5; 1024].as_device_boxed_mut()?;
+    /// let mut frame = vec![0.0; 1024];
+    /// futures::executor::block_on(data.get_into(&mut frame))?;
+    /// assert_eq!(frame, vec![0.5; 1024]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_into(&self, obj: &mut [T]) -> Result<(), GetError> {
+        take()
+            .map_err(|_| GetError::NoDevice)?
+            .lock()
+            .unwrap()
+            .get_into(self, obj)
+            .await
+            .map_err(GetError::Completion)
+    }
+}
+
+impl<T: FromBytes + Copy> DeviceBox<T> {
+    /// Downloads from self (a `DeviceBox<T>`) to a `T`
+    ///
+    /// This is useful for reading back a single value like a counter or the output of a reduction.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let mut count: DeviceBox<u32> = 0u32.into_device_boxed_mut()?;
+    /// count.set(7u32)?;
+    /// assert_eq!(futures::executor::block_on(count.get_one())?, 7);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_one(&self) -> Result<T, GetError> {
+        take()
+            .map_err(|_| GetError::NoDevice)?
+            .lock()
+            .unwrap()
+            .get_one(self)
+            .await
+            .map_err(GetError::Completion)
+    }
+}
+
+// what follows is for jagged data (like a Vec<Vec<T>>) where each row can have a different length
+
+/// Flattens jagged data into a constant `DeviceBox<[T]>` of all the values and a constant `DeviceBox<[u32]>` of offsets
+///
+/// The values of all rows are stored one after another and the offsets have 1 more element than there are rows. The values of row `i` are at
+/// indices `offsets[i]..offsets[i + 1]` of the values. This is the same layout as the rows of a [`CsrMatrix`](../sparse/struct.CsrMatrix.html).
+/// In a [`GlslKernel`](../compile_impls/struct.GlslKernel.html), you can declare parameters for both with [`param_jagged`](../compile_impls/struct.GlslKernel.html#method.param_jagged)
+/// and then use the generated functions for indexing into rows.
+/// ```
+/// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # futures::executor::block_on(assert_device_pool_initialized());
+/// let neighbors = vec![vec![1, 2], vec![], vec![0, 1, 3], vec![2]];
+/// let (values, offsets) = flatten_jagged_mut(&neighbors)?;
+/// assert_eq!(futures::executor::block_on(get_jagged(&values, &offsets))?, neighbors);
+/// # Ok(())
+/// # }
+/// ```
+pub fn flatten_jagged<T: AsBytes + Copy>(
+    rows: &[Vec<T>],
+) -> Result<(DeviceBox<[T]>, DeviceBox<[u32]>), NoDeviceError> {
+    flatten_jagged_as(rows, Mutability::Const)
+}
+
+/// Flattens jagged data like [`flatten_jagged`](fn.flatten_jagged.html) except that the values are stored in a mutable `DeviceBox<[T]>`
+///
+/// The offsets are still constant so that kernels can modify values but not the length of rows.
+pub fn flatten_jagged_mut<T: AsBytes + Copy>(
+    rows: &[Vec<T>],
+) -> Result<(DeviceBox<[T]>, DeviceBox<[u32]>), NoDeviceError> {
+    flatten_jagged_as(rows, Mutability::Mut)
+}
+
+pub(crate) fn flatten_jagged_as<T: AsBytes + Copy, R: AsRef<[T]>>(
+    rows: &[R],
+    mutability: Mutability,
+) -> Result<(DeviceBox<[T]>, DeviceBox<[u32]>), NoDeviceError> {
+    let mut offsets = Vec::with_capacity(rows.len() + 1);
+    offsets.push(0u32);
+    for row in rows {
+        offsets.push(offsets[offsets.len() - 1] + row.as_ref().len() as u32);
+    }
+    let values = rows
+        .iter()
+        .flat_map(|row| row.as_ref().iter().copied())
+        .collect::<Vec<T>>();
+
+    let mut device = take()?.lock().unwrap();
+    // empty buffers can't be bound so if there are no values, we store a single (zeroed) value that is never read
+    let values = match (values.is_empty(), mutability) {
+        (true, Mutability::Const) => device.create_with_size(std::mem::size_of::<T>()),
+        (true, Mutability::Mut) => device.create_with_size_mut(std::mem::size_of::<T>()),
+        (false, Mutability::Const) => device.create_from(values.as_slice()),
+        (false, Mutability::Mut) => device.create_from_mut(values.as_slice()),
+    };
+    Ok((values, device.create_from(offsets.as_slice())))
+}
+
+/// Downloads jagged data that was flattened with [`flatten_jagged_mut`](fn.flatten_jagged_mut.html) (or [`flatten_jagged`](fn.flatten_jagged.html)) back into a `Vec` of rows
+///
+/// Like [`DeviceBox::get`](../device/struct.DeviceBox.html#method.get), the values must be mutable.
+pub async fn get_jagged<T: FromBytes + Copy>(
+    values: &DeviceBox<[T]>,
+    offsets: &DeviceBox<[u32]>,
+) -> Result<Vec<Vec<T>>, GetError> {
+    let mut device = take().map_err(|_| GetError::NoDevice)?.lock().unwrap();
+    // the offsets are constant so we download them as bytes
+    let offsets = device
+        .get_bytes(offsets)
+        .await
+        .map_err(GetError::Completion)?
+        .chunks_exact(4)
+        .map(|offset| u32::from_le_bytes([offset[0], offset[1], offset[2], offset[3]]) as usize)
+        .collect::<Vec<usize>>();
+    let values = device.get(values).await.map_err(GetError::Completion)?;
+    Ok(unflatten_jagged(&values, &offsets))
+}
+
+// splits the given values into rows at the given offsets
+pub(crate) fn unflatten_jagged<T: Copy>(values: &[T], offsets: &[usize]) -> Vec<Vec<T>> {
+    offsets
+        .windows(2)
+        .map(|row| values[row[0]..row[1]].to_vec())
+        .collect()
+}
+
+// what follows is for storing structures as a structure of arrays (SoA) with 1 buffer for each field
+
+/// Structures stored on a device as a structure of arrays, with 1 buffer for each field
+///
+/// Kernels where neighboring threads read the same field of neighboring structures often run much faster when each field is stored in its own
+/// array. This is created from an array of structures with [`to_soa`](fn.to_soa.html) (or [`to_soa_mut`](fn.to_soa_mut.html)) and can be passed
+/// to [`call!`](../macro.call.html) as a single argument for a parameter declared with [`param_soa`](../compile_impls/struct.GlslKernel.html#method.param_soa).
+#[cfg(feature = "extras")]
+pub struct DeviceSoa<S> {
+    fields: Vec<DeviceBox<[u8]>>, // in the order of S::glsl_fields()
+    len: usize,
+    phantom: PhantomData<S>,
+}
+
+#[cfg(feature = "extras")]
+impl<S> DeviceSoa<S> {
+    /// Returns the number of structures
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether or not there are no structures
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the buffer of each field in the order the fields are declared
+    pub fn fields(&self) -> &[DeviceBox<[u8]>] {
+        &self.fields
+    }
+}
+
+#[cfg(feature = "extras")]
+impl<'a, S> IntoArg<'a> for &'a DeviceSoa<S> {
+    fn add_to(self, builder: ArgsBuilder<'a>) -> ArgsBuilder<'a> {
+        self.fields
+            .iter()
+            .fold(builder, |builder, field| field.add_to(builder))
+    }
+}
+
+#[cfg(feature = "extras")]
+impl<'a, S> IntoArg<'a> for &'a mut DeviceSoa<S> {
+    fn add_to(self, builder: ArgsBuilder<'a>) -> ArgsBuilder<'a> {
+        (&*self).add_to(builder)
+    }
+}
+
+/// Stores the given structures on the device as a constant structure of arrays
+///
+/// Each field is copied into its own buffer. Since the buffers are arrays of the GLSL types of the fields, each field must have the same size in
+/// Rust as in a GLSL array. So fields can't be `bool`s or 3-component vectors. Use [`param_soa`](../compile_impls/struct.GlslKernel.html#method.param_soa)
+/// to declare a parameter for a `DeviceSoa` in a [`GlslKernel`](../compile_impls/struct.GlslKernel.html).
+/// ```
+/// use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+///
+/// #[repr(C)]
+/// #[derive(AsBytes, FromBytes, Copy, Clone, Default, Debug, GlslStruct, PartialEq)]
+/// struct Particle {
+///     pos: [f32; 2],
+///     mass: f32,
+/// }
+///
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     futures::executor::block_on(assert_device_pool_initialized());
+///     let particles = vec![Particle { pos: [1.0, 2.0], mass: 3.0 }; 100];
+///     let soa = to_soa_mut(&particles)?;
+///     assert_eq!(soa.fields().len(), 2);
+///     assert_eq!(futures::executor::block_on(get_soa(&soa))?, particles);
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "extras")]
+pub fn to_soa<S: GlslStruct + AsBytes>(structs: &[S]) -> Result<DeviceSoa<S>, NoDeviceError> {
+    to_soa_as(structs, Mutability::Const)
+}
+
+/// Stores the given structures on the device as a mutable structure of arrays
+///
+/// See [`to_soa`](fn.to_soa.html) for more details.
+#[cfg(feature = "extras")]
+pub fn to_soa_mut<S: GlslStruct + AsBytes>(structs: &[S]) -> Result<DeviceSoa<S>, NoDeviceError> {
+    to_soa_as(structs, Mutability::Mut)
+}
+
+#[cfg(feature = "extras")]
+fn to_soa_as<S: GlslStruct + AsBytes>(
+    structs: &[S],
+    mutability: Mutability,
+) -> Result<DeviceSoa<S>, NoDeviceError> {
+    let fields = S::glsl_fields();
+    assert!(
+        !fields.is_empty(),
+        "expected `GlslStruct::glsl_fields` to describe the fields of the structure"
+    );
+
+    let mut device = take()?.lock().unwrap();
+    let buffers = fields
+        .iter()
+        .map(|field| {
+            // gather the bytes of this field from each structure
+            let bytes = structs
+                .iter()
+                .flat_map(|s| s.as_bytes()[field.offset..field.offset + field.size].to_vec())
+                .collect::<Vec<u8>>();
+            // empty buffers can't be bound so if there are no structures, we store a single (zeroed) field that is never read
+            match (bytes.is_empty(), mutability) {
+                (true, Mutability::Const) => device.create_with_size(field.size),
+                (true, Mutability::Mut) => device.create_with_size_mut(field.size),
+                (false, Mutability::Const) => device.create_from(bytes.as_slice()),
+                (false, Mutability::Mut) => device.create_from_mut(bytes.as_slice()),
+            }
+        })
+        .collect();
+    Ok(DeviceSoa {
+        fields: buffers,
+        len: structs.len(),
+        phantom: PhantomData,
+    })
+}
+
+/// Downloads a structure of arrays (see [`to_soa`](fn.to_soa.html)) back into an array of structures
+#[cfg(feature = "extras")]
+pub async fn get_soa<S: GlslStruct + AsBytes + FromBytes + Default + Clone>(
+    soa: &DeviceSoa<S>,
+) -> Result<Vec<S>, GetError> {
+    let fields = S::glsl_fields();
+    let mut structs = vec![S::default(); soa.len];
+    let mut device = take().map_err(|_| GetError::NoDevice)?.lock().unwrap();
+    for (field, buffer) in fields.iter().zip(soa.fields.iter()) {
+        let bytes = device
+            .get_bytes(buffer)
+            .await
+            .map_err(GetError::Completion)?;
+        // scatter the bytes of this field into each structure
+        for (s, field_bytes) in structs.iter_mut().zip(bytes.chunks_exact(field.size)) {
+            s.as_bytes_mut()[field.offset..field.offset + field.size].copy_from_slice(field_bytes);
+        }
+    }
+    Ok(structs)
+}
+
+// the first bytes of every file written by DeviceBox::to_file
+// the last 2 bytes are the version of the format
+const FILE_MAGIC: &[u8; 8] = b"EMUBOX01";
+
+impl<T: AsBytes + FromBytes + Copy> DeviceBox<[T]> {
+    /// Downloads from self (a `DeviceBox<[T]>`) and saves the data to a file at the given path
+    ///
+    /// This is useful for checkpointing a long computation so it can be resumed later with [`from_file`](#method.from_file).
+    /// The file starts with a small header storing the name of `T`, the number of elements, and whether or not self is mutable.
+    /// The elements follow as raw bytes in the device's byte order.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let path = std::env::temp_dir().join("emu_checkpoint");
+    /// let data: DeviceBox<[f32]> = vec![0.5; 1024].as_device_boxed_mut()?;
+    /// futures::executor::block_on(data.to_file(&path))?;
+    ///
+    /// // ...later, maybe in a different process
+    /// let resumed: DeviceBox<[f32]> = DeviceBox::from_file(&path)?;
+    /// assert_eq!(futures::executor::block_on(resumed.get())?, vec![0.5; 1024].into_boxed_slice());
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), PersistError> {
+        let data = self.get().await.map_err(PersistError::Get)?;
+        let type_name = core::any::type_name::<T>();
+
+        let mut file =
+            Vec::with_capacity(FILE_MAGIC.len() + type_name.len() + 13 + data.as_bytes().len());
+        file.extend_from_slice(FILE_MAGIC);
+        file.push((self.mutability == Some(Mutability::Mut)) as u8);
+        write_string(&mut file, type_name);
+        file.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        file.extend_from_slice(data.as_bytes());
+        std::fs::write(path, file).map_err(PersistError::Io)
+    }
+
+    /// Loads a `DeviceBox<[T]>` from a file saved with [`to_file`](#method.to_file)
+    ///
+    /// The loaded `DeviceBox` is mutable if and only if the saved one was. This returns `PersistError::TypeMismatch` if the file
+    /// holds elements of a type other than `T`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, PersistError> {
+        let file = std::fs::read(path).map_err(PersistError::Io)?;
+
+        // parse the header
+        let mut rest = file
+            .strip_prefix(&FILE_MAGIC[..])
+            .ok_or(PersistError::InvalidHeader)?;
+        let mutability = match read_bytes(&mut rest, 1)?[0] {
+            0 => Mutability::Const,
+            1 => Mutability::Mut,
+            _ => return Err(PersistError::InvalidHeader),
+        };
+        let type_name = read_string(&mut rest)?;
+        if type_name != core::any::type_name::<T>() {
+            return Err(PersistError::TypeMismatch(
+                type_name,
+                String::from(core::any::type_name::<T>()),
+            ));
+        }
+        let len = read_u64(&mut rest)? as usize;
+        let bytes = read_bytes(&mut rest, len * std::mem::size_of::<T>())?;
+
+        // the bytes might not be aligned for T so we upload them as bytes and then just re-type the DeviceBox
+        let mut device = take().map_err(|_| PersistError::NoDevice)?.lock().unwrap();
+        let device_obj: DeviceBox<[u8]> = match mutability {
+            Mutability::Mut => device.create_from_mut(bytes),
+            Mutability::Const => device.create_from(bytes),
+        };
+        // retyping keeps the id so that a recording sees this as the same buffer that was uploaded
+        Ok(device_obj.retype())
     }
 }
@@ -105,3 +105,90 @@ impl Cache for GlobalCache {
             .insert(key, device_fn_mut);
     }
 }
+
+lazy_static! {
+    static ref GLOBAL_KERNEL_REGISTRY: KernelRegistry = KernelRegistry::new();
+}
+
+/// A thread-safe registry of compiled kernels that can be looked up by name
+///
+/// While a [`Cache`](trait.Cache.html) is keyed by the hash of the source of each kernel, a `KernelRegistry` is keyed by
+/// human-readable names. So an application can compile all of its kernels during setup, register them, and then get them later from anywhere.
+/// ```
+/// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// futures::executor::block_on(assert_device_pool_initialized());
+///
+/// // during setup, compile and register a kernel
+/// let kernel: GlslKernel = GlslKernel::new()
+///     .param_mut::<[f32], _>("float[] data")
+///     .param::<f32, _>("float scalar")
+///     .with_kernel_code("data[gl_GlobalInvocationID.x] = data[gl_GlobalInvocationID.x] * scalar;");
+/// let finished = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(kernel)?.finish()?;
+/// KernelRegistry::global().insert("scale", finished);
+///
+/// // later on, get the kernel and launch it
+/// let mut data: DeviceBox<[f32]> = vec![1.0; 2048].as_device_boxed_mut()?;
+/// let scale = KernelRegistry::global().get("scale").expect("kernel should be registered");
+/// unsafe {
+///     spawn(2048).launch(call!(scale, &mut data, &DeviceBox::new(10.0f32)?))?;
+/// }
+/// assert_eq!(futures::executor::block_on(data.get())?, vec![10.0; 2048].into_boxed_slice());
+/// # Ok(())
+/// # }
+/// ```
+pub struct KernelRegistry {
+    kernels: RwLock<HashMap<String, Arc<DeviceFnMut>>>,
+}
+
+impl KernelRegistry {
+    /// Creates a new empty registry
+    pub fn new() -> Self {
+        Self {
+            kernels: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the global registry which can be used from anywhere
+    pub fn global() -> &'static Self {
+        &GLOBAL_KERNEL_REGISTRY
+    }
+
+    /// Registers the given kernel with the given name, returning the kernel previously registered with that name if there was one
+    pub fn insert(
+        &self,
+        name: impl Into<String>,
+        device_fn_mut: Arc<DeviceFnMut>,
+    ) -> Option<Arc<DeviceFnMut>> {
+        self.kernels
+            .write()
+            .unwrap()
+            .insert(name.into(), device_fn_mut)
+    }
+
+    /// Gets the kernel registered with the given name
+    pub fn get(&self, name: &str) -> Option<Arc<DeviceFnMut>> {
+        self.kernels.read().unwrap().get(name).map(Arc::clone)
+    }
+
+    /// Checks whether or not a kernel is registered with the given name
+    pub fn contains(&self, name: &str) -> bool {
+        self.kernels.read().unwrap().contains_key(name)
+    }
+
+    /// Unregisters the kernel with the given name, returning it if there was one
+    pub fn remove(&self, name: &str) -> Option<Arc<DeviceFnMut>> {
+        self.kernels.write().unwrap().remove(name)
+    }
+
+    /// Returns the names of all registered kernels
+    pub fn names(&self) -> Vec<String> {
+        self.kernels.read().unwrap().keys().cloned().collect()
+    }
+}
+
+impl Default for KernelRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
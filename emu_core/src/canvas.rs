@@ -0,0 +1,251 @@
+//! Rendering images with compute kernels
+//!
+//! A [`ComputeCanvas`](struct.ComputeCanvas.html) is an RGBA image on a device. It is drawn by a shading function written in GLSL that is run for
+//! every pixel (like a fragment shader, but launched as a compute kernel so no render pipeline is needed) and the result can be saved as a PNG.
+//! This is enough for ray marching signed distance fields, plotting functions, and visualizing simulations. This module requires the
+//! `glsl-compile` feature.
+//! ```
+//! # use {emu_core::prelude::*, emu_core::canvas::*, emu_glsl::*, zerocopy::*};
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # futures::executor::block_on(assert_device_pool_initialized());
+//! let mut canvas = ComputeCanvas::new(4, 2)?;
+//! // red increases from left to right
+//! canvas.render("vec4 shade(vec2 uv) { return vec4(uv.x, 0.0, 1.0, 1.0); }", 0.0)?;
+//!
+//! let rgba = futures::executor::block_on(canvas.get_rgba())?;
+//! assert_eq!(&rgba[..8], &[32, 0, 255, 255, 96, 0, 255, 255]);
+//! # let path = std::env::temp_dir().join("emu_canvas_doctest.png");
+//! futures::executor::block_on(canvas.save_png(&path))?;
+//! # assert!(std::fs::read(&path)?.starts_with(b"\x89PNG"));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::Path;
+
+use crate::boxed::*;
+use crate::cache::*;
+use crate::compile::*;
+use crate::compile_impls::*;
+use crate::device::*;
+use crate::error::*;
+use crate::spawn::*;
+
+// the width and height of the tile of pixels that each thread block shades
+const CANVAS_TILE_SIZE: u32 = 16;
+
+/// An RGBA image on a device that is drawn by a compute kernel
+///
+/// Each pixel is a `u32` with red in the lowest byte and alpha in the highest (what GLSL's `packUnorm4x8` produces). Pixels are stored in
+/// row-major order starting from the top left. [`pixels`](#method.pixels) and [`pixels_mut`](#method.pixels_mut) give access to the
+/// `DeviceBox` so that other kernels can draw on the canvas too.
+pub struct ComputeCanvas {
+    pixels: DeviceBox<[u32]>,
+    width: u32,
+    height: u32,
+}
+
+impl ComputeCanvas {
+    /// Creates a transparent black canvas with the given size on the device currently selected from the pool
+    pub fn new(width: u32, height: u32) -> Result<Self, NoDeviceError> {
+        Ok(Self {
+            pixels: vec![0u32; (width * height) as usize].as_device_boxed_mut()?,
+            width,
+            height,
+        })
+    }
+
+    /// Returns the width in pixels
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the height in pixels
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the pixels
+    pub fn pixels(&self) -> &DeviceBox<[u32]> {
+        &self.pixels
+    }
+
+    /// Returns the pixels so that they can be drawn on by other kernels
+    pub fn pixels_mut(&mut self) -> &mut DeviceBox<[u32]> {
+        &mut self.pixels
+    }
+
+    /// Draws every pixel with the given shading function
+    ///
+    /// The given GLSL code must define a function `vec4 shade(vec2 uv)` (and may define any other functions it uses). `uv` is the center of the
+    /// pixel scaled to `0..1` in both dimensions, with `(0, 0)` at the top left. The returned color is clamped to `0..1`. The code can also use
+    /// `resolution` (a `uvec2` of the width and height) and `time` (the given `float`, for animations). Kernels are cached so rendering frames of an
+    /// animation with the same code only compiles once.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_core::canvas::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// // ray march a sphere lit from the top left
+    /// let mut canvas = ComputeCanvas::new(64, 64)?;
+    /// canvas.render(r#"
+    /// float sdf(vec3 p) {
+    ///     return length(p) - 1.0;
+    /// }
+    ///
+    /// vec4 shade(vec2 uv) {
+    ///     vec3 origin = vec3(0.0, 0.0, -3.0);
+    ///     vec3 direction = normalize(vec3(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 1.5));
+    ///     float t = 0.0;
+    ///     for (int i = 0; i < 64; i++) {
+    ///         float d = sdf(origin + direction * t);
+    ///         if (d < 0.001) {
+    ///             vec3 normal = normalize(origin + direction * t);
+    ///             float light = max(dot(normal, normalize(vec3(-1.0, 1.0, -1.0))), 0.0);
+    ///             return vec4(vec3(light), 1.0);
+    ///         }
+    ///         t += d;
+    ///     }
+    ///     return vec4(0.0, 0.0, 0.0, 1.0);
+    /// }
+    /// "#, 0.0)?;
+    ///
+    /// let rgba = futures::executor::block_on(canvas.get_rgba())?;
+    /// // the corner misses the sphere and the center hits it
+    /// assert_eq!(&rgba[..4], &[0, 0, 0, 255]);
+    /// assert!(rgba[(32 * 64 + 32) * 4] > 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn render<S: Into<String>>(
+        &mut self,
+        shading_code: S,
+        time: f32,
+    ) -> Result<(), KernelError> {
+        if self.width == 0 || self.height == 0 {
+            return Ok(());
+        }
+
+        let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+            GlslKernel::new()
+                .spawn(CANVAS_TILE_SIZE)
+                .spawn(CANVAS_TILE_SIZE)
+                .param_mut::<[u32], _>("uint[] pixels")
+                .param::<[u32; 2], _>("uvec2 resolution")
+                .param::<f32, _>("float time")
+                .with_helper_code(shading_code)
+                .with_kernel_code(
+                    r#"
+uvec2 pixel = gl_GlobalInvocationID.xy;
+if (pixel.x < resolution.x && pixel.y < resolution.y) {
+    vec2 uv = (vec2(pixel) + 0.5) / vec2(resolution);
+    pixels[pixel.y * resolution.x + pixel.x] = packUnorm4x8(clamp(shade(uv), 0.0, 1.0));
+}
+"#,
+                ),
+        )?
+        .finish()?;
+
+        unsafe {
+            spawn((self.width + CANVAS_TILE_SIZE - 1) / CANVAS_TILE_SIZE)
+                .spawn((self.height + CANVAS_TILE_SIZE - 1) / CANVAS_TILE_SIZE)
+                .launch(crate::call!(
+                    kernel,
+                    &mut self.pixels,
+                    (self.width, self.height),
+                    time
+                ))?;
+        }
+        Ok(())
+    }
+
+    /// Downloads the pixels as `width * height * 4` bytes of red, green, blue, and alpha
+    pub async fn get_rgba(&self) -> Result<Vec<u8>, GetError> {
+        Ok(self
+            .pixels
+            .get()
+            .await?
+            .iter()
+            .flat_map(|pixel| pixel.to_le_bytes().to_vec())
+            .collect())
+    }
+
+    /// Downloads the pixels and saves them as a PNG image at the given path
+    ///
+    /// The image is not compressed, so this is meant for looking at results rather than for publishing them.
+    pub async fn save_png<Q: AsRef<Path>>(&self, path: Q) -> Result<(), PersistError> {
+        let rgba = self.get_rgba().await.map_err(PersistError::Get)?;
+        std::fs::write(path, encode_png(&rgba, self.width, self.height)).map_err(PersistError::Io)
+    }
+}
+
+// encodes an RGBA image as a PNG with the image data in stored (uncompressed) deflate blocks
+fn encode_png(rgba: &[u8], width: u32, height: u32) -> Vec<u8> {
+    // each row starts with a byte for the filter type, which is 0 for no filter
+    let mut raw = Vec::with_capacity(rgba.len() + height as usize);
+    for row in rgba.chunks((width * 4).max(1) as usize) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    // a zlib stream with no compression
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        zlib.push(if blocks.peek().is_none() { 1 } else { 0 });
+        zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // a bit depth of 8, the RGBA color type, the only compression and filter methods, and no interlacing
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_png_chunk(&mut png, b"IHDR", &header);
+    write_png_chunk(&mut png, b"IDAT", &zlib);
+    write_png_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (i, entry) in table.iter_mut().enumerate() {
+        let mut c = i as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+        *entry = c;
+    }
+    !data.iter().fold(!0u32, |crc, &byte| {
+        table[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
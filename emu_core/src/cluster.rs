@@ -0,0 +1,370 @@
+//! Ready-made kernels for clustering and nearest-neighbor search
+//!
+//! Points are stored as a `DeviceBox<[f32]>` of `num_points * dim` coordinates where the coordinates of each point are contiguous. Distances are
+//! squared Euclidean distances (so no square roots are taken). This module requires the `glsl-compile` feature.
+//! - [`distance_matrix`](fn.distance_matrix.html) for the distance between every pair of points from 2 sets
+//! - [`kmeans_assign`](fn.kmeans_assign.html) and [`kmeans_update`](fn.kmeans_update.html) for the 2 steps of an iteration of k-means
+//! - [`kmeans`](fn.kmeans.html) for running k-means for a number of iterations
+//! - [`knn`](fn.knn.html) for a brute-force search for the nearest neighbors of each of a set of queries
+//! ```
+//! # use {emu_core::prelude::*, emu_core::cluster::*, emu_glsl::*, zerocopy::*};
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # futures::executor::block_on(assert_device_pool_initialized());
+//! // 4 points in 2 dimensions that form 2 clusters
+//! let points: DeviceBox<[f32]> = vec![0.0, 0.0, 0.0, 1.0, 10.0, 10.0, 10.0, 11.0].as_device_boxed()?;
+//!
+//! let mut centroids: DeviceBox<[f32]> = vec![0.0, 0.0, 10.0, 10.0].as_device_boxed_mut()?;
+//! let assignments = kmeans(&points, &mut centroids, 2, 5)?;
+//! assert_eq!(futures::executor::block_on(assignments.get())?, vec![0, 0, 1, 1].into_boxed_slice());
+//! assert_eq!(futures::executor::block_on(centroids.get())?, vec![0.0, 0.5, 10.0, 10.5].into_boxed_slice());
+//!
+//! // the 2 nearest neighbors of (1, 1)
+//! let queries: DeviceBox<[f32]> = vec![1.0, 1.0].as_device_boxed()?;
+//! let (indices, distances) = knn(&points, &queries, 2, 2)?;
+//! assert_eq!(futures::executor::block_on(indices.get())?, vec![1, 0].into_boxed_slice());
+//! assert_eq!(futures::executor::block_on(distances.get())?, vec![1.0, 2.0].into_boxed_slice());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::boxed::*;
+use crate::cache::*;
+use crate::compile::*;
+use crate::compile_impls::*;
+use crate::device::*;
+use crate::error::*;
+use crate::spawn::*;
+
+// the number of threads in each thread block of the kernels in this module
+const CLUSTER_BLOCK_SIZE: u32 = 64;
+// the most thread blocks the kernels in this module launch, each thread strides through the outputs
+const MAX_CLUSTER_BLOCKS: u32 = 4096;
+// the most neighbors knn can find for each query
+// each thread keeps its best neighbors so far in local arrays that are sorted by insertion
+const MAX_KNN_NEIGHBORS: usize = 64;
+
+/// Computes the squared distance between every point in `a` and every point in `b`
+///
+/// This returns a mutable `DeviceBox` of `num_a * num_b` distances in row-major order, so the distance between point `i` of `a` and point `j` of
+/// `b` is at index `i * num_b + j`. Both sets of points must have `dim` coordinates each.
+/// ```
+/// # use {emu_core::prelude::*, emu_core::cluster::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # futures::executor::block_on(assert_device_pool_initialized());
+/// let a: DeviceBox<[f32]> = vec![0.0, 0.0, 1.0, 2.0].as_device_boxed()?;
+/// let b: DeviceBox<[f32]> = vec![3.0, 4.0].as_device_boxed()?;
+/// let distances = distance_matrix(&a, &b, 2)?;
+/// assert_eq!(futures::executor::block_on(distances.get())?, vec![25.0, 8.0].into_boxed_slice());
+/// # Ok(())
+/// # }
+/// ```
+pub fn distance_matrix(
+    a: &DeviceBox<[f32]>,
+    b: &DeviceBox<[f32]>,
+    dim: usize,
+) -> Result<DeviceBox<[f32]>, KernelError> {
+    let num_a = count_points(a, dim, "a");
+    let num_b = count_points(b, dim, "b");
+    let len = num_a * num_b;
+    let mut distances: DeviceBox<[f32]> = vec![0.0f32; len].as_device_boxed_mut()?;
+    if len == 0 {
+        return Ok(distances);
+    }
+
+    let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+        GlslKernel::new()
+            .spawn(CLUSTER_BLOCK_SIZE)
+            .param::<[f32], _>("float[] a")
+            .param::<[f32], _>("float[] b")
+            .param_mut::<[f32], _>("float[] distances")
+            .param::<[u32; 3], _>("uvec3 sizes")
+            .with_kernel_code(format!(
+                r#"
+// sizes is the number of points in a, the number of points in b, and the number of dimensions
+for (uint i = gl_GlobalInvocationID.x; i < sizes.x * sizes.y; i += gl_NumWorkGroups.x * {block_size}) {{
+    uint row = i / sizes.y;
+    uint col = i % sizes.y;
+    float sum = 0.0;
+    for (uint d = 0; d < sizes.z; d++) {{
+        float diff = a[row * sizes.z + d] - b[col * sizes.z + d];
+        sum += diff * diff;
+    }}
+    distances[i] = sum;
+}}
+"#,
+                block_size = CLUSTER_BLOCK_SIZE
+            )),
+    )?
+    .finish()?;
+
+    unsafe {
+        spawn(num_blocks(len)).launch(crate::call!(
+            kernel,
+            a,
+            b,
+            &mut distances,
+            (num_a as u32, num_b as u32, dim as u32)
+        ))?;
+    }
+
+    Ok(distances)
+}
+
+/// Assigns each point to the index of its nearest centroid
+///
+/// `assignments` must have an element for each point. If a point is equally near to more than 1 centroid, it is assigned to the first.
+pub fn kmeans_assign(
+    points: &DeviceBox<[f32]>,
+    centroids: &DeviceBox<[f32]>,
+    dim: usize,
+    assignments: &mut DeviceBox<[u32]>,
+) -> Result<(), KernelError> {
+    let num_points = count_points(points, dim, "points");
+    let num_centroids = count_points(centroids, dim, "centroids");
+    assert_eq!(
+        assignments.len(),
+        num_points,
+        "there must be an assignment for each point"
+    );
+    assert!(num_centroids > 0, "there must be at least 1 centroid");
+    if num_points == 0 {
+        return Ok(());
+    }
+
+    let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+        GlslKernel::new()
+            .spawn(CLUSTER_BLOCK_SIZE)
+            .param::<[f32], _>("float[] points")
+            .param::<[f32], _>("float[] centroids")
+            .param_mut::<[u32], _>("uint[] assignments")
+            .param::<[u32; 3], _>("uvec3 sizes")
+            .with_kernel_code(format!(
+                r#"
+// sizes is the number of points, the number of centroids, and the number of dimensions
+for (uint i = gl_GlobalInvocationID.x; i < sizes.x; i += gl_NumWorkGroups.x * {block_size}) {{
+    uint nearest = 0;
+    float nearest_distance = 0.0;
+    for (uint c = 0; c < sizes.y; c++) {{
+        float sum = 0.0;
+        for (uint d = 0; d < sizes.z; d++) {{
+            float diff = points[i * sizes.z + d] - centroids[c * sizes.z + d];
+            sum += diff * diff;
+        }}
+        if (c == 0 || sum < nearest_distance) {{
+            nearest = c;
+            nearest_distance = sum;
+        }}
+    }}
+    assignments[i] = nearest;
+}}
+"#,
+                block_size = CLUSTER_BLOCK_SIZE
+            )),
+    )?
+    .finish()?;
+
+    unsafe {
+        spawn(num_blocks(num_points)).launch(crate::call!(
+            kernel,
+            points,
+            centroids,
+            assignments,
+            (num_points as u32, num_centroids as u32, dim as u32)
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Moves each centroid to the mean of the points assigned to it
+///
+/// Centroids that no points are assigned to are left where they are. Each thread computes 1 coordinate of 1 centroid by going through all of the
+/// points, so this is meant for a small number of centroids.
+pub fn kmeans_update(
+    points: &DeviceBox<[f32]>,
+    assignments: &DeviceBox<[u32]>,
+    dim: usize,
+    centroids: &mut DeviceBox<[f32]>,
+) -> Result<(), KernelError> {
+    let num_points = count_points(points, dim, "points");
+    let num_centroids = count_points(centroids, dim, "centroids");
+    assert_eq!(
+        assignments.len(),
+        num_points,
+        "there must be an assignment for each point"
+    );
+    let len = num_centroids * dim;
+    if len == 0 {
+        return Ok(());
+    }
+
+    let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+        GlslKernel::new()
+            .spawn(CLUSTER_BLOCK_SIZE)
+            .param::<[f32], _>("float[] points")
+            .param::<[u32], _>("uint[] assignments")
+            .param_mut::<[f32], _>("float[] centroids")
+            .param::<[u32; 3], _>("uvec3 sizes")
+            .with_kernel_code(format!(
+                r#"
+// sizes is the number of points, the number of centroids, and the number of dimensions
+for (uint i = gl_GlobalInvocationID.x; i < sizes.y * sizes.z; i += gl_NumWorkGroups.x * {block_size}) {{
+    uint c = i / sizes.z;
+    uint d = i % sizes.z;
+    float sum = 0.0;
+    uint count = 0;
+    for (uint p = 0; p < sizes.x; p++) {{
+        if (assignments[p] == c) {{
+            sum += points[p * sizes.z + d];
+            count++;
+        }}
+    }}
+    if (count > 0) {{
+        centroids[i] = sum / float(count);
+    }}
+}}
+"#,
+                block_size = CLUSTER_BLOCK_SIZE
+            )),
+    )?
+    .finish()?;
+
+    unsafe {
+        spawn(num_blocks(len)).launch(crate::call!(
+            kernel,
+            points,
+            assignments,
+            centroids,
+            (num_points as u32, num_centroids as u32, dim as u32)
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Runs the given number of iterations of k-means, starting from the given centroids
+///
+/// Each iteration is a [`kmeans_assign`](fn.kmeans_assign.html) followed by a [`kmeans_update`](fn.kmeans_update.html). The centroids are
+/// updated in place and this returns the assignment of each point to its nearest centroid after the last iteration. Picking the initial centroids
+/// (for example, by choosing random points) is left to the caller.
+pub fn kmeans(
+    points: &DeviceBox<[f32]>,
+    centroids: &mut DeviceBox<[f32]>,
+    dim: usize,
+    iterations: usize,
+) -> Result<DeviceBox<[u32]>, KernelError> {
+    let num_points = count_points(points, dim, "points");
+    let mut assignments: DeviceBox<[u32]> = vec![0u32; num_points].as_device_boxed_mut()?;
+    for _ in 0..iterations {
+        kmeans_assign(points, centroids, dim, &mut assignments)?;
+        kmeans_update(points, &assignments, dim, centroids)?;
+    }
+    kmeans_assign(points, centroids, dim, &mut assignments)?;
+    Ok(assignments)
+}
+
+/// Finds the `k` nearest points to each query
+///
+/// This returns the indices of the neighbors and their squared distances, both with `k` elements for each query sorted from nearest to farthest.
+/// Points that are equally far away are sorted by index. Each thread compares 1 query to every point, which is fast enough for up to a few
+/// hundred thousand points. `k` must be at most 64 and at most the number of points.
+pub fn knn(
+    points: &DeviceBox<[f32]>,
+    queries: &DeviceBox<[f32]>,
+    dim: usize,
+    k: usize,
+) -> Result<(DeviceBox<[u32]>, DeviceBox<[f32]>), KernelError> {
+    let num_points = count_points(points, dim, "points");
+    let num_queries = count_points(queries, dim, "queries");
+    assert!(
+        k > 0 && k <= MAX_KNN_NEIGHBORS,
+        "k must be at least 1 and at most {}",
+        MAX_KNN_NEIGHBORS
+    );
+    assert!(k <= num_points, "k must be at most the number of points");
+    let mut indices: DeviceBox<[u32]> = vec![0u32; num_queries * k].as_device_boxed_mut()?;
+    let mut distances: DeviceBox<[f32]> = vec![0.0f32; num_queries * k].as_device_boxed_mut()?;
+    if num_queries == 0 {
+        return Ok((indices, distances));
+    }
+
+    let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+        GlslKernel::new()
+            .spawn(CLUSTER_BLOCK_SIZE)
+            .param::<[f32], _>("float[] points")
+            .param::<[f32], _>("float[] queries")
+            .param_mut::<[u32], _>("uint[] indices")
+            .param_mut::<[f32], _>("float[] distances")
+            .param::<[u32; 3], _>("uvec3 sizes")
+            .with_kernel_code(format!(
+                r#"
+// sizes is the number of points, the number of queries, and the number of dimensions
+for (uint q = gl_GlobalInvocationID.x; q < sizes.y; q += gl_NumWorkGroups.x * {block_size}) {{
+    float best_distances[{k}];
+    uint best_indices[{k}];
+    for (uint j = 0; j < {k}; j++) {{
+        best_distances[j] = uintBitsToFloat(0x7f800000u); // infinity
+        best_indices[j] = 0;
+    }}
+
+    for (uint p = 0; p < sizes.x; p++) {{
+        float sum = 0.0;
+        for (uint d = 0; d < sizes.z; d++) {{
+            float diff = points[p * sizes.z + d] - queries[q * sizes.z + d];
+            sum += diff * diff;
+        }}
+        // insert the point into the sorted neighbors, after any that are just as near
+        if (sum < best_distances[{k} - 1]) {{
+            uint j = {k} - 1;
+            while (j > 0 && best_distances[j - 1] > sum) {{
+                best_distances[j] = best_distances[j - 1];
+                best_indices[j] = best_indices[j - 1];
+                j--;
+            }}
+            best_distances[j] = sum;
+            best_indices[j] = p;
+        }}
+    }}
+
+    for (uint j = 0; j < {k}; j++) {{
+        indices[q * {k} + j] = best_indices[j];
+        distances[q * {k} + j] = best_distances[j];
+    }}
+}}
+"#,
+                block_size = CLUSTER_BLOCK_SIZE,
+                k = k
+            )),
+    )?
+    .finish()?;
+
+    unsafe {
+        spawn(num_blocks(num_queries)).launch(crate::call!(
+            kernel,
+            points,
+            queries,
+            &mut indices,
+            &mut distances,
+            (num_points as u32, num_queries as u32, dim as u32)
+        ))?;
+    }
+
+    Ok((indices, distances))
+}
+
+// the number of points with the given number of dimensions in the given DeviceBox
+fn count_points(points: &DeviceBox<[f32]>, dim: usize, name: &str) -> usize {
+    assert!(dim > 0, "there must be at least 1 dimension");
+    assert_eq!(
+        points.len() % dim,
+        0,
+        "the length of `{}` must be a multiple of the number of dimensions",
+        name
+    );
+    points.len() / dim
+}
+
+// the number of thread blocks to launch for the given number of outputs
+fn num_blocks(len: usize) -> u32 {
+    ((len as u32 + CLUSTER_BLOCK_SIZE - 1) / CLUSTER_BLOCK_SIZE).min(MAX_CLUSTER_BLOCKS)
+}
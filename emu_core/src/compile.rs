@@ -1,22 +1,131 @@
 //! The whole source-to-`DeviceFnMut` compilation pipeline
 
 use crate::cache::*;
+use crate::compile_impls::*;
 use crate::device::*;
 use crate::error::*;
 use crate::pool::*;
 
 use std::borrow::BorrowMut;
 use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
 
 use std::hash::{Hash, Hasher};
 use std::io::{Read, Seek};
-use std::sync::Arc;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
 
 // TODO in the future, generalize this to other types, not just struct
 /// A trait for structures that can exist in both Rust and GLSL
 pub trait GlslStruct {
     /// Provides the GLSL structure definition code to define this structure in GLSL
     fn as_glsl() -> String;
+
+    /// Provides the name of this structure in GLSL
+    ///
+    /// By default, this is the name given in the definition returned by [`as_glsl`](#tymethod.as_glsl).
+    fn glsl_name() -> String {
+        Self::as_glsl()
+            .trim_start()
+            .trim_start_matches("struct")
+            .trim_start()
+            .split(|c: char| c.is_whitespace() || c == '{')
+            .next()
+            .unwrap_or("")
+            .to_string()
+    }
+
+    /// Provides the name, GLSL type, and layout of each field of this structure in the order they are declared
+    ///
+    /// This is what lets structures be stored as a structure of arrays (see [`to_soa`](../boxed/fn.to_soa.html)). By default, no fields are provided.
+    fn glsl_fields() -> Vec<GlslField> {
+        vec![]
+    }
+}
+
+/// A field of a [`GlslStruct`](trait.GlslStruct.html)
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GlslField {
+    /// The name of the field in both Rust and GLSL
+    pub name: String,
+    /// The GLSL type of the field (e.g. - `vec2`)
+    pub glsl_type: String,
+    /// The offset in bytes of the field in the Rust structure
+    pub offset: usize,
+    /// The size in bytes of the field in the Rust structure
+    pub size: usize,
+}
+
+/// A trait for small vectors that can exist in both Rust (as arrays) and GLSL (as vector types)
+///
+/// This is implemented for 2- and 4-component arrays of `f32`, `i32`, and `u32` (e.g. - `[f32; 4]` is a `vec4` and `[i32; 2]` is an `ivec2`).
+/// 3-component vectors are left out on purpose because a `vec3` in a GLSL buffer is aligned to 16 bytes while a `[f32; 3]` is only 12 bytes.
+pub trait GlslVector {
+    /// Provides the name of the GLSL vector type
+    fn glsl_type() -> &'static str;
+}
+
+macro_rules! impl_glsl_vector {
+    ($($rust_type:ty => $glsl_type:expr),*) => {
+        $(
+            impl GlslVector for $rust_type {
+                fn glsl_type() -> &'static str {
+                    $glsl_type
+                }
+            }
+        )*
+    };
+}
+
+impl_glsl_vector! {
+    [f32; 2] => "vec2",
+    [f32; 4] => "vec4",
+    [i32; 2] => "ivec2",
+    [i32; 4] => "ivec4",
+    [u32; 2] => "uvec2",
+    [u32; 4] => "uvec4"
+}
+
+/// A trait for scalars that can exist in both Rust and GLSL
+///
+/// This is implemented for `f32` (a `float`), `i32` (an `int`), `u32` (a `uint`), and `f64` (a `double`). It's what the `$T` in a
+/// [`KernelTemplate`](../template/struct.KernelTemplate.html) is replaced with.
+pub trait GlslScalar {
+    /// Provides the name of the GLSL scalar type
+    fn glsl_type() -> &'static str;
+
+    /// Returns whether or not kernels using this type need 64-bit floats (see [`GlslKernel::with_f64`](../compile_impls/struct.GlslKernel.html#method.with_f64))
+    fn needs_f64() -> bool {
+        false
+    }
+}
+
+macro_rules! impl_glsl_scalar {
+    ($($rust_type:ty => $glsl_type:expr),*) => {
+        $(
+            impl GlslScalar for $rust_type {
+                fn glsl_type() -> &'static str {
+                    $glsl_type
+                }
+            }
+        )*
+    };
+}
+
+impl_glsl_scalar! {
+    f32 => "float",
+    i32 => "int",
+    u32 => "uint"
+}
+
+impl GlslScalar for f64 {
+    fn glsl_type() -> &'static str {
+        "double"
+    }
+
+    fn needs_f64() -> bool {
+        true
+    }
 }
 
 /// The trait to implement when adding support for a new source language (e.g. - HLSL, XLA, Swift SIL, etc.).
@@ -31,6 +140,147 @@ pub trait CompileToSpirv<I: Hash, P: BorrowMut<[u32]>> {
     fn compile_to_spirv(src: I) -> Result<Spirv<P>, CompileError>;
 }
 
+/// The trait to implement for a source language compiler that can be chosen at run-time
+///
+/// Unlike [`CompileToSpirv`](trait.CompileToSpirv.html), which is chosen with a type parameter at compile-time, implementations of this are
+/// trait objects that can be registered with a [`CompilerRegistry`](struct.CompilerRegistry.html) under a language tag (like "glsl").
+/// This is useful for tools that only get the source of a kernel at run-time (like REPLs or notebook backends).
+pub trait DynCompileToSpirv: Send + Sync {
+    /// Compiles the given source with the given parameters and entry point name into SPIR-V
+    fn compile_to_spirv(
+        &self,
+        src: &[u8],
+        params: DeviceFnMutParams,
+        entry_point: &str,
+    ) -> Result<Spirv<Vec<u32>>, CompileError>;
+}
+
+lazy_static! {
+    static ref GLOBAL_COMPILER_REGISTRY: CompilerRegistry = CompilerRegistry::with_defaults();
+}
+
+/// A registry mapping language tags (like "glsl" or "spirv") to compilers that can be chosen at run-time
+///
+/// The global registry comes with "spirv" (for SPIR-V binaries) and, with the `glsl-compile` feature, "glsl" (for GLSL compute shaders) registered.
+/// You can register more source languages with [`register`](#method.register).
+/// ```
+/// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// futures::executor::block_on(assert_device_pool_initialized());
+///
+/// // the language and source might come from a user at run-time
+/// let (lang, src) = ("glsl", r#"
+/// #version 450
+/// layout(local_size_x = 1) in;
+///
+/// layout(set = 0, binding = 0) buffer Data {
+///     float[] data;
+/// };
+///
+/// void main() {
+///     data[gl_GlobalInvocationID.x] *= 2.0;
+/// }
+/// "#);
+/// let params = ParamsBuilder::new().param::<[f32]>(Mutability::Mut).build();
+/// let c = CompilerRegistry::global()
+///     .compile::<GlobalCache>(lang, src.as_bytes(), params, "main")?
+///     .finish()?;
+///
+/// let mut data: DeviceBox<[f32]> = vec![1.0; 2048].as_device_boxed_mut()?;
+/// unsafe {
+///     spawn(2048).launch(call!(c, &mut data))?;
+/// }
+/// assert_eq!(futures::executor::block_on(data.get())?, vec![2.0; 2048].into_boxed_slice());
+/// # Ok(())
+/// # }
+/// ```
+pub struct CompilerRegistry {
+    compilers: RwLock<HashMap<String, Arc<dyn DynCompileToSpirv>>>,
+}
+
+impl CompilerRegistry {
+    /// Creates a new registry with no compilers registered
+    pub fn new() -> Self {
+        Self {
+            compilers: RwLock::new(HashMap::new()),
+        }
+    }
+
+    // creates a new registry with all the compilers that Emu comes with
+    fn with_defaults() -> Self {
+        let registry = Self::new();
+        registry.register("spirv", SpirvCompile);
+        #[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+        registry.register("glsl", GlslCompile);
+        registry
+    }
+
+    /// Returns the global registry which can be used from anywhere
+    pub fn global() -> &'static Self {
+        &GLOBAL_COMPILER_REGISTRY
+    }
+
+    /// Registers the given compiler under the given language tag, replacing any compiler already registered under that tag
+    pub fn register(&self, lang: impl Into<String>, compiler: impl DynCompileToSpirv + 'static) {
+        self.compilers
+            .write()
+            .unwrap()
+            .insert(lang.into(), Arc::new(compiler));
+    }
+
+    /// Checks whether or not a compiler is registered under the given language tag
+    pub fn contains(&self, lang: &str) -> bool {
+        self.compilers.read().unwrap().contains_key(lang)
+    }
+
+    /// Compiles the given source using the compiler registered under the given language tag
+    ///
+    /// This works just like [`compile`](fn.compile.html). The result is cached with the given cache. A `CompileError` is
+    /// returned if there is no compiler registered under the given language tag.
+    pub fn compile<C: Cache>(
+        &self,
+        lang: &str,
+        src: &[u8],
+        params: DeviceFnMutParams,
+        entry_point: &str,
+    ) -> Result<SpirvOrFinished<Vec<u32>, C>, CompileError> {
+        // get the hash of the source
+        let mut hasher = DefaultHasher::new();
+        lang.hash(&mut hasher);
+        src.hash(&mut hasher);
+        params.hash(&mut hasher);
+        entry_point.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        // check if source is in cache
+        // if not, compile to SPIR-V before returning
+        if C::contains(hash) {
+            Ok(SpirvOrFinished::Finished(C::get(hash)))
+        } else {
+            // we clone the Arc so that we don't hold the lock while compiling
+            let compiler = self
+                .compilers
+                .read()
+                .unwrap()
+                .get(lang)
+                .map(Arc::clone)
+                .ok_or_else(|| CompileError::UnknownLanguage(lang.to_string()))?;
+            let spirv = compiler.compile_to_spirv(src, params, entry_point)?;
+            Ok(SpirvOrFinished::SpirvAndHash((
+                spirv,
+                hash,
+                std::marker::PhantomData,
+            )))
+        }
+    }
+}
+
+impl Default for CompilerRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// A wrapper for SPIR-V bytecode
 ///
 /// The wrapper adds a few important details including parameters and the name of the relevant entry point in the bytecode.
@@ -42,6 +292,168 @@ pub struct Spirv<P: BorrowMut<[u32]>> {
     pub code: P,
 }
 
+// the first bytes of every file written by Spirv::to_file
+// the last 2 bytes are the version of the format
+const FILE_MAGIC: &[u8; 8] = b"EMUKRN01";
+
+impl<P: BorrowMut<[u32]>> Spirv<P> {
+    /// Saves this SPIR-V along with its entry point name and parameters to a file at the given path
+    ///
+    /// This lets you ship kernels that are already compiled (e.g. - as a pack of `.emu` files) so that an application doesn't need to compile
+    /// them from GLSL (or whatever the source language is) at run-time. Load them back with [`from_file`](#method.from_file) and then compile
+    /// them to a `DeviceFnMut` with [`SpirvCompile`](../compile_impls/struct.SpirvCompile.html).
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let path = std::env::temp_dir().join("double.emu");
+    ///
+    /// // this would be done ahead of time, maybe in a build script
+    /// let spirv = GlslKernelCompile::compile_to_spirv(
+    ///     GlslKernel::new()
+    ///         .param_mut::<[f32], _>("float[] data")
+    ///         .with_kernel_code("data[gl_GlobalInvocationID.x] = data[gl_GlobalInvocationID.x] * 2.0;"),
+    /// )?;
+    /// spirv.to_file(&path)?;
+    ///
+    /// // and this would be done in the application
+    /// let kernel = compile::<Spirv<Vec<u32>>, SpirvCompile, _, GlobalCache>(Spirv::from_file(&path)?)?.finish()?;
+    /// let mut data: DeviceBox<[f32]> = vec![1.0; 1024].as_device_boxed_mut()?;
+    /// unsafe {
+    ///     spawn(1024).launch(call!(kernel, &mut data))?;
+    /// }
+    /// assert_eq!(futures::executor::block_on(data.get())?, vec![2.0; 1024].into_boxed_slice());
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_file<Q: AsRef<Path>>(&self, path: Q) -> Result<(), PersistError> {
+        let code = self.code.borrow();
+        let mut file = Vec::with_capacity(FILE_MAGIC.len() + self.name.len() + code.len() * 4);
+        file.extend_from_slice(FILE_MAGIC);
+        write_string(&mut file, &self.name);
+        self.params.write_to(&mut file)?;
+        file.extend_from_slice(&(code.len() as u32).to_le_bytes());
+        for word in code {
+            file.extend_from_slice(&word.to_le_bytes());
+        }
+        std::fs::write(path, file).map_err(PersistError::Io)
+    }
+}
+
+impl Spirv<Vec<u32>> {
+    /// Loads SPIR-V along with its entry point name and parameters from a file saved with [`to_file`](#method.to_file)
+    pub fn from_file<Q: AsRef<Path>>(path: Q) -> Result<Self, PersistError> {
+        let file = std::fs::read(path).map_err(PersistError::Io)?;
+
+        let mut rest = file
+            .strip_prefix(&FILE_MAGIC[..])
+            .ok_or(PersistError::InvalidHeader)?;
+        let name = read_string(&mut rest)?;
+        let params = DeviceFnMutParams::read_from(&mut rest)?;
+        let len = read_u32(&mut rest)? as usize;
+        let code = read_bytes(&mut rest, len * 4)?
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect();
+
+        Ok(Self { params, name, code })
+    }
+
+    /// Marks every floating-point arithmetic operation as precise so that results are reproducible bit for bit
+    ///
+    /// Drivers are allowed to reassociate and contract floating-point operations (for example, fusing `a * b + c` into a single fused multiply-add)
+    /// and may do so differently depending on the surrounding code, the driver version, or the optimization level. This decorates the result of
+    /// every floating-point add, subtract, multiply, divide, remainder, negation, and vector/matrix product with SPIR-V's `NoContraction`
+    /// (what GLSL's `precise` qualifier compiles to). Each operation is then rounded by itself, exactly as written. Additions, subtractions, and
+    /// multiplications are correctly rounded on every device, so a kernel that only uses those matches the same computation on the CPU.
+    /// This usually makes kernels a little slower. Kernels built with [`GlslKernel`](../compile_impls/struct.GlslKernel.html) can use
+    /// [`with_deterministic_floats`](../compile_impls/struct.GlslKernel.html#method.with_deterministic_floats) instead.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let spirv = GlslKernelCompile::compile_to_spirv(
+    ///     GlslKernel::new()
+    ///         .param_mut::<[f32], _>("float[] data")
+    ///         .with_kernel_code("uint i = gl_GlobalInvocationID.x; data[i] = data[i] * 1.1 + 0.3;"),
+    /// )?
+    /// .with_deterministic_floats();
+    /// let kernel = compile::<Spirv<Vec<u32>>, SpirvCompile, _, GlobalCache>(spirv)?.finish()?;
+    ///
+    /// let data = (0..1024).map(|i| i as f32 / 7.0).collect::<Vec<f32>>();
+    /// let mut data_on_device: DeviceBox<[f32]> = data.as_device_boxed_mut()?;
+    /// unsafe { spawn(1024).launch(call!(kernel, &mut data_on_device))?; }
+    /// let result = futures::executor::block_on(data_on_device.get())?;
+    /// // every element is exactly what the CPU computes
+    /// for (x, y) in data.iter().zip(result.iter()) {
+    ///     assert_eq!((x * 1.1 + 0.3).to_bits(), y.to_bits());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_deterministic_floats(mut self) -> Self {
+        self.code = decorate_no_contraction(&self.code);
+        self
+    }
+}
+
+// the opcodes of instructions that come before the first type declaration in a SPIR-V module
+// (capabilities, extensions, imports, the memory model, entry points, execution modes, debug information, and annotations)
+const SPIRV_PREAMBLE_OPCODES: &[u32] = &[
+    2, 3, 4, 5, 6, 7, 10, 11, 14, 15, 16, 17, 71, 72, 73, 74, 75, 330, 331, 332, 5632, 5633,
+];
+// the opcodes of floating-point arithmetic instructions
+// OpFNegate, OpFAdd, OpFSub, OpFMul, OpFDiv, OpFRem, OpFMod, OpVectorTimesScalar, OpMatrixTimesScalar, OpVectorTimesMatrix,
+// OpMatrixTimesVector, OpMatrixTimesMatrix, OpDot
+const SPIRV_FLOAT_ARITHMETIC_OPCODES: &[u32] = &[
+    127, 129, 131, 133, 136, 140, 141, 142, 143, 144, 145, 146, 148,
+];
+const SPIRV_OP_DECORATE: u32 = 71;
+const SPIRV_DECORATION_NO_CONTRACTION: u32 = 42;
+
+// returns the given SPIR-V module with every floating-point arithmetic instruction decorated with NoContraction
+//
+// the decorations are inserted at the end of the annotations, which must come before any type declaration
+fn decorate_no_contraction(code: &[u32]) -> Vec<u32> {
+    if code.len() < 5 {
+        return code.to_vec();
+    }
+
+    // the header is 5 words and every instruction starts with a word with its length in the high 16 bits and its opcode in the low 16 bits
+    let mut insert_at = None;
+    let mut results = vec![];
+    let mut i = 5;
+    while i < code.len() {
+        let (len, opcode) = ((code[i] >> 16) as usize, code[i] & 0xffff);
+        if len == 0 || i + len > code.len() {
+            // the module is malformed so we leave it for validation to reject
+            return code.to_vec();
+        }
+        if insert_at.is_none() && !SPIRV_PREAMBLE_OPCODES.contains(&opcode) {
+            insert_at = Some(i);
+        }
+        // the result of an arithmetic instruction is its third word, after the type of the result
+        if SPIRV_FLOAT_ARITHMETIC_OPCODES.contains(&opcode) && len > 2 {
+            results.push(code[i + 2]);
+        }
+        i += len;
+    }
+
+    let insert_at = insert_at.unwrap_or(code.len());
+    let mut decorated = Vec::with_capacity(code.len() + results.len() * 3);
+    decorated.extend_from_slice(&code[..insert_at]);
+    for result in results {
+        decorated.extend_from_slice(&[
+            (3 << 16) | SPIRV_OP_DECORATE,
+            result,
+            SPIRV_DECORATION_NO_CONTRACTION,
+        ]);
+    }
+    decorated.extend_from_slice(&code[insert_at..]);
+    decorated
+}
+
 /// A builder for constructing a [`Spirv`](struct.Spirv.html)
 ///
 /// You can use it in the case where you are starting from either `u8` bytes or 4-byte `u32` words.
@@ -137,6 +549,125 @@ impl SpirvBuilder<Vec<u32>> {
     }
 }
 
+/// Embeds a SPIR-V binary file in the program at compile-time and evaluates to a [`Spirv<Vec<u32>>`](compile/struct.Spirv.html)
+///
+/// This is the common workflow for kernels compiled to SPIR-V ahead of time (e.g. - with `glslc` in a build script). The path is relative to the
+/// current file, like with `include_bytes!`. The file is checked at compile-time - it must start with the SPIR-V magic number in the
+/// endianness of the target and have a compute entry point. The name of the first compute entry point is used as the entry point name. The
+/// parameters of the kernel can be given as a [`DeviceFnMutParams`](device/struct.DeviceFnMutParams.html) after the path. Otherwise, the
+/// kernel has no parameters.
+/// ```
+/// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # futures::executor::block_on(assert_device_pool_initialized());
+/// let spirv = include_spirv!(
+///     concat!(env!("CARGO_MANIFEST_DIR"), "/examples/collatz.spv"),
+///     ParamsBuilder::new().param::<[u32]>(Mutability::Mut).build()
+/// );
+/// assert_eq!(spirv.name, "main");
+///
+/// let kernel = compile::<Spirv<Vec<u32>>, SpirvCompile, _, GlobalCache>(spirv)?.finish()?;
+/// let mut numbers: DeviceBox<[u32]> = vec![1, 2, 3, 4].as_device_boxed_mut()?;
+/// unsafe {
+///     spawn(4).launch(call!(kernel, &mut numbers))?;
+/// }
+/// assert_eq!(futures::executor::block_on(numbers.get())?, vec![0, 1, 7, 2].into_boxed_slice());
+/// # Ok(())
+/// # }
+/// ```
+#[macro_export]
+macro_rules! include_spirv {
+    ($path:expr) => {
+        $crate::include_spirv!($path, $crate::device::ParamsBuilder::new().build())
+    };
+    ($path:expr, $params:expr) => {{
+        const CODE: &[u8] = include_bytes!($path);
+        // this is evaluated at compile-time so an invalid file is a compile error
+        const ENTRY_POINT: (usize, usize) = $crate::compile::find_compute_entry_point(CODE);
+        $crate::compile::spirv_from_included(CODE, ENTRY_POINT, $params)
+    }};
+}
+
+const SPIRV_MAGIC: u32 = 0x07230203;
+const SPIRV_OP_ENTRY_POINT: u32 = 15;
+const SPIRV_EXECUTION_MODEL_GL_COMPUTE: u32 = 5;
+
+// reads the word at the given index of SPIR-V in the endianness of the target
+const fn spirv_word(code: &[u8], i: usize) -> u32 {
+    u32::from_ne_bytes([
+        code[4 * i],
+        code[4 * i + 1],
+        code[4 * i + 2],
+        code[4 * i + 3],
+    ])
+}
+
+// finds the name of the first compute entry point in SPIR-V, returning the index of its first word and its length in bytes
+// this is used by include_spirv! at compile-time so it panics (which is then a compile error) if the SPIR-V is invalid
+#[doc(hidden)]
+pub const fn find_compute_entry_point(code: &[u8]) -> (usize, usize) {
+    if code.len() % 4 != 0 || code.len() < 20 {
+        panic!("the included file is not SPIR-V since it isn't made of a 5-word header and instructions");
+    }
+    let magic = spirv_word(code, 0);
+    if magic == SPIRV_MAGIC.swap_bytes() {
+        panic!("the included SPIR-V is not in the endianness of the target");
+    }
+    if magic != SPIRV_MAGIC {
+        panic!(
+            "the included file is not SPIR-V since it doesn't start with the SPIR-V magic number"
+        );
+    }
+
+    // skip the 5-word header and then walk through instructions
+    let num_words = code.len() / 4;
+    let mut i = 5;
+    while i < num_words {
+        let word_count = (spirv_word(code, i) >> 16) as usize;
+        let opcode = spirv_word(code, i) & 0xffff;
+        if word_count == 0 || i + word_count > num_words {
+            panic!("the included SPIR-V has an instruction that runs past the end of the file");
+        }
+        if opcode == SPIRV_OP_ENTRY_POINT
+            && word_count >= 4
+            && spirv_word(code, i + 1) == SPIRV_EXECUTION_MODEL_GL_COMPUTE
+        {
+            // the name is a nul-terminated string packed into words starting with the lowest byte of each word
+            let mut len = 0;
+            while len < (word_count - 3) * 4
+                && (spirv_word(code, i + 3 + len / 4) >> (8 * (len % 4))) & 0xff != 0
+            {
+                len += 1;
+            }
+            return (i + 3, len);
+        }
+        i += word_count;
+    }
+    panic!("the included SPIR-V has no compute entry point");
+}
+
+// builds the Spirv that include_spirv! evaluates to from SPIR-V that has already been checked at compile-time
+#[doc(hidden)]
+pub fn spirv_from_included(
+    code: &[u8],
+    entry_point: (usize, usize),
+    params: DeviceFnMutParams,
+) -> Spirv<Vec<u32>> {
+    let code: Vec<u32> = code
+        .chunks_exact(4)
+        .map(|word| u32::from_ne_bytes([word[0], word[1], word[2], word[3]]))
+        .collect();
+    let (start, len) = entry_point;
+    let name: Vec<u8> = (0..len)
+        .map(|i| (code[start + i / 4] >> (8 * (i % 4))) as u8)
+        .collect();
+    Spirv {
+        params,
+        name: String::from_utf8_lossy(&name).into_owned(),
+        code,
+    }
+}
+
 /// Compiles the given source to `SpirvOrFinished`
 ///
 /// There are 4 things this function is generic over.
@@ -211,6 +742,120 @@ where
     }
 }
 
+/// Compiles and finishes all the given kernels at once, spreading the work over multiple threads
+///
+/// This is meant to be called at startup so that the first launch of each kernel doesn't have to wait for compilation (an interactive application
+/// would otherwise stutter the first time it uses each kernel). Compiling to SPIR-V (e.g. - with shaderc) happens in parallel. Creating each pipeline
+/// happens on the same threads but only 1 at a time since that needs the device. The finished kernels are put in the cache `C` and returned in the
+/// same order as the given sources. So a later [`compile`](fn.compile.html) of the same source is just a cache lookup.
+///
+/// Note that [`GlobalCache`](../cache/struct.GlobalCache.html) only holds 32 kernels by default. If you precompile more, you should
+/// [`reserve`](../cache/struct.GlobalCache.html#method.reserve) space for them first.
+/// ```
+/// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # futures::executor::block_on(assert_device_pool_initialized());
+/// let kernel = |op: &str| {
+///     GlslKernel::new()
+///         .param_mut::<[f32], _>("float[] data")
+///         .with_kernel_code(format!("data[gl_GlobalInvocationID.x] {}= 2.0;", op))
+/// };
+/// let kernels = precompile::<GlslKernel, GlslKernelCompile, Vec<u32>, GlobalCache>(vec![
+///     kernel("+"),
+///     kernel("*"),
+/// ])?;
+///
+/// // later on, compiling either kernel again is just a cache lookup
+/// let mut data: DeviceBox<[f32]> = vec![1.0; 1024].as_device_boxed_mut()?;
+/// let times_two = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(kernel("*"))?.finish()?;
+/// unsafe {
+///     spawn(1024).launch(call!(kernels[0].clone(), &mut data))?;
+///     spawn(1024).launch(call!(times_two, &mut data))?;
+/// }
+/// assert_eq!(futures::executor::block_on(data.get())?, vec![6.0; 1024].into_boxed_slice());
+/// # Ok(())
+/// # }
+/// ```
+pub fn precompile<I, U, P, C>(srcs: Vec<I>) -> Result<Vec<Arc<DeviceFnMut>>, CompileOrNoDeviceError>
+where
+    I: Hash + Send + 'static,
+    U: CompileToSpirv<I, P> + 'static,
+    P: BorrowMut<[u32]> + 'static,
+    C: Cache + 'static,
+{
+    let num_srcs = srcs.len();
+    let num_workers = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(num_srcs);
+
+    // each worker takes the next source that hasn't been taken yet until there are none left
+    let srcs = Arc::new(Mutex::new(srcs.into_iter().enumerate()));
+    let workers = (0..num_workers)
+        .map(|_| {
+            let srcs = srcs.clone();
+            std::thread::spawn(move || {
+                let mut finished = vec![];
+                loop {
+                    let next = srcs.lock().unwrap().next();
+                    match next {
+                        Some((i, src)) => finished.push((
+                            i,
+                            compile::<I, U, P, C>(src)
+                                .map_err(CompileOrNoDeviceError::Compile)?
+                                .finish()?,
+                        )),
+                        None => return Ok(finished),
+                    }
+                }
+            })
+        })
+        .collect::<Vec<
+            std::thread::JoinHandle<Result<Vec<(usize, Arc<DeviceFnMut>)>, CompileOrNoDeviceError>>,
+        >>();
+
+    // put the finished kernels back in the order of their sources
+    let mut finished = vec![None; num_srcs];
+    for worker in workers {
+        let worker_finished = worker
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))?;
+        for (i, device_fn_mut) in worker_finished {
+            finished[i] = Some(device_fn_mut);
+        }
+    }
+    Ok(finished.into_iter().map(Option::unwrap).collect())
+}
+
+/// Formats source code with a line number next to each line
+///
+/// This is what a [`CompileError::Source`](../error/enum.CompileError.html#variant.Source) uses to print the source code that failed to compile
+/// so that the line numbers in the compiler's log can be matched up with the code.
+/// ```
+/// # use emu_core::compile::*;
+/// assert_eq!(with_line_numbers("void main() {\n}\n"), "1 | void main() {\n2 | }\n");
+/// ```
+pub fn with_line_numbers(code: &str) -> String {
+    crate::error::with_line_numbers(code)
+}
+
+// the environment variable that, when set to a directory, makes us write all generated source code to that directory
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+const DUMP_KERNELS_VAR: &str = "EMU_DUMP_KERNELS";
+
+// writes the given generated source code to the directory in EMU_DUMP_KERNELS, if it is set
+// each file is named by a hash of its code so the same kernel is only written once
+// this is just for debugging so we don't let failing to write get in the way of compiling
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+pub(crate) fn dump_source(code: &str, extension: &str) {
+    if let Some(dir) = std::env::var_os(DUMP_KERNELS_VAR) {
+        let mut hasher = DefaultHasher::new();
+        code.hash(&mut hasher);
+        let path =
+            std::path::Path::new(&dir).join(format!("{:016x}.{}", hasher.finish(), extension));
+        let _ = std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(path, code));
+    }
+}
+
 /// Either a finished `DeviceFnMut` or compiled SPIR-V
 ///
 /// You can either call `finish` on this to get your final compiled `DeviceFnMut` or you can inspect/mutate the inner SPIR-V before finishing.
@@ -275,6 +920,37 @@ impl<P: BorrowMut<[u32]>, C: Cache> SpirvOrFinished<P, C> {
         }
     }
 
+    /// Passes the SPIR-V stored here through the given optimizer
+    ///
+    /// This is a hook for running an optimizer (like [spirv-opt](https://github.com/KhronosGroup/SPIRV-Tools) or a [naga](https://github.com/gfx-rs/naga)-based legalization)
+    /// between compiling to SPIR-V and finishing. Unlike [`get_code_mut`](#method.get_code_mut), the optimizer may return SPIR-V of a different length.
+    /// If this is already finished (because the source was in the cache), the optimizer isn't run since the cached `DeviceFnMut` was already optimized
+    /// when it was first finished.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let spirv_or_finished = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+    ///     GlslKernel::new()
+    ///         .param_mut::<[f32], _>("float[] data")
+    ///         .with_kernel_code("data[gl_GlobalInvocationID.x] = data[gl_GlobalInvocationID.x] * 2.0;"),
+    /// )?
+    /// .optimize_with(|spirv| {
+    ///     // a real optimizer would transform the SPIR-V here
+    ///     Ok(spirv.to_vec())
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn optimize_with<F>(mut self, optimizer: F) -> Result<Self, CompileError>
+    where
+        F: FnOnce(&[u32]) -> Result<P, CompileError>,
+    {
+        if let SpirvOrFinished::SpirvAndHash((spirv, _, _)) = &mut self {
+            spirv.code = optimizer(spirv.code.borrow())?;
+        }
+        Ok(self)
+    }
+
     /// Mutate the entry point name
     pub fn get_name_mut(&mut self) -> Option<&mut String> {
         match self {
@@ -295,21 +971,24 @@ impl<P: BorrowMut<[u32]>, C: Cache> SpirvOrFinished<P, C> {
     pub fn finish(&self) -> Result<Arc<DeviceFnMut>, CompileOrNoDeviceError> {
         match self {
             SpirvOrFinished::SpirvAndHash((spirv, src_hash, _)) => {
+                let device = take().map_err(|_| CompileOrNoDeviceError::NoDevice)?;
+                let device = device.lock().unwrap();
+                if requires_f64(spirv.code.borrow()) && !device.supports_f64() {
+                    return Err(CompileOrNoDeviceError::UnsupportedF64);
+                }
+
                 // compile SPIR-V to machine code (DeviceFnMut)
                 // then put it in the cache and return it
                 C::insert(
                     *src_hash,
                     Arc::new(
-                        take()
-                            .map_err(|_| CompileOrNoDeviceError::NoDevice)?
-                            .lock()
-                            .unwrap()
+                        device
                             .compile::<_, &[u32]>(
                                 spirv.params.clone(),
                                 spirv.name.clone(),
                                 spirv.code.borrow(),
                             )
-                            .map_err(|_| CompileOrNoDeviceError::Compile)?,
+                            .map_err(CompileOrNoDeviceError::Compile)?,
                     ),
                 );
                 Ok(C::get(*src_hash))
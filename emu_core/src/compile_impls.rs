@@ -24,6 +24,21 @@ impl<P: Hash + BorrowMut<[u32]>> CompileToSpirv<Spirv<P>, P> for SpirvCompile {
     }
 }
 
+impl DynCompileToSpirv for SpirvCompile {
+    fn compile_to_spirv(
+        &self,
+        src: &[u8],
+        params: DeviceFnMutParams,
+        entry_point: &str,
+    ) -> Result<Spirv<Vec<u32>>, CompileError> {
+        Ok(Spirv {
+            params,
+            name: String::from(entry_point),
+            code: convert_to_spirv(std::io::Cursor::new(src)).map_err(CompileError::Io)?,
+        })
+    }
+}
+
 //
 // Glsl
 //
@@ -75,14 +90,16 @@ impl<P: Hash + BorrowMut<[u32]>> CompileToSpirv<Spirv<P>, P> for SpirvCompile {
 /// # }
 /// ```
 #[derive(Hash)]
-#[cfg(feature = "glsl-compile")]
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
 pub struct Glsl {
     name: String,
     params_builder: ParamsBuilder,
     code: String,
+    optimization: Optimization,
+    deterministic_floats: bool,
 }
 
-#[cfg(feature = "glsl-compile")]
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
 impl Glsl {
     /// Creates a new GLSL builder
     pub fn new() -> Self {
@@ -90,6 +107,8 @@ impl Glsl {
             name: String::from("main"),
             params_builder: ParamsBuilder::new(),
             code: String::from("#version 450\nvoid main() {}"),
+            optimization: Optimization::None,
+            deterministic_floats: false,
         }
     }
 
@@ -118,35 +137,214 @@ impl Glsl {
         self.code = code.into();
         self
     }
+
+    /// Sets how much the GLSL should be optimized when compiled to SPIR-V (see [`Optimization`](enum.Optimization.html))
+    pub fn set_optimization(mut self, optimization: Optimization) -> Self {
+        self.optimization = optimization;
+        self
+    }
+
+    /// Sets whether floating-point results should be reproducible bit for bit (see [`Spirv::with_deterministic_floats`](../compile/struct.Spirv.html#method.with_deterministic_floats))
+    pub fn set_deterministic_floats(mut self, deterministic_floats: bool) -> Self {
+        self.deterministic_floats = deterministic_floats;
+        self
+    }
+}
+
+/// How much `shaderc` should optimize the SPIR-V it compiles GLSL to
+///
+/// `shaderc` runs [spirv-opt](https://github.com/KhronosGroup/SPIRV-Tools) with the preset for the chosen level. By default, SPIR-V isn't optimized
+/// at all and is left for the driver to optimize. Some drivers don't do much with unoptimized SPIR-V though so for complex kernels,
+/// optimizing for performance can make a big difference. With the `glsl-compile-naga` feature (and not `glsl-compile`), this is ignored.
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+#[derive(Hash, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Optimization {
+    /// Don't optimize
+    None,
+    /// Optimize to make the SPIR-V smaller
+    Size,
+    /// Optimize to make the kernel faster
+    Performance,
 }
 
-/// A `shaderc`-based compiler for [`Glsl`](struct.Glsl.html) to SPIR-V
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+impl Default for Optimization {
+    fn default() -> Self {
+        Optimization::None
+    }
+}
+
+// compiles the given GLSL compute kernel to SPIR-V
+//
+// this uses shaderc if the glsl-compile feature is enabled and naga if only the glsl-compile-naga feature is
+// sections are the first line and name of each fragment the source code was assembled from, in order (see source_error)
 #[cfg(feature = "glsl-compile")]
+fn compile_glsl(
+    code: &str,
+    entry_point: &str,
+    optimization: Optimization,
+    sections: &[(usize, &str)],
+) -> Result<Vec<u32>, CompileError> {
+    let mut compiler = shaderc::Compiler::new().ok_or(CompileError::Failed)?;
+    let mut options = shaderc::CompileOptions::new().ok_or(CompileError::Failed)?;
+    options.set_optimization_level(match optimization {
+        Optimization::None => shaderc::OptimizationLevel::Zero,
+        Optimization::Size => shaderc::OptimizationLevel::Size,
+        Optimization::Performance => shaderc::OptimizationLevel::Performance,
+    });
+    // subgroup operations need SPIR-V 1.3, which is what Vulkan 1.1 takes
+    if code.contains("GL_KHR_shader_subgroup") {
+        options.set_target_env(
+            shaderc::TargetEnv::Vulkan,
+            shaderc::EnvVersion::Vulkan1_1 as u32,
+        );
+    }
+    let binary_result = compiler
+        .compile_into_spirv(
+            code,
+            shaderc::ShaderKind::Compute,
+            SOURCE_NAME,
+            entry_point,
+            Some(&options),
+        )
+        .map_err(|error| source_error(code, &error.to_string(), sections))?;
+
+    // yes, copying the binary over into a vec is expensive
+    // but it's necessary so that we can allow users to mutate binary later on
+    // and the copying of the binary is dwarfed by many other operations of this library
+    // also, we cache anyway
+    Ok(binary_result.as_binary().to_vec())
+}
+
+#[cfg(all(feature = "glsl-compile-naga", not(feature = "glsl-compile")))]
+fn compile_glsl(
+    code: &str,
+    entry_point: &str,
+    _optimization: Optimization, // naga doesn't optimize
+    sections: &[(usize, &str)],
+) -> Result<Vec<u32>, CompileError> {
+    let module = naga::front::glsl::parse_str(
+        code,
+        entry_point,
+        naga::ShaderStage::Compute,
+        Default::default(),
+    )
+    .map_err(|error| source_error(code, &error.to_string(), sections))?;
+
+    // naga declares every capability we allow so we only allow doubles if the kernel asks for them
+    let mut capabilities = naga::FastHashSet::default();
+    capabilities.insert(naga::back::spv::Capability::Shader);
+    if code.contains("GL_ARB_gpu_shader_fp64") {
+        capabilities.insert(naga::back::spv::Capability::Float64);
+    }
+    naga::back::spv::write_vec(&module, naga::back::spv::WriterFlags::NONE, capabilities)
+        .map_err(|error| source_error(code, &error.to_string(), sections))
+}
+
+// the name we give the compiler for the source code, which shaderc uses like a file name in its log
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+const SOURCE_NAME: &str = "a compute kernel";
+
+// the number of the line that code appended to the given code would start on
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+fn next_line(code: &str) -> usize {
+    code.matches('\n').count() + 1
+}
+
+// turns a compiler's log into a CompileError carrying the source code and log
+//
+// sections are the first line and name of each fragment the source code was assembled from, in order
+// each location in the log is followed by where it is in the fragment it came from
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+fn source_error(code: &str, log: &str, sections: &[(usize, &str)]) -> CompileError {
+    let log = log
+        .lines()
+        .map(|line| {
+            // locations look like "a compute kernel:12: error: ..."
+            let fragment_location = line
+                .strip_prefix(SOURCE_NAME)
+                .and_then(|rest| rest.strip_prefix(':'))
+                .and_then(|rest| rest.split(':').next())
+                .and_then(|line_num| line_num.trim().parse::<usize>().ok())
+                .and_then(|line_num| {
+                    sections
+                        .iter()
+                        .rev()
+                        .find(|(start, _)| *start <= line_num)
+                        .map(|(start, name)| (line_num - start + 1, name))
+                });
+            match fragment_location {
+                Some((fragment_line_num, name)) => {
+                    format!("{} (line {} of {})\n", line, fragment_line_num, name)
+                }
+                None => format!("{}\n", line),
+            }
+        })
+        .collect();
+    CompileError::Source {
+        code: code.to_string(),
+        log,
+    }
+}
+
+/// A `shaderc`-based (or `naga`-based, with just the `glsl-compile-naga` feature) compiler for [`Glsl`](struct.Glsl.html) to SPIR-V
+///
+/// If the GLSL can't be compiled, a [`CompileError::Source`](../error/enum.CompileError.html#variant.Source) with the code and `shaderc`'s log is returned.
+/// A `CompileError::Failed` is returned if `shaderc` itself couldn't be started. Either way, invalid GLSL never panics.
+/// ```
+/// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+/// let result = compile::<Glsl, GlslCompile, _, GlobalCache>(
+///     Glsl::new().set_code_with_glsl("#version 450\nvoid main() { float x = 1.0 }"),
+/// );
+/// match result {
+///     Err(CompileError::Source { code, log }) => {
+///         assert!(code.contains("float x = 1.0"));
+///         assert!(log.contains("error"));
+///     }
+///     _ => panic!("expected the GLSL to fail to compile"),
+/// }
+/// ```
+///
+/// And if the `EMU_DUMP_KERNELS` environment variable is set to a directory, all compiled GLSL is written to a file there.
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
 pub struct GlslCompile;
 
-#[cfg(feature = "glsl-compile")]
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
 impl CompileToSpirv<Glsl, Vec<u32>> for GlslCompile {
     fn compile_to_spirv(src: Glsl) -> Result<Spirv<Vec<u32>>, CompileError> {
-        // (6) compile to SPIR-V
-        let mut compiler = shaderc::Compiler::new().unwrap();
-        let binary_result = compiler
-            .compile_into_spirv(
-                &src.code,
-                shaderc::ShaderKind::Compute,
-                "a compute kernel",
-                &src.name,
-                None,
-            )
-            .unwrap();
+        dump_source(&src.code, "comp");
 
-        // yes, copying the binary over into a vec is expensive
-        // but it's necessary so that we can allow users to mutate binary later on
-        // and the copying of the binary is dwarfed by many other operations of this library
-        // also, we cache anyway
-        Ok(Spirv {
+        // (6) compile to SPIR-V
+        let code = compile_glsl(&src.code, &src.name, src.optimization, &[])?;
+        let spirv = Spirv {
             params: src.params_builder.build(),
             name: src.name,
-            code: binary_result.as_binary().to_vec(),
+            code,
+        };
+
+        Ok(if src.deterministic_floats {
+            spirv.with_deterministic_floats()
+        } else {
+            spirv
+        })
+    }
+}
+
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+impl DynCompileToSpirv for GlslCompile {
+    fn compile_to_spirv(
+        &self,
+        src: &[u8],
+        params: DeviceFnMutParams,
+        entry_point: &str,
+    ) -> Result<Spirv<Vec<u32>>, CompileError> {
+        let code = std::str::from_utf8(src).map_err(CompileError::Utf8)?;
+        dump_source(code, "comp");
+
+        Ok(Spirv {
+            params,
+            name: String::from(entry_point),
+            code: compile_glsl(code, entry_point, Optimization::None, &[])?,
         })
     }
 }
@@ -178,7 +376,7 @@ impl CompileToSpirv<Glsl, Vec<u32>> for GlslCompile {
 /// # Ok(())
 /// # }
 /// ```
-#[cfg(feature = "glsl-compile")]
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
 #[derive(Hash)]
 pub struct GlslKernel {
     code: String,
@@ -188,12 +386,24 @@ pub struct GlslKernel {
     structs: Vec<String>,
     consts: Vec<(String, String)>,
     shared: Vec<String>,
+    tiles: Vec<(String, u32)>, // the name and number of elements of each tile declared with tile_load
+    generated_functions: Vec<String>, // functions generated by helpers like tile_load and param_jagged
     local_size: Vec<u32>,
+    grid_size: bool,
+    grid_stride: bool,
+    elements_per_thread: u32,
+    unroll: u32,
+    debug: bool,
+    f64: bool,
+    subgroups: bool,
+    block_adds: Vec<&'static str>, // the GLSL type of each block_add function declared with with_block_add
+    optimization: Optimization,
+    deterministic_floats: bool,
     helper_code: String,
     kernel_code: String,
 }
 
-#[cfg(feature = "glsl-compile")]
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
 impl GlslKernel {
     /// Initializes the builder
     pub fn new() -> Self {
@@ -205,7 +415,19 @@ impl GlslKernel {
             structs: vec![],
             consts: vec![],
             shared: vec![],
+            tiles: vec![],
+            generated_functions: vec![],
             local_size: vec![],
+            grid_size: false,
+            grid_stride: false,
+            elements_per_thread: 1,
+            unroll: 1,
+            debug: false,
+            f64: false,
+            subgroups: false,
+            block_adds: vec![],
+            optimization: Optimization::None,
+            deterministic_floats: false,
             helper_code: String::new(),
             kernel_code: String::new(),
         }
@@ -249,7 +471,8 @@ impl GlslKernel {
 
     /// Appends a GLSL structure definition for the type which this function is generic over
     ///
-    /// This can be used for any type that implements [`GlslStruct`](../compile/trait.GlslStruct.html).
+    /// This can be used for any type that implements [`GlslStruct`](../compile/trait.GlslStruct.html). Appending the same structure more than once has no effect.
+    /// You might prefer [`param_structs`](#method.param_structs) which does this for you.
     /// ```
     /// use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
     ///
@@ -303,7 +526,11 @@ impl GlslKernel {
     /// }
     /// ```
     pub fn with_struct<T: GlslStruct>(mut self) -> Self {
-        self.structs.push(T::as_glsl());
+        // a structure can only be defined once
+        let struct_def = T::as_glsl();
+        if !self.structs.contains(&struct_def) {
+            self.structs.push(struct_def);
+        }
         self
     }
 
@@ -376,7 +603,137 @@ impl GlslKernel {
         self
     }
 
+    /// Declares a shared tile and a `load_<tile>(uint start)` function that cooperatively loads it from the given buffer parameter
+    ///
+    /// The tile is declared like `"float tile[256]"` and the buffer must be an array parameter (like `"float[] data"`) of the same element type.
+    /// Calling `load_tile(start)` from every thread of a thread block loads `data[start..start + 256]` into `tile`. Consecutive threads load consecutive
+    /// elements so that reads from the buffer are coalesced and elements past the end of the buffer are loaded as zero. The function has a barrier before
+    /// loading (so no thread is still reading the previous tile) and a barrier after loading (so every thread sees the whole tile). This means it must be
+    /// called by all threads of a thread block together, not from inside branches that only some of them take.
+    ///
+    /// See [`for_each_tile`](#method.for_each_tile) for generating the loop that loads tile after tile and does something with each element.
+    pub fn tile_load(mut self, tile: impl Into<String>, source: impl Into<String>) -> Self {
+        let tile = tile.into();
+        let source = source.into();
+        let (declaration, size) = tile
+            .trim()
+            .strip_suffix(']')
+            .and_then(|tile| tile.split_once('['))
+            .expect("expected a tile to be declared like \"float tile[256]\"");
+        let (ty, name) = declaration
+            .trim()
+            .rsplit_once(char::is_whitespace)
+            .expect("expected a tile to be declared like \"float tile[256]\"");
+        let (ty, name) = (ty.trim(), name.trim());
+        let size = size
+            .trim()
+            .parse::<u32>()
+            .expect("expected the size of a tile to be a number");
+
+        self.generated_functions.push(format!(
+            r#"
+void load_{name}(uint start) {{
+    barrier();
+    uint num_threads = gl_WorkGroupSize.x * gl_WorkGroupSize.y * gl_WorkGroupSize.z;
+    for (uint i = gl_LocalInvocationIndex; i < {size}u; i += num_threads) {{
+        {name}[i] = start + i < uint({source}.length()) ? {source}[start + i] : {ty}(0);
+    }}
+    memoryBarrierShared();
+    barrier();
+}}
+"#,
+            name = name,
+            size = size,
+            source = source,
+            ty = ty
+        ));
+        self.tiles.push((String::from(name), size));
+        self.share(tile)
+    }
+
+    /// Generates a loop that loads the given tile (declared with [`tile_load`](#method.tile_load)) over and over to go through `len` elements
+    ///
+    /// For each tile, the given body is run once for each element of the tile, with `tile_start` being the index in the buffer of the first element of the
+    /// tile and `tile_index` being the index in the tile of the current element. This returns the generated GLSL, which you can then put anywhere in your
+    /// kernel code. `len` is a GLSL expression and must be the same for all threads of a thread block since each iteration loads a tile with barriers.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let x: DeviceBox<[f32]> = vec![1.0; 1000].as_device_boxed()?;
+    /// let mut y: DeviceBox<[f32]> = vec![0.0; 1024].as_device_boxed_mut()?;
+    ///
+    /// // each thread sums up all of x, reading it from shared memory 256 elements at a time
+    /// let kernel = GlslKernel::new()
+    ///     .spawn(256)
+    ///     .param::<[f32], _>("float[] x")
+    ///     .param_mut::<[f32], _>("float[] y")
+    ///     .tile_load("float tile[256]", "x");
+    /// let sum_tiles = kernel.for_each_tile("tile", "x.length()", "sum += tile[tile_index];");
+    /// let kernel = kernel.with_kernel_code(format!(
+    ///     "float sum = 0.0;\n{}\ny[gl_GlobalInvocationID.x] = sum;",
+    ///     sum_tiles
+    /// ));
+    /// let c = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(kernel)?.finish()?;
+    /// unsafe { spawn(1024 / 256).launch(call!(c, &x, &mut y))?; }
+    /// assert_eq!(futures::executor::block_on(y.get())?, vec![1000.0; 1024].into_boxed_slice());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn for_each_tile(&self, tile: &str, len: &str, body: &str) -> String {
+        let size = self
+            .tiles
+            .iter()
+            .find(|(name, _)| name == tile)
+            .map(|(_, size)| *size)
+            .expect("expected the tile to be declared with `tile_load` first");
+        format!(
+            r#"
+for (uint tile_start = 0u; tile_start < ({len}); tile_start += {size}u) {{
+    load_{tile}(tile_start);
+    for (uint tile_index = 0u; tile_index < min({size}u, ({len}) - tile_start); tile_index++) {{
+        {body}
+    }}
+}}
+"#,
+            tile = tile,
+            size = size,
+            len = len,
+            body = body
+        )
+    }
+
     /// Generates code for a buffer through which constant data can be passed into the kernel
+    ///
+    /// The buffer is declared `readonly` and bound as a read-only storage buffer. So a kernel that writes to it fails to compile and drivers
+    /// are free to optimize reads from it.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+    ///     GlslKernel::new()
+    ///         .param::<[f32], _>("float[] x")
+    ///         .param_mut::<[f32], _>("float[] y")
+    ///         .param::<f32, _>("float a")
+    ///         .with_kernel_code("y[gl_GlobalInvocationID.x] += a * x[gl_GlobalInvocationID.x];"),
+    /// )?
+    /// .finish()?;
+    /// let x: DeviceBox<[f32]> = vec![1.0; 1024].as_device_boxed()?;
+    /// let mut y: DeviceBox<[f32]> = vec![1.0; 1024].as_device_boxed_mut()?;
+    /// unsafe { spawn(1024).launch(call!(kernel, &x, &mut y, &DeviceBox::new(2.0f32)?))?; }
+    /// assert_eq!(futures::executor::block_on(y.get())?, vec![3.0; 1024].into_boxed_slice());
+    ///
+    /// // writing to a constant parameter is an error
+    /// let result = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+    ///     GlslKernel::new()
+    ///         .param::<[f32], _>("float[] x")
+    ///         .with_kernel_code("x[gl_GlobalInvocationID.x] = 0.0;"),
+    /// );
+    /// assert!(result.is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
     pub fn param<T: ?Sized, I: Into<String>>(mut self, param: I) -> Self {
         self.params_builder = self.params_builder.param::<T>(Mutability::Const);
         self.params.push(param.into());
@@ -392,6 +749,580 @@ impl GlslKernel {
         self
     }
 
+    /// Generates code for a buffer of GLSL vectors through which constant data can be passed into the kernel
+    ///
+    /// The given name is used to declare an array of the vector type corresponding to `T` (see [`GlslVector`](../compile/trait.GlslVector.html)).
+    /// This is useful for kernels where each thread processes several elements at once.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let mut data: DeviceBox<[[f32; 4]]> = vec![[1.0; 4]; 512].as_device_boxed_mut()?;
+    /// let offsets: DeviceBox<[[f32; 4]]> = vec![[0.0, 1.0, 2.0, 3.0]; 512].as_device_boxed()?;
+    ///
+    /// // each thread processes 4 floats at once
+    /// let kernel: GlslKernel = GlslKernel::new()
+    ///     .param_vec_mut::<[f32; 4], _>("data")
+    ///     .param_vec::<[f32; 4], _>("offsets")
+    ///     .with_kernel_code("data[gl_GlobalInvocationID.x] += offsets[gl_GlobalInvocationID.x];");
+    /// let c = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(kernel)?.finish()?;
+    /// unsafe { spawn(512).launch(call!(c, &mut data, &offsets))?; }
+    /// assert_eq!(futures::executor::block_on(data.get())?, vec![[1.0, 2.0, 3.0, 4.0]; 512].into_boxed_slice());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn param_vec<T: GlslVector, I: Into<String>>(self, name: I) -> Self {
+        let param = format!("{}[] {}", T::glsl_type(), name.into());
+        self.param::<[T], _>(param)
+    }
+
+    /// Generates code for a buffer of GLSL vectors through which mutable data can be passed into the kernel
+    ///
+    /// See [`param_vec`](#method.param_vec) for more details.
+    pub fn param_vec_mut<T: GlslVector, I: Into<String>>(self, name: I) -> Self {
+        let param = format!("{}[] {}", T::glsl_type(), name.into());
+        self.param_mut::<[T], _>(param)
+    }
+
+    /// Generates code for a pair of buffers through which constant jagged data (see [`flatten_jagged`](../boxed/fn.flatten_jagged.html)) can be passed into the kernel
+    ///
+    /// The jagged data is declared like `"float rows"`. This declares a `float[] rows_values` parameter and a `uint[] rows_offsets` parameter, so you
+    /// pass in the values and then the offsets. It also generates a `uint rows_len(uint row)` function for the length of a row and a
+    /// `float rows_get(uint row, uint i)` function for the `i`th element of a row.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let (values, offsets) = flatten_jagged(&[vec![1.0, 2.0], vec![], vec![3.0, 4.0, 5.0]])?;
+    /// let mut sums: DeviceBox<[f32]> = vec![0.0; 3].as_device_boxed_mut()?;
+    ///
+    /// let kernel = GlslKernel::new()
+    ///     .param_jagged::<f32, _>("float rows")
+    ///     .param_mut::<[f32], _>("float[] sums")
+    ///     .with_kernel_code(r#"
+    /// uint row = gl_GlobalInvocationID.x;
+    /// float sum = 0.0;
+    /// for (uint i = 0; i < rows_len(row); i++) {
+    ///     sum += rows_get(row, i);
+    /// }
+    /// sums[row] = sum;
+    /// "#);
+    /// let c = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(kernel)?.finish()?;
+    /// unsafe { spawn(3).launch(call!(c, &values, &offsets, &mut sums))?; }
+    /// assert_eq!(futures::executor::block_on(sums.get())?, vec![3.0, 0.0, 12.0].into_boxed_slice());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn param_jagged<T, I: Into<String>>(self, jagged: I) -> Self {
+        self.param_jagged_as::<T>(jagged.into(), Mutability::Const)
+    }
+
+    /// Generates code for a pair of buffers through which mutable jagged data (see [`flatten_jagged_mut`](../boxed/fn.flatten_jagged_mut.html)) can be passed into the kernel
+    ///
+    /// This is like [`param_jagged`](#method.param_jagged) except that the values are mutable and there is also a generated
+    /// `void rows_set(uint row, uint i, float value)` function for setting the `i`th element of a row. The offsets are still constant.
+    pub fn param_jagged_mut<T, I: Into<String>>(self, jagged: I) -> Self {
+        self.param_jagged_as::<T>(jagged.into(), Mutability::Mut)
+    }
+
+    fn param_jagged_as<T>(mut self, jagged: String, mutability: Mutability) -> Self {
+        let (ty, name) = jagged
+            .trim()
+            .rsplit_once(char::is_whitespace)
+            .expect("expected jagged data to be declared like \"float rows\"");
+        let (ty, name) = (ty.trim(), name.trim());
+
+        let mut functions = format!(
+            r#"
+uint {name}_len(uint row) {{
+    return {name}_offsets[row + 1] - {name}_offsets[row];
+}}
+
+{ty} {name}_get(uint row, uint i) {{
+    return {name}_values[{name}_offsets[row] + i];
+}}
+"#,
+            name = name,
+            ty = ty
+        );
+        if mutability == Mutability::Mut {
+            functions += &format!(
+                r#"
+void {name}_set(uint row, uint i, {ty} value) {{
+    {name}_values[{name}_offsets[row] + i] = value;
+}}
+"#,
+                name = name,
+                ty = ty
+            );
+        }
+        self.generated_functions.push(functions);
+
+        let values = format!("{}[] {}_values", ty, name);
+        let offsets = format!("uint[] {}_offsets", name);
+        match mutability {
+            Mutability::Mut => self.param_mut::<[T], _>(values),
+            Mutability::Const => self.param::<[T], _>(values),
+        }
+        .param::<[u32], _>(offsets)
+    }
+
+    /// Generates code for a [`WorkQueue`](../work_queue/struct.WorkQueue.html) that the kernel can pop items off of and push items onto
+    ///
+    /// The queue is declared like `"uint nodes"` with the GLSL type of its items and its name. This generates a `bool nodes_pop(out uint item)`
+    /// function that pops the next item of the current round (returning false if there are none left or termination was signaled), a
+    /// `bool nodes_push(uint item)` function that pushes an item for the next round (returning false if the queue is full), and a
+    /// `void nodes_terminate()` function that signals termination. The queue takes up 2 parameters but the `WorkQueue` is passed to `call!` as a
+    /// single argument. See [`work_queue`](../work_queue/index.html) for an example.
+    pub fn param_work_queue<T, I: Into<String>>(mut self, queue: I) -> Self {
+        use crate::work_queue::*;
+
+        let queue = queue.into();
+        let (ty, name) = queue
+            .trim()
+            .rsplit_once(char::is_whitespace)
+            .expect("expected a work queue to be declared like \"uint nodes\"");
+        let (ty, name) = (ty.trim(), name.trim());
+
+        self.generated_functions.push(format!(
+            r#"
+bool {name}_pop(out {ty} item) {{
+    if ({name}_header[{terminated}] != 0u) {{
+        return false;
+    }}
+    uint i = atomicAdd({name}_header[{head}], 1u);
+    if (i >= {name}_header[{round_end}]) {{
+        return false;
+    }}
+    item = {name}_items[i % {name}_header[{capacity}]];
+    return true;
+}}
+
+bool {name}_push({ty} item) {{
+    uint tail = {name}_header[{tail}];
+    while (tail - {name}_header[{round_start}] < {name}_header[{capacity}]) {{
+        uint prev = atomicCompSwap({name}_header[{tail}], tail, tail + 1u);
+        if (prev == tail) {{
+            {name}_items[tail % {name}_header[{capacity}]] = item;
+            return true;
+        }}
+        tail = prev;
+    }}
+    atomicAdd({name}_header[{dropped}], 1u);
+    return false;
+}}
+
+void {name}_terminate() {{
+    atomicExchange({name}_header[{terminated}], 1u);
+}}
+"#,
+            name = name,
+            ty = ty,
+            head = HEAD,
+            round_start = ROUND_START,
+            round_end = ROUND_END,
+            tail = TAIL,
+            capacity = CAPACITY,
+            terminated = TERMINATED,
+            dropped = DROPPED
+        ));
+
+        let items = format!("{}[] {}_items", ty, name);
+        let header = format!("uint[] {}_header", name);
+        self.param_mut::<[T], _>(items)
+            .param_mut::<[u32], _>(header)
+    }
+
+    /// Generates code for a buffer of structures through which constant data can be passed into the kernel
+    ///
+    /// This defines the structure with [`with_struct`](#method.with_struct) and then declares an array of it with the given name. So the GLSL
+    /// definition of the structure and the type of the parameter are always in sync with the Rust structure.
+    /// ```
+    /// use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    ///
+    /// #[repr(C)]
+    /// #[derive(AsBytes, FromBytes, Copy, Clone, Default, Debug, GlslStruct, PartialEq)]
+    /// struct Circle {
+    ///     pos: [f32; 2],
+    ///     radius: f32,
+    /// }
+    ///
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     futures::executor::block_on(assert_device_pool_initialized());
+    ///     let circles: DeviceBox<[Circle]> = vec![Circle { pos: [0.0; 2], radius: 2.0 }; 256].as_device_boxed()?;
+    ///     let mut areas: DeviceBox<[f32]> = vec![0.0; 256].as_device_boxed_mut()?;
+    ///
+    ///     let kernel: GlslKernel = GlslKernel::new()
+    ///         .param_structs::<Circle, _>("circles")
+    ///         .param_mut::<[f32], _>("float[] areas")
+    ///         .with_kernel_code("areas[gl_GlobalInvocationID.x] = 3.0 * pow(circles[gl_GlobalInvocationID.x].radius, 2);");
+    ///     let c = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(kernel)?.finish()?;
+    ///     unsafe { spawn(256).launch(call!(c, &circles, &mut areas))?; }
+    ///     assert_eq!(futures::executor::block_on(areas.get())?, vec![12.0; 256].into_boxed_slice());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn param_structs<T: GlslStruct, I: Into<String>>(self, name: I) -> Self {
+        let param = format!("{}[] {}", T::glsl_name(), name.into());
+        self.with_struct::<T>().param::<[T], _>(param)
+    }
+
+    /// Generates code for a buffer of structures through which mutable data can be passed into the kernel
+    ///
+    /// See [`param_structs`](#method.param_structs) for more details.
+    pub fn param_structs_mut<T: GlslStruct, I: Into<String>>(self, name: I) -> Self {
+        let param = format!("{}[] {}", T::glsl_name(), name.into());
+        self.with_struct::<T>().param_mut::<[T], _>(param)
+    }
+
+    /// Generates code for a structure of arrays (see [`to_soa`](../boxed/fn.to_soa.html)) through which constant data can be passed into the kernel
+    ///
+    /// Each field gets its own buffer named after the given name and the field (e.g. - `particles_mass`) so that threads can read the fields they
+    /// need directly. The structure is also defined and a `Particle particles_get(uint i)` function is generated for reading a whole structure.
+    /// The buffers take up 1 parameter for each field but the `DeviceSoa` is passed to `call!` as a single argument.
+    /// ```
+    /// use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    ///
+    /// #[repr(C)]
+    /// #[derive(AsBytes, FromBytes, Copy, Clone, Default, Debug, GlslStruct, PartialEq)]
+    /// struct Particle {
+    ///     pos: [f32; 2],
+    ///     mass: f32,
+    /// }
+    ///
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     futures::executor::block_on(assert_device_pool_initialized());
+    ///     let particles = to_soa(&vec![Particle { pos: [1.0, 2.0], mass: 3.0 }; 256])?;
+    ///     let mut momenta: DeviceBox<[f32]> = vec![0.0; 256].as_device_boxed_mut()?;
+    ///
+    ///     let kernel = GlslKernel::new()
+    ///         .param_soa::<Particle, _>("particles")
+    ///         .param_mut::<[f32], _>("float[] momenta")
+    ///         .with_kernel_code(r#"
+    /// uint i = gl_GlobalInvocationID.x;
+    /// Particle p = particles_get(i);
+    /// momenta[i] = particles_mass[i] * (p.pos.x + p.pos.y);
+    /// "#);
+    ///     let c = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(kernel)?.finish()?;
+    ///     unsafe { spawn(256).launch(call!(c, &particles, &mut momenta))?; }
+    ///     assert_eq!(futures::executor::block_on(momenta.get())?, vec![9.0; 256].into_boxed_slice());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn param_soa<T: GlslStruct, I: Into<String>>(self, name: I) -> Self {
+        self.param_soa_as::<T>(name.into(), Mutability::Const)
+    }
+
+    /// Generates code for a structure of arrays (see [`to_soa_mut`](../boxed/fn.to_soa_mut.html)) through which mutable data can be passed into the kernel
+    ///
+    /// This is like [`param_soa`](#method.param_soa) except that the buffers are mutable and there is also a generated
+    /// `void particles_set(uint i, Particle value)` function for writing a whole structure.
+    pub fn param_soa_mut<T: GlslStruct, I: Into<String>>(self, name: I) -> Self {
+        self.param_soa_as::<T>(name.into(), Mutability::Mut)
+    }
+
+    fn param_soa_as<T: GlslStruct>(mut self, name: String, mutability: Mutability) -> Self {
+        let fields = T::glsl_fields();
+        assert!(
+            !fields.is_empty(),
+            "expected `GlslStruct::glsl_fields` to describe the fields of the structure"
+        );
+        let ty = T::glsl_name();
+
+        let mut functions = format!(
+            "\n{ty} {name}_get(uint i) {{\n    {ty} value;\n",
+            ty = ty,
+            name = name
+        );
+        for field in &fields {
+            functions += &format!(
+                "    value.{field} = {name}_{field}[i];\n",
+                name = name,
+                field = field.name
+            );
+        }
+        functions += "    return value;\n}\n";
+        if mutability == Mutability::Mut {
+            functions += &format!(
+                "\nvoid {name}_set(uint i, {ty} value) {{\n",
+                ty = ty,
+                name = name
+            );
+            for field in &fields {
+                functions += &format!(
+                    "    {name}_{field}[i] = value.{field};\n",
+                    name = name,
+                    field = field.name
+                );
+            }
+            functions += "}\n";
+        }
+        self.generated_functions.push(functions);
+
+        self = self.with_struct::<T>();
+        for field in &fields {
+            let param = format!("{}[] {}_{}", field.glsl_type, name, field.name);
+            self = match mutability {
+                Mutability::Mut => self.param_mut::<[u8], _>(param),
+                Mutability::Const => self.param::<[u8], _>(param),
+            };
+        }
+        self
+    }
+
+    /// Declares a `uvec3 grid_size` holding the global size of the [`Grid`](../device/struct.Grid.html) that the kernel is launched over
+    ///
+    /// This is a uniform that is always the last parameter, no matter when this is called (see [`ParamsBuilder::grid_size`](../device/struct.ParamsBuilder.html#method.grid_size)).
+    /// You don't pass it in yourself. Launching with a `Spawner` made from a `Grid` (or passing a `Grid` to [`Device::call`](../device/struct.Device.html#method.call))
+    /// passes it in for you. This is useful for skipping threads that are out of bounds when the global size isn't a multiple of the size of
+    /// each thread block. See [`Grid`](../device/struct.Grid.html) for an example.
+    pub fn with_grid_size(mut self) -> Self {
+        self.grid_size = true;
+        self
+    }
+
+    /// Has each thread run the kernel code in a loop over the "x" dimension of the [`Grid`](../device/struct.Grid.html) that the kernel is launched over
+    ///
+    /// There is a limit on the number of thread blocks that can be spawned in each dimension (65535 on some backends) so a kernel with a thread
+    /// per element can't go over more than a few million elements at once. With this, the kernel can be launched with a `Spawner` made from a
+    /// `Grid` with [`Grid::with_grid_stride`](../device/struct.Grid.html#method.with_grid_stride). That spawns no more thread blocks than the
+    /// limit and each thread runs the kernel code for every element in the "x" dimension of the grid that is a whole grid of threads apart
+    /// (a grid-stride loop) until it reaches the global size of the grid. Like with [`with_elements_per_thread`](#method.with_elements_per_thread),
+    /// the kernel code gets the element it is run for as `uvec3 emu_global_id`. Threads are never run for elements out of bounds so the kernel
+    /// code doesn't need to check that. This declares the grid size like [`with_grid_size`](#method.with_grid_size) does and makes the number of
+    /// elements per thread have no effect since each thread goes over as many elements as it takes. The loop is still unrolled by the factor
+    /// set with [`with_unroll`](#method.with_unroll).
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+    ///     GlslKernel::new()
+    ///         .param_mut::<[f32], _>("float[] data")
+    ///         .with_grid_stride()
+    ///         .with_kernel_code("data[emu_global_id.x] = data[emu_global_id.x] + 1.0;"),
+    /// )?
+    /// .finish()?;
+    /// // with 1 thread in each thread block, this would be too many thread blocks without a grid-stride loop
+    /// let mut data: DeviceBox<[f32]> = vec![1.0; 1 << 20].as_device_boxed_mut()?;
+    /// let grid = Grid::linear(1 << 20).with_grid_stride();
+    /// assert_eq!(grid.work_space_dim(), (65535, 1, 1));
+    /// unsafe { Spawner::from(grid).launch(call!(kernel, &mut data))?; }
+    /// assert_eq!(futures::executor::block_on(data.get())?, vec![2.0; 1 << 20].into_boxed_slice());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_grid_stride(mut self) -> Self {
+        self.grid_size = true;
+        self.grid_stride = true;
+        self
+    }
+
+    /// Has each thread run the kernel code for the given number of elements instead of just 1
+    ///
+    /// For cheap element-wise kernels over a lot of data, a thread per element can leave a wide GPU spending more time starting threads than
+    /// doing work. With this, each thread runs the kernel code in a loop, once for each of its elements. The elements of a thread are a whole
+    /// grid of threads apart in the "x" dimension (a grid-stride loop) so that neighboring threads still access neighboring elements. The
+    /// kernel code gets the element it is run for as `uvec3 emu_global_id` and should use it instead of `gl_GlobalInvocationID`. It's
+    /// declared even without this, as just `gl_GlobalInvocationID`, so kernel code that uses it works with any number of elements per thread.
+    /// A `return` in the kernel code only skips the rest of the current element. You spawn as many threads as there would be elements
+    /// divided by the number of elements per thread.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+    ///     GlslKernel::new()
+    ///         .spawn(64)
+    ///         .with_elements_per_thread(8)
+    ///         .with_unroll(4)
+    ///         .param_mut::<[f32], _>("float[] data")
+    ///         .with_kernel_code("data[emu_global_id.x] = data[emu_global_id.x] * 2.0;"),
+    /// )?
+    /// .finish()?;
+    /// let mut data: DeviceBox<[f32]> = vec![1.0; 1 << 16].as_device_boxed_mut()?;
+    /// // each of the 64 threads in each thread block goes over 8 elements
+    /// unsafe { spawn((1 << 16) / 64 / 8).launch(call!(kernel, &mut data))?; }
+    /// assert_eq!(futures::executor::block_on(data.get())?, vec![2.0; 1 << 16].into_boxed_slice());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_elements_per_thread(mut self, elements_per_thread: u32) -> Self {
+        self.elements_per_thread = elements_per_thread.max(1);
+        self
+    }
+
+    /// Unrolls the loop over the elements of each thread (see [`with_elements_per_thread`](#method.with_elements_per_thread)) by the given factor
+    ///
+    /// The kernel code is run for this many elements, one after the other, in each iteration of the loop. This gives the driver straight-line
+    /// code to schedule even if it doesn't unroll loops itself. A factor more than the number of elements per thread unrolls the whole loop.
+    pub fn with_unroll(mut self, factor: u32) -> Self {
+        self.unroll = factor.max(1);
+        self
+    }
+
+    /// Declares a buffer for debug records so that `emu_assert(cond)` and `emu_printf(value)` can be called in the kernel code and helper code
+    ///
+    /// The buffer is a parameter after all the others (but before the grid size, see [`with_grid_size`](#method.with_grid_size)) no matter when
+    /// this is called. You pass in a [`DebugBuffer`](../debug/struct.DebugBuffer.html) for it and read the records after launching.
+    /// `emu_assert` records the line and thread whenever its condition is false and `emu_printf` records the line, the thread, and a `float`,
+    /// `int`, `uint`, or `bool` value. Since every call of them writes to the same buffer, they are meant for debugging and should be removed
+    /// (along with the call to this) afterwards. See [`debug`](../debug/index.html) for an example.
+    pub fn with_debug(mut self) -> Self {
+        self.debug = true;
+        self
+    }
+
+    /// Enables double precision so that `double` and `dvec*` types can be used in the kernel
+    ///
+    /// This requires the `GL_ARB_gpu_shader_fp64` extension and a device that supports 64-bit floats (see [`Device::supports_f64`](../device/struct.Device.html#method.supports_f64)).
+    /// If the device doesn't support them, [`finish`](../compile/enum.SpirvOrFinished.html#method.finish) returns `CompileOrNoDeviceError::UnsupportedF64`.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let kernel: GlslKernel = GlslKernel::new()
+    ///     .with_f64()
+    ///     .param_mut::<[f64], _>("double[] data")
+    ///     .with_kernel_code("data[gl_GlobalInvocationID.x] = data[gl_GlobalInvocationID.x] * 2.0lf;");
+    /// match compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(kernel)?.finish() {
+    ///     Ok(finished) => {
+    ///         let mut data: DeviceBox<[f64]> = vec![1.5f64; 64].as_device_boxed_mut()?;
+    ///         unsafe { spawn(64).launch(call!(finished, &mut data))?; }
+    ///         assert_eq!(futures::executor::block_on(data.get())?, vec![3.0f64; 64].into_boxed_slice());
+    ///     }
+    ///     // the selected device has no support for doubles
+    ///     Err(CompileOrNoDeviceError::UnsupportedF64) => {}
+    ///     Err(e) => return Err(e.into()),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_f64(mut self) -> Self {
+        self.f64 = true;
+        self
+    }
+
+    /// Enables subgroup operations (like `subgroupAdd` and `subgroupElect`) so that threads of a subgroup (a wave or warp) can work together
+    /// without going through shared memory
+    ///
+    /// This requires the `GL_KHR_shader_subgroup_basic` and `GL_KHR_shader_subgroup_arithmetic` extensions, which need the `glsl-compile` feature
+    /// (`naga` can't compile them yet) and a device that supports them. WebGPU can't tell whether a device supports them so use
+    /// [`subgroup_size`](fn.subgroup_size.html) to check before compiling a kernel that uses them.
+    pub fn with_subgroups(mut self) -> Self {
+        self.subgroups = true;
+        self
+    }
+
+    /// Generates a `T block_add(T value)` function (where `T` is the GLSL name of the given [`GlslScalar`](../compile/trait.GlslScalar.html))
+    /// that returns the sum of the values passed in by every thread of a thread block
+    ///
+    /// If subgroups are enabled (see [`with_subgroups`](#method.with_subgroups)), each subgroup adds up its values with `subgroupAdd` and
+    /// then the sums of the subgroups are added up through shared memory. This is usually 2-4x faster than the tree reduction in shared memory that
+    /// is generated otherwise, so the same kernel can be compiled either way depending on what the device supports. Like a barrier, `block_add`
+    /// must be called by every thread of the thread block (so not from inside a branch that only some threads take).
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let data: DeviceBox<[f32]> = vec![1.0; 256 * 64].as_device_boxed()?;
+    /// let mut sums: DeviceBox<[f32]> = vec![0.0; 64].as_device_boxed_mut()?;
+    ///
+    /// let mut kernel = GlslKernel::new()
+    ///     .spawn(256)
+    ///     .param::<[f32], _>("float[] data")
+    ///     .param_mut::<[f32], _>("float[] sums")
+    ///     .with_block_add::<f32>()
+    ///     .with_kernel_code(r#"
+    /// float sum = block_add(data[gl_GlobalInvocationID.x]);
+    /// if (gl_LocalInvocationID.x == 0) {
+    ///     sums[gl_WorkGroupID.x] = sum;
+    /// }
+    /// "#);
+    /// if subgroup_size()?.is_some() {
+    ///     kernel = kernel.with_subgroups();
+    /// }
+    /// let c = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(kernel)?.finish()?;
+    /// unsafe { spawn(64).launch(call!(c, &data, &mut sums))?; }
+    /// assert_eq!(futures::executor::block_on(sums.get())?, vec![256.0; 64].into_boxed_slice());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_block_add<T: GlslScalar>(mut self) -> Self {
+        if T::needs_f64() {
+            self.f64 = true;
+        }
+        if !self.block_adds.contains(&T::glsl_type()) {
+            self.block_adds.push(T::glsl_type());
+        }
+        self
+    }
+
+    /// Optimizes the compiled SPIR-V with the given level of [`Optimization`](enum.Optimization.html)
+    ///
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let mut data: DeviceBox<[f32]> = vec![1.0; 2048].as_device_boxed_mut()?;
+    /// let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+    ///     GlslKernel::new()
+    ///         .param_mut::<[f32], _>("float[] data")
+    ///         .with_optimization(Optimization::Performance)
+    ///         .with_kernel_code("data[gl_GlobalInvocationID.x] = data[gl_GlobalInvocationID.x] * 2.0;"),
+    /// )?
+    /// .finish()?;
+    /// unsafe { spawn(2048).launch(call!(kernel, &mut data))?; }
+    /// assert_eq!(futures::executor::block_on(data.get())?, vec![2.0; 2048].into_boxed_slice());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_optimization(mut self, optimization: Optimization) -> Self {
+        self.optimization = optimization;
+        self
+    }
+
+    /// Compiles the kernel so that floating-point results are reproducible bit for bit
+    ///
+    /// Every floating-point operation is rounded by itself, exactly as written, instead of letting the driver reassociate or fuse operations.
+    /// See [`Spirv::with_deterministic_floats`](../compile/struct.Spirv.html#method.with_deterministic_floats) for more details.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+    ///     GlslKernel::new()
+    ///         .param::<[f32], _>("float[] x")
+    ///         .param_mut::<[f32], _>("float[] sums")
+    ///         .with_deterministic_floats()
+    ///         .with_kernel_code(r#"
+    /// float sum = 0.0;
+    /// for (uint i = 0; i < 256; i++) {
+    ///     sum += x[i] * x[(i + gl_GlobalInvocationID.x) % 256];
+    /// }
+    /// sums[gl_GlobalInvocationID.x] = sum;
+    /// "#),
+    /// )?
+    /// .finish()?;
+    ///
+    /// // the results of 2 runs are identical
+    /// let x: DeviceBox<[f32]> = (0..256).map(|i| (i as f32).sin()).collect::<Vec<f32>>().as_device_boxed()?;
+    /// let mut runs = vec![];
+    /// for _ in 0..2 {
+    ///     let mut sums: DeviceBox<[f32]> = vec![0.0; 256].as_device_boxed_mut()?;
+    ///     unsafe { spawn(256).launch(call!(kernel.clone(), &x, &mut sums))?; }
+    ///     runs.push(futures::executor::block_on(sums.get())?);
+    /// }
+    /// let bits = |run: &[f32]| run.iter().map(|sum| sum.to_bits()).collect::<Vec<u32>>();
+    /// assert_eq!(bits(&runs[0]), bits(&runs[1]));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_deterministic_floats(mut self) -> Self {
+        self.deterministic_floats = true;
+        self
+    }
+
     /// Adds the given helper code
     ///
     /// This helper code may include additional type or function definitions.
@@ -433,15 +1364,186 @@ impl GlslKernel {
     }
 }
 
-/// Another `shaderc`-based compiler for compiling [`GlslKernel`](struct.GlslKernel.html)
-#[cfg(feature = "glsl-compile")]
+// GLSL for a function that adds up the given type of value over a thread block of the given size
+//
+// with subgroups, each subgroup adds up its values and then every thread adds up the sums of the subgroups
+// without them, the values are added up in a tree in shared memory
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+fn block_add_function(ty: &str, block_size: u32, subgroups: bool) -> String {
+    if subgroups {
+        format!(
+            r#"
+shared {ty} emu_block_add_{ty}[{block_size}];
+
+{ty} block_add({ty} value) {{
+    {ty} subgroup_sum = subgroupAdd(value);
+    if (subgroupElect()) {{
+        emu_block_add_{ty}[gl_SubgroupID] = subgroup_sum;
+    }}
+    memoryBarrierShared();
+    barrier();
+    {ty} sum = {ty}(0);
+    for (uint i = 0; i < gl_NumSubgroups; i++) {{
+        sum += emu_block_add_{ty}[i];
+    }}
+    barrier();
+    return sum;
+}}
+"#,
+            ty = ty,
+            block_size = block_size
+        )
+    } else {
+        format!(
+            r#"
+shared {ty} emu_block_add_{ty}[{block_size}];
+
+{ty} block_add({ty} value) {{
+    emu_block_add_{ty}[gl_LocalInvocationIndex] = value;
+    memoryBarrierShared();
+    barrier();
+    for (uint stride = 1; stride < {block_size}; stride *= 2) {{
+        if (gl_LocalInvocationIndex % (2 * stride) == 0 && gl_LocalInvocationIndex + stride < {block_size}) {{
+            emu_block_add_{ty}[gl_LocalInvocationIndex] += emu_block_add_{ty}[gl_LocalInvocationIndex + stride];
+        }}
+        memoryBarrierShared();
+        barrier();
+    }}
+    {ty} sum = emu_block_add_{ty}[0];
+    barrier();
+    return sum;
+}}
+"#,
+            ty = ty,
+            block_size = block_size
+        )
+    }
+}
+
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+lazy_static! {
+    // the subgroup size of each device in the pool that has been checked, by its index in the pool
+    static ref SUBGROUP_SIZES: std::sync::Mutex<std::collections::HashMap<usize, Option<u32>>> =
+        std::sync::Mutex::new(std::collections::HashMap::new());
+}
+
+/// Returns the number of threads in each subgroup of the device currently selected from the pool or `None` if it doesn't support subgroup operations
+///
+/// WebGPU doesn't say whether a device supports subgroup operations, so the first time this is called for a device, it compiles and launches
+/// a tiny kernel that uses them (see [`GlslKernel::with_subgroups`](struct.GlslKernel.html#method.with_subgroups)) and downloads the subgroup size
+/// it sees. If the kernel can't be compiled (like with just the `glsl-compile-naga` feature) or launched, subgroups aren't supported. The result is
+/// remembered so later calls are cheap.
+/// ```
+/// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # futures::executor::block_on(assert_device_pool_initialized());
+/// match subgroup_size()? {
+///     Some(size) => println!("subgroups have {} threads", size),
+///     None => println!("subgroups aren't supported"),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+pub fn subgroup_size() -> Result<Option<u32>, NoDeviceError> {
+    let index = crate::pool::info()?.index;
+    if let Some(size) = SUBGROUP_SIZES.lock().unwrap().get(&index) {
+        return Ok(*size);
+    }
+
+    // any failure along the way means the device (or the compiler) doesn't support subgroups
+    let probe = || -> Option<u32> {
+        let kernel =
+            crate::compile::compile::<GlslKernel, GlslKernelCompile, _, crate::cache::GlobalCache>(
+                GlslKernel::new()
+                    .with_subgroups()
+                    .spawn(64)
+                    .param_mut::<[u32], _>("uint[] size")
+                    .with_kernel_code(
+                        "if (gl_LocalInvocationID.x == 0) { size[0] = gl_SubgroupSize; }",
+                    ),
+            )
+            .ok()?
+            .finish()
+            .ok()?;
+        let mut size: DeviceBox<[u32]> =
+            crate::boxed::AsDeviceBoxed::as_device_boxed_mut(&vec![0u32]).ok()?;
+        unsafe {
+            crate::spawn::spawn(1)
+                .launch(crate::call!(kernel, &mut size))
+                .ok()?;
+        }
+        futures::executor::block_on(size.get())
+            .ok()?
+            .first()
+            .copied()
+            .filter(|size| *size > 0)
+    };
+    let size = probe();
+    SUBGROUP_SIZES.lock().unwrap().insert(index, size);
+    Ok(size)
+}
+
+/// Another `shaderc`-based (or `naga`-based, with just the `glsl-compile-naga` feature) compiler for compiling [`GlslKernel`](struct.GlslKernel.html)
+///
+/// A `GlslKernel` is assembled from many fragments of code so the line numbers `shaderc` reports aren't the line numbers of your code.
+/// If compiling fails, the returned [`CompileError::Source`](../error/enum.CompileError.html#variant.Source) has the whole assembled code and each location
+/// in its log says which fragment (like "kernel code" or "helper code") it is in and at what line. Printing the error shows the assembled code with line numbers.
+/// ```
+/// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+/// let result = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+///     GlslKernel::new()
+///         .param_mut::<[f32], _>("float[] data")
+///         .with_kernel_code("data[gl_GlobalInvocationID.x] = undeclared;"),
+/// );
+/// match result {
+///     Err(CompileError::Source { log, .. }) => assert!(log.contains("line 1 of kernel code")),
+///     _ => panic!("expected the kernel to fail to compile"),
+/// }
+///
+/// // syntax errors are reported the same way
+/// let result = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+///     GlslKernel::new()
+///         .param_mut::<[f32], _>("float[] data")
+///         .with_helper_code("float twice(float x) {\n    return 2.0 * x\n}\n")
+///         .with_kernel_code("data[gl_GlobalInvocationID.x] = twice(data[gl_GlobalInvocationID.x]);"),
+/// );
+/// match result {
+///     Err(CompileError::Source { log, .. }) => assert!(log.contains("of helper code")),
+///     _ => panic!("expected the kernel to fail to compile"),
+/// }
+/// ```
+///
+/// Invalid code never panics. Compiling straight to SPIR-V (which doesn't need a device) returns the error too.
+/// ```
+/// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+/// let result = GlslKernelCompile::compile_to_spirv(
+///     GlslKernel::new()
+///         .param_mut::<[f32], _>("float[] data")
+///         .with_kernel_code("data[gl_GlobalInvocationID.x] = = 1.0;"),
+/// );
+/// assert!(matches!(result, Err(CompileError::Source { .. })));
+/// ```
+///
+/// If the `EMU_DUMP_KERNELS` environment variable is set to a directory, the assembled code of every compiled `GlslKernel` is written to a file there,
+/// named by a hash of the code.
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
 pub struct GlslKernelCompile;
 
-#[cfg(feature = "glsl-compile")]
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
 impl CompileToSpirv<GlslKernel, Vec<u32>> for GlslKernelCompile {
     fn compile_to_spirv(mut src: GlslKernel) -> Result<Spirv<Vec<u32>>, CompileError> {
         let kernel_name = String::from("main");
 
+        // (0) extensions
+        if src.f64 {
+            src.code += "#extension GL_ARB_gpu_shader_fp64 : require\n";
+        }
+        if src.subgroups {
+            src.code += "#extension GL_KHR_shader_subgroup_basic : require\n";
+            src.code += "#extension GL_KHR_shader_subgroup_arithmetic : require\n";
+        }
+
         // (1) local size
         if src.local_size.len() == 0 {
             src.local_size = vec![1];
@@ -471,23 +1573,61 @@ impl CompileToSpirv<GlslKernel, Vec<u32>> for GlslKernelCompile {
         }
         src.code += ") in;\n";
 
+        // we keep track of where each fragment of code starts so that errors can point back to them
+        let mut sections = vec![];
+
         // (2) structs
+        if src.structs.len() > 0 {
+            sections.push((next_line(&src.code), "struct definitions"));
+        }
         for struct_def in src.structs {
             src.code += &struct_def;
         }
 
         // (3) buffer for each parameter
-        for (i, param) in src.params.iter().enumerate() {
+        if src.params.len() > 0 {
+            sections.push((next_line(&src.code), "parameters"));
+        }
+        for (i, (param, mutability)) in src
+            .params
+            .iter()
+            .zip(src.params_mutability.iter())
+            .enumerate()
+        {
             src.code += "\nlayout(set = 0, binding = ";
             src.code += &i.to_string();
-            src.code += ") buffer Buffer";
+            // constant parameters are bound as read-only storage buffers so they must be declared readonly
+            src.code += match mutability {
+                Mutability::Const => ") readonly buffer Buffer",
+                Mutability::Mut => ") buffer Buffer",
+            };
             src.code += &i.to_string();
             src.code += " {\n";
             src.code += param;
             src.code += ";\n};\n";
         }
 
+        if src.debug {
+            src.params_builder = src.params_builder.param::<[u32]>(Mutability::Mut);
+            src.code += "\nlayout(set = 0, binding = ";
+            src.code += &src.params.len().to_string();
+            src.code += ") buffer EmuDebug {\nuint[] emu_debug;\n};\n";
+            src.generated_functions.push(debug_functions());
+            src.kernel_code = with_debug_locations(&src.kernel_code, 0);
+            src.helper_code = with_debug_locations(&src.helper_code, 1);
+        }
+
+        if src.grid_size {
+            src.params_builder = src.params_builder.grid_size();
+            src.code += "\nlayout(set = 0, binding = ";
+            src.code += &(src.params.len() + src.debug as usize).to_string();
+            src.code += ") uniform GridSize {\nuvec3 grid_size;\n};\n";
+        }
+
         // (4) consts
+        if src.consts.len() > 0 {
+            sections.push((next_line(&src.code), "constants"));
+        }
         for (left_hand, right_hand) in src.consts {
             src.code += &left_hand;
             src.code += " = ";
@@ -496,40 +1636,183 @@ impl CompileToSpirv<GlslKernel, Vec<u32>> for GlslKernelCompile {
         }
 
         // (5) shared
+        if src.shared.len() > 0 {
+            sections.push((next_line(&src.code), "shared variables"));
+        }
         for shared in src.shared {
             src.code += "shared ";
             src.code += &shared;
             src.code += ";\n";
         }
 
-        // (6) helper code
+        // (6) functions generated by helpers
+        // block_add is generated here since it depends on the size of thread blocks and on whether subgroups are enabled
+        let block_size = src.local_size.iter().product::<u32>();
+        for ty in &src.block_adds {
+            src.generated_functions
+                .push(block_add_function(ty, block_size, src.subgroups));
+        }
+        if src.generated_functions.len() > 0 {
+            sections.push((next_line(&src.code), "generated functions"));
+        }
+        for generated_function in src.generated_functions {
+            src.code += &generated_function;
+        }
+
+        // (7) helper code
+        if src.helper_code.len() > 0 {
+            sections.push((next_line(&src.code), "helper code"));
+        }
         src.code += &src.helper_code;
 
-        // (7) kernel code
-        src.code += "\nvoid main() {\n";
-        src.code += &src.kernel_code;
-        src.code += "}\n";
-
-        // (8) compile to SPIR-V
-        let mut compiler = shaderc::Compiler::new().unwrap();
-        let binary_result = compiler
-            .compile_into_spirv(
-                &src.code,
-                shaderc::ShaderKind::Compute,
-                "a compute kernel",
-                "main",
-                None,
-            )
-            .unwrap();
+        // (8) kernel code
+        // if each thread goes over more than 1 element, the kernel code is a function that main calls for each element in a grid-stride loop
+        if src.grid_stride || src.elements_per_thread > 1 {
+            src.code += "\nvoid emu_kernel(uvec3 emu_global_id) {\n";
+            sections.push((next_line(&src.code), "kernel code"));
+            src.code += &src.kernel_code;
+            src.code += "}\n";
+            src.code += "\nvoid main() {\n";
+            src.code += "uvec3 emu_stride = gl_NumWorkGroups * gl_WorkGroupSize;\n";
+            if src.grid_stride {
+                // the loop goes on until the end of the grid so the last iteration may always have fewer elements left than are unrolled
+                let unroll = src.unroll;
+                src.code += &format!(
+                    "for (uint emu_x = gl_GlobalInvocationID.x; emu_x < grid_size.x; emu_x += {}u * emu_stride.x) {{\n",
+                    unroll
+                );
+                for j in 0..unroll {
+                    if j > 0 {
+                        src.code += &format!("if (emu_x + {}u * emu_stride.x < grid_size.x) ", j);
+                    }
+                    src.code += &format!(
+                        "emu_kernel(uvec3(emu_x + {}u * emu_stride.x, gl_GlobalInvocationID.yz));\n",
+                        j
+                    );
+                }
+            } else {
+                let unroll = src.unroll.min(src.elements_per_thread);
+                src.code += &format!(
+                    "for (uint emu_element = 0u; emu_element < {}u; emu_element += {}u) {{\n",
+                    src.elements_per_thread, unroll
+                );
+                for j in 0..unroll {
+                    // the last iteration may have fewer elements left than are unrolled
+                    if src.elements_per_thread % unroll != 0 {
+                        src.code +=
+                            &format!("if (emu_element + {}u < {}u) ", j, src.elements_per_thread);
+                    }
+                    src.code += &format!(
+                        "emu_kernel(uvec3(gl_GlobalInvocationID.x + (emu_element + {}u) * emu_stride.x, gl_GlobalInvocationID.yz));\n",
+                        j
+                    );
+                }
+            }
+            src.code += "}\n}\n";
+        } else {
+            src.code += "\nvoid main() {\n";
+            src.code += "uvec3 emu_global_id = gl_GlobalInvocationID;\n";
+            sections.push((next_line(&src.code), "kernel code"));
+            src.code += &src.kernel_code;
+            src.code += "}\n";
+        }
 
-        // yes, copying the binary over into a vec is expensive
-        // but it's necessary so that we can allow users to mutate binary later on
-        // and the copying of the binary is dwarfed by many other operations of this library
-        // also, we cache anyway
-        Ok(Spirv {
+        dump_source(&src.code, "comp");
+
+        // (9) compile to SPIR-V
+        let code = compile_glsl(&src.code, "main", src.optimization, &sections)?;
+        let spirv = Spirv {
             params: src.params_builder.build(),
             name: kernel_name,
-            code: binary_result.as_binary().to_vec(),
+            code,
+        };
+
+        Ok(if src.deterministic_floats {
+            spirv.with_deterministic_floats()
+        } else {
+            spirv
         })
     }
 }
+
+// generates the functions that emu_assert and emu_printf are rewritten to call, see the debug module for the layout of the records
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+fn debug_functions() -> String {
+    let mut functions = format!(
+        r#"
+void emu_debug_record(uint kind, uint fragment, uint line, uint value_type, uint value) {{
+    uint i = atomicAdd(emu_debug[0], 1u);
+    if (i < emu_debug[1]) {{
+        uint offset = {header_len}u + {record_len}u * i;
+        emu_debug[offset] = kind;
+        emu_debug[offset + 1u] = fragment;
+        emu_debug[offset + 2u] = line;
+        emu_debug[offset + 3u] = gl_GlobalInvocationID.x;
+        emu_debug[offset + 4u] = gl_GlobalInvocationID.y;
+        emu_debug[offset + 5u] = gl_GlobalInvocationID.z;
+        emu_debug[offset + 6u] = value_type;
+        emu_debug[offset + 7u] = value;
+    }}
+}}
+
+void emu_debug_assert(uint fragment, uint line, bool cond) {{
+    if (!cond) {{
+        emu_debug_record(1u, fragment, line, 0u, 0u);
+    }}
+}}
+"#,
+        header_len = crate::debug::HEADER_LEN,
+        record_len = crate::debug::RECORD_LEN
+    );
+    for (ty, value_type, bits) in &[
+        ("float", 1, "floatBitsToUint(value)"),
+        ("int", 2, "uint(value)"),
+        ("uint", 3, "value"),
+        ("bool", 4, "uint(value)"),
+    ] {
+        functions += &format!(
+            "\nvoid emu_debug_printf(uint fragment, uint line, {ty} value) {{\n    emu_debug_record(2u, fragment, line, {value_type}u, {bits});\n}}\n",
+            ty = ty,
+            value_type = value_type,
+            bits = bits
+        );
+    }
+    functions
+}
+
+// rewrites each call of emu_assert or emu_printf in the given fragment of code to pass in where it is
+// e.g. - `emu_assert(x > 0)` on line 3 of kernel code becomes `emu_debug_assert(0u, 3u, x > 0)`
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+fn with_debug_locations(code: &str, fragment: u32) -> String {
+    code.split('\n')
+        .enumerate()
+        .map(|(i, line)| {
+            let mut line = line.to_string();
+            for name in &["assert", "printf"] {
+                let call = format!("emu_{}(", name);
+                let located_call = format!("emu_debug_{}({}u, {}u, ", name, fragment, i + 1);
+                line = replace_calls(&line, &call, &located_call);
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// replaces each occurrence of the given call that isn't just the end of a longer identifier
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+fn replace_calls(line: &str, call: &str, replacement: &str) -> String {
+    let mut replaced = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find(call) {
+        let in_identifier = rest[..start]
+            .chars()
+            .last()
+            .map_or(false, |c| c.is_alphanumeric() || c == '_');
+        replaced += &rest[..start];
+        replaced += if in_identifier { call } else { replacement };
+        rest = &rest[start + call.len()..];
+    }
+    replaced += rest;
+    replaced
+}
@@ -0,0 +1,256 @@
+//! Assertions and printing from inside kernels
+//!
+//! A [`GlslKernel`](../compile_impls/struct.GlslKernel.html) built with [`with_debug`](../compile_impls/struct.GlslKernel.html#method.with_debug)
+//! can call `emu_assert(cond)` and `emu_printf(value)` in its kernel code and helper code. Each failed assertion and each printed value is written as
+//! a record to a [`DebugBuffer`](struct.DebugBuffer.html) that is passed in as the last argument. After launching, the records are downloaded and
+//! decoded with [`DebugBuffer::read`](struct.DebugBuffer.html#method.read). This module requires the `glsl-compile` feature.
+//! ```
+//! # use {emu_core::prelude::*, emu_core::debug::*, emu_glsl::*, zerocopy::*};
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # futures::executor::block_on(assert_device_pool_initialized());
+//! let mut data: DeviceBox<[f32]> = vec![1.0, 2.0, -3.0, 4.0].as_device_boxed_mut()?;
+//! let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+//!     GlslKernel::new()
+//!         .with_debug()
+//!         .param_mut::<[f32], _>("float[] data")
+//!         .with_kernel_code(r#"
+//! uint i = gl_GlobalInvocationID.x;
+//! emu_assert(data[i] >= 0.0);
+//! if (i == 0) {
+//!     emu_printf(data[i]);
+//! }
+//! data[i] = sqrt(data[i]);
+//! "#),
+//! )?
+//! .finish()?;
+//!
+//! let mut debug = DebugBuffer::new(64)?;
+//! unsafe { spawn(4).launch(call!(kernel, &mut data, &mut debug))?; }
+//! let output = futures::executor::block_on(debug.read())?;
+//! assert_eq!(output.records.len(), 2);
+//! assert!(output.records.contains(&DebugRecord {
+//!     kind: DebugKind::AssertionFailed,
+//!     location: DebugLocation { fragment: "kernel code", line: 3 },
+//!     thread: (2, 0, 0),
+//! }));
+//! assert!(output.records.contains(&DebugRecord {
+//!     kind: DebugKind::Printf(DebugValue::Float(1.0)),
+//!     location: DebugLocation { fragment: "kernel code", line: 5 },
+//!     thread: (0, 0, 0),
+//! }));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+
+use crate::boxed::*;
+use crate::device::*;
+use crate::error::*;
+use crate::pool::*;
+
+// the number of u32s before the first record, the first is the number of records written (or attempted) and the second is the capacity
+pub(crate) const HEADER_LEN: u32 = 2;
+// the number of u32s in each record
+// a record is the kind, the fragment, the line, the global ID of the thread (3 u32s), the type of the value, and the bits of the value
+pub(crate) const RECORD_LEN: u32 = 8;
+
+// the fragments of a GlslKernel that emu_assert and emu_printf can be used in, indexed by the fragment of a record
+pub(crate) const FRAGMENTS: [&str; 2] = ["kernel code", "helper code"];
+
+/// A buffer that kernels built with [`with_debug`](../compile_impls/struct.GlslKernel.html#method.with_debug) write debug records to
+///
+/// This is passed to [`call!`](../macro.call.html) as the last argument (before the grid size, if there is one). It holds up to a fixed number of
+/// records and records past that are dropped (but counted). Records aren't cleared when they are read. So the same buffer can be passed to many
+/// launches and read once at the end. Call [`clear`](#method.clear) to start over.
+pub struct DebugBuffer {
+    data: DeviceBox<[u32]>,
+    capacity: u32,
+}
+
+impl DebugBuffer {
+    /// Creates a buffer that can hold the given number of records on the device currently selected from the pool
+    pub fn new(capacity: u32) -> Result<Self, NoDeviceError> {
+        Ok(Self {
+            data: Self::initial_data(capacity).as_device_boxed_mut()?,
+            capacity,
+        })
+    }
+
+    /// Returns the number of records this can hold
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Throws away all records written so far
+    pub fn clear(&mut self) -> Result<(), NoDeviceError> {
+        take()?
+            .lock()
+            .unwrap()
+            .set_from(&mut self.data, Self::initial_data(self.capacity))
+            .expect("the records of a debug buffer are always the same size");
+        Ok(())
+    }
+
+    /// Downloads and decodes the records written so far
+    ///
+    /// Records from different threads are in the order that the threads happened to write them, which may differ between launches.
+    pub async fn read(&self) -> Result<DebugOutput, GetError> {
+        let data = self.data.get().await?;
+        let written = data[0];
+        let records = data[HEADER_LEN as usize..]
+            .chunks(RECORD_LEN as usize)
+            .take(written.min(self.capacity) as usize)
+            .filter_map(decode_record)
+            .collect();
+        Ok(DebugOutput {
+            records,
+            dropped: written.saturating_sub(self.capacity),
+        })
+    }
+
+    fn initial_data(capacity: u32) -> Vec<u32> {
+        let mut data = vec![0; (HEADER_LEN + RECORD_LEN * capacity) as usize];
+        data[1] = capacity;
+        data
+    }
+}
+
+impl<'a> IntoArg<'a> for &'a DebugBuffer {
+    fn add_to(self, builder: ArgsBuilder<'a>) -> ArgsBuilder<'a> {
+        (&self.data).add_to(builder)
+    }
+}
+
+impl<'a> IntoArg<'a> for &'a mut DebugBuffer {
+    fn add_to(self, builder: ArgsBuilder<'a>) -> ArgsBuilder<'a> {
+        (&mut self.data).add_to(builder)
+    }
+}
+
+/// The records read from a [`DebugBuffer`](struct.DebugBuffer.html)
+#[derive(Clone, Debug, PartialEq)]
+pub struct DebugOutput {
+    /// The records that fit in the buffer
+    pub records: Vec<DebugRecord>,
+    /// The number of records that didn't fit in the buffer
+    pub dropped: u32,
+}
+
+impl DebugOutput {
+    /// Returns the records of failed assertions
+    pub fn failed_assertions(&self) -> impl Iterator<Item = &DebugRecord> {
+        self.records
+            .iter()
+            .filter(|record| record.kind == DebugKind::AssertionFailed)
+    }
+}
+
+impl fmt::Display for DebugOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for record in &self.records {
+            writeln!(f, "{}", record)?;
+        }
+        if self.dropped > 0 {
+            writeln!(
+                f,
+                "{} more records didn't fit in the debug buffer",
+                self.dropped
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A single failed assertion or printed value
+#[derive(Clone, Debug, PartialEq)]
+pub struct DebugRecord {
+    /// What happened
+    pub kind: DebugKind,
+    /// Where in the code of the kernel it happened
+    pub location: DebugLocation,
+    /// The global ID (`gl_GlobalInvocationID`) of the thread it happened in
+    pub thread: (u32, u32, u32),
+}
+
+impl fmt::Display for DebugRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "thread ({}, {}, {}) at {}: ",
+            self.thread.0, self.thread.1, self.thread.2, self.location
+        )?;
+        match &self.kind {
+            DebugKind::AssertionFailed => write!(f, "assertion failed"),
+            DebugKind::Printf(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// The kind of a [`DebugRecord`](struct.DebugRecord.html)
+#[derive(Clone, Debug, PartialEq)]
+pub enum DebugKind {
+    /// `emu_assert` was called with a false condition
+    AssertionFailed,
+    /// `emu_printf` was called with the given value
+    Printf(DebugValue),
+}
+
+/// A value printed with `emu_printf`
+///
+/// `emu_printf` can be called with a `float`, `int`, `uint`, or `bool`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DebugValue {
+    Float(f32),
+    Int(i32),
+    Uint(u32),
+    Bool(bool),
+}
+
+impl fmt::Display for DebugValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DebugValue::Float(value) => write!(f, "{}", value),
+            DebugValue::Int(value) => write!(f, "{}", value),
+            DebugValue::Uint(value) => write!(f, "{}", value),
+            DebugValue::Bool(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// A line in one of the fragments of code of a [`GlslKernel`](../compile_impls/struct.GlslKernel.html)
+///
+/// Lines are numbered from 1 like in the log of a [`CompileError::Source`](../error/enum.CompileError.html#variant.Source).
+#[derive(Clone, Debug, PartialEq)]
+pub struct DebugLocation {
+    /// The fragment, either "kernel code" or "helper code"
+    pub fragment: &'static str,
+    /// The line in the fragment
+    pub line: u32,
+}
+
+impl fmt::Display for DebugLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {} of {}", self.line, self.fragment)
+    }
+}
+
+// decodes a record, returning None if it was corrupted (e.g. - by a kernel writing out of bounds)
+fn decode_record(record: &[u32]) -> Option<DebugRecord> {
+    let kind = match (record[0], record[6]) {
+        (1, _) => DebugKind::AssertionFailed,
+        (2, 1) => DebugKind::Printf(DebugValue::Float(f32::from_bits(record[7]))),
+        (2, 2) => DebugKind::Printf(DebugValue::Int(record[7] as i32)),
+        (2, 3) => DebugKind::Printf(DebugValue::Uint(record[7])),
+        (2, 4) => DebugKind::Printf(DebugValue::Bool(record[7] != 0)),
+        _ => return None,
+    };
+    Some(DebugRecord {
+        kind,
+        location: DebugLocation {
+            fragment: FRAGMENTS.get(record[1] as usize)?,
+            line: record[2],
+        },
+        thread: (record[3], record[4], record[5]),
+    })
+}
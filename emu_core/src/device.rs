@@ -10,16 +10,23 @@ use crate::error::*;
 
 // some std stuff...
 use std::collections::HashMap;
+#[cfg(any(feature = "pool", feature = "record"))]
+use std::convert::TryInto;
 use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::io::{Read, Seek};
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::{
     borrow::{Borrow, Cow},
+    future::Future,
     num::NonZeroU64,
+    pin::Pin,
 };
 
-use futures::TryFutureExt;
+use futures::{lock::Mutex, FutureExt};
 use wgpu::{util::DeviceExt, ComputePassDescriptor};
 // zerocopy is used for serializing and deserializing data to/from devices
 use zerocopy::*;
@@ -29,6 +36,41 @@ use zerocopy::*;
 // derive_more allows us to easily derive interop with wgpu stuff
 use derive_more::{From, Into};
 
+// a future for when a fence submitted with Device::submit_fence completes
+pub(crate) type FenceFuture =
+    Pin<Box<dyn Future<Output = Result<(), wgpu::BufferAsyncError>> + Send>>;
+
+// the most that we upload to a device in order to fill a DeviceBox
+const FILL_CHUNK_SIZE: usize = 1 << 16;
+
+// the first and the longest sleeps between polls of a device while waiting with a timeout
+// each sleep is twice as long as the one before so that short waits return quickly and long waits don't spin
+const MIN_POLL_BACKOFF: Duration = Duration::from_micros(10);
+const MAX_POLL_BACKOFF: Duration = Duration::from_millis(1);
+
+// the most thread blocks that can be spawned in each dimension
+// wgpu doesn't expose this limit yet but every backend supports at least this many
+pub(crate) const MAX_WORKGROUPS_PER_DIMENSION: u32 = 65535;
+// the largest thread block and the most threads in a thread block
+// wgpu doesn't expose these limits yet but every backend supports at least this much
+const MAX_WORKGROUP_SIZE: (u32, u32, u32) = (256, 256, 64);
+const MAX_INVOCATIONS_PER_WORKGROUP: u32 = 256;
+// the largest buffer that can be bound to a kernel
+// wgpu doesn't expose this limit yet but no backend can bind more than this since ranges of bound buffers are 32-bit
+const MAX_STORAGE_BUFFER_BINDING_SIZE: u64 = u32::MAX as u64;
+
+// the size of each slab that small DeviceBoxs are allocated from
+const SLAB_SIZE: u64 = 1 << 22;
+// the smallest and largest blocks that slabs are divided into
+// blocks are at least as large as the alignment of binding offsets so that every block can be bound to a kernel
+const MIN_SLAB_BLOCK_SIZE: u64 = wgpu::BIND_BUFFER_ALIGNMENT;
+const MAX_SLAB_BLOCK_SIZE: u64 = 1 << 20;
+
+// the size of each page that arguments passed by value are uploaded to
+const VALUE_PAGE_SIZE: u64 = 1 << 16;
+// each argument passed by value gets a block of a page that is aligned for binding
+const VALUE_BLOCK_SIZE: u64 = wgpu::BIND_BUFFER_ALIGNMENT;
+
 /// Contains information about a device
 #[derive(From, Into, Clone, PartialEq)]
 pub struct DeviceInfo(pub wgpu::AdapterInfo);
@@ -46,6 +88,36 @@ impl fmt::Debug for DeviceInfo {
     }
 }
 
+impl fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({:?} on {:?}, vendor {}, device {})",
+            self.name(),
+            self.device_type(),
+            self.0.backend,
+            self.vendor_id(),
+            self.device_id()
+        )
+    }
+}
+
+// the backend is serialized by name since wgpu's types aren't serializable
+#[cfg(feature = "serde")]
+impl serde::Serialize for DeviceInfo {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut info = serializer.serialize_struct("DeviceInfo", 5)?;
+        info.serialize_field("name", &self.name())?;
+        info.serialize_field("vendor_id", &self.vendor_id())?;
+        info.serialize_field("device_id", &self.device_id())?;
+        info.serialize_field("device_type", &self.device_type())?;
+        info.serialize_field("backend", &format!("{:?}", self.0.backend))?;
+        info.end()
+    }
+}
+
 impl DeviceInfo {
     /// The name of the device (e.g. - "Intel(R) UHD Graphics 620 (Kabylake GT2)"")
     pub fn name(&self) -> String {
@@ -76,6 +148,7 @@ impl DeviceInfo {
 
 /// Represents a type of device
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum DeviceType {
     Cpu,
     IntegratedGpu,
@@ -84,10 +157,648 @@ pub enum DeviceType {
     Other,
 }
 
+/// Options for creating the WebGPU instance that devices are found with and for choosing among the adapters it finds
+///
+/// These are used by [`Device::all_with_options`](struct.Device.html#method.all_with_options) and by
+/// [`pool_with_options`](../pool/fn.pool_with_options.html) to initialize the pool of devices.
+/// ```
+/// # use emu_core::prelude::*;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// // only use Vulkan, prefer a discrete GPU, and skip software devices
+/// let options = InstanceOptions::new()
+///     .with_backends(wgpu::BackendBit::VULKAN)
+///     .with_power_preference(wgpu::PowerPreference::HighPerformance)
+///     .with_filter(|info| info.device_type() != DeviceType::Cpu);
+/// futures::executor::block_on(assert_device_pool_initialized_with(options));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct InstanceOptions {
+    backends: wgpu::BackendBit,
+    power_preference: Option<wgpu::PowerPreference>,
+    filters: Vec<Arc<dyn Fn(&DeviceInfo) -> bool + Send + Sync>>,
+    validation: bool,
+}
+
+impl InstanceOptions {
+    /// Creates options that find devices with `wgpu::BackendBit::PRIMARY` in the order they are enumerated and validate kernels
+    pub fn new() -> Self {
+        Self {
+            backends: wgpu::BackendBit::PRIMARY,
+            power_preference: None,
+            filters: vec![],
+            validation: true,
+        }
+    }
+
+    /// Sets the backends to create the instance with (e.g. - only `wgpu::BackendBit::VULKAN` to avoid a broken OpenGL driver)
+    pub fn with_backends(mut self, backends: wgpu::BackendBit) -> Self {
+        self.backends = backends;
+        self
+    }
+
+    /// Orders the devices so that the ones that best match the given power preference come first
+    ///
+    /// The first device is the one that is selected by default. `HighPerformance` puts discrete GPUs first and `LowPower` puts integrated
+    /// GPUs first. Either way, software devices come last.
+    pub fn with_power_preference(mut self, power_preference: wgpu::PowerPreference) -> Self {
+        self.power_preference = Some(power_preference);
+        self
+    }
+
+    /// Only uses adapters for which the given function returns `true`
+    ///
+    /// This can be called more than once and an adapter must pass every filter.
+    pub fn with_filter<F: Fn(&DeviceInfo) -> bool + Send + Sync + 'static>(
+        mut self,
+        filter: F,
+    ) -> Self {
+        self.filters.push(Arc::new(filter));
+        self
+    }
+
+    /// Sets whether or not kernels are validated when they are compiled (they are by default)
+    pub fn with_validation(mut self, validation: bool) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    /// Returns the backends the instance is created with
+    pub fn backends(&self) -> wgpu::BackendBit {
+        self.backends
+    }
+
+    /// Checks whether or not an adapter with the given information passes every filter
+    pub fn allows(&self, info: &DeviceInfo) -> bool {
+        self.filters.iter().all(|filter| filter(info))
+    }
+
+    // enumerates the adapters allowed by these options, in the order devices are added to the pool
+    pub(crate) fn adapters(&self) -> Vec<wgpu::Adapter> {
+        let instance = wgpu::Instance::new(self.backends);
+        let mut adapters = instance
+            .enumerate_adapters(self.backends)
+            .filter(|adapter| self.allows(&DeviceInfo(adapter.get_info())))
+            .collect::<Vec<wgpu::Adapter>>();
+        if let Some(power_preference) = self.power_preference {
+            // the sort is stable so adapters of the same type stay in the order they were enumerated
+            adapters.sort_by_key(|adapter| {
+                power_preference_rank(power_preference, &adapter.get_info().device_type)
+            });
+        }
+        adapters
+    }
+}
+
+impl Default for InstanceOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for InstanceOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstanceOptions")
+            .field("backends", &self.backends)
+            .field("power_preference", &self.power_preference)
+            .field("filters", &self.filters.len())
+            .field("validation", &self.validation)
+            .finish()
+    }
+}
+
+// how well an adapter of the given type matches the given power preference, lower is better
+fn power_preference_rank(
+    power_preference: wgpu::PowerPreference,
+    device_type: &wgpu::DeviceType,
+) -> u8 {
+    match (power_preference, device_type) {
+        (wgpu::PowerPreference::HighPerformance, wgpu::DeviceType::DiscreteGpu) => 0,
+        (wgpu::PowerPreference::HighPerformance, wgpu::DeviceType::IntegratedGpu) => 1,
+        (wgpu::PowerPreference::LowPower, wgpu::DeviceType::IntegratedGpu) => 0,
+        (wgpu::PowerPreference::LowPower, wgpu::DeviceType::DiscreteGpu) => 1,
+        (_, wgpu::DeviceType::Cpu) => 3,
+        _ => 2,
+    }
+}
+
+/// The limits on how threads of a kernel can be grouped into thread blocks (workgroups) on a device
+///
+/// See [`Device::workgroup_limits`](struct.Device.html#method.workgroup_limits).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct WorkgroupLimits {
+    /// The largest size of a thread block in each dimension
+    pub max_size: (u32, u32, u32),
+    /// The most threads in a single thread block
+    pub max_invocations: u32,
+    /// The most thread blocks that can be launched in each dimension
+    pub max_count_per_dimension: u32,
+}
+
+// the limits on thread blocks that every device supports
+pub(crate) const GUARANTEED_WORKGROUP_LIMITS: WorkgroupLimits = WorkgroupLimits {
+    max_size: MAX_WORKGROUP_SIZE,
+    max_invocations: MAX_INVOCATIONS_PER_WORKGROUP,
+    max_count_per_dimension: MAX_WORKGROUPS_PER_DIMENSION,
+};
+
+// handles the errors that WebGPU reports for a Device
+//
+// by default, WebGPU panics whenever it finds something wrong (like a kernel that fails validation)
+// this instead captures errors reported while a kernel is being compiled or launched so that they can be returned by compile and call
+// any other error (like one reported while creating a buffer) is passed to the callback registered with on_error or, if there is no callback,
+// panics just like it would by default
+//
+// errors WebGPU reports while submitting work to a queue (or writing to a buffer through a queue) can't be handled and still panic
+#[derive(Clone)]
+pub(crate) struct DeviceErrors {
+    state: Arc<std::sync::Mutex<DeviceErrorsState>>,
+}
+
+type ErrorCallback = Arc<dyn Fn(&wgpu::Error) + Send + Sync>;
+
+struct DeviceErrorsState {
+    // the first error reported in each scope, innermost scope last
+    scopes: Vec<Option<String>>,
+    callback: Option<ErrorCallback>,
+}
+
+impl DeviceErrors {
+    // starts handling the errors of the given WebGPU device, replacing whatever error handler it had before
+    pub(crate) fn new(device: &wgpu::Device) -> Self {
+        let state = Arc::new(std::sync::Mutex::new(DeviceErrorsState {
+            scopes: vec![],
+            callback: None,
+        }));
+        let handler_state = state.clone();
+        device.on_uncaptured_error(move |error| {
+            let mut state = handler_state.lock().unwrap();
+            if let Some(scope) = state.scopes.last_mut() {
+                if scope.is_none() {
+                    *scope = Some(error.to_string());
+                }
+            } else if let Some(callback) = state.callback.clone() {
+                // the callback is called without the lock so that it can use the device
+                drop(state);
+                callback(&error);
+            } else {
+                // without a callback, we keep WebGPU's default behavior of panicking
+                drop(state);
+                panic!("unhandled device error: {}", error);
+            }
+        });
+        Self { state }
+    }
+
+    // runs the given function and returns the first error WebGPU reported while it ran
+    pub(crate) fn scope<T>(&self, f: impl FnOnce() -> T) -> (T, Option<String>) {
+        self.state.lock().unwrap().scopes.push(None);
+        let result = f();
+        let error = self.state.lock().unwrap().scopes.pop().flatten();
+        (result, error)
+    }
+}
+
+/// Uploads to a [`Device`](struct.Device.html) that have been deferred until the next launch or [`flush`](struct.Device.html#method.flush)
+///
+/// See [`Device::defer_uploads`](struct.Device.html#method.defer_uploads).
+#[derive(Default)]
+pub struct DeferredUploads {
+    enabled: bool,
+    len: usize, // the number of uploads written to the queue since the last submission
+}
+
+impl DeferredUploads {
+    /// Returns whether or not uploads are being deferred
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns the number of uploads that are waiting to be submitted
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether or not there are no uploads waiting to be submitted
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+// a callback registered with Device::then along with the fence it waits on
+struct PendingCallback {
+    _fence: wgpu::Buffer, // kept alive until the fence completes
+    done: FenceFuture,
+    callback: Box<dyn FnOnce(Result<(), LaunchError>) + Send>,
+}
+
+/// Host callbacks that are waiting for work submitted to a [`Device`](struct.Device.html) to complete
+///
+/// See [`Device::then`](struct.Device.html#method.then).
+#[derive(Default)]
+pub struct PendingCallbacks {
+    // the futures of fences are only Send so the callbacks are behind a lock to keep Device Sync
+    pending: std::sync::Mutex<Vec<PendingCallback>>,
+}
+
+impl PendingCallbacks {
+    /// Returns the number of callbacks that are waiting for their work to complete
+    pub fn len(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    /// Returns whether or not there are no callbacks waiting
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// `DeviceBox`s on a [`Device`](struct.Device.html) that are watched for changes made by launches
+///
+/// See [`Device::watch`](struct.Device.html#method.watch).
+#[derive(Default)]
+pub struct Watchpoints {
+    // the name, storage buffer, offset, and size of each watched DeviceBox by its id
+    // the buffer is held weakly so that watching a DeviceBox doesn't keep its memory alive after it is dropped
+    watched: HashMap<u64, (String, std::sync::Weak<wgpu::Buffer>, u64, u64)>,
+    log: Vec<WatchEntry>,
+}
+
+impl Watchpoints {
+    /// Returns the number of `DeviceBox`s being watched
+    pub fn len(&self) -> usize {
+        self.watched.len()
+    }
+
+    /// Returns whether or not no `DeviceBox`s are being watched
+    pub fn is_empty(&self) -> bool {
+        self.watched.is_empty()
+    }
+
+    /// Returns an entry for each launch of a kernel that bound a watched `DeviceBox` mutably, oldest first
+    pub fn log(&self) -> &[WatchEntry] {
+        &self.log
+    }
+
+    // returns the log and clears it
+    fn take_log(&mut self) -> Vec<WatchEntry> {
+        std::mem::take(&mut self.log)
+    }
+}
+
+/// A launch of a kernel that bound a watched `DeviceBox` mutably
+///
+/// See [`Device::watch`](struct.Device.html#method.watch).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WatchEntry {
+    /// The name the `DeviceBox` was watched with
+    pub name: String,
+    /// The name of the entry point of the launched kernel, if it is known
+    pub kernel: Option<String>,
+    /// The checksum of the data in the `DeviceBox` before the launch
+    pub before: u64,
+    /// The checksum of the data in the `DeviceBox` after the launch
+    pub after: u64,
+}
+
+impl WatchEntry {
+    /// Returns whether or not the launch changed the data in the `DeviceBox`
+    pub fn changed(&self) -> bool {
+        self.before != self.after
+    }
+}
+
+impl fmt::Display for WatchEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} `{}` ({:016x} -> {:016x})",
+            self.kernel.as_deref().unwrap_or("an unnamed kernel"),
+            if self.changed() {
+                "changed"
+            } else {
+                "didn't change"
+            },
+            self.name,
+            self.before,
+            self.after
+        )
+    }
+}
+
+/// Large buffers on a [`Device`](struct.Device.html) that small `DeviceBox`s are allocated from
+///
+/// See [`Device::allocate_from_slabs`](struct.Device.html#method.allocate_from_slabs).
+#[derive(Default)]
+pub struct SlabAllocator {
+    enabled: bool,
+    // slabs by the size of their blocks
+    slabs: HashMap<u64, Vec<Arc<Slab>>>,
+}
+
+impl SlabAllocator {
+    /// Returns whether or not small `DeviceBox`s are being allocated from slabs
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns the number of slabs that have been allocated
+    pub fn num_slabs(&self) -> usize {
+        self.slabs.values().map(|slabs| slabs.len()).sum()
+    }
+
+    // frees the memory of every slab that no DeviceBox is allocated from
+    fn release_free_slabs(&mut self) {
+        for slabs in self.slabs.values_mut() {
+            // each DeviceBox allocated from a slab holds a reference to it
+            slabs.retain(|slab| Arc::strong_count(slab) > 1);
+        }
+    }
+
+    // allocates a block of the given size (which must be at most MAX_SLAB_BLOCK_SIZE) from a slab, allocating a new slab if they are all full
+    fn allocate(&mut self, device: &wgpu::Device, size: u64) -> SlabAllocation {
+        let block_size = size.next_power_of_two().max(MIN_SLAB_BLOCK_SIZE);
+        let slabs = self.slabs.entry(block_size).or_insert_with(Vec::new);
+        for slab in slabs.iter() {
+            let offset = slab.free.lock().unwrap().pop();
+            if let Some(offset) = offset {
+                return SlabAllocation {
+                    slab: slab.clone(),
+                    offset,
+                };
+            }
+        }
+
+        let slab = Arc::new(Slab {
+            storage_buffer: Arc::new(device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size: SLAB_SIZE,
+                usage: wgpu::BufferUsage::STORAGE
+                    | wgpu::BufferUsage::COPY_DST
+                    | wgpu::BufferUsage::COPY_SRC,
+                mapped_at_creation: false,
+            })),
+            staging: Arc::new(Mutex::new(Staging::new(device, SLAB_SIZE, None))),
+            block_size,
+            // blocks are handed out from the start of the slab
+            free: std::sync::Mutex::new(
+                (1..SLAB_SIZE / block_size)
+                    .rev()
+                    .map(|block| block * block_size)
+                    .collect(),
+            ),
+        });
+        slabs.push(slab.clone());
+        SlabAllocation { slab, offset: 0 }
+    }
+}
+
+// the buffers that arguments passed by value (see IntoArg) are uploaded to
+//
+// every argument uploaded in a frame gets its own block of a page
+// a frame ends once every launch encoded in it has been submitted (or dropped) and the pages are then reused from the start
+// this is safe since uploads through the queue happen after all the work submitted before them
+#[derive(Default)]
+pub(crate) struct ValueBuffers {
+    // launches are encoded with a shared reference to the device so the pages are behind a lock
+    frame: std::sync::Mutex<ValueFrame>,
+    leases: Arc<std::sync::atomic::AtomicUsize>, // the number of encoded launches that haven't been submitted yet
+}
+
+// held by an encoded launch until it is submitted so that the blocks its arguments were uploaded to aren't reused before then
+pub(crate) struct ValueLease(Arc<std::sync::atomic::AtomicUsize>);
+
+impl Drop for ValueLease {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+#[derive(Default)]
+struct ValueFrame {
+    pages: Vec<Arc<wgpu::Buffer>>,
+    next_block: u64, // the number of blocks handed out in this frame
+}
+
+impl ValueBuffers {
+    // starts encoding a launch, starting a new frame if every launch encoded so far has been submitted
+    fn lease(&self) -> ValueLease {
+        let mut frame = self.frame.lock().unwrap();
+        if self.leases.load(std::sync::atomic::Ordering::SeqCst) == 0 {
+            frame.next_block = 0;
+        }
+        self.leases
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        ValueLease(self.leases.clone())
+    }
+
+    // uploads the given bytes to a block that isn't used by anything else in this frame and returns its page and offset
+    fn upload(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+    ) -> (Arc<wgpu::Buffer>, u64) {
+        assert!(
+            bytes.len() as u64 <= VALUE_BLOCK_SIZE,
+            "arguments passed by value can be at most {} bytes",
+            VALUE_BLOCK_SIZE
+        );
+        let mut frame = self.frame.lock().unwrap();
+        let blocks_per_page = VALUE_PAGE_SIZE / VALUE_BLOCK_SIZE;
+        let page_num = (frame.next_block / blocks_per_page) as usize;
+        let offset = (frame.next_block % blocks_per_page) * VALUE_BLOCK_SIZE;
+        frame.next_block += 1;
+        if page_num == frame.pages.len() {
+            frame
+                .pages
+                .push(Arc::new(device.create_buffer(&wgpu::BufferDescriptor {
+                    label: None,
+                    size: VALUE_PAGE_SIZE,
+                    // values are bound like constant DeviceBoxs except for grid sizes, which are uniforms
+                    usage: wgpu::BufferUsage::STORAGE
+                        | wgpu::BufferUsage::UNIFORM
+                        | wgpu::BufferUsage::COPY_DST,
+                    mapped_at_creation: false,
+                })));
+        }
+        let page = frame.pages[page_num].clone();
+        queue.write_buffer(&page, offset, bytes);
+        (page, offset)
+    }
+}
+
+// a storage buffer and a staging buffer of SLAB_SIZE bytes that are divided into blocks of the same size
+struct Slab {
+    storage_buffer: Arc<wgpu::Buffer>,
+    // a buffer can only be mapped once at a time so all the DeviceBoxs in a slab share a lock on its staging buffer
+    staging: Arc<Mutex<Staging>>,
+    block_size: u64,
+    free: std::sync::Mutex<Vec<u64>>, // the offsets of blocks that aren't allocated
+}
+
+// the staging buffer that a DeviceBox is downloaded through
+// it's behind a lock since a buffer can only be mapped once at a time
+pub(crate) struct Staging {
+    buffer: wgpu::Buffer,
+    size: u64,
+    label: Option<String>,
+    // set when mapping the buffer fails or is given up on, which can leave the buffer in a state where it can't be mapped again
+    // a poisoned staging buffer is replaced with a new one before the next download
+    poisoned: bool,
+}
+
+impl Staging {
+    fn new(device: &wgpu::Device, size: u64, label: Option<String>) -> Self {
+        Self {
+            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: label.as_deref(),
+                size,
+                usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            size,
+            label,
+            poisoned: false,
+        }
+    }
+
+    // replaces the buffer with a new one if it is poisoned
+    fn recover(&mut self, device: &wgpu::Device) {
+        if self.poisoned {
+            *self = Self::new(device, self.size, self.label.take());
+        }
+    }
+}
+
+// a block of a slab that a DeviceBox is allocated in, which is freed when this is dropped
+pub(crate) struct SlabAllocation {
+    slab: Arc<Slab>,
+    offset: u64,
+}
+
+impl Drop for SlabAllocation {
+    fn drop(&mut self) {
+        self.slab.free.lock().unwrap().push(self.offset);
+    }
+}
+
+impl fmt::Debug for DeviceErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DeviceErrors").finish()
+    }
+}
+
+/// Options for creating a [`DeviceBox`](struct.DeviceBox.html) with [`Device::create_with_options`](struct.Device.html#method.create_with_options)
+/// or [`Device::create_from_with_options`](struct.Device.html#method.create_from_with_options)
+///
+/// The default options create the same buffers as [`Device::create_from`](struct.Device.html#method.create_from) and the other constructors. Labels
+/// show up in validation errors and in GPU captures (like the ones RenderDoc makes) so that you can tell which buffer is which.
+/// ```
+/// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut device = &mut futures::executor::block_on(Device::all())[0];
+/// let positions: DeviceBox<[f32]> = device.create_from_with_options(
+///     vec![0.0; 1024].as_slice(),
+///     &DeviceBoxOptions::new().with_label("positions").with_mutability(Mutability::Mut),
+/// );
+/// assert_eq!(futures::executor::block_on(device.get(&positions))?, vec![0.0; 1024].into_boxed_slice());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceBoxOptions {
+    usage: wgpu::BufferUsage,
+    mapped_at_creation: bool,
+    label: Option<String>,
+    mutability: Mutability,
+}
+
+impl DeviceBoxOptions {
+    /// Creates options for an unlabeled constant `DeviceBox` that can be passed to kernels, uploaded to, and downloaded from
+    pub fn new() -> Self {
+        Self {
+            usage: wgpu::BufferUsage::STORAGE
+                | wgpu::BufferUsage::COPY_DST
+                | wgpu::BufferUsage::COPY_SRC,
+            mapped_at_creation: true,
+            label: None,
+            mutability: Mutability::Const,
+        }
+    }
+
+    /// Sets the usage of the storage buffer
+    ///
+    /// The usage must include `STORAGE` for the `DeviceBox` to be passed to kernels and `COPY_DST` for it to be uploaded to after it is created.
+    /// Leaving out `COPY_SRC` is an optimization for data that is only ever read by kernels (like weights or lookup tables). Such a `DeviceBox`
+    /// can't be downloaded, so it is created without a staging buffer and only takes up half as much memory.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut device = &mut futures::executor::block_on(Device::all())[0];
+    /// let weights: DeviceBox<[f32]> = device.create_from_with_options(
+    ///     vec![0.5; 1 << 20].as_slice(),
+    ///     &DeviceBoxOptions::new()
+    ///         .with_usage(wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST)
+    ///         .with_label("weights"),
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_usage(mut self, usage: wgpu::BufferUsage) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Sets whether or not the storage buffer is mapped when it is created (it is by default)
+    ///
+    /// This only matters for [`Device::create_from_with_options`](struct.Device.html#method.create_from_with_options). When the storage buffer is
+    /// mapped at creation, the data is written straight into it and it doesn't need the `COPY_DST` usage. Otherwise, the data is uploaded like with
+    /// [`Device::set_from`](struct.Device.html#method.set_from) so it can be deferred (see [`Device::defer_uploads`](struct.Device.html#method.defer_uploads)),
+    /// but then the size of the data must be a multiple of 4 bytes.
+    pub fn with_mapped_at_creation(mut self, mapped_at_creation: bool) -> Self {
+        self.mapped_at_creation = mapped_at_creation;
+        self
+    }
+
+    /// Sets the label of the buffers
+    ///
+    /// The storage buffer gets the given label and the staging buffer gets the label with `" (staging)"` appended.
+    pub fn with_label<S: Into<String>>(mut self, label: S) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Sets whether or not the `DeviceBox` is mutable (it is constant by default)
+    pub fn with_mutability(mut self, mutability: Mutability) -> Self {
+        self.mutability = mutability;
+        self
+    }
+
+    /// Returns the usage of the storage buffer
+    pub fn usage(&self) -> wgpu::BufferUsage {
+        self.usage
+    }
+
+    /// Returns the label of the storage buffer
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+}
+
+impl Default for DeviceBoxOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Represents a single device
 ///
-/// Since its fields are public, you can easily construct and mutate a `Device`'s
-/// WebGPU internals. To get a `Device` from an existing device pool, you will want to use [`take`](../pool/fn.take.html).
+/// You can construct a `Device` from WebGPU internals you already have with [`from_wgpu`](#method.from_wgpu) and get at them again with
+/// [`device`](#method.device), [`queue`](#method.queue), and [`info`](#method.info). To get a `Device` from an existing device pool, you will
+/// want to use [`take`](../pool/fn.take.html).
 ///
 /// One thing to remember is that each `Device` owns its data. So even though the device pool lets you create `DeviceBox`s on different devices,
 /// you cannot use them together in the same kernel.
@@ -100,6 +811,22 @@ pub struct Device {
     ///
     /// This is optional so that you don't _need_ information to construct a `Device` yourself.
     pub info: Option<DeviceInfo>,
+    // the flags used when creating shader modules for kernels compiled on this device
+    pub(crate) shader_flags: wgpu::ShaderFlags,
+    // the handler of errors WebGPU reports for this device
+    pub(crate) errors: DeviceErrors,
+    // the uploads that are waiting to be submitted to this device
+    pub(crate) deferred_uploads: DeferredUploads,
+    // the slabs that small DeviceBoxs on this device are allocated from
+    pub(crate) slabs: SlabAllocator,
+    // the pages that arguments passed by value are uploaded to
+    pub(crate) value_buffers: ValueBuffers,
+    // the host callbacks that are waiting for work submitted to this device to complete
+    pub(crate) callbacks: PendingCallbacks,
+    // the DeviceBoxs on this device that are watched for changes by launches
+    pub(crate) watchpoints: Watchpoints,
+    // the buffers of the fence that waits with a timeout reuse, created on the first wait
+    pub(crate) wait_fence: Option<(wgpu::Buffer, wgpu::Buffer)>,
 }
 
 impl Device {
@@ -116,10 +843,23 @@ impl Device {
     ///
     /// If you are using the default pool, don't forget to call [`assert_device_pool_initialized`](../pool/fn.assert_device_pool_initialized.html) before doing anthing with a device.
     pub async fn all() -> Vec<Self> {
-        let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
-        let adapters = instance.enumerate_adapters(wgpu::BackendBit::PRIMARY);
+        Self::all_with_backends(wgpu::BackendBit::PRIMARY).await
+    }
+
+    /// Gets all detected devices that use one of the given backends
+    ///
+    /// This is just like [`all`](#method.all) except that you can choose the backends (e.g. - only Vulkan) to look for devices with.
+    /// `all` looks for devices with `wgpu::BackendBit::PRIMARY`.
+    pub async fn all_with_backends(backends: wgpu::BackendBit) -> Vec<Self> {
+        Self::all_with_options(&InstanceOptions::new().with_backends(backends)).await
+    }
 
-        futures::future::join_all(adapters.into_iter().map(|adapter| {
+    /// Gets all detected devices that are allowed by the given options
+    ///
+    /// Adapters that are filtered out by the options are skipped before any device is requested from them. So this can be used to avoid adapters
+    /// with broken drivers.
+    pub async fn all_with_options(options: &InstanceOptions) -> Vec<Self> {
+        futures::future::join_all(options.adapters().into_iter().map(|adapter| {
             async move {
                 let info = adapter.get_info().clone();
                 // we then get a device and a queue
@@ -130,12 +870,16 @@ impl Device {
                 // searching for devices does not need to be async
                 // it takes barely any time and should really only be the first thing Emu is used to do
                 // also, it's a one-time thing
+                //
+                // we ask for 64-bit floats whenever the adapter has them so that kernels using doubles can run
+                // and we ask for the adapter's limits since the default limits only allow 4 buffers per kernel
+                let features = adapter.features() & wgpu::Features::SHADER_FLOAT64;
                 let (device, queue) = adapter
                     .request_device(
                         &wgpu::DeviceDescriptor {
                             label: None,
-                            features: wgpu::Features::empty(),
-                            limits: wgpu::Limits::default(),
+                            features,
+                            limits: adapter.limits(),
                         },
                         None,
                     )
@@ -146,18 +890,234 @@ impl Device {
                 // there is no cost to returning device info so we just do it
                 // it might be useful for making an iterator over devices
 
-                println!("{:#?}", device.limits());
-
-                Device {
-                    device: device,
-                    queue: queue,
-                    info: Some(DeviceInfo(info)),
+                let mut device = Device::from_wgpu(device, queue, Some(DeviceInfo(info)));
+                if !options.validation {
+                    device.set_shader_flags(wgpu::ShaderFlags::empty());
                 }
+                device
             }
         }))
         .await
     }
 
+    /// Wraps a WebGPU device and its queue that you created yourself
+    ///
+    /// This replaces the error handler of the WebGPU device (see [`on_error`](#method.on_error)). The info is optional so that you don't _need_
+    /// information to construct a `Device` yourself.
+    pub fn from_wgpu(device: wgpu::Device, queue: wgpu::Queue, info: Option<DeviceInfo>) -> Self {
+        Device {
+            errors: DeviceErrors::new(&device),
+            device,
+            queue,
+            info,
+            shader_flags: wgpu::ShaderFlags::VALIDATION,
+            deferred_uploads: DeferredUploads::default(),
+            slabs: SlabAllocator::default(),
+            value_buffers: ValueBuffers::default(),
+            callbacks: PendingCallbacks::default(),
+            watchpoints: Watchpoints::default(),
+            wait_fence: None,
+        }
+    }
+
+    /// Returns the WebGPU device wrapped by this
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    /// Returns the queue this submits work to
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    /// Returns information about the device, if there is any
+    pub fn info(&self) -> Option<&DeviceInfo> {
+        self.info.as_ref()
+    }
+
+    /// Returns the flags used when creating shader modules for kernels compiled on this device
+    ///
+    /// By default, this is `wgpu::ShaderFlags::VALIDATION`.
+    pub fn shader_flags(&self) -> wgpu::ShaderFlags {
+        self.shader_flags
+    }
+
+    /// Sets the flags used when creating shader modules for kernels compiled on this device from now on
+    pub fn set_shader_flags(&mut self, flags: wgpu::ShaderFlags) {
+        self.shader_flags = flags;
+    }
+
+    /// Returns whether this device can run kernels that use 64-bit floats (doubles)
+    ///
+    /// Devices found with [`all`](#method.all) have 64-bit floats enabled whenever they support them.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// if take()?.lock().unwrap().supports_f64() {
+    ///     println!("doubles are supported");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn supports_f64(&self) -> bool {
+        self.device
+            .features()
+            .contains(wgpu::Features::SHADER_FLOAT64)
+    }
+
+    /// Registers a callback for errors WebGPU reports outside of compiling and launching kernels
+    ///
+    /// Errors reported while compiling or launching a kernel are returned by [`compile`](#method.compile) and [`call`](#method.call) instead.
+    /// Without a callback, other errors panic (like they do by default in WebGPU). Registering a callback replaces the previous one.
+    /// Errors WebGPU reports while submitting work to a queue (or writing to a buffer through a queue) can't be handled and still panic.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// take()?.lock().unwrap().on_error(|error| println!("device error: {}", error));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_error(&self, callback: impl Fn(&wgpu::Error) + Send + Sync + 'static) {
+        self.errors.state.lock().unwrap().callback = Some(Arc::new(callback));
+    }
+
+    /// Returns the limits on thread blocks (workgroups) of kernels launched on this device
+    ///
+    /// WebGPU doesn't report these limits for each device yet. So these are the limits that every device is guaranteed to support, which
+    /// are what you should stay within for a kernel to run anywhere.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let limits = take()?.lock().unwrap().workgroup_limits();
+    /// assert!(limits.max_invocations >= 256);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn workgroup_limits(&self) -> WorkgroupLimits {
+        GUARANTEED_WORKGROUP_LIMITS
+    }
+
+    /// Sets whether or not uploads with [`set_from`](#method.set_from) are deferred
+    ///
+    /// Each upload is normally submitted to the device right away, which is expensive when there are many small uploads (like parameters
+    /// that change every frame). When uploads are deferred, `set_from` still copies the data right away (so the host data can be dropped)
+    /// but doesn't submit anything. All deferred uploads are then submitted together with the next launch, download, or other work submitted to this device,
+    /// or when [`flush`](#method.flush) is called. So uploads still happen in order with respect to everything else. Turning deferring off
+    /// flushes any uploads that are waiting.
+    ///
+    /// Uploads that are still waiting when the `Device` is dropped are never submitted.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let mut params: Vec<DeviceBox<f32>> = (0..32).map(|_| DeviceBox::new_mut(0.0f32)).collect::<Result<_, _>>()?;
+    /// let mut device = take()?.lock().unwrap();
+    /// device.defer_uploads(true);
+    /// for (i, param) in params.iter_mut().enumerate() {
+    ///     device.set_from(param, &(i as f32))?;
+    /// }
+    /// assert_eq!(device.deferred_uploads().len(), 32);
+    /// // all 32 uploads are submitted together
+    /// device.flush();
+    /// assert_eq!(futures::executor::block_on(device.get_one(&params[31]))?, 31.0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn defer_uploads(&mut self, defer: bool) {
+        self.deferred_uploads.enabled = defer;
+        if !defer {
+            self.flush();
+        }
+    }
+
+    /// Returns the uploads that are waiting to be submitted (see [`defer_uploads`](#method.defer_uploads))
+    pub fn deferred_uploads(&self) -> &DeferredUploads {
+        &self.deferred_uploads
+    }
+
+    /// Submits all the uploads that have been deferred (see [`defer_uploads`](#method.defer_uploads))
+    pub fn flush(&mut self) {
+        if !self.deferred_uploads.is_empty() {
+            // submitting nothing still submits everything written to the queue
+            self.submit_all(vec![]);
+        }
+    }
+
+    /// Sets whether or not small `DeviceBox`s are allocated from slabs
+    ///
+    /// Creating a buffer on a device is expensive compared to launching a small kernel, so code that creates many short-lived `DeviceBox`s (like
+    /// temporary results that are dropped right after the next launch) can spend most of its time allocating. When slabs are enabled, every
+    /// `DeviceBox` of at most 1 MiB that is created with [`create_with_size`](#method.create_with_size), [`create_from`](#method.create_from), or their
+    /// `_mut` versions is instead given a block of a larger buffer (a slab) that is allocated once and then reused. Blocks are bound to kernels with
+    /// an offset into the slab, so kernels see each `DeviceBox` just like one with its own buffer. When a `DeviceBox` is dropped, its block can be
+    /// given to the next `DeviceBox` of a similar size. `DeviceBox`s that are allocated from slabs are always zeroed (or set to the data they are
+    /// created from) before they are used.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut device = &mut futures::executor::block_on(Device::all())[0];
+    /// device.allocate_from_slabs(true);
+    /// for i in 0..100 {
+    ///     let mut data: DeviceBox<[f32]> = device.create_from_mut(vec![i as f32; 256].as_slice());
+    ///     let zeros: DeviceBox<[f32]> = device.create_with_size_mut(256 * std::mem::size_of::<f32>());
+    ///     assert_eq!(futures::executor::block_on(device.get(&data))?, vec![i as f32; 256].into_boxed_slice());
+    ///     assert_eq!(futures::executor::block_on(device.get(&zeros))?, vec![0.0; 256].into_boxed_slice());
+    /// }
+    /// // all 200 `DeviceBox`s were allocated from the same slab
+    /// assert_eq!(device.slabs().num_slabs(), 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Slabs are kept after all the `DeviceBox`s in them are dropped. You can free them with
+    /// [`release_free_slabs`](#method.release_free_slabs).
+    pub fn allocate_from_slabs(&mut self, enabled: bool) {
+        self.slabs.enabled = enabled;
+    }
+
+    /// Returns the slabs that small `DeviceBox`s on this device are allocated from (see [`allocate_from_slabs`](#method.allocate_from_slabs))
+    pub fn slabs(&self) -> &SlabAllocator {
+        &self.slabs
+    }
+
+    /// Frees the memory of every slab that no `DeviceBox` is allocated from
+    pub fn release_free_slabs(&mut self) {
+        self.slabs.release_free_slabs();
+    }
+
+    // submits the given work, which runs after any deferred uploads
+    pub(crate) fn submit_all<I: IntoIterator<Item = wgpu::CommandBuffer>>(
+        &mut self,
+        command_buffers: I,
+    ) {
+        self.deferred_uploads.len = 0;
+        self.queue.submit(command_buffers);
+    }
+
+    fn submit(&mut self, command_buffer: wgpu::CommandBuffer) {
+        self.submit_all(vec![command_buffer]);
+    }
+
+    // writes the given bytes to part of the given DeviceBox, starting at the given offset in bytes
+    // like a deferred upload, this is written to the queue and happens right before the next submission
+    #[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+    pub(crate) fn write_at<T: ?Sized>(
+        &mut self,
+        device_obj: &DeviceBox<T>,
+        offset: u64,
+        bytes: &[u8],
+    ) {
+        self.queue.write_buffer(
+            &device_obj.storage_buffer,
+            device_obj.offset + offset,
+            bytes,
+        );
+        self.deferred_uploads.len += 1;
+    }
+
     /// Creates a constant `DeviceBox<T>` with size of given number of bytes
     ///
     /// ```
@@ -231,124 +1191,485 @@ impl Device {
     where
         T: ?Sized,
     {
-        let staging_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
-            label: None,
-            size: size as u64,
-            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
-            mapped_at_creation: false,
-        });
+        if let Some(device_obj) = self.create_in_slab(size as u64, mutability) {
+            // the block may still hold the data of a dropped DeviceBox
+            let block_size = device_obj.allocation.as_ref().unwrap().slab.block_size;
+            self.queue.write_buffer(
+                &device_obj.storage_buffer,
+                device_obj.offset,
+                &vec![0; block_size as usize],
+            );
+            self.deferred_uploads.len += 1;
+            #[cfg(feature = "record")]
+            crate::record::record(|| crate::record::Event::Create {
+                buffer: device_obj.id,
+                size: device_obj.size,
+                mutability,
+                data: None,
+            });
+            return device_obj;
+        }
+
+        self.create_with_options(size, &DeviceBoxOptions::new().with_mutability(mutability))
+    }
+
+    fn create_from_as<T, B: Borrow<T>>(
+        &mut self,
+        host_obj: B,
+        mutability: Mutability,
+    ) -> DeviceBox<T>
+    where
+        T: AsBytes + ?Sized,
+    {
+        // serialize the data into bytes
+        // these bytes can later be deserialized back into T
+        let host_obj_bytes = host_obj.borrow().as_bytes();
 
+        if let Some(device_obj) = self.create_in_slab(host_obj_bytes.len() as u64, mutability) {
+            // writes to buffers must be a multiple of 4 bytes and the block is at least that large
+            self.queue.write_buffer(
+                &device_obj.storage_buffer,
+                device_obj.offset,
+                &padded_bytes(host_obj_bytes),
+            );
+            self.deferred_uploads.len += 1;
+            #[cfg(feature = "record")]
+            crate::record::record(|| crate::record::Event::Create {
+                buffer: device_obj.id,
+                size: device_obj.size,
+                mutability,
+                data: Some(host_obj_bytes.to_vec()),
+            });
+            return device_obj;
+        }
+
+        self.create_from_with_options(
+            host_obj,
+            &DeviceBoxOptions::new().with_mutability(mutability),
+        )
+    }
+
+    /// Creates a `DeviceBox<T>` with size of given number of bytes and the given options
+    ///
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut device = &mut futures::executor::block_on(Device::all())[0];
+    /// let mut histogram: DeviceBox<[u32]> = device.create_with_options(
+    ///     256 * std::mem::size_of::<u32>(),
+    ///     &DeviceBoxOptions::new().with_label("histogram").with_mutability(Mutability::Mut),
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// `DeviceBox`s created with options are never allocated from slabs (see [`allocate_from_slabs`](#method.allocate_from_slabs)).
+    pub fn create_with_options<T>(
+        &mut self,
+        size: usize,
+        options: &DeviceBoxOptions,
+    ) -> DeviceBox<T>
+    where
+        T: ?Sized,
+    {
         let storage_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
-            label: None,
-            size: size as u64, // casting usize to u64 is safe since usize is subtype of u64
-            usage: match mutability {
-                Mutability::Mut => wgpu::BufferUsage::STORAGE,
-                Mutability::Const => wgpu::BufferUsage::STORAGE,
-            } | wgpu::BufferUsage::COPY_DST
-                | wgpu::BufferUsage::COPY_SRC,
+            label: options.label(),
+            size: padded_size(size as u64), // casting usize to u64 is safe since usize is subtype of u64
+            usage: options.usage,
             mapped_at_creation: false,
         });
+        let device_obj = self.create_around(storage_buffer, size as u64, options);
+        #[cfg(feature = "record")]
+        crate::record::record(|| crate::record::Event::Create {
+            buffer: device_obj.id,
+            size: device_obj.size,
+            mutability: options.mutability,
+            data: None,
+        });
+        device_obj
+    }
+
+    /// Creates a `DeviceBox<T>` from a borrow of `T` with the given options
+    ///
+    /// See [`DeviceBoxOptions`](struct.DeviceBoxOptions.html) for an example.
+    /// `DeviceBox`s created with options are never allocated from slabs (see [`allocate_from_slabs`](#method.allocate_from_slabs)).
+    pub fn create_from_with_options<T, B: Borrow<T>>(
+        &mut self,
+        host_obj: B,
+        options: &DeviceBoxOptions,
+    ) -> DeviceBox<T>
+    where
+        T: AsBytes + ?Sized,
+    {
+        // serialize the data into bytes
+        // these bytes can later be deserialized back into T
+        let host_obj_bytes = host_obj.borrow().as_bytes();
+
+        // create an initialized storage buffer of appropriate size
+        let storage_buffer = if options.mapped_at_creation {
+            // this writes the data to the storage buffer while it is mapped at creation
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: options.label(),
+                    usage: options.usage,
+                    contents: host_obj_bytes,
+                })
+        } else {
+            let storage_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: options.label(),
+                size: padded_size(host_obj_bytes.len() as u64),
+                usage: options.usage,
+                mapped_at_creation: false,
+            });
+            // like a deferred upload, this is written to the queue and happens right before the next submission
+            self.queue
+                .write_buffer(&storage_buffer, 0, &padded_bytes(host_obj_bytes));
+            self.deferred_uploads.len += 1;
+            storage_buffer
+        };
+
+        // return the final DeviceBox
+        // note that we keep both the storage buffer and the staging buffer
+        // we will re-use the staging buffer for reads (but not for writes, for writes we just create a new upload buffer)
+        let device_obj = self.create_around(storage_buffer, host_obj_bytes.len() as u64, options);
+        #[cfg(feature = "record")]
+        crate::record::record(|| crate::record::Event::Create {
+            buffer: device_obj.id,
+            size: device_obj.size,
+            mutability: options.mutability,
+            data: Some(host_obj_bytes.to_vec()),
+        });
+        device_obj
+    }
+
+    // creates a DeviceBox around the given storage buffer with a staging buffer to download it with
+    fn create_around<T>(
+        &mut self,
+        storage_buffer: wgpu::Buffer,
+        size: u64,
+        options: &DeviceBoxOptions,
+    ) -> DeviceBox<T>
+    where
+        T: ?Sized,
+    {
+        let staging_label = options.label().map(|label| format!("{} (staging)", label));
+        let staging = Staging::new(
+            &self.device,
+            // a storage buffer that can't be copied from can't be downloaded so nothing is ever staged for it
+            if options.usage.contains(wgpu::BufferUsage::COPY_SRC) {
+                padded_size(size)
+            } else {
+                0
+            },
+            staging_label,
+        );
         DeviceBox {
-            staging_buffer,
-            storage_buffer,
-            size: size as u64,
+            staging: Arc::new(Mutex::new(staging)),
+            storage_buffer: Arc::new(storage_buffer),
+            offset: 0,
+            size,
             phantom: PhantomData,
-            mutability: Some(mutability),
+            mutability: Some(options.mutability),
+            usage: options.usage,
+            allocation: None,
+            id: next_id(),
         }
     }
 
-    fn create_from_as<T, B: Borrow<T>>(
+    // creates a DeviceBox in a block of a slab if slabs are enabled and a block can be large enough
+    // the contents of the block are left as they are
+    fn create_in_slab<T>(&mut self, size: u64, mutability: Mutability) -> Option<DeviceBox<T>>
+    where
+        T: ?Sized,
+    {
+        if !self.slabs.enabled || size == 0 || size > MAX_SLAB_BLOCK_SIZE {
+            return None;
+        }
+        let allocation = self.slabs.allocate(&self.device, size);
+        Some(DeviceBox {
+            staging: allocation.slab.staging.clone(),
+            storage_buffer: allocation.slab.storage_buffer.clone(),
+            offset: allocation.offset,
+            size,
+            phantom: PhantomData,
+            mutability: Some(mutability),
+            usage: DeviceBoxOptions::new().usage(),
+            allocation: Some(allocation),
+            id: next_id(),
+        })
+    }
+
+    // TODO say what is blocking and what isn't in the comments
+    /// Uploads data from the given borrow to `T` to the given `DeviceBox<T>` that lives on this (meaning `self`) device
+    ///
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut device = &mut futures::executor::block_on(Device::all())[0];
+    /// let data = vec![0.0; 2048];
+    /// let mut data_on_gpu: DeviceBox<[f32]> = device.create_from_mut(data.as_slice());
+    /// device.set_from(&mut data_on_gpu, vec![0.5; 2048].as_slice())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// The data must be the same size (in bytes) as the `DeviceBox`. Otherwise, nothing is uploaded and `SetError::SizeMismatch` is returned.
+    /// [`set_from_resizing`](#method.set_from_resizing) instead resizes the `DeviceBox` to fit the data.
+    pub fn set_from<T, B: Borrow<T>>(
         &mut self,
+        device_obj: &mut DeviceBox<T>,
         host_obj: B,
-        mutability: Mutability,
-    ) -> DeviceBox<T>
+    ) -> Result<(), SetError>
     where
         T: AsBytes + ?Sized,
     {
+        if device_obj.mutability.is_some() {
+            assert_eq!(device_obj.mutability.unwrap(), Mutability::Mut, "expected the `DeviceBox` being set to be mutable (each `DeviceBox` constructor has a \"constant\" version and a \"mut\" version)");
+        }
+
         // serialize the data into bytes
         // these bytes can later be deserialized back into T
         let host_obj_bytes = host_obj.borrow().as_bytes();
+        if host_obj_bytes.len() as u64 != device_obj.size {
+            return Err(SetError::SizeMismatch {
+                expected: device_obj.size,
+                actual: host_obj_bytes.len() as u64,
+            });
+        }
+        #[cfg(feature = "record")]
+        crate::record::record(|| crate::record::Event::Set {
+            buffer: device_obj.id,
+            data: host_obj_bytes.to_vec(),
+        });
+
+        // if uploads are deferred, the data is written to the queue
+        // WebGPU copies it right away but only copies it to the storage buffer with the next submission
+        if self.deferred_uploads.enabled {
+            self.queue.write_buffer(
+                &device_obj.storage_buffer,
+                device_obj.offset,
+                &padded_bytes(host_obj_bytes),
+            );
+            self.deferred_uploads.len += 1;
+            return Ok(());
+        }
+
+        // create an upload buffer with host_obj copied over
+        // the staging buffer of the device box is only for downloads (and may be shared with other device boxes)
+        let upload_buffer = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: host_obj_bytes,
+                usage: wgpu::BufferUsage::COPY_SRC,
+            });
+
+        // now copy over the upload buffer to the storage buffer
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        // the upload buffer is padded to a multiple of 4 bytes just like the storage buffer
+        encoder.copy_buffer_to_buffer(
+            &upload_buffer,
+            0,
+            &device_obj.storage_buffer,
+            device_obj.offset,
+            padded_size(device_obj.size),
+        );
+        self.submit(encoder.finish());
+        Ok(())
+    }
+
+    /// Uploads data to the given `DeviceBox<T>` like [`set_from`](#method.set_from) but first resizes the `DeviceBox` if the data is a different size
+    ///
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut device = &mut futures::executor::block_on(Device::all())[0];
+    /// let mut batch: DeviceBox<[f32]> = device.create_from_mut(vec![0.0; 256].as_slice());
+    /// device.set_from_resizing(&mut batch, vec![0.5; 1024].as_slice());
+    /// assert_eq!(batch.len(), 1024);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_from_resizing<T, B: Borrow<T>>(&mut self, device_obj: &mut DeviceBox<T>, host_obj: B)
+    where
+        T: AsBytes + ?Sized,
+    {
+        let size = host_obj.borrow().as_bytes().len();
+        if size as u64 != device_obj.size {
+            self.resize(device_obj, size);
+        }
+        self.set_from(device_obj, host_obj)
+            .expect("the `DeviceBox` was resized to fit the data");
+    }
 
-        // create a staging buffer with host_obj copied over
-        let staging_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
-            label: None,
-            size: host_obj_bytes.len() as u64,
-            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
-            mapped_at_creation: false,
-        });
-        // then create an initialized storage buffer of appropriate size
-        let storage_buffer = self
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: None,
-                usage: match mutability {
-                    Mutability::Mut => wgpu::BufferUsage::STORAGE,
-                    Mutability::Const => wgpu::BufferUsage::STORAGE,
-                } | wgpu::BufferUsage::COPY_SRC
-                    | wgpu::BufferUsage::COPY_DST,
-                contents: host_obj_bytes,
-            });
+    /// Resizes the given `DeviceBox<T>` to the given number of bytes, keeping as much of the data already in it as fits
+    ///
+    /// This allocates a new buffer (with the same mutability and usage) and copies the data over on the device. Bytes past the old size are
+    /// zero. Since the `DeviceBox` is then a different buffer, it stops being watched (see [`watch`](#method.watch)).
+    pub fn resize<T: ?Sized>(&mut self, device_obj: &mut DeviceBox<T>, size: usize) {
+        let mutability = device_obj.mutability.unwrap_or(Mutability::Mut);
+        let resized: DeviceBox<T> = if device_obj.allocation.is_some() {
+            self.create_with_size_as(size, mutability)
+        } else {
+            self.create_with_options(
+                size,
+                &DeviceBoxOptions::new()
+                    .with_usage(device_obj.usage)
+                    .with_mutability(mutability),
+            )
+        };
 
-        // return the final DeviceBox
-        // note that we keep both the storage buffer and the staging buffer
-        // we will re-use the staging buffer for reads (but not for writes, for writes we just create a new staging buffer)
-        DeviceBox {
-            staging_buffer,
-            storage_buffer,
-            size: host_obj_bytes.len() as u64,
-            phantom: PhantomData,
-            mutability: Some(mutability),
+        // copies must be a multiple of 4 bytes so a few bytes at the end of an oddly sized DeviceBox may be dropped
+        let copy_size = device_obj.size.min(resized.size) / wgpu::COPY_BUFFER_ALIGNMENT
+            * wgpu::COPY_BUFFER_ALIGNMENT;
+        if copy_size > 0 {
+            assert!(
+                device_obj.usage.contains(wgpu::BufferUsage::COPY_SRC),
+                "the `DeviceBox` being resized should have been created with the `COPY_SRC` usage"
+            );
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+            encoder.copy_buffer_to_buffer(
+                &device_obj.storage_buffer,
+                device_obj.offset,
+                &resized.storage_buffer,
+                resized.offset,
+                copy_size,
+            );
+            self.submit(encoder.finish());
         }
+        *device_obj = resized;
     }
 
-    // TODO say what is blocking and what isn't in the comments
-    /// Uploads data from the given borrow to `T` to the given `DeviceBox<T>` that lives on this (meaning `self`) device
+    /// Sets every element of the given `DeviceBox<[T]>` to the given value
     ///
+    /// Only a small chunk of copies of `value` is ever uploaded, no matter how large the `DeviceBox` is. The rest of the `DeviceBox` is filled by
+    /// copying that chunk over and over on the device. So this is much cheaper than uploading a whole host vector with [`set_from`](#method.set_from).
     /// ```
     /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
     /// let mut device = &mut futures::executor::block_on(Device::all())[0];
-    /// let data = vec![0.0; 2048];
-    /// let mut data_on_gpu: DeviceBox<[f32]> = device.create_from_mut(data.as_slice());
-    /// device.set_from(&mut data_on_gpu, vec![0.5; 2048].as_slice());
+    /// let mut data_on_gpu: DeviceBox<[f32]> = device.create_from_mut(vec![0.0; 1 << 20].as_slice());
+    /// device.fill(&mut data_on_gpu, 1.5);
+    /// assert_eq!(futures::executor::block_on(device.get(&data_on_gpu))?,
+    ///     vec![1.5; 1 << 20].into_boxed_slice());
     /// # Ok(())
     /// # }
     /// ```
-    pub fn set_from<T, B: Borrow<T>>(&mut self, device_obj: &mut DeviceBox<T>, host_obj: B)
+    pub fn fill<T>(&mut self, device_obj: &mut DeviceBox<[T]>, value: T)
     where
-        T: AsBytes + ?Sized,
+        T: AsBytes + Copy,
+    {
+        self.fill_with_bytes(device_obj, value.as_bytes());
+    }
+
+    /// Sets every byte of the given `DeviceBox<T>` to zero
+    ///
+    /// This is useful for resetting accumulators between iterations without uploading a host vector of zeros each time.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut device = &mut futures::executor::block_on(Device::all())[0];
+    /// let mut histogram: DeviceBox<[u32]> = device.create_from_mut(vec![7; 256].as_slice());
+    /// device.zero(&mut histogram);
+    /// assert_eq!(futures::executor::block_on(device.get(&histogram))?,
+    ///     vec![0; 256].into_boxed_slice());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn zero<T>(&mut self, device_obj: &mut DeviceBox<T>)
+    where
+        T: ?Sized,
     {
+        self.fill_with_bytes(device_obj, &[0; 4]);
+    }
+
+    // fills the storage buffer of the given DeviceBox with the given pattern of bytes repeated
+    //
+    // we upload a chunk of the pattern repeated (at most FILL_CHUNK_SIZE bytes) and then
+    // copy that chunk to each part of the storage buffer
+    // copies must be 4-byte aligned so the chunk must be a multiple of both 4 and the pattern's size
+    // the last 1-3 bytes of an oddly sized DeviceBox are written to the queue instead
+    pub(crate) fn fill_with_bytes<T: ?Sized>(
+        &mut self,
+        device_obj: &mut DeviceBox<T>,
+        pattern: &[u8],
+    ) {
         if device_obj.mutability.is_some() {
-            assert_eq!(device_obj.mutability.unwrap(), Mutability::Mut, "expected the `DeviceBox` being set to be mutable (each `DeviceBox` constructor has a \"constant\" version and a \"mut\" version)");
+            assert_eq!(device_obj.mutability.unwrap(), Mutability::Mut, "expected the `DeviceBox` being filled to be mutable (each `DeviceBox` constructor has a \"constant\" version and a \"mut\" version)");
         }
+        if device_obj.size == 0 || pattern.is_empty() {
+            return;
+        }
+        #[cfg(feature = "record")]
+        crate::record::record(|| crate::record::Event::Fill {
+            buffer: device_obj.id,
+            pattern: pattern.to_vec(),
+        });
 
-        // serialize the data into bytes
-        // these bytes can later be deserialized back into T
-        let host_obj_bytes = host_obj.borrow().as_bytes();
-
-        // create a staging buffer with host_obj copied over
-        // set this staging buffer as the new staging buffer for the device box
-        let staging_buffer = self
+        // find the smallest length that is a multiple of both 4 and the pattern's length
+        let mut unit_len = pattern.len();
+        while unit_len % wgpu::COPY_BUFFER_ALIGNMENT as usize != 0 {
+            unit_len += pattern.len();
+        }
+        let aligned_size =
+            device_obj.size / wgpu::COPY_BUFFER_ALIGNMENT * wgpu::COPY_BUFFER_ALIGNMENT;
+        if aligned_size < device_obj.size {
+            // writes to buffers must be a multiple of 4 bytes and the buffer (or block of a slab) is padded to that
+            let tail = pattern
+                .iter()
+                .copied()
+                .cycle()
+                .skip(aligned_size as usize % pattern.len())
+                .take(wgpu::COPY_BUFFER_ALIGNMENT as usize)
+                .collect::<Vec<u8>>();
+            self.queue.write_buffer(
+                &device_obj.storage_buffer,
+                device_obj.offset + aligned_size,
+                &tail,
+            );
+            self.deferred_uploads.len += 1;
+        }
+        if aligned_size == 0 {
+            return;
+        }
+        let chunk_len = std::cmp::min(
+            unit_len * std::cmp::max(1, FILL_CHUNK_SIZE / unit_len),
+            aligned_size as usize,
+        );
+        let chunk = pattern
+            .iter()
+            .copied()
+            .cycle()
+            .take(chunk_len)
+            .collect::<Vec<u8>>();
+        let chunk_buffer = self
             .device
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: None,
-                contents: host_obj_bytes,
+                contents: &chunk,
                 usage: wgpu::BufferUsage::COPY_SRC,
             });
-        device_obj.staging_buffer = staging_buffer;
 
-        // now copy over the staging buffer to the storage buffer
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-        encoder.copy_buffer_to_buffer(
-            &device_obj.staging_buffer,
-            0,
-            &device_obj.storage_buffer,
-            0,
-            device_obj.size,
-        );
-        self.queue.submit(vec![encoder.finish()]);
+        let mut offset = 0;
+        while offset < aligned_size {
+            let len = std::cmp::min(chunk_len as u64, aligned_size - offset);
+            encoder.copy_buffer_to_buffer(
+                &chunk_buffer,
+                0,
+                &device_obj.storage_buffer,
+                device_obj.offset + offset,
+                len,
+            );
+            offset += len;
+        }
+        self.submit(encoder.finish());
     }
 
     /// Downloads data from the given `DeviceBox<T>` asynchronously and returns a boxed slice of `T`
@@ -364,11 +1685,19 @@ impl Device {
     /// // create some data on a GPU and mutate it in place
     /// let data = vec![0.0; 2048];
     /// let mut data_on_gpu: DeviceBox<[f32]> = device.create_from_mut(data.as_slice());
-    /// device.set_from(&mut data_on_gpu, vec![0.5; 2048].as_slice());
+    /// device.set_from(&mut data_on_gpu, vec![0.5; 2048].as_slice())?;
     ///
     /// // use `get` to download from the GPU
     /// assert_eq!(futures::executor::block_on(device.get(&data_on_gpu))?,
     ///     vec![0.5; 2048].into_boxed_slice());
+    ///
+    /// // data doesn't have to be a multiple of 4 bytes in size
+    /// let mut bytes_on_gpu: DeviceBox<[u8]> = device.create_from_mut(vec![1u8, 2, 3].as_slice());
+    /// assert_eq!(futures::executor::block_on(device.get(&bytes_on_gpu))?,
+    ///     vec![1u8, 2, 3].into_boxed_slice());
+    /// device.set_from(&mut bytes_on_gpu, vec![4u8, 5, 6].as_slice())?;
+    /// assert_eq!(futures::executor::block_on(device.get(&bytes_on_gpu))?,
+    ///     vec![4u8, 5, 6].into_boxed_slice());
     /// # Ok(())
     /// # }
     /// ```
@@ -383,52 +1712,397 @@ impl Device {
             assert_eq!(device_obj.mutability.unwrap(), Mutability::Mut, "the `DeviceBox` from which you are downloading data from a device should be mutable, not constant");
         }
 
+        // only 1 download can use the staging buffer at a time
+        let mut staging = device_obj.staging.lock().await;
+
         // first, we copy over data from the storage buffer to the staging buffer
         // the staging buffer is host visible so we can then work with it more easily
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-        encoder.copy_buffer_to_buffer(
-            &device_obj.storage_buffer,
-            0,
-            &device_obj.staging_buffer,
-            0,
-            device_obj.size,
+        // then we map it so that we can deserialize staging_buffer -> [T]
+        self.map_staging(device_obj, &mut staging).await?;
+
+        Ok(Self::read_from_staging(device_obj, &staging))
+    }
+
+    /// Downloads data from the given `DeviceBox<T>` like [`get`](#method.get) but gives up after the given timeout
+    ///
+    /// `get` blocks until the device has finished all work submitted before the download. If a kernel never terminates
+    /// or the driver stops responding, that is forever. `get_with_timeout` instead polls the device until either the
+    /// data is ready or the timeout elapses, in which case a `GetError::Timeout` is returned.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*, std::time::Duration};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut device = &mut futures::executor::block_on(Device::all())[0];
+    /// let data_on_gpu: DeviceBox<[f32]> = device.create_from_mut(vec![0.5; 2048].as_slice());
+    ///
+    /// // a server might rather report an error than hang on a wedged device
+    /// let data = futures::executor::block_on(device.get_with_timeout(&data_on_gpu, Duration::from_secs(5)))?;
+    /// assert_eq!(data, vec![0.5; 2048].into_boxed_slice());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Note that if the timeout does elapse, the staging buffer of the `DeviceBox` is still waiting to be mapped. So it's poisoned and replaced
+    /// with a new staging buffer before the next download and later downloads still work. This is true of `DeviceBox`s allocated from slabs
+    /// (see [`allocate_from_slabs`](#method.allocate_from_slabs)) too, which share a staging buffer with the rest of their slab.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*, std::time::Duration};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// emu_core::testing::run(|| -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut device = take()?.lock()?;
+    ///     let own: DeviceBox<[f32]> = device.create_from_mut(vec![0.5; 1 << 20].as_slice());
+    ///     device.allocate_from_slabs(true);
+    ///     let in_slab: DeviceBox<[f32]> = device.create_from_mut(vec![1.5; 16].as_slice());
+    ///
+    ///     // a timeout of 0 gives up on the download right away (unless the device is done after polling once)
+    ///     let _ = futures::executor::block_on(device.get_with_timeout(&own, Duration::from_secs(0)));
+    ///     let _ = futures::executor::block_on(device.get_with_timeout(&in_slab, Duration::from_secs(0)));
+    ///     assert_eq!(futures::executor::block_on(device.get(&own))?, vec![0.5; 1 << 20].into_boxed_slice());
+    ///     assert_eq!(futures::executor::block_on(device.get(&in_slab))?, vec![1.5; 16].into_boxed_slice());
+    ///     Ok(())
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_with_timeout<T>(
+        &mut self,
+        device_obj: &DeviceBox<[T]>,
+        timeout: Duration,
+    ) -> Result<Box<[T]>, GetError>
+    where
+        T: FromBytes + Copy,
+    {
+        if device_obj.mutability.is_some() {
+            assert_eq!(device_obj.mutability.unwrap(), Mutability::Mut, "the `DeviceBox` from which you are downloading data from a device should be mutable, not constant");
+        }
+
+        let mut staging = device_obj.staging.lock().await;
+        staging.recover(&self.device);
+        self.copy_to_staging(device_obj, &staging.buffer);
+
+        // we box the future so that it can be polled by reference
+        let result = Box::pin(
+            device_obj
+                .staging_slice(&staging)
+                .map_async(wgpu::MapMode::Read),
+        );
+        // until the mapping completes or fails, the staging buffer is poisoned
+        staging.poisoned = true;
+        self.poll_with_timeout(result, timeout)
+            .ok_or(GetError::Timeout)?
+            .map_err(|source| {
+                GetError::Completion(CompletionError::Map {
+                    device: self.info.clone(),
+                    source,
+                })
+            })?;
+        staging.poisoned = false;
+
+        Ok(Self::read_from_staging(device_obj, &staging))
+    }
+
+    /// Downloads data from the given `DeviceBox<[T]>` asynchronously into the given slice
+    ///
+    /// This is like [`get`](#method.get) but instead of allocating a new `Box<[T]>` each time, the data is deserialized straight into
+    /// a slice you provide. This is useful when you download every frame and want to reuse the same `Vec`.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut device = &mut futures::executor::block_on(Device::all())[0];
+    /// let data_on_gpu: DeviceBox<[f32]> = device.create_from_mut(vec![0.5; 2048].as_slice());
+    ///
+    /// // the same buffer can be reused for every download
+    /// let mut data = vec![0.0; 2048];
+    /// futures::executor::block_on(device.get_into(&data_on_gpu, &mut data))?;
+    /// assert_eq!(data, vec![0.5; 2048]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// This will panic if the length of the given slice is not the same as the length of the slice stored in the `DeviceBox`.
+    pub async fn get_into<T>(
+        &mut self,
+        device_obj: &DeviceBox<[T]>,
+        host_obj: &mut [T],
+    ) -> Result<(), CompletionError>
+    where
+        T: FromBytes + Copy,
+    {
+        if device_obj.mutability.is_some() {
+            assert_eq!(device_obj.mutability.unwrap(), Mutability::Mut, "the `DeviceBox` from which you are downloading data from a device should be mutable, not constant");
+        }
+        assert_eq!(
+            host_obj.len() * std::mem::size_of::<T>(),
+            device_obj.size as usize,
+            "the slice you are downloading data into should be the same length as the slice stored in the `DeviceBox`"
         );
-        self.queue.submit(vec![encoder.finish()]);
 
-        // now we can return a future for data read from staging buffer
-        // this does a kind of complicated deserialization procedure
-        // basically it does staging_buffer -> [T]
+        let mut staging = device_obj.staging.lock().await;
+        self.map_staging(device_obj, &mut staging).await?;
+
+        // deserialize each size_of(T) item directly into the slice we were given
+        for (host_item, item) in host_obj.iter_mut().zip(
+            device_obj.staging_slice(&staging).get_mapped_range()[..device_obj.size as usize]
+                .chunks_exact(std::mem::size_of::<T>()),
+        ) {
+            let layout_verified: LayoutVerified<_, T> = LayoutVerified::new(item).unwrap();
+            *host_item = *layout_verified;
+        }
+        staging.buffer.unmap();
+
+        Ok(())
+    }
+
+    /// Downloads a single `T` from the given `DeviceBox<T>` asynchronously
+    ///
+    /// This is like [`get`](#method.get) but for a `DeviceBox` that holds just one value instead of a slice. So you can read back
+    /// a counter or the output of a reduction without wrapping it in a slice of length 1.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut device = &mut futures::executor::block_on(Device::all())[0];
+    /// let mut sum_on_gpu: DeviceBox<f32> = device.create_from_mut(0.0);
+    /// device.set_from(&mut sum_on_gpu, 42.0)?;
+    ///
+    /// assert_eq!(futures::executor::block_on(device.get_one(&sum_on_gpu))?, 42.0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_one<T>(&mut self, device_obj: &DeviceBox<T>) -> Result<T, CompletionError>
+    where
+        T: FromBytes + Copy,
+    {
+        if device_obj.mutability.is_some() {
+            assert_eq!(device_obj.mutability.unwrap(), Mutability::Mut, "the `DeviceBox` from which you are downloading data from a device should be mutable, not constant");
+        }
+
+        let mut staging = device_obj.staging.lock().await;
+        self.map_staging(device_obj, &mut staging).await?;
+
+        let mapped = device_obj.staging_slice(&staging).get_mapped_range();
+        let layout_verified: LayoutVerified<_, T> =
+            LayoutVerified::new(&mapped[..device_obj.size as usize]).unwrap(); // the DeviceBox is exactly size_of(T)
+        let value = *layout_verified;
+        drop(mapped);
+        staging.buffer.unmap();
+        Ok(value)
+    }
+
+    // downloads the raw bytes stored in the given DeviceBox, no matter if it is constant or mutable
+    #[cfg(any(feature = "pool", feature = "record"))]
+    pub(crate) async fn get_bytes<T: ?Sized>(
+        &mut self,
+        device_obj: &DeviceBox<T>,
+    ) -> Result<Vec<u8>, CompletionError> {
+        let mut staging = device_obj.staging.lock().await;
+        self.map_staging(device_obj, &mut staging).await?;
+
+        let bytes = device_obj.staging_slice(&staging).get_mapped_range()
+            [..device_obj.size as usize]
+            .to_vec();
+        staging.buffer.unmap();
+        Ok(bytes)
+    }
+
+    // downloads the raw bytes stored in the given DeviceBox on the device behind the given lock
+    // the lock is only held while the download is submitted and never across an `.await`
+    #[cfg(feature = "pool")]
+    pub(crate) async fn get_bytes_locked<T: ?Sized>(
+        device: &std::sync::Mutex<Device>,
+        device_obj: &DeviceBox<T>,
+    ) -> Result<Vec<u8>, CompletionError> {
+        let mut staging = device_obj.staging.lock().await;
+        let (result, info) = {
+            let mut device = device.lock().unwrap();
+            (
+                device.start_map_staging(device_obj, &mut staging),
+                device.info.clone(),
+            )
+        };
+        result.await.map_err(|source| {
+            staging.poisoned = true;
+            CompletionError::Map {
+                device: info,
+                source,
+            }
+        })?;
+
+        let bytes = device_obj.staging_slice(&staging).get_mapped_range()
+            [..device_obj.size as usize]
+            .to_vec();
+        staging.buffer.unmap();
+        Ok(bytes)
+    }
+
+    // copies the given DeviceBox to its (locked) staging buffer and maps the staging buffer for reading
+    // if mapping fails, the staging buffer is poisoned so that the next download replaces it instead of failing too
+    async fn map_staging<T: ?Sized>(
+        &mut self,
+        device_obj: &DeviceBox<T>,
+        staging: &mut Staging,
+    ) -> Result<(), CompletionError> {
+        self.start_map_staging(device_obj, staging)
+            .await
+            .map_err(|source| {
+                staging.poisoned = true;
+                CompletionError::Map {
+                    device: self.info.clone(),
+                    source,
+                }
+            })
+    }
+
+    // the part of map_staging that needs the device, returning the result of mapping without borrowing the device
+    fn start_map_staging<T: ?Sized>(
+        &mut self,
+        device_obj: &DeviceBox<T>,
+        staging: &mut Staging,
+    ) -> impl Future<Output = Result<(), wgpu::BufferAsyncError>> {
+        staging.recover(&self.device);
+        self.copy_to_staging(device_obj, &staging.buffer);
+
         let result = device_obj
-            .staging_buffer
-            .slice(..)
+            .staging_slice(staging)
             .map_async(wgpu::MapMode::Read);
 
-        //.map_read(0u64, device_obj.size); // this gets a GpuFuture<Result<BufferReadMapping, ()>>
-
         // poll the device
         // TODO this should not be blocking (since this is async) we need to find some way to poll a
         self.device.poll(wgpu::Maintain::Wait);
 
-        //result.map_err(|_error| CompletionError).await?;
+        result
+    }
 
-        result.map_err(|_| CompletionError).await?;
+    // encodes and submits a copy of the storage buffer of the given DeviceBox to the given staging buffer
+    fn copy_to_staging<T: ?Sized>(
+        &mut self,
+        device_obj: &DeviceBox<T>,
+        staging_buffer: &wgpu::Buffer,
+    ) {
+        assert!(
+            device_obj.usage.contains(wgpu::BufferUsage::COPY_SRC),
+            "the `DeviceBox` being downloaded should have been created with the `COPY_SRC` usage"
+        );
+        #[cfg(feature = "record")]
+        crate::record::record(|| crate::record::Event::Get {
+            buffer: device_obj.id,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_buffer_to_buffer(
+            &device_obj.storage_buffer,
+            device_obj.offset,
+            staging_buffer,
+            device_obj.offset,
+            padded_size(device_obj.size),
+        );
+        self.submit(encoder.finish());
+    }
 
-        Ok(device_obj
-            .staging_buffer
-            .slice(..)
-            .get_mapped_range()
+    // deserializes the (already mapped) staging buffer of the given DeviceBox
+    fn read_from_staging<T: FromBytes + Copy>(
+        device_obj: &DeviceBox<[T]>,
+        staging: &Staging,
+    ) -> Box<[T]> {
+        let data = device_obj.staging_slice(staging).get_mapped_range()[..device_obj.size as usize]
             .chunks_exact(std::mem::size_of::<T>()) // this creates an iterator over each item of size = size_of(T)
             .map(|item| {
                 let layout_verified: LayoutVerified<_, T> = LayoutVerified::new(item).unwrap(); // TODO ensure this unwrap makes sense
                 *layout_verified
             }) // this deserializes each size_of(T) item
-            .collect()) // this collects it all into a [T]
+            .collect(); // this collects it all into a [T]
+                        // unmapping lets the staging buffer be mapped again by the next download
+        staging.buffer.unmap();
+        data
+    }
+
+    // polls the device until the given future completes or the timeout elapses
+    // this returns None if the timeout elapsed first
+    fn poll_with_timeout<F: Future + Unpin>(
+        &self,
+        mut future: F,
+        timeout: Duration,
+    ) -> Option<F::Output> {
+        let deadline = Instant::now() + timeout;
+        let mut backoff = MIN_POLL_BACKOFF;
+        loop {
+            // unlike Maintain::Wait, Maintain::Poll doesn't block on the device
+            self.device.poll(wgpu::Maintain::Poll);
+            if let Some(output) = (&mut future).now_or_never() {
+                return Some(output);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            std::thread::sleep(backoff.min(deadline - now));
+            backoff = (backoff * 2).min(MAX_POLL_BACKOFF);
+        }
+    }
+
+    // waits for all work submitted so far to complete or for the timeout to elapse
+    fn wait_with_timeout(&mut self, timeout: Duration) -> Result<(), LaunchError> {
+        // the fence is taken so that it is only put back for the next wait once it is unmapped
+        // if the wait times out or fails, the fence is still waiting to be mapped and is dropped instead
+        let (fence_src, fence_dst) = match self.wait_fence.take() {
+            Some(fence) => fence,
+            None => self.create_fence(),
+        };
+        self.submit_fence_copy(&fence_src, &fence_dst);
+        let result = Box::pin(fence_dst.slice(..).map_async(wgpu::MapMode::Read));
+        self.poll_with_timeout(result, timeout)
+            .ok_or(LaunchError::Timeout)?
+            .map_err(LaunchError::Runtime)?;
+        fence_dst.unmap();
+        self.wait_fence = Some((fence_src, fence_dst));
+        Ok(())
+    }
+
+    // submits a fence that completes once all work submitted so far has completed
+    //
+    // wgpu doesn't let us wait on a submission directly so we submit a tiny copy after everything else
+    // and return the buffer it copies to along with a future for when that buffer is mapped (submissions complete in order)
+    // the buffer must be kept alive until the future completes
+    pub(crate) fn submit_fence(&mut self) -> (wgpu::Buffer, FenceFuture) {
+        let (fence_src, fence_dst) = self.create_fence();
+        self.submit_fence_copy(&fence_src, &fence_dst);
+
+        let result = Box::pin(fence_dst.slice(..).map_async(wgpu::MapMode::Read));
+        (fence_dst, result)
+    }
+
+    // creates the buffers that a fence copies from and to
+    fn create_fence(&self) -> (wgpu::Buffer, wgpu::Buffer) {
+        let fence_src = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: &[0; 4],
+                usage: wgpu::BufferUsage::COPY_SRC,
+            });
+        let fence_dst = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: 4,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        (fence_src, fence_dst)
+    }
+
+    // submits the copy of a fence after everything submitted so far
+    fn submit_fence_copy(&mut self, fence_src: &wgpu::Buffer, fence_dst: &wgpu::Buffer) {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_buffer_to_buffer(fence_src, 0, fence_dst, 0, 4);
+        self.submit(encoder.finish());
     }
 
     /// Runs the given `DeviceFnMut` on a multi-dimensional space of threads to launch and arguments to pass to the launched kernel
     ///
+    /// The space of threads is a [`Grid`](struct.Grid.html) or a tuple of the number of thread blocks to spawn in each dimension. If the kernel
+    /// declares its grid size (like with [`GlslKernel::with_grid_size`](../compile_impls/struct.GlslKernel.html#method.with_grid_size)), the
+    /// global size of the grid is passed in for it. For a tuple, that is the number of threads spawned.
+    ///
     /// This is unsafe because it runs arbitrary code on a device.
     /// ```no_run
     /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
@@ -463,14 +2137,178 @@ impl Device {
     pub unsafe fn call<'a>(
         &mut self,
         device_fn_mut: &DeviceFnMut,
-        work_space_dim: (u32, u32, u32),
+        grid: impl Into<Grid>,
         args: DeviceFnMutArgs<'a>,
     ) -> Result<(), LaunchError> {
+        // watched DeviceBoxs that the launch can write to are checksummed before and after it
+        let watched = self.watched_by(device_fn_mut, &args);
+        let mut before = vec![];
+        for (_, buffer, offset, size) in &watched {
+            before.push(self.checksum(buffer, *offset, *size)?);
+        }
+
+        let (command_buffer, lease) = self.encode_call(device_fn_mut, grid.into(), args)?;
+
+        // finally, send the command
+        self.submit(command_buffer);
+        drop(lease);
+
+        for ((name, buffer, offset, size), before) in watched.into_iter().zip(before) {
+            let after = self.checksum(&buffer, offset, size)?;
+            self.watchpoints.log.push(WatchEntry {
+                name,
+                kernel: device_fn_mut.name.clone(),
+                before,
+                after,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Watches the given `DeviceBox` for changes made by launches, giving it the given name in the log
+    ///
+    /// After this, every launch with [`call`](#method.call) (or anything that calls it, like [`Spawner::launch`](../spawn/struct.Spawner.html#method.launch))
+    /// that binds the `DeviceBox` to a mutable parameter downloads a checksum of its data before and after the launch and adds an entry to
+    /// [`watchpoints().log()`](struct.Watchpoints.html#method.log). So when a buffer ends up with corrupt data after a long pipeline of kernels, the log
+    /// tells which kernels wrote to it and which of them actually changed it. Each checksum blocks until everything submitted so far completes, so
+    /// watching is only for debugging. Launches that are recorded and submitted later (like those of an [`AsyncQueue`](../queue/struct.AsyncQueue.html)
+    /// or a [`Schedule`](../schedule/struct.Schedule.html)) aren't watched.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+    ///     GlslKernel::new()
+    ///         .param_mut::<[f32], _>("float[] data")
+    ///         .param::<f32, _>("float scale")
+    ///         .with_kernel_code("data[gl_GlobalInvocationID.x] = data[gl_GlobalInvocationID.x] * scale;"),
+    /// )?
+    /// .finish()?;
+    /// let mut data: DeviceBox<[f32]> = vec![1.0; 1024].as_device_boxed_mut()?;
+    /// take()?.lock().unwrap().watch(&data, "data");
+    /// unsafe {
+    ///     spawn(1024).launch(call!(kernel.clone(), &mut data, 1.0f32))?;
+    ///     spawn(1024).launch(call!(kernel.clone(), &mut data, 2.0f32))?;
+    /// }
+    /// let log = take()?.lock().unwrap().take_watch_log();
+    /// assert_eq!(log.len(), 2);
+    /// assert!(!log[0].changed());
+    /// assert!(log[1].changed());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn watch<T: ?Sized>(&mut self, device_obj: &DeviceBox<T>, name: impl Into<String>) {
+        assert!(
+            device_obj.usage.contains(wgpu::BufferUsage::COPY_SRC),
+            "the `DeviceBox` being watched should have been created with the `COPY_SRC` usage"
+        );
+        self.watchpoints.watched.insert(
+            device_obj.id,
+            (
+                name.into(),
+                Arc::downgrade(&device_obj.storage_buffer),
+                device_obj.offset,
+                device_obj.size,
+            ),
+        );
+    }
+
+    /// Stops watching the given `DeviceBox` (see [`watch`](#method.watch))
+    pub fn unwatch<T: ?Sized>(&mut self, device_obj: &DeviceBox<T>) {
+        self.watchpoints.watched.remove(&device_obj.id);
+    }
+
+    /// Returns the `DeviceBox`s that are being watched along with the log of launches that bound them mutably (see [`watch`](#method.watch))
+    pub fn watchpoints(&self) -> &Watchpoints {
+        &self.watchpoints
+    }
+
+    /// Returns the log of launches that bound a watched `DeviceBox` mutably (see [`watch`](#method.watch)) and clears it
+    pub fn take_watch_log(&mut self) -> Vec<WatchEntry> {
+        self.watchpoints.take_log()
+    }
+
+    // the name, storage buffer, offset, and size of each watched DeviceBox that the given launch binds mutably
+    // DeviceBoxs that have been dropped since they were watched stop being watched
+    fn watched_by(
+        &mut self,
+        device_fn_mut: &DeviceFnMut,
+        args: &DeviceFnMutArgs,
+    ) -> Vec<(String, Arc<wgpu::Buffer>, u64, u64)> {
+        if self.watchpoints.watched.is_empty() {
+            return vec![];
+        }
+        self.watchpoints
+            .watched
+            .retain(|_, (_, buffer, _, _)| buffer.strong_count() > 0);
+        args.mutable_buffer_ids(Some(&device_fn_mut.param_types))
+            .into_iter()
+            .filter_map(|id| {
+                let (name, buffer, offset, size) = self.watchpoints.watched.get(&id)?;
+                Some((name.clone(), buffer.upgrade()?, *offset, *size))
+            })
+            .collect()
+    }
+
+    // downloads the given bytes of the given buffer and hashes them (with 64-bit FNV-1a), blocking until everything submitted so far completes
+    fn checksum(
+        &mut self,
+        buffer: &wgpu::Buffer,
+        offset: u64,
+        size: u64,
+    ) -> Result<u64, LaunchError> {
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: padded_size(size),
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_buffer_to_buffer(buffer, offset, &readback, 0, padded_size(size));
+        self.submit(encoder.finish());
+
+        let slice = readback.slice(..);
+        let result = slice.map_async(wgpu::MapMode::Read);
+        self.device.poll(wgpu::Maintain::Wait);
+        futures::executor::block_on(result).map_err(LaunchError::Runtime)?;
+        let checksum = slice.get_mapped_range()[..size as usize]
+            .iter()
+            .fold(0xcbf29ce484222325u64, |hash, byte| {
+                (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+            });
+        readback.unmap();
+        Ok(checksum)
+    }
+
+    // records a launch of the given DeviceFnMut into a command buffer without submitting it
+    // the returned lease must be held until the command buffer is submitted (or dropped)
+    pub(crate) unsafe fn encode_call<'a>(
+        &self,
+        device_fn_mut: &DeviceFnMut,
+        grid: Grid,
+        args: DeviceFnMutArgs<'a>,
+    ) -> Result<(wgpu::CommandBuffer, ValueLease), LaunchError> {
+        let work_space_dim = grid.work_space_dim_for(device_fn_mut)?;
+        // kernels that declare their grid size get it passed in by value (padded to the size of a uvec4)
+        let args = match device_fn_mut.grid_size_param() {
+            Some((set_num, binding_num)) => {
+                let (x, y, z) = grid.grid_size_for(device_fn_mut)?;
+                args.with_grid_size_at(set_num, binding_num, [x, y, z, 0])
+            }
+            None => args,
+        };
+
+        // check that args are passed for exactly the bind groups the kernel has parameters in
+        // set numbers don't have to be contiguous so each bind group is matched up by its set number
+        args.check_sets(&device_fn_mut.param_types)?;
+
         // check that params and args match in type
-        for (set_num, set) in &args.bind_groups {
-            for (binding_num, binding) in &set.0 {
+        for (set_num, binding_num, arg_type) in args.arg_infos() {
+            {
                 let message = "the compiled `DeviceFnMut` does not have parameters that match the arguments being passed to it";
-                let arg_type = &binding.1;
                 let param_type = device_fn_mut
                     .param_types
                     .get(&set_num)
@@ -498,51 +2336,360 @@ impl Device {
             }
         }
 
-        // begin the encoder of command to send to device
-        // then, generate command to do computation
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        // check that the kernel can't race with itself through aliased arguments
+        args.check_aliasing(Some(&device_fn_mut.param_types))?;
+
+        // check that the launch fits within the limits of the device
+        // otherwise, the driver would fail (or worse, silently do less work than asked for)
+        for &dim in &[work_space_dim.0, work_space_dim.1, work_space_dim.2] {
+            if dim > MAX_WORKGROUPS_PER_DIMENSION {
+                return Err(LaunchError::LimitExceeded {
+                    limit: "thread blocks in a dimension",
+                    requested: dim as u64,
+                    max: MAX_WORKGROUPS_PER_DIMENSION as u64,
+                });
+            }
+        }
+        if let Some((x, y, z)) = device_fn_mut.workgroup_size {
+            let workgroup_limits = self.workgroup_limits();
+            for &(size, max_size) in &[
+                (x, workgroup_limits.max_size.0),
+                (y, workgroup_limits.max_size.1),
+                (z, workgroup_limits.max_size.2),
+            ] {
+                if size > max_size {
+                    return Err(LaunchError::LimitExceeded {
+                        limit: "threads in a dimension of a thread block",
+                        requested: size as u64,
+                        max: max_size as u64,
+                    });
+                }
+            }
+            let invocations = x as u64 * y as u64 * z as u64;
+            if invocations > workgroup_limits.max_invocations as u64 {
+                return Err(LaunchError::LimitExceeded {
+                    limit: "threads in a thread block",
+                    requested: invocations,
+                    max: workgroup_limits.max_invocations as u64,
+                });
+            }
+        }
+        args.check_limits(&self.device.limits())?;
+        #[cfg(feature = "record")]
+        crate::record::record(|| crate::record::Event::Call {
+            kernel: device_fn_mut.id,
+            work_space_dim,
+            args: args.recorded_args(),
+        });
+
+        // errors in the launch are captured and returned so that a bad launch is never submitted
+        let lease = self.value_buffers.lease();
+        let (command_buffer, error) = self.errors.scope(|| {
+            // begin the encoder of command to send to device
+            // then, generate command to do computation
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
 
-        let mut bind_groups = vec![];
-        for (set_num, (bind_group, _offsets)) in &args.bind_groups {
-            bind_groups.push(
-                self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                    label: None, // TODO maybe in all these label fields, we should actually use a label
-                    layout: &device_fn_mut.bind_group_layouts[&set_num],
-                    entries: bind_group
-                        .values()
-                        .map(|binding| binding.0.clone())
-                        .collect::<Vec<wgpu::BindGroupEntry<'a>>>()
-                        .as_slice(),
+            // arguments passed by value are uploaded to blocks of pages that are reused once this launch is submitted
+            let value_buffers = args
+                .values
+                .iter()
+                .flat_map(|(set_num, values)| {
+                    values.iter().map(move |(binding_num, (bytes, _))| {
+                        let (page, offset) =
+                            self.value_buffers.upload(&self.device, &self.queue, bytes);
+                        (*set_num, *binding_num, page, offset, bytes.len() as u64)
+                    })
+                })
+                .collect::<Vec<(u32, u32, Arc<wgpu::Buffer>, u64, u64)>>();
+
+            // a bind group is created for each set number the kernel has a layout for
+            // including sets that only fill a gap in the numbering (those have no bindings)
+            let mut set_nums = device_fn_mut
+                .bind_group_layouts
+                .keys()
+                .copied()
+                .collect::<Vec<u32>>();
+            set_nums.sort();
+            let bind_groups = set_nums
+                .into_iter()
+                .map(|set_num| {
+                    let entries = args
+                        .bind_groups
+                        .get(&set_num)
+                        .into_iter()
+                        .flat_map(|(bindings, _offsets)| {
+                            bindings.values().map(|binding| binding.0.clone())
+                        })
+                        .chain(
+                            value_buffers
+                                .iter()
+                                .filter(|(value_set_num, _, _, _, _)| *value_set_num == set_num)
+                                .map(
+                                    |(_, binding_num, page, offset, size)| wgpu::BindGroupEntry {
+                                        binding: *binding_num,
+                                        resource: wgpu::BindingResource::Buffer {
+                                            buffer: page,
+                                            offset: *offset,
+                                            size: wgpu::BufferSize::new(*size),
+                                        },
+                                    },
+                                ),
+                        )
+                        .collect::<Vec<wgpu::BindGroupEntry>>();
                     // TODO ensure the above clone is okay, it should be only cloning the underlying borrow of a buffer and not cloning the entire buffer
-                }),
-            );
-        }
-        {
-            // our compute pass will have 2 parts
-            // 1. the pipeline, using the device_fn_mut
-            // 2. the bind group, using the args
-            let mut cpass = encoder.begin_compute_pass(&ComputePassDescriptor { label: None });
-            // first we set the pipeline
-            cpass.set_pipeline(&device_fn_mut.compute_pipeline);
-            // then we apply the bind groups, binding all the arguments
-
-            for (set_num, (_bind_group, offsets)) in args.bind_groups {
-                // bind_group = collection of bindings
-                cpass.set_bind_group(set_num, &bind_groups[set_num as usize], &*offsets);
+                    (
+                        set_num,
+                        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                            label: None, // TODO maybe in all these label fields, we should actually use a label
+                            layout: &device_fn_mut.bind_group_layouts[&set_num],
+                            entries: entries.as_slice(),
+                        }),
+                    )
+                })
+                .collect::<Vec<(u32, wgpu::BindGroup)>>();
+            {
+                // our compute pass will have 2 parts
+                // 1. the pipeline, using the device_fn_mut
+                // 2. the bind group, using the args
+                let mut cpass = encoder.begin_compute_pass(&ComputePassDescriptor { label: None });
+                // first we set the pipeline
+                cpass.set_pipeline(&device_fn_mut.compute_pipeline);
+                // then we apply the bind groups, binding all the arguments
+                // each is bound at its own set number (not at its position among the bind groups)
+                for (set_num, bind_group) in &bind_groups {
+                    // bind_group = collection of bindings
+                    let offsets = args
+                        .bind_groups
+                        .get(set_num)
+                        .map_or(&[][..], |(_bindings, offsets)| offsets.as_slice());
+                    cpass.set_bind_group(*set_num, bind_group, offsets);
+                }
+                // finally we dispatch the compute pass with given work space dims
+                // note that these work space dims would essentially be the same things that are between triple brackets in CUDA
+                cpass.dispatch(work_space_dim.0, work_space_dim.1, work_space_dim.2);
             }
-            // finally we dispatch the compute pass with given work space dims
-            // note that these work space dims would essentially be the same things that are between triple brackets in CUDA
-            cpass.dispatch(work_space_dim.0, work_space_dim.1, work_space_dim.2);
+
+            encoder.finish()
+        });
+        match error {
+            Some(error) => Err(LaunchError::Validation {
+                kernel: device_fn_mut.name.clone(),
+                device: self.info.clone(),
+                message: error,
+            }),
+            None => Ok((command_buffer, lease)),
         }
+    }
 
-        // finally, send the command
-        self.queue.submit(vec![encoder.finish()]);
+    /// Runs the given `DeviceFnMut` like [`call`](#method.call) and then waits for it to complete, giving up after the given timeout
+    ///
+    /// `call` doesn't wait for the launched kernel to finish. This does and so it can act as a watchdog for kernels that might never
+    /// terminate (or drivers that might wedge). If the timeout elapses first, `LaunchError::Timeout` is returned. The kernel may of
+    /// course still be running on the device at that point.
+    ///
+    /// This is unsafe for the same reason `call` is unsafe.
+    /// ```no_run
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*, std::time::Duration};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut device = &mut futures::executor::block_on(Device::all())[0];
+    /// # let shader: Vec<u32> = convert_to_spirv(std::io::Cursor::new(vec![
+    /// #     0x03, 0x02, 0x23, 0x07,    0x00, 0x00, 0x01, 0x00,
+    /// #     0x00, 0x00, 0x00, 0x00,    0x00, 0x00, 0x00, 0x00,
+    /// #     0x00, 0x00, 0x00, 0x00,
+    /// #     0x0e, 0x00, 0x03, 0x00,    0x00, 0x00, 0x00, 0x00,
+    /// #     0x01, 0x00, 0x00, 0x00]))?;
+    /// let shader_compiled = device.compile(ParamsBuilder::new().build(), "main", shader)?;
+    /// unsafe {
+    ///     device.call_with_timeout(&shader_compiled, (1, 1, 1), ArgsBuilder::new().build(), Duration::from_secs(1))?
+    /// };
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub unsafe fn call_with_timeout<'a>(
+        &mut self,
+        device_fn_mut: &DeviceFnMut,
+        grid: impl Into<Grid>,
+        args: DeviceFnMutArgs<'a>,
+        timeout: Duration,
+    ) -> Result<(), LaunchError> {
+        self.call(device_fn_mut, grid, args)?;
+        self.wait_with_timeout(timeout)
+    }
 
+    /// Runs the given `DeviceFnMut` like [`call`](#method.call) and then runs the given callback on the host once it completes
+    ///
+    /// See [`then`](#method.then) for when the callback is run. This is unsafe for the same reason `call` is unsafe.
+    pub unsafe fn call_then<'a, F>(
+        &mut self,
+        device_fn_mut: &DeviceFnMut,
+        grid: impl Into<Grid>,
+        args: DeviceFnMutArgs<'a>,
+        callback: F,
+    ) -> Result<(), LaunchError>
+    where
+        F: FnOnce(Result<(), LaunchError>) + Send + 'static,
+    {
+        self.call(device_fn_mut, grid, args)?;
+        self.then(callback);
         Ok(())
     }
 
+    /// Registers a callback to run on the host once all the work submitted to this device so far has completed
+    ///
+    /// This lets host-side post-processing of one launch be pipelined with the next launches instead of blocking on a download after each one.
+    /// The callback doesn't run on its own. It runs on the thread that next calls [`poll`](#method.poll) or [`wait`](#method.wait) after the work
+    /// has completed. Callbacks run in the order they were registered. The callback is passed `LaunchError::Runtime` if waiting for the work failed.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+    ///     GlslKernel::new()
+    ///         .param_mut::<[f32], _>("float[] data")
+    ///         .with_kernel_code("data[gl_GlobalInvocationID.x] += 1.0;"),
+    /// )?
+    /// .finish()?;
+    /// let mut data: DeviceBox<[f32]> = vec![0.0; 1024].as_device_boxed_mut()?;
+    ///
+    /// let completed = Arc::new(AtomicUsize::new(0));
+    /// let mut device = take()?.lock().unwrap();
+    /// for _ in 0..4 {
+    ///     let completed = completed.clone();
+    ///     unsafe {
+    ///         device.call_then(&kernel, (1024, 1, 1), ArgsBuilder::new().arg(&mut data).build(), move |result| {
+    ///             result.unwrap();
+    ///             completed.fetch_add(1, Ordering::SeqCst);
+    ///         })?;
+    ///     }
+    /// }
+    /// // the host can do other work here and check in on the device with `poll` every now and then
+    /// device.poll();
+    /// device.wait();
+    /// assert_eq!(completed.load(Ordering::SeqCst), 4);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn then<F>(&mut self, callback: F)
+    where
+        F: FnOnce(Result<(), LaunchError>) + Send + 'static,
+    {
+        let (fence, done) = self.submit_fence();
+        self.callbacks
+            .pending
+            .lock()
+            .unwrap()
+            .push(PendingCallback {
+                _fence: fence,
+                done,
+                callback: Box::new(callback),
+            });
+    }
+
+    /// Runs the callbacks (see [`then`](#method.then)) of work that has completed without blocking and returns the number still waiting
+    pub fn poll(&mut self) -> usize {
+        self.device.poll(wgpu::Maintain::Poll);
+        self.run_callbacks()
+    }
+
+    /// Returns the callbacks (see [`then`](#method.then)) that are waiting for their work to complete
+    pub fn callbacks(&self) -> &PendingCallbacks {
+        &self.callbacks
+    }
+
+    /// Blocks until all the work submitted to this device has completed and then runs all the callbacks (see [`then`](#method.then))
+    pub fn wait(&mut self) {
+        self.device.poll(wgpu::Maintain::Wait);
+        self.run_callbacks();
+    }
+
+    // runs the callbacks whose fences have completed (in the order they were registered) and returns the number still waiting
+    fn run_callbacks(&mut self) -> usize {
+        // the callbacks are taken out of the lock before they run so that a callback can't deadlock on it
+        let mut completed = vec![];
+        let mut pending = self.callbacks.pending.lock().unwrap();
+        for mut callback in std::mem::take(&mut *pending) {
+            match (&mut callback.done).now_or_never() {
+                Some(result) => completed.push((callback.callback, result)),
+                None => pending.push(callback),
+            }
+        }
+        let num_pending = pending.len();
+        drop(pending);
+        for (callback, result) in completed {
+            callback(result.map_err(LaunchError::Runtime));
+        }
+        num_pending
+    }
+
+    /// Runs the given closure with a [`Scope`](struct.Scope.html) for launching kernels and then blocks until everything submitted to this device
+    /// has completed
+    ///
+    /// Launches with [`call`](#method.call) are submitted and then forgotten about, so nothing stops a `DeviceBox` from being dropped (or
+    /// overwritten from the host) while a submitted kernel still uses it. The arguments of a launch in a scope instead have to borrow `DeviceBox`s
+    /// that live longer than the whole call to `scope`. Since `scope` only returns after all of the launches complete, the borrow checker makes sure
+    /// that the kernels are done with their `DeviceBox`s before anything else can touch them. This is true even if the closure panics.
+    ///
+    /// `DeviceBox`s should be passed to launches by shared reference (like `&data`). Two arguments that borrow the same `DeviceBox` mutably
+    /// (like `&mut data`) would have to be borrowed at the same time until the scope ends. Whether or not a kernel can write to a `DeviceBox` is
+    /// decided by how the `DeviceBox` was created, just like it is for any other argument.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+    ///     GlslKernel::new()
+    ///         .param_mut::<[f32], _>("float[] data")
+    ///         .with_kernel_code("data[gl_GlobalInvocationID.x] += 1.0;"),
+    /// )?
+    /// .finish()?;
+    /// let data: DeviceBox<[f32]> = vec![0.0; 1024].as_device_boxed_mut()?;
+    ///
+    /// let mut device = take()?.lock().unwrap();
+    /// device.scope(|s| unsafe {
+    ///     s.launch(&spawn(1024), call!(kernel.clone(), &data))?;
+    ///     s.launch(&spawn(1024), call!(kernel.clone(), &data))
+    /// })?;
+    /// drop(device);
+    /// assert_eq!(futures::executor::block_on(data.get())?, vec![2.0; 1024].into_boxed_slice());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// A `DeviceBox` that is borrowed by a launch in a scope can't be dropped until the scope ends.
+    /// ```compile_fail,E0505
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// # let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+    /// #     GlslKernel::new()
+    /// #         .param_mut::<[f32], _>("float[] data")
+    /// #         .with_kernel_code("data[gl_GlobalInvocationID.x] += 1.0;"),
+    /// # )?
+    /// # .finish()?;
+    /// let data: DeviceBox<[f32]> = vec![0.0; 1024].as_device_boxed_mut()?;
+    /// take()?.lock().unwrap().scope(|s| {
+    ///     unsafe { s.launch(&spawn(1024), call!(kernel.clone(), &data))? };
+    ///     drop(data); // the kernel might still be using `data` here
+    ///     Ok::<_, LaunchError>(())
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn scope<'env, F, R>(&mut self, f: F) -> R
+    where
+        F: for<'d> FnOnce(&mut Scope<'d, 'env>) -> R,
+    {
+        // the scope waits when it's dropped so that we still wait if the closure panics
+        let mut scope = Scope {
+            device: self,
+            env: PhantomData,
+        };
+        f(&mut scope)
+    }
+
     /// Compiles a `DeviceFnMut` using the given parameters, entry point name, and SPIR-V program
     ///
     /// The entry point is where in the SPIR-V program the compiled kernel should be entered upon execution.
@@ -580,6 +2727,15 @@ impl Device {
         program_entry: T,
         program: P,
     ) -> Result<DeviceFnMut, CompileError> {
+        // creating a shader module that needs doubles on a device without them would fail validation
+        if requires_f64(program.borrow()) && !self.supports_f64() {
+            return Err(CompileError::UnsupportedF64 {
+                device: self.info.clone(),
+            });
+        }
+        let program_entry = program_entry.into();
+        #[cfg(feature = "record")]
+        let recorded_params = program_params.clone();
         // TODO return a Result with error for compile error
         // TODO use proper error types
         let mut bind_group_layouts: HashMap<u32, wgpu::BindGroupLayout> = HashMap::new();
@@ -609,36 +2765,166 @@ impl Device {
                     }),
             );
         }
+        // the pipeline layout has a bind group layout for each set number in order, up to the largest one
+        // so sets with no parameters that are skipped in the numbering (like set 1 if only 0 and 2 are used) get an empty layout
+        let num_sets = bind_group_layouts
+            .keys()
+            .max()
+            .map_or(0, |set_num| set_num + 1);
+        for set_num in 0..num_sets {
+            if !bind_group_layouts.contains_key(&set_num) {
+                bind_group_layouts.insert(
+                    set_num,
+                    self.device
+                        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                            label: None,
+                            entries: &[],
+                        }),
+                );
+            }
+        }
         let pipeline_layout = self
             .device
             .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: None,
-                bind_group_layouts: bind_group_layouts
-                    .values()
+                bind_group_layouts: (0..num_sets)
+                    .map(|set_num| &bind_group_layouts[&set_num])
                     .collect::<Vec<&wgpu::BindGroupLayout>>()
                     .as_slice(),
                 push_constant_ranges: &[],
             });
-        let pipeline = self
-            .device
-            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-                label: None,
-                layout: Some(&pipeline_layout),
-                // TODO use a Result for this function instead of unwrap_or hack
-                module: &self
-                    .device
-                    .create_shader_module(&wgpu::ShaderModuleDescriptor {
-                        label: None,
-                        source: wgpu::ShaderSource::SpirV(Cow::Borrowed(program.borrow())),
-                        flags: wgpu::ShaderFlags::VALIDATION,
-                    }), // this is where we compile the bytecode program itself
-                entry_point: program_entry.into().as_str(), // this will probably be something like "main" or the name of the main function
+        let (pipeline, error) = self.errors.scope(|| {
+            self.device
+                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: None,
+                    layout: Some(&pipeline_layout),
+                    // TODO use a Result for this function instead of unwrap_or hack
+                    module: &self
+                        .device
+                        .create_shader_module(&wgpu::ShaderModuleDescriptor {
+                            label: None,
+                            source: wgpu::ShaderSource::SpirV(Cow::Borrowed(program.borrow())),
+                            flags: self.shader_flags,
+                        }), // this is where we compile the bytecode program itself
+                    entry_point: program_entry.as_str(), // this will probably be something like "main" or the name of the main function
+                })
+        });
+        if let Some(error) = error {
+            return Err(CompileError::Validation {
+                entry_point: program_entry,
+                device: self.info.clone(),
+                message: error,
             });
-        Ok(DeviceFnMut {
+        }
+        let device_fn_mut = DeviceFnMut {
             param_types,
             bind_group_layouts,
             compute_pipeline: pipeline,
-        })
+            workgroup_size: workgroup_size(program.borrow(), &program_entry),
+            name: Some(program_entry.clone()),
+            id: next_id(),
+        };
+        #[cfg(feature = "record")]
+        crate::record::record(|| crate::record::Event::Compile {
+            kernel: device_fn_mut.id,
+            name: program_entry,
+            params: recorded_params,
+            code: program.borrow().to_vec(),
+        });
+        Ok(device_fn_mut)
+    }
+}
+
+// finds the local size that a SPIR-V program declares for the entry point with the given name
+pub(crate) fn workgroup_size(program: &[u32], entry_point: &str) -> Option<(u32, u32, u32)> {
+    const OP_ENTRY_POINT: u32 = 15;
+    const OP_EXECUTION_MODE: u32 = 16;
+    const EXECUTION_MODE_LOCAL_SIZE: u32 = 17;
+
+    // entry point names come before execution modes so we can look for both in one walk through the instructions
+    let mut entry_point_id = None;
+    let mut i = 5;
+    while i < program.len() {
+        let word_count = (program[i] >> 16) as usize;
+        let opcode = program[i] & 0xffff;
+        if word_count == 0 || i + word_count > program.len() {
+            break;
+        }
+        let operands = &program[i + 1..i + word_count];
+        if opcode == OP_ENTRY_POINT && operands.len() >= 3 {
+            // the name is a nul-terminated string packed into little-endian words
+            let name: Vec<u8> = operands[2..]
+                .iter()
+                .flat_map(|word| word.to_le_bytes().to_vec())
+                .take_while(|byte| *byte != 0)
+                .collect();
+            if name == entry_point.as_bytes() {
+                entry_point_id = Some(operands[1]);
+            }
+        }
+        if opcode == OP_EXECUTION_MODE
+            && operands.len() == 5
+            && Some(operands[0]) == entry_point_id
+            && operands[1] == EXECUTION_MODE_LOCAL_SIZE
+        {
+            return Some((operands[2], operands[3], operands[4]));
+        }
+        i += word_count;
+    }
+    None
+}
+
+// checks if a SPIR-V program declares the Float64 capability
+pub(crate) fn requires_f64(program: &[u32]) -> bool {
+    const OP_CAPABILITY: u32 = 17;
+    const CAPABILITY_FLOAT64: u32 = 10;
+
+    // skip the 5-word header and then walk through instructions
+    // each instruction starts with a word holding its word count (high 16 bits) and opcode (low 16 bits)
+    let mut i = 5;
+    while i < program.len() {
+        let word_count = (program[i] >> 16) as usize;
+        let opcode = program[i] & 0xffff;
+        if opcode == OP_CAPABILITY
+            && word_count == 2
+            && program.get(i + 1) == Some(&CAPABILITY_FLOAT64)
+        {
+            return true;
+        }
+        if word_count == 0 {
+            break;
+        }
+        i += word_count;
+    }
+    false
+}
+
+/// A scope that kernels are launched in, which waits for all of them to complete before it ends
+///
+/// See [`Device::scope`](struct.Device.html#method.scope) for more.
+pub struct Scope<'d, 'env> {
+    pub(crate) device: &'d mut Device,
+    // invariant so that the arguments of launches can't borrow anything that lives for less than the whole scope
+    env: PhantomData<&'env mut &'env ()>,
+}
+
+impl<'d, 'env> Scope<'d, 'env> {
+    /// Runs the given `DeviceFnMut` like [`Device::call`](struct.Device.html#method.call) with arguments that must outlive the scope
+    ///
+    /// This is unsafe for the same reason `Device::call` is unsafe.
+    pub unsafe fn call(
+        &mut self,
+        device_fn_mut: &DeviceFnMut,
+        grid: impl Into<Grid>,
+        args: DeviceFnMutArgs<'env>,
+    ) -> Result<(), LaunchError> {
+        self.device.call(device_fn_mut, grid, args)
+    }
+}
+
+impl<'d, 'env> Drop for Scope<'d, 'env> {
+    fn drop(&mut self) {
+        self.device.wait();
     }
 }
 
@@ -747,52 +3033,408 @@ pub fn convert_to_spirv<T: Read + Seek>(src: T) -> Result<Vec<u32>, std::io::Err
 /// Emu keeps tracks of whether or not data is mutable as well as their type to ensure that data is safely passed back and forth to and from
 /// kernels running on a GPU.
 ///
+/// `DeviceBox<T>` is `Send` and `Sync` whenever `T` is, so it can be shared across threads (for example, in an `Arc`).
+/// Writes (like [`set`](#method.set)) take `&mut self` so the borrow checker already keeps them from racing with anything else.
+/// Downloads (like [`get`](#method.get)) only take `&self` but they all go through the same staging buffer. So each `DeviceBox` has
+/// an internal lock that downloads hold while they use the staging buffer, which means concurrent downloads of the same `DeviceBox`
+/// happen one after another instead of corrupting each other. If mapping the staging buffer fails (or a download gives up on it with a timeout),
+/// the staging buffer is replaced with a new one before the next download. So one failed download doesn't make every later download fail. Kernels can also be launched from many threads at once since each
+/// launch locks the device it runs on from the global pool.
+/// ```
+/// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # futures::executor::block_on(assert_device_pool_initialized());
+/// let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+///     GlslKernel::new()
+///         .param_mut::<[f32], _>("float[] data")
+///         .with_kernel_code("data[gl_GlobalInvocationID.x] = data[gl_GlobalInvocationID.x] + 1.0;"),
+/// )?
+/// .finish()?;
+///
+/// // each thread launches the same kernel on its own data
+/// let threads: Vec<_> = (0..4)
+///     .map(|i| {
+///         let kernel = kernel.clone();
+///         std::thread::spawn(move || {
+///             let mut data: DeviceBox<[f32]> = vec![i as f32; 1024].as_device_boxed_mut().unwrap();
+///             unsafe {
+///                 spawn(1024).launch(call!(kernel, &mut data)).unwrap();
+///             }
+///             futures::executor::block_on(data.get()).unwrap()
+///         })
+///     })
+///     .collect();
+/// for (i, thread) in threads.into_iter().enumerate() {
+///     assert_eq!(thread.join().unwrap(), vec![i as f32 + 1.0; 1024].into_boxed_slice());
+/// }
+///
+/// // and many threads can download the same data at once
+/// let shared = std::sync::Arc::new(vec![0.5f32; 1024].as_device_boxed_mut()?);
+/// let threads: Vec<_> = (0..4)
+///     .map(|_| {
+///         let shared = shared.clone();
+///         std::thread::spawn(move || futures::executor::block_on(shared.get()).unwrap())
+///     })
+///     .collect();
+/// for thread in threads {
+///     assert_eq!(thread.join().unwrap(), vec![0.5; 1024].into_boxed_slice());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
 /// Also, `DeviceBox` implements `From` and `Into` to help you switch between `DeviceBox` and its WebGPU internals if you want to.
 /// The WebGPU internals are encapsulated in a 4-tuple corresponding simply to the staging buffer, storage buffer, and size in bytes respectively (there is also an optional mutability marker).
 /// You should ignore the staging buffer for now since we are working towards replacing 1 staging buffer per `DeviceBox` with a global pool of staging buffers
 /// that is shared by all `DeviceBox`s..
+/// A `DeviceBox` that was allocated from a slab (see [`Device::allocate_from_slabs`](struct.Device.html#method.allocate_from_slabs)) shares its buffers
+/// with other `DeviceBox`s, so converting it into its WebGPU internals panics.
 pub struct DeviceBox<T>
 where
     T: ?Sized,
 {
-    pub(crate) staging_buffer: wgpu::Buffer,
-    pub(crate) storage_buffer: wgpu::Buffer,
-    pub(crate) size: u64, // inv: size being constant and equal to sizes of staging, storage buffers respectively
+    pub(crate) staging: Arc<Mutex<Staging>>, // locked by downloads while they map and read the staging buffer, shared by everything using the staging buffer
+    pub(crate) storage_buffer: Arc<wgpu::Buffer>,
+    pub(crate) offset: u64, // where the data starts in both buffers, which is only not 0 for a DeviceBox allocated from a slab
+    pub(crate) size: u64, // inv: size being constant and equal to sizes of staging, storage buffers respectively (unless allocated from a slab)
     pub(crate) phantom: PhantomData<T>,
     pub(crate) mutability: Option<Mutability>, // TODO for now constant scalars are passed in as storage buffers
-                                               // this is fine for now but in the future we should allow a DeviceBox to potentially use a uniform for small sizes of constant data
-                                               // this optimization would make memory transfer faster (maybe)
+    // this is fine for now but in the future we should allow a DeviceBox to potentially use a uniform for small sizes of constant data
+    // this optimization would make memory transfer faster (maybe)
+    pub(crate) usage: wgpu::BufferUsage, // the usage of the storage buffer
+    pub(crate) allocation: Option<SlabAllocation>, // the block of a slab this is allocated in, which is freed when this is dropped
+    pub(crate) id: u64, // unique among all DeviceBox's, used for recording which buffers API calls use
+}
+
+// the next id to give to a DeviceBox or a DeviceFnMut
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+pub(crate) fn next_id() -> u64 {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+// the size of a buffer that holds the given number of bytes
+// buffers are padded to a multiple of 4 bytes so that the bytes at the end of an oddly sized DeviceBox can be written
+fn padded_size(size: u64) -> u64 {
+    (size + wgpu::COPY_BUFFER_ALIGNMENT - 1) / wgpu::COPY_BUFFER_ALIGNMENT
+        * wgpu::COPY_BUFFER_ALIGNMENT
+}
+
+// the given bytes padded with zeros to a multiple of 4 bytes, which is what writes to buffers must be
+fn padded_bytes(bytes: &[u8]) -> std::borrow::Cow<'_, [u8]> {
+    if bytes.len() as u64 == padded_size(bytes.len() as u64) {
+        std::borrow::Cow::Borrowed(bytes)
+    } else {
+        let mut padded = bytes.to_vec();
+        padded.resize(padded_size(bytes.len() as u64) as usize, 0);
+        std::borrow::Cow::Owned(padded)
+    }
+}
+
+// a DeviceBox, a DeviceFnMut, and a Device can all be shared across threads
+// this fails to compile if that ever stops being true
+#[allow(dead_code)]
+fn assert_send_sync() {
+    fn is_send_sync<T: Send + Sync + ?Sized>() {}
+    is_send_sync::<DeviceBox<[f32]>>();
+    is_send_sync::<DeviceBox<u32>>();
+    is_send_sync::<DeviceFnMut>();
+    is_send_sync::<Device>();
 }
 
 impl<T: ?Sized> From<(wgpu::Buffer, wgpu::Buffer, u64, Option<Mutability>)> for DeviceBox<T> {
     fn from(wgpu_stuff: (wgpu::Buffer, wgpu::Buffer, u64, Option<Mutability>)) -> Self {
         Self {
-            staging_buffer: wgpu_stuff.0,
-            storage_buffer: wgpu_stuff.1,
+            staging: Arc::new(Mutex::new(Staging {
+                buffer: wgpu_stuff.0,
+                size: wgpu_stuff.2,
+                label: None,
+                poisoned: false,
+            })),
+            storage_buffer: Arc::new(wgpu_stuff.1),
+            offset: 0,
             size: wgpu_stuff.2,
             phantom: PhantomData,
             mutability: wgpu_stuff.3,
+            usage: DeviceBoxOptions::new().usage(),
+            allocation: None,
+            id: next_id(),
         }
     }
 }
 
 impl<T: ?Sized> Into<(wgpu::Buffer, wgpu::Buffer, u64, Option<Mutability>)> for DeviceBox<T> {
     fn into(self) -> (wgpu::Buffer, wgpu::Buffer, u64, Option<Mutability>) {
+        assert!(
+            self.allocation.is_none(),
+            "a `DeviceBox` allocated from a slab shares its buffers so it can't be converted into them"
+        );
         (
-            self.staging_buffer,
-            self.storage_buffer,
+            Arc::try_unwrap(self.staging)
+                .ok()
+                .unwrap()
+                .into_inner()
+                .buffer,
+            Arc::try_unwrap(self.storage_buffer).unwrap(),
             self.size,
             self.mutability,
         )
     }
 }
 
+impl<T: ?Sized> DeviceBox<T> {
+    // changes the type of this without changing its buffers (or its id, so that recorded API calls still refer to the same buffer)
+    #[cfg(feature = "pool")]
+    pub(crate) fn retype<U: ?Sized>(self) -> DeviceBox<U> {
+        DeviceBox {
+            staging: self.staging,
+            storage_buffer: self.storage_buffer,
+            offset: self.offset,
+            size: self.size,
+            phantom: PhantomData,
+            mutability: self.mutability,
+            usage: self.usage,
+            allocation: self.allocation,
+            id: self.id,
+        }
+    }
+
+    // the part of the given staging buffer (the staging buffer of this, locked) this downloads into
+    // this is padded to a multiple of 4 bytes so only the first `size` bytes of it are data
+    fn staging_slice<'a>(&self, staging: &'a Staging) -> wgpu::BufferSlice<'a> {
+        staging
+            .buffer
+            .slice(self.offset..self.offset + padded_size(self.size))
+    }
+}
+
+impl<T> DeviceBox<[T]> {
+    /// Returns the number of elements
+    pub fn len(&self) -> usize {
+        self.size as usize / std::mem::size_of::<T>()
+    }
+
+    /// Returns whether or not there are no elements
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+}
+
+/// A space of threads described by its global size and the size of each thread block
+///
+/// `Grid` does the math of figuring out how many thread blocks to spawn so you don't have to divide and round up at every call site.
+/// You can turn a `Grid` into a [`Spawner`](../spawn/struct.Spawner.html) with `Spawner::from` and launch with that or pass it to
+/// [`Device::call`](struct.Device.html#method.call) directly. A tuple of the number of thread blocks to spawn in each dimension can be used
+/// wherever a `Grid` can. If the size of each thread block isn't set, the size the kernel was compiled with is used.
+/// ```
+/// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// futures::executor::block_on(assert_device_pool_initialized());
+///
+/// // 1000 is not a multiple of 256 so some threads will be out of bounds
+/// let mut data_on_gpu: DeviceBox<[f32]> = vec![1.0; 1000].as_device_boxed_mut()?;
+/// let grid = Grid::linear(1000).with_local(256);
+/// assert_eq!(grid.work_space_dim(), (4, 1, 1));
+///
+/// // the global size is passed in for the parameter that's declared with `with_grid_size`
+/// let kernel: GlslKernel = GlslKernel::new()
+///     .spawn(256)
+///     .param_mut::<[f32], _>("float[] data")
+///     .with_grid_size()
+///     .with_kernel_code(r#"
+/// if (gl_GlobalInvocationID.x < grid_size.x) {
+///     data[gl_GlobalInvocationID.x] = data[gl_GlobalInvocationID.x] * 2.0;
+/// }
+///     "#);
+/// let c = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(kernel)?.finish()?;
+/// unsafe {
+///     Spawner::from(grid).launch(call!(c.clone(), &mut data_on_gpu))?;
+/// }
+///
+/// // without a local size, the size the kernel was compiled with is used and any other size is an error
+/// unsafe {
+///     Spawner::from(Grid::linear(1000)).launch(call!(c.clone(), &mut data_on_gpu))?;
+///     let result = Spawner::from(Grid::linear(1000).with_local(128)).launch(call!(c, &mut data_on_gpu));
+///     assert!(matches!(result, Err(LaunchError::LocalSizeMismatch { .. })));
+/// }
+///
+/// assert_eq!(futures::executor::block_on(data_on_gpu.get())?, vec![4.0; 1000].into_boxed_slice());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Grid {
+    global_size: (u32, u32, u32),
+    // if this isn't set, it is the size of the thread blocks of the kernel launched over this
+    local_size: Option<(u32, u32, u32)>,
+    grid_stride: bool,
+    // whether this was made from a number of thread blocks, in which case the global size is that number of thread blocks
+    blocks: bool,
+}
+
+impl Grid {
+    /// Creates a 1-dimensional grid of the given size
+    pub fn linear(size: u32) -> Self {
+        Self::dim3(size, 1, 1)
+    }
+
+    /// Creates a 2-dimensional grid of the given width and height
+    pub fn dim2(width: u32, height: u32) -> Self {
+        Self::dim3(width, height, 1)
+    }
+
+    /// Creates a 3-dimensional grid of the given width, height, and depth
+    pub fn dim3(width: u32, height: u32, depth: u32) -> Self {
+        Self {
+            global_size: (width, height, depth),
+            local_size: None,
+            grid_stride: false,
+            blocks: false,
+        }
+    }
+
+    /// Sets the size of each thread block in the "x" dimension
+    ///
+    /// This must be the same as the size that the kernel was compiled with (e.g. - with [`GlslKernel::spawn`](../compile_impls/struct.GlslKernel.html#method.spawn)).
+    /// Launching a kernel compiled with a different size fails with `LaunchError::LocalSizeMismatch`. If the local size isn't set, the size
+    /// the kernel was compiled with is used.
+    pub fn with_local(self, x: u32) -> Self {
+        self.with_local_3d(x, 1, 1)
+    }
+
+    /// Sets the size of each thread block in the "x" and "y" dimensions
+    pub fn with_local_2d(self, x: u32, y: u32) -> Self {
+        self.with_local_3d(x, y, 1)
+    }
+
+    /// Sets the size of each thread block in the "x", "y", and "z" dimensions
+    pub fn with_local_3d(mut self, x: u32, y: u32, z: u32) -> Self {
+        assert!(
+            x > 0 && y > 0 && z > 0,
+            "the size of a thread block should not be 0 in any dimension"
+        );
+        self.local_size = Some((x, y, z));
+        self
+    }
+
+    /// Caps the number of thread blocks spawned in the "x" dimension at the most that can be spawned in a dimension
+    ///
+    /// Without this, launching over a grid with more thread blocks in a dimension than the device can spawn (65535 on some backends) fails with
+    /// `LaunchError::LimitExceeded`. With this, the kernel has to go over the rest of the grid itself with a grid-stride loop. Kernels compiled
+    /// with [`GlslKernel::with_grid_stride`](../compile_impls/struct.GlslKernel.html#method.with_grid_stride) do that. Only the "x" dimension is
+    /// capped so billion-element arrays should be laid out along it.
+    pub fn with_grid_stride(mut self) -> Self {
+        self.grid_stride = true;
+        self
+    }
+
+    /// Returns the number of threads in each dimension
+    pub fn global_size(&self) -> (u32, u32, u32) {
+        self.global_size
+    }
+
+    /// Returns the number of threads in each dimension of each thread block, if it was set
+    pub fn local_size(&self) -> Option<(u32, u32, u32)> {
+        self.local_size
+    }
+
+    /// Returns the number of thread blocks to spawn in each dimension
+    ///
+    /// This is the global size divided by the local size, rounded up. So there may be more threads spawned than the global size. If this is a
+    /// grid-stride grid (see [`with_grid_stride`](#method.with_grid_stride)), there may be fewer thread blocks spawned in the "x" dimension.
+    /// If the local size isn't set, this is for thread blocks of 1 thread.
+    pub fn work_space_dim(&self) -> (u32, u32, u32) {
+        self.work_space_dim_with(self.local_size.unwrap_or((1, 1, 1)))
+    }
+}
+
+impl Grid {
+    fn work_space_dim_with(&self, local_size: (u32, u32, u32)) -> (u32, u32, u32) {
+        let x = div_round_up(self.global_size.0, local_size.0);
+        (
+            if self.grid_stride {
+                x.min(MAX_WORKGROUPS_PER_DIMENSION)
+            } else {
+                x
+            },
+            div_round_up(self.global_size.1, local_size.1),
+            div_round_up(self.global_size.2, local_size.2),
+        )
+    }
+
+    // the number of thread blocks to spawn in each dimension to launch the given kernel over this
+    // the local size set for this is checked against the size of the kernel's thread blocks and defaults to it
+    pub(crate) fn work_space_dim_for(
+        &self,
+        device_fn_mut: &DeviceFnMut,
+    ) -> Result<(u32, u32, u32), LaunchError> {
+        // a grid made from a number of thread blocks already is the number of thread blocks
+        if self.blocks {
+            return Ok(self.global_size);
+        }
+        let local_size = match (self.local_size, device_fn_mut.workgroup_size) {
+            (Some(local_size), Some(workgroup_size)) if local_size != workgroup_size => {
+                return Err(LaunchError::LocalSizeMismatch {
+                    grid: local_size,
+                    kernel: workgroup_size,
+                })
+            }
+            (Some(local_size), _) | (None, Some(local_size)) => local_size,
+            // without knowing the size of the kernel's thread blocks, each thread block is assumed to be 1 thread like it is by default in GLSL
+            (None, None) => (1, 1, 1),
+        };
+        Ok(self.work_space_dim_with(local_size))
+    }
+
+    // the global size that is passed in to the given kernel if it declares its grid size
+    // a grid made from a number of thread blocks covers every thread spawned, which is only known if the size of the kernel's thread blocks is
+    pub(crate) fn grid_size_for(
+        &self,
+        device_fn_mut: &DeviceFnMut,
+    ) -> Result<(u32, u32, u32), LaunchError> {
+        if self.blocks {
+            let (x, y, z) = device_fn_mut
+                .workgroup_size
+                .ok_or(LaunchError::UnknownGridSize)?;
+            let dim = |blocks: u32, threads: u32| {
+                blocks
+                    .checked_mul(threads)
+                    .ok_or(LaunchError::LimitExceeded {
+                        limit: "threads in a dimension of the grid",
+                        requested: blocks as u64 * threads as u64,
+                        max: u32::MAX as u64,
+                    })
+            };
+            Ok((
+                dim(self.global_size.0, x)?,
+                dim(self.global_size.1, y)?,
+                dim(self.global_size.2, z)?,
+            ))
+        } else {
+            Ok(self.global_size)
+        }
+    }
+}
+
+/// A number of thread blocks to spawn in each dimension
+impl From<(u32, u32, u32)> for Grid {
+    fn from(work_space_dim: (u32, u32, u32)) -> Self {
+        Self {
+            blocks: true,
+            ..Self::dim3(work_space_dim.0, work_space_dim.1, work_space_dim.2)
+        }
+    }
+}
+
+fn div_round_up(n: u32, d: u32) -> u32 {
+    n / d + if n % d == 0 { 0 } else { 1 }
+}
+
 /// Represents a compiled kernel that can then be launched across spawned threads with [`Device::call`](struct.Device.html#method.call) or [`spawn`](../spawn/fn.spawn.html)
 ///
 /// While compiling a `DeviceFnMut` is expensive, running a `DeviceFnMut` with varying work space dimensions or arguments incurs no significant extra compilation.
 /// There isn't really much you will need to do with this. Just know that this is basically the final compiled kernel. It's the end of the compilation pipeline (it's generated
 /// from SPIR-V) and is the input to the execution of your kernel.
-#[derive(From, Into)]
 pub struct DeviceFnMut {
     // we really just need 2 things to define a function
     // 1. the layout of input buffers to be bound (think of this as declaring the parameters of the function)
@@ -801,6 +3443,133 @@ pub struct DeviceFnMut {
     pub(crate) param_types: HashMap<u32, HashMap<u32, ArgAndParamInfo>>, // you can just set all types to None if you don't care about type checking
     pub(crate) bind_group_layouts: HashMap<u32, wgpu::BindGroupLayout>,  // u32 = set number
     pub(crate) compute_pipeline: wgpu::ComputePipeline, // inv: has PipelineLayout consistent with above BindGroupLayout's
+    pub(crate) workgroup_size: Option<(u32, u32, u32)>, // reflected from the SPIR-V, if it could be found
+    pub(crate) name: Option<String>, // the name of the entry point, if it is known, for the messages of errors
+    #[cfg_attr(not(feature = "record"), allow(dead_code))]
+    pub(crate) id: u64, // unique among all DeviceFnMut's, used for recording which kernels API calls use
+}
+
+impl
+    From<(
+        HashMap<u32, HashMap<u32, ArgAndParamInfo>>,
+        HashMap<u32, wgpu::BindGroupLayout>,
+        wgpu::ComputePipeline,
+    )> for DeviceFnMut
+{
+    fn from(
+        wgpu_stuff: (
+            HashMap<u32, HashMap<u32, ArgAndParamInfo>>,
+            HashMap<u32, wgpu::BindGroupLayout>,
+            wgpu::ComputePipeline,
+        ),
+    ) -> Self {
+        Self {
+            param_types: wgpu_stuff.0,
+            bind_group_layouts: wgpu_stuff.1,
+            compute_pipeline: wgpu_stuff.2,
+            workgroup_size: None,
+            name: None,
+            id: next_id(),
+        }
+    }
+}
+
+impl
+    Into<(
+        HashMap<u32, HashMap<u32, ArgAndParamInfo>>,
+        HashMap<u32, wgpu::BindGroupLayout>,
+        wgpu::ComputePipeline,
+    )> for DeviceFnMut
+{
+    fn into(
+        self,
+    ) -> (
+        HashMap<u32, HashMap<u32, ArgAndParamInfo>>,
+        HashMap<u32, wgpu::BindGroupLayout>,
+        wgpu::ComputePipeline,
+    ) {
+        (
+            self.param_types,
+            self.bind_group_layouts,
+            self.compute_pipeline,
+        )
+    }
+}
+
+impl DeviceFnMut {
+    // the set number and binding number of the parameter the grid size is passed in for, if the kernel declares one
+    pub(crate) fn grid_size_param(&self) -> Option<(u32, u32)> {
+        self.param_types.iter().find_map(|(set_num, set)| {
+            set.iter()
+                .find(|(_, info)| info.is_grid_size())
+                .map(|(binding_num, _)| (*set_num, *binding_num))
+        })
+    }
+
+    /// Returns the size of each thread block (workgroup) of this kernel
+    ///
+    /// This is the local size declared by the kernel (like with `layout(local_size_x = 64) in;` in GLSL), as found in its SPIR-V. It is
+    /// `None` if the kernel wasn't compiled with [`Device::compile`](struct.Device.html#method.compile) or if its SPIR-V doesn't declare a
+    /// constant local size.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+    ///     GlslKernel::new()
+    ///         .spawn(64)
+    ///         .param_mut::<[f32], _>("float[] data")
+    ///         .with_kernel_code("data[gl_GlobalInvocationID.x] = 1.0;"),
+    /// )?
+    /// .finish()?;
+    /// assert_eq!(kernel.workgroup_size(), Some((64, 1, 1)));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn workgroup_size(&self) -> Option<(u32, u32, u32)> {
+        self.workgroup_size
+    }
+
+    /// Returns how many thread blocks to launch in each dimension so that at least the given number of threads run this kernel
+    ///
+    /// Each thread block has [`workgroup_size`](#method.workgroup_size) threads (or 1 thread if that isn't known). Thread blocks are launched
+    /// along the first dimension until there are too many for one dimension and then the rest are spread along the second dimension. So the
+    /// kernel should compute the index of each thread from `gl_GlobalInvocationID.x` and `gl_GlobalInvocationID.y` and check that it is less
+    /// than `total_items` since a few more threads than asked for may run.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+    ///     GlslKernel::new()
+    ///         .spawn(64)
+    ///         .param_mut::<[f32], _>("float[] data")
+    ///         .with_kernel_code("if (gl_GlobalInvocationID.x < data.length()) data[gl_GlobalInvocationID.x] = 1.0;"),
+    /// )?
+    /// .finish()?;
+    /// let mut data: DeviceBox<[f32]> = vec![0.0; 1000].as_device_boxed_mut()?;
+    /// let (x, y, z) = kernel.suggest_dispatch(1000);
+    /// assert_eq!((x, y, z), (16, 1, 1));
+    /// unsafe { spawn(x).spawn(y).spawn(z).launch(call!(kernel, &mut data))?; }
+    /// assert_eq!(futures::executor::block_on(data.get())?, vec![1.0; 1000].into_boxed_slice());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn suggest_dispatch(&self, total_items: u32) -> (u32, u32, u32) {
+        let (x, y, z) = self.workgroup_size.unwrap_or((1, 1, 1));
+        let threads_per_workgroup = (x as u64 * y as u64 * z as u64).max(1);
+        let num_workgroups =
+            ((total_items as u64 + threads_per_workgroup - 1) / threads_per_workgroup) as u32;
+        if num_workgroups <= MAX_WORKGROUPS_PER_DIMENSION {
+            (num_workgroups.max(1), 1, 1)
+        } else {
+            (
+                MAX_WORKGROUPS_PER_DIMENSION,
+                (num_workgroups + MAX_WORKGROUPS_PER_DIMENSION - 1) / MAX_WORKGROUPS_PER_DIMENSION,
+                1,
+            )
+        }
+    }
 }
 
 /// Describes the parameters that can be passed to a `DeviceFnMut`
@@ -831,9 +3600,16 @@ impl Hash for DeviceFnMutParams {
 impl DeviceFnMutParams {
     /// Constructs a set of parameters where each parameter is mutable
     pub fn new(num_params: usize) -> Self {
+        Self::with_mutabilities(&vec![Mutability::Mut; num_params])
+    }
+
+    /// Constructs a set of parameters with the given mutabilities
+    ///
+    /// Each constant parameter is bound as a read-only storage buffer. So the kernel must declare it as read-only (like with `readonly` in GLSL).
+    pub fn with_mutabilities(mutabilities: &[Mutability]) -> Self {
         let mut bind_group_layouts = HashMap::new();
         let mut binding_layouts = HashMap::new();
-        for _ in 0..num_params {
+        for mutability in mutabilities {
             let new_binding_layout_idx = binding_layouts.len() as u32;
             binding_layouts.insert(
                 new_binding_layout_idx,
@@ -842,9 +3618,11 @@ impl DeviceFnMutParams {
                         binding: new_binding_layout_idx,
                         visibility: wgpu::ShaderStage::COMPUTE,
                         ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            ty: wgpu::BufferBindingType::Storage {
+                                read_only: *mutability == Mutability::Const,
+                            },
                             has_dynamic_offset: false,
-                            min_binding_size: None, // for now, this is just always mutable bc I don't know if readonly is more performant
+                            min_binding_size: None,
                         },
                         count: None,
                     },
@@ -856,6 +3634,285 @@ impl DeviceFnMutParams {
 
         Self { bind_group_layouts }
     }
+
+    /// Returns the layout of the parameter at the given set number and binding number, if there is one
+    ///
+    /// Constant parameters are laid out as read-only storage buffers and mutable ones as writable storage buffers.
+    /// ```
+    /// # use emu_core::prelude::*;
+    /// fn is_read_only(params: &DeviceFnMutParams, binding_num: u32) -> bool {
+    ///     match params.get_layout_entry(0, binding_num).unwrap().ty {
+    ///         wgpu::BindingType::Buffer {
+    ///             ty: wgpu::BufferBindingType::Storage { read_only },
+    ///             ..
+    ///         } => read_only,
+    ///         _ => panic!("expected a storage buffer"),
+    ///     }
+    /// }
+    ///
+    /// let params = DeviceFnMutParams::with_mutabilities(&[Mutability::Mut, Mutability::Const]);
+    /// assert!(!is_read_only(&params, 0));
+    /// assert!(is_read_only(&params, 1));
+    ///
+    /// let params = ParamsBuilder::new()
+    ///     .param::<[f32]>(Mutability::Const)
+    ///     .param::<[f32]>(Mutability::Mut)
+    ///     .param::<f32>(Mutability::Const)
+    ///     .build();
+    /// assert!(is_read_only(&params, 0));
+    /// assert!(!is_read_only(&params, 1));
+    /// assert!(is_read_only(&params, 2));
+    /// assert!(params.get_layout_entry(0, 3).is_none());
+    /// ```
+    pub fn get_layout_entry(
+        &self,
+        set_num: u32,
+        binding_num: u32,
+    ) -> Option<&wgpu::BindGroupLayoutEntry> {
+        self.bind_group_layouts
+            .get(&set_num)?
+            .get(&binding_num)
+            .map(|(entry, _)| entry)
+    }
+
+    /// Adds a parameter with the given layout to the bind group with the given set number
+    ///
+    /// This is for kernels with parameters that [`ParamsBuilder`](struct.ParamsBuilder.html) can't describe (like a uniform buffer). To put
+    /// parameters that `ParamsBuilder` can describe in a bind group other than 0, use [`with_set`](#method.with_set). The binding number is the one in the entry and a parameter already at that binding number
+    /// is replaced. Since there is no type or mutability for the parameter, arguments passed for it aren't checked.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// let params = DeviceFnMutParams::new(0).with_layout_entry(
+    ///     1,
+    ///     wgpu::BindGroupLayoutEntry {
+    ///         binding: 0,
+    ///         visibility: wgpu::ShaderStage::COMPUTE,
+    ///         ty: wgpu::BindingType::Buffer {
+    ///             ty: wgpu::BufferBindingType::Uniform,
+    ///             has_dynamic_offset: false,
+    ///             min_binding_size: None,
+    ///         },
+    ///         count: None,
+    ///     },
+    /// );
+    /// ```
+    pub fn with_layout_entry(mut self, set_num: u32, entry: wgpu::BindGroupLayoutEntry) -> Self {
+        self.bind_group_layouts
+            .entry(set_num)
+            .or_insert_with(HashMap::new)
+            .insert(entry.binding, (entry, ArgAndParamInfo::default()));
+        self
+    }
+
+    /// Puts the parameters built by the given `ParamsBuilder` in the bind group with the given set number
+    ///
+    /// This replaces any parameters already in that bind group. Set numbers don't have to be contiguous. A kernel may use sets 0 and 2
+    /// without declaring anything in set 1, in which case the bind group at set 1 is left empty. Arguments are then passed in the matching
+    /// set numbers with [`DeviceFnMutArgs::with_set`](struct.DeviceFnMutArgs.html#method.with_set).
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let mut kernel = compile::<Glsl, GlslCompile, _, GlobalCache>(Glsl::new().set_code_with_glsl(r#"
+    /// #version 450
+    /// layout(local_size_x = 1) in;
+    ///
+    /// layout(set = 0, binding = 0) buffer Data {
+    ///     float[] data;
+    /// };
+    /// layout(set = 2, binding = 0) readonly buffer Scalar {
+    ///     float scalar;
+    /// };
+    ///
+    /// void main() {
+    ///     data[gl_GlobalInvocationID.x] *= scalar;
+    /// }
+    /// "#))?;
+    /// if let Some(params) = kernel.get_params_mut() {
+    ///     *params = DeviceFnMutParams::new(0)
+    ///         .with_set(0, ParamsBuilder::new().param::<[f32]>(Mutability::Mut))
+    ///         .with_set(2, ParamsBuilder::new().param::<f32>(Mutability::Const));
+    /// }
+    /// let kernel = kernel.finish()?;
+    ///
+    /// let mut data: DeviceBox<[f32]> = vec![1.0; 64].as_device_boxed_mut()?;
+    /// let scalar: DeviceBox<f32> = DeviceBox::new(3.0)?;
+    /// let args = DeviceFnMutArgs::new()
+    ///     .with_set(0, ArgsBuilder::new().arg(&mut data))
+    ///     .with_set(2, ArgsBuilder::new().arg(&scalar));
+    /// unsafe { take()?.lock().unwrap().call(&kernel, (64, 1, 1), args)?; }
+    /// assert_eq!(futures::executor::block_on(data.get())?, vec![3.0; 64].into_boxed_slice());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_set(mut self, set_num: u32, params: ParamsBuilder) -> Self {
+        self.bind_group_layouts
+            .insert(set_num, params.binding_layouts);
+        self
+    }
+}
+
+// what follows is for reading and writing parameters (and other things) as bytes
+// these are used for saving DeviceBox's and Spirv's to files
+
+// removes the given number of bytes from the front of the given bytes and returns them
+#[cfg(any(feature = "pool", feature = "record"))]
+pub(crate) fn read_bytes<'a>(bytes: &mut &'a [u8], n: usize) -> Result<&'a [u8], PersistError> {
+    if bytes.len() < n {
+        return Err(PersistError::InvalidHeader);
+    }
+    let (read, rest) = bytes.split_at(n);
+    *bytes = rest;
+    Ok(read)
+}
+
+#[cfg(any(feature = "pool", feature = "record"))]
+pub(crate) fn read_u32(bytes: &mut &[u8]) -> Result<u32, PersistError> {
+    Ok(u32::from_le_bytes(
+        read_bytes(bytes, 4)?.try_into().unwrap(),
+    ))
+}
+
+#[cfg(any(feature = "pool", feature = "record"))]
+pub(crate) fn read_u64(bytes: &mut &[u8]) -> Result<u64, PersistError> {
+    Ok(u64::from_le_bytes(
+        read_bytes(bytes, 8)?.try_into().unwrap(),
+    ))
+}
+
+#[cfg(any(feature = "pool", feature = "record"))]
+pub(crate) fn read_string(bytes: &mut &[u8]) -> Result<String, PersistError> {
+    let len = read_u32(bytes)? as usize;
+    std::str::from_utf8(read_bytes(bytes, len)?)
+        .map(String::from)
+        .map_err(|_| PersistError::InvalidHeader)
+}
+
+#[cfg(any(feature = "pool", feature = "record"))]
+pub(crate) fn write_string(out: &mut Vec<u8>, string: &str) {
+    out.extend_from_slice(&(string.len() as u32).to_le_bytes());
+    out.extend_from_slice(string.as_bytes());
+}
+
+#[cfg(any(feature = "extras", feature = "record"))]
+impl DeviceFnMutParams {
+    // appends the parameters as bytes
+    // only buffers can be parameters of a kernel that is written
+    pub(crate) fn write_to(&self, out: &mut Vec<u8>) -> Result<(), PersistError> {
+        let mut set_nums = self.bind_group_layouts.keys().collect::<Vec<_>>();
+        set_nums.sort();
+        out.extend_from_slice(&(set_nums.len() as u32).to_le_bytes());
+        for set_num in set_nums {
+            let set = &self.bind_group_layouts[set_num];
+            let mut binding_nums = set.keys().collect::<Vec<_>>();
+            binding_nums.sort();
+            out.extend_from_slice(&set_num.to_le_bytes());
+            out.extend_from_slice(&(binding_nums.len() as u32).to_le_bytes());
+            for binding_num in binding_nums {
+                let (entry, info) = &set[binding_num];
+                let (ty, has_dynamic_offset, min_binding_size) = match entry.ty {
+                    wgpu::BindingType::Buffer {
+                        ty,
+                        has_dynamic_offset,
+                        min_binding_size,
+                    } => (
+                        match ty {
+                            wgpu::BufferBindingType::Storage { read_only: false } => 0u8,
+                            wgpu::BufferBindingType::Storage { read_only: true } => 1,
+                            wgpu::BufferBindingType::Uniform => 2,
+                        },
+                        has_dynamic_offset,
+                        min_binding_size.map_or(0, |size| size.get()),
+                    ),
+                    _ => {
+                        return Err(PersistError::Unsupported(
+                            "only buffers can be parameters of a kernel that is saved",
+                        ))
+                    }
+                };
+                out.extend_from_slice(&binding_num.to_le_bytes());
+                out.push(ty);
+                out.push(has_dynamic_offset as u8);
+                out.extend_from_slice(&min_binding_size.to_le_bytes());
+                match &info.type_name {
+                    Some(type_name) => {
+                        out.push(1);
+                        write_string(out, type_name);
+                    }
+                    None => out.push(0),
+                }
+                out.push(match info.mutability {
+                    None => 0,
+                    Some(Mutability::Mut) => 1,
+                    Some(Mutability::Const) => 2,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // forgets the type of each parameter so that any buffer can be passed as an argument
+    #[cfg(feature = "record")]
+    pub(crate) fn without_type_names(mut self) -> Self {
+        for set in self.bind_group_layouts.values_mut() {
+            for (_, info) in set.values_mut() {
+                info.type_name = None;
+            }
+        }
+        self
+    }
+
+    // reads parameters that were written with write_to from the front of the given bytes
+    pub(crate) fn read_from(bytes: &mut &[u8]) -> Result<Self, PersistError> {
+        let mut bind_group_layouts = HashMap::new();
+        for _ in 0..read_u32(bytes)? {
+            let set_num = read_u32(bytes)?;
+            let mut set = HashMap::new();
+            for _ in 0..read_u32(bytes)? {
+                let binding_num = read_u32(bytes)?;
+                let flags = read_bytes(bytes, 2)?;
+                let ty = match flags[0] {
+                    0 => wgpu::BufferBindingType::Storage { read_only: false },
+                    1 => wgpu::BufferBindingType::Storage { read_only: true },
+                    2 => wgpu::BufferBindingType::Uniform,
+                    _ => return Err(PersistError::InvalidHeader),
+                };
+                let has_dynamic_offset = flags[1] != 0;
+                let min_binding_size = NonZeroU64::new(read_u64(bytes)?);
+                let type_name = match read_bytes(bytes, 1)?[0] {
+                    0 => None,
+                    _ => Some(read_string(bytes)?),
+                };
+                let mutability = match read_bytes(bytes, 1)?[0] {
+                    0 => None,
+                    1 => Some(Mutability::Mut),
+                    2 => Some(Mutability::Const),
+                    _ => return Err(PersistError::InvalidHeader),
+                };
+                set.insert(
+                    binding_num,
+                    (
+                        wgpu::BindGroupLayoutEntry {
+                            binding: binding_num,
+                            visibility: wgpu::ShaderStage::COMPUTE,
+                            ty: wgpu::BindingType::Buffer {
+                                ty,
+                                has_dynamic_offset,
+                                min_binding_size,
+                            },
+                            count: None,
+                        },
+                        ArgAndParamInfo {
+                            type_name,
+                            mutability,
+                        },
+                    ),
+                );
+            }
+            bind_group_layouts.insert(set_num, set);
+        }
+        Ok(Self { bind_group_layouts })
+    }
 }
 
 /// Says whether or not something is mutable
@@ -931,6 +3988,31 @@ impl ParamsBuilder {
         self
     }
 
+    /// Adds on a parameter that the global size of the [`Grid`](struct.Grid.html) a kernel is launched over is passed in for
+    ///
+    /// The grid size is passed in for you by [`Device::call`](struct.Device.html#method.call) (and everything built on it) as a uniform
+    /// buffer holding a `uvec3` (like `layout(set = 0, binding = 1) uniform GridSize { uvec3 grid_size; };` in GLSL).
+    pub fn grid_size(mut self) -> Self {
+        let new_binding_layout_idx = self.binding_layouts.len() as u32;
+        self.binding_layouts.insert(
+            new_binding_layout_idx,
+            (
+                wgpu::BindGroupLayoutEntry {
+                    binding: new_binding_layout_idx,
+                    visibility: wgpu::ShaderStage::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        has_dynamic_offset: false,
+                        ty: wgpu::BufferBindingType::Uniform,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                ArgAndParamInfo::grid_size(),
+            ),
+        );
+        self
+    }
+
     /// Builds a `DeviceFnMutParams`
     pub fn build(self) -> DeviceFnMutParams {
         let mut bind_group_layouts = HashMap::new();
@@ -950,6 +4032,21 @@ pub struct ArgAndParamInfo {
     mutability: Option<Mutability>,
 }
 
+impl ArgAndParamInfo {
+    // the info of the parameter a grid size is passed in for and of the grid size passed in for it
+    // the type is Grid so that it can't be mistaken for any argument passed in by the user
+    fn grid_size() -> Self {
+        Self {
+            type_name: Some(String::from(core::any::type_name::<Grid>())),
+            mutability: Some(Mutability::Const),
+        }
+    }
+
+    fn is_grid_size(&self) -> bool {
+        self == &Self::grid_size()
+    }
+}
+
 /// Holds the actual arguments to be passed into a [`DeviceFnMut`](struct.DeviceFnMut.html)
 ///
 /// See [`ArgsBuilder`](struct.ArgsBuilder.html) for a convenience builder of `DeviceFnMutArgs`.
@@ -958,8 +4055,8 @@ pub struct ArgAndParamInfo {
 /// Each set stores a `Vec<u32>` which can be empty as a reasonable default.
 ///
 /// Looking into WebGPU docs and Emu source code is probably the best way to figure out how to work with the WebGPU
-/// data structures encapsulated by `DeviceFnMutArgs`.
-#[derive(From, Into)]
+/// data structures encapsulated by `DeviceFnMutArgs`. Arguments passed by value (see [`IntoArg`](trait.IntoArg.html)) aren't
+/// bound to a buffer until launch so they aren't included when converting a `DeviceFnMutArgs` into its WebGPU internals.
 pub struct DeviceFnMutArgs<'a> {
     // this contains information for each bind group (marked by a u32 set number)
     // each bind group has a set of bindings (mapped from u32 binding number) and a set of offsets
@@ -980,13 +4077,361 @@ pub struct DeviceFnMutArgs<'a> {
             Vec</*wgpu::BufferAddress*/ u32>,
         ),
     >, // (u32, u32) = (set number, binding number)
+    // arguments passed by value (like scalars and tuples) as bytes
+    // these are uploaded to the value buffers of the device when launched
+    values: HashMap<u32, HashMap<u32, (Vec<u8>, ArgAndParamInfo)>>, // (u32, u32) = (set number, binding number)
+    // the id of the DeviceBox bound at each binding, if it's known
+    buffer_ids: HashMap<u32, HashMap<u32, u64>>, // (u32, u32) = (set number, binding number)
+}
+
+impl<'a>
+    From<
+        HashMap<
+            u32,
+            (
+                HashMap<u32, (wgpu::BindGroupEntry<'a>, ArgAndParamInfo)>,
+                Vec<u32>,
+            ),
+        >,
+    > for DeviceFnMutArgs<'a>
+{
+    fn from(
+        bind_groups: HashMap<
+            u32,
+            (
+                HashMap<u32, (wgpu::BindGroupEntry<'a>, ArgAndParamInfo)>,
+                Vec<u32>,
+            ),
+        >,
+    ) -> Self {
+        Self {
+            bind_groups,
+            values: HashMap::new(),
+            buffer_ids: HashMap::new(),
+        }
+    }
+}
+
+impl<'a>
+    Into<
+        HashMap<
+            u32,
+            (
+                HashMap<u32, (wgpu::BindGroupEntry<'a>, ArgAndParamInfo)>,
+                Vec<u32>,
+            ),
+        >,
+    > for DeviceFnMutArgs<'a>
+{
+    fn into(
+        self,
+    ) -> HashMap<
+        u32,
+        (
+            HashMap<u32, (wgpu::BindGroupEntry<'a>, ArgAndParamInfo)>,
+            Vec<u32>,
+        ),
+    > {
+        self.bind_groups
+    }
+}
+
+impl<'a> DeviceFnMutArgs<'a> {
+    /// Constructs a set of arguments with no arguments
+    ///
+    /// Add arguments with [`with_entry`](#method.with_entry) or use [`ArgsBuilder`](struct.ArgsBuilder.html) instead.
+    pub fn new() -> Self {
+        Self {
+            bind_groups: HashMap::new(),
+            values: HashMap::new(),
+            buffer_ids: HashMap::new(),
+        }
+    }
+
+    /// Binds the resource of the given entry (like a `wgpu::Buffer` you manage yourself) in the bind group with the given set number
+    ///
+    /// The binding number is the one in the entry and an argument already at that binding number is replaced. Since there is no type or
+    /// mutability for the argument, it isn't checked against its parameter. It is still checked for aliasing if it is a buffer.
+    /// The buffer must be made by the same `Device` the kernel is launched on and must have the `wgpu::BufferUsage::STORAGE` usage.
+    pub fn with_entry(mut self, set_num: u32, entry: wgpu::BindGroupEntry<'a>) -> Self {
+        if let Some(values) = self.values.get_mut(&set_num) {
+            values.remove(&entry.binding);
+        }
+        if let Some(buffer_ids) = self.buffer_ids.get_mut(&set_num) {
+            buffer_ids.remove(&entry.binding);
+        }
+        self.bind_groups
+            .entry(set_num)
+            .or_insert_with(|| (HashMap::new(), vec![]))
+            .0
+            .insert(entry.binding, (entry, ArgAndParamInfo::default()));
+        self
+    }
+
+    /// Puts the arguments built by the given `ArgsBuilder` in the bind group with the given set number
+    ///
+    /// This replaces any arguments already in that bind group. It's how arguments are passed to a kernel with parameters in more than 1 bind
+    /// group (see [`DeviceFnMutParams::with_set`](struct.DeviceFnMutParams.html#method.with_set)). Each bind group is bound at its own set
+    /// number so set numbers don't have to be contiguous. But arguments must be passed in every set the kernel has parameters in and in no other.
+    pub fn with_set(mut self, set_num: u32, args: ArgsBuilder<'a>) -> Self {
+        self.bind_groups.insert(set_num, (args.bindings, vec![]));
+        self.values.insert(set_num, args.values);
+        self.buffer_ids.insert(set_num, args.buffer_ids);
+        self
+    }
+
+    // the number of arguments in the bind group with the given set number
+    pub(crate) fn num_args(&self, set_num: u32) -> usize {
+        self.bind_groups
+            .get(&set_num)
+            .map_or(0, |(bindings, _)| bindings.len())
+            + self.values.get(&set_num).map_or(0, |values| values.len())
+    }
+
+    // the type and mutability of each argument, including those passed by value
+    fn arg_infos(&self) -> impl Iterator<Item = (u32, u32, &ArgAndParamInfo)> {
+        self.bind_groups
+            .iter()
+            .flat_map(|(set_num, (bindings, _))| {
+                bindings
+                    .iter()
+                    .map(move |(binding_num, (_, info))| (*set_num, *binding_num, info))
+            })
+            .chain(self.values.iter().flat_map(|(set_num, values)| {
+                values
+                    .iter()
+                    .map(move |(binding_num, (_, info))| (*set_num, *binding_num, info))
+            }))
+    }
+
+    // checks that arguments are passed in exactly the bind groups that have parameters
+    //
+    // a bind group with no arguments in it (like the one an empty ArgsBuilder builds) is as good as not passing it
+    pub(crate) fn check_sets(
+        &self,
+        param_types: &HashMap<u32, HashMap<u32, ArgAndParamInfo>>,
+    ) -> Result<(), LaunchError> {
+        for (set_num, params) in param_types {
+            if !params.is_empty() && self.num_args(*set_num) == 0 {
+                return Err(LaunchError::MissingBindGroup { set: *set_num });
+            }
+        }
+        for set_num in self.bind_groups.keys().chain(self.values.keys()) {
+            let num_params = param_types.get(set_num).map_or(0, |params| params.len());
+            if num_params == 0 && self.num_args(*set_num) > 0 {
+                return Err(LaunchError::UnexpectedBindGroup { set: *set_num });
+            }
+        }
+        Ok(())
+    }
+
+    // the ids of the DeviceBoxs that are bound mutably (in the sense of check_aliasing)
+    pub(crate) fn mutable_buffer_ids(
+        &self,
+        param_types: Option<&HashMap<u32, HashMap<u32, ArgAndParamInfo>>>,
+    ) -> Vec<u64> {
+        self.bind_groups
+            .iter()
+            .flat_map(|(set_num, (bindings, _offsets))| {
+                bindings
+                    .iter()
+                    .filter(move |(binding_num, (_, arg_info))| {
+                        let param_mutability = param_types
+                            .and_then(|param_types| param_types.get(set_num))
+                            .and_then(|set| set.get(binding_num))
+                            .and_then(|param_info| param_info.mutability);
+                        param_mutability.or(arg_info.mutability) == Some(Mutability::Mut)
+                    })
+                    .filter_map(move |(binding_num, _)| {
+                        self.buffer_ids.get(set_num)?.get(binding_num).copied()
+                    })
+            })
+            .collect()
+    }
+
+    // checks that no buffer is bound more than once where one of those bindings is mutable
+    //
+    // a binding is mutable if its parameter is mutable or, if the parameter isn't known, if its argument is mutable
+    pub(crate) fn check_aliasing(
+        &self,
+        param_types: Option<&HashMap<u32, HashMap<u32, ArgAndParamInfo>>>,
+    ) -> Result<(), AliasError> {
+        let mut bound_buffers: Vec<(*const wgpu::Buffer, bool)> = vec![];
+        for (set_num, (bindings, _offsets)) in &self.bind_groups {
+            for (binding_num, (entry, arg_info)) in bindings {
+                if let wgpu::BindingResource::Buffer { buffer, .. } = &entry.resource {
+                    let param_mutability = param_types
+                        .and_then(|param_types| param_types.get(set_num))
+                        .and_then(|set| set.get(binding_num))
+                        .and_then(|param_info| param_info.mutability);
+                    let is_mut = param_mutability.or(arg_info.mutability) == Some(Mutability::Mut);
+                    let buffer = *buffer as *const wgpu::Buffer;
+                    for (bound_buffer, bound_is_mut) in &bound_buffers {
+                        if *bound_buffer == buffer && (is_mut || *bound_is_mut) {
+                            return Err(AliasError);
+                        }
+                    }
+                    bound_buffers.push((buffer, is_mut));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // checks that the number of bind groups, the number of buffers, and the size of each buffer are within the given limits
+    fn check_limits(&self, limits: &wgpu::Limits) -> Result<(), LaunchError> {
+        let check = |limit: &'static str, requested: u64, max: u64| {
+            if requested > max {
+                Err(LaunchError::LimitExceeded {
+                    limit,
+                    requested,
+                    max,
+                })
+            } else {
+                Ok(())
+            }
+        };
+
+        let num_bind_groups = self
+            .bind_groups
+            .keys()
+            .chain(self.values.keys())
+            .max()
+            .map_or(0, |set_num| set_num + 1);
+        check(
+            "bind groups",
+            num_bind_groups as u64,
+            limits.max_bind_groups as u64,
+        )?;
+        let num_buffers = (0..num_bind_groups)
+            .map(|set_num| self.num_args(set_num))
+            .sum::<usize>();
+        check(
+            "storage buffers",
+            num_buffers as u64,
+            limits.max_storage_buffers_per_shader_stage as u64,
+        )?;
+        for (bindings, _) in self.bind_groups.values() {
+            for (entry, _) in bindings.values() {
+                if let wgpu::BindingResource::Buffer {
+                    size: Some(size), ..
+                } = &entry.resource
+                {
+                    check(
+                        "bytes in a buffer",
+                        size.get(),
+                        MAX_STORAGE_BUFFER_BINDING_SIZE,
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // binds an argument at the given set number and binding number
+    #[cfg(feature = "record")]
+    pub(crate) fn with_arg_at<T: ?Sized>(
+        mut self,
+        set_num: u32,
+        binding_num: u32,
+        device_obj: &'a DeviceBox<T>,
+    ) -> Self {
+        self.bind_groups
+            .entry(set_num)
+            .or_insert_with(|| (HashMap::new(), vec![]))
+            .0
+            .insert(binding_num, binding_for(binding_num, device_obj));
+        self.buffer_ids
+            .entry(set_num)
+            .or_insert_with(HashMap::new)
+            .insert(binding_num, device_obj.id);
+        self
+    }
+
+    // the arguments as they are written to a recording, sorted by set number and binding number
+    #[cfg(feature = "record")]
+    fn recorded_args(&self) -> Vec<(u32, u32, crate::record::Arg)> {
+        let mut recorded_args = self
+            .bind_groups
+            .iter()
+            .flat_map(|(set_num, (bindings, _))| {
+                bindings.keys().map(move |binding_num| {
+                    (
+                        *set_num,
+                        *binding_num,
+                        self.buffer_ids
+                            .get(set_num)
+                            .and_then(|ids| ids.get(binding_num))
+                            .map_or(crate::record::Arg::Unknown, |id| {
+                                crate::record::Arg::Buffer(*id)
+                            }),
+                    )
+                })
+            })
+            .chain(self.values.iter().flat_map(|(set_num, values)| {
+                values.iter().map(move |(binding_num, (bytes, _))| {
+                    (
+                        *set_num,
+                        *binding_num,
+                        crate::record::Arg::Value(bytes.clone()),
+                    )
+                })
+            }))
+            .collect::<Vec<_>>();
+        recorded_args.sort_by_key(|(set_num, binding_num, _)| (*set_num, *binding_num));
+        recorded_args
+    }
+
+    // passes the given grid size at the given set number and binding number
+    fn with_grid_size_at(mut self, set_num: u32, binding_num: u32, grid_size: [u32; 4]) -> Self {
+        self.values
+            .entry(set_num)
+            .or_insert_with(HashMap::new)
+            .insert(
+                binding_num,
+                (grid_size.as_bytes().to_vec(), ArgAndParamInfo::grid_size()),
+            );
+        self
+    }
+
+    // passes a value at the given set number and binding number as if it were a DeviceBox with no type or mutability
+    #[cfg(feature = "record")]
+    pub(crate) fn with_value_at(mut self, set_num: u32, binding_num: u32, bytes: Vec<u8>) -> Self {
+        self.values
+            .entry(set_num)
+            .or_insert_with(HashMap::new)
+            .insert(binding_num, (bytes, ArgAndParamInfo::default()));
+        self
+    }
+}
+
+// creates a binding of the given DeviceBox at the given binding number
+fn binding_for<'a, T: ?Sized>(
+    binding_idx: u32,
+    device_obj: &'a DeviceBox<T>,
+) -> (wgpu::BindGroupEntry<'a>, ArgAndParamInfo) {
+    (
+        wgpu::BindGroupEntry {
+            binding: binding_idx,
+            resource: wgpu::BindingResource::Buffer {
+                buffer: &device_obj.storage_buffer,
+                offset: device_obj.offset,
+                size: Some(NonZeroU64::new(device_obj.size).unwrap()),
+            },
+        },
+        ArgAndParamInfo {
+            type_name: Some(String::from(core::any::type_name::<T>())),
+            mutability: device_obj.mutability,
+        },
+    ) // for now we use type name, in the future we will use something more unique like core::any::TypeID
 }
 
 /// Helps with building a `DeviceFnMutArgs`
 ///
 /// `ArgsBuilder` helps you build a `DeviceFnMutArgs` by providing references to each `DeviceBox` argument. It's perfectly safe to
 /// pass a reference to a mutable `DeviceBox`. If the kernel these arguments are being passed to only accepts mutable arguments, Emu
-/// will assert that they are at runtime.
+/// will assert that they are at runtime. If the same `DeviceBox` is passed in more than once and the kernel may mutate it through one of those
+/// parameters, launching fails with `LaunchError::Alias`.
 /// ```
 /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
 /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -1000,8 +4445,11 @@ pub struct DeviceFnMutArgs<'a> {
 /// # Ok(())
 /// # }
 /// ```
+#[derive(Clone)]
 pub struct ArgsBuilder<'a> {
     bindings: HashMap<u32, (wgpu::BindGroupEntry<'a>, ArgAndParamInfo)>,
+    values: HashMap<u32, (Vec<u8>, ArgAndParamInfo)>,
+    buffer_ids: HashMap<u32, u64>,
 }
 
 impl<'a> ArgsBuilder<'a> {
@@ -1009,38 +4457,243 @@ impl<'a> ArgsBuilder<'a> {
     pub fn new() -> Self {
         Self {
             bindings: HashMap::new(),
+            values: HashMap::new(),
+            buffer_ids: HashMap::new(),
         }
     }
 
-    /// Declare a new arguments by passing in a `DeviceBox`
-    pub fn arg<T: ?Sized>(mut self, device_obj: &'a DeviceBox<T>) -> Self {
-        let new_binding_idx = self.bindings.len() as u32;
+    /// Declare a new argument by passing in a `DeviceBox` or a value (see [`IntoArg`](trait.IntoArg.html))
+    pub fn arg<A: IntoArg<'a>>(self, arg: A) -> Self {
+        arg.add_to(self)
+    }
+
+    /// Declares a `wgpu::Buffer` that you manage yourself as the next argument
+    ///
+    /// This lets code that already creates its own buffers launch kernels compiled with Emu without copying into a `DeviceBox`. The whole
+    /// buffer is bound. It must be made by the same `Device` the kernel is launched on and must have the `wgpu::BufferUsage::STORAGE` usage.
+    /// Since the type of its elements isn't known, it isn't checked against the type of its parameter. But its mutability is.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # use wgpu::util::DeviceExt;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+    ///     GlslKernel::new()
+    ///         .param_mut::<[f32], _>("float[] data")
+    ///         .with_kernel_code("data[gl_GlobalInvocationID.x] = 2.0 * data[gl_GlobalInvocationID.x];"),
+    /// )?
+    /// .finish()?;
+    ///
+    /// let mut device = take()?.lock().unwrap();
+    /// let buffer = device.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+    ///     label: None,
+    ///     contents: vec![1.0f32; 1024].as_bytes(),
+    ///     usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_SRC,
+    /// });
+    /// let args = ArgsBuilder::new().arg_buffer(&buffer, Mutability::Mut).build();
+    /// unsafe { device.call(&kernel, (1024, 1, 1), args)?; }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn arg_buffer(mut self, buffer: &'a wgpu::Buffer, mutability: Mutability) -> Self {
+        let binding_idx = self.next_binding_idx();
         self.bindings.insert(
-            new_binding_idx,
+            binding_idx,
             (
                 wgpu::BindGroupEntry {
-                    binding: new_binding_idx,
+                    binding: binding_idx,
                     resource: wgpu::BindingResource::Buffer {
-                        buffer: &device_obj.storage_buffer,
+                        buffer,
                         offset: 0,
-                        size: Some(NonZeroU64::new(device_obj.size).unwrap()),
+                        size: None,
                     },
                 },
                 ArgAndParamInfo {
-                    type_name: Some(String::from(core::any::type_name::<T>())),
-                    mutability: device_obj.mutability,
+                    type_name: None,
+                    mutability: Some(mutability),
                 },
-            ), // for now we use type name, in the future we will use something more unique like core::any::TypeID
+            ),
         );
-
         self
     }
 
+    // the binding number of the next argument
+    fn next_binding_idx(&self) -> u32 {
+        (self.bindings.len() + self.values.len()) as u32
+    }
+
+    // the number of arguments declared so far
+    #[cfg(feature = "extras")]
+    pub(crate) fn num_args(&self) -> usize {
+        self.next_binding_idx() as usize
+    }
+
+    // replaces the argument at the given binding number with the argument at the other binding number of the other builder
+    #[cfg(feature = "extras")]
+    pub(crate) fn replace_arg(
+        &mut self,
+        binding_idx: u32,
+        other: &mut ArgsBuilder<'a>,
+        other_binding_idx: u32,
+    ) {
+        self.bindings.remove(&binding_idx);
+        self.values.remove(&binding_idx);
+        self.buffer_ids.remove(&binding_idx);
+        if let Some((mut entry, info)) = other.bindings.remove(&other_binding_idx) {
+            entry.binding = binding_idx;
+            self.bindings.insert(binding_idx, (entry, info));
+        }
+        if let Some(value) = other.values.remove(&other_binding_idx) {
+            self.values.insert(binding_idx, value);
+        }
+        if let Some(id) = other.buffer_ids.remove(&other_binding_idx) {
+            self.buffer_ids.insert(binding_idx, id);
+        }
+    }
+
     /// Builds the final `DeviceFnMutArgs`
     pub fn build(self) -> DeviceFnMutArgs<'a> {
         let mut bind_groups = HashMap::with_capacity(4);
         bind_groups.insert(0, (self.bindings, vec![])); // again, we usually don't need more than 1 set, so we default to just 1
+        let mut values = HashMap::new();
+        values.insert(0, self.values);
+        let mut buffer_ids = HashMap::new();
+        buffer_ids.insert(0, self.buffer_ids);
+
+        DeviceFnMutArgs {
+            bind_groups,
+            values,
+            buffer_ids,
+        }
+    }
+
+    /// Builds the final `DeviceFnMutArgs` like [`build`](#method.build) but checks that no mutable `DeviceBox` is passed in more than once
+    ///
+    /// [`Device::call`](struct.Device.html#method.call) always does this check (also taking into account which parameters are mutable)
+    /// so this is only useful for catching the mistake early.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let data: DeviceBox<[f32]> = vec![0.0; 4096].as_device_boxed_mut()?;
+    /// assert!(ArgsBuilder::new().arg(&data).arg(&data).try_build().is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn try_build(self) -> Result<DeviceFnMutArgs<'a>, AliasError> {
+        let args = self.build();
+        args.check_aliasing(None)?;
+        Ok(args)
+    }
+}
+
+/// A trait for anything that can be passed as an argument to a kernel
+///
+/// This is implemented for references to a `DeviceBox` as well as for values of `f32`, `i32`, `u32`, and `f64` and tuples of 2 to 4 of the same `f32`, `i32`, or `u32`.
+/// Values are uploaded each time the kernel is launched so you don't have to wrap each of them in a `DeviceBox` yourself. They are uploaded to blocks
+/// of a few buffers that the device reuses after each submission, so passing values doesn't create buffers on every launch.
+/// A tuple is passed just like an array so it can be used for a vector parameter (e.g. - `(width, height)` of `u32`s can be passed to a `uvec2` parameter
+/// declared with `.param::<[u32; 2], _>("uvec2 size")`).
+/// ```
+/// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # futures::executor::block_on(assert_device_pool_initialized());
+/// let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+///     GlslKernel::new()
+///         .param_mut::<[f32], _>("float[] data")
+///         .param::<f32, _>("float scalar")
+///         .param::<[u32; 2], _>("uvec2 size")
+///         .with_kernel_code(r#"
+/// uint index = gl_GlobalInvocationID.x;
+/// if (index < size.x * size.y) {
+///     data[index] = data[index] * scalar;
+/// }
+/// "#),
+/// )?
+/// .finish()?;
+///
+/// let (width, height) = (32u32, 32u32);
+/// let mut data: DeviceBox<[f32]> = vec![1.0; 1024].as_device_boxed_mut()?;
+/// unsafe {
+///     spawn(1024).launch(call!(kernel, &mut data, 3.0f32, (width, height)))?;
+/// }
+/// assert_eq!(futures::executor::block_on(data.get())?, vec![3.0; 1024].into_boxed_slice());
+/// # Ok(())
+/// # }
+/// ```
+pub trait IntoArg<'a> {
+    /// Adds this as the next argument to the given `ArgsBuilder`
+    fn add_to(self, builder: ArgsBuilder<'a>) -> ArgsBuilder<'a>;
+}
+
+impl<'a, T: ?Sized> IntoArg<'a> for &'a DeviceBox<T> {
+    fn add_to(self, mut builder: ArgsBuilder<'a>) -> ArgsBuilder<'a> {
+        let new_binding_idx = builder.next_binding_idx();
+        builder
+            .bindings
+            .insert(new_binding_idx, binding_for(new_binding_idx, self));
+        builder.buffer_ids.insert(new_binding_idx, self.id);
+        builder
+    }
+}
 
-        DeviceFnMutArgs { bind_groups }
+impl<'a, T: ?Sized> IntoArg<'a> for &'a mut DeviceBox<T> {
+    fn add_to(self, builder: ArgsBuilder<'a>) -> ArgsBuilder<'a> {
+        (&*self).add_to(builder)
     }
 }
+
+// adds an argument passed by value with the given bytes as if it were a constant DeviceBox<T>
+fn add_value_to<'a, T: ?Sized>(mut builder: ArgsBuilder<'a>, bytes: Vec<u8>) -> ArgsBuilder<'a> {
+    let new_binding_idx = builder.next_binding_idx();
+    builder.values.insert(
+        new_binding_idx,
+        (
+            bytes,
+            ArgAndParamInfo {
+                type_name: Some(String::from(core::any::type_name::<T>())),
+                mutability: Some(Mutability::Const),
+            },
+        ),
+    );
+    builder
+}
+
+macro_rules! impl_into_arg_for_scalar {
+    ($($scalar:ty),*) => {
+        $(
+            impl<'a> IntoArg<'a> for $scalar {
+                fn add_to(self, builder: ArgsBuilder<'a>) -> ArgsBuilder<'a> {
+                    add_value_to::<$scalar>(builder, self.as_bytes().to_vec())
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_into_arg_for_tuple {
+    ($($scalar:ty),*) => {
+        $(
+            impl<'a> IntoArg<'a> for ($scalar, $scalar) {
+                fn add_to(self, builder: ArgsBuilder<'a>) -> ArgsBuilder<'a> {
+                    add_value_to::<[$scalar; 2]>(builder, [self.0, self.1].as_bytes().to_vec())
+                }
+            }
+
+            impl<'a> IntoArg<'a> for ($scalar, $scalar, $scalar) {
+                fn add_to(self, builder: ArgsBuilder<'a>) -> ArgsBuilder<'a> {
+                    add_value_to::<[$scalar; 3]>(builder, [self.0, self.1, self.2].as_bytes().to_vec())
+                }
+            }
+
+            impl<'a> IntoArg<'a> for ($scalar, $scalar, $scalar, $scalar) {
+                fn add_to(self, builder: ArgsBuilder<'a>) -> ArgsBuilder<'a> {
+                    add_value_to::<[$scalar; 4]>(builder, [self.0, self.1, self.2, self.3].as_bytes().to_vec())
+                }
+            }
+        )*
+    };
+}
+
+impl_into_arg_for_scalar!(f32, i32, u32, f64);
+impl_into_arg_for_tuple!(f32, i32, u32);
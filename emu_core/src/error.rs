@@ -1,149 +1,415 @@
 //! Various error types
+//!
+//! Errors that are caused by other errors (like a download that failed because a buffer couldn't be mapped) return the cause from
+//! [`source`](https://doc.rust-lang.org/std/error/trait.Error.html#method.source). So printing an error only prints what went wrong at the
+//! level it was returned from. [`display_chain`](fn.display_chain.html) prints the whole chain (and so does `{:#}` with `anyhow`). Most of
+//! the enums here are `#[non_exhaustive]` so that more context can be added to them without breaking matches on them.
 
 use std::error::Error;
 use std::fmt;
 
-use derive_more::Display;
-
-// TOOD maybe there is a better approach to errors...
-
-/// An error for when there is no device to complete a certain operation
-pub struct NoDeviceError;
-
-impl Error for NoDeviceError {}
-
-impl fmt::Debug for NoDeviceError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "no device could be found")
-    }
+use thiserror::Error;
+
+use crate::device::DeviceInfo;
+
+// errors that are printed with unwrap or returned from main are printed with Debug
+// so that is the same as Display for errors that are structures with nothing in them
+macro_rules! impl_debug_with_display {
+    ($($error:ty),*) => {
+        $(
+            impl fmt::Debug for $error {
+                fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    fmt::Display::fmt(self, f)
+                }
+            }
+        )*
+    };
 }
 
-impl fmt::Display for NoDeviceError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "no device could be found")
+/// Formats the given error followed by each of its sources, separated by `": "`
+///
+/// ```
+/// # use emu_core::prelude::*;
+/// let error = GetError::Completion(CompletionError::Map {
+///     device: None,
+///     source: wgpu::BufferAsyncError,
+/// });
+/// assert_eq!(
+///     display_chain(&error),
+///     "failed to download from the device: Error occurred when trying to async map a buffer"
+/// );
+/// ```
+pub fn display_chain(error: &(dyn Error + 'static)) -> String {
+    let mut chain = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        chain += ": ";
+        chain += &error.to_string();
+        source = error.source();
     }
+    chain
 }
 
+/// An error for when there is no device to complete a certain operation
+#[derive(Error)]
+#[error("no device could be found")]
+pub struct NoDeviceError;
+
 /// An error when a device is there but not available for use
+#[derive(Error)]
+#[error("currently selected device is already taken and unavailable")]
 pub struct UnavailableDeviceError;
 
-impl Error for UnavailableDeviceError {}
-
-impl fmt::Debug for UnavailableDeviceError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "currently selected device is already taken and unavailable"
-        )
-    }
+/// An error for compilation failures
+#[derive(Error)]
+#[non_exhaustive]
+pub enum CompileError {
+    /// Compilation failed and there is nothing more to say about why
+    #[error("failed to compile")]
+    Failed,
+    /// Compiling source code failed
+    ///
+    /// `code` is the fully assembled source code that was compiled and `log` is what the compiler reported. When the source code was
+    /// assembled from many fragments (like with a [`GlslKernel`](../compile_impls/struct.GlslKernel.html)), locations in the log are also
+    /// mapped back to the fragment they came from. Printing this error prints both with line numbers next to the source code.
+    #[error("failed to compile\n\n{}\n\n{}", .log.trim_end(), with_line_numbers(.code))]
+    Source { code: String, log: String },
+    /// The SPIR-V to compile could not be read
+    #[error("failed to read SPIR-V")]
+    Io(#[source] std::io::Error),
+    /// The source code is not valid UTF-8
+    #[error("source code is not valid UTF-8")]
+    Utf8(#[source] std::str::Utf8Error),
+    /// No compiler is registered for the given source language (see [`CompilerRegistry`](../compile/struct.CompilerRegistry.html))
+    #[error("no compiler is registered for `{0}`")]
+    UnknownLanguage(String),
+    /// The kernel uses 64-bit floats but the device doesn't support them
+    #[error("kernel requires 64-bit floats but {} doesn't support them", describe_device(.device))]
+    UnsupportedF64 {
+        /// The device the kernel was compiled for
+        device: Option<DeviceInfo>,
+    },
+    /// The device rejected the compiled program (for example, because it failed validation)
+    #[error("failed to compile `{}` for {}: {}", .entry_point, describe_device(.device), .message)]
+    Validation {
+        /// The name of the entry point of the kernel
+        entry_point: String,
+        /// The device the kernel was compiled for
+        device: Option<DeviceInfo>,
+        /// What WebGPU reported
+        message: String,
+    },
 }
 
-impl fmt::Display for UnavailableDeviceError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "currently selected device is already taken and unavailable"
-        )
+// describes a device for the message of an error
+fn describe_device(device: &Option<DeviceInfo>) -> String {
+    match device {
+        Some(info) => info.to_string(),
+        None => String::from("the device"),
     }
 }
 
-/// An error for compilation failures
-pub struct CompileError;
-
-impl Error for CompileError {}
-
-impl fmt::Debug for CompileError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "failed to compile")
+// describes a kernel for the message of an error
+fn describe_kernel(name: &Option<String>) -> String {
+    match name {
+        Some(name) => format!("`{}`", name),
+        None => String::from("kernel"),
     }
 }
 
-impl fmt::Display for CompileError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "failed to compile")
-    }
+// formats source code with a line number next to each line
+// this is here (and not just in compile) so that a CompileError can be printed even without the extras feature
+pub(crate) fn with_line_numbers(code: &str) -> String {
+    let width = code.lines().count().to_string().len();
+    code.lines()
+        .enumerate()
+        .map(|(i, line)| format!("{:>width$} | {}\n", i + 1, line, width = width))
+        .collect()
 }
 
 /// An error for failure to complete data movement or computation
-pub struct CompletionError;
-
-impl Error for CompletionError {}
-
-impl fmt::Debug for CompletionError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "failed to successfully complete memory creation/transfer/computation"
-        )
-    }
-}
-
-impl fmt::Display for CompletionError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "failed to successfully complete memory creation/transfer/computation"
-        )
-    }
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum CompletionError {
+    /// The buffer that data is downloaded through could not be mapped for reading
+    #[error("failed to download from {}", describe_device(.device))]
+    Map {
+        /// The device the data was downloaded from
+        device: Option<DeviceInfo>,
+        /// What WebGPU reported
+        #[source]
+        source: wgpu::BufferAsyncError,
+    },
 }
 
 /// An error that occurs when you attempt to initialize an already initialized pool of devices
+#[derive(Error)]
+#[error("pool of devices is already initialized")]
 pub struct PoolAlreadyInitializedError;
 
-impl Error for PoolAlreadyInitializedError {}
+/// An error in loading the configuration of the pool of devices
+#[cfg(feature = "pool")]
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// The configuration file could not be read
+    #[error("failed to read the configuration file")]
+    Io(#[source] std::io::Error),
+    /// The configuration file is not valid TOML
+    #[error("the configuration is not valid TOML")]
+    Parse(#[source] toml::de::Error),
+    /// A key in the configuration has a value that isn't valid
+    #[error("invalid value for `{0}`: {1}")]
+    InvalidValue(String, String),
+}
 
-impl fmt::Debug for PoolAlreadyInitializedError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "pool of devices is already initialized")
-    }
+/// An error in getting data stored in a `DeviceBox`
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum GetError {
+    #[error(transparent)]
+    Completion(#[from] CompletionError),
+    #[error("no device could be found")]
+    NoDevice,
+    #[error("timed out waiting for the download to complete")]
+    Timeout,
+    /// The launches that had to be submitted before downloading (like the ones batched in an [`AsyncQueue`](../queue/struct.AsyncQueue.html)) failed
+    #[error("failed to submit launches before downloading")]
+    Launch(#[source] LaunchError),
 }
 
-impl fmt::Display for PoolAlreadyInitializedError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "pool of devices is already initialized")
+/// An error in uploading data to a `DeviceBox`
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum SetError {
+    #[error("no device could be found")]
+    NoDevice,
+    /// The data being uploaded isn't the same size as the `DeviceBox` it is uploaded to
+    #[error("can't upload {actual} bytes to a `DeviceBox` of {expected} bytes")]
+    SizeMismatch {
+        /// The size of the `DeviceBox` in bytes
+        expected: u64,
+        /// The size of the data in bytes
+        actual: u64,
+    },
+}
+
+impl From<NoDeviceError> for SetError {
+    fn from(_error: NoDeviceError) -> Self {
+        SetError::NoDevice
     }
 }
 
-/// An error in getting data stored in a `DeviceBox`
-#[derive(Debug, Display)]
-pub enum GetError {
-    Completion,
+/// An error in saving a `DeviceBox`, a `Spirv`, or a `ComputeCanvas` to a file or loading one from a file
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum PersistError {
+    /// The file could not be read or written
+    #[error("failed to read or write the file")]
+    Io(#[source] std::io::Error),
+    /// The data could not be downloaded from the device
+    #[error(transparent)]
+    Get(#[from] GetError),
+    #[error("no device could be found")]
     NoDevice,
+    /// The file wasn't written by [`to_file`](../device/struct.DeviceBox.html#method.to_file) or is truncated
+    #[error("file does not contain a saved `DeviceBox`")]
+    InvalidHeader,
+    /// The file holds elements of a different type than the one being loaded
+    #[error("file holds elements of type `{0}` but `{1}` was expected")]
+    TypeMismatch(String, String),
+    /// The thing being saved has something that can't be saved
+    #[error("{0}")]
+    Unsupported(&'static str),
+    /// The image file could not be decoded or encoded
+    #[cfg(feature = "image")]
+    #[error("failed to decode or encode the image")]
+    Image(#[source] image::ImageError),
 }
 
-impl Error for GetError {}
-
 /// An error for capturing compilation fails or no device present
-#[derive(Debug, Display)]
+#[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum CompileOrNoDeviceError {
-    Compile,
+    #[error(transparent)]
+    Compile(#[from] CompileError),
+    #[error("no device could be found")]
     NoDevice,
+    /// The kernel uses 64-bit floats but the device doesn't support them
+    #[error("kernel requires 64-bit floats but the device doesn't support them")]
+    UnsupportedF64,
 }
 
-impl Error for CompileOrNoDeviceError {}
-
 /// A runtime error that occurs on the device
+#[derive(Error)]
+#[error("runtime error occurred")]
 pub struct RuntimeError;
 
-impl Error for RuntimeError {}
+/// An error for when the same buffer is bound to more than 1 parameter and at least one of them is mutable
+///
+/// Letting this through would mean that a kernel could race with itself, reading and writing the same data through different parameters.
+#[derive(Error)]
+#[error("the same buffer is passed as more than 1 argument and at least one of them is mutable")]
+pub struct AliasError;
+
+impl_debug_with_display!(
+    NoDeviceError,
+    UnavailableDeviceError,
+    CompileError,
+    PoolAlreadyInitializedError,
+    RuntimeError,
+    AliasError
+);
 
-impl fmt::Debug for RuntimeError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "runtime error occurred")
+/// An error in launching kernels
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum LaunchError {
+    #[error("no device could be found")]
+    NoDevice,
+    /// Waiting for launches to complete failed
+    #[error("failed to wait for launches to complete")]
+    Runtime(#[source] wgpu::BufferAsyncError),
+    #[error("timed out waiting for launches to complete")]
+    Timeout,
+    /// The same buffer was passed as more than 1 argument and at least one of them is mutable
+    #[error(transparent)]
+    Alias(#[from] AliasError),
+    /// The device rejected the launch (for example, because it failed validation)
+    #[error("launch of {} failed validation on {}: {}", describe_kernel(.kernel), describe_device(.device), .message)]
+    Validation {
+        /// The name of the entry point of the kernel, if it is known
+        kernel: Option<String>,
+        /// The device the kernel was launched on
+        device: Option<DeviceInfo>,
+        /// What WebGPU reported
+        message: String,
+    },
+    /// The launch needs more of something (like thread blocks in a dimension) than the device supports
+    #[error("launch requires {requested} {limit} but the device supports at most {max}")]
+    LimitExceeded {
+        /// What there is too much of
+        limit: &'static str,
+        /// How much of it the launch requires
+        requested: u64,
+        /// How much of it the device supports
+        max: u64,
+    },
+    /// The kernel has parameters in the bind group with this set number but no arguments were passed for it
+    #[error("no arguments were passed for the parameters in set {set}")]
+    MissingBindGroup {
+        /// The set number of the bind group
+        set: u32,
+    },
+    /// Arguments were passed in the bind group with this set number but the kernel has no parameters there
+    #[error("arguments were passed in set {set} but the kernel has no parameters there")]
+    UnexpectedBindGroup {
+        /// The set number of the bind group
+        set: u32,
+    },
+    /// The kernel declares its grid size but it was launched over a number of thread blocks without a known size
+    #[error("the kernel declares its grid size but it was launched over a number of thread blocks of unknown size (launch over a `Grid` instead)")]
+    UnknownGridSize,
+    /// The size of each thread block of the `Grid` launched over is not the size the kernel was compiled with
+    #[error("the grid has thread blocks of size {grid:?} but the kernel was compiled with thread blocks of size {kernel:?}")]
+    LocalSizeMismatch {
+        /// The size of each thread block of the grid
+        grid: (u32, u32, u32),
+        /// The size of each thread block of the kernel
+        kernel: (u32, u32, u32),
+    },
+}
+
+/// An error in replaying a recorded [`Trace`](../record/struct.Trace.html)
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ReplayError {
+    /// A recorded kernel failed to compile on the device it is replayed on
+    #[error(transparent)]
+    Compile(CompileError),
+    /// A recorded launch failed on the device it is replayed on
+    #[error(transparent)]
+    Launch(LaunchError),
+    /// A recorded download failed to complete
+    #[error(transparent)]
+    Get(CompletionError),
+    /// A recorded upload doesn't fit the buffer it is uploaded to
+    #[error(transparent)]
+    Set(SetError),
+    /// An event uses a buffer that wasn't created earlier in the trace
+    #[error("buffer {0} is used before it is created in the trace")]
+    UnknownBuffer(u64),
+    /// An event launches a kernel that wasn't compiled earlier in the trace
+    #[error("kernel {0} is launched before it is compiled in the trace")]
+    UnknownKernel(u64),
+    /// A launch was passed a buffer that wasn't made from a `DeviceBox` and so wasn't recorded
+    #[error("a launch was passed a buffer that wasn't recorded")]
+    UnrecordedArg,
+}
+
+/// An error in running one of the kernels that come with Emu (like [`CsrMatrix::spmv`](../sparse/struct.CsrMatrix.html#method.spmv))
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum KernelError {
+    #[error("no device could be found")]
+    NoDevice,
+    #[error(transparent)]
+    Compile(#[from] CompileOrNoDeviceError),
+    #[error(transparent)]
+    Launch(#[from] LaunchError),
+}
+
+impl From<NoDeviceError> for KernelError {
+    fn from(_error: NoDeviceError) -> Self {
+        KernelError::NoDevice
     }
 }
 
-impl fmt::Display for RuntimeError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "runtime error occurred")
+impl From<CompileError> for KernelError {
+    fn from(error: CompileError) -> Self {
+        KernelError::Compile(CompileOrNoDeviceError::Compile(error))
     }
 }
 
-/// An error in launching kernels
-#[derive(Debug, Display)]
-pub enum LaunchError {
+/// An error in checking that the elements of a `DeviceBox<[f32]>` are finite (see [`check_finite`](../device/struct.DeviceBox.html#method.check_finite))
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum CheckFiniteError {
+    /// The element at the given index is NaN or infinite and so are no elements before it
+    #[error("element {0} is not finite")]
+    NotFinite(usize),
+    /// The kernel that checks the elements failed to run
+    #[error(transparent)]
+    Kernel(#[from] KernelError),
+    /// The result of the check could not be downloaded
+    #[error(transparent)]
+    Get(#[from] GetError),
+}
+
+/// An error in using a [`WorkQueue`](../work_queue/struct.WorkQueue.html)
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum WorkQueueError {
+    #[error("no device could be found")]
     NoDevice,
-    Runtime,
+    /// The items don't fit in the queue
+    #[error("{requested} items were pushed but the queue only has room for {available} more")]
+    Full {
+        /// The number of items that were pushed
+        requested: usize,
+        /// The number of items that there was room for
+        available: usize,
+    },
+    /// The state of the queue could not be downloaded
+    #[error(transparent)]
+    Get(#[from] GetError),
+    /// A launch of the kernel that works on the queue failed
+    #[error(transparent)]
+    Launch(#[from] LaunchError),
 }
 
-impl Error for LaunchError {}
+impl From<NoDeviceError> for WorkQueueError {
+    fn from(_error: NoDeviceError) -> Self {
+        WorkQueueError::NoDevice
+    }
+}
@@ -0,0 +1,192 @@
+//! Loading image files into `DeviceBox`s and saving `DeviceBox`s as image files
+//!
+//! This module requires the `image` feature, which decodes and encodes images with the [`image`](https://docs.rs/image) crate (PNG and JPEG
+//! are supported). A [`DeviceImage`](struct.DeviceImage.html) is a `DeviceBox<[u8]>` or a `DeviceBox<[f32]>` of interleaved channels along with
+//! the [`ImageLayout`](struct.ImageLayout.html) needed to make sense of it. Pixels are stored in row-major order starting from the top left,
+//! which is the layout the kernels in [`algo`](../algo/index.html) expect.
+//! ```no_run
+//! # use {emu_core::prelude::*, emu_core::images::*};
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # futures::executor::block_on(assert_device_pool_initialized());
+//! // bytes are normalized to floats between 0.0 and 1.0
+//! let photo: DeviceImage<f32> = DeviceImage::open("photo.jpg", Channels::Luma)?;
+//! println!("loaded a {}x{} image", photo.layout.width, photo.layout.height);
+//! futures::executor::block_on(photo.save("photo.png"))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::Path;
+
+use zerocopy::*;
+
+use crate::boxed::*;
+use crate::device::*;
+use crate::error::*;
+
+/// The channels of each pixel of an image
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Channels {
+    /// A single channel of brightness
+    Luma,
+    /// Red, green, and blue
+    Rgb,
+    /// Red, green, blue, and alpha
+    Rgba,
+}
+
+impl Channels {
+    /// Returns the number of channels
+    pub fn count(&self) -> u32 {
+        match self {
+            Channels::Luma => 1,
+            Channels::Rgb => 3,
+            Channels::Rgba => 4,
+        }
+    }
+
+    // the color type of an image file with these channels and a byte for each channel
+    fn color_type(&self) -> image::ColorType {
+        match self {
+            Channels::Luma => image::ColorType::L8,
+            Channels::Rgb => image::ColorType::Rgb8,
+            Channels::Rgba => image::ColorType::Rgba8,
+        }
+    }
+}
+
+/// How the pixels of an image are laid out in a `DeviceBox`
+///
+/// The channels of each pixel are next to each other (interleaved) and pixels are in row-major order starting from the top left. So channel `c` of
+/// the pixel at `(x, y)` is element `(y * width + x) * channels.count() + c`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ImageLayout {
+    /// The number of pixels in each row
+    pub width: u32,
+    /// The number of rows
+    pub height: u32,
+    /// The channels of each pixel
+    pub channels: Channels,
+}
+
+impl ImageLayout {
+    /// Returns the number of elements (not pixels) an image with this layout has
+    pub fn len(&self) -> usize {
+        self.width as usize * self.height as usize * self.channels.count() as usize
+    }
+
+    /// Returns whether or not an image with this layout has no pixels
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A type of element that a [`DeviceImage`](struct.DeviceImage.html) can hold
+///
+/// Image files have a byte for each channel. `u8` elements are those bytes and `f32` elements are those bytes normalized to be between 0.0 and
+/// 1.0 (and clamped to that range when saved).
+pub trait ImageElement: AsBytes + FromBytes + Copy {
+    /// Converts a byte of an image file to an element
+    fn from_byte(byte: u8) -> Self;
+    /// Converts an element to a byte of an image file
+    fn to_byte(self) -> u8;
+}
+
+impl ImageElement for u8 {
+    fn from_byte(byte: u8) -> Self {
+        byte
+    }
+
+    fn to_byte(self) -> u8 {
+        self
+    }
+}
+
+impl ImageElement for f32 {
+    fn from_byte(byte: u8) -> Self {
+        byte as f32 / 255.0
+    }
+
+    fn to_byte(self) -> u8 {
+        (self.clamp(0.0, 1.0) * 255.0).round() as u8
+    }
+}
+
+/// An image on a device along with its layout
+pub struct DeviceImage<T: ImageElement> {
+    /// The channels of the pixels of the image, laid out as described by `layout`
+    pub pixels: DeviceBox<[T]>,
+    /// How the pixels are laid out
+    pub layout: ImageLayout,
+}
+
+impl<T: ImageElement> DeviceImage<T> {
+    /// Puts together an image from its pixels and their layout
+    ///
+    /// This is useful for saving the output of a kernel. `pixels` must have exactly `layout.len()` elements.
+    pub fn new(pixels: DeviceBox<[T]>, layout: ImageLayout) -> Self {
+        assert_eq!(
+            pixels.size as usize,
+            layout.len() * std::mem::size_of::<T>(),
+            "an image must have `width * height * channels` elements"
+        );
+        Self { pixels, layout }
+    }
+
+    /// Loads the image file at the given path into a mutable `DeviceBox` on the device currently selected from the pool
+    ///
+    /// The image is converted to the given channels (so a color photo can be loaded as `Channels::Luma` to get its brightness).
+    pub fn open<Q: AsRef<Path>>(path: Q, channels: Channels) -> Result<Self, PersistError> {
+        let file = image::open(path).map_err(PersistError::Image)?;
+        let (width, height, bytes) = match channels {
+            Channels::Luma => {
+                let file = file.to_luma8();
+                (file.width(), file.height(), file.into_raw())
+            }
+            Channels::Rgb => {
+                let file = file.to_rgb8();
+                (file.width(), file.height(), file.into_raw())
+            }
+            Channels::Rgba => {
+                let file = file.to_rgba8();
+                (file.width(), file.height(), file.into_raw())
+            }
+        };
+        let pixels = bytes
+            .into_iter()
+            .map(T::from_byte)
+            .collect::<Vec<T>>()
+            .as_device_boxed_mut()
+            .map_err(|_| PersistError::NoDevice)?;
+        Ok(Self {
+            pixels,
+            layout: ImageLayout {
+                width,
+                height,
+                channels,
+            },
+        })
+    }
+
+    /// Downloads the image and saves it to the given path
+    ///
+    /// The format of the file is picked from the extension of the path (like `.png` or `.jpg`).
+    pub async fn save<Q: AsRef<Path>>(&self, path: Q) -> Result<(), PersistError> {
+        let bytes = self
+            .pixels
+            .get()
+            .await
+            .map_err(PersistError::Get)?
+            .iter()
+            .map(|element| element.to_byte())
+            .collect::<Vec<u8>>();
+        image::save_buffer(
+            path,
+            &bytes,
+            self.layout.width,
+            self.layout.height,
+            self.layout.channels.color_type(),
+        )
+        .map_err(PersistError::Image)
+    }
+}
@@ -16,10 +16,32 @@
 //! - See [`SpirvBuilder`](compile/struct.SpirvBuilder.html), [`Glsl`](compile_impls/struct.Glsl.html), [`GlslKernel`](compile_impls/struct.GlslKernel.html) for simple source
 //! languages to use for writing compute kernels
 //! - See [`compile`](compile/fn.compile.html) for compiling source language to `SpirvOrFinished` and then finishing to `DeviceFnMut`
+//! - See [`CompilerRegistry`](compile/struct.CompilerRegistry.html) for compiling source languages chosen at run-time by a language tag (like "glsl")
+//! - See [`KernelRegistry`](cache/struct.KernelRegistry.html) for registering compiled `DeviceFnMut`s by name and getting them later from anywhere
 //! - See [`spawn`](spawn/fn.spawn.html) for spawning threads on GPU and launching compiled kernels (`DeviceFnMut`s)
 //! - See [`pool`](pool/index.html)'s [`pool`](pool/fn.pool.html)/[`select`](pool/fn.select.html)/[`take`](pool/fn.take.html) for
 //! managing the global pool of devices
 //! - See [`assert_device_pool_initialized`](pool/fn.assert_device_pool_initialized.html)
+//! - See [`AsyncQueue`](queue/struct.AsyncQueue.html) for batching launches and limiting how much work is in flight when driving a device from asynchronous code
+//! - See [`Schedule`](schedule/struct.Schedule.html) for running a graph of launches and transfers ordered by the `DeviceBox`s they read and write
+//! - See [`TempArena`](arena/struct.TempArena.html) for recycling scratch buffers across the passes of multi-pass algorithms
+//! - See [`sparse`](sparse/index.html) for sparse matrices (like [`CsrMatrix`](sparse/struct.CsrMatrix.html)) and kernels for multiplying them with vectors
+//! - See [`KernelTemplate`](template/struct.KernelTemplate.html) for writing a kernel once with `$T` in place of its element type and compiling it for `f32`s, `i32`s, `u32`s, or `f64`s
+//! - See [`GlslKernel::with_block_add`](compile_impls/struct.GlslKernel.html#method.with_block_add) and [`subgroup_size`](compile_impls/fn.subgroup_size.html) for block-wide sums that use subgroup (wave/warp) operations on devices that support them
+//! - See [`batch`](batch/index.html) for packing thousands of small independent problems (like tiny linear systems) into one buffer and solving them with a single launch
+//! - See [`algo`](algo/index.html) for ready-made kernels for image convolution, Gaussian blur, stencils, and histograms
+//! - See [`debug`](debug/index.html) for assertions and printing from inside kernels with `emu_assert` and `emu_printf`
+//! - See [`Device::watch`](device/struct.Device.html#method.watch) for finding the kernel that corrupts a `DeviceBox` by checksumming it around every launch that writes to it
+//! - See [`WorkQueue`](work_queue/struct.WorkQueue.html) for feeding persistent kernels from a queue of work items on the device
+//! - See [`cluster`](cluster/index.html) for distance matrices, k-means, and brute-force k-nearest neighbors over points in a `DeviceBox<[f32]>`
+//! - See [`ParticleSystem`](particles/struct.ParticleSystem.html) for N-body simulation of particles under gravity with a tiled force kernel
+//! - See [`ComputeCanvas`](canvas/struct.ComputeCanvas.html) for rendering images (like ray-marched signed distance fields) with a GLSL shading function and saving them as PNGs
+//! - See [`images`](images/index.html) for loading PNG and JPEG files into `DeviceBox`s (along with their width, height, and channels) and saving them back
+//! - See [`nn`](nn/index.html) for convolution, pooling, batch normalization, and dense layers over [`DeviceTensor`](nn/struct.DeviceTensor.html)s for running trained networks
+//! - See `kernel!` in [`emu_glsl`](https://docs.rs/emu_glsl) for writing kernels in a subset of Rust that are translated to `GlslKernel`s and launched over `DeviceBox`s
+//! - See [`testing`](testing/index.html) for running tests of kernels on a software device in CI environments without a GPU and checking them against reference implementations
+//! - See [`record`](record/index.html) and [`replay`](replay/index.html) for recording a timeline of API calls (to debug or to attach to a bug report) and re-running it on another device
+//! - See [`python`](python/index.html) for Python bindings that upload numpy arrays, compile GLSL kernels, and launch them
 //!
 //! Note that `Device` and `pool` are the lowest-level building blocks for the
 //! rest of Emu and as such, you could technically use either just `Device` and
@@ -35,30 +57,106 @@
 //! [`shaderc`](https://docs.rs/shaderc/0.6.2/shaderc/index.html). In the future, when a Rust-based GLSL-to-SPIR-V compiler is finished (there is work going towards this),
 //! there will be a simpler pure-Rust dependency but until then, you should follow [steps here](https://docs.rs/shaderc/0.6.2/shaderc/index.html) to ensure the platforms you
 //! target will have `shaderc`.
+//! If building `shaderc` is a problem for you (it often is on Windows and on musl targets), you can instead enable the `glsl-compile-naga` feature.
+//! This compiles `Glsl` and `GlslKernel` with the pure-Rust [`naga`](https://github.com/gfx-rs/naga) instead. `naga`'s GLSL frontend is still young though
+//! so it doesn't support everything `shaderc` does and it doesn't optimize. If both features are enabled, `shaderc` is used.
+//! There is also the `record` feature which enables the [`record`](record/index.html) and [`replay`](replay/index.html) modules. It is off by default
+//! since it adds a (small) cost to each API call, even when nothing is being recorded.
+//! The `python` feature enables the [`python`](python/index.html) module with [PyO3](https://github.com/PyO3/pyo3) bindings for scripting Emu from Python.
+//! It turns on `glsl-compile-naga` so that building the bindings doesn't need `shaderc`.
+//! The `serde` feature implements `Serialize` for [`DeviceInfo`](device/struct.DeviceInfo.html) and for the description of the pool returned by
+//! [`topology`](pool/fn.topology.html), which is useful for including the GPU environment in logs and bug reports.
+//! The `image` feature enables the [`images`](images/index.html) module, which decodes and encodes image files with the [`image`](https://docs.rs/image) crate.
+//! Finally, the `pool` and `extras` features are on by default. Without them, only [`device`](device/index.html) and [`error`](error/index.html) are
+//! left, which is all that's needed by a plugin that is handed a `Device` by its host application. `pool` enables the global pool of devices
+//! ([`pool`](pool/index.html)) and everything built on it ([`boxed`](boxed/index.html) and [`arena`](arena/index.html)). Since the pool is global
+//! state, you can switch it off for environments that forbid global state. `extras` (which needs `pool`) enables compiling, caching, and launching
+//! kernels ([`compile`](compile/index.html), [`compile_impls`](compile_impls/index.html), [`cache`](cache/index.html), [`spawn`](spawn/index.html),
+//! [`queue`](queue/index.html), [`schedule`](schedule/index.html), and [`testing`](testing/index.html)). The GLSL features turn on `extras`.
 //! Of course, if you really don't want to use `shaderc`, you could always [compile your code to SPIR-V at compile time](https://crates.io/crates/glsl-to-spirv-macros) and
-//! then use SPIR-V as input to Emu.
+//! then use SPIR-V as input to Emu. [`include_spirv!`](macro.include_spirv.html) embeds SPIR-V files compiled ahead of time in your program.
 //!
 //! Also, some basic guides that will likely be helpful in using Emu are the following.
 //! - [How to use CUDA](https://www.nvidia.com/docs/IO/116711/sc11-cuda-c-basics.pdf) - This explains the idea of launching kernels on a 3-dimensional space of threads, which Emu
 //! and CUDA share
 //! - [How to write GLSL compute shaders](https://www.khronos.org/opengl/wiki/Compute_Shader) - This explains some of the stuff that is specific to SPIR-V, which Emu uses as input
 
+#[cfg(any(feature = "pool", feature = "record"))]
 #[macro_use]
 extern crate lazy_static; // we use lazy_static for global device pool and global kernel cache
 
 // the high-level compile-cache-spawn-launch functionality
+#[cfg(feature = "extras")]
 pub mod cache; // includes the Cache trait for implementing disk/in-memory caches of JIT compiled programs
+#[cfg(feature = "extras")]
 pub mod compile; // includes the Compile trait for implementing source language inputs to Emu (e.g. - XLA, Halide, GLSL, Swift SIL, Julia IR, etc.)
+#[cfg(feature = "extras")]
 pub mod compile_impls;
+#[cfg(feature = "extras")]
 pub mod spawn; // use for spawning threads and launching a DeviceFnMut
                // a set of traits and functions for working with DeviceBox's
+#[cfg(feature = "pool")]
 pub mod boxed;
 // a pool of devices to reduce some boilerplate, use for a CUDA-esque API where a global device pool is shared by all Emu users
+#[cfg(feature = "pool")]
 pub mod pool;
 // a set of types for errors in device usage
 pub mod error;
 // the lowest-level abstraction over wgpu-rs, use this for easy zero-cost interop with wgpu-rs data structures
 pub mod device;
+// a queue for driving a device from asynchronous code without queueing unbounded work
+#[cfg(feature = "extras")]
+pub mod queue;
+// a graph of launches and transfers that is submitted in as few submissions as its dependencies allow
+#[cfg(feature = "extras")]
+pub mod schedule;
+// scratch buffers that are recycled across the passes of multi-pass algorithms
+#[cfg(feature = "pool")]
+pub mod arena;
+// tools for testing kernels, even on machines without a GPU
+#[cfg(feature = "extras")]
+pub mod testing;
+// sparse matrices and kernels for working with them
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+pub mod sparse;
+// kernels that are compiled for each type of element they are used with
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+pub mod template;
+// packing many small problems into one buffer and solving them with a single launch
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+pub mod batch;
+// ready-made kernels for common algorithms like image convolution and histograms
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+pub mod algo;
+// assertions and printing from inside kernels
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+pub mod debug;
+// a queue of work items for persistent kernels
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+pub mod work_queue;
+// kernels for clustering and nearest-neighbor search
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+pub mod cluster;
+// N-body simulation of particles
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+pub mod particles;
+// rendering images with compute kernels
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+pub mod canvas;
+// loading and saving image files as DeviceBox's
+#[cfg(all(feature = "image", feature = "pool"))]
+pub mod images;
+// layers for running inference with convolutional neural networks
+#[cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+pub mod nn;
+// recording API calls into a trace and replaying the trace on a device
+#[cfg(feature = "record")]
+pub mod record;
+#[cfg(feature = "record")]
+pub mod replay;
+// Python bindings for the pool, device boxes, and kernels
+#[cfg(feature = "python")]
+pub mod python;
 
 macro_rules! pub_use {
 	($($module:ident),*) => ($(pub use crate::$module::*;)*)
@@ -66,6 +164,13 @@ macro_rules! pub_use {
 
 pub mod prelude {
     //! The module to import to import everything else
-    pub use crate::call;
-    pub_use! {compile, compile_impls, cache, spawn, boxed, device, error, pool}
+    //!
+    //! Only the modules enabled by features (see the [crate](../index.html) documentation) are imported.
+    #[cfg(feature = "extras")]
+    pub use crate::{call, include_spirv};
+    pub_use! {device, error}
+    #[cfg(feature = "pool")]
+    pub_use! {boxed, pool}
+    #[cfg(feature = "extras")]
+    pub_use! {compile, compile_impls, cache, spawn}
 }
@@ -0,0 +1,623 @@
+//! Layers for running convolutional neural networks
+//!
+//! This is for running inference with a small network that was already trained (with something like PyTorch) without pulling in a full framework.
+//! Activations are [`DeviceTensor`](struct.DeviceTensor.html)s of `f32`s in NCHW order (batch, channels, height, width) and weights are loaded
+//! from flat slices in the order PyTorch stores them. This module requires the `glsl-compile` feature.
+//! - [`Conv2d`](struct.Conv2d.html) for 2D convolutions
+//! - [`max_pool_2d`](fn.max_pool_2d.html) and [`avg_pool_2d`](fn.avg_pool_2d.html) for pooling
+//! - [`BatchNorm`](struct.BatchNorm.html) for batch normalization (with the statistics gathered during training)
+//! - [`Dense`](struct.Dense.html) for fully connected layers
+//! - [`relu`](fn.relu.html) for the ReLU activation
+//! ```
+//! # use {emu_core::prelude::*, emu_core::nn::*, emu_glsl::*, zerocopy::*};
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # futures::executor::block_on(assert_device_pool_initialized());
+//! // a 4x4 image with 1 channel
+//! let image = DeviceTensor::from_slice(&[1, 1, 4, 4], &[1.0; 16])?;
+//!
+//! // a 3x3 convolution that adds up each pixel's neighborhood, followed by 2x2 max pooling
+//! let conv = Conv2d::new(1, 1, (3, 3), &[1.0; 9], &[0.0])?.with_padding(1);
+//! let features = max_pool_2d(&conv.forward(&image)?, 2, 2)?;
+//! assert_eq!(features.shape(), &[1, 1, 2, 2]);
+//!
+//! // a fully connected layer with 2 outputs
+//! let dense = Dense::new(4, 2, &[0.25, 0.25, 0.25, 0.25, 1.0, 0.0, 0.0, 0.0], &[0.0, 1.0])?;
+//! let output = dense.forward(&features.reshape(&[1, 4]))?;
+//! assert_eq!(futures::executor::block_on(output.get())?, vec![9.0, 10.0].into_boxed_slice());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::boxed::*;
+use crate::cache::*;
+use crate::compile::*;
+use crate::compile_impls::*;
+use crate::device::*;
+use crate::error::*;
+use crate::spawn::*;
+
+// the number of threads in each thread block of the kernels of layers
+const NN_BLOCK_SIZE: u32 = 64;
+// the most thread blocks the kernels of layers launch, each thread strides through the elements of the output
+const MAX_NN_BLOCKS: u32 = 4096;
+
+/// A tensor of `f32`s on a device
+///
+/// This is just a mutable `DeviceBox<[f32]>` along with a shape. The elements are in row-major order so the last dimension is contiguous.
+pub struct DeviceTensor {
+    data: DeviceBox<[f32]>,
+    shape: Vec<usize>,
+}
+
+impl DeviceTensor {
+    /// Uploads the given elements to a new tensor with the given shape on the device currently selected from the pool
+    pub fn from_slice(shape: &[usize], data: &[f32]) -> Result<Self, NoDeviceError> {
+        assert_eq!(
+            data.len(),
+            shape.iter().product::<usize>(),
+            "the number of elements must be the product of the dimensions of the shape"
+        );
+        Ok(Self {
+            data: data.as_device_boxed_mut()?,
+            shape: shape.to_vec(),
+        })
+    }
+
+    /// Creates a tensor of zeros with the given shape on the device currently selected from the pool
+    pub fn zeros(shape: &[usize]) -> Result<Self, NoDeviceError> {
+        Self::from_slice(shape, &vec![0.0; shape.iter().product()])
+    }
+
+    /// Wraps a `DeviceBox` in a tensor with the given shape
+    pub fn from_device_box(data: DeviceBox<[f32]>, shape: &[usize]) -> Self {
+        assert_eq!(
+            data.len(),
+            shape.iter().product::<usize>(),
+            "the number of elements must be the product of the dimensions of the shape"
+        );
+        Self {
+            data,
+            shape: shape.to_vec(),
+        }
+    }
+
+    /// Returns the shape
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    /// Returns the number of elements
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns whether or not there are no elements
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Changes the shape without moving any elements
+    ///
+    /// The new shape must have the same number of elements. This is how the output of the last convolution is flattened before a
+    /// [`Dense`](struct.Dense.html) layer.
+    pub fn reshape(self, shape: &[usize]) -> Self {
+        Self::from_device_box(self.data, shape)
+    }
+
+    /// Returns the elements as a `DeviceBox` that can be passed to kernels
+    pub fn as_device_box(&self) -> &DeviceBox<[f32]> {
+        &self.data
+    }
+
+    /// Returns the elements as a mutable `DeviceBox` that can be passed to kernels
+    pub fn as_device_box_mut(&mut self) -> &mut DeviceBox<[f32]> {
+        &mut self.data
+    }
+
+    /// Unwraps the `DeviceBox` that holds the elements
+    pub fn into_device_box(self) -> DeviceBox<[f32]> {
+        self.data
+    }
+
+    /// Downloads the elements
+    pub async fn get(&self) -> Result<Box<[f32]>, GetError> {
+        self.data.get().await
+    }
+
+    // returns the shape of an NCHW tensor as a uvec4
+    fn nchw(&self, name: &str) -> (u32, u32, u32, u32) {
+        assert_eq!(
+            self.shape.len(),
+            4,
+            "`{}` must have 4 dimensions (batch, channels, height, width)",
+            name
+        );
+        (
+            self.shape[0] as u32,
+            self.shape[1] as u32,
+            self.shape[2] as u32,
+            self.shape[3] as u32,
+        )
+    }
+}
+
+/// A 2D convolution
+///
+/// The weights are in the same order as PyTorch's `Conv2d` (output channels, input channels, height, width) and there is a bias for each
+/// output channel. Each thread computes 1 element of the output directly (without im2col) so no extra memory is needed. Pixels in the padding
+/// are 0.
+pub struct Conv2d {
+    weights: DeviceBox<[f32]>,
+    bias: DeviceBox<[f32]>,
+    in_channels: usize,
+    out_channels: usize,
+    kernel_size: (usize, usize),
+    stride: usize,
+    padding: usize,
+}
+
+impl Conv2d {
+    /// Uploads the given weights and bias for a convolution with a kernel of the given height and width
+    pub fn new(
+        in_channels: usize,
+        out_channels: usize,
+        kernel_size: (usize, usize),
+        weights: &[f32],
+        bias: &[f32],
+    ) -> Result<Self, NoDeviceError> {
+        assert_eq!(
+            weights.len(),
+            out_channels * in_channels * kernel_size.0 * kernel_size.1,
+            "there must be `out_channels * in_channels * kernel_height * kernel_width` weights"
+        );
+        assert_eq!(
+            bias.len(),
+            out_channels,
+            "there must be a bias for each output channel"
+        );
+        Ok(Self {
+            weights: weights.as_device_boxed()?,
+            bias: bias.as_device_boxed()?,
+            in_channels,
+            out_channels,
+            kernel_size,
+            stride: 1,
+            padding: 0,
+        })
+    }
+
+    /// Sets the number of pixels the kernel moves by between neighboring outputs (1 by default)
+    pub fn with_stride(mut self, stride: usize) -> Self {
+        assert!(stride > 0, "the stride must be at least 1");
+        self.stride = stride;
+        self
+    }
+
+    /// Sets the number of pixels of 0s added to each side of the input (0 by default)
+    pub fn with_padding(mut self, padding: usize) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Convolves the given input with shape (batch, in channels, height, width)
+    pub fn forward(&self, input: &DeviceTensor) -> Result<DeviceTensor, KernelError> {
+        let (batch, channels, height, width) = input.nchw("input");
+        assert_eq!(
+            channels as usize, self.in_channels,
+            "the input must have `in_channels` channels"
+        );
+        let (kernel_height, kernel_width) = self.kernel_size;
+        assert!(
+            height as usize + 2 * self.padding >= kernel_height
+                && width as usize + 2 * self.padding >= kernel_width,
+            "the padded input must be at least as large as the kernel"
+        );
+        let out_height = (height as usize + 2 * self.padding - kernel_height) / self.stride + 1;
+        let out_width = (width as usize + 2 * self.padding - kernel_width) / self.stride + 1;
+        let mut output =
+            DeviceTensor::zeros(&[batch as usize, self.out_channels, out_height, out_width])?;
+        if output.is_empty() {
+            return Ok(output);
+        }
+
+        let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+            GlslKernel::new()
+                .spawn(NN_BLOCK_SIZE)
+                .param::<[f32], _>("float[] input")
+                .param::<[f32], _>("float[] weights")
+                .param::<[f32], _>("float[] bias")
+                .param_mut::<[f32], _>("float[] dst")
+                .param::<[u32; 4], _>("uvec4 in_shape")
+                .param::<[u32; 4], _>("uvec4 out_shape")
+                .param::<[u32; 4], _>("uvec4 conv")
+                .with_kernel_code(format!(
+                    r#"
+// conv is the kernel height, the kernel width, the stride, and the padding
+uint len = out_shape.x * out_shape.y * out_shape.z * out_shape.w;
+for (uint i = gl_GlobalInvocationID.x; i < len; i += gl_NumWorkGroups.x * {block_size}) {{
+    uint ox = i % out_shape.w;
+    uint oy = (i / out_shape.w) % out_shape.z;
+    uint oc = (i / (out_shape.w * out_shape.z)) % out_shape.y;
+    uint n = i / (out_shape.w * out_shape.z * out_shape.y);
+    float sum = bias[oc];
+    for (uint c = 0; c < in_shape.y; c++) {{
+        for (uint ky = 0; ky < conv.x; ky++) {{
+            int y = int(oy * conv.z + ky) - int(conv.w);
+            if (y < 0 || y >= int(in_shape.z)) {{
+                continue;
+            }}
+            for (uint kx = 0; kx < conv.y; kx++) {{
+                int x = int(ox * conv.z + kx) - int(conv.w);
+                if (x < 0 || x >= int(in_shape.w)) {{
+                    continue;
+                }}
+                sum += input[((n * in_shape.y + c) * in_shape.z + uint(y)) * in_shape.w + uint(x)]
+                    * weights[((oc * in_shape.y + c) * conv.x + ky) * conv.y + kx];
+            }}
+        }}
+    }}
+    dst[i] = sum;
+}}
+"#,
+                    block_size = NN_BLOCK_SIZE
+                )),
+        )?
+        .finish()?;
+
+        let out_shape = output.nchw("output");
+        unsafe {
+            spawn(num_blocks(output.len())).launch(crate::call!(
+                kernel,
+                &input.data,
+                &self.weights,
+                &self.bias,
+                &mut output.data,
+                (batch, channels, height, width),
+                out_shape,
+                (
+                    kernel_height as u32,
+                    kernel_width as u32,
+                    self.stride as u32,
+                    self.padding as u32
+                )
+            ))?;
+        }
+
+        Ok(output)
+    }
+}
+
+/// Takes the maximum of each window of the given size (in both dimensions) of the given input with shape (batch, channels, height, width)
+///
+/// The windows start every `stride` pixels and there is no padding, so pixels past the last full window are ignored.
+pub fn max_pool_2d(
+    input: &DeviceTensor,
+    size: usize,
+    stride: usize,
+) -> Result<DeviceTensor, KernelError> {
+    pool_2d(input, size, stride, "max(result, value)", "result")
+}
+
+/// Takes the average of each window of the given size (in both dimensions) of the given input with shape (batch, channels, height, width)
+///
+/// The windows start every `stride` pixels and there is no padding, so pixels past the last full window are ignored.
+/// ```
+/// # use {emu_core::prelude::*, emu_core::nn::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # futures::executor::block_on(assert_device_pool_initialized());
+/// let input = DeviceTensor::from_slice(&[1, 1, 2, 4], &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0])?;
+/// let output = avg_pool_2d(&input, 2, 2)?;
+/// assert_eq!(output.shape(), &[1, 1, 1, 2]);
+/// assert_eq!(futures::executor::block_on(output.get())?, vec![3.5, 5.5].into_boxed_slice());
+/// # Ok(())
+/// # }
+/// ```
+pub fn avg_pool_2d(
+    input: &DeviceTensor,
+    size: usize,
+    stride: usize,
+) -> Result<DeviceTensor, KernelError> {
+    pool_2d(
+        input,
+        size,
+        stride,
+        "result + value",
+        &format!("result / {}.0", size * size),
+    )
+}
+
+// pools each window by folding its values into the result with the given GLSL and then finishing the result with the given GLSL
+fn pool_2d(
+    input: &DeviceTensor,
+    size: usize,
+    stride: usize,
+    fold: &str,
+    finish: &str,
+) -> Result<DeviceTensor, KernelError> {
+    let (batch, channels, height, width) = input.nchw("input");
+    assert!(
+        size > 0 && stride > 0,
+        "the size and the stride must be at least 1"
+    );
+    assert!(
+        height as usize >= size && width as usize >= size,
+        "the input must be at least as large as a window"
+    );
+    let mut output = DeviceTensor::zeros(&[
+        batch as usize,
+        channels as usize,
+        (height as usize - size) / stride + 1,
+        (width as usize - size) / stride + 1,
+    ])?;
+    if output.is_empty() {
+        return Ok(output);
+    }
+
+    let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+        GlslKernel::new()
+            .spawn(NN_BLOCK_SIZE)
+            .param::<[f32], _>("float[] input")
+            .param_mut::<[f32], _>("float[] dst")
+            .param::<[u32; 4], _>("uvec4 in_shape")
+            .param::<[u32; 4], _>("uvec4 out_shape")
+            .param::<[u32; 2], _>("uvec2 window")
+            .with_kernel_code(format!(
+                r#"
+// window is the size and the stride
+uint len = out_shape.x * out_shape.y * out_shape.z * out_shape.w;
+for (uint i = gl_GlobalInvocationID.x; i < len; i += gl_NumWorkGroups.x * {block_size}) {{
+    uint ox = i % out_shape.w;
+    uint oy = (i / out_shape.w) % out_shape.z;
+    uint plane = i / (out_shape.w * out_shape.z); // the index of the channel among all channels of all images
+    uint origin = (plane * in_shape.z + oy * window.y) * in_shape.w + ox * window.y;
+    float result = input[origin];
+    for (uint y = 0; y < window.x; y++) {{
+        for (uint x = 0; x < window.x; x++) {{
+            float value = input[origin + y * in_shape.w + x];
+            if (x + y > 0) {{
+                result = {fold};
+            }}
+        }}
+    }}
+    dst[i] = {finish};
+}}
+"#,
+                block_size = NN_BLOCK_SIZE,
+                fold = fold,
+                finish = finish
+            )),
+    )?
+    .finish()?;
+
+    let out_shape = output.nchw("output");
+    unsafe {
+        spawn(num_blocks(output.len())).launch(crate::call!(
+            kernel,
+            &input.data,
+            &mut output.data,
+            (batch, channels, height, width),
+            out_shape,
+            (size as u32, stride as u32)
+        ))?;
+    }
+
+    Ok(output)
+}
+
+/// Batch normalization with fixed statistics, as used for inference
+///
+/// Each channel is normalized with the mean and variance gathered during training and then scaled and shifted. This is the same as PyTorch's
+/// `BatchNorm2d` (or `BatchNorm1d`) in evaluation mode. The channels are the second dimension of the input, whatever its shape.
+pub struct BatchNorm {
+    scale: DeviceBox<[f32]>,
+    shift: DeviceBox<[f32]>,
+    channels: usize,
+}
+
+impl BatchNorm {
+    /// Uploads the given running mean, running variance, weight (gamma), and bias (beta) of each channel
+    ///
+    /// Since the statistics are fixed, the normalization is folded into a single scale and shift for each channel here.
+    pub fn new(
+        mean: &[f32],
+        variance: &[f32],
+        weight: &[f32],
+        bias: &[f32],
+        eps: f32,
+    ) -> Result<Self, NoDeviceError> {
+        let channels = mean.len();
+        assert!(
+            variance.len() == channels && weight.len() == channels && bias.len() == channels,
+            "there must be a mean, variance, weight, and bias for each channel"
+        );
+        let scale = variance
+            .iter()
+            .zip(weight)
+            .map(|(variance, weight)| weight / (variance + eps).sqrt())
+            .collect::<Vec<f32>>();
+        let shift = mean
+            .iter()
+            .zip(&scale)
+            .zip(bias)
+            .map(|((mean, scale), bias)| bias - mean * scale)
+            .collect::<Vec<f32>>();
+        Ok(Self {
+            scale: scale.as_device_boxed()?,
+            shift: shift.as_device_boxed()?,
+            channels,
+        })
+    }
+
+    /// Normalizes the given input in place
+    pub fn forward(&self, input: &mut DeviceTensor) -> Result<(), KernelError> {
+        assert!(
+            input.shape.len() >= 2 && input.shape[1] == self.channels,
+            "the second dimension of the input must be the channels"
+        );
+        let plane = input.shape[2..].iter().product::<usize>();
+        if input.is_empty() {
+            return Ok(());
+        }
+
+        let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+            GlslKernel::new()
+                .spawn(NN_BLOCK_SIZE)
+                .param_mut::<[f32], _>("float[] data")
+                .param::<[f32], _>("float[] scale")
+                .param::<[f32], _>("float[] shift")
+                .param::<[u32; 3], _>("uvec3 sizes")
+                .with_kernel_code(format!(
+                    r#"
+// sizes is the number of elements, the number of elements in each channel of each image, and the number of channels
+for (uint i = gl_GlobalInvocationID.x; i < sizes.x; i += gl_NumWorkGroups.x * {block_size}) {{
+    uint c = (i / sizes.y) % sizes.z;
+    data[i] = data[i] * scale[c] + shift[c];
+}}
+"#,
+                    block_size = NN_BLOCK_SIZE
+                )),
+        )?
+        .finish()?;
+
+        let len = input.len();
+        unsafe {
+            spawn(num_blocks(len)).launch(crate::call!(
+                kernel,
+                &mut input.data,
+                &self.scale,
+                &self.shift,
+                (len as u32, plane as u32, self.channels as u32)
+            ))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A fully connected layer
+///
+/// The weights are in the same order as PyTorch's `Linear` (output features, input features) and there is a bias for each output feature.
+pub struct Dense {
+    weights: DeviceBox<[f32]>,
+    bias: DeviceBox<[f32]>,
+    in_features: usize,
+    out_features: usize,
+}
+
+impl Dense {
+    /// Uploads the given weights and bias
+    pub fn new(
+        in_features: usize,
+        out_features: usize,
+        weights: &[f32],
+        bias: &[f32],
+    ) -> Result<Self, NoDeviceError> {
+        assert_eq!(
+            weights.len(),
+            out_features * in_features,
+            "there must be `out_features * in_features` weights"
+        );
+        assert_eq!(
+            bias.len(),
+            out_features,
+            "there must be a bias for each output feature"
+        );
+        Ok(Self {
+            weights: weights.as_device_boxed()?,
+            bias: bias.as_device_boxed()?,
+            in_features,
+            out_features,
+        })
+    }
+
+    /// Applies the layer to the given input with shape (batch, in features), returning an output with shape (batch, out features)
+    pub fn forward(&self, input: &DeviceTensor) -> Result<DeviceTensor, KernelError> {
+        assert!(
+            input.shape.len() == 2 && input.shape[1] == self.in_features,
+            "the input must have shape (batch, in_features)"
+        );
+        let batch = input.shape[0];
+        let mut output = DeviceTensor::zeros(&[batch, self.out_features])?;
+        if output.is_empty() {
+            return Ok(output);
+        }
+
+        let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+            GlslKernel::new()
+                .spawn(NN_BLOCK_SIZE)
+                .param::<[f32], _>("float[] input")
+                .param::<[f32], _>("float[] weights")
+                .param::<[f32], _>("float[] bias")
+                .param_mut::<[f32], _>("float[] dst")
+                .param::<[u32; 3], _>("uvec3 sizes")
+                .with_kernel_code(format!(
+                    r#"
+// sizes is the batch size, the number of input features, and the number of output features
+for (uint i = gl_GlobalInvocationID.x; i < sizes.x * sizes.z; i += gl_NumWorkGroups.x * {block_size}) {{
+    uint n = i / sizes.z;
+    uint o = i % sizes.z;
+    float sum = bias[o];
+    for (uint k = 0; k < sizes.y; k++) {{
+        sum += weights[o * sizes.y + k] * input[n * sizes.y + k];
+    }}
+    dst[i] = sum;
+}}
+"#,
+                    block_size = NN_BLOCK_SIZE
+                )),
+        )?
+        .finish()?;
+
+        unsafe {
+            spawn(num_blocks(output.len())).launch(crate::call!(
+                kernel,
+                &input.data,
+                &self.weights,
+                &self.bias,
+                &mut output.data,
+                (
+                    batch as u32,
+                    self.in_features as u32,
+                    self.out_features as u32
+                )
+            ))?;
+        }
+
+        Ok(output)
+    }
+}
+
+/// Replaces each negative element of the given tensor with 0
+pub fn relu(input: &mut DeviceTensor) -> Result<(), KernelError> {
+    if input.is_empty() {
+        return Ok(());
+    }
+
+    let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+        GlslKernel::new()
+            .spawn(NN_BLOCK_SIZE)
+            .param_mut::<[f32], _>("float[] data")
+            .param::<u32, _>("uint len")
+            .with_kernel_code(format!(
+                r#"
+for (uint i = gl_GlobalInvocationID.x; i < len; i += gl_NumWorkGroups.x * {block_size}) {{
+    data[i] = max(data[i], 0.0);
+}}
+"#,
+                block_size = NN_BLOCK_SIZE
+            )),
+    )?
+    .finish()?;
+
+    let len = input.len();
+    unsafe {
+        spawn(num_blocks(len)).launch(crate::call!(kernel, &mut input.data, len as u32))?;
+    }
+
+    Ok(())
+}
+
+// the number of thread blocks to launch for the given number of elements
+fn num_blocks(len: usize) -> u32 {
+    ((len as u32 + NN_BLOCK_SIZE - 1) / NN_BLOCK_SIZE).min(MAX_NN_BLOCKS)
+}
@@ -0,0 +1,317 @@
+//! N-body simulation of particles under gravity
+//!
+//! A [`ParticleSystem`](struct.ParticleSystem.html) holds an array of [`Particle`](struct.Particle.html)s on a device and steps them forward in
+//! time with velocity Verlet integration. Every particle attracts every other particle, so each step computes `n * n` interactions. This is
+//! also meant to be read as an example of a complete simulation built on Emu.
+//! - `Particle` is a [`GlslStruct`](../compile/trait.GlslStruct.html) so kernels declare arrays of it with
+//! [`param_structs`](../compile_impls/struct.GlslKernel.html#method.param_structs).
+//! - The kernel that computes forces is tiled. Each thread block loads a tile of particles into shared memory and then every thread in the block
+//! reads the tile from there, so each particle is read from global memory once per thread block instead of once per thread.
+//! - Particles are double buffered. Each step reads from one buffer and writes to the other and then the buffers are swapped, so no thread ever
+//! reads a particle that another thread is writing.
+//!
+//! This module requires the `glsl-compile` feature.
+//! ```
+//! # use {emu_core::prelude::*, emu_core::particles::*, emu_glsl::*, zerocopy::*};
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # futures::executor::block_on(assert_device_pool_initialized());
+//! // 2 particles at rest that pull each other together
+//! let mut system = ParticleSystem::new(&[
+//!     Particle::new([-1.0, 0.0, 0.0], [0.0; 3], 1.0),
+//!     Particle::new([1.0, 0.0, 0.0], [0.0; 3], 1.0),
+//! ])?;
+//! system.run(0.01, 10)?;
+//!
+//! let particles = futures::executor::block_on(system.get())?;
+//! assert!(particles[0].position[0] > -1.0 && particles[0].velocity[0] > 0.0);
+//! // momentum is conserved
+//! assert_eq!(particles[0].position[0], -particles[1].position[0]);
+//! assert_eq!(particles[0].velocity[0], -particles[1].velocity[0]);
+//! # Ok(())
+//! # }
+//! ```
+
+use zerocopy::*;
+
+use crate::boxed::*;
+use crate::cache::*;
+use crate::compile::*;
+use crate::compile_impls::*;
+use crate::device::*;
+use crate::error::*;
+use crate::spawn::*;
+
+// the number of threads in each thread block of the kernels for particles
+// this is also the number of particles in each tile of the kernel that computes forces
+const PARTICLES_BLOCK_SIZE: u32 = 128;
+
+/// A particle with a position, a velocity, and a mass
+///
+/// This has the same layout in Rust and in GLSL (where it is a `struct Particle`).
+#[repr(C)]
+#[derive(AsBytes, FromBytes, Copy, Clone, Default, Debug, PartialEq)]
+pub struct Particle {
+    pub position: [f32; 3],
+    pub mass: f32,
+    pub velocity: [f32; 3],
+    // a vec3 is aligned like a vec4 in GLSL so this makes the size of a particle a multiple of 16 bytes
+    padding: f32,
+}
+
+impl Particle {
+    /// Creates a particle
+    pub fn new(position: [f32; 3], velocity: [f32; 3], mass: f32) -> Self {
+        Self {
+            position,
+            mass,
+            velocity,
+            padding: 0.0,
+        }
+    }
+}
+
+impl GlslStruct for Particle {
+    fn as_glsl() -> String {
+        String::from(
+            "struct Particle { vec3 position; float mass; vec3 velocity; float padding; };",
+        )
+    }
+
+    fn glsl_name() -> String {
+        String::from("Particle")
+    }
+
+    fn glsl_fields() -> Vec<GlslField> {
+        [
+            ("position", "vec3", 0, 12),
+            ("mass", "float", 12, 4),
+            ("velocity", "vec3", 16, 12),
+            ("padding", "float", 28, 4),
+        ]
+        .iter()
+        .map(|(name, glsl_type, offset, size)| GlslField {
+            name: name.to_string(),
+            glsl_type: glsl_type.to_string(),
+            offset: *offset,
+            size: *size,
+        })
+        .collect()
+    }
+}
+
+/// A system of particles that attract each other under gravity
+///
+/// The gravitational constant is 1 and the softening length is 0.01 by default. Softening keeps the force between particles that get very close
+/// from blowing up (and is what keeps a particle from attracting itself).
+pub struct ParticleSystem {
+    // the particles are in the buffer at index current and the other buffer is written to by the next step
+    particles: [DeviceBox<[Particle]>; 2],
+    current: usize,
+    // the acceleration of each particle at its current position, once it has been computed
+    accelerations: DeviceBox<[[f32; 4]]>,
+    accelerations_computed: bool,
+    len: usize,
+    gravitational_constant: f32,
+    softening: f32,
+}
+
+impl ParticleSystem {
+    /// Uploads the given particles to the device currently selected from the pool
+    pub fn new(particles: &[Particle]) -> Result<Self, NoDeviceError> {
+        Ok(Self {
+            particles: [
+                particles.as_device_boxed_mut()?,
+                particles.as_device_boxed_mut()?,
+            ],
+            current: 0,
+            accelerations: vec![[0.0f32; 4]; particles.len()].as_device_boxed_mut()?,
+            accelerations_computed: false,
+            len: particles.len(),
+            gravitational_constant: 1.0,
+            softening: 0.01,
+        })
+    }
+
+    /// Sets the gravitational constant
+    pub fn with_gravitational_constant(mut self, gravitational_constant: f32) -> Self {
+        self.gravitational_constant = gravitational_constant;
+        self.accelerations_computed = false;
+        self
+    }
+
+    /// Sets the softening length, which must be more than 0
+    pub fn with_softening(mut self, softening: f32) -> Self {
+        assert!(softening > 0.0, "the softening length must be more than 0");
+        self.softening = softening;
+        self.accelerations_computed = false;
+        self
+    }
+
+    /// Returns the number of particles
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether or not there are no particles
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the particles as they are after the last step
+    ///
+    /// This can be passed to other kernels, for example to render the particles.
+    pub fn particles(&self) -> &DeviceBox<[Particle]> {
+        &self.particles[self.current]
+    }
+
+    /// Downloads the particles as they are after the last step
+    pub async fn get(&self) -> Result<Box<[Particle]>, GetError> {
+        self.particles().get().await
+    }
+
+    /// Moves the particles forward in time by `dt`
+    ///
+    /// A step of velocity Verlet integration launches 2 kernels. The first moves each particle using its velocity and acceleration and does
+    /// the first half of the update to its velocity, writing to the other buffer. The second computes the new accelerations at the new positions and
+    /// does the second half of the update to the velocities. Then the buffers are swapped.
+    pub fn step(&mut self, dt: f32) -> Result<(), KernelError> {
+        if self.len == 0 {
+            return Ok(());
+        }
+        if !self.accelerations_computed {
+            self.compute_accelerations(self.current, 0.0)?;
+            self.accelerations_computed = true;
+        }
+
+        let next = 1 - self.current;
+        self.drift(dt)?;
+        self.compute_accelerations(next, 0.5 * dt)?;
+        self.current = next;
+        Ok(())
+    }
+
+    /// Takes the given number of steps of size `dt`
+    pub fn run(&mut self, dt: f32, steps: usize) -> Result<(), KernelError> {
+        for _ in 0..steps {
+            self.step(dt)?;
+        }
+        Ok(())
+    }
+
+    // moves each particle and half-updates its velocity, from the current buffer into the other buffer
+    fn drift(&mut self, dt: f32) -> Result<(), KernelError> {
+        let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+            GlslKernel::new()
+                .spawn(PARTICLES_BLOCK_SIZE)
+                .param_structs::<Particle, _>("particles")
+                .param_structs_mut::<Particle, _>("next_particles")
+                .param_vec::<[f32; 4], _>("accelerations")
+                .param::<u32, _>("uint len")
+                .param::<f32, _>("float dt")
+                .with_kernel_code(
+                    r#"
+uint i = gl_GlobalInvocationID.x;
+if (i < len) {
+    Particle particle = particles[i];
+    vec3 acceleration = accelerations[i].xyz;
+    particle.position += particle.velocity * dt + 0.5 * acceleration * dt * dt;
+    particle.velocity += 0.5 * acceleration * dt;
+    next_particles[i] = particle;
+}
+"#,
+                ),
+        )?
+        .finish()?;
+
+        let (current, next) = split_buffers(&mut self.particles, self.current);
+        unsafe {
+            spawn(num_blocks(self.len)).launch(crate::call!(
+                kernel,
+                current,
+                next,
+                &self.accelerations,
+                self.len as u32,
+                dt
+            ))?;
+        }
+        Ok(())
+    }
+
+    // computes the acceleration of each particle in the given buffer and adds the acceleration times kick to its velocity
+    fn compute_accelerations(&mut self, buffer: usize, kick: f32) -> Result<(), KernelError> {
+        let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+            GlslKernel::new()
+                .spawn(PARTICLES_BLOCK_SIZE)
+                .param_structs_mut::<Particle, _>("particles")
+                .param_vec_mut::<[f32; 4], _>("accelerations")
+                .param::<u32, _>("uint len")
+                .param::<f32, _>("float gravitational_constant")
+                .param::<f32, _>("float softening_squared")
+                .param::<f32, _>("float kick")
+                // the position and mass of each particle in the tile
+                .share(format!("vec4 tile[{}]", PARTICLES_BLOCK_SIZE))
+                .with_kernel_code(format!(
+                    r#"
+uint i = gl_GlobalInvocationID.x;
+vec3 position = i < len ? particles[i].position : vec3(0.0);
+vec3 acceleration = vec3(0.0);
+
+for (uint tile_start = 0; tile_start < len; tile_start += {block_size}) {{
+    // every thread loads 1 particle of the tile, even threads past the last particle so that they reach the barriers
+    uint j = tile_start + gl_LocalInvocationID.x;
+    tile[gl_LocalInvocationID.x] = j < len ? vec4(particles[j].position, particles[j].mass) : vec4(0.0);
+    memoryBarrierShared();
+    barrier();
+
+    // particles past the end have no mass so they don't pull on anything
+    for (uint k = 0; k < {block_size}; k++) {{
+        vec3 offset = tile[k].xyz - position;
+        float inverse_distance = inversesqrt(dot(offset, offset) + softening_squared);
+        acceleration += offset * (tile[k].w * inverse_distance * inverse_distance * inverse_distance);
+    }}
+    barrier();
+}}
+
+if (i < len) {{
+    acceleration *= gravitational_constant;
+    accelerations[i] = vec4(acceleration, 0.0);
+    particles[i].velocity += acceleration * kick;
+}}
+"#,
+                    block_size = PARTICLES_BLOCK_SIZE
+                )),
+        )?
+        .finish()?;
+
+        unsafe {
+            spawn(num_blocks(self.len)).launch(crate::call!(
+                kernel,
+                &mut self.particles[buffer],
+                &mut self.accelerations,
+                self.len as u32,
+                self.gravitational_constant,
+                self.softening * self.softening,
+                kick
+            ))?;
+        }
+        Ok(())
+    }
+}
+
+// returns the buffer at the given index and the other buffer
+fn split_buffers(
+    particles: &mut [DeviceBox<[Particle]>; 2],
+    current: usize,
+) -> (&DeviceBox<[Particle]>, &mut DeviceBox<[Particle]>) {
+    let (first, second) = particles.split_at_mut(1);
+    if current == 0 {
+        (&first[0], &mut second[0])
+    } else {
+        (&second[0], &mut first[0])
+    }
+}
+
+// the number of thread blocks to launch for the given number of particles
+fn num_blocks(len: usize) -> u32 {
+    (len as u32 + PARTICLES_BLOCK_SIZE - 1) / PARTICLES_BLOCK_SIZE
+}
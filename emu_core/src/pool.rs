@@ -5,6 +5,8 @@
 
 use derive_more::{From, Into};
 use std::cell::RefCell;
+use std::fmt;
+use std::path::Path;
 use std::sync::Mutex;
 
 use crate::device::*;
@@ -57,6 +59,190 @@ fn maybe_initialize_device_idx() {
     }
 }
 
+/// Configuration for how the default pool of devices is initialized
+///
+/// When no custom pool has been set with [`pool`](fn.pool.html), [`assert_device_pool_initialized`](fn.assert_device_pool_initialized.html)
+/// loads a `PoolConfig` with [`load`](#method.load) and only adds devices that match it to the pool. This lets deployment environments pin the device
+/// that gets used without any code changes. A configuration file (by default `emu.toml` in the current directory) looks like this.
+/// ```toml
+/// [pool]
+/// device_name = "nvidia"  # a case-insensitive substring of the device name
+/// vendor_id = 4318
+/// backends = ["vulkan"]   # any of "vulkan", "metal", "dx12", "dx11", "gl", "browser_webgpu", "primary", "secondary"
+/// validation = false      # whether or not kernels are validated when compiled
+/// ```
+/// Each key can also be set with an environment variable (`EMU_DEVICE_NAME`, `EMU_VENDOR_ID`, `EMU_BACKENDS`, `EMU_VALIDATION`) which
+/// takes precedence over the file. `EMU_BACKENDS` is a comma-separated list.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PoolConfig {
+    /// Only use devices with a name containing this (ignoring case)
+    pub device_name: Option<String>,
+    /// Only use devices with this vendor ID
+    pub vendor_id: Option<usize>,
+    /// Only look for devices with these backends (`wgpu::BackendBit::PRIMARY` if `None`)
+    pub backends: Option<wgpu::BackendBit>,
+    /// Whether or not to validate kernels when they are compiled (`true` if `None`)
+    pub validation: Option<bool>,
+}
+
+impl PoolConfig {
+    /// Loads the configuration from a file and from environment variables
+    ///
+    /// The file is at the path in the `EMU_CONFIG` environment variable or, if that isn't set, `emu.toml`. It's fine for `emu.toml` to not
+    /// exist but a file named by `EMU_CONFIG` must exist (otherwise, `ConfigError::Io` is returned).
+    pub fn load() -> Result<Self, ConfigError> {
+        let mut config = match std::env::var("EMU_CONFIG") {
+            Ok(path) => Self::from_file(path)?,
+            Err(_) if Path::new("emu.toml").exists() => Self::from_file("emu.toml")?,
+            Err(_) => Self::default(),
+        };
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    /// Reads the configuration from the given TOML file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        Self::from_toml(&std::fs::read_to_string(path).map_err(ConfigError::Io)?)
+    }
+
+    /// Reads the configuration from the `[pool]` table of the given TOML
+    /// ```
+    /// # use emu_core::prelude::*;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let config = PoolConfig::from_toml(r#"
+    /// [pool]
+    /// device_name = "intel"
+    /// backends = ["vulkan", "metal"]
+    /// "#)?;
+    /// assert_eq!(config.device_name, Some(String::from("intel")));
+    /// assert_eq!(config.backends, Some(wgpu::BackendBit::VULKAN | wgpu::BackendBit::METAL));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_toml(src: &str) -> Result<Self, ConfigError> {
+        let value = src.parse::<toml::Value>().map_err(ConfigError::Parse)?;
+        let mut config = Self::default();
+
+        if let Some(table) = value.get("pool") {
+            let invalid = |key: &str, value: &toml::Value| {
+                ConfigError::InvalidValue(String::from(key), value.to_string())
+            };
+            if let Some(name) = table.get("device_name") {
+                config.device_name = Some(String::from(
+                    name.as_str().ok_or_else(|| invalid("device_name", name))?,
+                ));
+            }
+            if let Some(vendor_id) = table.get("vendor_id") {
+                config.vendor_id = Some(
+                    vendor_id
+                        .as_integer()
+                        .filter(|vendor_id| *vendor_id >= 0)
+                        .ok_or_else(|| invalid("vendor_id", vendor_id))?
+                        as usize,
+                );
+            }
+            if let Some(backends) = table.get("backends") {
+                let mut bits = wgpu::BackendBit::empty();
+                for backend in backends
+                    .as_array()
+                    .ok_or_else(|| invalid("backends", backends))?
+                {
+                    bits |= backend
+                        .as_str()
+                        .and_then(parse_backend)
+                        .ok_or_else(|| invalid("backends", backend))?;
+                }
+                config.backends = Some(bits);
+            }
+            if let Some(validation) = table.get("validation") {
+                config.validation = Some(
+                    validation
+                        .as_bool()
+                        .ok_or_else(|| invalid("validation", validation))?,
+                );
+            }
+        }
+
+        Ok(config)
+    }
+
+    // overrides fields with any environment variables that are set
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        if let Ok(name) = std::env::var("EMU_DEVICE_NAME") {
+            self.device_name = Some(name);
+        }
+        if let Ok(vendor_id) = std::env::var("EMU_VENDOR_ID") {
+            self.vendor_id = Some(vendor_id.trim().parse().map_err(|_| {
+                ConfigError::InvalidValue(String::from("EMU_VENDOR_ID"), vendor_id)
+            })?);
+        }
+        if let Ok(backends) = std::env::var("EMU_BACKENDS") {
+            let mut bits = wgpu::BackendBit::empty();
+            for backend in backends.split(',') {
+                bits |= parse_backend(backend.trim()).ok_or_else(|| {
+                    ConfigError::InvalidValue(String::from("EMU_BACKENDS"), backends.clone())
+                })?;
+            }
+            self.backends = Some(bits);
+        }
+        if let Ok(validation) = std::env::var("EMU_VALIDATION") {
+            self.validation = Some(match validation.trim() {
+                "1" | "true" => true,
+                "0" | "false" => false,
+                _ => {
+                    return Err(ConfigError::InvalidValue(
+                        String::from("EMU_VALIDATION"),
+                        validation,
+                    ))
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks whether or not a device with the given information matches this configuration
+    pub fn matches(&self, info: Option<&DeviceInfo>) -> bool {
+        if self.device_name.is_none() && self.vendor_id.is_none() {
+            return true;
+        }
+        // we can't pin a device we know nothing about
+        info.map_or(false, |info| {
+            self.device_name.as_ref().map_or(true, |name| {
+                info.name()
+                    .to_ascii_lowercase()
+                    .contains(&name.to_ascii_lowercase())
+            }) && self
+                .vendor_id
+                .map_or(true, |vendor_id| info.vendor_id() == vendor_id)
+        })
+    }
+
+    /// Converts this configuration to options for finding devices
+    ///
+    /// Devices that don't [`match`](#method.matches) are filtered out.
+    pub fn to_instance_options(&self) -> InstanceOptions {
+        let config = self.clone();
+        InstanceOptions::new()
+            .with_backends(self.backends.unwrap_or(wgpu::BackendBit::PRIMARY))
+            .with_validation(self.validation.unwrap_or(true))
+            .with_filter(move |info| config.matches(Some(info)))
+    }
+}
+
+fn parse_backend(backend: &str) -> Option<wgpu::BackendBit> {
+    match backend.to_ascii_lowercase().as_str() {
+        "vulkan" => Some(wgpu::BackendBit::VULKAN),
+        "metal" => Some(wgpu::BackendBit::METAL),
+        "dx12" => Some(wgpu::BackendBit::DX12),
+        "dx11" => Some(wgpu::BackendBit::DX11),
+        "gl" => Some(wgpu::BackendBit::GL),
+        "browser_webgpu" => Some(wgpu::BackendBit::BROWSER_WEBGPU),
+        "primary" => Some(wgpu::BackendBit::PRIMARY),
+        "secondary" => Some(wgpu::BackendBit::SECONDARY),
+        _ => None,
+    }
+}
+
 /// Sets the device pool to the given `Vec` of devices
 ///
 /// You can use `pool` to set up a custom pool of devices. It can only be successfully called just once. Calling `pool` multiple times will result in a panic at runtime.
@@ -109,22 +295,85 @@ pub fn pool(new_device_pool: Vec<DevicePoolMember>) -> Result<(), PoolAlreadyIni
 /// So if you are an application, definitely call this before you use Emu do anything on a GPU device.
 /// If you are a library, definitely make sure that you call this before every possible first time that you use Emu.
 /// You don't have to call it before _every_ API call of course - just before every time when it's possible that this is the first time you are using Emu.
+///
+/// The devices that are added to the pool can be pinned with a configuration file or environment variables. See [`PoolConfig`](struct.PoolConfig.html)
+/// for more details. If the configuration is invalid, the error is printed to stderr and the default configuration is used instead. To find out
+/// about an invalid configuration, use [`try_assert_device_pool_initialized`](fn.try_assert_device_pool_initialized.html). To choose the devices in
+/// code instead, use [`assert_device_pool_initialized_with`](fn.assert_device_pool_initialized_with.html).
 pub async fn assert_device_pool_initialized() {
     if CUSTOM_DEVICE_POOL.lock().unwrap().is_none() {
-        let devices = Device::all().await;
-        *CUSTOM_DEVICE_POOL.lock().unwrap() = Some(
-            devices
-                .into_iter()
-                .map(|device| {
-                    let info = device.info.clone();
-                    DevicePoolMember {
-                        device: Mutex::new(device),
-                        device_info: info,
-                    }
-                })
-                .collect::<Vec<DevicePoolMember>>(),
-        );
+        let config = PoolConfig::load().unwrap_or_else(|error| {
+            eprintln!(
+                "ignoring invalid configuration for pool of devices ({}), using the default configuration",
+                error
+            );
+            PoolConfig::default()
+        });
+        assert_device_pool_initialized_with(config.to_instance_options()).await;
+    }
+}
+
+/// Asserts that the device pool has been initialized like [`assert_device_pool_initialized`](fn.assert_device_pool_initialized.html) but returns an
+/// error if the configuration is invalid
+///
+/// If the configuration is invalid, the pool isn't initialized.
+/// ```
+/// # use emu_core::prelude::*;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// futures::executor::block_on(try_assert_device_pool_initialized())?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn try_assert_device_pool_initialized() -> Result<(), ConfigError> {
+    if CUSTOM_DEVICE_POOL.lock().unwrap().is_none() {
+        let config = PoolConfig::load()?;
+        assert_device_pool_initialized_with(config.to_instance_options()).await;
     }
+    Ok(())
+}
+
+/// Asserts that the device pool has been initialized, initializing it with the devices allowed by the given options if it hasn't been
+///
+/// This is just like [`assert_device_pool_initialized`](fn.assert_device_pool_initialized.html) except that the instance and the adapters the
+/// pool is made from are chosen with [`InstanceOptions`](../device/struct.InstanceOptions.html) instead of with a configuration file. If the
+/// pool was already initialized, the options are ignored.
+pub async fn assert_device_pool_initialized_with(options: InstanceOptions) {
+    if CUSTOM_DEVICE_POOL.lock().unwrap().is_none() {
+        let members = pool_members(&options).await;
+        let mut custom_device_pool = CUSTOM_DEVICE_POOL.lock().unwrap();
+        if custom_device_pool.is_none() {
+            *custom_device_pool = Some(members);
+        }
+    }
+}
+
+/// Sets the device pool to the devices allowed by the given options
+///
+/// This is like [`pool`](fn.pool.html) except that the devices are found for you with
+/// [`Device::all_with_options`](../device/struct.Device.html#method.all_with_options). Like `pool`, this can only be successfully called once.
+pub async fn pool_with_options(
+    options: InstanceOptions,
+) -> Result<(), PoolAlreadyInitializedError> {
+    if CUSTOM_DEVICE_POOL.lock().unwrap().is_some() {
+        return Err(PoolAlreadyInitializedError);
+    }
+    let members = pool_members(&options).await;
+    pool(members)
+}
+
+// finds the devices allowed by the given options and wraps them up for the pool
+async fn pool_members(options: &InstanceOptions) -> Vec<DevicePoolMember> {
+    Device::all_with_options(options)
+        .await
+        .into_iter()
+        .map(|device| {
+            let info = device.info.clone();
+            DevicePoolMember {
+                device: Mutex::new(device),
+                device_info: info,
+            }
+        })
+        .collect()
 }
 
 /// Takes the device currently selected out of the device pool and hands you a mutex for mutating the device's sate
@@ -211,6 +460,257 @@ pub fn info() -> Result<DevicePoolMemberInfo, NoDeviceError> {
     })
 }
 
+/// A description of a device in the pool
+///
+/// See [`topology`](fn.topology.html).
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DeviceTopology {
+    /// The index of the device in the pool
+    pub index: usize,
+    /// Whether or not this is the device currently selected by the thread that described the pool
+    pub selected: bool,
+    /// Information about the device (like its name, vendor, and type)
+    pub info: Option<DeviceInfo>,
+    /// The names of the WebGPU features enabled for the device (like `SHADER_FLOAT64`)
+    pub features: Vec<String>,
+    /// The most bind groups a kernel can use
+    pub max_bind_groups: u32,
+    /// The most storage buffers (like `DeviceBox`s) that can be passed to a kernel
+    pub max_storage_buffers: u32,
+    /// The limits on thread blocks of kernels launched on the device
+    pub workgroup_limits: WorkgroupLimits,
+}
+
+/// A description of all devices in the pool
+///
+/// This is what [`topology`](fn.topology.html) returns. It can be printed (the `Display` implementation is meant to be read by people) or,
+/// with the `serde` feature, serialized.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PoolTopology {
+    /// The devices in the pool in order
+    pub devices: Vec<DeviceTopology>,
+}
+
+impl fmt::Display for PoolTopology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.devices.is_empty() {
+            return writeln!(f, "no devices");
+        }
+        for device in &self.devices {
+            write!(f, "device {}", device.index)?;
+            if device.selected {
+                write!(f, " (selected)")?;
+            }
+            match &device.info {
+                Some(info) => writeln!(f, ": {}", info)?,
+                None => writeln!(f, ": no information")?,
+            }
+            if device.features.is_empty() {
+                writeln!(f, "    features: none")?;
+            } else {
+                writeln!(f, "    features: {}", device.features.join(", "))?;
+            }
+            writeln!(
+                f,
+                "    limits: {} bind groups, {} storage buffers, {} threads per thread block ({:?} in each dimension), {} thread blocks in each dimension",
+                device.max_bind_groups,
+                device.max_storage_buffers,
+                device.workgroup_limits.max_invocations,
+                device.workgroup_limits.max_size,
+                device.workgroup_limits.max_count_per_dimension
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Describes all devices in the pool, including their type, vendor, limits, and features
+///
+/// This is meant for logging the environment Emu is running in, like when reporting a bug that only happens on some devices.
+/// Each device is locked while it is described so this must not be called while holding a device from [`take`](fn.take.html).
+/// ```
+/// # use emu_core::prelude::*;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// futures::executor::block_on(assert_device_pool_initialized());
+/// let topology = topology();
+/// assert_eq!(topology.devices.len(), info_all().len());
+/// println!("{}", topology);
+/// # Ok(())
+/// # }
+/// ```
+pub fn topology() -> PoolTopology {
+    maybe_initialize_device_pool();
+    maybe_initialize_device_idx();
+
+    let selected = DEVICE_IDX.with(|idx| *idx.borrow());
+    PoolTopology {
+        devices: DEVICE_POOL
+            .as_ref()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .map(|(i, member)| {
+                let device = member.device.lock().unwrap();
+                let limits = device.device.limits();
+                DeviceTopology {
+                    index: i,
+                    selected: selected == Some(i),
+                    info: member.device_info.clone(),
+                    features: feature_names(device.device.features()),
+                    max_bind_groups: limits.max_bind_groups,
+                    max_storage_buffers: limits.max_storage_buffers_per_shader_stage,
+                    workgroup_limits: device.workgroup_limits(),
+                }
+            })
+            .collect(),
+    }
+}
+
+// the names of the given WebGPU features
+fn feature_names(features: wgpu::Features) -> Vec<String> {
+    // bitflags are printed as their names separated by " | " (or "(empty)" if there are none)
+    format!("{:?}", features)
+        .split(" | ")
+        .filter(|feature| *feature != "(empty)")
+        .map(String::from)
+        .collect()
+}
+
+/// The capabilities of an adapter (a device that hasn't been requested yet)
+///
+/// See [`probe`](fn.probe.html). Note that WebGPU doesn't have a feature for 16-bit floats yet so kernels that want them should use
+/// 32-bit floats instead.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AdapterCapabilities {
+    /// Information about the adapter (like its name, vendor, and type)
+    pub info: DeviceInfo,
+    /// The names of the WebGPU features the adapter supports (like `SHADER_FLOAT64`)
+    pub features: Vec<String>,
+    /// The most bind groups a kernel can use
+    pub max_bind_groups: u32,
+    /// The most storage buffers (like `DeviceBox`s) that can be passed to a kernel
+    pub max_storage_buffers: u32,
+    /// The most bytes of push constants a kernel can use (0 if push constants aren't supported)
+    pub max_push_constant_size: u32,
+    /// The limits on thread blocks of kernels launched on the adapter
+    pub workgroup_limits: WorkgroupLimits,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    wgpu_features: wgpu::Features,
+}
+
+impl AdapterCapabilities {
+    /// Returns whether the adapter supports all of the given WebGPU features
+    pub fn supports(&self, features: wgpu::Features) -> bool {
+        self.wgpu_features.contains(features)
+    }
+
+    /// Returns whether the adapter can run kernels that use 64-bit floats (doubles)
+    ///
+    /// Devices in the default pool have 64-bit floats enabled whenever their adapter supports them (see [`Device::supports_f64`](../device/struct.Device.html#method.supports_f64)).
+    pub fn supports_f64(&self) -> bool {
+        self.supports(wgpu::Features::SHADER_FLOAT64)
+    }
+
+    /// Returns whether the adapter supports timestamp queries (for timing kernels on the device)
+    pub fn supports_timestamps(&self) -> bool {
+        self.supports(wgpu::Features::TIMESTAMP_QUERY)
+    }
+
+    /// Returns whether the adapter supports push constants
+    pub fn supports_push_constants(&self) -> bool {
+        self.supports(wgpu::Features::PUSH_CONSTANTS) && self.max_push_constant_size > 0
+    }
+}
+
+/// Lists the capabilities of all adapters that the default pool would be made from
+///
+/// Unlike [`topology`](fn.topology.html), this doesn't request any devices and doesn't initialize the pool. So it can be called first to decide
+/// which optional features of an application to enable (or which options to initialize the pool with) before any resources are allocated. The adapters
+/// are listed in the order devices would be added to the pool.
+/// ```
+/// # use emu_core::prelude::*;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let use_doubles = probe().first().map_or(false, |adapter| adapter.supports_f64());
+/// futures::executor::block_on(assert_device_pool_initialized());
+/// if use_doubles {
+///     println!("running with 64-bit floats");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn probe() -> Vec<AdapterCapabilities> {
+    probe_with_options(&InstanceOptions::new())
+}
+
+/// Lists the capabilities of all adapters allowed by the given options
+///
+/// This is just like [`probe`](fn.probe.html) but for a pool initialized with [`assert_device_pool_initialized_with`](fn.assert_device_pool_initialized_with.html).
+pub fn probe_with_options(options: &InstanceOptions) -> Vec<AdapterCapabilities> {
+    options
+        .adapters()
+        .into_iter()
+        .map(|adapter| {
+            let features = adapter.features();
+            let limits = adapter.limits();
+            AdapterCapabilities {
+                info: DeviceInfo(adapter.get_info()),
+                features: feature_names(features),
+                max_bind_groups: limits.max_bind_groups,
+                max_storage_buffers: limits.max_storage_buffers_per_shader_stage,
+                max_push_constant_size: limits.max_push_constant_size,
+                workgroup_limits: GUARANTEED_WORKGROUP_LIMITS,
+                wgpu_features: features,
+            }
+        })
+        .collect()
+}
+
+/// Copies the data in a `DeviceBox` on one device in the pool to a new `DeviceBox` on another device in the pool
+///
+/// The devices are given by their index in the pool (see [`info_all`](fn.info_all.html)) and `device_obj` must be stored on the source device.
+/// This lets you build pipelines where different stages run on different devices. For example, you might preprocess data on an integrated GPU and then
+/// do heavy compute on a discrete GPU. WebGPU doesn't support copying directly between devices so the data is downloaded to the host and then uploaded to
+/// the destination device. The new `DeviceBox` has the same mutability as `device_obj`.
+/// ```
+/// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// futures::executor::block_on(assert_device_pool_initialized());
+/// let last = info_all().len() - 1;
+/// select(|idx, _info| idx == 0)?;
+/// let data: DeviceBox<[f32]> = vec![1.0; 1024].as_device_boxed_mut()?;
+/// let data_on_last = futures::executor::block_on(transfer(0, last, &data))?;
+/// select(|idx, _info| idx == last)?;
+/// assert_eq!(futures::executor::block_on(data_on_last.get())?, vec![1.0; 1024].into_boxed_slice());
+/// # Ok(())
+/// # }
+/// ```
+pub async fn transfer<T: ?Sized>(
+    src_device_idx: usize,
+    dst_device_idx: usize,
+    device_obj: &DeviceBox<T>,
+) -> Result<DeviceBox<T>, GetError> {
+    maybe_initialize_device_pool();
+
+    let pool = DEVICE_POOL.as_ref().unwrap();
+    let src_device = &pool.get(src_device_idx).ok_or(GetError::NoDevice)?.device;
+    let dst_device = &pool.get(dst_device_idx).ok_or(GetError::NoDevice)?.device;
+
+    // we only lock 1 device at a time so that transferring between the same device doesn't deadlock
+    // and the source device is never locked across an `.await`
+    let bytes = Device::get_bytes_locked(src_device, device_obj)
+        .await
+        .map_err(GetError::Completion)?;
+    let mut dst_device = dst_device.lock().unwrap();
+    let copied: DeviceBox<[u8]> = match device_obj.mutability {
+        Some(Mutability::Const) => dst_device.create_from(bytes.as_slice()),
+        _ => dst_device.create_from_mut(bytes.as_slice()),
+    };
+    Ok(copied.retype())
+}
+
 /// Selects a device from the pool using the given selector function
 ///
 /// Emu uses thread-local storage to keep track of the selected device for each thread.
@@ -0,0 +1,429 @@
+//! Python bindings for compiling and launching kernels
+//!
+//! With the `python` feature, this module defines a Python extension module called `emu` with 3 classes.
+//! - `PyDevicePool` initializes the pool of devices and lists and selects devices in it
+//! - `PyDeviceBox` uploads an array (anything supporting the buffer protocol, like a numpy array) of `float32`, `int32`, or `uint32` to the
+//! device and downloads it back
+//! - `PyKernel` compiles a kernel from GLSL and launches it with `PyDeviceBox`s and numbers as arguments
+//!
+//! The parameters of a `PyKernel` are declared in GLSL (like `"float[] data"` or `"uint n"`) and are mutable if prefixed with `mut`. Arrays
+//! are passed `PyDeviceBox`s with elements of the same type and scalars are passed Python numbers.
+//! ```python
+//! import numpy as np
+//! import emu
+//!
+//! pool = emu.PyDevicePool()
+//! print(pool.devices())
+//!
+//! data = emu.PyDeviceBox(np.arange(1024, dtype=np.float32))
+//! kernel = emu.PyKernel(
+//!     ["mut float[] data", "float scale"],
+//!     "data[gl_GlobalInvocationID.x] *= scale;",
+//! )
+//! kernel.launch(1024, [data, 2.0])
+//!
+//! result = np.zeros(1024, dtype=np.float32)
+//! data.get_into(result)
+//! ```
+//! To build the extension module, make a crate with `crate-type = ["cdylib"]` and a `lib.name` of `emu` that depends on `emu_core` with the
+//! `python` feature and on `pyo3` with the `extension-module` feature. [`maturin`](https://github.com/PyO3/maturin) can then build and
+//! install it. GLSL is compiled with `naga` (so `shaderc` isn't needed) unless the `glsl-compile` feature is also enabled.
+
+use std::error::Error;
+use std::sync::Arc;
+
+use pyo3::buffer::{Element, PyBuffer};
+use pyo3::exceptions::{PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use zerocopy::*;
+
+use crate::boxed::*;
+use crate::cache::*;
+use crate::compile::*;
+use crate::compile_impls::*;
+use crate::device::*;
+use crate::error::*;
+use crate::pool::*;
+use crate::spawn::*;
+
+// errors from Emu are raised as RuntimeError's with the whole chain of sources in the message
+fn to_py_err(error: impl Error + 'static) -> PyErr {
+    PyRuntimeError::new_err(display_chain(&error))
+}
+
+/// The pool of devices that kernels are compiled for and launched on
+///
+/// Creating a `PyDevicePool` initializes the pool if it hasn't been initialized. The devices can be pinned with a configuration file or
+/// environment variables (see [`PoolConfig`](../pool/struct.PoolConfig.html)).
+#[pyclass]
+pub struct PyDevicePool {}
+
+#[pymethods]
+impl PyDevicePool {
+    #[new]
+    fn new() -> PyResult<Self> {
+        futures::executor::block_on(assert_device_pool_initialized());
+        take().map_err(to_py_err)?;
+        Ok(Self {})
+    }
+
+    /// Returns the names of the devices in the pool in order
+    fn devices(&self) -> Vec<String> {
+        info_all()
+            .into_iter()
+            .map(|member_info| member_info.info.map(|info| info.name()).unwrap_or_default())
+            .collect()
+    }
+
+    /// Selects the device with the given index for the current thread
+    fn select(&self, index: usize) -> PyResult<()> {
+        crate::pool::select(|i, _| i == index).map_err(to_py_err)
+    }
+}
+
+// the types of elements that can be in a PyDeviceBox
+#[derive(Clone, Copy, PartialEq)]
+enum PyDtype {
+    F32,
+    I32,
+    U32,
+}
+
+impl PyDtype {
+    fn name(self) -> &'static str {
+        match self {
+            PyDtype::F32 => "float32",
+            PyDtype::I32 => "int32",
+            PyDtype::U32 => "uint32",
+        }
+    }
+}
+
+enum PyDeviceBoxData {
+    F32(DeviceBox<[f32]>),
+    I32(DeviceBox<[i32]>),
+    U32(DeviceBox<[u32]>),
+}
+
+// runs the given expression with the DeviceBox of whichever type of elements is stored
+macro_rules! with_device_box {
+    ($data:expr, $device_box:ident => $body:expr) => {
+        match $data {
+            PyDeviceBoxData::F32($device_box) => $body,
+            PyDeviceBoxData::I32($device_box) => $body,
+            PyDeviceBoxData::U32($device_box) => $body,
+        }
+    };
+}
+
+/// An array of `float32`, `int32`, or `uint32` on a device
+#[pyclass]
+pub struct PyDeviceBox {
+    data: PyDeviceBoxData,
+}
+
+fn check_len(device_box_len: usize, array_len: usize) -> PyResult<()> {
+    if device_box_len == array_len {
+        Ok(())
+    } else {
+        Err(PyValueError::new_err(format!(
+            "the array has {} elements but the device box has {}",
+            array_len, device_box_len
+        )))
+    }
+}
+
+fn upload<T: Element + AsBytes>(
+    py: Python,
+    device_box: &mut DeviceBox<[T]>,
+    array: &PyAny,
+) -> PyResult<()> {
+    let buffer = PyBuffer::<T>::get(array)?;
+    check_len(device_box.len(), buffer.item_count())?;
+    device_box.set(buffer.to_vec(py)?).map_err(to_py_err)
+}
+
+fn download_into<T: Element + FromBytes + Send + Sync>(
+    py: Python,
+    device_box: &DeviceBox<[T]>,
+    array: &PyAny,
+) -> PyResult<()> {
+    let buffer = PyBuffer::<T>::get(array)?;
+    if buffer.readonly() {
+        return Err(PyValueError::new_err(
+            "can't download into a read-only array",
+        ));
+    }
+    check_len(device_box.len(), buffer.item_count())?;
+    let data = py
+        .allow_threads(|| futures::executor::block_on(device_box.get()))
+        .map_err(to_py_err)?;
+    buffer.copy_from_slice(py, &data)
+}
+
+fn download<T: Element + FromBytes + ToPyObject + Send + Sync>(
+    py: Python,
+    device_box: &DeviceBox<[T]>,
+) -> PyResult<PyObject> {
+    let data = py
+        .allow_threads(|| futures::executor::block_on(device_box.get()))
+        .map_err(to_py_err)?;
+    Ok(data.to_vec().to_object(py))
+}
+
+#[pymethods]
+impl PyDeviceBox {
+    /// Uploads the given array to the device currently selected from the pool
+    ///
+    /// The array can be anything supporting the buffer protocol (like a numpy array or an `array.array`) with elements of type `float32`,
+    /// `int32`, or `uint32`.
+    #[new]
+    fn new(py: Python, array: &PyAny) -> PyResult<Self> {
+        let data = if let Ok(buffer) = PyBuffer::<f32>::get(array) {
+            PyDeviceBoxData::F32(
+                buffer
+                    .to_vec(py)?
+                    .as_device_boxed_mut()
+                    .map_err(to_py_err)?,
+            )
+        } else if let Ok(buffer) = PyBuffer::<i32>::get(array) {
+            PyDeviceBoxData::I32(
+                buffer
+                    .to_vec(py)?
+                    .as_device_boxed_mut()
+                    .map_err(to_py_err)?,
+            )
+        } else if let Ok(buffer) = PyBuffer::<u32>::get(array) {
+            PyDeviceBoxData::U32(
+                buffer
+                    .to_vec(py)?
+                    .as_device_boxed_mut()
+                    .map_err(to_py_err)?,
+            )
+        } else {
+            return Err(PyTypeError::new_err(
+                "expected an array supporting the buffer protocol with elements of type float32, int32, or uint32",
+            ));
+        };
+        Ok(Self { data })
+    }
+
+    /// The number of elements
+    #[getter]
+    fn size(&self) -> usize {
+        with_device_box!(&self.data, device_box => device_box.len())
+    }
+
+    /// The type of the elements (`"float32"`, `"int32"`, or `"uint32"`)
+    #[getter]
+    fn dtype(&self) -> &'static str {
+        self.dtype_of().name()
+    }
+
+    /// Uploads the given array, which must have the same type and number of elements
+    fn set(&mut self, py: Python, array: &PyAny) -> PyResult<()> {
+        with_device_box!(&mut self.data, device_box => upload(py, device_box, array))
+    }
+
+    /// Downloads into the given writable array, which must have the same type and number of elements
+    fn get_into(&self, py: Python, array: &PyAny) -> PyResult<()> {
+        with_device_box!(&self.data, device_box => download_into(py, device_box, array))
+    }
+
+    /// Downloads to a list
+    fn to_list(&self, py: Python) -> PyResult<PyObject> {
+        with_device_box!(&self.data, device_box => download(py, device_box))
+    }
+}
+
+impl PyDeviceBox {
+    fn dtype_of(&self) -> PyDtype {
+        match self.data {
+            PyDeviceBoxData::F32(_) => PyDtype::F32,
+            PyDeviceBoxData::I32(_) => PyDtype::I32,
+            PyDeviceBoxData::U32(_) => PyDtype::U32,
+        }
+    }
+}
+
+// a parameter of a PyKernel
+struct PyParam {
+    dtype: PyDtype,
+    is_array: bool,
+}
+
+// parses a parameter like "mut float[] data" into its declaration in GLSL, whether or not it is mutable, and its type
+fn parse_param(param: &str) -> PyResult<(String, bool, PyParam)> {
+    let param = param.trim();
+    let (decl, is_mut) = match param.strip_prefix("mut ") {
+        Some(decl) => (decl.trim(), true),
+        None => (param, false),
+    };
+    let mut words = decl.split_whitespace();
+    let (ty, name) = match (words.next(), words.next(), words.next()) {
+        (Some(ty), Some(name), None) => (ty, name),
+        _ => {
+            return Err(PyValueError::new_err(format!(
+                "expected a parameter like \"float[] data\" but got \"{}\"",
+                param
+            )))
+        }
+    };
+    let is_array = ty.ends_with("[]") || name.ends_with("[]");
+    let dtype = match ty.trim_end_matches("[]") {
+        "float" => PyDtype::F32,
+        "int" => PyDtype::I32,
+        "uint" => PyDtype::U32,
+        _ => {
+            return Err(PyValueError::new_err(format!(
+                "the type of parameter \"{}\" must be float, int, or uint (or an array of them)",
+                param
+            )))
+        }
+    };
+    if is_mut && !is_array {
+        return Err(PyValueError::new_err(format!(
+            "parameter \"{}\" is a scalar so it can't be mutable",
+            param
+        )));
+    }
+    Ok((decl.to_string(), is_mut, PyParam { dtype, is_array }))
+}
+
+fn add_param<T: ?Sized>(kernel: GlslKernel, decl: String, is_mut: bool) -> GlslKernel {
+    if is_mut {
+        kernel.param_mut::<T, _>(decl)
+    } else {
+        kernel.param::<T, _>(decl)
+    }
+}
+
+/// A kernel compiled from GLSL for the device currently selected from the pool
+#[pyclass]
+pub struct PyKernel {
+    kernel: Arc<DeviceFnMut>,
+    params: Vec<PyParam>,
+}
+
+#[pymethods]
+impl PyKernel {
+    /// Compiles a kernel with the given parameters and code
+    ///
+    /// Each parameter is declared in GLSL and prefixed with `mut` if it is mutable (like `"mut float[] data"`). The code is the body of the
+    /// kernel and the helper code can define functions it uses. The local size is the number of threads in each thread block in up to 3
+    /// dimensions. Kernels are cached so compiling the same kernel more than once is cheap.
+    #[new]
+    #[args(helper_code = "None", local_size = "None")]
+    fn new(
+        params: Vec<String>,
+        code: &str,
+        helper_code: Option<&str>,
+        local_size: Option<Vec<u32>>,
+    ) -> PyResult<Self> {
+        let mut kernel = GlslKernel::new();
+        for num_threads in local_size.unwrap_or_default() {
+            kernel = kernel.spawn(num_threads);
+        }
+        let mut py_params = vec![];
+        for param in &params {
+            let (decl, is_mut, py_param) = parse_param(param)?;
+            kernel = match (py_param.dtype, py_param.is_array) {
+                (PyDtype::F32, true) => add_param::<[f32]>(kernel, decl, is_mut),
+                (PyDtype::I32, true) => add_param::<[i32]>(kernel, decl, is_mut),
+                (PyDtype::U32, true) => add_param::<[u32]>(kernel, decl, is_mut),
+                (PyDtype::F32, false) => add_param::<f32>(kernel, decl, is_mut),
+                (PyDtype::I32, false) => add_param::<i32>(kernel, decl, is_mut),
+                (PyDtype::U32, false) => add_param::<u32>(kernel, decl, is_mut),
+            };
+            py_params.push(py_param);
+        }
+        if let Some(helper_code) = helper_code {
+            kernel = kernel.with_helper_code(helper_code);
+        }
+        kernel = kernel.with_kernel_code(code);
+
+        let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(kernel)
+            .map_err(to_py_err)?
+            .finish()
+            .map_err(to_py_err)?;
+        Ok(Self {
+            kernel,
+            params: py_params,
+        })
+    }
+
+    /// Launches the kernel on the given number of thread blocks with the given arguments
+    ///
+    /// The number of thread blocks is a number or a tuple of up to 3 numbers (1 for each dimension). Arrays are passed as `PyDeviceBox`s and
+    /// scalars as numbers. This doesn't wait for the kernel to complete. Downloading a `PyDeviceBox` does.
+    fn launch(&self, blocks: &PyAny, args: Vec<&PyAny>) -> PyResult<()> {
+        let blocks: Vec<u32> = match blocks.extract::<u32>() {
+            Ok(num_blocks) => vec![num_blocks],
+            Err(_) => blocks.extract()?,
+        };
+        if blocks.is_empty() || blocks.len() > 3 {
+            return Err(PyValueError::new_err(
+                "the number of thread blocks must be given for 1 to 3 dimensions",
+            ));
+        }
+        if args.len() != self.params.len() {
+            return Err(PyValueError::new_err(format!(
+                "the kernel has {} parameters but {} arguments were given",
+                self.params.len(),
+                args.len()
+            )));
+        }
+
+        // the device boxes are borrowed before building the arguments so that they outlive the arguments
+        let device_boxes = self
+            .params
+            .iter()
+            .zip(&args)
+            .map(|(param, arg)| {
+                if param.is_array {
+                    let device_box = arg.extract::<PyRef<PyDeviceBox>>()?;
+                    if device_box.dtype_of() != param.dtype {
+                        return Err(PyTypeError::new_err(format!(
+                            "expected a device box of {} but got a device box of {}",
+                            param.dtype.name(),
+                            device_box.dtype_of().name()
+                        )));
+                    }
+                    Ok(Some(device_box))
+                } else {
+                    Ok(None)
+                }
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+
+        let mut builder = ArgsBuilder::new();
+        for ((param, arg), device_box) in self.params.iter().zip(&args).zip(&device_boxes) {
+            builder = match device_box {
+                Some(device_box) => {
+                    with_device_box!(&device_box.data, device_box => builder.arg(device_box))
+                }
+                None => match param.dtype {
+                    PyDtype::F32 => builder.arg(arg.extract::<f32>()?),
+                    PyDtype::I32 => builder.arg(arg.extract::<i32>()?),
+                    PyDtype::U32 => builder.arg(arg.extract::<u32>()?),
+                },
+            };
+        }
+        let args = builder.try_build().map_err(to_py_err)?;
+
+        let mut spawner = spawn(blocks[0]);
+        for &num_blocks in &blocks[1..] {
+            spawner = spawner.spawn(num_blocks);
+        }
+        unsafe { spawner.launch((self.kernel.clone(), args)) }.map_err(to_py_err)
+    }
+}
+
+/// The `emu` Python module
+#[pymodule]
+pub fn emu(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<PyDevicePool>()?;
+    m.add_class::<PyDeviceBox>()?;
+    m.add_class::<PyKernel>()?;
+    Ok(())
+}
@@ -0,0 +1,236 @@
+//! A queue for driving a device from asynchronous code with backpressure
+//!
+//! [`launch`](../spawn/struct.Spawner.html#method.launch) submits work to a device right away and never waits. That's fine for a program that launches
+//! a handful of kernels but an asynchronous application (like a web server running inference for each request) can end up queueing work faster than the
+//! device can do it. [`AsyncQueue`](struct.AsyncQueue.html) instead records launches into batches, submits a batch once it's full, and waits (asynchronously)
+//! before submitting if too many batches are still running on the device.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use futures::FutureExt;
+use zerocopy::*;
+
+use crate::device::*;
+use crate::error::*;
+use crate::pool::*;
+use crate::spawn::*;
+
+// the default number of batches that can be running on the device at once
+const DEFAULT_MAX_IN_FLIGHT: usize = 4;
+// the default number of launches recorded before they are submitted together
+const DEFAULT_MAX_BATCH: usize = 16;
+
+/// A queue of launches and downloads for a single device that limits how much work is submitted at once
+///
+/// An `AsyncQueue` is made for the device [`take`](../pool/fn.take.html) would return and keeps using that device even if another one is selected later.
+/// Launches are recorded and submitted in batches of up to [`with_max_batch`](#method.with_max_batch) launches. Before a batch is submitted, the queue waits
+/// until fewer than [`with_max_in_flight`](#method.with_max_in_flight) batches are still running on the device. So awaiting a launch is how an application
+/// feels backpressure from the device.
+/// ```
+/// # use {emu_core::prelude::*, emu_core::queue::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # futures::executor::block_on(assert_device_pool_initialized());
+/// let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+///     GlslKernel::new()
+///         .param_mut::<[f32], _>("float[] data")
+///         .with_kernel_code("data[gl_GlobalInvocationID.x] = data[gl_GlobalInvocationID.x] + 1.0;"),
+/// )?
+/// .finish()?;
+///
+/// let queue = AsyncQueue::new()?.with_max_in_flight(2).with_max_batch(8);
+/// let mut data: DeviceBox<[f32]> = vec![0.0; 1024].as_device_boxed_mut()?;
+/// futures::executor::block_on(async {
+///     for _ in 0..100 {
+///         unsafe {
+///             queue.launch(&spawn(1024), call!(kernel.clone(), &mut data)).await?;
+///         }
+///     }
+///     // downloading submits whatever hasn't been submitted yet
+///     assert_eq!(queue.get(&data).await?, vec![100.0; 1024].into_boxed_slice());
+///     Ok::<(), Box<dyn std::error::Error>>(())
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncQueue {
+    device: &'static Mutex<Device>,
+    max_in_flight: usize,
+    max_batch: usize,
+    state: Mutex<AsyncQueueState>,
+}
+
+struct AsyncQueueState {
+    // launches that have been recorded but not yet submitted
+    pending: Vec<wgpu::CommandBuffer>,
+    // held until the pending launches are submitted so that the arguments they pass by value aren't overwritten
+    pending_leases: Vec<ValueLease>,
+    // batches that have been submitted but may not have completed yet, oldest first
+    in_flight: VecDeque<Batch>,
+}
+
+// a submitted batch of launches
+struct Batch {
+    _fence: wgpu::Buffer,
+    done: FenceFuture,
+}
+
+impl AsyncQueue {
+    /// Creates a queue for the currently selected device in the pool
+    ///
+    /// By default, at most 4 batches can be in flight and each batch has at most 16 launches.
+    pub fn new() -> Result<Self, NoDeviceError> {
+        Ok(Self {
+            device: take()?,
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            max_batch: DEFAULT_MAX_BATCH,
+            state: Mutex::new(AsyncQueueState {
+                pending: vec![],
+                pending_leases: vec![],
+                in_flight: VecDeque::new(),
+            }),
+        })
+    }
+
+    /// Sets the most batches that can be submitted to the device and not yet completed
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        assert!(
+            max_in_flight > 0,
+            "at least 1 batch must be allowed to be in flight"
+        );
+        self.max_in_flight = max_in_flight;
+        self
+    }
+
+    /// Sets the most launches that are recorded before being submitted together
+    pub fn with_max_batch(mut self, max_batch: usize) -> Self {
+        assert!(max_batch > 0, "a batch must have at least 1 launch");
+        self.max_batch = max_batch;
+        self
+    }
+
+    /// The number of batches that have been submitted but haven't yet been seen to complete
+    pub fn in_flight(&self) -> usize {
+        self.state.lock().unwrap().in_flight.len()
+    }
+
+    /// The number of launches that have been recorded but not yet submitted
+    pub fn pending(&self) -> usize {
+        self.state.lock().unwrap().pending.len()
+    }
+
+    /// Records a launch of the given `DeviceFnMut` with the given arguments on the space of threads of the given `Spawner`
+    ///
+    /// This works just like [`launch`](../spawn/struct.Spawner.html#method.launch) except that the launch is only submitted once the current batch is full
+    /// (or the queue is [`flush`](#method.flush)ed). If the batch is full and too many batches are in flight, this waits for one of them to complete.
+    ///
+    /// This is unsafe for the same reason `launch` is unsafe.
+    pub async unsafe fn launch<'a>(
+        &self,
+        spawner: &Spawner,
+        device_fn_mut_with_args: (Arc<DeviceFnMut>, DeviceFnMutArgs<'a>),
+    ) -> Result<(), LaunchError> {
+        let (device_fn_mut, args) = device_fn_mut_with_args;
+        let batch_is_full = {
+            let device = self.device.lock().unwrap();
+            let (command_buffer, lease) =
+                device.encode_call(&device_fn_mut, spawner.grid()?, args)?;
+
+            let mut state = self.state.lock().unwrap();
+            state.pending.push(command_buffer);
+            state.pending_leases.push(lease);
+            state.pending.len() >= self.max_batch
+        };
+
+        if batch_is_full {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Submits all recorded launches, first waiting until fewer than the maximum number of batches are in flight
+    pub async fn flush(&self) -> Result<(), LaunchError> {
+        if self.state.lock().unwrap().pending.is_empty() {
+            return Ok(());
+        }
+        while self.retire()? >= self.max_in_flight {
+            YieldNow(false).await;
+        }
+
+        let mut device = self.device.lock().unwrap();
+        let mut state = self.state.lock().unwrap();
+        device.submit_all(state.pending.drain(..));
+        state.pending_leases.clear();
+        let (fence, done) = device.submit_fence();
+        state.in_flight.push_back(Batch {
+            _fence: fence,
+            done,
+        });
+        Ok(())
+    }
+
+    /// Submits all recorded launches and waits for everything submitted so far to complete
+    pub async fn finish(&self) -> Result<(), LaunchError> {
+        self.flush().await?;
+        while self.retire()? > 0 {
+            YieldNow(false).await;
+        }
+        Ok(())
+    }
+
+    /// Downloads the data in the given `DeviceBox<[T]>` after submitting all recorded launches
+    ///
+    /// The download happens after every launch recorded so far, just like [`DeviceBox::get`](../device/struct.DeviceBox.html#method.get) happens after every launch
+    /// submitted so far.
+    pub async fn get<T: FromBytes + Copy>(
+        &self,
+        device_obj: &DeviceBox<[T]>,
+    ) -> Result<Box<[T]>, GetError> {
+        self.flush().await.map_err(GetError::Launch)?;
+        self.device
+            .lock()
+            .unwrap()
+            .get(device_obj)
+            .await
+            .map_err(GetError::Completion)
+    }
+
+    // polls the device and removes batches that have completed from the front of the queue
+    // this returns the number of batches still in flight
+    fn retire(&self) -> Result<usize, LaunchError> {
+        let device = self.device.lock().unwrap();
+        device.device.poll(wgpu::Maintain::Poll);
+
+        let mut state = self.state.lock().unwrap();
+        while let Some(batch) = state.in_flight.front_mut() {
+            match (&mut batch.done).now_or_never() {
+                Some(result) => {
+                    result.map_err(LaunchError::Runtime)?;
+                    state.in_flight.pop_front();
+                }
+                None => break,
+            }
+        }
+        Ok(state.in_flight.len())
+    }
+}
+
+// a future that is pending the first time it is polled so that other tasks get a chance to run while we wait on the device
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            Poll::Ready(())
+        } else {
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
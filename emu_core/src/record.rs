@@ -0,0 +1,386 @@
+//! Recording the API calls made to devices so that they can be replayed later
+//!
+//! This module requires the `record` feature. While recording, every `DeviceBox` that is created, set, filled, or downloaded and every
+//! kernel that is compiled or launched is added to a [`Trace`](struct.Trace.html). A trace can be printed as a timeline of which buffers each call
+//! used, saved to a file to attach to a bug report, and re-executed with [`replay`](../replay/fn.replay.html).
+//! ```
+//! # use {emu_core::prelude::*, emu_core::record::*, emu_glsl::*, zerocopy::*};
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # futures::executor::block_on(assert_device_pool_initialized());
+//! start_recording();
+//! let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+//!     GlslKernel::new()
+//!         .param_mut::<[f32], _>("float[] data")
+//!         .with_kernel_code("data[gl_GlobalInvocationID.x] = data[gl_GlobalInvocationID.x] * 2.0;"),
+//! )?
+//! .finish()?;
+//! let mut data: DeviceBox<[f32]> = vec![1.0; 1024].as_device_boxed_mut()?;
+//! unsafe {
+//!     spawn(1024).launch(call!(kernel, &mut data))?;
+//! }
+//! futures::executor::block_on(data.get())?;
+//! let trace = stop_recording();
+//!
+//! // this prints each call on its own line
+//! println!("{}", trace);
+//! trace.to_file(std::env::temp_dir().join("doubling.emutrace"))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::device::*;
+use crate::error::*;
+
+// the first bytes of every file written by Trace::to_file
+const FILE_MAGIC: &[u8] = b"EMUTRC01";
+
+lazy_static! {
+    // the events recorded so far, or None if we aren't recording
+    static ref RECORDING: Mutex<Option<Vec<Event>>> = Mutex::new(None);
+}
+
+/// Starts recording API calls, throwing away anything recorded before
+pub fn start_recording() {
+    *RECORDING.lock().unwrap() = Some(vec![]);
+}
+
+/// Stops recording API calls and returns everything recorded since [`start_recording`](fn.start_recording.html)
+///
+/// If nothing is being recorded, this returns an empty `Trace`.
+pub fn stop_recording() -> Trace {
+    Trace {
+        events: RECORDING.lock().unwrap().take().unwrap_or_default(),
+    }
+}
+
+/// Returns whether or not API calls are currently being recorded
+pub fn is_recording() -> bool {
+    RECORDING.lock().unwrap().is_some()
+}
+
+// adds the given event to the recording
+// the event is only made if we are recording so that this is cheap otherwise
+pub(crate) fn record(event: impl FnOnce() -> Event) {
+    if let Some(events) = RECORDING.lock().unwrap().as_mut() {
+        events.push(event());
+    }
+}
+
+/// An argument passed to a launched kernel
+#[derive(Clone, Debug, PartialEq)]
+pub enum Arg {
+    /// The `DeviceBox` with the given id
+    Buffer(u64),
+    /// A value (like a scalar or a tuple) passed as bytes
+    Value(Vec<u8>),
+    /// A buffer that wasn't made from a `DeviceBox` (e.g. - one bound directly from wgpu) and so can't be replayed
+    Unknown,
+}
+
+/// A single API call that was recorded
+///
+/// Buffers and kernels are identified by ids that are unique within a run of a program.
+#[derive(Clone)]
+pub enum Event {
+    /// A kernel was compiled with [`Device::compile`](../device/struct.Device.html#method.compile)
+    Compile {
+        kernel: u64,
+        name: String,
+        params: DeviceFnMutParams,
+        code: Vec<u32>,
+    },
+    /// A `DeviceBox` was created, either with its initial data or uninitialized
+    Create {
+        buffer: u64,
+        size: u64,
+        mutability: Mutability,
+        data: Option<Vec<u8>>,
+    },
+    /// A `DeviceBox` was set with [`Device::set_from`](../device/struct.Device.html#method.set_from)
+    Set { buffer: u64, data: Vec<u8> },
+    /// A `DeviceBox` was filled with a pattern of bytes repeated
+    Fill { buffer: u64, pattern: Vec<u8> },
+    /// A `DeviceBox` was downloaded
+    Get { buffer: u64 },
+    /// A kernel was launched with the given arguments, each with its set number and binding number
+    Call {
+        kernel: u64,
+        work_space_dim: (u32, u32, u32),
+        args: Vec<(u32, u32, Arg)>,
+    },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Compile {
+                kernel, name, code, ..
+            } => write!(
+                f,
+                "compile kernel {} with entry point `{}` ({} words of SPIR-V)",
+                kernel,
+                name,
+                code.len()
+            ),
+            Event::Create {
+                buffer,
+                size,
+                mutability,
+                data,
+            } => write!(
+                f,
+                "create {} buffer {} of {} bytes{}",
+                match mutability {
+                    Mutability::Mut => "mutable",
+                    Mutability::Const => "constant",
+                },
+                buffer,
+                size,
+                if data.is_some() {
+                    " from host data"
+                } else {
+                    ""
+                }
+            ),
+            Event::Set { buffer, data } => {
+                write!(f, "set buffer {} to {} bytes", buffer, data.len())
+            }
+            Event::Fill { buffer, pattern } => {
+                write!(f, "fill buffer {} with {:?}", buffer, pattern)
+            }
+            Event::Get { buffer } => write!(f, "get buffer {}", buffer),
+            Event::Call {
+                kernel,
+                work_space_dim,
+                args,
+            } => {
+                write!(f, "call kernel {} on {:?} with", kernel, work_space_dim)?;
+                for (i, (_, _, arg)) in args.iter().enumerate() {
+                    write!(f, "{}", if i == 0 { " " } else { ", " })?;
+                    match arg {
+                        Arg::Buffer(buffer) => write!(f, "buffer {}", buffer)?,
+                        Arg::Value(bytes) => write!(f, "value of {} bytes", bytes.len())?,
+                        Arg::Unknown => write!(f, "unknown buffer")?,
+                    }
+                }
+                if args.is_empty() {
+                    write!(f, " no arguments")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A timeline of API calls that were recorded
+///
+/// Printing a `Trace` prints each event on its own line, numbered in the order it happened.
+#[derive(Clone, Default)]
+pub struct Trace {
+    pub events: Vec<Event>,
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, event) in self.events.iter().enumerate() {
+            writeln!(f, "{}: {}", i, event)?;
+        }
+        Ok(())
+    }
+}
+
+impl Trace {
+    /// Saves the trace to a file that can be loaded back with [`from_file`](#method.from_file)
+    ///
+    /// This fails with `PersistError::Unsupported` if a recorded kernel has parameters that aren't buffers.
+    pub fn to_file<Q: AsRef<Path>>(&self, path: Q) -> Result<(), PersistError> {
+        let mut file = FILE_MAGIC.to_vec();
+        file.extend_from_slice(&(self.events.len() as u64).to_le_bytes());
+        for event in &self.events {
+            match event {
+                Event::Compile {
+                    kernel,
+                    name,
+                    params,
+                    code,
+                } => {
+                    file.push(0);
+                    file.extend_from_slice(&kernel.to_le_bytes());
+                    write_string(&mut file, name);
+                    params.write_to(&mut file)?;
+                    file.extend_from_slice(&(code.len() as u32).to_le_bytes());
+                    for word in code {
+                        file.extend_from_slice(&word.to_le_bytes());
+                    }
+                }
+                Event::Create {
+                    buffer,
+                    size,
+                    mutability,
+                    data,
+                } => {
+                    file.push(1);
+                    file.extend_from_slice(&buffer.to_le_bytes());
+                    file.extend_from_slice(&size.to_le_bytes());
+                    file.push(match mutability {
+                        Mutability::Const => 0,
+                        Mutability::Mut => 1,
+                    });
+                    match data {
+                        Some(data) => {
+                            file.push(1);
+                            write_bytes(&mut file, data);
+                        }
+                        None => file.push(0),
+                    }
+                }
+                Event::Set { buffer, data } => {
+                    file.push(2);
+                    file.extend_from_slice(&buffer.to_le_bytes());
+                    write_bytes(&mut file, data);
+                }
+                Event::Fill { buffer, pattern } => {
+                    file.push(3);
+                    file.extend_from_slice(&buffer.to_le_bytes());
+                    write_bytes(&mut file, pattern);
+                }
+                Event::Get { buffer } => {
+                    file.push(4);
+                    file.extend_from_slice(&buffer.to_le_bytes());
+                }
+                Event::Call {
+                    kernel,
+                    work_space_dim,
+                    args,
+                } => {
+                    file.push(5);
+                    file.extend_from_slice(&kernel.to_le_bytes());
+                    for dim in &[work_space_dim.0, work_space_dim.1, work_space_dim.2] {
+                        file.extend_from_slice(&dim.to_le_bytes());
+                    }
+                    file.extend_from_slice(&(args.len() as u32).to_le_bytes());
+                    for (set_num, binding_num, arg) in args {
+                        file.extend_from_slice(&set_num.to_le_bytes());
+                        file.extend_from_slice(&binding_num.to_le_bytes());
+                        match arg {
+                            Arg::Buffer(buffer) => {
+                                file.push(0);
+                                file.extend_from_slice(&buffer.to_le_bytes());
+                            }
+                            Arg::Value(bytes) => {
+                                file.push(1);
+                                write_bytes(&mut file, bytes);
+                            }
+                            Arg::Unknown => file.push(2),
+                        }
+                    }
+                }
+            }
+        }
+        std::fs::write(path, file).map_err(PersistError::Io)
+    }
+
+    /// Loads a trace from a file saved with [`to_file`](#method.to_file)
+    pub fn from_file<Q: AsRef<Path>>(path: Q) -> Result<Self, PersistError> {
+        let file = std::fs::read(path).map_err(PersistError::Io)?;
+
+        let mut rest = file
+            .strip_prefix(FILE_MAGIC)
+            .ok_or(PersistError::InvalidHeader)?;
+        let num_events = read_u64(&mut rest)?;
+        let mut events = vec![];
+        for _ in 0..num_events {
+            let event = match read_bytes(&mut rest, 1)?[0] {
+                0 => {
+                    let kernel = read_u64(&mut rest)?;
+                    let name = read_string(&mut rest)?;
+                    let params = DeviceFnMutParams::read_from(&mut rest)?;
+                    let len = read_u32(&mut rest)? as usize;
+                    let code = read_bytes(&mut rest, len * 4)?
+                        .chunks_exact(4)
+                        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+                        .collect();
+                    Event::Compile {
+                        kernel,
+                        name,
+                        params,
+                        code,
+                    }
+                }
+                1 => {
+                    let buffer = read_u64(&mut rest)?;
+                    let size = read_u64(&mut rest)?;
+                    let mutability = match read_bytes(&mut rest, 1)?[0] {
+                        0 => Mutability::Const,
+                        1 => Mutability::Mut,
+                        _ => return Err(PersistError::InvalidHeader),
+                    };
+                    let data = match read_bytes(&mut rest, 1)?[0] {
+                        0 => None,
+                        _ => Some(read_byte_vec(&mut rest)?),
+                    };
+                    Event::Create {
+                        buffer,
+                        size,
+                        mutability,
+                        data,
+                    }
+                }
+                2 => Event::Set {
+                    buffer: read_u64(&mut rest)?,
+                    data: read_byte_vec(&mut rest)?,
+                },
+                3 => Event::Fill {
+                    buffer: read_u64(&mut rest)?,
+                    pattern: read_byte_vec(&mut rest)?,
+                },
+                4 => Event::Get {
+                    buffer: read_u64(&mut rest)?,
+                },
+                5 => {
+                    let kernel = read_u64(&mut rest)?;
+                    let work_space_dim = (
+                        read_u32(&mut rest)?,
+                        read_u32(&mut rest)?,
+                        read_u32(&mut rest)?,
+                    );
+                    let mut args = vec![];
+                    for _ in 0..read_u32(&mut rest)? {
+                        let set_num = read_u32(&mut rest)?;
+                        let binding_num = read_u32(&mut rest)?;
+                        let arg = match read_bytes(&mut rest, 1)?[0] {
+                            0 => Arg::Buffer(read_u64(&mut rest)?),
+                            1 => Arg::Value(read_byte_vec(&mut rest)?),
+                            2 => Arg::Unknown,
+                            _ => return Err(PersistError::InvalidHeader),
+                        };
+                        args.push((set_num, binding_num, arg));
+                    }
+                    Event::Call {
+                        kernel,
+                        work_space_dim,
+                        args,
+                    }
+                }
+                _ => return Err(PersistError::InvalidHeader),
+            };
+            events.push(event);
+        }
+
+        Ok(Self { events })
+    }
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+    out.extend_from_slice(bytes);
+}
+
+fn read_byte_vec(bytes: &mut &[u8]) -> Result<Vec<u8>, PersistError> {
+    let len = read_u64(bytes)? as usize;
+    Ok(read_bytes(bytes, len)?.to_vec())
+}
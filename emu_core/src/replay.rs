@@ -0,0 +1,124 @@
+//! Replaying recorded API calls on a device
+//!
+//! This module requires the `record` feature. [`replay`](fn.replay.html) re-executes a [`Trace`](../record/struct.Trace.html) on a chosen device,
+//! so a trace recorded on one machine (and maybe attached to a bug report) can be re-run on another machine or on another device of the same machine.
+//! ```
+//! # use {emu_core::prelude::*, emu_core::record::*, emu_core::replay::*, emu_glsl::*, zerocopy::*};
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # futures::executor::block_on(assert_device_pool_initialized());
+//! # start_recording();
+//! # let mut data: DeviceBox<[f32]> = vec![1.0; 1024].as_device_boxed_mut()?;
+//! # futures::executor::block_on(data.get())?;
+//! # stop_recording().to_file(std::env::temp_dir().join("replayed.emutrace"))?;
+//! let trace = Trace::from_file(std::env::temp_dir().join("replayed.emutrace"))?;
+//! let mut device = &mut futures::executor::block_on(Device::all())[0];
+//! futures::executor::block_on(unsafe { replay(&trace, &mut device) })?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+
+use crate::device::*;
+use crate::error::*;
+use crate::record::*;
+
+/// Re-executes each event of the given trace, in order, on the given device
+///
+/// Buffers are re-created with the same size, mutability, and initial data as when they were recorded and kernels are re-compiled from the
+/// recorded SPIR-V. The types of buffers aren't recorded so kernels are replayed without checking the types of their arguments. Downloads
+/// are replayed and waited on but the downloaded data is thrown away. Note that a kernel is only recorded when it is compiled. So if a kernel was
+/// compiled (and cached) before recording started, launching it can't be replayed and this fails with `ReplayError::UnknownKernel`.
+///
+/// This is unsafe for the same reason [`Device::call`](../device/struct.Device.html#method.call) is unsafe.
+pub async unsafe fn replay(trace: &Trace, device: &mut Device) -> Result<(), ReplayError> {
+    let mut buffers: HashMap<u64, DeviceBox<[u8]>> = HashMap::new();
+    let mut kernels: HashMap<u64, DeviceFnMut> = HashMap::new();
+
+    for event in &trace.events {
+        match event {
+            Event::Compile {
+                kernel,
+                name,
+                params,
+                code,
+            } => {
+                let device_fn_mut = device
+                    .compile(
+                        params.clone().without_type_names(),
+                        name.as_str(),
+                        code.as_slice(),
+                    )
+                    .map_err(ReplayError::Compile)?;
+                kernels.insert(*kernel, device_fn_mut);
+            }
+            Event::Create {
+                buffer,
+                size,
+                mutability,
+                data,
+            } => {
+                let device_obj = match (data, mutability) {
+                    (Some(data), Mutability::Mut) => device.create_from_mut(data.as_slice()),
+                    (Some(data), Mutability::Const) => device.create_from(data.as_slice()),
+                    (None, Mutability::Mut) => device.create_with_size_mut(*size as usize),
+                    (None, Mutability::Const) => device.create_with_size(*size as usize),
+                };
+                buffers.insert(*buffer, device_obj);
+            }
+            Event::Set { buffer, data } => {
+                let device_obj = buffers
+                    .get_mut(buffer)
+                    .ok_or(ReplayError::UnknownBuffer(*buffer))?;
+                device
+                    .set_from(device_obj, data.as_slice())
+                    .map_err(ReplayError::Set)?;
+            }
+            Event::Fill { buffer, pattern } => {
+                let device_obj = buffers
+                    .get_mut(buffer)
+                    .ok_or(ReplayError::UnknownBuffer(*buffer))?;
+                device.fill_with_bytes(device_obj, pattern);
+            }
+            Event::Get { buffer } => {
+                let device_obj = buffers
+                    .get(buffer)
+                    .ok_or(ReplayError::UnknownBuffer(*buffer))?;
+                device
+                    .get_bytes(device_obj)
+                    .await
+                    .map_err(ReplayError::Get)?;
+            }
+            Event::Call {
+                kernel,
+                work_space_dim,
+                args,
+            } => {
+                let device_fn_mut = kernels
+                    .get(kernel)
+                    .ok_or(ReplayError::UnknownKernel(*kernel))?;
+                let mut replayed_args = DeviceFnMutArgs::from(HashMap::new());
+                for (set_num, binding_num, arg) in args {
+                    replayed_args = match arg {
+                        Arg::Buffer(buffer) => replayed_args.with_arg_at(
+                            *set_num,
+                            *binding_num,
+                            buffers
+                                .get(buffer)
+                                .ok_or(ReplayError::UnknownBuffer(*buffer))?,
+                        ),
+                        Arg::Value(bytes) => {
+                            replayed_args.with_value_at(*set_num, *binding_num, bytes.clone())
+                        }
+                        Arg::Unknown => return Err(ReplayError::UnrecordedArg),
+                    };
+                }
+                device
+                    .call(device_fn_mut, *work_space_dim, replayed_args)
+                    .map_err(ReplayError::Launch)?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,314 @@
+//! A graph of launches and transfers that is ordered by the `DeviceBox`s each one reads and writes
+//!
+//! Launching kernels one after another with [`launch`](../spawn/struct.Spawner.html#method.launch) submits each of them on its own and in the order
+//! they were launched. A [`Schedule`](struct.Schedule.html) instead records launches and transfers as nodes of a graph along with the `DeviceBox`s
+//! each node reads and writes. A node depends on every earlier node that writes something it reads or writes (or that reads something it writes).
+//! When the schedule is run, nodes are grouped into waves where every node only depends on nodes of earlier waves and each wave is submitted as a single
+//! submission. So independent work is batched together without having to sequence it by hand.
+
+use std::sync::Arc;
+
+use zerocopy::*;
+
+use crate::device::*;
+use crate::error::*;
+use crate::pool::*;
+use crate::spawn::*;
+
+/// The `DeviceBox`s that a launch reads and writes
+///
+/// A `DeviceBox` that a launch is passed but that isn't in either set isn't used to order the launch. So every `DeviceBox` passed to a launch should
+/// be in one of them.
+#[derive(Clone, Debug, Default)]
+pub struct Access {
+    reads: Vec<u64>,
+    writes: Vec<u64>,
+}
+
+impl Access {
+    /// Creates a set of accesses that reads and writes nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the given `DeviceBox` to the `DeviceBox`s that are read
+    pub fn reads<T: ?Sized>(mut self, device_obj: &DeviceBox<T>) -> Self {
+        self.reads.push(device_obj.id);
+        self
+    }
+
+    /// Adds the given `DeviceBox` to the `DeviceBox`s that are written (and maybe also read)
+    pub fn writes<T: ?Sized>(mut self, device_obj: &DeviceBox<T>) -> Self {
+        self.writes.push(device_obj.id);
+        self
+    }
+
+    // whether or not the node with these accesses has to run after a node with the given accesses
+    fn depends_on(&self, earlier: &Access) -> bool {
+        self.reads
+            .iter()
+            .chain(self.writes.iter())
+            .any(|id| earlier.writes.contains(id))
+            || self.writes.iter().any(|id| earlier.reads.contains(id))
+    }
+}
+
+// the work a node of a schedule does
+enum Work<'a> {
+    Launch {
+        device_fn_mut: Arc<DeviceFnMut>,
+        grid: Grid,
+        args: DeviceFnMutArgs<'a>,
+    },
+    Upload {
+        buffer: Arc<wgpu::Buffer>,
+        offset: u64,
+        bytes: Vec<u8>,
+        #[cfg(feature = "record")]
+        id: u64,
+    },
+    Copy {
+        src: Arc<wgpu::Buffer>,
+        src_offset: u64,
+        dst: Arc<wgpu::Buffer>,
+        dst_offset: u64,
+        size: u64,
+    },
+}
+
+struct Node<'a> {
+    work: Work<'a>,
+    access: Access,
+}
+
+/// A graph of launches and transfers on a single device that is run with as few submissions as the dependencies between them allow
+///
+/// A `Schedule` is made for the device [`take`](../pool/fn.take.html) would return and keeps using that device even if another one is selected later.
+/// Nodes are added in program order. Reordering never lets a node see a `DeviceBox` in a different state than it would if every node was run in
+/// that order.
+/// ```
+/// # use {emu_core::prelude::*, emu_core::schedule::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # futures::executor::block_on(assert_device_pool_initialized());
+/// let scale = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+///     GlslKernel::new()
+///         .param_mut::<[f32], _>("float[] data")
+///         .with_kernel_code("data[gl_GlobalInvocationID.x] = data[gl_GlobalInvocationID.x] * 2.0;"),
+/// )?
+/// .finish()?;
+/// let add = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+///     GlslKernel::new()
+///         .param_mut::<[f32], _>("float[] sum")
+///         .param::<[f32], _>("float[] other")
+///         .with_kernel_code("sum[gl_GlobalInvocationID.x] = sum[gl_GlobalInvocationID.x] + other[gl_GlobalInvocationID.x];"),
+/// )?
+/// .finish()?;
+///
+/// let a: DeviceBox<[f32]> = vec![0.0; 1024].as_device_boxed_mut()?;
+/// let b: DeviceBox<[f32]> = vec![0.0; 1024].as_device_boxed_mut()?;
+/// let mut schedule = Schedule::new()?;
+/// schedule.upload(&a, vec![1.0f32; 1024].as_slice());
+/// schedule.upload(&b, vec![3.0f32; 1024].as_slice());
+/// unsafe {
+///     // the 2 scales don't depend on each other so they are submitted together
+///     schedule.launch(&spawn(1024), call!(scale.clone(), &a), Access::new().writes(&a))?;
+///     schedule.launch(&spawn(1024), call!(scale.clone(), &b), Access::new().writes(&b))?;
+///     schedule.launch(&spawn(1024), call!(add.clone(), &a, &b), Access::new().writes(&a).reads(&b))?;
+/// }
+/// assert_eq!(schedule.waves(), vec![vec![0, 1], vec![2, 3], vec![4]]);
+/// unsafe { schedule.run()? };
+/// assert_eq!(futures::executor::block_on(a.get())?, vec![8.0; 1024].into_boxed_slice());
+/// # Ok(())
+/// # }
+/// ```
+pub struct Schedule<'a> {
+    device: &'static std::sync::Mutex<Device>,
+    nodes: Vec<Node<'a>>,
+}
+
+impl<'a> Schedule<'a> {
+    /// Creates an empty schedule for the currently selected device in the pool
+    pub fn new() -> Result<Self, NoDeviceError> {
+        Ok(Self {
+            device: take()?,
+            nodes: vec![],
+        })
+    }
+
+    /// The number of launches and transfers in the schedule
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns whether or not there are no launches or transfers in the schedule
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Adds a launch of the given `DeviceFnMut` with the given arguments on the space of threads of the given `Spawner`
+    ///
+    /// This returns the index of the launch among the nodes of the schedule. The launch is ordered after every earlier node whose accesses conflict with
+    /// the given accesses. `DeviceBox`s have to be passed to the launch by shared reference (like `&data`) since the schedule holds on to the arguments
+    /// until it is run and the accesses refer to them too. Whether or not the kernel can write to a `DeviceBox` is decided by how the `DeviceBox` was
+    /// created, just like it is for any other argument.
+    ///
+    /// This is unsafe for the same reason [`launch`](../spawn/struct.Spawner.html#method.launch) is unsafe.
+    pub unsafe fn launch(
+        &mut self,
+        spawner: &Spawner,
+        device_fn_mut_with_args: (Arc<DeviceFnMut>, DeviceFnMutArgs<'a>),
+        access: Access,
+    ) -> Result<usize, LaunchError> {
+        let (device_fn_mut, args) = device_fn_mut_with_args;
+        Ok(self.push(
+            Work::Launch {
+                device_fn_mut,
+                grid: spawner.grid()?,
+                args,
+            },
+            access,
+        ))
+    }
+
+    /// Adds an upload of the given data to the given `DeviceBox`, which is written by the upload
+    ///
+    /// This returns the index of the upload among the nodes of the schedule. The data is copied right away so it can be dropped before the schedule
+    /// is run.
+    pub fn upload<T, B: std::borrow::Borrow<T>>(
+        &mut self,
+        device_obj: &DeviceBox<T>,
+        host_obj: B,
+    ) -> usize
+    where
+        T: AsBytes + ?Sized,
+    {
+        if device_obj.mutability.is_some() {
+            assert_eq!(device_obj.mutability.unwrap(), Mutability::Mut, "expected the `DeviceBox` being uploaded to be mutable (each `DeviceBox` constructor has a \"constant\" version and a \"mut\" version)");
+        }
+        self.push(
+            Work::Upload {
+                buffer: device_obj.storage_buffer.clone(),
+                offset: device_obj.offset,
+                bytes: host_obj.borrow().as_bytes().to_vec(),
+                #[cfg(feature = "record")]
+                id: device_obj.id,
+            },
+            Access::new().writes(device_obj),
+        )
+    }
+
+    /// Adds a copy of all of the data in one `DeviceBox` to another `DeviceBox` of the same size, which reads the first and writes the second
+    ///
+    /// This returns the index of the copy among the nodes of the schedule.
+    pub fn copy<T: ?Sized>(&mut self, src: &DeviceBox<T>, dst: &DeviceBox<T>) -> usize {
+        assert_eq!(
+            src.size, dst.size,
+            "a `DeviceBox` can only be copied to a `DeviceBox` of the same size"
+        );
+        self.push(
+            Work::Copy {
+                src: src.storage_buffer.clone(),
+                src_offset: src.offset,
+                dst: dst.storage_buffer.clone(),
+                dst_offset: dst.offset,
+                size: src.size,
+            },
+            Access::new().reads(src).writes(dst),
+        )
+    }
+
+    // adds a node and returns its index
+    fn push(&mut self, work: Work<'a>, access: Access) -> usize {
+        self.nodes.push(Node { work, access });
+        self.nodes.len() - 1
+    }
+
+    /// Groups the indices of the nodes into the waves they are submitted in
+    ///
+    /// Each node is in the wave right after the last wave with a node it depends on. Nodes in the same wave are in the order they were added.
+    pub fn waves(&self) -> Vec<Vec<usize>> {
+        let mut wave_of_node: Vec<usize> = Vec::with_capacity(self.nodes.len());
+        for (i, node) in self.nodes.iter().enumerate() {
+            let wave = self.nodes[..i]
+                .iter()
+                .zip(wave_of_node.iter())
+                .filter(|(earlier, _)| node.access.depends_on(&earlier.access))
+                .map(|(_, wave)| wave + 1)
+                .max()
+                .unwrap_or(0);
+            wave_of_node.push(wave);
+        }
+
+        let mut waves = vec![vec![]; wave_of_node.iter().max().map_or(0, |wave| wave + 1)];
+        for (i, wave) in wave_of_node.into_iter().enumerate() {
+            waves[wave].push(i);
+        }
+        waves
+    }
+
+    /// Runs every node, submitting each wave (see [`waves`](#method.waves)) as a single submission
+    ///
+    /// This doesn't wait for the submitted work to complete. If a launch can't be recorded (like if its arguments don't match the kernel's parameters),
+    /// the error is returned and the waves before the launch's wave will have already been submitted.
+    ///
+    /// This is unsafe because it runs the launches added with [`launch`](#method.launch).
+    pub unsafe fn run(self) -> Result<(), LaunchError> {
+        let waves = self.waves();
+        let mut nodes = self.nodes.into_iter().map(Some).collect::<Vec<_>>();
+        let mut device = self.device.lock().unwrap();
+
+        for wave in waves {
+            let mut command_buffers = vec![];
+            let mut leases = vec![]; // held until the wave is submitted
+            for i in wave {
+                match nodes[i].take().unwrap().work {
+                    Work::Launch {
+                        device_fn_mut,
+                        grid,
+                        args,
+                    } => {
+                        let (command_buffer, lease) =
+                            device.encode_call(&device_fn_mut, grid, args)?;
+                        command_buffers.push(command_buffer);
+                        leases.push(lease);
+                    }
+                    // uploads are written to the queue, which copies them to their buffers right before the wave is submitted
+                    // every node they conflict with is in an earlier wave (already submitted) or a later one
+                    Work::Upload {
+                        buffer,
+                        offset,
+                        bytes,
+                        #[cfg(feature = "record")]
+                        id,
+                    } => {
+                        #[cfg(feature = "record")]
+                        crate::record::record(|| crate::record::Event::Set {
+                            buffer: id,
+                            data: bytes.clone(),
+                        });
+                        device.queue.write_buffer(&buffer, offset, &bytes);
+                    }
+                    Work::Copy {
+                        src,
+                        src_offset,
+                        dst,
+                        dst_offset,
+                        size,
+                    } => {
+                        let mut encoder =
+                            device
+                                .device
+                                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                                    label: None,
+                                });
+                        encoder.copy_buffer_to_buffer(&src, src_offset, &dst, dst_offset, size);
+                        command_buffers.push(encoder.finish());
+                    }
+                }
+            }
+            device.submit_all(command_buffers);
+            drop(leases);
+        }
+        Ok(())
+    }
+}
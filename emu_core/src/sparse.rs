@@ -0,0 +1,295 @@
+//! Sparse matrices stored on a device and kernels for working with them
+//!
+//! The main thing here is [`CsrMatrix`](struct.CsrMatrix.html), a matrix in [compressed sparse row](https://en.wikipedia.org/wiki/Sparse_matrix#Compressed_sparse_row_(CSR,_CRS_or_Yale_format))
+//! format. It can be multiplied by a dense vector with [`spmv`](struct.CsrMatrix.html#method.spmv), which is the building block of most iterative solvers
+//! (like conjugate gradient). This module requires the `glsl-compile` feature.
+
+use crate::boxed::*;
+use crate::cache::*;
+use crate::compile::*;
+use crate::compile_impls::*;
+use crate::device::*;
+use crate::error::*;
+use crate::spawn::*;
+
+// the number of threads that work on each row in the kernel for rows with many non-zeros
+const VECTOR_WIDTH: u32 = 32;
+// the number of rows in each thread block in the kernel for rows with few non-zeros
+const SCALAR_BLOCK_SIZE: u32 = 64;
+// if rows have at least this many non-zeros on average, we use a thread block per row
+const VECTOR_THRESHOLD: usize = 8;
+// the most thread blocks we can launch in a single dimension
+const MAX_BLOCKS_PER_DIM: u32 = 65535;
+
+/// A sparse matrix of `f32`s in compressed sparse row (CSR) format, stored on a device
+///
+/// A CSR matrix is made of 3 arrays.
+/// - `row_offsets` has `rows + 1` elements and the non-zeros of row `i` are at indices `row_offsets[i]..row_offsets[i + 1]` of the other arrays
+/// - `col_indices` has the column of each non-zero
+/// - `values` has the value of each non-zero
+///
+/// The easiest way to make one is from a list of `(row, column, value)` triplets.
+/// ```
+/// # use {emu_core::prelude::*, emu_core::sparse::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # futures::executor::block_on(assert_device_pool_initialized());
+/// // [2 0 1]
+/// // [0 3 0]
+/// let matrix = CsrMatrix::from_triplets(2, 3, &[(0, 0, 2.0), (1, 1, 3.0), (0, 2, 1.0)])?;
+/// let x: DeviceBox<[f32]> = vec![1.0, 2.0, 3.0].as_device_boxed()?;
+/// let mut y: DeviceBox<[f32]> = vec![0.0; 2].as_device_boxed_mut()?;
+/// matrix.spmv(&x, &mut y)?;
+/// assert_eq!(futures::executor::block_on(y.get())?, vec![5.0, 6.0].into_boxed_slice());
+/// # Ok(())
+/// # }
+/// ```
+pub struct CsrMatrix {
+    rows: usize,
+    cols: usize,
+    nnz: usize,
+    row_offsets: DeviceBox<[u32]>,
+    col_indices: DeviceBox<[u32]>, // inv: has at least 1 element so that it can be bound, even when nnz is 0
+    values: DeviceBox<[f32]>, // inv: has at least 1 element so that it can be bound, even when nnz is 0
+}
+
+impl CsrMatrix {
+    /// Creates a matrix with the given number of rows and columns from a list of `(row, column, value)` triplets
+    ///
+    /// Triplets can be in any order. If more than 1 triplet is at the same row and column, their values are added together.
+    /// This panics if a triplet is out of bounds.
+    pub fn from_triplets(
+        rows: usize,
+        cols: usize,
+        triplets: &[(usize, usize, f32)],
+    ) -> Result<Self, NoDeviceError> {
+        let mut triplets = triplets.to_vec();
+        for (row, col, _) in &triplets {
+            assert!(
+                *row < rows && *col < cols,
+                "triplet at ({}, {}) is out of bounds of a {}x{} matrix",
+                row,
+                col,
+                rows,
+                cols
+            );
+        }
+        triplets.sort_by_key(|(row, col, _)| (*row, *col));
+
+        // build up the CSR arrays, summing duplicates along the way
+        let mut row_offsets = vec![0u32; rows + 1];
+        let mut col_indices: Vec<u32> = vec![];
+        let mut values: Vec<f32> = vec![];
+        let mut last: Option<(usize, usize)> = None;
+        for (row, col, value) in triplets {
+            if last == Some((row, col)) {
+                *values.last_mut().unwrap() += value;
+            } else {
+                col_indices.push(col as u32);
+                values.push(value);
+                row_offsets[row + 1] += 1;
+                last = Some((row, col));
+            }
+        }
+        for i in 0..rows {
+            row_offsets[i + 1] += row_offsets[i];
+        }
+
+        Self::from_csr(rows, cols, &row_offsets, &col_indices, &values)
+    }
+
+    /// Creates a matrix with the given number of rows and columns from CSR arrays that are already built
+    ///
+    /// This panics if the arrays aren't consistent with each other and with the number of rows.
+    pub fn from_csr(
+        rows: usize,
+        cols: usize,
+        row_offsets: &[u32],
+        col_indices: &[u32],
+        values: &[f32],
+    ) -> Result<Self, NoDeviceError> {
+        assert_eq!(
+            row_offsets.len(),
+            rows + 1,
+            "a matrix with {} rows must have {} row offsets",
+            rows,
+            rows + 1
+        );
+        assert_eq!(
+            col_indices.len(),
+            values.len(),
+            "there must be a column index for each value"
+        );
+        assert_eq!(
+            row_offsets[rows] as usize,
+            values.len(),
+            "the last row offset must be the number of values"
+        );
+
+        let nnz = values.len();
+        // empty buffers can't be bound so we pad empty matrices with a non-zero that is never read
+        let (col_indices, values) = if nnz == 0 {
+            (&[0u32][..], &[0.0f32][..])
+        } else {
+            (col_indices, values)
+        };
+
+        Ok(Self {
+            rows,
+            cols,
+            nnz,
+            row_offsets: row_offsets.as_device_boxed()?,
+            col_indices: col_indices.as_device_boxed()?,
+            values: values.as_device_boxed()?,
+        })
+    }
+
+    /// The number of rows
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// The number of columns
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// The number of stored non-zeros
+    pub fn nnz(&self) -> usize {
+        self.nnz
+    }
+
+    /// The row offsets, which has `rows + 1` elements
+    pub fn row_offsets(&self) -> &DeviceBox<[u32]> {
+        &self.row_offsets
+    }
+
+    /// The column index of each non-zero
+    pub fn col_indices(&self) -> &DeviceBox<[u32]> {
+        &self.col_indices
+    }
+
+    /// The value of each non-zero
+    pub fn values(&self) -> &DeviceBox<[f32]> {
+        &self.values
+    }
+
+    /// Multiplies this matrix by the dense vector `x` and stores the result in `y` (`y = A * x`)
+    ///
+    /// `x` must have `cols` elements and `y` must have `rows` elements. This picks between 2 kernels based on how many non-zeros
+    /// each row has on average. If rows are short, each thread computes a whole row. If rows are long, a block of 32 threads computes
+    /// each row together and reduces their partial sums in shared memory.
+    pub fn spmv(&self, x: &DeviceBox<[f32]>, y: &mut DeviceBox<[f32]>) -> Result<(), KernelError> {
+        assert_eq!(
+            x.size as usize / std::mem::size_of::<f32>(),
+            self.cols,
+            "`x` must have as many elements as the matrix has columns"
+        );
+        assert_eq!(
+            y.size as usize / std::mem::size_of::<f32>(),
+            self.rows,
+            "`y` must have as many elements as the matrix has rows"
+        );
+        if self.rows == 0 {
+            return Ok(());
+        }
+
+        let rows = DeviceBox::new(self.rows as u32)?;
+        if self.nnz >= VECTOR_THRESHOLD * self.rows {
+            let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+                csr_params(GlslKernel::new())
+                    .spawn(VECTOR_WIDTH)
+                    .share(format!("float partial_sums[{}]", VECTOR_WIDTH))
+                    .with_kernel_code(format!(
+                        r#"
+uint row = gl_WorkGroupID.x + gl_WorkGroupID.y * gl_NumWorkGroups.x;
+uint lane = gl_LocalInvocationID.x;
+float sum = 0.0;
+if (row < rows) {{
+    for (uint i = row_offsets[row] + lane; i < row_offsets[row + 1]; i += {width}) {{
+        sum += values[i] * x[col_indices[i]];
+    }}
+}}
+partial_sums[lane] = sum;
+memoryBarrierShared();
+barrier();
+for (uint stride = {width} / 2; stride > 0; stride /= 2) {{
+    if (lane < stride) {{
+        partial_sums[lane] += partial_sums[lane + stride];
+    }}
+    memoryBarrierShared();
+    barrier();
+}}
+if (row < rows && lane == 0) {{
+    y[row] = partial_sums[0];
+}}
+"#,
+                        width = VECTOR_WIDTH
+                    )),
+            )?
+            .finish()?;
+
+            // we launch a thread block for each row
+            // there's a limit on how many thread blocks there can be in each dimension so we spread them over 2 dimensions
+            let num_rows = self.rows as u32;
+            let blocks_x = num_rows.min(MAX_BLOCKS_PER_DIM);
+            let blocks_y = (num_rows + blocks_x - 1) / blocks_x;
+            unsafe {
+                spawn(blocks_x).spawn(blocks_y).launch(crate::call!(
+                    kernel,
+                    &self.row_offsets,
+                    &self.col_indices,
+                    &self.values,
+                    x,
+                    y,
+                    &rows
+                ))?;
+            }
+        } else {
+            let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+                csr_params(GlslKernel::new())
+                    .spawn(SCALAR_BLOCK_SIZE)
+                    .with_kernel_code(
+                        r#"
+uint row = gl_GlobalInvocationID.x;
+if (row < rows) {
+    float sum = 0.0;
+    for (uint i = row_offsets[row]; i < row_offsets[row + 1]; i++) {
+        sum += values[i] * x[col_indices[i]];
+    }
+    y[row] = sum;
+}
+"#,
+                    ),
+            )?
+            .finish()?;
+
+            let num_rows = self.rows as u32;
+            unsafe {
+                spawn((num_rows + SCALAR_BLOCK_SIZE - 1) / SCALAR_BLOCK_SIZE).launch(
+                    crate::call!(
+                        kernel,
+                        &self.row_offsets,
+                        &self.col_indices,
+                        &self.values,
+                        x,
+                        y,
+                        &rows
+                    ),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// declares the parameters shared by the kernels for SpMV
+fn csr_params(kernel: GlslKernel) -> GlslKernel {
+    kernel
+        .param::<[u32], _>("uint[] row_offsets")
+        .param::<[u32], _>("uint[] col_indices")
+        .param::<[f32], _>("float[] values")
+        .param::<[f32], _>("float[] x")
+        .param_mut::<[f32], _>("float[] y")
+        .param::<u32, _>("uint rows")
+}
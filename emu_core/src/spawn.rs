@@ -5,6 +5,9 @@ use crate::error::*;
 use crate::pool::*;
 
 use std::sync::Arc;
+use std::time::Duration;
+
+pub use crate::device::Grid;
 
 /// Constructs a [`Spawner`](struct.Spawner.html) with the given number of threads spawned
 ///
@@ -51,6 +54,7 @@ use std::sync::Arc;
 pub fn spawn(num_threads: u32) -> Spawner {
     Spawner {
         work_space_dim: vec![num_threads],
+        grid: None,
     }
 }
 
@@ -59,16 +63,30 @@ pub fn spawn(num_threads: u32) -> Spawner {
 /// See [`spawn`](fn.spawn.html) for more details.
 pub struct Spawner {
     work_space_dim: Vec<u32>,
+    grid: Option<Grid>,
+}
+
+impl From<Grid> for Spawner {
+    fn from(grid: Grid) -> Self {
+        let work_space_dim = grid.work_space_dim();
+        Self {
+            work_space_dim: vec![work_space_dim.0, work_space_dim.1, work_space_dim.2],
+            grid: Some(grid),
+        }
+    }
 }
 
 impl Spawner {
     /// Adds a new dimension to the space of threads with size determined by the given number of threads
+    ///
+    /// If this `Spawner` was made from a [`Grid`](../device/struct.Grid.html), the threads are no longer described by the grid.
     pub fn spawn(mut self, num_threads: u32) -> Self {
         self.work_space_dim.push(num_threads);
+        self.grid = None;
         self
     }
 
-    fn get_work_space_dim(&self) -> Result<(u32, u32, u32), LaunchError> {
+    pub(crate) fn get_work_space_dim(&self) -> Result<(u32, u32, u32), LaunchError> {
         match self.work_space_dim.len() {
             0 => Ok((0, 0, 0)),
             1 => Ok((self.work_space_dim[0], 1, 1)),
@@ -82,28 +100,224 @@ impl Spawner {
         }
     }
 
+    // the grid this was made from or the thread blocks spawned so far
+    pub(crate) fn grid(&self) -> Result<Grid, LaunchError> {
+        match self.grid {
+            Some(grid) => Ok(grid),
+            None => Ok(Grid::from(self.get_work_space_dim()?)),
+        }
+    }
+
     /// Launches given `DeviceFnMut` with given arguments on the space of threads built so far
     ///
     /// You can provide the arguments using [`ArgsBuilder`](../device/struct.ArgsBuilder.html) or using the `call` macro.
+    /// If the `DeviceFnMut` declares its grid size (like with [`GlslKernel::with_grid_size`](../compile_impls/struct.GlslKernel.html#method.with_grid_size)),
+    /// the global size of the [`Grid`](../device/struct.Grid.html) this `Spawner` was made from is passed in for it. If this `Spawner` wasn't made from a
+    /// `Grid`, the number of threads spawned is passed in instead.
+    ///
+    /// Before anything is submitted, the launch is checked against the limits of the device (like the number of thread blocks in each dimension,
+    /// the number of threads in each thread block, and the number of buffers). If a limit is exceeded, `LaunchError::LimitExceeded` says which one.
+    /// ```
+    /// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # futures::executor::block_on(assert_device_pool_initialized());
+    /// let data: DeviceBox<[f32]> = vec![0.0; 1 << 16].as_device_boxed_mut()?;
+    /// let is_limit_exceeded = |result: Result<(), LaunchError>, expected: &str| match result {
+    ///     Err(LaunchError::LimitExceeded { limit, .. }) => limit == expected,
+    ///     _ => false,
+    /// };
+    ///
+    /// // too many thread blocks in the "x" dimension
+    /// let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+    ///     GlslKernel::new()
+    ///         .param_mut::<[f32], _>("float[] data")
+    ///         .with_kernel_code("data[gl_GlobalInvocationID.x] = 1.0;"),
+    /// )?
+    /// .finish()?;
+    /// let result = unsafe { spawn(1 << 16).launch(call!(kernel, &data)) };
+    /// assert!(is_limit_exceeded(result, "thread blocks in a dimension"));
+    ///
+    /// // too many threads in each thread block
+    /// let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+    ///     GlslKernel::new()
+    ///         .spawn(1024)
+    ///         .param_mut::<[f32], _>("float[] data")
+    ///         .with_kernel_code("data[gl_GlobalInvocationID.x] = 1.0;"),
+    /// )?
+    /// .finish()?;
+    /// let result = unsafe { spawn(1).launch(call!(kernel, &data)) };
+    /// assert!(is_limit_exceeded(result, "threads in a dimension of a thread block"));
+    ///
+    /// // too many buffers
+    /// let max_buffers = take()?.lock().unwrap().device.limits().max_storage_buffers_per_shader_stage;
+    /// let input: DeviceBox<[f32]> = vec![1.0; 64].as_device_boxed()?;
+    /// let mut kernel = GlslKernel::new().param_mut::<[f32], _>("float[] data");
+    /// let mut args = ArgsBuilder::new().arg(&data);
+    /// for i in 0..max_buffers {
+    ///     kernel = kernel.param::<[f32], _>(format!("float[] input_{}", i));
+    ///     args = args.arg(&input);
+    /// }
+    /// let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+    ///     kernel.with_kernel_code("data[gl_GlobalInvocationID.x] = input_0[gl_GlobalInvocationID.x];"),
+    /// )?
+    /// .finish()?;
+    /// let result = unsafe { spawn(64).launch((kernel, args.build())) };
+    /// assert!(is_limit_exceeded(result, "storage buffers"));
+    /// # Ok(())
+    /// # }
+    /// ```
     pub unsafe fn launch<'a>(
         &self,
         device_fn_mut_with_args: (Arc<DeviceFnMut>, DeviceFnMutArgs<'a>),
     ) -> Result<(), LaunchError> {
-        take()
-            .map_err(|_| LaunchError::NoDevice)?
-            .lock()
-            .unwrap()
-            .call(
-                &device_fn_mut_with_args.0,
-                self.get_work_space_dim()?,
-                device_fn_mut_with_args.1,
-            )
+        let (device_fn_mut, args) = device_fn_mut_with_args;
+        let mut device = take().map_err(|_| LaunchError::NoDevice)?.lock().unwrap();
+        device.call(&device_fn_mut, self.grid()?, args)
+    }
+
+    /// Launches given `DeviceFnMut` like [`launch`](#method.launch) but then waits for it to complete, giving up after the given timeout
+    ///
+    /// If the launched kernel doesn't complete in time, `LaunchError::Timeout` is returned.
+    pub unsafe fn launch_with_timeout<'a>(
+        &self,
+        device_fn_mut_with_args: (Arc<DeviceFnMut>, DeviceFnMutArgs<'a>),
+        timeout: Duration,
+    ) -> Result<(), LaunchError> {
+        let (device_fn_mut, args) = device_fn_mut_with_args;
+        let mut device = take().map_err(|_| LaunchError::NoDevice)?.lock().unwrap();
+        device.call_with_timeout(&device_fn_mut, self.grid()?, args, timeout)
+    }
+}
+
+impl<'d, 'env> Scope<'d, 'env> {
+    /// Launches given `DeviceFnMut` on the space of threads of the given `Spawner` like [`Spawner::launch`](struct.Spawner.html#method.launch)
+    /// but on the scope's device and with arguments that must outlive the scope
+    ///
+    /// The launch is submitted right away and the scope doesn't end until it completes. This is unsafe for the same reason `Spawner::launch`
+    /// is unsafe.
+    pub unsafe fn launch(
+        &mut self,
+        spawner: &Spawner,
+        device_fn_mut_with_args: (Arc<DeviceFnMut>, DeviceFnMutArgs<'env>),
+    ) -> Result<(), LaunchError> {
+        let (device_fn_mut, args) = device_fn_mut_with_args;
+        self.device.call(&device_fn_mut, spawner.grid()?, args)
+    }
+}
+
+/// A compiled kernel bound to a space of threads and a fixed set of arguments
+///
+/// Kernels that are launched over and over with (mostly) the same arguments can be bound once and then launched with [`run`](#method.run)
+/// instead of rebuilding the arguments at every call site. [`run_with`](#method.run_with) launches with some of the arguments replaced, which
+/// is useful for arguments that change on every launch (like a time step).
+/// ```
+/// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # futures::executor::block_on(assert_device_pool_initialized());
+/// let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+///     GlslKernel::new()
+///         .param_mut::<[f32], _>("float[] data")
+///         .param::<f32, _>("float step")
+///         .with_kernel_code("data[gl_GlobalInvocationID.x] += step;"),
+/// )?
+/// .finish()?;
+///
+/// let data: DeviceBox<[f32]> = vec![0.0; 1024].as_device_boxed_mut()?;
+/// let advance = BoundKernel::new(kernel, spawn(1024)).arg(&data).arg(1.0f32);
+/// unsafe {
+///     advance.run()?;
+///     advance.run()?;
+///     advance.run_with(Overrides::new().arg(1, 0.5f32))?;
+/// }
+/// assert_eq!(futures::executor::block_on(data.get())?, vec![2.5; 1024].into_boxed_slice());
+/// # Ok(())
+/// # }
+/// ```
+pub struct BoundKernel<'a> {
+    device_fn_mut: Arc<DeviceFnMut>,
+    spawner: Spawner,
+    args: ArgsBuilder<'a>,
+}
+
+impl<'a> BoundKernel<'a> {
+    /// Binds the given `DeviceFnMut` to the given space of threads with no arguments yet
+    pub fn new(device_fn_mut: Arc<DeviceFnMut>, spawner: Spawner) -> Self {
+        Self {
+            device_fn_mut,
+            spawner,
+            args: ArgsBuilder::new(),
+        }
+    }
+
+    /// Binds the next argument (see [`IntoArg`](../device/trait.IntoArg.html) for what can be passed)
+    pub fn arg<A: IntoArg<'a>>(mut self, arg: A) -> Self {
+        self.args = self.args.arg(arg);
+        self
+    }
+
+    /// Launches the kernel with the bound arguments
+    ///
+    /// This is unsafe for the same reason [`launch`](struct.Spawner.html#method.launch) is unsafe.
+    pub unsafe fn run(&self) -> Result<(), LaunchError> {
+        self.spawner
+            .launch((self.device_fn_mut.clone(), self.args.clone().build()))
+    }
+
+    /// Launches the kernel with the bound arguments except that the arguments in the given `Overrides` take their place
+    ///
+    /// This is unsafe for the same reason [`launch`](struct.Spawner.html#method.launch) is unsafe.
+    pub unsafe fn run_with<'b>(&self, overrides: Overrides<'b>) -> Result<(), LaunchError>
+    where
+        'a: 'b,
+    {
+        let Overrides {
+            mut args,
+            positions,
+        } = overrides;
+        let mut bound_args: ArgsBuilder<'b> = self.args.clone();
+        for (i, position) in positions.into_iter().enumerate() {
+            assert!(
+                (position as usize) < bound_args.num_args(),
+                "expected the overridden argument at position {} to be one of the {} bound arguments",
+                position,
+                bound_args.num_args()
+            );
+            bound_args.replace_arg(position, &mut args, i as u32);
+        }
+        self.spawner
+            .launch((self.device_fn_mut.clone(), bound_args.build()))
+    }
+}
+
+/// Arguments that take the place of some of the arguments of a [`BoundKernel`](struct.BoundKernel.html) for a single launch
+///
+/// See [`BoundKernel::run_with`](struct.BoundKernel.html#method.run_with).
+pub struct Overrides<'a> {
+    args: ArgsBuilder<'a>,
+    positions: Vec<u32>, // the position of each argument in args among the bound arguments
+}
+
+impl<'a> Overrides<'a> {
+    /// Starts with no overridden arguments
+    pub fn new() -> Self {
+        Self {
+            args: ArgsBuilder::new(),
+            positions: vec![],
+        }
+    }
+
+    /// Overrides the argument at the given position (counting from 0) with the given argument
+    pub fn arg<A: IntoArg<'a>>(mut self, position: u32, arg: A) -> Self {
+        self.args = self.args.arg(arg);
+        self.positions.push(position);
+        self
     }
 }
 
 /// A macro which evaluates to something that can be passed into [`launch`](spawn/struct.Spawner.html#method.launch)
 ///
-/// For example usage, see [`spawn`](spawn/fn.spawn.html)
+/// Arguments can be references to `DeviceBox`s or small values like `3.0f32` and `(width, height)`. See [`IntoArg`](device/trait.IntoArg.html) for
+/// everything that can be passed. For example usage, see [`spawn`](spawn/fn.spawn.html)
 #[macro_export]
 macro_rules! call {
 	($fn_mut:expr $( ,$fn_mut_arg:expr )*) => (
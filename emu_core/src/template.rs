@@ -0,0 +1,221 @@
+//! Kernels that are written once and compiled for each type of element they are used with
+//!
+//! A library of algorithms built on Emu usually wants the same kernel for `f32`s, `i32`s, and `u32`s. Instead of writing out a `GlslKernel`
+//! for each of them, a [`KernelTemplate`](struct.KernelTemplate.html) is written with `$T` wherever the type of element goes. Each
+//! [`instantiate`](struct.KernelTemplate.html#method.instantiate) replaces `$T` with the GLSL name of a [`GlslScalar`](../compile/trait.GlslScalar.html)
+//! and compiles the result. This module requires the `glsl-compile` feature.
+
+use std::sync::Arc;
+
+use crate::cache::*;
+use crate::compile::*;
+use crate::compile_impls::*;
+use crate::device::*;
+use crate::error::*;
+
+// a parameter of a template, which is declared once `$T` is known
+#[derive(Clone)]
+enum TemplateParam {
+    // a parameter whose declared type is `$T` or `$T[]`
+    Generic {
+        declaration: String,
+        mutability: Mutability,
+    },
+    // a parameter whose Rust type doesn't depend on `$T`, along with the method of GlslKernel that declares it
+    Fixed {
+        declaration: String,
+        declare: fn(GlslKernel, String) -> GlslKernel,
+    },
+}
+
+/// A [`GlslKernel`](../compile_impls/struct.GlslKernel.html) with `$T` in place of the type of element it works on
+///
+/// `$T` can be used in the declarations of parameters, constants, and shared variables and in the helper and body code. Literals are best written
+/// with a constructor (like `$T(2)`) so that they make sense for every type.
+/// ```
+/// # use {emu_core::prelude::*, emu_core::template::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # futures::executor::block_on(assert_device_pool_initialized());
+/// let axpy = KernelTemplate::new()
+///     .param_t_mut("$T[] y")
+///     .param_t("$T[] x")
+///     .param_t("$T a")
+///     .with_kernel_code("y[gl_GlobalInvocationID.x] += a * x[gl_GlobalInvocationID.x] + $T(1);");
+///
+/// let mut y: DeviceBox<[f32]> = vec![1.0; 1024].as_device_boxed_mut()?;
+/// let x: DeviceBox<[f32]> = vec![2.0; 1024].as_device_boxed()?;
+/// let mut counts: DeviceBox<[i32]> = vec![1; 1024].as_device_boxed_mut()?;
+/// let steps: DeviceBox<[i32]> = vec![2; 1024].as_device_boxed()?;
+/// unsafe {
+///     spawn(1024).launch(call!(axpy.instantiate::<f32>()?, &mut y, &x, 0.5f32))?;
+///     spawn(1024).launch(call!(axpy.instantiate::<i32>()?, &mut counts, &steps, 3i32))?;
+/// }
+/// assert_eq!(futures::executor::block_on(y.get())?, vec![3.0; 1024].into_boxed_slice());
+/// assert_eq!(futures::executor::block_on(counts.get())?, vec![8; 1024].into_boxed_slice());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct KernelTemplate {
+    local_size: Vec<u32>,
+    params: Vec<TemplateParam>,
+    consts: Vec<(String, String)>,
+    shared: Vec<String>,
+    helper_code: String,
+    kernel_code: String,
+}
+
+impl KernelTemplate {
+    /// Initializes the template
+    pub fn new() -> Self {
+        Self {
+            local_size: vec![],
+            params: vec![],
+            consts: vec![],
+            shared: vec![],
+            helper_code: String::new(),
+            kernel_code: String::new(),
+        }
+    }
+
+    /// Spawns threads within each thread block (see [`GlslKernel::spawn`](../compile_impls/struct.GlslKernel.html#method.spawn))
+    pub fn spawn(mut self, num_threads: u32) -> Self {
+        self.local_size.push(num_threads);
+        self
+    }
+
+    /// Declares a parameter through which constant data of type `$T` (like `"$T scale"`) or `[$T]` (like `"$T[] data"`) is passed in
+    pub fn param_t(self, param: impl Into<String>) -> Self {
+        self.param_generic(param.into(), Mutability::Const)
+    }
+
+    /// Declares a parameter through which mutable data of type `$T` or `[$T]` is passed in (see [`param_t`](#method.param_t))
+    pub fn param_t_mut(self, param: impl Into<String>) -> Self {
+        self.param_generic(param.into(), Mutability::Mut)
+    }
+
+    fn param_generic(mut self, declaration: String, mutability: Mutability) -> Self {
+        assert!(
+            declaration.trim_start().starts_with("$T ")
+                || declaration.trim_start().starts_with("$T[]"),
+            "expected the type of a parameter declared with `param_t` to be `$T` or `$T[]` but it was declared as `{}`",
+            declaration
+        );
+        self.params.push(TemplateParam::Generic {
+            declaration,
+            mutability,
+        });
+        self
+    }
+
+    /// Declares a parameter through which constant data of a type that doesn't depend on `$T` is passed in
+    ///
+    /// This is just like [`GlslKernel::param`](../compile_impls/struct.GlslKernel.html#method.param) (like `param::<[u32], _>("uint[] indices")`).
+    pub fn param<P: ?Sized, I: Into<String>>(mut self, param: I) -> Self {
+        self.params.push(TemplateParam::Fixed {
+            declaration: param.into(),
+            declare: GlslKernel::param::<P, String>,
+        });
+        self
+    }
+
+    /// Declares a parameter through which mutable data of a type that doesn't depend on `$T` is passed in
+    pub fn param_mut<P: ?Sized, I: Into<String>>(mut self, param: I) -> Self {
+        self.params.push(TemplateParam::Fixed {
+            declaration: param.into(),
+            declare: GlslKernel::param_mut::<P, String>,
+        });
+        self
+    }
+
+    /// Appends a constant definition (see [`GlslKernel::with_const`](../compile_impls/struct.GlslKernel.html#method.with_const))
+    pub fn with_const(
+        mut self,
+        left_hand: impl Into<String>,
+        right_hand: impl Into<String>,
+    ) -> Self {
+        self.consts.push((left_hand.into(), right_hand.into()));
+        self
+    }
+
+    /// Creates a shared variable (see [`GlslKernel::share`](../compile_impls/struct.GlslKernel.html#method.share))
+    pub fn share(mut self, shared: impl Into<String>) -> Self {
+        self.shared.push(shared.into());
+        self
+    }
+
+    /// Adds code for helper functions that are defined before the body code
+    pub fn with_helper_code(mut self, code: impl Into<String>) -> Self {
+        self.helper_code = code.into();
+        self
+    }
+
+    /// Adds the body code for the kernel
+    pub fn with_kernel_code(mut self, code: impl Into<String>) -> Self {
+        self.kernel_code = code.into();
+        self
+    }
+
+    /// Generates the `GlslKernel` with every `$T` replaced with the GLSL name of `T`
+    ///
+    /// This is useful for compiling with a cache other than the global one or for adding things templates don't support.
+    pub fn generate<T: GlslScalar>(&self) -> GlslKernel {
+        let substitute = |code: &str| code.replace("$T", T::glsl_type());
+        let mut kernel = GlslKernel::new();
+        if T::needs_f64() {
+            kernel = kernel.with_f64();
+        }
+        for num_threads in &self.local_size {
+            kernel = kernel.spawn(*num_threads);
+        }
+        for param in &self.params {
+            kernel = match param {
+                TemplateParam::Generic {
+                    declaration,
+                    mutability,
+                } => {
+                    let is_array = declaration.trim_start().starts_with("$T[]");
+                    match (is_array, mutability) {
+                        (true, Mutability::Mut) => {
+                            kernel.param_mut::<[T], _>(substitute(declaration))
+                        }
+                        (true, Mutability::Const) => {
+                            kernel.param::<[T], _>(substitute(declaration))
+                        }
+                        (false, Mutability::Mut) => {
+                            kernel.param_mut::<T, _>(substitute(declaration))
+                        }
+                        (false, Mutability::Const) => kernel.param::<T, _>(substitute(declaration)),
+                    }
+                }
+                TemplateParam::Fixed {
+                    declaration,
+                    declare,
+                } => declare(kernel, substitute(declaration)),
+            };
+        }
+        for (left_hand, right_hand) in &self.consts {
+            kernel = kernel.with_const(substitute(left_hand), substitute(right_hand));
+        }
+        for shared in &self.shared {
+            kernel = kernel.share(substitute(shared));
+        }
+        kernel
+            .with_helper_code(substitute(&self.helper_code))
+            .with_kernel_code(substitute(&self.kernel_code))
+    }
+
+    /// Generates the kernel for `T` (see [`generate`](#method.generate)) and compiles it for the device currently selected from the pool
+    ///
+    /// Instantiations are cached in the [`GlobalCache`](../cache/struct.GlobalCache.html), so instantiating the same template with the same type
+    /// again is just a cache lookup. If `T` is `f64` and the device doesn't support 64-bit floats, `CompileOrNoDeviceError::UnsupportedF64` is returned.
+    pub fn instantiate<T: GlslScalar>(&self) -> Result<Arc<DeviceFnMut>, CompileOrNoDeviceError> {
+        compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(self.generate::<T>())?.finish()
+    }
+}
+
+impl Default for KernelTemplate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
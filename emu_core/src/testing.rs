@@ -0,0 +1,299 @@
+//! Tools for testing kernels, including in CI environments without a GPU
+//!
+//! Most CI machines don't have a physical GPU. They can still run kernels with a software implementation of Vulkan like
+//! [lavapipe](https://docs.mesa3d.org/drivers/lavapipe.html) or [SwiftShader](https://github.com/google/swiftshader), or with OpenGL through Mesa's llvmpipe.
+//! [`assert_test_device_pool_initialized`](fn.assert_test_device_pool_initialized.html) initializes the pool of devices with such a software device when there is one,
+//! and [`run`](fn.run.html) uses it to run a test. You will usually use these through the `#[emu_test]` attribute from `emu_glsl`.
+//! ```ignore
+//! use {emu_core::prelude::*, emu_glsl::*};
+//!
+//! #[emu_test]
+//! fn doubles_data() -> Result<(), Box<dyn std::error::Error>> {
+//!     let mut data: DeviceBox<[f32]> = vec![1.0; 64].as_device_boxed_mut()?;
+//!     // ...compile and launch a kernel on `data`...
+//!     Ok(())
+//! }
+//! ```
+//!
+//! There are also helpers for checking a kernel against a reference implementation in Rust. [`assert_kernel_matches`](fn.assert_kernel_matches.html)
+//! runs a kernel on some input and compares what it produces to what a closure produces for the same input. Floats are compared with a tolerance
+//! (see [`ApproxEq`](trait.ApproxEq.html)) and input can be generated with [`generate_input`](fn.generate_input.html).
+
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use zerocopy::*;
+
+use crate::boxed::*;
+use crate::device::*;
+use crate::pool::*;
+use crate::spawn::*;
+
+lazy_static! {
+    // whether or not the pool of devices for tests has been initialized
+    // tests run on many threads so this makes sure devices are only detected once
+    // this is held while devices are detected, which is awaited, so it's an async mutex
+    static ref TEST_DEVICE_POOL_INITIALIZED: futures::lock::Mutex<bool> = futures::lock::Mutex::new(false);
+}
+
+/// Returns whether the given device is a software implementation running on the CPU
+///
+/// This is true for lavapipe, SwiftShader, llvmpipe, and WARP as well as anything else that reports itself as a CPU device.
+pub fn is_software(info: &DeviceInfo) -> bool {
+    let name = info.name().to_ascii_lowercase();
+    info.device_type() == DeviceType::Cpu
+        || [
+            "llvmpipe",
+            "lavapipe",
+            "swiftshader",
+            "microsoft basic render driver",
+        ]
+        .iter()
+        .any(|software_name| name.contains(software_name))
+}
+
+/// Gets all detected software devices
+///
+/// This first looks for software devices with the primary backends (Vulkan, Metal, DX12, WebGPU). If there aren't any, it falls back to
+/// the secondary backends (OpenGL, DX11).
+pub async fn software_devices() -> Vec<Device> {
+    for backends in &[wgpu::BackendBit::PRIMARY, wgpu::BackendBit::SECONDARY] {
+        let devices = Device::all_with_backends(*backends)
+            .await
+            .into_iter()
+            .filter(|device| device.info.as_ref().map(is_software).unwrap_or(false))
+            .collect::<Vec<Device>>();
+        if devices.len() > 0 {
+            return devices;
+        }
+    }
+    vec![]
+}
+
+/// Asserts that the device pool has been initialized for running tests
+///
+/// This is just like [`assert_device_pool_initialized`](../pool/fn.assert_device_pool_initialized.html) except that, if a software device can be found
+/// with [`software_devices`](fn.software_devices.html), the pool only has software devices. This way, tests behave the same on a developer's machine as they do
+/// in CI. If there are no software devices, this falls back to the devices `assert_device_pool_initialized` would use. If a custom pool has already been set
+/// with [`pool`](../pool/fn.pool.html), that pool is used.
+pub async fn assert_test_device_pool_initialized() {
+    let mut initialized = TEST_DEVICE_POOL_INITIALIZED.lock().await;
+    if !*initialized {
+        let devices = software_devices().await;
+        if devices.len() > 0 {
+            // it's fine if this fails since that means a custom pool was already set
+            let _ = pool(
+                devices
+                    .into_iter()
+                    .map(|device| {
+                        let info = device.info.clone();
+                        DevicePoolMember {
+                            device: Mutex::new(device),
+                            device_info: info,
+                        }
+                    })
+                    .collect(),
+            );
+        }
+        assert_device_pool_initialized().await;
+        *initialized = true;
+    }
+}
+
+/// Runs the given test with the pool of devices for tests
+///
+/// This initializes the pool with [`assert_test_device_pool_initialized`](fn.assert_test_device_pool_initialized.html) and panics with a helpful message
+/// if no device could be found. The `#[emu_test]` attribute from `emu_glsl` wraps the body of a test with this.
+/// ```
+/// # use {emu_core::prelude::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// emu_core::testing::run(|| -> Result<(), Box<dyn std::error::Error>> {
+///     let data: DeviceBox<[f32]> = vec![1.0; 64].as_device_boxed()?;
+///     assert_eq!(futures::executor::block_on(data.get())?, vec![1.0; 64].into_boxed_slice());
+///     Ok(())
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn run<R>(test: impl FnOnce() -> R) -> R {
+    futures::executor::block_on(assert_test_device_pool_initialized());
+    assert!(
+        take().is_ok(),
+        "no device could be found for running tests; in CI, install a software adapter like lavapipe or SwiftShader"
+    );
+    test()
+}
+
+/// A trait for values that can be compared with a tolerance
+///
+/// Floats are equal if they are within the tolerance of each other either absolutely or relative to the larger of the two. Integers must be exactly equal.
+pub trait ApproxEq {
+    /// Returns whether `self` and `other` are equal with the given tolerance
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool;
+}
+
+macro_rules! impl_approx_eq_float {
+    ($($float:ty),*) => {
+        $(
+            impl ApproxEq for $float {
+                fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+                    let (a, b) = (*self as f64, *other as f64);
+                    if a == b || (a.is_nan() && b.is_nan()) {
+                        return true;
+                    }
+                    let difference = (a - b).abs();
+                    difference <= tolerance || difference <= tolerance * a.abs().max(b.abs())
+                }
+            }
+        )*
+    }
+}
+
+macro_rules! impl_approx_eq_int {
+    ($($int:ty),*) => {
+        $(
+            impl ApproxEq for $int {
+                fn approx_eq(&self, other: &Self, _tolerance: f64) -> bool {
+                    self == other
+                }
+            }
+        )*
+    }
+}
+
+impl_approx_eq_float!(f32, f64);
+impl_approx_eq_int!(i32, u32, i64, u64);
+
+/// Asserts that 2 slices are equal with the given tolerance
+///
+/// This panics with the index of the first element that doesn't match if there is one.
+/// ```
+/// # use emu_core::testing::*;
+/// assert_approx_eq(&[1.0f32, 2.0, 3.0], &[1.0, 2.0000001, 3.0], 1e-5);
+/// ```
+pub fn assert_approx_eq<T: ApproxEq + Debug>(actual: &[T], expected: &[T], tolerance: f64) {
+    assert_eq!(
+        actual.len(),
+        expected.len(),
+        "expected {} elements but got {}",
+        expected.len(),
+        actual.len()
+    );
+    for (i, (a, e)) in actual.iter().zip(expected.iter()).enumerate() {
+        assert!(
+            a.approx_eq(e, tolerance),
+            "element {} is {:?} but expected {:?} (with tolerance {})",
+            i,
+            a,
+            e,
+            tolerance
+        );
+    }
+}
+
+/// A trait for values that can be randomly generated to use as input to kernels
+///
+/// Floats are generated in the range `[-1, 1)` and integers are generated in the range `[0, 1024)`.
+pub trait Generate {
+    /// Generates a value, updating the state of the random number generator
+    fn generate(state: &mut u64) -> Self;
+}
+
+// a simple xorshift random number generator
+// this is good enough for making test input and keeps input reproducible with a seed
+fn next_random(state: &mut u64) -> u64 {
+    // xorshift never leaves 0 so we nudge it
+    if *state == 0 {
+        *state = 0x9e37_79b9_7f4a_7c15;
+    }
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+impl Generate for f32 {
+    fn generate(state: &mut u64) -> Self {
+        f64::generate(state) as f32
+    }
+}
+
+impl Generate for f64 {
+    fn generate(state: &mut u64) -> Self {
+        (next_random(state) >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+    }
+}
+
+impl Generate for i32 {
+    fn generate(state: &mut u64) -> Self {
+        (next_random(state) % 1024) as i32
+    }
+}
+
+impl Generate for u32 {
+    fn generate(state: &mut u64) -> Self {
+        (next_random(state) % 1024) as u32
+    }
+}
+
+/// Generates input of the given length
+///
+/// The same seed always generates the same input so that failures can be reproduced.
+/// ```
+/// # use emu_core::testing::*;
+/// let input: Vec<f32> = generate_input(1024, 42);
+/// assert_eq!(input, generate_input::<f32>(1024, 42));
+/// assert!(input.iter().all(|x| *x >= -1.0 && *x < 1.0));
+/// ```
+pub fn generate_input<T: Generate>(len: usize, seed: u64) -> Vec<T> {
+    let mut state = seed;
+    (0..len).map(|_| T::generate(&mut state)).collect()
+}
+
+/// Asserts that a kernel produces the same output as a reference implementation
+///
+/// The kernel is expected to take a single mutable array that it transforms in place. It is launched with the given `Spawner` on a copy of `input` and
+/// what it produces is compared to what `reference` returns for `input` with [`assert_approx_eq`](fn.assert_approx_eq.html).
+/// This panics if there is no device, if the kernel can't be launched, or if the output doesn't match.
+///
+/// This is unsafe for the same reason [`launch`](../spawn/struct.Spawner.html#method.launch) is unsafe - the kernel must actually take a single mutable array of `T`.
+/// ```
+/// # use {emu_core::prelude::*, emu_core::testing::*, emu_glsl::*, zerocopy::*};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # futures::executor::block_on(assert_device_pool_initialized());
+/// let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+///     GlslKernel::new()
+///         .param_mut::<[f32], _>("float[] data")
+///         .with_kernel_code("data[gl_GlobalInvocationID.x] = data[gl_GlobalInvocationID.x] * 2.0 + 1.0;"),
+/// )?
+/// .finish()?;
+///
+/// let input: Vec<f32> = generate_input(1024, 42);
+/// unsafe {
+///     assert_kernel_matches(&kernel, &spawn(1024), &input, |input| {
+///         input.iter().map(|x| x * 2.0 + 1.0).collect()
+///     }, 1e-6);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub unsafe fn assert_kernel_matches<T, F>(
+    kernel: &Arc<DeviceFnMut>,
+    spawner: &Spawner,
+    input: &[T],
+    reference: F,
+    tolerance: f64,
+) where
+    T: AsBytes + FromBytes + Copy + ApproxEq + Debug,
+    F: FnOnce(&[T]) -> Vec<T>,
+{
+    let mut data: DeviceBox<[T]> = input
+        .as_device_boxed_mut()
+        .expect("no device could be found for running the kernel");
+    spawner
+        .launch(crate::call!(kernel.clone(), &mut data))
+        .expect("failed to launch the kernel");
+    let actual =
+        futures::executor::block_on(data.get()).expect("failed to get the output of the kernel");
+    assert_approx_eq(&actual, &reference(input), tolerance);
+}
@@ -0,0 +1,225 @@
+//! A queue of work items on a device for persistent kernels
+//!
+//! Irregular workloads (like traversing a BVH or running a graph of tasks) produce new work as they go. Launching a kernel for each item is too
+//! expensive. Instead, a persistent kernel is launched with just enough threads to fill the device and each thread loops, popping items off of a
+//! [`WorkQueue`](struct.WorkQueue.html) and pushing any new items it finds. A kernel declares the queue with
+//! [`GlslKernel::param_work_queue`](../compile_impls/struct.GlslKernel.html#method.param_work_queue) and [`WorkQueue::run`](struct.WorkQueue.html#method.run)
+//! launches it until there is no work left. This module requires the `glsl-compile` feature.
+//!
+//! WebGPU doesn't let the host write to a buffer while a kernel is using it. So the kernel runs in rounds. In each round, the items that were
+//! in the queue when the round started are popped and items pushed during the round are popped in the next round. The host can push items
+//! and signal termination between rounds.
+//! ```
+//! # use {emu_core::prelude::*, emu_core::work_queue::*, emu_glsl::*, zerocopy::*};
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # futures::executor::block_on(assert_device_pool_initialized());
+//! // visit every node of a binary tree with 1000 nodes, starting from the root
+//! let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+//!     GlslKernel::new()
+//!         .spawn(64)
+//!         .param_work_queue::<u32, _>("uint nodes")
+//!         .param_mut::<[u32], _>("uint[] visited")
+//!         .with_kernel_code(r#"
+//! uint node;
+//! while (nodes_pop(node)) {
+//!     atomicAdd(visited[0], 1u);
+//!     if (2u * node + 1u < 1000u) nodes_push(2u * node + 1u);
+//!     if (2u * node + 2u < 1000u) nodes_push(2u * node + 2u);
+//! }
+//! "#),
+//! )?
+//! .finish()?;
+//!
+//! let mut nodes: WorkQueue<u32> = WorkQueue::new(1024)?;
+//! futures::executor::block_on(nodes.push(&[0]))?;
+//! let mut visited: DeviceBox<[u32]> = vec![0u32; 1].as_device_boxed_mut()?;
+//! let rounds = futures::executor::block_on(nodes.run(|nodes| unsafe {
+//!     spawn(4).launch(call!(kernel.clone(), nodes, &mut visited))
+//! }))?;
+//! // each level of the tree is a round
+//! assert_eq!(rounds, 10);
+//! assert_eq!(futures::executor::block_on(visited.get())?, vec![1000].into_boxed_slice());
+//! # Ok(())
+//! # }
+//! ```
+
+use zerocopy::*;
+
+use crate::boxed::*;
+use crate::device::*;
+use crate::error::*;
+use crate::pool::*;
+
+// the index of each field of the header of a queue
+// head, round start, round end, and tail are counters of items that only go up (within a round)
+// the slot of the item with counter i is i % capacity
+pub(crate) const HEAD: usize = 0; // the next item to pop
+pub(crate) const ROUND_START: usize = 1; // the first item of the round, the queue is full when the tail is capacity items past this
+pub(crate) const ROUND_END: usize = 2; // items at or past this were pushed during the round and aren't popped until the next round
+pub(crate) const TAIL: usize = 3; // where the next item is pushed
+pub(crate) const CAPACITY: usize = 4;
+pub(crate) const TERMINATED: usize = 5;
+pub(crate) const DROPPED: usize = 6; // the number of items the device couldn't push because the queue was full
+pub(crate) const HEADER_LEN: usize = 8;
+
+/// A queue of items of type `T` on a device
+///
+/// This is passed to [`call!`](../macro.call.html) as a single argument for a parameter declared with
+/// [`param_work_queue`](../compile_impls/struct.GlslKernel.html#method.param_work_queue). The queue holds up to a fixed number of items that
+/// haven't been popped yet. See [`work_queue`](index.html) for an example.
+pub struct WorkQueue<T> {
+    items: DeviceBox<[T]>,
+    header: DeviceBox<[u32]>,
+    capacity: u32,
+}
+
+impl<T: AsBytes + FromBytes + Copy> WorkQueue<T> {
+    /// Creates an empty queue that can hold the given number of items on the device currently selected from the pool
+    pub fn new(capacity: u32) -> Result<Self, NoDeviceError> {
+        assert!(capacity > 0, "a queue must be able to hold at least 1 item");
+        Ok(Self {
+            items: DeviceBox::with_size_mut(capacity as usize * std::mem::size_of::<T>())?,
+            header: Self::initial_header(capacity).as_device_boxed_mut()?,
+            capacity,
+        })
+    }
+
+    /// Returns the number of items this can hold
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Appends the given items to the end of the queue
+    ///
+    /// If they don't all fit, nothing is pushed and this returns `WorkQueueError::Full`.
+    pub async fn push<B: AsRef<[T]>>(&mut self, items: B) -> Result<(), WorkQueueError> {
+        let items = items.as_ref();
+        let mut header = self.header.get().await?;
+        let available = (self.capacity - Self::header_len(&header)) as usize;
+        if items.len() > available {
+            return Err(WorkQueueError::Full {
+                requested: items.len(),
+                available,
+            });
+        }
+
+        // the items may wrap around the end of the buffer
+        let tail = header[TAIL];
+        let slot = (tail % self.capacity) as usize;
+        let (before_end, after_end) =
+            items.split_at(items.len().min(self.capacity as usize - slot));
+        let mut device = take()?.lock().unwrap();
+        device.write_at(
+            &self.items,
+            (slot * std::mem::size_of::<T>()) as u64,
+            before_end.as_bytes(),
+        );
+        if !after_end.is_empty() {
+            device.write_at(&self.items, 0, after_end.as_bytes());
+        }
+        header[TAIL] = tail + items.len() as u32;
+        device
+            .set_from(&mut self.header, &*header)
+            .expect("the header of a queue is always the same size");
+        Ok(())
+    }
+
+    /// Returns the number of items that haven't been popped yet
+    pub async fn len(&self) -> Result<u32, GetError> {
+        Ok(Self::header_len(&self.header.get().await?))
+    }
+
+    /// Returns whether or not termination was signaled, either by [`terminate`](#method.terminate) or by the kernel
+    pub async fn is_terminated(&self) -> Result<bool, GetError> {
+        Ok(self.header.get().await?[TERMINATED] != 0)
+    }
+
+    /// Returns the number of items the kernel tried to push while the queue was full
+    ///
+    /// These items are lost. If this isn't 0, the queue should have been created with a larger capacity.
+    pub async fn dropped(&self) -> Result<u32, GetError> {
+        Ok(self.header.get().await?[DROPPED])
+    }
+
+    /// Signals termination so that popping items fails and [`run`](#method.run) stops
+    ///
+    /// A kernel can signal termination itself by calling the generated `nodes_terminate()` function (for a queue named `nodes`).
+    pub fn terminate(&mut self) -> Result<(), NoDeviceError> {
+        take()?.lock().unwrap().write_at(
+            &self.header,
+            (TERMINATED * std::mem::size_of::<u32>()) as u64,
+            1u32.as_bytes(),
+        );
+        Ok(())
+    }
+
+    /// Throws away all items and the signal to terminate
+    pub fn clear(&mut self) -> Result<(), NoDeviceError> {
+        self.set_header(Self::initial_header(self.capacity))
+    }
+
+    /// Repeatedly calls the given function to launch a kernel that works on the queue until the queue is empty or termination is signaled
+    ///
+    /// Each call starts a new round (see [`work_queue`](index.html)). Returns the number of rounds.
+    pub async fn run<F>(&mut self, mut launch: F) -> Result<u32, WorkQueueError>
+    where
+        F: FnMut(&Self) -> Result<(), LaunchError>,
+    {
+        let mut rounds = 0;
+        loop {
+            let header = self.header.get().await?;
+            if header[TERMINATED] != 0 || Self::header_len(&header) == 0 {
+                return Ok(rounds);
+            }
+
+            // every item up to the tail is popped in the next round
+            // we also move the counters back by a multiple of the capacity so that they never overflow
+            let consumed = header[HEAD].min(header[ROUND_END]);
+            let base = consumed - consumed % self.capacity;
+            let (consumed, tail) = (consumed - base, header[TAIL] - base);
+            let mut next_header = header.to_vec();
+            next_header[HEAD] = consumed;
+            next_header[ROUND_START] = consumed;
+            next_header[ROUND_END] = tail;
+            next_header[TAIL] = tail;
+            self.set_header(next_header)?;
+
+            launch(self)?;
+            rounds += 1;
+        }
+    }
+
+    // the number of items that haven't been popped yet according to the given header
+    // the head may have gone past the end of the round when threads tried to pop from an empty round
+    fn header_len(header: &[u32]) -> u32 {
+        header[TAIL] - header[HEAD].min(header[ROUND_END])
+    }
+
+    // uploads the given header, which is always the size of the header the queue was created with
+    fn set_header(&mut self, header: Vec<u32>) -> Result<(), NoDeviceError> {
+        take()?
+            .lock()
+            .unwrap()
+            .set_from(&mut self.header, header)
+            .expect("the header of a queue is always the same size");
+        Ok(())
+    }
+
+    fn initial_header(capacity: u32) -> Vec<u32> {
+        let mut header = vec![0; HEADER_LEN];
+        header[CAPACITY] = capacity;
+        header
+    }
+}
+
+impl<'a, T> IntoArg<'a> for &'a WorkQueue<T> {
+    fn add_to(self, builder: ArgsBuilder<'a>) -> ArgsBuilder<'a> {
+        builder.arg(&self.items).arg(&self.header)
+    }
+}
+
+impl<'a, T> IntoArg<'a> for &'a mut WorkQueue<T> {
+    fn add_to(self, builder: ArgsBuilder<'a>) -> ArgsBuilder<'a> {
+        builder.arg(&self.items).arg(&self.header)
+    }
+}
@@ -0,0 +1,43 @@
+//! Launches and downloads from many threads at once on the same `DeviceBox`
+#![cfg(any(feature = "glsl-compile", feature = "glsl-compile-naga"))]
+
+use {emu_core::prelude::*, rayon::prelude::*, std::sync::Arc};
+
+#[test]
+fn launches_and_downloads_concurrently() -> Result<(), Box<dyn std::error::Error>> {
+    // this fails (instead of passing without testing anything) if there's no device
+    emu_core::testing::run(launch_and_download_concurrently)
+}
+
+fn launch_and_download_concurrently() -> Result<(), Box<dyn std::error::Error>> {
+    let kernel = compile::<GlslKernel, GlslKernelCompile, _, GlobalCache>(
+        GlslKernel::new()
+            .param_mut::<[f32], _>("float[] data")
+            .with_kernel_code("data[gl_GlobalInvocationID.x] += 1.0;"),
+    )?
+    .finish()?;
+    let data: Arc<DeviceBox<[f32]>> = Arc::new(vec![0.0; 1024].as_device_boxed_mut()?);
+
+    // half of the iterations launch and half download, all on the same DeviceBox
+    (0..64)
+        .into_par_iter()
+        .try_for_each(|i| -> Result<(), String> {
+            if i % 2 == 0 {
+                unsafe { spawn(1024).launch(call!(kernel.clone(), &*data)) }
+                    .map_err(|e| e.to_string())
+            } else {
+                let downloaded =
+                    futures::executor::block_on(data.get()).map_err(|e| e.to_string())?;
+                // each launch adds 1 to every element so a download only ever sees whole launches
+                assert!(downloaded.iter().all(|x| *x == downloaded[0]));
+                assert!(downloaded[0] >= 0.0 && downloaded[0] <= 32.0);
+                Ok(())
+            }
+        })?;
+
+    assert_eq!(
+        futures::executor::block_on(data.get())?,
+        vec![32.0; 1024].into_boxed_slice()
+    );
+    Ok(())
+}
@@ -0,0 +1,19 @@
+use std::env;
+use std::path::PathBuf;
+
+// generates the C header for the functions exported by this crate
+// the generated header is checked in so that people using a pre-built library don't need to build it themselves
+fn main() {
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml"))
+        .expect("failed to read cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("failed to generate C header for emu_core_capi")
+        .write_to_file(crate_dir.join("include").join("emu_core.h"));
+}
@@ -0,0 +1,147 @@
+/* The C API of Emu (see emu_core_capi) */
+
+#ifndef EMU_CORE_H
+#define EMU_CORE_H
+
+/* This file is generated by cbindgen when emu_core_capi is built. Don't edit it by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The result of calling a function of the C API
+typedef enum EmuStatus {
+  // The call succeeded
+  EMU_STATUS_OK = 0,
+  // A pointer that must not be null was null, a string wasn't valid UTF-8, or a size didn't match the size of a buffer
+  EMU_STATUS_INVALID_ARGUMENT,
+  // There is no device in the pool (or the pool hasn't been initialized with `emu_pool_init`)
+  EMU_STATUS_NO_DEVICE,
+  // The SPIR-V couldn't be read or compiled to a kernel
+  EMU_STATUS_COMPILE,
+  // The kernel couldn't be launched
+  EMU_STATUS_LAUNCH,
+  // The contents of a buffer couldn't be downloaded
+  EMU_STATUS_GET,
+  // Emu panicked (for example, because the arguments of a launch didn't match the parameters of the kernel)
+  EMU_STATUS_PANIC,
+} EmuStatus;
+
+// A buffer of bytes on a device
+//
+// This is created with `emu_buffer_create` or `emu_buffer_create_from` and must be freed with `emu_buffer_free`.
+typedef struct EmuBuffer EmuBuffer;
+
+// A kernel compiled for the device currently selected from the pool
+//
+// This is created with `emu_kernel_compile_spirv` and must be freed with `emu_kernel_free`.
+typedef struct EmuKernel EmuKernel;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Returns the message of the last error on this thread or null if there hasn't been one
+//
+// The returned string is owned by Emu and is valid until the next call that fails on this thread.
+const char *emu_last_error_message(void);
+
+// Initializes the pool of devices if it hasn't been initialized
+//
+// The devices can be chosen with a configuration file or environment variables just like with `assert_device_pool_initialized` in
+// `emu_core`. This returns `EMU_STATUS_NO_DEVICE` if no device was found.
+EmuStatus emu_pool_init(void);
+
+// Creates a buffer with the given number of bytes on the device currently selected from the pool
+//
+// The contents of the buffer are unspecified until something is uploaded to it.
+//
+// # Safety
+//
+// `out` must be a valid pointer to write the created buffer to.
+EmuStatus emu_buffer_create(size_t size, EmuBuffer **out);
+
+// Creates a buffer on the device currently selected from the pool with a copy of the given bytes
+//
+// # Safety
+//
+// `data` must point to `size` readable bytes and `out` must be a valid pointer to write the created buffer to.
+EmuStatus emu_buffer_create_from(const uint8_t *data, size_t size, EmuBuffer **out);
+
+// Returns the number of bytes in the given buffer (or 0 if it is null)
+//
+// # Safety
+//
+// `buffer` must be null or a buffer that hasn't been freed.
+size_t emu_buffer_size(const EmuBuffer *buffer);
+
+// Uploads the given bytes to the buffer
+//
+// `size` must be the size of the buffer.
+//
+// # Safety
+//
+// `buffer` must be a buffer that hasn't been freed and `data` must point to `size` readable bytes.
+EmuStatus emu_buffer_upload(EmuBuffer *buffer, const uint8_t *data, size_t size);
+
+// Downloads the contents of the buffer into the given bytes, waiting for any launches using the buffer to complete
+//
+// `size` must be the size of the buffer.
+//
+// # Safety
+//
+// `buffer` must be a buffer that hasn't been freed and `data` must point to `size` writable bytes.
+EmuStatus emu_buffer_download(const EmuBuffer *buffer, uint8_t *data, size_t size);
+
+// Frees the given buffer (which may be null)
+//
+// # Safety
+//
+// `buffer` must be null or a buffer that hasn't been freed.
+void emu_buffer_free(EmuBuffer *buffer);
+
+// Compiles a kernel from SPIR-V for the device currently selected from the pool
+//
+// `code` is the bytes of a SPIR-V module (like what `glslc` writes) and `entry_point` is the name of the function to enter. Each parameter
+// of the kernel is a storage buffer and `params_mut` says for each of the `num_params` parameters whether or not it is mutable. Kernels are
+// cached so compiling the same SPIR-V more than once is cheap.
+//
+// # Safety
+//
+// `code` must point to `code_len` readable bytes, `entry_point` must be a null-terminated string, `params_mut` must point to `num_params`
+// readable `bool`s, and `out` must be a valid pointer to write the compiled kernel to.
+EmuStatus emu_kernel_compile_spirv(const uint8_t *code,
+                                   size_t code_len,
+                                   const char *entry_point,
+                                   const bool *params_mut,
+                                   size_t num_params,
+                                   EmuKernel **out);
+
+// Frees the given kernel (which may be null)
+//
+// # Safety
+//
+// `kernel` must be null or a kernel that hasn't been freed.
+void emu_kernel_free(EmuKernel *kernel);
+
+// Launches the kernel on a space of `blocks_x * blocks_y * blocks_z` thread blocks with the given buffers as arguments
+//
+// The buffers are passed to the parameters of the kernel in order. This doesn't wait for the kernel to complete. Downloading a buffer does.
+//
+// # Safety
+//
+// `kernel` must be a kernel that hasn't been freed and `buffers` must point to `num_buffers` buffers that haven't been freed.
+EmuStatus emu_launch(const EmuKernel *kernel,
+                     uint32_t blocks_x,
+                     uint32_t blocks_y,
+                     uint32_t blocks_z,
+                     const EmuBuffer *const *buffers,
+                     size_t num_buffers);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* EMU_CORE_H */
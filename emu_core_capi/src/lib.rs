@@ -0,0 +1,396 @@
+//! `emu_core_capi` is a C API for [`emu_core`](https://docs.rs/emu_core). It lets host programs written in other languages (like C, C++, or
+//! Python through `ctypes`) embed the Emu runtime - initialize the pool of devices, create buffers on a device, upload and download them,
+//! compile kernels from SPIR-V, and launch kernels.
+//!
+//! This crate builds a shared library and a static library. The header to include is `include/emu_core.h`, which is generated by
+//! [`cbindgen`](https://github.com/eqrion/cbindgen) whenever this crate is built. Here's how it might be used from C.
+//! ```c
+//! #include "emu_core.h"
+//!
+//! // a kernel compiled from GLSL with `glslc` that doubles each float in the buffer
+//! uint8_t *spirv = ...;
+//! size_t spirv_len = ...;
+//!
+//! float data[1024] = { ... };
+//! EmuBuffer *buffer;
+//! EmuKernel *kernel;
+//! bool params_mut[] = { true };
+//!
+//! if (emu_pool_init() != EMU_STATUS_OK ||
+//!     emu_buffer_create_from((const uint8_t *) data, sizeof(data), &buffer) != EMU_STATUS_OK ||
+//!     emu_kernel_compile_spirv(spirv, spirv_len, "main", params_mut, 1, &kernel) != EMU_STATUS_OK ||
+//!     emu_launch(kernel, 1024, 1, 1, (const EmuBuffer *const *) &buffer, 1) != EMU_STATUS_OK ||
+//!     emu_buffer_download(buffer, (uint8_t *) data, sizeof(data)) != EMU_STATUS_OK) {
+//!     fprintf(stderr, "%s\n", emu_last_error_message());
+//! }
+//!
+//! emu_kernel_free(kernel);
+//! emu_buffer_free(buffer);
+//! ```
+//! Every function that can fail returns an [`EmuStatus`](enum.EmuStatus.html). When it isn't `EMU_STATUS_OK`, a message describing what went
+//! wrong can be gotten with [`emu_last_error_message`](fn.emu_last_error_message.html). Panics inside of Emu (like when the arguments of a
+//! launch don't match the parameters of the kernel) are caught and returned as `EMU_STATUS_PANIC` instead of unwinding into the host program.
+//!
+//! Buffers are untyped bytes (a `DeviceBox<[u8]>`) and the parameters of kernels are storage buffers. So the host program is responsible for
+//! laying out data the way the kernel expects it.
+
+use std::cell::RefCell;
+use std::error::Error;
+use std::ffi::{CStr, CString};
+use std::fmt::Display;
+use std::io::Cursor;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+use std::sync::Arc;
+
+use emu_core::prelude::*;
+
+/// The result of calling a function of the C API
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmuStatus {
+    /// The call succeeded
+    Ok = 0,
+    /// A pointer that must not be null was null, a string wasn't valid UTF-8, or a size didn't match the size of a buffer
+    InvalidArgument,
+    /// There is no device in the pool (or the pool hasn't been initialized with `emu_pool_init`)
+    NoDevice,
+    /// The SPIR-V couldn't be read or compiled to a kernel
+    Compile,
+    /// The kernel couldn't be launched
+    Launch,
+    /// The contents of a buffer couldn't be downloaded
+    Get,
+    /// Emu panicked (for example, because the arguments of a launch didn't match the parameters of the kernel)
+    Panic,
+}
+
+/// A buffer of bytes on a device
+///
+/// This is created with `emu_buffer_create` or `emu_buffer_create_from` and must be freed with `emu_buffer_free`.
+pub struct EmuBuffer(DeviceBox<[u8]>);
+
+/// A kernel compiled for the device currently selected from the pool
+///
+/// This is created with `emu_kernel_compile_spirv` and must be freed with `emu_kernel_free`.
+pub struct EmuKernel(Arc<DeviceFnMut>);
+
+thread_local! {
+    // the message of the last error on this thread
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+fn set_last_error(message: impl Display) {
+    // interior null bytes would cut the message short in C so they are removed
+    let message = message.to_string().replace('\0', "");
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = CString::new(message).ok());
+}
+
+// runs the body of an exported function
+// errors are stored as the last error and panics are caught so that they don't unwind across the FFI boundary
+fn ffi_call(f: impl FnOnce() -> Result<(), (EmuStatus, String)>) -> EmuStatus {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => EmuStatus::Ok,
+        Ok(Err((status, message))) => {
+            set_last_error(message);
+            status
+        }
+        Err(panic) => {
+            let message = if let Some(message) = panic.downcast_ref::<&str>() {
+                message.to_string()
+            } else if let Some(message) = panic.downcast_ref::<String>() {
+                message.clone()
+            } else {
+                String::from("Emu panicked")
+            };
+            set_last_error(message);
+            EmuStatus::Panic
+        }
+    }
+}
+
+// the message of an error has the whole chain of its sources
+fn fail<E: Error + 'static>(status: EmuStatus) -> impl FnOnce(E) -> (EmuStatus, String) {
+    move |error| (status, display_chain(&error))
+}
+
+fn null_pointer(name: &str) -> (EmuStatus, String) {
+    (
+        EmuStatus::InvalidArgument,
+        format!("`{}` must not be null", name),
+    )
+}
+
+// borrows a slice from a pointer and length given by the host program
+// a null pointer is only allowed for an empty slice
+unsafe fn host_slice<'a, T>(
+    data: *const T,
+    len: usize,
+    name: &str,
+) -> Result<&'a [T], (EmuStatus, String)> {
+    if len == 0 {
+        Ok(&[])
+    } else if data.is_null() {
+        Err(null_pointer(name))
+    } else {
+        Ok(slice::from_raw_parts(data, len))
+    }
+}
+
+/// Returns the message of the last error on this thread or null if there hasn't been one
+///
+/// The returned string is owned by Emu and is valid until the next call that fails on this thread.
+#[no_mangle]
+pub extern "C" fn emu_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last_error| match &*last_error.borrow() {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// Initializes the pool of devices if it hasn't been initialized
+///
+/// The devices can be chosen with a configuration file or environment variables just like with `assert_device_pool_initialized` in
+/// `emu_core`. This returns `EMU_STATUS_NO_DEVICE` if no device was found.
+#[no_mangle]
+pub extern "C" fn emu_pool_init() -> EmuStatus {
+    ffi_call(|| {
+        futures::executor::block_on(assert_device_pool_initialized());
+        take().map(|_| ()).map_err(fail(EmuStatus::NoDevice))
+    })
+}
+
+/// Creates a buffer with the given number of bytes on the device currently selected from the pool
+///
+/// The contents of the buffer are unspecified until something is uploaded to it.
+///
+/// # Safety
+///
+/// `out` must be a valid pointer to write the created buffer to.
+#[no_mangle]
+pub unsafe extern "C" fn emu_buffer_create(size: usize, out: *mut *mut EmuBuffer) -> EmuStatus {
+    ffi_call(|| {
+        if out.is_null() {
+            return Err(null_pointer("out"));
+        }
+        let buffer = DeviceBox::<[u8]>::with_size_mut(size).map_err(fail(EmuStatus::NoDevice))?;
+        *out = Box::into_raw(Box::new(EmuBuffer(buffer)));
+        Ok(())
+    })
+}
+
+/// Creates a buffer on the device currently selected from the pool with a copy of the given bytes
+///
+/// # Safety
+///
+/// `data` must point to `size` readable bytes and `out` must be a valid pointer to write the created buffer to.
+#[no_mangle]
+pub unsafe extern "C" fn emu_buffer_create_from(
+    data: *const u8,
+    size: usize,
+    out: *mut *mut EmuBuffer,
+) -> EmuStatus {
+    ffi_call(|| {
+        if out.is_null() {
+            return Err(null_pointer("out"));
+        }
+        let data = host_slice(data, size, "data")?;
+        let buffer = data
+            .as_device_boxed_mut()
+            .map_err(fail(EmuStatus::NoDevice))?;
+        *out = Box::into_raw(Box::new(EmuBuffer(buffer)));
+        Ok(())
+    })
+}
+
+/// Returns the number of bytes in the given buffer (or 0 if it is null)
+///
+/// # Safety
+///
+/// `buffer` must be null or a buffer that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn emu_buffer_size(buffer: *const EmuBuffer) -> usize {
+    buffer.as_ref().map(|buffer| buffer.0.len()).unwrap_or(0)
+}
+
+/// Uploads the given bytes to the buffer
+///
+/// `size` must be the size of the buffer.
+///
+/// # Safety
+///
+/// `buffer` must be a buffer that hasn't been freed and `data` must point to `size` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn emu_buffer_upload(
+    buffer: *mut EmuBuffer,
+    data: *const u8,
+    size: usize,
+) -> EmuStatus {
+    ffi_call(|| {
+        let buffer = buffer.as_mut().ok_or_else(|| null_pointer("buffer"))?;
+        if size != buffer.0.len() {
+            return Err((
+                EmuStatus::InvalidArgument,
+                format!(
+                    "can't upload {} bytes to a buffer of {} bytes",
+                    size,
+                    buffer.0.len()
+                ),
+            ));
+        }
+        let data = host_slice(data, size, "data")?;
+        buffer.0.set(data).map_err(|error| match error {
+            SetError::NoDevice => fail(EmuStatus::NoDevice)(error),
+            _ => fail(EmuStatus::InvalidArgument)(error),
+        })
+    })
+}
+
+/// Downloads the contents of the buffer into the given bytes, waiting for any launches using the buffer to complete
+///
+/// `size` must be the size of the buffer.
+///
+/// # Safety
+///
+/// `buffer` must be a buffer that hasn't been freed and `data` must point to `size` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn emu_buffer_download(
+    buffer: *const EmuBuffer,
+    data: *mut u8,
+    size: usize,
+) -> EmuStatus {
+    ffi_call(|| {
+        let buffer = buffer.as_ref().ok_or_else(|| null_pointer("buffer"))?;
+        if size != buffer.0.len() {
+            return Err((
+                EmuStatus::InvalidArgument,
+                format!(
+                    "can't download a buffer of {} bytes to {} bytes",
+                    buffer.0.len(),
+                    size
+                ),
+            ));
+        }
+        if size == 0 {
+            return Ok(());
+        }
+        if data.is_null() {
+            return Err(null_pointer("data"));
+        }
+        let data = slice::from_raw_parts_mut(data, size);
+        futures::executor::block_on(buffer.0.get_into(data)).map_err(fail(EmuStatus::Get))
+    })
+}
+
+/// Frees the given buffer (which may be null)
+///
+/// # Safety
+///
+/// `buffer` must be null or a buffer that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn emu_buffer_free(buffer: *mut EmuBuffer) {
+    if !buffer.is_null() {
+        drop(Box::from_raw(buffer));
+    }
+}
+
+/// Compiles a kernel from SPIR-V for the device currently selected from the pool
+///
+/// `code` is the bytes of a SPIR-V module (like what `glslc` writes) and `entry_point` is the name of the function to enter. Each parameter
+/// of the kernel is a storage buffer and `params_mut` says for each of the `num_params` parameters whether or not it is mutable. Kernels are
+/// cached so compiling the same SPIR-V more than once is cheap.
+///
+/// # Safety
+///
+/// `code` must point to `code_len` readable bytes, `entry_point` must be a null-terminated string, `params_mut` must point to `num_params`
+/// readable `bool`s, and `out` must be a valid pointer to write the compiled kernel to.
+#[no_mangle]
+pub unsafe extern "C" fn emu_kernel_compile_spirv(
+    code: *const u8,
+    code_len: usize,
+    entry_point: *const c_char,
+    params_mut: *const bool,
+    num_params: usize,
+    out: *mut *mut EmuKernel,
+) -> EmuStatus {
+    ffi_call(|| {
+        if out.is_null() {
+            return Err(null_pointer("out"));
+        }
+        if entry_point.is_null() {
+            return Err(null_pointer("entry_point"));
+        }
+        let code = host_slice(code, code_len, "code")?;
+        let entry_point = CStr::from_ptr(entry_point)
+            .to_str()
+            .map_err(fail(EmuStatus::InvalidArgument))?;
+        let params_mut = host_slice(params_mut, num_params, "params_mut")?;
+
+        let mut builder = SpirvBuilder::new().set_entry_point_name(entry_point);
+        for &param_mut in params_mut {
+            builder = if param_mut {
+                builder.add_param_mut::<[u8]>()
+            } else {
+                builder.add_param::<[u8]>()
+            };
+        }
+        let spirv = builder
+            .set_code_with_u8(Cursor::new(code))
+            .map_err(fail(EmuStatus::Compile))?
+            .build();
+
+        let kernel = compile::<Spirv<_>, SpirvCompile, Vec<u32>, GlobalCache>(spirv)
+            .map_err(fail(EmuStatus::Compile))?
+            .finish()
+            .map_err(fail(EmuStatus::Compile))?;
+        *out = Box::into_raw(Box::new(EmuKernel(kernel)));
+        Ok(())
+    })
+}
+
+/// Frees the given kernel (which may be null)
+///
+/// # Safety
+///
+/// `kernel` must be null or a kernel that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn emu_kernel_free(kernel: *mut EmuKernel) {
+    if !kernel.is_null() {
+        drop(Box::from_raw(kernel));
+    }
+}
+
+/// Launches the kernel on a space of `blocks_x * blocks_y * blocks_z` thread blocks with the given buffers as arguments
+///
+/// The buffers are passed to the parameters of the kernel in order. This doesn't wait for the kernel to complete. Downloading a buffer does.
+///
+/// # Safety
+///
+/// `kernel` must be a kernel that hasn't been freed and `buffers` must point to `num_buffers` buffers that haven't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn emu_launch(
+    kernel: *const EmuKernel,
+    blocks_x: u32,
+    blocks_y: u32,
+    blocks_z: u32,
+    buffers: *const *const EmuBuffer,
+    num_buffers: usize,
+) -> EmuStatus {
+    ffi_call(|| {
+        let kernel = kernel.as_ref().ok_or_else(|| null_pointer("kernel"))?;
+        let mut args = ArgsBuilder::new();
+        for &buffer in host_slice(buffers, num_buffers, "buffers")? {
+            let buffer = buffer.as_ref().ok_or_else(|| null_pointer("buffers"))?;
+            args = args.arg(&buffer.0);
+        }
+        let args = args.try_build().map_err(fail(EmuStatus::Launch))?;
+
+        spawn(blocks_x)
+            .spawn(blocks_y)
+            .spawn(blocks_z)
+            .launch((kernel.0.clone(), args))
+            .map_err(fail(EmuStatus::Launch))
+    })
+}
@@ -0,0 +1,639 @@
+// the generation of the adjoints of kernels given to kernel! that are marked with #[differentiable]
+//
+// given the gradient of a loss with respect to each output of a kernel, its adjoint adds the gradient of the loss with respect to each input
+// of the kernel to a buffer. each thread first recomputes the variables of the kernel and then goes through the statements of the kernel
+// backwards, propagating gradients from outputs and variables to what they were computed from (this is reverse-mode differentiation). many
+// threads may read the same element of an input (or the same scalar), so gradients of inputs are added with atomic operations.
+
+use std::collections::HashSet;
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, ToTokens};
+use syn::visit::Visit;
+use syn::{
+    Block, Error, Expr, ExprAssign, ExprAssignOp, ExprPath, Ident, ItemFn, Local, Pat, Result,
+    Stmt, UnOp,
+};
+
+use crate::kernel::*;
+
+// generates a Rust function named like the given kernel but ending with _grad that launches the adjoint of the kernel
+pub(crate) fn expand(kernel: &ItemFn, params: &[Param]) -> Result<TokenStream> {
+    // (1) check that the kernel can be differentiated
+    for param in params {
+        if param.glsl_type == "double" {
+            return Err(Error::new_spanned(
+                &param.ty,
+                "a differentiable kernel can't use `f64`",
+            ));
+        }
+        if param.kind == ParamKind::ArrayMut && param.glsl_type != "float" {
+            return Err(Error::new_spanned(
+                &param.ty,
+                "a differentiable kernel can only write to slices of `f32`",
+            ));
+        }
+    }
+    let mut mutable = HashSet::new();
+    check_stmts(&kernel.block.stmts, true, &mut mutable)?;
+    check_accesses(&kernel.block.stmts, params, &mutable)?;
+
+    // (2) translate the adjoint to GLSL
+    let mut adjoint = Adjoint {
+        translator: Translator::new(params),
+        num_temps: 0,
+    };
+    adjoint.translator.guard();
+    adjoint.translator.skip_writes = true;
+    adjoint.translator.stmts(&kernel.block.stmts)?;
+    if adjoint.translator.f64 {
+        return Err(Error::new_spanned(
+            &kernel.sig.ident,
+            "a differentiable kernel can't use `f64`",
+        ));
+    }
+    let scalars = params
+        .iter()
+        .filter(|param| param.kind == ParamKind::Scalar && param.glsl_type == "float")
+        .collect::<Vec<_>>();
+    for scalar in &scalars {
+        adjoint.line(&format!("float emu_adj_{} = 0.0;", scalar.ident));
+    }
+    adjoint.declare_adjoints(&kernel.block.stmts)?;
+    adjoint.reverse(&kernel.block.stmts)?;
+    for scalar in &scalars {
+        adjoint.line(&format!("emu_grad_{0}(emu_adj_{0});", scalar.ident));
+    }
+
+    // (3) declare the gradients along with the parameters
+    // the gradients of inputs are declared as uints in GLSL so that they can be added to with atomicCompSwap
+    let mut helper_code = String::new();
+    let mut launch_params = vec![];
+    for param in params {
+        let grad = format_ident!("{}_grad", param.ident);
+        match (param.kind, param.glsl_type == "float") {
+            (ParamKind::ArrayMut, _) => {
+                let declaration = format!("float[] {}", grad);
+                launch_params.push(LaunchParam {
+                    ident: grad,
+                    rust_type: quote! { &::emu_core::device::DeviceBox<[f32]> },
+                    declaration: quote! { .param::<[f32], _>(#declaration) },
+                });
+            }
+            (ParamKind::Array, true) => {
+                let declaration = format!("uint[] {}", grad);
+                launch_params.push(param.launch_param());
+                launch_params.push(LaunchParam {
+                    ident: grad,
+                    rust_type: quote! { &mut ::emu_core::device::DeviceBox<[f32]> },
+                    declaration: quote! { .param_mut::<[f32], _>(#declaration) },
+                });
+                helper_code += &atomic_add(&param.ident, true);
+            }
+            (ParamKind::Scalar, true) => {
+                let declaration = format!("uint {}", grad);
+                launch_params.push(param.launch_param());
+                launch_params.push(LaunchParam {
+                    ident: grad,
+                    rust_type: quote! { &mut ::emu_core::device::DeviceBox<f32> },
+                    declaration: quote! { .param_mut::<f32, _>(#declaration) },
+                });
+                helper_code += &atomic_add(&param.ident, false);
+            }
+            _ => launch_params.push(param.launch_param()),
+        }
+    }
+
+    // (4) generate the Rust function
+    // the adjoint is launched over the same number of threads as the kernel
+    let len_param = params
+        .iter()
+        .find(|param| param.kind != ParamKind::Scalar)
+        .unwrap();
+    let len_ident = if len_param.kind == ParamKind::ArrayMut {
+        format_ident!("{}_grad", len_param.ident)
+    } else {
+        len_param.ident.clone()
+    };
+    let name = &kernel.sig.ident;
+    let doc = format!(
+        "Adds the gradients of a loss with respect to the `f32` inputs of [`{0}`] to their `_grad` buffers, given the gradients with respect to the outputs of `{0}`",
+        name
+    );
+    Ok(launcher(
+        quote! { #[doc = #doc] },
+        &kernel.vis,
+        &format_ident!("{}_grad", name),
+        &launch_params,
+        &len_ident,
+        quote! { .with_helper_code(#helper_code) },
+        &adjoint.translator.glsl,
+    ))
+}
+
+// returns a GLSL function that atomically adds to the gradient of the given slice or scalar
+fn atomic_add(ident: &Ident, slice: bool) -> String {
+    let (index, grad) = if slice {
+        ("uint i, ", format!("{}_grad[i]", ident))
+    } else {
+        ("", format!("{}_grad", ident))
+    };
+    format!(
+        r#"
+void emu_grad_{ident}({index}float value) {{
+    if (value == 0.0) {{
+        return;
+    }}
+    uint expected = {grad};
+    while (true) {{
+        uint actual = atomicCompSwap({grad}, expected, floatBitsToUint(uintBitsToFloat(expected) + value));
+        if (actual == expected) {{
+            break;
+        }}
+        expected = actual;
+    }}
+}}
+"#,
+        ident = ident,
+        index = index,
+        grad = grad
+    )
+}
+
+fn local_name(local: &Local) -> Result<String> {
+    let pat = match &local.pat {
+        Pat::Type(pat_type) => &*pat_type.pat,
+        pat => pat,
+    };
+    Ok(get_name(pat, "variable")?.to_string())
+}
+
+// checks that the given statements only use what can be differentiated, collecting the names of mutable variables
+fn check_stmts(stmts: &[Stmt], top_level: bool, mutable: &mut HashSet<String>) -> Result<()> {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Local(local) => {
+                let pat = match &local.pat {
+                    Pat::Type(pat_type) => &*pat_type.pat,
+                    pat => pat,
+                };
+                if let Pat::Ident(pat_ident) = pat {
+                    if pat_ident.mutability.is_some() {
+                        if !top_level {
+                            return Err(Error::new_spanned(
+                                pat_ident,
+                                "a mutable variable of a differentiable kernel must be declared outside of any `if` or `for`",
+                            ));
+                        }
+                        mutable.insert(pat_ident.ident.to_string());
+                    }
+                }
+            }
+            Stmt::Expr(expr) | Stmt::Semi(expr, _) => check_stmt_expr(expr, mutable)?,
+            Stmt::Item(_) => {}
+        }
+    }
+    Ok(())
+}
+
+fn check_stmt_expr(expr: &Expr, mutable: &mut HashSet<String>) -> Result<()> {
+    match expr {
+        Expr::If(expr_if) => {
+            check_stmts(&expr_if.then_branch.stmts, false, mutable)?;
+            if let Some((_, else_branch)) = &expr_if.else_branch {
+                check_stmt_expr(else_branch, mutable)?;
+            }
+        }
+        Expr::ForLoop(expr_for) => check_stmts(&expr_for.body.stmts, false, mutable)?,
+        Expr::Block(expr_block) => check_stmts(&expr_block.block.stmts, false, mutable)?,
+        Expr::Assign(expr_assign) => {
+            if let Expr::Index(_) = &*expr_assign.left {
+            } else {
+                return Err(Error::new_spanned(
+                    &expr_assign.left,
+                    "a variable of a differentiable kernel can only be updated with `+=` or `-=`",
+                ));
+            }
+        }
+        Expr::AssignOp(expr_assign_op) => {
+            let op = expr_assign_op.op.to_token_stream().to_string();
+            if op != "+=" && op != "-=" {
+                return Err(Error::new_spanned(
+                    expr_assign_op.op,
+                    "a differentiable kernel can only update with `=`, `+=`, or `-=`",
+                ));
+            }
+        }
+        Expr::While(_) | Expr::Loop(_) | Expr::Break(_) | Expr::Continue(_) | Expr::Return(_) => {
+            return Err(Error::new_spanned(
+                expr,
+                "a differentiable kernel can't use `while`, `loop`, `break`, `continue`, or `return`",
+            ))
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// the names that a statement reads and the variables that it updates
+#[derive(Default)]
+struct Accesses {
+    reads: Vec<Ident>,
+    updates: Vec<Ident>,
+}
+
+impl Accesses {
+    fn visit_target(&mut self, target: &Expr) {
+        match target {
+            Expr::Index(expr_index) => self.visit_expr(&expr_index.index),
+            Expr::Path(expr_path) if expr_path.path.get_ident().is_some() => self
+                .updates
+                .push(expr_path.path.get_ident().unwrap().clone()),
+            target => self.visit_expr(target),
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for Accesses {
+    fn visit_expr_path(&mut self, expr_path: &'ast ExprPath) {
+        if let Some(ident) = expr_path.path.get_ident() {
+            self.reads.push(ident.clone());
+        }
+    }
+
+    fn visit_expr_assign(&mut self, expr_assign: &'ast ExprAssign) {
+        self.visit_target(&expr_assign.left);
+        self.visit_expr(&expr_assign.right);
+    }
+
+    fn visit_expr_assign_op(&mut self, expr_assign_op: &'ast ExprAssignOp) {
+        self.visit_target(&expr_assign_op.left);
+        self.visit_expr(&expr_assign_op.right);
+    }
+}
+
+// checks that no slice that is written to is read and that no mutable variable is read before it is done being updated
+// the adjoint needs the values of variables as they were when they were read but it only has their final values
+fn check_accesses(stmts: &[Stmt], params: &[Param], mutable: &HashSet<String>) -> Result<()> {
+    let accesses = stmts
+        .iter()
+        .map(|stmt| {
+            let mut accesses = Accesses::default();
+            accesses.visit_stmt(stmt);
+            accesses
+        })
+        .collect::<Vec<_>>();
+    for (i, statement_accesses) in accesses.iter().enumerate() {
+        for read in &statement_accesses.reads {
+            let name = read.to_string();
+            if params
+                .iter()
+                .any(|param| param.kind == ParamKind::ArrayMut && param.ident == name)
+            {
+                return Err(Error::new_spanned(
+                    read,
+                    format!(
+                        "a differentiable kernel can't read from `{}` since it writes to it",
+                        name
+                    ),
+                ));
+            }
+            if mutable.contains(&name)
+                && accesses[i..]
+                    .iter()
+                    .any(|later| later.updates.iter().any(|update| *update == name))
+            {
+                return Err(Error::new_spanned(
+                    read,
+                    format!(
+                        "a differentiable kernel can't read `{}` until after the last statement that updates it",
+                        name
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// translates the reverse pass of an adjoint to GLSL
+struct Adjoint<'a> {
+    translator: Translator<'a>,
+    num_temps: usize,
+}
+
+impl<'a> Adjoint<'a> {
+    fn line(&mut self, line: &str) {
+        self.translator.line(line);
+    }
+
+    // stores the given GLSL in a new variable and returns its name
+    fn temp(&mut self, value: String) -> String {
+        let name = format!("emu_tmp{}", self.num_temps);
+        self.num_temps += 1;
+        self.line(&format!("float {} = {};", name, value));
+        name
+    }
+
+    fn glsl(&mut self, expr: &Expr) -> Result<String> {
+        Ok(self.translator.expr(expr)?.0)
+    }
+
+    fn is_float(&mut self, expr: &Expr) -> Result<bool> {
+        Ok(self.translator.expr(expr)?.1.as_deref() == Some("float"))
+    }
+
+    // declares the adjoints of the float variables declared by the given statements
+    fn declare_adjoints(&mut self, stmts: &[Stmt]) -> Result<()> {
+        for stmt in stmts {
+            if let Stmt::Local(local) = stmt {
+                let name = local_name(local)?;
+                if self.translator.var_type(&name).map(String::as_str) == Some("float") {
+                    self.line(&format!("float emu_adj_{} = 0.0;", name));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // goes backwards through the given statements, propagating gradients
+    fn reverse(&mut self, stmts: &[Stmt]) -> Result<()> {
+        for stmt in stmts.iter().rev() {
+            match stmt {
+                Stmt::Local(local) => {
+                    let name = local_name(local)?;
+                    if self.translator.var_type(&name).map(String::as_str) == Some("float") {
+                        let (_, init) = local.init.as_ref().unwrap();
+                        self.backprop(init, &format!("emu_adj_{}", name))?;
+                    }
+                }
+                Stmt::Expr(expr) | Stmt::Semi(expr, _) => self.reverse_expr(expr)?,
+                Stmt::Item(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    // goes backwards through a nested block after recomputing the variables it declares
+    // the block must already be opened with a {
+    fn reverse_block(&mut self, block: &Block, vars: Vec<(String, String)>) -> Result<()> {
+        self.translator.indent += 1;
+        self.translator.scopes.push(vars.into_iter().collect());
+        for stmt in &block.stmts {
+            if let Stmt::Local(_) = stmt {
+                self.translator.stmt(stmt)?;
+            }
+        }
+        self.declare_adjoints(&block.stmts)?;
+        self.reverse(&block.stmts)?;
+        self.translator.scopes.pop();
+        self.translator.indent -= 1;
+        Ok(())
+    }
+
+    fn reverse_expr(&mut self, expr: &Expr) -> Result<()> {
+        match expr {
+            Expr::If(expr_if) => {
+                let cond = self.glsl(&expr_if.cond)?;
+                self.line(&format!("if ({}) {{", cond));
+                self.reverse_block(&expr_if.then_branch, vec![])?;
+                match expr_if
+                    .else_branch
+                    .as_ref()
+                    .map(|(_, else_branch)| &**else_branch)
+                {
+                    Some(Expr::Block(else_block)) => {
+                        self.line("} else {");
+                        self.reverse_block(&else_block.block, vec![])?;
+                    }
+                    Some(else_if) => {
+                        self.line("} else {");
+                        self.translator.indent += 1;
+                        self.reverse_expr(else_if)?;
+                        self.translator.indent -= 1;
+                    }
+                    None => {}
+                }
+                self.line("}");
+            }
+            Expr::ForLoop(expr_for) => {
+                // once the variables of an iteration are recomputed, it doesn't depend on the others
+                // so the iterations can go in the same order as before
+                let var = self.translator.for_header(expr_for)?;
+                self.reverse_block(&expr_for.body, vec![var])?;
+                self.line("}");
+            }
+            Expr::Block(expr_block) => {
+                self.line("{");
+                self.reverse_block(&expr_block.block, vec![])?;
+                self.line("}");
+            }
+            Expr::Assign(expr_assign) => {
+                if let (Some(param), Expr::Index(target)) =
+                    (self.translator.written_slice(expr), &*expr_assign.left)
+                {
+                    let index = self.glsl(&target.index)?;
+                    let adj = self.temp(format!("{}_grad[{}]", param.ident, index));
+                    self.backprop(&expr_assign.right, &adj)?;
+                }
+            }
+            Expr::AssignOp(expr_assign_op) => {
+                let sign = if expr_assign_op.op.to_token_stream().to_string() == "-=" {
+                    "-"
+                } else {
+                    ""
+                };
+                let adj = match (self.translator.written_slice(expr), &*expr_assign_op.left) {
+                    (Some(param), Expr::Index(target)) => {
+                        let index = self.glsl(&target.index)?;
+                        self.temp(format!("{}{}_grad[{}]", sign, param.ident, index))
+                    }
+                    (_, left) if self.is_float(left)? => {
+                        // adding to a variable doesn't change how the gradient of its final value propagates to its previous value
+                        // so the gradient of each value added is the gradient of the final value
+                        let name = self.glsl(left)?;
+                        if sign.is_empty() {
+                            format!("emu_adj_{}", name)
+                        } else {
+                            self.temp(format!("-emu_adj_{}", name))
+                        }
+                    }
+                    _ => return Ok(()),
+                };
+                self.backprop(&expr_assign_op.right, &adj)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // propagates the given gradient of the given expression (the name of a variable) to what it is computed from
+    fn backprop(&mut self, expr: &Expr, adj: &str) -> Result<()> {
+        if !self.is_float(expr)? {
+            return Ok(());
+        }
+        match expr {
+            Expr::Lit(_) => {}
+            Expr::Path(_) => {
+                // this is either a variable or a scalar parameter
+                let name = self.glsl(expr)?;
+                self.line(&format!("emu_adj_{} += {};", name, adj));
+            }
+            Expr::Index(expr_index) => {
+                // this is an element of a slice that isn't written to
+                let name = expr_index.expr.to_token_stream().to_string();
+                let index = self.glsl(&expr_index.index)?;
+                self.line(&format!("emu_grad_{}(uint({}), {});", name, index, adj));
+            }
+            Expr::Paren(expr_paren) => self.backprop(&expr_paren.expr, adj)?,
+            Expr::Cast(expr_cast) => self.backprop(&expr_cast.expr, adj)?,
+            Expr::Unary(expr_unary) => {
+                if let UnOp::Neg(_) = expr_unary.op {
+                    let adj = self.temp(format!("-{}", adj));
+                    self.backprop(&expr_unary.expr, &adj)?;
+                }
+            }
+            Expr::Binary(expr_binary) => {
+                let (left, right) = (&*expr_binary.left, &*expr_binary.right);
+                let (left_glsl, right_glsl) = (self.glsl(left)?, self.glsl(right)?);
+                let (left_partial, right_partial) =
+                    match expr_binary.op.to_token_stream().to_string().as_str() {
+                        "+" => (adj.to_string(), adj.to_string()),
+                        "-" => (adj.to_string(), format!("-{}", adj)),
+                        "*" => (
+                            format!("{} * ({})", adj, right_glsl),
+                            format!("({}) * {}", left_glsl, adj),
+                        ),
+                        "/" => (
+                            format!("{} / ({})", adj, right_glsl),
+                            format!(
+                                "-{} * ({}) / (({}) * ({}))",
+                                adj, left_glsl, right_glsl, right_glsl
+                            ),
+                        ),
+                        op => {
+                            return Err(Error::new_spanned(
+                                expr_binary.op,
+                                format!("`{}` can't be differentiated", op),
+                            ))
+                        }
+                    };
+                self.backprop_partial(left, left_partial)?;
+                self.backprop_partial(right, right_partial)?;
+            }
+            Expr::Call(expr_call) => {
+                let func = expr_call.func.to_token_stream().to_string();
+                let args = expr_call.args.iter().collect::<Vec<_>>();
+                self.backprop_call(expr, &func, &args, adj)?;
+            }
+            Expr::MethodCall(expr_method_call) => {
+                let func = glsl_function(&expr_method_call.method.to_string()).unwrap();
+                let mut args = vec![&*expr_method_call.receiver];
+                args.extend(expr_method_call.args.iter());
+                self.backprop_call(expr, func, &args, adj)?;
+            }
+            expr => {
+                return Err(Error::new_spanned(
+                    expr,
+                    "this kind of expression can't be differentiated",
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    // propagates the given product of the gradient of an expression and its partial derivative with respect to the given operand
+    fn backprop_partial(&mut self, operand: &Expr, partial: String) -> Result<()> {
+        if let Expr::Lit(_) = operand {
+            return Ok(());
+        }
+        if self.is_float(operand)? {
+            // the partial derivative may just be 1 and then there's no need for a new variable
+            let adj = if partial.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                partial
+            } else {
+                self.temp(partial)
+            };
+            self.backprop(operand, &adj)?;
+        }
+        Ok(())
+    }
+
+    fn backprop_call(&mut self, call: &Expr, func: &str, args: &[&Expr], adj: &str) -> Result<()> {
+        let glsl = args
+            .iter()
+            .map(|arg| self.glsl(arg).map(|arg| format!("({})", arg)))
+            .collect::<Result<Vec<_>>>()?;
+        let g = adj;
+        // the products of the gradient and the partial derivatives with respect to each argument
+        let partials = match (func, glsl.as_slice()) {
+            ("sqrt", [x]) => vec![format!("{} * 0.5 / sqrt{}", g, x)],
+            ("exp", [x]) => vec![format!("{} * exp{}", g, x)],
+            ("exp2", [x]) => vec![format!("{} * exp2{} * 0.6931472", g, x)],
+            ("log", [x]) => vec![format!("{} / {}", g, x)],
+            ("log2", [x]) => vec![format!("{} / ({} * 0.6931472)", g, x)],
+            ("pow", [x, y]) => vec![
+                format!("{} * {} * pow({}, {} - 1.0)", g, y, x, y),
+                format!("{} * pow({}, {}) * log{}", g, x, y, x),
+            ],
+            ("sin", [x]) => vec![format!("{} * cos{}", g, x)],
+            ("cos", [x]) => vec![format!("-{} * sin{}", g, x)],
+            ("tan", [x]) => vec![format!("{} / (cos{} * cos{})", g, x, x)],
+            ("asin", [x]) => vec![format!("{} / sqrt(1.0 - {} * {})", g, x, x)],
+            ("acos", [x]) => vec![format!("-{} / sqrt(1.0 - {} * {})", g, x, x)],
+            ("atan", [x]) => vec![format!("{} / (1.0 + {} * {})", g, x, x)],
+            ("atan", [y, x]) => vec![
+                format!("{} * {} / ({} * {} + {} * {})", g, x, x, x, y, y),
+                format!("-{} * {} / ({} * {} + {} * {})", g, y, x, x, y, y),
+            ],
+            ("sinh", [x]) => vec![format!("{} * cosh{}", g, x)],
+            ("cosh", [x]) => vec![format!("{} * sinh{}", g, x)],
+            ("tanh", [x]) => vec![format!("{} * (1.0 - tanh{} * tanh{})", g, x, x)],
+            ("abs", [x]) => vec![format!("{} * sign{}", g, x)],
+            ("min", [x, y]) => vec![
+                format!("({} <= {} ? {} : 0.0)", x, y, g),
+                format!("({} <= {} ? 0.0 : {})", x, y, g),
+            ],
+            ("max", [x, y]) => vec![
+                format!("({} >= {} ? {} : 0.0)", x, y, g),
+                format!("({} >= {} ? 0.0 : {})", x, y, g),
+            ],
+            ("clamp", [x, low, high]) => vec![
+                format!("({} >= {} && {} <= {} ? {} : 0.0)", x, low, x, high, g),
+                format!("({} < {} ? {} : 0.0)", x, low, g),
+                format!("({} > {} ? {} : 0.0)", x, high, g),
+            ],
+            ("mix", [x, y, a]) => vec![
+                format!("{} * (1.0 - {})", g, a),
+                format!("{} * {}", g, a),
+                format!("{} * ({} - {})", g, y, x),
+            ],
+            ("fma", [a, b, _]) => vec![
+                format!("{} * {}", g, b),
+                format!("{} * {}", g, a),
+                g.to_string(),
+            ],
+            ("degrees", [_]) => vec![format!("{} * 57.29578", g)],
+            ("radians", [_]) => vec![format!("{} * 0.017453292", g)],
+            // these are piecewise constant
+            ("floor", _)
+            | ("ceil", _)
+            | ("round", _)
+            | ("trunc", _)
+            | ("sign", _)
+            | ("fract", _) => {
+                vec![]
+            }
+            _ => {
+                return Err(Error::new_spanned(
+                    call,
+                    format!("`{}` can't be differentiated", func),
+                ))
+            }
+        };
+        for (arg, partial) in args.iter().zip(partials) {
+            self.backprop_partial(arg, partial)?;
+        }
+        Ok(())
+    }
+}
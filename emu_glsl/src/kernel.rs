@@ -0,0 +1,829 @@
+// the translation of functions given to kernel! into GLSL and into Rust functions that compile and launch the GLSL with emu_core
+
+use std::collections::HashMap;
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::parse::{Parse, ParseStream};
+use syn::{
+    Block, Error, Expr, ExprForLoop, FnArg, Ident, ItemFn, Lit, Member, Pat, RangeLimits, Result,
+    ReturnType, Stmt, Type, UnOp, Visibility,
+};
+
+// the number of threads in each thread block of a kernel
+const BLOCK_SIZE: u32 = 64;
+
+// the functions given to kernel!
+pub(crate) struct Kernels(pub(crate) Vec<ItemFn>);
+
+impl Parse for Kernels {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut kernels = vec![];
+        while !input.is_empty() {
+            kernels.push(input.parse()?);
+        }
+        Ok(Kernels(kernels))
+    }
+}
+
+// how a parameter of a kernel is passed in
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum ParamKind {
+    Scalar,
+    Array,
+    ArrayMut,
+}
+
+// a parameter of a kernel
+pub(crate) struct Param {
+    pub(crate) ident: Ident,
+    pub(crate) kind: ParamKind,
+    pub(crate) ty: Type, // the Rust type of the scalar or of each element of the array
+    pub(crate) glsl_type: String, // the GLSL type of the scalar or of each element of the array
+}
+
+impl Param {
+    pub(crate) fn launch_param(&self) -> LaunchParam {
+        let (ident, ty) = (&self.ident, &self.ty);
+        let (rust_type, declaration) = match self.kind {
+            ParamKind::Scalar => {
+                let declaration = format!("{} {}", self.glsl_type, ident);
+                (quote! { #ty }, quote! { .param::<#ty, _>(#declaration) })
+            }
+            ParamKind::Array => {
+                let declaration = format!("{}[] {}", self.glsl_type, ident);
+                (
+                    quote! { &::emu_core::device::DeviceBox<[#ty]> },
+                    quote! { .param::<[#ty], _>(#declaration) },
+                )
+            }
+            ParamKind::ArrayMut => {
+                let declaration = format!("{}[] {}", self.glsl_type, ident);
+                (
+                    quote! { &mut ::emu_core::device::DeviceBox<[#ty]> },
+                    quote! { .param_mut::<[#ty], _>(#declaration) },
+                )
+            }
+        };
+        LaunchParam {
+            ident: ident.clone(),
+            rust_type,
+            declaration,
+        }
+    }
+}
+
+// returns the GLSL type of the given Rust type if it is a type that kernels can work with
+fn glsl_type(ty: &Type) -> Option<String> {
+    match ty.to_token_stream().to_string().as_str() {
+        "f32" => Some(String::from("float")),
+        "f64" => Some(String::from("double")),
+        "i32" => Some(String::from("int")),
+        "u32" => Some(String::from("uint")),
+        _ => None,
+    }
+}
+
+// the words that are keywords (or reserved) in GLSL but not in Rust
+const GLSL_KEYWORDS: &[&str] = &[
+    "attribute",
+    "uniform",
+    "varying",
+    "buffer",
+    "shared",
+    "coherent",
+    "volatile",
+    "restrict",
+    "readonly",
+    "writeonly",
+    "layout",
+    "centroid",
+    "flat",
+    "smooth",
+    "noperspective",
+    "patch",
+    "sample",
+    "subroutine",
+    "in",
+    "out",
+    "inout",
+    "float",
+    "double",
+    "int",
+    "uint",
+    "void",
+    "bool",
+    "invariant",
+    "precise",
+    "discard",
+    "lowp",
+    "mediump",
+    "highp",
+    "precision",
+    "switch",
+    "case",
+    "default",
+    "common",
+    "partition",
+    "active",
+    "asm",
+    "class",
+    "union",
+    "enum",
+    "typedef",
+    "template",
+    "this",
+    "resource",
+    "goto",
+    "inline",
+    "noinline",
+    "public",
+    "static",
+    "extern",
+    "external",
+    "interface",
+    "long",
+    "short",
+    "half",
+    "fixed",
+    "unsigned",
+    "superp",
+    "input",
+    "output",
+    "filter",
+    "sizeof",
+    "cast",
+    "namespace",
+    "using",
+];
+
+// returns the name that the given pattern binds if it is just a name that can be used in GLSL
+pub(crate) fn get_name<'a>(pat: &'a Pat, what: &str) -> Result<&'a Ident> {
+    let ident = match pat {
+        Pat::Ident(pat) if pat.by_ref.is_none() && pat.subpat.is_none() => &pat.ident,
+        pat => {
+            return Err(Error::new_spanned(
+                pat,
+                format!("expected the name of a {}", what),
+            ))
+        }
+    };
+    let name = ident.to_string();
+    // names starting with gl_ are GLSL's, names starting with emu_ are Emu's, and id is the global ID of the thread
+    if GLSL_KEYWORDS.contains(&name.as_str())
+        || name.starts_with("gl_")
+        || name.starts_with("emu_")
+        || name == "id"
+    {
+        return Err(Error::new_spanned(
+            ident,
+            format!(
+                "`{}` can't be used as the name of a {} in a kernel",
+                name, what
+            ),
+        ));
+    }
+    Ok(ident)
+}
+
+fn get_param(arg: &FnArg) -> Result<Param> {
+    let arg = match arg {
+        FnArg::Typed(arg) => arg,
+        FnArg::Receiver(receiver) => {
+            return Err(Error::new_spanned(receiver, "a kernel can't take `self`"))
+        }
+    };
+    let ident = get_name(&arg.pat, "parameter")?.clone();
+    let (kind, ty) = match &*arg.ty {
+        Type::Reference(reference) => match &*reference.elem {
+            Type::Slice(slice) => (
+                if reference.mutability.is_some() {
+                    ParamKind::ArrayMut
+                } else {
+                    ParamKind::Array
+                },
+                (*slice.elem).clone(),
+            ),
+            _ => {
+                return Err(Error::new_spanned(
+                    reference,
+                    "expected a scalar (like `f32`) or a slice of scalars (like `&[f32]` or `&mut [f32]`)",
+                ))
+            }
+        },
+        ty => (ParamKind::Scalar, ty.clone()),
+    };
+    let glsl_type = glsl_type(&ty).ok_or_else(|| {
+        Error::new_spanned(
+            &ty,
+            "expected one of `f32`, `f64`, `i32`, or `u32` as the type of a scalar or the elements of a slice",
+        )
+    })?;
+    Ok(Param {
+        ident,
+        kind,
+        ty,
+        glsl_type,
+    })
+}
+
+// generates a Rust function that compiles the given kernel to GLSL and launches it
+// if the kernel is marked with #[differentiable], a function that launches its adjoint is generated too
+pub(crate) fn expand(kernel: &ItemFn) -> Result<TokenStream> {
+    let sig = &kernel.sig;
+    if !sig.generics.params.is_empty() || sig.generics.where_clause.is_some() {
+        return Err(Error::new_spanned(
+            &sig.generics,
+            "a kernel can't be generic",
+        ));
+    }
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(Error::new_spanned(asyncness, "a kernel can't be async"));
+    }
+    if let ReturnType::Type(_, ty) = &sig.output {
+        return Err(Error::new_spanned(ty, "a kernel can't return anything"));
+    }
+    let params = sig
+        .inputs
+        .iter()
+        .map(get_param)
+        .collect::<Result<Vec<_>>>()?;
+    let len_param = params
+        .iter()
+        .find(|param| param.kind != ParamKind::Scalar)
+        .ok_or_else(|| {
+            Error::new_spanned(
+                &sig.ident,
+                "a kernel must have a slice parameter, 1 thread is launched for each of its elements",
+            )
+        })?;
+    let (differentiable, attrs): (Vec<_>, Vec<_>) = kernel
+        .attrs
+        .iter()
+        .partition(|attr| attr.path.is_ident("differentiable"));
+
+    // translate the body to GLSL
+    let mut translator = Translator::new(&params);
+    translator.guard();
+    translator.stmts(&kernel.block.stmts)?;
+    let f64 = translator.f64 || params.iter().any(|param| param.glsl_type == "double");
+    let with_f64 = if f64 {
+        quote! { .with_f64() }
+    } else {
+        quote! {}
+    };
+
+    let launch_params = params.iter().map(Param::launch_param).collect::<Vec<_>>();
+    let mut expanded = launcher(
+        quote! { #(#attrs)* },
+        &kernel.vis,
+        &sig.ident,
+        &launch_params,
+        &len_param.ident,
+        with_f64,
+        &translator.glsl,
+    );
+    if !differentiable.is_empty() {
+        expanded.extend(crate::grad::expand(kernel, &params)?);
+    }
+    Ok(expanded)
+}
+
+// a parameter of a generated Rust function and of the kernel it launches
+pub(crate) struct LaunchParam {
+    pub(crate) ident: Ident,
+    pub(crate) rust_type: TokenStream, // the type of the parameter of the Rust function
+    pub(crate) declaration: TokenStream, // the call to a method of GlslKernel that declares the parameter in GLSL
+}
+
+// generates a Rust function that compiles the given kernel code and launches it with 1 thread for each element of the slice with the given name
+// the builder calls are made on the GlslKernel after its parameters are declared
+pub(crate) fn launcher(
+    attrs: TokenStream,
+    vis: &Visibility,
+    name: &Ident,
+    params: &[LaunchParam],
+    len_ident: &Ident,
+    builder_calls: TokenStream,
+    kernel_code: &str,
+) -> TokenStream {
+    let idents = params.iter().map(|param| &param.ident).collect::<Vec<_>>();
+    let rust_types = params.iter().map(|param| &param.rust_type);
+    let declarations = params.iter().map(|param| &param.declaration);
+
+    quote! {
+        #attrs
+        #vis fn #name(#(#idents: #rust_types),*) -> ::std::result::Result<(), ::emu_core::error::KernelError> {
+            let emu_len = #len_ident.len();
+            if emu_len == 0 {
+                return Ok(());
+            }
+
+            let emu_kernel = ::emu_core::compile::compile::<
+                ::emu_core::compile_impls::GlslKernel,
+                ::emu_core::compile_impls::GlslKernelCompile,
+                _,
+                ::emu_core::cache::GlobalCache,
+            >(
+                ::emu_core::compile_impls::GlslKernel::new()
+                    .spawn(#BLOCK_SIZE)
+                    #(#declarations)*
+                    .param::<u32, _>("uint emu_len")
+                    #builder_calls
+                    .with_kernel_code(#kernel_code),
+            )?
+            .finish()?;
+
+            let emu_num_blocks = (emu_len as u32 + #BLOCK_SIZE - 1) / #BLOCK_SIZE;
+            unsafe {
+                ::emu_core::spawn::spawn(emu_num_blocks).launch((
+                    emu_kernel,
+                    ::emu_core::device::ArgsBuilder::new()
+                        #(.arg(#idents))*
+                        .arg(emu_len as u32)
+                        .build(),
+                ))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+// translates the body of a kernel to GLSL
+pub(crate) struct Translator<'a> {
+    pub(crate) params: &'a [Param],
+    pub(crate) scopes: Vec<HashMap<String, String>>, // the GLSL type of each local variable in each scope
+    pub(crate) glsl: String,
+    pub(crate) indent: usize,
+    pub(crate) f64: bool,         // whether or not the body uses doubles
+    pub(crate) skip_writes: bool, // whether or not assignments to elements of mutable slices are left out
+}
+
+impl<'a> Translator<'a> {
+    pub(crate) fn new(params: &'a [Param]) -> Self {
+        Self {
+            params,
+            scopes: vec![HashMap::new()],
+            glsl: String::new(),
+            indent: 0,
+            f64: false,
+            skip_writes: false,
+        }
+    }
+
+    // returns right away from the threads past the end of the first slice
+    pub(crate) fn guard(&mut self) {
+        self.line("if (gl_GlobalInvocationID.x >= emu_len) {");
+        self.line("    return;");
+        self.line("}");
+    }
+
+    pub(crate) fn line(&mut self, line: &str) {
+        self.glsl += &"    ".repeat(self.indent);
+        self.glsl += line;
+        self.glsl += "\n";
+    }
+
+    pub(crate) fn stmts(&mut self, stmts: &[Stmt]) -> Result<()> {
+        for stmt in stmts {
+            self.stmt(stmt)?;
+        }
+        Ok(())
+    }
+
+    // translates the statements of a block, which must already be opened with a {
+    pub(crate) fn block(&mut self, block: &Block, vars: Vec<(String, String)>) -> Result<()> {
+        self.indent += 1;
+        self.scopes.push(vars.into_iter().collect());
+        self.stmts(&block.stmts)?;
+        self.scopes.pop();
+        self.indent -= 1;
+        Ok(())
+    }
+
+    pub(crate) fn stmt(&mut self, stmt: &Stmt) -> Result<()> {
+        match stmt {
+            Stmt::Local(local) => {
+                let (pat, ty) = match &local.pat {
+                    Pat::Type(pat_type) => (&*pat_type.pat, Some(&*pat_type.ty)),
+                    pat => (pat, None),
+                };
+                let ident = get_name(pat, "variable")?;
+                let init = match &local.init {
+                    Some((_, init)) => init,
+                    None => {
+                        return Err(Error::new_spanned(
+                            local,
+                            "a variable in a kernel must be given a value when it is declared",
+                        ))
+                    }
+                };
+                let (value, value_type) = self.expr(init)?;
+                let glsl_type = match ty {
+                    // unlike parameters, variables can be bools
+                    Some(Type::Path(path)) if path.path.is_ident("bool") => String::from("bool"),
+                    Some(ty) => glsl_type(ty).ok_or_else(|| {
+                        Error::new_spanned(
+                            ty,
+                            "expected one of `f32`, `f64`, `i32`, `u32`, or `bool`",
+                        )
+                    })?,
+                    None => value_type.ok_or_else(|| {
+                        Error::new_spanned(
+                            init,
+                            format!("can't infer the type of `{}`, add a type annotation", ident),
+                        )
+                    })?,
+                };
+                if glsl_type == "double" {
+                    self.f64 = true;
+                }
+                self.line(&format!("{} {} = {};", glsl_type, ident, value));
+                self.scopes
+                    .last_mut()
+                    .unwrap()
+                    .insert(ident.to_string(), glsl_type);
+                Ok(())
+            }
+            Stmt::Expr(expr) | Stmt::Semi(expr, _) => self.stmt_expr(expr),
+            Stmt::Item(item) => Err(Error::new_spanned(
+                item,
+                "items can't be declared in a kernel",
+            )),
+        }
+    }
+
+    pub(crate) fn stmt_expr(&mut self, expr: &Expr) -> Result<()> {
+        match expr {
+            Expr::If(expr_if) => {
+                let (cond, _) = self.expr(&expr_if.cond)?;
+                self.line(&format!("if ({}) {{", cond));
+                self.block(&expr_if.then_branch, vec![])?;
+                match expr_if
+                    .else_branch
+                    .as_ref()
+                    .map(|(_, else_branch)| &**else_branch)
+                {
+                    Some(Expr::Block(else_block)) => {
+                        self.line("} else {");
+                        self.block(&else_block.block, vec![])?;
+                    }
+                    Some(else_if) => {
+                        self.line("} else {");
+                        self.indent += 1;
+                        self.stmt_expr(else_if)?;
+                        self.indent -= 1;
+                    }
+                    None => {}
+                }
+                self.line("}");
+            }
+            Expr::While(expr_while) if expr_while.label.is_none() => {
+                let (cond, _) = self.expr(&expr_while.cond)?;
+                self.line(&format!("while ({}) {{", cond));
+                self.block(&expr_while.body, vec![])?;
+                self.line("}");
+            }
+            Expr::Loop(expr_loop) if expr_loop.label.is_none() => {
+                self.line("while (true) {");
+                self.block(&expr_loop.body, vec![])?;
+                self.line("}");
+            }
+            Expr::ForLoop(expr_for) if expr_for.label.is_none() => {
+                let var = self.for_header(expr_for)?;
+                self.block(&expr_for.body, vec![var])?;
+                self.line("}");
+            }
+            Expr::Block(expr_block) if expr_block.label.is_none() => {
+                self.line("{");
+                self.block(&expr_block.block, vec![])?;
+                self.line("}");
+            }
+            Expr::Break(expr_break) if expr_break.label.is_none() && expr_break.expr.is_none() => {
+                self.line("break;")
+            }
+            Expr::Continue(expr_continue) if expr_continue.label.is_none() => {
+                self.line("continue;")
+            }
+            Expr::Return(expr_return) if expr_return.expr.is_none() => self.line("return;"),
+            expr if self.skip_writes && self.written_slice(expr).is_some() => {}
+            expr => {
+                let (glsl, _) = self.expr(expr)?;
+                self.line(&format!("{};", glsl));
+            }
+        }
+        Ok(())
+    }
+
+    // opens a GLSL for loop for the given Rust for loop, returning the name and type of the variable it declares
+    pub(crate) fn for_header(&mut self, expr_for: &ExprForLoop) -> Result<(String, String)> {
+        let ident = get_name(&expr_for.pat, "variable")?;
+        let range = match &*expr_for.expr {
+            Expr::Range(range) if range.from.is_some() && range.to.is_some() => range,
+            expr => {
+                return Err(Error::new_spanned(
+                    expr,
+                    "a kernel can only loop over a range like `0..n`",
+                ))
+            }
+        };
+        let (from, from_type) = self.expr(range.from.as_ref().unwrap())?;
+        let (to, to_type) = self.expr(range.to.as_ref().unwrap())?;
+        let glsl_type = to_type.or(from_type).unwrap_or_else(|| String::from("int"));
+        let comparison = match range.limits {
+            RangeLimits::HalfOpen(_) => "<",
+            RangeLimits::Closed(_) => "<=",
+        };
+        self.line(&format!(
+            "for ({ty} {i} = {from}; {i} {cmp} {to}; {i}++) {{",
+            ty = glsl_type,
+            i = ident,
+            from = from,
+            cmp = comparison,
+            to = to
+        ));
+        Ok((ident.to_string(), glsl_type))
+    }
+
+    // returns the mutable slice that the given expression assigns to an element of (if it does)
+    pub(crate) fn written_slice(&self, expr: &Expr) -> Option<&'a Param> {
+        let target = match expr {
+            Expr::Assign(expr_assign) => &*expr_assign.left,
+            Expr::AssignOp(expr_assign_op) => &*expr_assign_op.left,
+            _ => return None,
+        };
+        match target {
+            Expr::Index(expr_index) => match &*expr_index.expr {
+                Expr::Path(expr_path) => expr_path
+                    .path
+                    .get_ident()
+                    .filter(|ident| self.var_type(&ident.to_string()).is_none())
+                    .and_then(|ident| self.param(&ident.to_string()))
+                    .filter(|param| param.kind == ParamKind::ArrayMut),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub(crate) fn var_type(&self, name: &str) -> Option<&String> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    pub(crate) fn param(&self, name: &str) -> Option<&'a Param> {
+        self.params.iter().find(|param| param.ident == name)
+    }
+
+    // translates an expression to GLSL, returning the GLSL and its type (if it is known)
+    pub(crate) fn expr(&mut self, expr: &Expr) -> Result<(String, Option<String>)> {
+        Ok(match expr {
+            Expr::Lit(expr_lit) => match &expr_lit.lit {
+                Lit::Int(int) => match int.suffix() {
+                    "" | "i32" => (int.base10_digits().to_string(), Some(String::from("int"))),
+                    "u32" => (
+                        format!("{}u", int.base10_digits()),
+                        Some(String::from("uint")),
+                    ),
+                    "f32" => (
+                        format!("{}.0", int.base10_digits()),
+                        Some(String::from("float")),
+                    ),
+                    "f64" => {
+                        self.f64 = true;
+                        (
+                            format!("{}.0lf", int.base10_digits()),
+                            Some(String::from("double")),
+                        )
+                    }
+                    _ => return Err(Error::new_spanned(int, "unsupported type of literal")),
+                },
+                Lit::Float(float) => match float.suffix() {
+                    "" | "f32" => (
+                        float.base10_digits().to_string(),
+                        Some(String::from("float")),
+                    ),
+                    "f64" => {
+                        self.f64 = true;
+                        (
+                            format!("{}lf", float.base10_digits()),
+                            Some(String::from("double")),
+                        )
+                    }
+                    _ => return Err(Error::new_spanned(float, "unsupported type of literal")),
+                },
+                Lit::Bool(b) => (b.value.to_string(), Some(String::from("bool"))),
+                lit => return Err(Error::new_spanned(lit, "unsupported literal in a kernel")),
+            },
+            Expr::Path(expr_path)
+                if expr_path.qself.is_none() && expr_path.path.get_ident().is_some() =>
+            {
+                let ident = expr_path.path.get_ident().unwrap();
+                let name = ident.to_string();
+                if let Some(var_type) = self.var_type(&name) {
+                    (name, Some(var_type.clone()))
+                } else if let Some(param) = self.param(&name) {
+                    if param.kind != ParamKind::Scalar {
+                        return Err(Error::new_spanned(
+                            ident,
+                            format!("`{}` is a slice and can only be indexed", name),
+                        ));
+                    }
+                    (name, Some(param.glsl_type.clone()))
+                } else if name == "id" {
+                    (
+                        String::from("gl_GlobalInvocationID"),
+                        Some(String::from("uvec3")),
+                    )
+                } else {
+                    return Err(Error::new_spanned(
+                        ident,
+                        format!("cannot find `{}` in this kernel", name),
+                    ));
+                }
+            }
+            Expr::Field(expr_field) => {
+                let (base, base_type) = self.expr(&expr_field.base)?;
+                let component_type = base_type.as_deref().and_then(component_type);
+                match (&expr_field.member, component_type) {
+                    (Member::Named(member), Some(component_type))
+                        if ["x", "y", "z", "w"].contains(&member.to_string().as_str()) =>
+                    {
+                        (format!("{}.{}", base, member), Some(component_type))
+                    }
+                    (member, _) => {
+                        return Err(Error::new_spanned(
+                            member,
+                            "only the `x`, `y`, `z`, and `w` components of a vector (like `id`) can be accessed",
+                        ))
+                    }
+                }
+            }
+            Expr::Index(expr_index) => {
+                let param = match &*expr_index.expr {
+                    Expr::Path(expr_path) => expr_path
+                        .path
+                        .get_ident()
+                        .filter(|ident| self.var_type(&ident.to_string()).is_none())
+                        .and_then(|ident| self.param(&ident.to_string()))
+                        .filter(|param| param.kind != ParamKind::Scalar),
+                    _ => None,
+                };
+                let param = param.ok_or_else(|| {
+                    Error::new_spanned(&expr_index.expr, "only slice parameters can be indexed")
+                })?;
+                let (index, _) = self.expr(&expr_index.index)?;
+                (
+                    format!("{}[{}]", param.ident, index),
+                    Some(param.glsl_type.clone()),
+                )
+            }
+            Expr::Binary(expr_binary) => {
+                let (left, left_type) = self.expr(&expr_binary.left)?;
+                let (right, right_type) = self.expr(&expr_binary.right)?;
+                let mut op = expr_binary.op.to_token_stream().to_string();
+                let glsl_type = match op.as_str() {
+                    "==" | "!=" | "<" | "<=" | ">" | ">=" | "&&" | "||" => {
+                        Some(String::from("bool"))
+                    }
+                    _ => left_type.or(right_type),
+                };
+                // ^ of bools is a logical operator in Rust but not in GLSL
+                if op == "^" && glsl_type.as_deref() == Some("bool") {
+                    op = String::from("^^");
+                }
+                (format!("{} {} {}", left, op, right), glsl_type)
+            }
+            Expr::AssignOp(expr_assign_op) => {
+                let (left, left_type) = self.expr(&expr_assign_op.left)?;
+                let (right, _) = self.expr(&expr_assign_op.right)?;
+                let op = expr_assign_op.op.to_token_stream().to_string();
+                (format!("{} {} {}", left, op, right), left_type)
+            }
+            Expr::Assign(expr_assign) => {
+                let (left, left_type) = self.expr(&expr_assign.left)?;
+                let (right, _) = self.expr(&expr_assign.right)?;
+                (format!("{} = {}", left, right), left_type)
+            }
+            Expr::Unary(expr_unary) => {
+                let (operand, operand_type) = self.expr(&expr_unary.expr)?;
+                let op = match expr_unary.op {
+                    UnOp::Neg(_) => "-",
+                    // ! of integers is a bitwise operator in Rust but not in GLSL
+                    UnOp::Not(_) if operand_type.as_deref() == Some("bool") => "!",
+                    UnOp::Not(_) => "~",
+                    UnOp::Deref(_) => {
+                        return Err(Error::new_spanned(expr_unary, "a kernel can't dereference"))
+                    }
+                };
+                (format!("{}{}", op, operand), operand_type)
+            }
+            Expr::Paren(expr_paren) => {
+                let (inner, inner_type) = self.expr(&expr_paren.expr)?;
+                (format!("({})", inner), inner_type)
+            }
+            Expr::Cast(expr_cast) => {
+                let (inner, _) = self.expr(&expr_cast.expr)?;
+                let glsl_type = glsl_type(&expr_cast.ty).ok_or_else(|| {
+                    Error::new_spanned(
+                        &expr_cast.ty,
+                        "expected one of `f32`, `f64`, `i32`, or `u32`",
+                    )
+                })?;
+                if glsl_type == "double" {
+                    self.f64 = true;
+                }
+                (format!("{}({})", glsl_type, inner), Some(glsl_type))
+            }
+            Expr::Call(expr_call) => {
+                let func = match &*expr_call.func {
+                    Expr::Path(expr_path) if expr_path.path.get_ident().is_some() => {
+                        expr_path.path.get_ident().unwrap().to_string()
+                    }
+                    func => {
+                        return Err(Error::new_spanned(
+                            func,
+                            "a kernel can only call GLSL's built-in functions (like `sqrt`)",
+                        ))
+                    }
+                };
+                let mut args = vec![];
+                let mut glsl_type = None;
+                for arg in &expr_call.args {
+                    let (arg, arg_type) = self.expr(arg)?;
+                    args.push(arg);
+                    glsl_type = glsl_type.or(arg_type);
+                }
+                (format!("{}({})", func, args.join(", ")), glsl_type)
+            }
+            Expr::MethodCall(expr_method_call) => {
+                let method = expr_method_call.method.to_string();
+                let func = glsl_function(&method).ok_or_else(|| {
+                    Error::new_spanned(
+                        &expr_method_call.method,
+                        format!("`{}` can't be called in a kernel", method),
+                    )
+                })?;
+                let (receiver, receiver_type) = self.expr(&expr_method_call.receiver)?;
+                let mut args = vec![receiver];
+                for arg in &expr_method_call.args {
+                    args.push(self.expr(arg)?.0);
+                }
+                (format!("{}({})", func, args.join(", ")), receiver_type)
+            }
+            expr => {
+                return Err(Error::new_spanned(
+                    expr,
+                    "this kind of expression isn't supported in a kernel",
+                ))
+            }
+        })
+    }
+}
+
+// returns the GLSL type of each component of a vector of the given GLSL type
+fn component_type(vector_type: &str) -> Option<String> {
+    let prefix = vector_type.strip_suffix(|c| c == '2' || c == '3' || c == '4')?;
+    Some(String::from(match prefix {
+        "vec" => "float",
+        "dvec" => "double",
+        "ivec" => "int",
+        "uvec" => "uint",
+        "bvec" => "bool",
+        _ => return None,
+    }))
+}
+
+// returns the GLSL function for the given method of f32, f64, i32, or u32
+pub(crate) fn glsl_function(method: &str) -> Option<&'static str> {
+    Some(match method {
+        "abs" => "abs",
+        "sqrt" => "sqrt",
+        "exp" => "exp",
+        "exp2" => "exp2",
+        "ln" => "log",
+        "log2" => "log2",
+        "powf" => "pow",
+        "sin" => "sin",
+        "cos" => "cos",
+        "tan" => "tan",
+        "asin" => "asin",
+        "acos" => "acos",
+        "atan" => "atan",
+        "atan2" => "atan",
+        "sinh" => "sinh",
+        "cosh" => "cosh",
+        "tanh" => "tanh",
+        "floor" => "floor",
+        "ceil" => "ceil",
+        "round" => "round",
+        "trunc" => "trunc",
+        "signum" => "sign",
+        "min" => "min",
+        "max" => "max",
+        "clamp" => "clamp",
+        "mul_add" => "fma",
+        "to_degrees" => "degrees",
+        "to_radians" => "radians",
+        _ => return None,
+    })
+}
@@ -1,10 +1,13 @@
-//! `emu_glsl` is a crate for GLSL-Rust interop. Currently, it only provides
+//! `emu_glsl` is a crate for GLSL-Rust interop. Currently, it mainly provides
 //! a single derive macro - `glsl_struct`. This macro derives a trait that
 //! is defined in the `emu_core` crate - `GlslStruct`. This is what the trait
 //! looks like.
 //! ```
+//! # pub struct GlslField;
 //! pub trait GlslStruct {
 //!     fn as_glsl() -> String; // return the GLSL struct definition of Self
+//!     fn glsl_name() -> String; // return the name of Self in GLSL
+//!     fn glsl_fields() -> Vec<GlslField>; // return the name, GLSL type, offset, and size of each field of Self
 //! }
 //! ```
 //! `emu_glsl` lets you derive this trait for simple structures where each
@@ -28,11 +31,19 @@
 //!     conv: bool, // make sure polygons in same thread block have same convexity
 //! }
 //! ```
+//!
+//! It also provides an `#[emu_test]` attribute for tests that run kernels. See
+//! [`emu_test`](attr.emu_test.html) for more. And it provides a `kernel!` macro for
+//! writing kernels in a subset of Rust instead of GLSL (and for generating their adjoints for computing
+//! gradients). See [`kernel`](macro.kernel.html) for more.
 
 extern crate proc_macro;
 use proc_macro::TokenStream;
 use quote::{quote, ToTokens};
-use syn::{parse_macro_input, Data, DeriveInput, Fields, Type};
+use syn::{parse_macro_input, Data, DeriveInput, Fields, ItemFn, Type};
+
+mod grad;
+mod kernel;
 
 fn rust_to_glsl(rust: String) -> String {
     String::from(match rust.as_ref() {
@@ -53,6 +64,11 @@ pub fn glsl_struct(input: TokenStream) -> TokenStream {
 
     // generate GLSL code
     let mut glsl = String::from("struct ");
+    // the name, GLSL type, and Rust type of each field
+    let mut field_names = vec![];
+    let mut field_glsl_types = vec![];
+    let mut field_idents = vec![];
+    let mut field_types = vec![];
     glsl += &name.to_string();
     glsl += " {";
     if let Data::Struct(struct_data) = input.data {
@@ -60,7 +76,7 @@ pub fn glsl_struct(input: TokenStream) -> TokenStream {
             // generate code for each field
             for field in named_fields.named.iter() {
                 // generate code for the field's type
-                glsl += &(match &field.ty {
+                let glsl_type = match &field.ty {
                     // TODO add support for more features
                     Type::Path(type_path) => {
                         rust_to_glsl(type_path.path.get_ident().unwrap().to_string())
@@ -83,14 +99,16 @@ pub fn glsl_struct(input: TokenStream) -> TokenStream {
                         }
                     }
                     _ => rust_to_glsl(field.ty.to_token_stream().to_string()),
-                });
+                };
+                let ident = field.ident.as_ref().expect("field must have an identifier");
+                glsl += &glsl_type;
                 glsl += " ";
-                glsl += &field
-                    .ident
-                    .as_ref()
-                    .expect("field must have an identifier")
-                    .to_string();
-                glsl += "; "
+                glsl += &ident.to_string();
+                glsl += "; ";
+                field_names.push(ident.to_string());
+                field_glsl_types.push(glsl_type);
+                field_idents.push(ident.clone());
+                field_types.push(field.ty.clone());
             }
         } else {
             panic!("expected a struct with named fields");
@@ -101,14 +119,146 @@ pub fn glsl_struct(input: TokenStream) -> TokenStream {
     glsl += " };";
 
     // create Rust code for implementation with GLSL code embedded
+    let name_literal = name.to_string();
     let expanded = quote! {
         impl GlslStruct for #name {
             fn as_glsl() -> String {
                 String::from(#glsl)
             }
+
+            fn glsl_name() -> String {
+                String::from(#name_literal)
+            }
+
+            fn glsl_fields() -> Vec<GlslField> {
+                let uninit = ::core::mem::MaybeUninit::<#name>::uninit();
+                let base = uninit.as_ptr() as usize;
+                vec![#(GlslField {
+                    name: String::from(#field_names),
+                    glsl_type: String::from(#field_glsl_types),
+                    offset: unsafe { ::core::ptr::addr_of!((*uninit.as_ptr()).#field_idents) } as usize - base,
+                    size: ::core::mem::size_of::<#field_types>(),
+                }),*]
+            }
         }
     };
 
     // return Rust code as TokenStream
     TokenStream::from(expanded)
 }
+
+/// Marks a function as a test that runs kernels
+///
+/// This is just like `#[test]` except that the body of the test is run with `emu_core::testing::run`. That initializes the pool of devices
+/// with a software device (like lavapipe or SwiftShader) when one is available so the test can run in CI environments without a GPU.
+/// The test may return anything a normal test may return, including a `Result`.
+/// ```rust,ignore
+/// #[emu_test]
+/// fn data_is_uploaded() -> Result<(), Box<dyn std::error::Error>> {
+///     let data: DeviceBox<[f32]> = vec![1.0; 64].as_device_boxed()?;
+///     assert_eq!(futures::executor::block_on(data.get())?, vec![1.0; 64].into_boxed_slice());
+///     Ok(())
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn emu_test(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = parse_macro_input!(item as ItemFn);
+    if sig.inputs.len() > 0 {
+        panic!("expected a test function with no parameters");
+    }
+    if sig.asyncness.is_some() {
+        panic!("expected a test function that is not async");
+    }
+
+    // wrap the body so that it runs with the pool of devices for tests
+    let expanded = quote! {
+        #[test]
+        #(#attrs)*
+        #vis #sig {
+            ::emu_core::testing::run(|| #block)
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+/// Turns functions written in a subset of Rust into functions that launch them as kernels
+///
+/// Each function is translated to the kernel code of a `GlslKernel` and replaced with a
+/// function of the same name that compiles it (through the `GlobalCache`) and launches it on the device currently selected from the pool. So
+/// `emu_core` must be a dependency with the `glsl-compile` or `glsl-compile-naga` feature enabled. The generated function has the following
+/// parameters.
+/// - `&[T]` becomes `&DeviceBox<[T]>`
+/// - `&mut [T]` becomes `&mut DeviceBox<[T]>`
+/// - `T` stays `T`
+///
+/// `T` may be `f32`, `f64`, `i32`, or `u32`. 1 thread is launched for each element of the first slice and `id` is the `uvec3` global ID of the
+/// thread (so `id.x` is the index of its element). The generated function returns a `Result<(), KernelError>`.
+///
+/// The body may declare variables with `let` (their types are inferred from their values when they aren't given), assign to variables and
+/// elements of slices, and use `if`, `while`, `loop`, and `for` over a range. Expressions may use literals, arithmetic, comparisons, casts with
+/// `as`, GLSL's built-in functions (like `sqrt(x)` or `max(x, y)`), and the methods of `f32` that GLSL has an equivalent for (like `x.sqrt()`
+/// or `x.mul_add(a, b)`). Anything else is a compile error that points at what isn't supported. Slices aren't bounds-checked.
+/// ```rust,ignore
+/// kernel! {
+///     /// Multiplies each element of `data` by `k`
+///     pub fn scale(data: &mut [f32], k: f32) {
+///         data[id.x] *= k;
+///     }
+///
+///     pub fn saxpy(y: &mut [f32], x: &[f32], a: f32) {
+///         let i = id.x;
+///         y[i] = a.mul_add(x[i], y[i]);
+///     }
+/// }
+///
+/// let mut y: DeviceBox<[f32]> = vec![1.0; 1024].as_device_boxed_mut()?;
+/// let x: DeviceBox<[f32]> = vec![2.0; 1024].as_device_boxed()?;
+/// scale(&mut y, 3.0)?;
+/// saxpy(&mut y, &x, 0.5)?;
+/// assert_eq!(futures::executor::block_on(y.get())?, vec![4.0; 1024].into_boxed_slice());
+/// ```
+///
+/// A function marked with `#[differentiable]` also gets a function for launching its adjoint, named like it but ending with `_grad`. The
+/// adjoint takes the gradient of a loss with respect to each `&mut [f32]` and adds the gradient of the loss with respect to each `f32` input
+/// to a buffer. The parameters are in the same order as before but each `&mut [f32]` named `y` is replaced with `y_grad: &DeviceBox<[f32]>`,
+/// each `&[f32]` named `x` is followed by `x_grad: &mut DeviceBox<[f32]>`, and each `f32` named `a` is followed by `a_grad: &mut DeviceBox<f32>`.
+/// Gradients are added to what's already in the buffers (with atomic operations since many threads may read the same input), so you need to
+/// zero them before the first launch. A differentiable kernel can't use `f64`, `while`, `loop`, `break`, `continue`, or `return` and it can't
+/// read from a slice that it writes to. Each element of a slice it writes to should be written with `=` at most once (but it may be added to with
+/// `+=` and `-=` any number of times). Its mutable variables must be declared outside of any `if` or `for` and may only be updated with `+=` and
+/// `-=`. Since only their final values are known when going backwards, they can't be read until after the last statement that updates them.
+/// This is enough for elementwise operations and for reductions that loop over a range.
+/// ```rust,ignore
+/// kernel! {
+///     #[differentiable]
+///     pub fn dense(y: &mut [f32], w: &[f32], x: &[f32], n: u32) {
+///         let mut sum = 0.0;
+///         for j in 0..n {
+///             sum += w[id.x * n + j] * x[j];
+///         }
+///         y[id.x] = sum.tanh();
+///     }
+/// }
+///
+/// // the gradient of sum(y) with respect to w and x
+/// let y_grad: DeviceBox<[f32]> = vec![1.0; 16].as_device_boxed()?;
+/// let mut w_grad: DeviceBox<[f32]> = vec![0.0; 16 * 32].as_device_boxed_mut()?;
+/// let mut x_grad: DeviceBox<[f32]> = vec![0.0; 32].as_device_boxed_mut()?;
+/// dense_grad(&y_grad, &w, &mut w_grad, &x, &mut x_grad, 32)?;
+/// ```
+#[proc_macro]
+pub fn kernel(input: TokenStream) -> TokenStream {
+    let kernels = parse_macro_input!(input as kernel::Kernels);
+    let expanded = kernels
+        .0
+        .iter()
+        .map(|kernel| kernel::expand(kernel).unwrap_or_else(|error| error.to_compile_error()));
+
+    TokenStream::from(quote! { #(#expanded)* })
+}
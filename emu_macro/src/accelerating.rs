@@ -11,12 +11,18 @@ use syn::fold::Fold;
 use syn::spanned::Spanned;
 use syn::visit::Visit;
 use syn::*;
-use proc_macro2::Span;
+use proc_macro2::{Literal, Span};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 
 // for etc.use crate::generator::Generator;
 use crate::generator::Generator;
+use crate::identifier::desugar_enumerate;
 use crate::identifier::get_global_work_size;
 use crate::identifier::Dim;
+use crate::identifier::Size;
+use crate::passing::gpu_ident;
 
 // there is passing
 // then there is accelerating
@@ -27,6 +33,12 @@ use crate::identifier::Dim;
 pub struct Accelerator {
     pub ready_to_launch: bool, // whether or not we are yet ready to launch
     pub errors: Vec<Error>,    // errors that we collect through accelerating
+    pub declared_lens: HashMap<String, Expr>, // lengths of data declared with gpu_do!(assert_len(data, n))
+    pub device_fns: HashMap<String, ItemFn>, // functions defined in the tagged function that launched code can call
+    pub device_consts: HashMap<String, ItemConst>, // constants defined in the tagged function that launched code can use
+    pub notes: Vec<String>, // what was done with the GPU, in order, for #[gpu_use(explain)]
+    pub elements_per_thread: u32, // how many elements each work-item of the next launch goes over, from gpu_do!(launch(elements_per_thread = n))
+    pub unroll: u32, // how many of those elements are unrolled into each iteration, from gpu_do!(launch(unroll = n))
 }
 
 impl Accelerator {
@@ -34,6 +46,77 @@ impl Accelerator {
         Self {
             ready_to_launch: false,
             errors: vec![],
+            declared_lens: HashMap::new(),
+            device_fns: HashMap::new(),
+            device_consts: HashMap::new(),
+            notes: vec![],
+            elements_per_thread: 1,
+            unroll: 1,
+        }
+    }
+
+    // generates a check that the given data has the length declared for it with assert_len, if any
+    fn len_check(&self, data: &str) -> proc_macro2::TokenStream {
+        if let Some(len) = self.declared_lens.get(data) {
+            let ident = Ident::new(data, Span::call_site());
+            quote! {
+                if (#ident).as_slice().len() != (#len) as usize {
+                    panic!("`{}` has length {} but was declared to have length {}", #data, (#ident).as_slice().len(), (#len) as usize)
+                }
+            }
+        } else {
+            quote! {}
+        }
+    }
+}
+
+// describes a for loop by its header (like "for i in 0 .. 1000") for notes
+fn describe_for_loop(for_loop: &ExprForLoop) -> String {
+    let pat = &for_loop.pat;
+    let expr = &for_loop.expr;
+    quote! { for #pat in #expr }.to_string()
+}
+
+// describes the size of a dimension of a launch for notes
+fn describe_size(dim: &Dim) -> String {
+    match dim {
+        Dim::RangeFromZero(_var, Size::Literal(size)) => size.to_string(),
+        Dim::RangeFromZero(_var, Size::Variable(size)) => size.clone(),
+        Dim::RangeFromZero(_var, Size::Length(data)) => format!("{}.len()", data),
+        Dim::RangeFromZero(_var, Size::Expression(size)) => size.clone(),
+    }
+}
+
+// gets the parameter of a closure that maps each element (like the x in |x| x * 2.0 or in |x: f32| x * 2.0)
+fn get_closure_param(closure: &ExprClosure) -> Option<Ident> {
+    if closure.inputs.len() != 1 {
+        return None;
+    }
+    let pat = match &closure.inputs[0] {
+        Pat::Type(pat_type) => &*pat_type.pat,
+        pat => pat,
+    };
+    match pat {
+        Pat::Ident(pat_ident) if pat_ident.by_ref.is_none() && pat_ident.subpat.is_none() => {
+            Some(pat_ident.ident.clone())
+        }
+        _ => None,
+    }
+}
+
+// replaces each use of the parameter of a closure with the element it is applied to
+//
+// this is how the body of a closure passed to launch_map becomes the right-hand side of an assignment in a for loop
+struct ClosureParamReplacer {
+    param: Ident,
+    element: Expr,
+}
+
+impl Fold for ClosureParamReplacer {
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        match e {
+            Expr::Path(path) if path.path.is_ident(&self.param) => self.element.clone(),
+            e => syn::fold::fold_expr(self, e),
         }
     }
 }
@@ -94,6 +177,16 @@ impl Fold for Accelerator {
     fn fold_expr(&mut self, ii: Expr) -> Expr {
         // TODO look at attrs and qself to know if this is a node we can actually work with
 
+        // the variable that holds the GPU
+        let gpu = gpu_ident();
+
+        // iteration over an enumerated slice is launched just like the equivalent for loop over indices
+        if self.ready_to_launch {
+            if let Some(for_loop) = desugar_enumerate(&ii) {
+                return self.fold_expr(Expr::ForLoop(for_loop));
+            }
+        }
+
         match ii.clone() {
             // transform macros into calls to OpenCL to transfer data
             // don't try to fold on substructure of macro
@@ -123,41 +216,62 @@ impl Fold for Accelerator {
                             .path
                             .is_ident(&Ident::new("load", Span::call_site()))
                         {
+                            let len_check = self.len_check(&arg_literal.clone().unwrap_or_default());
+                            self.notes.push(if self.declared_lens.contains_key(&arg_literal.clone().unwrap_or_default()) {
+                                format!("loads `{}` to the GPU after checking that it has the length declared for it", arg_literal.clone().unwrap_or_default())
+                            } else {
+                                format!("loads `{}` to the GPU", arg_literal.clone().unwrap_or_default())
+                            });
                             let new_code = quote! {
                                 {
-                                    let hash = (#arg).as_slice() as *const [f32];
+                                    #len_check
+                                    // the data may be a slice of f32s or of structures made up of f32s
+                                    // either way, we load it as a slice of f32s
+                                    let floats = as_floats((#arg).as_slice());
+                                    let hash = floats as *const [f32];
                                     // if hash is already key, copy_host_slice to existing buffer
                                     // else, create new buffer
-                                    if gpu.buffers.contains_key(&hash) {
-                                        gpu
+                                    if #gpu.buffers.contains_key(&hash) {
+                                        #gpu
                                             .buffers
                                             .get(&hash)
                                             .unwrap()
                                             .cmd()
-                                            .queue(&gpu.queue)
+                                            .queue(&#gpu.queue)
                                             .offset(0)
-                                            .write((#arg).as_slice())
+                                            .write(floats)
                                             .enq().expect(&format!("failed to load `{}` to GPU", #arg_literal).as_str());
                                     } else {
-                                        let _: &[f32] = (#arg).as_slice();
-                                        gpu.buffers.insert(
-                                            hash,
-                                            ocl::Buffer::<f32>::builder()
-                                                .queue(gpu.queue.clone())
+                                        // a buffer of the same length left by an earlier GPU is reused instead of allocating a new one
+                                        let buffer = match #gpu.reuse_buffer(floats.len()) {
+                                            Some(buffer) => {
+                                                buffer
+                                                    .cmd()
+                                                    .queue(&#gpu.queue)
+                                                    .offset(0)
+                                                    .write(floats)
+                                                    .enq().expect(&format!("failed to load `{}` to GPU", #arg_literal).as_str());
+                                                buffer
+                                            }
+                                            None => ocl::Buffer::<f32>::builder()
+                                                .queue(#gpu.queue.clone())
                                                 .flags(ocl::flags::MEM_READ_WRITE)
                                                 .len({
-                                                    let length = (#arg).len();
+                                                    let length = floats.len();
                                                     if length == 0 {
                                                         panic!("`{}` cannot be empty", #arg_literal)
                                                     } else {
                                                         length
                                                     }
                                                 })
-                                                .copy_host_slice((#arg).as_slice())
+                                                .copy_host_slice(floats)
                                                 .build()
-                                                .expect(&format!("failed to load `{}` to GPU", #arg_literal).as_str())
-                                        );
+                                                .expect(&format!("failed to load `{}` to GPU", #arg_literal).as_str()),
+                                        };
+                                        #gpu.buffers.insert(hash, buffer);
                                     }
+                                    // the data on the GPU is now the same as the data on the CPU
+                                    #gpu.modified.remove(&hash);
                                 }
                             };
 
@@ -165,23 +279,53 @@ impl Fold for Accelerator {
                                 .expect("could not generate call to OpenCL API to launch kernel");
 
                             new_ast
+                        } else if path
+                            .path
+                            .is_ident(&Ident::new("load_cached", Span::call_site()))
+                        {
+                            let len_check = self.len_check(&arg_literal.clone().unwrap_or_default());
+                            // the buffer left by an earlier GPU is only reused if the data is at the same address and of the same length
+                            let new_code = quote! {
+                                {
+                                    #len_check
+                                    let hash = as_floats((#arg).as_slice()) as *const [f32];
+                                    if !#gpu.reuse_cached_buffer(hash) {
+                                        gpu_do!(load(#arg));
+                                    }
+                                    #gpu.cache_buffer(hash);
+                                }
+                            };
+
+                            let new_ast = syn::parse_str::<Expr>(&new_code.to_string())
+                                .expect("could not generate call to OpenCL API to load data");
+
+                            // folding the fallback load notes it, so that note is replaced with one for loading with caching
+                            let new_expr = self.fold_expr(new_ast);
+                            self.notes.pop();
+                            self.notes.push(format!(
+                                "loads `{}` to the GPU unless it is still there from an earlier call and keeps it there after returning",
+                                arg_literal.clone().unwrap_or_default()
+                            ));
+                            new_expr
                         } else if path
                             .path
                             .is_ident(&Ident::new("read", Span::call_site()))
                         {
+                            self.notes.push(format!("reads `{}` from the GPU", arg_literal.clone().unwrap_or_default()));
                             let new_code = quote! {
                                 {
-                                    let hash = (#arg).as_slice() as *const [f32];
+                                    let hash = as_floats((#arg).as_slice()) as *const [f32];
 
-                                    gpu
+                                    #gpu
                                         .buffers
                                         .get(&hash)
                                         .expect(&format!("`{}` not loaded to GPU", #arg_literal).as_str())
                                         .cmd()
-                                        .queue(&gpu.queue)
+                                        .queue(&#gpu.queue)
                                         .offset(0)
-                                        .read((#arg).as_mut_slice())
+                                        .read(as_floats_mut((#arg).as_mut_slice()))
                                         .enq().expect(&format!("failed to read `{}` from GPU", #arg_literal).as_str());
+                                    #gpu.modified.remove(&hash);
                                 }
                             };
 
@@ -189,14 +333,289 @@ impl Fold for Accelerator {
                                 .expect("could not generate call to OpenCL API to launch kernel");
 
                             new_ast
+                        } else if path
+                            .path
+                            .is_ident(&Ident::new("reserve", Span::call_site()))
+                        {
+                            // what is the number of elements to reserve room for
+                            let len = if let Some(len) = call.args.iter().nth(1) {
+                                len
+                            } else {
+                                self.errors.push(Error::new(
+                                    call.args.span(),
+                                    "expected number of elements to reserve room for (like `reserve(data, 1000)`)",
+                                ));
+                                return parse_quote! { {} };
+                            };
+                            self.notes.push(format!(
+                                "reserves room for `{}` elements of `{}` on the GPU without loading anything",
+                                len.to_token_stream(),
+                                arg_literal.clone().unwrap_or_default()
+                            ));
+
+                            let new_code = quote! {
+                                {
+                                    // we allocate room for the given number of elements without copying anything
+                                    // this is useful for data that is only ever written to by launches before being read
+                                    let floats = as_floats((#arg).as_slice());
+                                    let hash = floats as *const [f32];
+                                    let len = (#len) as usize;
+                                    if len < (#arg).as_slice().len() {
+                                        panic!("`{}` has length {} so room for only {} elements cannot be reserved", #arg_literal, (#arg).as_slice().len(), len)
+                                    }
+                                    #gpu.buffers.insert(
+                                        hash,
+                                        ocl::Buffer::<f32>::builder()
+                                            .queue(#gpu.queue.clone())
+                                            .flags(ocl::flags::MEM_READ_WRITE)
+                                            .len({
+                                                let length = len * floats_per_element((#arg).as_slice());
+                                                if length == 0 {
+                                                    panic!("`{}` cannot be empty", #arg_literal)
+                                                } else {
+                                                    length
+                                                }
+                                            })
+                                            .build()
+                                            .expect(&format!("failed to reserve `{}` on GPU", #arg_literal).as_str())
+                                    );
+                                    #gpu.modified.remove(&hash);
+                                }
+                            };
+
+                            let new_ast = syn::parse_str::<Expr>(&new_code.to_string())
+                                .expect("could not generate call to OpenCL API to reserve buffer");
+
+                            new_ast
+                        } else if path
+                            .path
+                            .is_ident(&Ident::new("unload", Span::call_site()))
+                        {
+                            self.notes.push(format!("unloads `{}` from the GPU", arg_literal.clone().unwrap_or_default()));
+                            let new_code = quote! {
+                                {
+                                    // dropping the buffer frees it on the GPU
+                                    // everything already queued that uses it still completes
+                                    let hash = as_floats((#arg).as_slice()) as *const [f32];
+
+                                    #gpu
+                                        .buffers
+                                        .remove(&hash)
+                                        .expect(&format!("`{}` not loaded to GPU", #arg_literal).as_str());
+                                    #gpu.modified.remove(&hash);
+                                }
+                            };
+
+                            let new_ast = syn::parse_str::<Expr>(&new_code.to_string())
+                                .expect("could not generate call to OpenCL API to free buffer");
+
+                            new_ast
+                        } else if path
+                            .path
+                            .is_ident(&Ident::new("assert_len", Span::call_site()))
+                        {
+                            // what is the length the data is declared to have
+                            let (data, len) = match (arg, call.args.iter().nth(1)) {
+                                (Some(Expr::Path(data)), Some(len)) if data.path.get_ident().is_some() => {
+                                    (data.path.get_ident().unwrap().to_string(), len.clone())
+                                }
+                                _ => {
+                                    self.errors.push(Error::new(
+                                        call.args.span(),
+                                        "expected data and the length it must have (like `assert_len(data, 1000)`)",
+                                    ));
+                                    return parse_quote! { {} };
+                                }
+                            };
+
+                            // the length is checked right away, whenever the data is loaded, and before each launch that indexes the data
+                            self.notes.push(format!("checks that `{}` has length `{}`", data, len.to_token_stream()));
+                            self.declared_lens.insert(data.clone(), len);
+                            let len_check = self.len_check(&data);
+                            let new_code = quote! {
+                                {
+                                    #len_check
+                                }
+                            };
+
+                            let new_ast = syn::parse_str::<Expr>(&new_code.to_string())
+                                .expect("could not generate check of length of data");
+
+                            new_ast
+                        } else if path
+                            .path
+                            .is_ident(&Ident::new("name", Span::call_site()))
+                        {
+                            // what is the name to give the data
+                            let name = if let Some(name) = call.args.iter().nth(1) {
+                                name
+                            } else {
+                                self.errors.push(Error::new(
+                                    call.args.span(),
+                                    "expected name to give data (like `name(data, \"weights\")`)",
+                                ));
+                                return parse_quote! { {} };
+                            };
+                            self.notes.push(format!(
+                                "names the data on the GPU for `{}` {}",
+                                arg_literal.clone().unwrap_or_default(),
+                                name.to_token_stream()
+                            ));
+
+                            let new_code = quote! {
+                                {
+                                    // if the data is loaded, its buffer gets the name
+                                    // otherwise, the data gets the buffer that already has the name
+                                    let floats = as_floats((#arg).as_slice());
+                                    let hash = floats as *const [f32];
+                                    let name = (#name).to_string();
+                                    if let Some(buffer) = #gpu.buffers.get(&hash) {
+                                        #gpu.names.insert(name, buffer.clone());
+                                    } else {
+                                        let buffer = #gpu
+                                            .names
+                                            .get(&name)
+                                            .expect(&format!("`{}` not loaded to GPU and no data on GPU is named {:?}", #arg_literal, name).as_str())
+                                            .clone();
+                                        if buffer.len() != floats.len() {
+                                            panic!("`{}` has {} floats but data on GPU named {:?} has {} floats", #arg_literal, floats.len(), name, buffer.len())
+                                        }
+                                        #gpu.buffers.insert(hash, buffer);
+                                        // we don't know what has been done to the named buffer so the data on the CPU may be out of date
+                                        #gpu.modified.insert(hash);
+                                    }
+                                }
+                            };
+
+                            let new_ast = syn::parse_str::<Expr>(&new_code.to_string())
+                                .expect("could not generate code to name buffer");
+
+                            new_ast
+                        } else if path
+                            .path
+                            .is_ident(&Ident::new("sync", Span::call_site()))
+                        {
+                            self.notes.push(String::from("waits for the GPU to finish"));
+                            let new_code = quote! {
+                                {
+                                    #gpu.queue.finish().expect("failed to wait for GPU to finish");
+                                }
+                            };
+
+                            let new_ast = syn::parse_str::<Expr>(&new_code.to_string())
+                                .expect("could not generate call to OpenCL API to wait for GPU");
+
+                            new_ast
+                        } else if path
+                            .path
+                            .is_ident(&Ident::new("with", Span::call_site()))
+                        {
+                            // naming the GPU declares a variable so it is handled by fold_stmt
+                            // if we get here, it isn't a statement of its own
+                            self.errors.push(Error::new(
+                                ii.span(),
+                                "expected `gpu_do!(with(name))` to be a statement of its own",
+                            ));
+
+                            ii
                         } else if path
                             .path
                             .is_ident(&Ident::new("launch", Span::call_site()))
                         {
                             self.ready_to_launch = true;
 
+                            // the launch may have options (like launch(elements_per_thread = 4, unroll = 2))
+                            // these are used when the for loop that follows is launched
+                            self.elements_per_thread = 1;
+                            self.unroll = 1;
+                            for option in &call.args {
+                                let name_and_value = match option {
+                                    Expr::Assign(assign) => match (&*assign.left, &*assign.right) {
+                                        (Expr::Path(name), Expr::Lit(ExprLit { lit: Lit::Int(value), .. })) => name
+                                            .path
+                                            .get_ident()
+                                            .map(|name| name.to_string())
+                                            .zip(value.base10_parse::<u32>().ok().filter(|value| *value > 0)),
+                                        _ => None,
+                                    },
+                                    _ => None,
+                                };
+                                match name_and_value {
+                                    Some((name, value)) if name == "elements_per_thread" => self.elements_per_thread = value,
+                                    Some((name, value)) if name == "unroll" => self.unroll = value,
+                                    _ => self.errors.push(Error::new(
+                                        option.span(),
+                                        "expected `elements_per_thread` or `unroll` set to a positive integer (like `launch(elements_per_thread = 4, unroll = 2)`)",
+                                    )),
+                                }
+                            }
+                            if self.unroll > self.elements_per_thread {
+                                self.errors.push(Error::new(
+                                    call.args.span(),
+                                    "can't unroll more elements than each work-item goes over (set with `elements_per_thread`)",
+                                ));
+                                self.unroll = 1;
+                            }
+
                             // just return the macro invocation
                             ii
+                        } else if path
+                            .path
+                            .is_ident(&Ident::new("launch_map", Span::call_site()))
+                        {
+                            // what is being mapped and what is it mapped with
+                            let (data, closure, param) = match (arg, call.args.iter().nth(1)) {
+                                (Some(Expr::Path(data)), Some(Expr::Closure(closure)))
+                                    if call.args.len() == 2
+                                        && data.path.get_ident().is_some()
+                                        && get_closure_param(closure).is_some() =>
+                                {
+                                    (
+                                        data.path.get_ident().unwrap().clone(),
+                                        closure.clone(),
+                                        get_closure_param(closure).unwrap(),
+                                    )
+                                }
+                                _ => {
+                                    self.errors.push(Error::new(
+                                        call.args.span(),
+                                        "expected data and a closure to map each element with (like `launch_map(data, |x| x * 2.0)`)",
+                                    ));
+                                    return parse_quote! { {} };
+                                }
+                            };
+                            self.notes.push(format!(
+                                "maps each element of `{}` with `{}` into new data that stays on the GPU until it is read",
+                                data,
+                                closure.to_token_stream()
+                            ));
+
+                            // the closure is launched as a for loop that writes to new data (reserved but not loaded) instead of to the data itself
+                            // this is then folded just like if it was written out with reserve and launch
+                            let mapped = Ident::new("__emu_mapped", Span::call_site());
+                            let index = Ident::new("__emu_i", Span::call_site());
+                            let data_literal = data.to_string();
+                            let element: Expr = parse_quote! { #data[#index] };
+                            let body = ClosureParamReplacer { param, element }.fold_expr(*closure.body);
+                            let new_code = quote! {
+                                {
+                                    if !#gpu.buffers.contains_key(&(as_floats((#data).as_slice()) as *const [f32])) {
+                                        panic!("`{}` not loaded to GPU", #data_literal)
+                                    }
+                                    let mut #mapped = vec![0.0f32; (#data).as_slice().len()];
+                                    gpu_do!(reserve(#mapped, (#data).as_slice().len()));
+                                    gpu_do!(launch());
+                                    for #index in 0..#data.len() {
+                                        #mapped[#index] = (#body);
+                                    }
+                                    #mapped
+                                }
+                            };
+
+                            let new_ast = syn::parse_str::<Expr>(&new_code.to_string())
+                                .expect("could not generate launch of closure to map data");
+
+                            self.fold_expr(new_ast)
                         } else {
                             ii
                         }
@@ -211,6 +630,10 @@ impl Fold for Accelerator {
                     // if we find a for loop, we only fold if we are not yet ready to launch
                     // if we are ready to launch, this better be a proper for loop
                     // and if it isn't a proper for loop, we will just leave it as it is and report errors
+                    self.notes.push(format!(
+                        "runs `{}` on the CPU since it doesn't come right after `gpu_do!(launch())`",
+                        describe_for_loop(&i)
+                    ));
                     return fold_expr_default!(self, Expr::ForLoop(i.clone()));
                 } else {
                     self.ready_to_launch = false;
@@ -219,13 +642,27 @@ impl Fold for Accelerator {
                 // attempt to get global work size of the kernel to be launched
                 let (global_work_size_dims, block_for_kernel) =
                     get_global_work_size(vec![], i.clone());
+                // each dimension's size is either a literal or a variable
+                // either way, it's passed to OpenCL as a usize
                 let global_work_size = global_work_size_dims
                     .iter()
-                    .map(|dim| {
-                        if let Dim::RangeFromZero(_var, size) = dim {
-                            *size
-                        } else {
-                            0
+                    .map(|dim| match dim {
+                        Dim::RangeFromZero(_var, Size::Literal(size)) => {
+                            let size = *size as usize;
+                            quote! { #size }
+                        }
+                        Dim::RangeFromZero(_var, Size::Variable(size)) => {
+                            let size = Ident::new(size, Span::call_site());
+                            quote! { ((#size) as usize) }
+                        }
+                        Dim::RangeFromZero(_var, Size::Length(data)) => {
+                            let data = Ident::new(data, Span::call_site());
+                            quote! { ((#data).len()) }
+                        }
+                        Dim::RangeFromZero(_var, Size::Expression(size)) => {
+                            let size = syn::parse_str::<Expr>(size)
+                                .expect("could not parse size of loop");
+                            quote! { ((#size) as usize) }
                         }
                     })
                     .collect::<Vec<_>>();
@@ -238,14 +675,41 @@ impl Fold for Accelerator {
                     // then just pretend we didn't see it and keep moving on
                     self.errors
                         .push(Error::new(i.span(), "unexpected kind of for loop"));
+                    self.notes.push(format!(
+                        "can't launch `{}` since it isn't a kind of for loop that can be launched",
+                        describe_for_loop(&i)
+                    ));
                     return i.into();
                 }
+                let global_work_size_description = global_work_size_dims
+                    .iter()
+                    .map(describe_size)
+                    .collect::<Vec<_>>()
+                    .join(", ");
 
                 // (a) generate program
                 // we use the generator here
                 let block = block_for_kernel.unwrap();
                 let mut code_generator = Generator::from(global_work_size_dims);
+                code_generator.device_fns = self.device_fns.clone();
+                code_generator.device_consts = self.device_consts.clone();
+                code_generator.elements_per_thread = self.elements_per_thread;
+                code_generator.unroll = self.unroll;
+                self.elements_per_thread = 1;
+                self.unroll = 1;
                 code_generator.visit_block(&block);
+                if code_generator.failed_to_generate {
+                    self.notes.push(format!(
+                        "can't launch `{}` since code for the GPU couldn't be generated from it\n{}",
+                        describe_for_loop(&i),
+                        code_generator
+                            .errors
+                            .iter()
+                            .map(|error| format!("- {}", error))
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    ));
+                }
                 self.errors.append(&mut code_generator.errors);
                 if code_generator.failed_to_generate {
                     // on failing, we just fold on the inside
@@ -255,19 +719,95 @@ impl Fold for Accelerator {
                 }
                 let program = code_generator.code;
 
+                // describe the launch
+                let mut launch_note = format!(
+                    "launches `{}` as a kernel with a global work size of [{}]",
+                    describe_for_loop(&i),
+                    global_work_size_description
+                );
+                for param in &code_generator.params {
+                    launch_note += &if param.is_array && param.fields.is_empty() {
+                        format!("\n- `{}` is passed in as the buffer it was loaded to", param.name)
+                    } else if param.is_array {
+                        format!(
+                            "\n- `{}` is passed in as the buffer it was loaded to along with where the fields {} are in each element",
+                            param.name,
+                            param.fields.iter().map(|field| format!("`{}`", field)).collect::<Vec<_>>().join(", ")
+                        )
+                    } else if let Some(host_expr) = &param.host_expr {
+                        format!(
+                            "\n- `{}` is lifted out of the loop so it is evaluated once on the CPU and passed in",
+                            host_expr.to_token_stream()
+                        )
+                    } else {
+                        format!("\n- `{}` is passed in as a value", param.name)
+                    };
+                }
+                for (name, dim) in &code_generator.bounds_checks {
+                    if let Dim::RangeFromZero(var, _) = &code_generator.global_work_size_dims[*dim] {
+                        launch_note += &format!(
+                            "\n- `{}` is checked to be long enough to be indexed by `{}` before launching",
+                            name, var
+                        );
+                    }
+                }
+                if code_generator.elements_per_thread > 1 {
+                    launch_note += &format!(
+                        "\n- each work-item goes over {} elements of the first dimension in a grid-stride loop with {} of them unrolled into each iteration",
+                        code_generator.elements_per_thread, code_generator.unroll
+                    );
+                }
+                launch_note += "\n- the loop runs on the CPU instead when its iterations plus the floats in the arrays it writes to are fewer than the `cpu_threshold` of the GPU (as long as the arrays it only reads haven't been written to on the GPU)";
+                launch_note += &format!("\n- the kernel is\n```c\n{}\n```", program.trim());
+                self.notes.push(launch_note);
+
+                // the program is known now so we hash it now instead of on every launch
+                let mut hasher = DefaultHasher::new();
+                program.hash(&mut hasher);
+                let program_key = Literal::u64_suffixed(hasher.finish());
+
                 // (b) generate arguments
                 let args = code_generator.params.iter().map(|param| {
                     let ident = Ident::new(&param.name, Span::call_site());
                     let ident_literal = ident.to_string().clone();
 
                     if param.is_array {
+                        // an array of structures also needs its stride and the offset of each field (in f32s) passed in
+                        // we compute these from the first element (which must exist since the array was loaded)
+                        let field_offsets = param.fields.iter().map(|field| {
+                            let field = Ident::new(field, Span::call_site());
+                            quote! {
+                                .arg(&({
+                                    let element = &(#ident).as_slice()[0];
+                                    (&element.#field as *const f32 as usize - element as *const _ as usize) / std::mem::size_of::<f32>()
+                                } as i32))
+                            }
+                        }).collect::<Vec<_>>();
+                        let stride = if param.fields.is_empty() {
+                            quote! {}
+                        } else {
+                            quote! {
+                                .arg(&((std::mem::size_of_val(&(#ident).as_slice()[0]) / std::mem::size_of::<f32>()) as i32))
+                            }
+                        };
+
                         quote! {
                             .arg(
-                                gpu
+                                #gpu
                                     .buffers
-                                    .get(&((#ident).as_slice() as *const [f32]))
+                                    .get(&(as_floats((#ident).as_slice()) as *const [f32]))
                                     .expect(format!("`{}` not loaded to GPU", #ident_literal).as_str())
                             )
+                            #stride
+                            #(#field_offsets)*
+                        }
+                    } else if let Some(host_expr) = &param.host_expr {
+                        // a lifted expression is evaluated once before launching
+                        quote! {
+                            .arg(&{
+                                let lifted: f32 = #host_expr;
+                                lifted
+                            })
                         }
                     } else {
                         quote! {
@@ -276,60 +816,199 @@ impl Fold for Accelerator {
                     }
                 }).collect::<Vec<_>>();
 
-                // (c) generate code
+                // the number of work-items in each dimension
+                // if each work-item goes over more than 1 element of the first dimension, there are fewer work-items in it than iterations
+                let elements_per_thread = code_generator.elements_per_thread as usize;
+                let dispatch_size = global_work_size
+                    .iter()
+                    .enumerate()
+                    .map(|(dim, size)| {
+                        if dim == 0 && elements_per_thread > 1 {
+                            quote! { ((#size + #elements_per_thread - 1) / #elements_per_thread) }
+                        } else {
+                            size.clone()
+                        }
+                    })
+                    .collect::<Vec<_>>();
+
+                // (c) generate checks that arrays indexed by the loop are long enough
+                let global_work_size_dims = code_generator.global_work_size_dims.clone();
+                let bounds_checks = code_generator.bounds_checks.iter().map(|(name, dim)| {
+                    let ident = Ident::new(name, Span::call_site());
+                    let ident_literal = name.clone();
+                    let len = &global_work_size[*dim];
+                    let var = if let Dim::RangeFromZero(var, _) = &global_work_size_dims[*dim] {
+                        var.clone()
+                    } else {
+                        String::new()
+                    };
+
+                    // if the data was declared to have a length, we check that the loop stays within that length
+                    // when both are literals, this can be checked right now at compile time
+                    let declared_len_check = if let Some(declared_len) = self.declared_lens.get(name) {
+                        if let (Expr::Lit(ExprLit { lit: Lit::Int(declared_len), .. }), Dim::RangeFromZero(_, Size::Literal(size))) = (declared_len, &global_work_size_dims[*dim]) {
+                            if declared_len.base10_parse::<u64>().map_or(false, |declared_len| declared_len < *size as u64) {
+                                self.errors.push(Error::new(
+                                    i.span(),
+                                    format!("`{}` is declared to have length {} but is indexed by `{}` which goes up to {}", name, declared_len, var, size),
+                                ));
+                            }
+                        }
+                        let len_check = self.len_check(name);
+                        quote! {
+                            #len_check
+                            assert!(
+                                (#declared_len) as usize >= #len,
+                                "`{}` is declared to have length {} but is indexed by `{}` which goes up to {}",
+                                #ident_literal,
+                                (#declared_len) as usize,
+                                #var,
+                                #len
+                            );
+                        }
+                    } else {
+                        quote! {}
+                    };
+
+                    quote! {
+                        #declared_len_check
+                        assert!(
+                            (#ident).as_slice().len() >= #len,
+                            "`{}` has length {} but is indexed by `{}` which goes up to {}",
+                            #ident_literal,
+                            (#ident).as_slice().len(),
+                            #var,
+                            #len
+                        );
+                    }
+                }).collect::<Vec<_>>();
+
+                // (d) generate code for running on the CPU instead
+                // the loop runs on scratch copies of every array it uses, read back from the GPU, and the arrays that are written to are written back after
+                // the data on the CPU isn't necessarily what was loaded (it may have changed since then) so it's left alone
+                //
+                // this way, running on the CPU instead can't be told apart from launching
+                let written_arrays = code_generator
+                    .params
+                    .iter()
+                    .filter(|param| param.is_array && param.is_written)
+                    .map(|param| Ident::new(&param.name, Span::call_site()))
+                    .collect::<Vec<_>>();
+                let read_arrays = code_generator
+                    .params
+                    .iter()
+                    .filter(|param| param.is_array && !param.is_written)
+                    .map(|param| Ident::new(&param.name, Span::call_site()))
+                    .collect::<Vec<_>>();
+                let arrays = read_arrays.iter().chain(written_arrays.iter()).collect::<Vec<_>>();
+                let arrays_literals = arrays
+                    .iter()
+                    .map(|ident| ident.to_string())
+                    .collect::<Vec<_>>();
+                let scratch_arrays = (0..arrays.len())
+                    .map(|i| Ident::new(&format!("__emu_scratch_{}", i), Span::call_site()))
+                    .collect::<Vec<_>>();
+                let read_scratch_arrays = &scratch_arrays[..read_arrays.len()];
+                let written_scratch_arrays = &scratch_arrays[read_arrays.len()..];
+                let written_arrays_literals = &arrays_literals[read_arrays.len()..];
+
+                // (e) generate code
                 let new_code = quote! {
                     {
-                        let __main__ = || {
-                            #i
-                        };
+                        #(#bounds_checks)*
+
+                        // the work is the number of iterations plus the number of floats that would have to be read from and written back to the GPU
+                        // everything declared here is prefixed so that it can't be confused with what the loop uses
+                        let __emu_work: usize = 1usize #(* #global_work_size)* #(+ as_floats((#arrays).as_slice()).len())*;
+                        let __emu_on_cpu = __emu_work < #gpu.cpu_threshold;
+
+                        if __emu_on_cpu {
+                            #(
+                                let #scratch_arrays = {
+                                    let __emu_buffer = #gpu
+                                        .buffers
+                                        .get(&(as_floats((#arrays).as_slice()) as *const [f32]))
+                                        .expect(&format!("`{}` not loaded to GPU", #arrays_literals).as_str());
+                                    let mut __emu_scratch = vec![0.0f32; __emu_buffer.len()];
+                                    __emu_buffer
+                                        .cmd()
+                                        .queue(&#gpu.queue)
+                                        .offset(0)
+                                        .read(__emu_scratch.as_mut_slice())
+                                        .enq().expect(&format!("failed to read `{}` from GPU", #arrays_literals).as_str());
+                                    __emu_scratch
+                                };
+                            )*
+                            #(let mut #written_scratch_arrays = #written_scratch_arrays;)*
 
-                        let program_from = String::from(#program);
-
-                        if gpu.programs.contains_key(&program_from) {
-
-                            let kernel = ocl::Kernel::builder()
-                                .program(gpu.programs.get(&program_from).unwrap())
-                                .name("__main__")
-                                .queue(gpu.queue.clone())
-                                .global_work_size([#(#global_work_size),*])
-                                #(#args)*
-                                .build().expect("failed to compile kernel from program to be run on GPU");
-
-                            unsafe {
-                                kernel.cmd()
-                                    .queue(&gpu.queue)
-                                    .global_work_offset(kernel.default_global_work_offset())
-                                    .global_work_size([#(#global_work_size),*])
-                                    .local_work_size(kernel.default_local_work_size())
-                                    .enq().expect("failed to run compiled kernel on GPU");
+                            // each array is shadowed by its scratch copy while the loop runs
+                            {
+                                #(let #read_arrays = as_slice_like((#read_arrays).as_slice(), #read_scratch_arrays.as_slice());)*
+                                #(let #written_arrays = as_slice_like_mut((#written_arrays).as_slice(), #written_scratch_arrays.as_mut_slice());)*
+
+                                #i
                             }
+
+                            #(
+                                #gpu
+                                    .buffers
+                                    .get(&(as_floats((#written_arrays).as_slice()) as *const [f32]))
+                                    .expect(&format!("`{}` not loaded to GPU", #written_arrays_literals).as_str())
+                                    .cmd()
+                                    .queue(&#gpu.queue)
+                                    .offset(0)
+                                    .write(#written_scratch_arrays.as_slice())
+                                    .enq().expect(&format!("failed to load `{}` to GPU", #written_arrays_literals).as_str());
+                            )*
                         } else {
-                            let program = ocl::Program::builder()
-                                    .devices(gpu.device)
-                                    .src(&program_from)
-                                    .build(&gpu.context).expect("failed to compile program to be run on GPU");
-
-                            let kernel = ocl::Kernel::builder()
-                                .program(&program)
-                                .name("__main__")
-                                .queue(gpu.queue.clone())
-                                .global_work_size([#(#global_work_size),*])
-                                #(#args)*
-                                .build().expect("failed to compile kernel from program to be run on GPU");
-
-                            unsafe {
-                                kernel.cmd()
-                                    .queue(&gpu.queue)
-                                    .global_work_offset(kernel.default_global_work_offset())
-                                    .global_work_size([#(#global_work_size),*])
-                                    .local_work_size(kernel.default_local_work_size())
-                                    .enq().expect("failed to run compiled kernel on GPU");
-                            }
+                            let program_key: u64 = #program_key;
 
-                            gpu.programs.insert(program_from, program);
-                        }
+                            if #gpu.programs.contains_key(&program_key) {
+
+                                let kernel = ocl::Kernel::builder()
+                                    .program(#gpu.programs.get(&program_key).unwrap())
+                                    .name("__main__")
+                                    .queue(#gpu.queue.clone())
+                                    .global_work_size([#(#dispatch_size),*])
+                                    #(#args)*
+                                    #(.arg(&(#global_work_size as i32)))*
+                                    .build().expect("failed to compile kernel from program to be run on GPU");
 
+                                unsafe {
+                                    kernel.cmd()
+                                        .queue(&#gpu.queue)
+                                        .global_work_offset(kernel.default_global_work_offset())
+                                        .global_work_size([#(#dispatch_size),*])
+                                        .local_work_size(kernel.default_local_work_size())
+                                        .enq().expect("failed to run compiled kernel on GPU");
+                                }
+                            } else {
+                                let program = build_program(&#gpu, #program);
+
+                                let kernel = ocl::Kernel::builder()
+                                    .program(&program)
+                                    .name("__main__")
+                                    .queue(#gpu.queue.clone())
+                                    .global_work_size([#(#dispatch_size),*])
+                                    #(#args)*
+                                    #(.arg(&(#global_work_size as i32)))*
+                                    .build().expect("failed to compile kernel from program to be run on GPU");
+
+                                unsafe {
+                                    kernel.cmd()
+                                        .queue(&#gpu.queue)
+                                        .global_work_offset(kernel.default_global_work_offset())
+                                        .global_work_size([#(#dispatch_size),*])
+                                        .local_work_size(kernel.default_local_work_size())
+                                        .enq().expect("failed to run compiled kernel on GPU");
+                                }
+
+                                #gpu.programs.insert(program_key, program);
+                            }
+                        }
 
+                        // the data on the CPU is out of date once the GPU has written to it
+                        #(#gpu.modified.insert(as_floats((#written_arrays).as_slice()) as *const [f32]);)*
                     }
                 };
 
@@ -354,6 +1033,44 @@ impl Fold for Accelerator {
         }
     }
 
+    // gpu_do!(with(name)) becomes a let statement that gives the GPU a name
+    // so that the name can be used in the rest of the block
+    fn fold_stmt(&mut self, s: Stmt) -> Stmt {
+        if let Stmt::Semi(Expr::Macro(i), _) = &s {
+            if let Ok(call) = syn::parse2::<ExprCall>(i.mac.tokens.clone()) {
+                if let Expr::Path(path) = &*call.func {
+                    if path
+                        .path
+                        .is_ident(&Ident::new("with", Span::call_site()))
+                        && !self.ready_to_launch
+                    {
+                        let gpu = gpu_ident();
+                        return match call.args.first() {
+                            Some(Expr::Path(name))
+                                if call.args.len() == 1 && name.path.get_ident().is_some() =>
+                            {
+                                let name = name.path.get_ident().unwrap();
+                                self.notes.push(format!("names the GPU `{}` so it can be used directly", name));
+                                parse_quote! {
+                                    let #name: &mut Gpu = &mut #gpu;
+                                }
+                            }
+                            _ => {
+                                self.errors.push(Error::new(
+                                    call.args.span(),
+                                    "expected name to give the GPU (like `with(gpu)`)",
+                                ));
+                                parse_quote! { {} }
+                            }
+                        };
+                    }
+                }
+            }
+        }
+
+        syn::fold::fold_stmt(self, s)
+    }
+
     // don't fold on substructures of items
     // items can't use GPU argument to the function the item is in
     fn fold_item(&mut self, i: Item) -> Item {
@@ -381,9 +1098,16 @@ impl Fold for Accelerator {
             // because let statement could be assigning a value where that value
             // is a block expression. in that case, we want to look at the block
             // expression in case that uses the GPU for stuff
+            //
+            // the value could also be a gpu_do!() that produces new data (like gpu_do!(launch_map(data, |x| x * 2.0)))
+            // so a macro is folded like any other expression that could be a gpu_do!()
             let mut new_expr = None;
             if let Some(mut expr) = l.init.clone() {
-                expr.1 = Box::new(fold_expr_default!(self, *(expr.1)));
+                expr.1 = Box::new(if let Expr::Macro(_) = *expr.1 {
+                    self.fold_expr(*(expr.1))
+                } else {
+                    fold_expr_default!(self, *(expr.1))
+                });
                 new_expr = Some(expr);
             }
 
@@ -0,0 +1,87 @@
+// for etc.
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+// explaining is what writes a report of what was done with a function tagged with #[gpu_use(explain)]
+//
+// we can't emit notes that show up alongside warnings and errors (that isn't stable yet)
+// so instead the report is written to a file that can be read after compiling
+pub struct Explanation {
+    pub function: String,
+    // how the function gets the GPU (it's either created in the function or passed to it)
+    pub gpu: String,
+    // the helper functions that the GPU is passed to
+    pub helper_functions: Vec<String>,
+    // what was done with the body of the function, in order
+    // these are collected by the accelerator
+    pub notes: Vec<String>,
+}
+
+impl Explanation {
+    // formats the explanation as a report that is meant to be read by people
+    pub fn to_report(&self) -> String {
+        let mut report = format!("# `{}`\n\n{}\n", self.function, self.gpu);
+        if !self.helper_functions.is_empty() {
+            report += &format!(
+                "the GPU is passed to and returned from {}\n",
+                self.helper_functions
+                    .iter()
+                    .map(|helper_function| format!("`{}`", helper_function))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        report += "\n";
+
+        if self.notes.is_empty() {
+            report += "nothing in the body uses the GPU\n";
+        }
+        for note in &self.notes {
+            // notes can span many lines (like ones that have the source of a kernel)
+            // the first line is a bullet and the rest are indented under it
+            let mut lines = note.lines();
+            if let Some(first_line) = lines.next() {
+                report += &format!("- {}\n", first_line);
+            }
+            for line in lines {
+                report += &format!("  {}\n", line);
+            }
+        }
+
+        report
+    }
+}
+
+// where reports are written
+//
+// this is $EMU_EXPLAIN_DIR if it is set
+// otherwise it is emu-explain in the target directory (cargo runs the compiler from the root of the workspace so by default that is ./target)
+pub fn get_explain_dir() -> PathBuf {
+    if let Ok(dir) = env::var("EMU_EXPLAIN_DIR") {
+        PathBuf::from(dir)
+    } else if let Ok(dir) = env::var("CARGO_TARGET_DIR") {
+        PathBuf::from(dir).join("emu-explain")
+    } else {
+        PathBuf::from("target").join("emu-explain")
+    }
+}
+
+// writes the report for the given explanation and returns the path to it
+//
+// the name of the crate being compiled is part of the name of the file so that functions with the same name in
+// different crates (like the main functions of different binaries) don't overwrite each other's reports
+pub fn write_explanation(explanation: &Explanation) -> io::Result<PathBuf> {
+    let dir = get_explain_dir();
+    fs::create_dir_all(&dir)?;
+
+    let file_name = match env::var("CARGO_CRATE_NAME") {
+        Ok(crate_name) => format!("{}-{}.md", crate_name, explanation.function),
+        Err(_) => format!("{}.md", explanation.function),
+    };
+    let path = dir.join(file_name);
+    fs::write(&path, explanation.to_report())?;
+
+    Ok(path)
+}
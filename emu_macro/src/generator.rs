@@ -6,6 +6,7 @@ use syn::*;
 
 // for etc.
 use crate::identifier::Dim;
+use std::collections::HashMap;
 
 // represents a parameter of a kernel
 //
@@ -18,6 +19,155 @@ use crate::identifier::Dim;
 pub struct Parameter {
     pub is_array: bool,
     pub name: String,
+    // if this is an array of structures (like particles[i].x), these are the fields that are used
+    //
+    // we don't know what the structure looks like (it's defined somewhere we can't see)
+    // so we can't generate an OpenCL struct that is guaranteed to have the same layout
+    // instead, the array is passed in as a flat array of floats along with the stride of each
+    // structure and the offset of each used field (both in floats) which are computed at run-time
+    pub fields: Vec<String>,
+    // if this is a scalar expression that was lifted out of the kernel (like (scale - offset).abs()),
+    // this is the expression which is evaluated on the host before launching and passed in
+    pub host_expr: Option<Expr>,
+    // whether or not launched code assigns to elements of this array
+    // arrays that are only read don't need to be synchronized when a launch runs on the CPU instead
+    pub is_written: bool,
+}
+
+// this is used to check if an expression can be evaluated on the host before launching
+//
+// an expression can be evaluated on the host if it doesn't depend on the variable of any
+// dimension of the loop and doesn't read from any array (which may have been changed on the GPU)
+struct HostExprChecker<'a> {
+    global_work_size_dims: &'a [Dim],
+    locals: &'a [Local],
+    is_host_expr: bool,
+}
+
+impl<'a, 'ast> Visit<'ast> for HostExprChecker<'a> {
+    fn visit_expr_path(&mut self, node: &'ast ExprPath) {
+        if let Some(ident) = node.path.get_ident() {
+            for global_work_size_dim in self.global_work_size_dims {
+                match global_work_size_dim {
+                    Dim::RangeFromZero(name, _) => {
+                        if ident.to_string() == *name {
+                            self.is_host_expr = false;
+                        }
+                    }
+                }
+            }
+            if self.locals.iter().any(|local| ident == &local.name) {
+                self.is_host_expr = false;
+            }
+        }
+        syn::visit::visit_expr_path(self, node);
+    }
+
+    fn visit_expr_index(&mut self, _node: &'ast ExprIndex) {
+        self.is_host_expr = false;
+    }
+
+    // these could have side effects that shouldn't be moved out of the loop
+    fn visit_expr_assign(&mut self, _node: &'ast ExprAssign) {
+        self.is_host_expr = false;
+    }
+
+    fn visit_expr_assign_op(&mut self, _node: &'ast ExprAssignOp) {
+        self.is_host_expr = false;
+    }
+
+    fn visit_expr_block(&mut self, _node: &'ast ExprBlock) {
+        self.is_host_expr = false;
+    }
+
+    fn visit_expr_closure(&mut self, _node: &'ast ExprClosure) {
+        self.is_host_expr = false;
+    }
+
+    fn visit_expr_macro(&mut self, _node: &'ast ExprMacro) {
+        self.is_host_expr = false;
+    }
+
+    fn visit_expr_return(&mut self, _node: &'ast ExprReturn) {
+        self.is_host_expr = false;
+    }
+
+    // this could be a reference to an array (like in sum(&data, 0)) which may have been changed on the GPU
+    fn visit_expr_reference(&mut self, _node: &'ast ExprReference) {
+        self.is_host_expr = false;
+    }
+}
+
+// represents the type of a parameter of a device function
+//
+// a device function is a function defined inside of a function tagged with #[gpu_use] that can be called
+// from a launched loop (and from other device functions)
+// we only support a few types of parameters, the same types that can be used in a launched loop
+#[derive(Clone, Copy, PartialEq)]
+enum DeviceFnParamType {
+    Float, // f32
+    Int,   // usize or i32, used for indices
+    Array, // &[f32] or &mut [f32]
+}
+
+impl DeviceFnParamType {
+    fn from(ty: &Type) -> Option<Self> {
+        match ty {
+            Type::Path(path) => match path.path.get_ident().map(|ident| ident.to_string()).as_deref() {
+                Some("f32") => Some(DeviceFnParamType::Float),
+                Some("usize") | Some("i32") => Some(DeviceFnParamType::Int),
+                _ => None,
+            },
+            Type::Reference(reference) => match &*reference.elem {
+                Type::Slice(slice) => match DeviceFnParamType::from(&slice.elem) {
+                    Some(DeviceFnParamType::Float) => Some(DeviceFnParamType::Array),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn to_opencl(&self) -> &'static str {
+        match self {
+            DeviceFnParamType::Float => "float",
+            DeviceFnParamType::Int => "int",
+            DeviceFnParamType::Array => "global float*",
+        }
+    }
+}
+
+// represents a variable declared with let in launched code
+//
+// its type is either written down (like let x: f32 = ...) or inferred from the expression it's initialized with
+// each variable is given its own name in OpenCL so that shadowing (like let x = x * 2.0) doesn't redeclare a variable
+pub struct Local {
+    pub name: String,
+    pub opencl_name: String,
+    ty: DeviceFnParamType,
+}
+
+// looks at the signature of a device function and returns the name and type of each parameter
+fn get_device_fn_params(device_fn: &ItemFn) -> Result<Vec<(String, DeviceFnParamType)>> {
+    device_fn
+        .sig
+        .inputs
+        .iter()
+        .map(|input| {
+            if let FnArg::Typed(pat_type) = input {
+                if let (Pat::Ident(pat_ident), Some(ty)) =
+                    (&*pat_type.pat, DeviceFnParamType::from(&pat_type.ty))
+                {
+                    return Ok((pat_ident.ident.to_string(), ty));
+                }
+            }
+            Err(Error::new(
+                input.span(),
+                "parameters of functions called from launched code must be `f32`, `usize`, `i32`, `&[f32]`, or `&mut [f32]`",
+            ))
+        })
+        .collect()
 }
 
 // this makes it easy to compile a Parameter
@@ -35,6 +185,19 @@ impl ToString for Parameter {
         result += " emumumu_"; // prefix all identifiers with emumumu
         result += &self.name;
 
+        // an array of structures also needs the stride and field offsets
+        if !self.fields.is_empty() {
+            result += ", int emumumu_";
+            result += &self.name;
+            result += "__stride";
+            for field in &self.fields {
+                result += ", int emumumu_";
+                result += &self.name;
+                result += "__";
+                result += field;
+            }
+        }
+
         result
     }
 }
@@ -89,6 +252,41 @@ pub struct Generator {
     // for example, when we implement variables we need to look at an expression and see if we can detect what the type must be
     // note that we don't need to do some complex Hindley-Milner stuff, we can assume it is correctly typed and only uses types from a small subset (basically usize, f32, [f32], bool)
     pub is_next_ident_array: bool,
+    // arrays that are indexed directly by the variable of a dimension (like data[i])
+    // each is the name of the array and the dimension it is indexed by
+    // these are used to check at run-time that the arrays are long enough for the loop to go over
+    pub bounds_checks: Vec<(String, usize)>,
+    // whether or not scalar expressions that don't depend on the loop can be lifted out of the kernel
+    // this is turned off inside of indices since indices aren't floats
+    pub lifting_allowed: bool,
+    // functions defined in the function tagged with #[gpu_use] that can be called from launched code
+    // each one that is called is generated as an OpenCL function that comes before the kernel
+    pub device_fns: HashMap<String, ItemFn>,
+    // constants defined in the function tagged with #[gpu_use] that can be used from launched code and device functions
+    // each one that is used is generated as an OpenCL constant that comes before the functions that use it
+    pub device_consts: HashMap<String, ItemConst>,
+    // the generated OpenCL constants and functions and their names, callees always come before their callers
+    pub functions: String,
+    pub generated_device_fns: Vec<String>,
+    pub generated_device_consts: Vec<String>,
+    // the device functions that are being generated right now, used to reject recursion
+    pub device_fn_stack: Vec<String>,
+    // if this is generating the body of a device function, these are its parameters
+    // these are the only identifiers the body can use
+    pub device_fn_params: Option<Vec<String>>,
+    // how many elements of the first dimension each work-item goes over (in a grid-stride loop) and how many
+    // of those are unrolled into each iteration of that loop, set with gpu_do!(launch(elements_per_thread = 4, unroll = 2))
+    pub elements_per_thread: u32,
+    pub unroll: u32,
+    // how many loops (inside of the launched loop) the statement being generated is in
+    // break and continue can only be used inside of those loops
+    pub loop_depth: u32,
+    // whether or not an index is being generated, integer literals (like the 0 in flag[0]) can only be used in indices
+    pub in_index: bool,
+    // the variables declared in launched code that are in scope, innermost last
+    // and how many variables have been declared so far (used to give each one its own name)
+    pub locals: Vec<Local>,
+    pub num_locals: usize,
     // used for propogating errors
     pub failed_to_generate: bool,
     pub errors: Vec<Error>,
@@ -106,9 +304,813 @@ impl Generator {
             failed_to_generate: false,
             block_allowed: true,
             is_next_ident_array: false,
+            bounds_checks: vec![],
+            lifting_allowed: true,
+            device_fns: HashMap::new(),
+            device_consts: HashMap::new(),
+            functions: String::new(),
+            generated_device_fns: vec![],
+            generated_device_consts: vec![],
+            device_fn_stack: vec![],
+            device_fn_params: None,
+            elements_per_thread: 1,
+            unroll: 1,
+            loop_depth: 0,
+            in_index: false,
+            locals: vec![],
+            num_locals: 0,
             errors: vec![],
         }
     }
+
+    // generates an OpenCL function for the given device function (and any device functions and constants it uses)
+    // a device function must return an f32 and its body must be an expression that may come after some variables declared with let
+    fn generate_device_fn(&mut self, name: &str, device_fn: &ItemFn) {
+        let params = match get_device_fn_params(device_fn) {
+            Ok(params) => params,
+            Err(error) => {
+                self.failed_to_generate = true;
+                self.errors.push(error);
+                return;
+            }
+        };
+        let returns_f32 = if let ReturnType::Type(_, ty) = &device_fn.sig.output {
+            DeviceFnParamType::from(ty) == Some(DeviceFnParamType::Float)
+        } else {
+            false
+        };
+        let (body, locals) = match device_fn.block.stmts.split_last() {
+            Some((Stmt::Expr(expr), locals))
+                if returns_f32 && locals.iter().all(|stmt| matches!(stmt, Stmt::Local(_))) =>
+            {
+                (expr, locals)
+            }
+            _ => {
+                self.failed_to_generate = true;
+                self.errors.push(Error::new(
+                    device_fn.sig.ident.span(),
+                    "functions called from launched code must return an `f32` and their body must be a single expression (that may come after some `let`s)",
+                ));
+                return;
+            }
+        };
+
+        // the body is generated by a separate generator that only knows about the parameters
+        // expressions in the body can't be lifted since they depend on the parameters
+        // the parameters are in scope like variables so that the types of variables declared from them can be inferred
+        let mut generator = Generator::from(vec![]);
+        generator.device_fns = self.device_fns.clone();
+        generator.device_consts = self.device_consts.clone();
+        generator.generated_device_fns = self.generated_device_fns.clone();
+        generator.generated_device_consts = self.generated_device_consts.clone();
+        generator.device_fn_stack = self.device_fn_stack.clone();
+        generator.device_fn_stack.push(String::from(name));
+        generator.device_fn_params = Some(params.iter().map(|(param, _)| param.clone()).collect());
+        generator.locals = params
+            .iter()
+            .map(|(param, ty)| Local {
+                name: param.clone(),
+                opencl_name: format!("emumumu_{}", param),
+                ty: *ty,
+            })
+            .collect();
+        generator.lifting_allowed = false;
+        for local in locals {
+            if let Stmt::Local(local) = local {
+                generator.body += "\t";
+                generator.visit_local(local);
+            }
+        }
+        generator.body += "\treturn ";
+        generator.visit_expr(body);
+        self.errors.append(&mut generator.errors);
+        if generator.failed_to_generate {
+            self.failed_to_generate = true;
+            return;
+        }
+
+        self.functions += &generator.functions;
+        self.functions += "float emumumu_";
+        self.functions += name;
+        self.functions += "(";
+        self.functions += &params
+            .iter()
+            .map(|(param, ty)| format!("{} emumumu_{}", ty.to_opencl(), param))
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.functions += ") {\n";
+        self.functions += &generator.body;
+        self.functions += ";\n}\n";
+        self.generated_device_fns = generator.generated_device_fns;
+        self.generated_device_fns.push(String::from(name));
+        self.generated_device_consts = generator.generated_device_consts;
+    }
+
+    // generates an OpenCL constant for the given device constant (and any device constants it uses)
+    // a device constant must be an f32 or an index and its value can only use literals and other constants
+    fn generate_device_const(&mut self, name: &str, device_const: &ItemConst) {
+        let ty = match DeviceFnParamType::from(&device_const.ty) {
+            Some(DeviceFnParamType::Array) | None => {
+                self.failed_to_generate = true;
+                self.errors.push(Error::new(
+                    device_const.ty.span(),
+                    "constants used in launched code must be `f32`, `usize`, or `i32`",
+                ));
+                return;
+            }
+            Some(ty) => ty,
+        };
+
+        let mut generator = Generator::from(vec![]);
+        generator.device_consts = self.device_consts.clone();
+        generator.generated_device_consts = self.generated_device_consts.clone();
+        generator.device_fn_params = Some(vec![]);
+        generator.lifting_allowed = false;
+        match ty {
+            DeviceFnParamType::Int => generator.visit_index(&device_const.expr),
+            _ => generator.visit_expr(&device_const.expr),
+        }
+        self.errors.append(&mut generator.errors);
+        if generator.failed_to_generate {
+            self.failed_to_generate = true;
+            return;
+        }
+
+        self.functions += &generator.functions;
+        self.functions += "constant ";
+        self.functions += ty.to_opencl();
+        self.functions += " emumumu_";
+        self.functions += name;
+        self.functions += " = ";
+        self.functions += &generator.body;
+        self.functions += ";\n";
+        self.generated_device_consts = generator.generated_device_consts;
+        self.generated_device_consts.push(String::from(name));
+    }
+
+    // the type of the device constant with the given name, if there is one (and it isn't shadowed by a variable)
+    fn device_const_type(&self, path: &ExprPath) -> Option<DeviceFnParamType> {
+        let name = path.path.get_ident()?.to_string();
+        if self.find_local(path).is_some() {
+            return None;
+        }
+        self.device_consts
+            .get(&name)
+            .and_then(|device_const| DeviceFnParamType::from(&device_const.ty))
+    }
+
+    // generates code for a call to a device function
+    // arrays are passed in as pointers and scalars are passed in by value
+    fn visit_device_fn_call(&mut self, call: &ExprCall) {
+        let name = if let Expr::Path(path) = &*call.func {
+            path.path.get_ident().map(|ident| ident.to_string())
+        } else {
+            None
+        };
+        let device_fn = name
+            .as_ref()
+            .and_then(|name| self.device_fns.get(name))
+            .cloned();
+        let (name, device_fn) = match (name, device_fn) {
+            (Some(name), Some(device_fn)) => (name, device_fn),
+            _ => {
+                self.failed_to_generate = true;
+                self.errors.push(Error::new(
+                    call.func.span(),
+                    "can only call functions defined inside of the function tagged with `#[gpu_use]`",
+                ));
+                return;
+            }
+        };
+        if self.device_fn_stack.contains(&name) {
+            self.failed_to_generate = true;
+            self.errors.push(Error::new(
+                call.func.span(),
+                "functions called from launched code can't be recursive",
+            ));
+            return;
+        }
+        if !self.generated_device_fns.contains(&name) {
+            self.generate_device_fn(&name, &device_fn);
+        }
+        let params = match get_device_fn_params(&device_fn) {
+            Ok(params) => params,
+            Err(_) => return, // the error was already reported when generating the function
+        };
+
+        self.body += "emumumu_";
+        self.body += &name;
+        self.body += "(";
+        for (i, (arg, (_, ty))) in call.args.iter().zip(params.iter()).enumerate() {
+            if i > 0 {
+                self.body += ", ";
+            }
+            match ty {
+                DeviceFnParamType::Array => {
+                    // an array must be passed in as the identifier of an array (maybe borrowed like &data)
+                    let array = match arg {
+                        Expr::Reference(reference) => &*reference.expr,
+                        _ => arg,
+                    };
+                    if let Expr::Path(_path) = array {
+                        self.is_next_ident_array = true;
+                        self.visit_expr(array);
+                        self.is_next_ident_array = false;
+                    } else {
+                        self.failed_to_generate = true;
+                        self.errors.push(Error::new(
+                            arg.span(),
+                            "expected name of a 1D array",
+                        ));
+                    }
+                }
+                DeviceFnParamType::Int => self.visit_index(arg),
+                DeviceFnParamType::Float => self.visit_expr(arg),
+            }
+        }
+        self.body += ")";
+    }
+
+    // lifts the given expression out of the kernel if it can be evaluated on the host before launching
+    // the expression is then passed in as a parameter instead of being generated as code
+    // returns false if it can't be lifted
+    fn lift(&mut self, expr: &Expr) -> bool {
+        // identifiers and literals are already handled without lifting
+        if !self.lifting_allowed || matches!(expr, Expr::Path(_) | Expr::Lit(_)) {
+            return false;
+        }
+
+        let mut checker = HostExprChecker {
+            global_work_size_dims: &self.global_work_size_dims,
+            locals: &self.locals,
+            is_host_expr: true,
+        };
+        checker.visit_expr(expr);
+        if !checker.is_host_expr {
+            return false;
+        }
+
+        let name = format!(
+            "_lifted_{}",
+            self.params
+                .iter()
+                .filter(|param| param.host_expr.is_some())
+                .count()
+        );
+        self.body += "emumumu_";
+        self.body += &name;
+        self.params.push(Parameter {
+            is_array: false,
+            name,
+            fields: vec![],
+            host_expr: Some(expr.clone()),
+            is_written: false,
+        });
+        true
+    }
+
+    // generates code for an index into an array
+    fn visit_index(&mut self, index: &Expr) {
+        let lifting_allowed = self.lifting_allowed;
+        let in_index = self.in_index;
+        self.lifting_allowed = false;
+        self.in_index = true;
+        self.visit_expr(index);
+        self.lifting_allowed = lifting_allowed;
+        self.in_index = in_index;
+    }
+
+    // records that the given array is indexed with the given index
+    // if the index is just the variable of a dimension, we can check that the array is long enough at run-time
+    fn record_index(&mut self, array: &Expr, index: &Expr) {
+        if let (Expr::Path(array), Expr::Path(index)) = (array, index) {
+            if let (Some(array), Some(index)) = (array.path.get_ident(), index.path.get_ident()) {
+                for (i, global_work_size_dim) in self.global_work_size_dims.iter().enumerate() {
+                    match global_work_size_dim {
+                        Dim::RangeFromZero(name, _) => {
+                            let bounds_check = (array.to_string(), i);
+                            if index.to_string() == *name && !self.bounds_checks.contains(&bounds_check) {
+                                self.bounds_checks.push(bounds_check);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // generates code for a series of statements, each indented by the given number of tabs
+    // a statement is an assignment, a while loop, a loop, an if (maybe with an else), or a break or continue in a loop
+    fn visit_stmts(&mut self, stmts: &[Stmt], indent: usize) {
+        let tabs = "\t".repeat(indent);
+        // variables declared in these statements go out of scope after them
+        let num_locals_in_scope = self.locals.len();
+        for stmt in stmts {
+            match stmt {
+                Stmt::Local(local) => {
+                    self.body += &tabs;
+                    self.visit_local(local);
+                }
+                // loops and ifs don't need to be followed by a semicolon
+                Stmt::Semi(expr, _) | Stmt::Expr(expr) => {
+                    match expr {
+                        Expr::Assign(assign) if matches!(stmt, Stmt::Semi(..)) => {
+                            self.body += &tabs;
+                            if self.visit_assignee(&assign.left) {
+                                self.body += " = ";
+                                self.visit_value(&assign.left, &assign.right);
+                                self.body += ";\n";
+                            }
+                        }
+                        // or a compound assignment with one of the binops we handle
+                        Expr::AssignOp(assign_op) if matches!(stmt, Stmt::Semi(..)) => {
+                            let is_index = self.is_index_expr(&assign_op.left);
+                            let op = match assign_op.op {
+                                BinOp::AddEq(_) => Some(" += "),
+                                BinOp::SubEq(_) => Some(" -= "),
+                                BinOp::MulEq(_) => Some(" *= "),
+                                BinOp::DivEq(_) => Some(" /= "),
+                                BinOp::RemEq(_) if is_index => Some(" %= "),
+                                _ => None,
+                            };
+                            if let (BinOp::RemEq(_), false) = (assign_op.op, is_index) {
+                                // there is no %= for floats so data[i] %= x becomes data[i] = fmod(data[i], x)
+                                self.body += &tabs;
+                                if self.visit_assignee(&assign_op.left) {
+                                    self.body += " = fmod(";
+                                    self.visit_assignee(&assign_op.left);
+                                    self.body += ", ";
+                                    self.visit_expr(&assign_op.right);
+                                    self.body += ");\n";
+                                }
+                            } else if let Some(op) = op {
+                                self.body += &tabs;
+                                if self.visit_assignee(&assign_op.left) {
+                                    self.body += op;
+                                    self.visit_value(&assign_op.left, &assign_op.right);
+                                    self.body += ";\n";
+                                }
+                            } else {
+                                self.failed_to_generate = true;
+                                self.errors.push(Error::new(
+                                    (assign_op.op.clone()).span(),
+                                    "unsupported compound assignment",
+                                ));
+                            }
+                        }
+                        // a while loop runs for as long as its condition holds, which can depend on the data (like data[i] > 1.0)
+                        Expr::While(expr_while) if expr_while.label.is_none() => {
+                            self.body += &tabs;
+                            self.body += "while (";
+                            self.visit_condition(&expr_while.cond);
+                            self.body += ") {\n";
+                            self.loop_depth += 1;
+                            self.visit_stmts(&expr_while.body.stmts, indent + 1);
+                            self.loop_depth -= 1;
+                            self.body += &tabs;
+                            self.body += "}\n";
+                        }
+                        // a loop runs until it is broken out of
+                        Expr::Loop(expr_loop) if expr_loop.label.is_none() => {
+                            self.body += &tabs;
+                            self.body += "while (1) {\n";
+                            self.loop_depth += 1;
+                            self.visit_stmts(&expr_loop.body.stmts, indent + 1);
+                            self.loop_depth -= 1;
+                            self.body += &tabs;
+                            self.body += "}\n";
+                        }
+                        Expr::If(expr_if) => {
+                            self.body += &tabs;
+                            self.visit_if(expr_if, indent);
+                        }
+                        // breaking out of (or continuing) the launched loop itself would need every iteration to know about it
+                        // so only loops inside of the launched loop can be broken out of
+                        Expr::Break(expr_break)
+                            if expr_break.label.is_none() && expr_break.expr.is_none() =>
+                        {
+                            if self.loop_depth > 0 {
+                                self.body += &tabs;
+                                self.body += "break;\n";
+                            } else {
+                                self.failed_to_generate = true;
+                                self.errors.push(Error::new(
+                                    expr_break.span(),
+                                    "can only break out of a loop inside of a launched loop",
+                                ));
+                            }
+                        }
+                        Expr::Continue(expr_continue) if expr_continue.label.is_none() => {
+                            if self.loop_depth > 0 {
+                                self.body += &tabs;
+                                self.body += "continue;\n";
+                            } else {
+                                self.failed_to_generate = true;
+                                self.errors.push(Error::new(
+                                    expr_continue.span(),
+                                    "can only continue a loop inside of a launched loop",
+                                ));
+                            }
+                        }
+                        _ => {
+                            self.failed_to_generate = true;
+                            self.errors.push(Error::new(
+                                (expr.clone()).span(),
+                                "only an assignment, a loop, an if, or a break or continue is a supported statement",
+                            ));
+                        }
+                    }
+                }
+                _ => {
+                    self.failed_to_generate = true;
+                    self.errors
+                        .push(Error::new((stmt.clone()).span(), "unsupported item"));
+                }
+            }
+        }
+        self.locals.truncate(num_locals_in_scope);
+    }
+
+    // generates code for declaring a variable with let
+    // the type is either written down or inferred from the expression the variable is initialized with
+    fn visit_local(&mut self, local: &syn::Local) {
+        let (pat, ty) = match &local.pat {
+            Pat::Type(pat_type) => (&*pat_type.pat, Some(&*pat_type.ty)),
+            pat => (pat, None),
+        };
+        let name = match pat {
+            Pat::Ident(pat_ident) if pat_ident.by_ref.is_none() && pat_ident.subpat.is_none() => {
+                pat_ident.ident.to_string()
+            }
+            _ => {
+                self.failed_to_generate = true;
+                self.errors.push(Error::new(
+                    pat.span(),
+                    "can only declare a variable with a name (like `let x = ...`)",
+                ));
+                return;
+            }
+        };
+        let init = local.init.as_ref().map(|(_, init)| &**init);
+        let ty = match (ty, init) {
+            (Some(ty), _) => match DeviceFnParamType::from(ty) {
+                Some(DeviceFnParamType::Array) | None => {
+                    self.failed_to_generate = true;
+                    self.errors.push(Error::new(
+                        ty.span(),
+                        "variables in launched code must be `f32`, `usize`, or `i32`",
+                    ));
+                    return;
+                }
+                Some(ty) => ty,
+            },
+            (None, Some(init)) => match self.infer_type(init) {
+                Some(ty) => ty,
+                None => {
+                    self.failed_to_generate = true;
+                    self.errors.push(Error::new(
+                        local.pat.span(),
+                        format!(
+                            "can't infer the type of `{}`, write it down (like `let {}: f32 = ...` or `let {}: usize = ...`)",
+                            name, name, name
+                        ),
+                    ));
+                    return;
+                }
+            },
+            (None, None) => {
+                self.failed_to_generate = true;
+                self.errors.push(Error::new(
+                    local.pat.span(),
+                    format!(
+                        "can't infer the type of `{}` without a value, write it down (like `let {}: f32;`)",
+                        name, name
+                    ),
+                ));
+                return;
+            }
+        };
+
+        let opencl_name = format!("emumumu__let{}_{}", self.num_locals, name);
+        self.num_locals += 1;
+        self.body += ty.to_opencl();
+        self.body += " ";
+        self.body += &opencl_name;
+        // the initializer is generated before the variable is in scope since it could use a variable it shadows
+        if let Some(init) = init {
+            self.body += " = ";
+            match ty {
+                DeviceFnParamType::Int => self.visit_index(init),
+                _ => self.visit_expr(init),
+            }
+        }
+        self.body += ";\n";
+        self.locals.push(Local {
+            name,
+            opencl_name,
+            ty,
+        });
+    }
+
+    // generates code for the value assigned to the given assignee
+    // the value is an index if the assignee is a variable holding an index
+    fn visit_value(&mut self, assignee: &Expr, value: &Expr) {
+        if self.is_index_expr(assignee) {
+            self.visit_index(value);
+        } else {
+            self.visit_expr(value);
+        }
+    }
+
+    // the variable declared in launched code with the given name that is in scope, if there is one
+    fn find_local(&self, path: &ExprPath) -> Option<&Local> {
+        self.locals
+            .iter()
+            .rev()
+            .find(|local| path.path.is_ident(&local.name))
+    }
+
+    // infers whether the given expression is a float or an index
+    // returns None if it can't tell (like for a comparison or a block)
+    fn infer_type(&self, expr: &Expr) -> Option<DeviceFnParamType> {
+        match expr {
+            Expr::Lit(lit) => match lit.lit {
+                Lit::Float(_) => Some(DeviceFnParamType::Float),
+                Lit::Int(_) => Some(DeviceFnParamType::Int),
+                _ => None,
+            },
+            Expr::Path(path) => {
+                if let Some(local) = self.find_local(path) {
+                    Some(local.ty)
+                } else if let Some(ty) = self.device_const_type(path) {
+                    Some(ty)
+                } else if self.is_index_expr(expr) {
+                    Some(DeviceFnParamType::Int)
+                } else if path.path.get_ident().is_some() {
+                    // anything else is passed in as a float
+                    Some(DeviceFnParamType::Float)
+                } else {
+                    None
+                }
+            }
+            // elements of arrays, fields of elements of arrays of structures, and the results of functions are all floats
+            Expr::Index(_) | Expr::Field(_) | Expr::Call(_) => Some(DeviceFnParamType::Float),
+            Expr::Cast(cast) => DeviceFnParamType::from(&cast.ty)
+                .filter(|ty| *ty != DeviceFnParamType::Array),
+            // both sides of an arithmetic operator have the same type so whichever side can be inferred is the type
+            Expr::Binary(binary) => match binary.op {
+                BinOp::Add(_)
+                | BinOp::Sub(_)
+                | BinOp::Mul(_)
+                | BinOp::Div(_)
+                | BinOp::Rem(_)
+                | BinOp::Shl(_)
+                | BinOp::Shr(_)
+                | BinOp::BitAnd(_)
+                | BinOp::BitOr(_)
+                | BinOp::BitXor(_) => self
+                    .infer_type(&binary.left)
+                    .or_else(|| self.infer_type(&binary.right)),
+                _ => None,
+            },
+            Expr::Unary(unary) if matches!(unary.op, UnOp::Neg(_)) => self.infer_type(&unary.expr),
+            Expr::Paren(paren) => self.infer_type(&paren.expr),
+            _ => None,
+        }
+    }
+
+    // generates code for an if (and any else if or else that follows it)
+    // the caller has already indented the first line
+    fn visit_if(&mut self, expr_if: &ExprIf, indent: usize) {
+        let tabs = "\t".repeat(indent);
+        self.body += "if (";
+        self.visit_condition(&expr_if.cond);
+        self.body += ") {\n";
+        self.visit_stmts(&expr_if.then_branch.stmts, indent + 1);
+        self.body += &tabs;
+        self.body += "}";
+        match expr_if.else_branch.as_ref().map(|(_, else_branch)| &**else_branch) {
+            Some(Expr::If(else_if)) => {
+                self.body += " else ";
+                self.visit_if(else_if, indent);
+            }
+            Some(Expr::Block(else_block)) => {
+                self.body += " else {\n";
+                self.visit_stmts(&else_block.block.stmts, indent + 1);
+                self.body += &tabs;
+                self.body += "}\n";
+            }
+            _ => self.body += "\n",
+        }
+    }
+
+    // checks if the given expression is an index (an integer) rather than a float
+    // this is the case if it uses the variable of a dimension (outside of indexing an array) or an integer literal
+    fn is_index_expr(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Lit(ExprLit {
+                lit: Lit::Int(_), ..
+            }) => true,
+            Expr::Path(path) => match self.find_local(path) {
+                Some(local) => local.ty == DeviceFnParamType::Int,
+                None => {
+                    self.device_const_type(path) == Some(DeviceFnParamType::Int)
+                        || self.global_work_size_dims.iter().any(|dim| match dim {
+                            Dim::RangeFromZero(name, _) => path.path.is_ident(name),
+                        })
+                }
+            },
+            Expr::Cast(cast) => DeviceFnParamType::from(&cast.ty) == Some(DeviceFnParamType::Int),
+            Expr::Binary(binary) => {
+                self.is_index_expr(&binary.left) || self.is_index_expr(&binary.right)
+            }
+            Expr::Unary(unary) => self.is_index_expr(&unary.expr),
+            Expr::Paren(paren) => self.is_index_expr(&paren.expr),
+            _ => false,
+        }
+    }
+
+    // generates code for the condition of a while loop or an if
+    // a condition is a comparison of floats (like data[i] > 1.0) or of indices (like i < j) or a combination of conditions with &&, ||, and !
+    fn visit_condition(&mut self, cond: &Expr) {
+        match cond {
+            Expr::Binary(binary) => {
+                let (op, is_logical) = match binary.op {
+                    BinOp::Lt(_) => (" < ", false),
+                    BinOp::Le(_) => (" <= ", false),
+                    BinOp::Gt(_) => (" > ", false),
+                    BinOp::Ge(_) => (" >= ", false),
+                    BinOp::Eq(_) => (" == ", false),
+                    BinOp::Ne(_) => (" != ", false),
+                    BinOp::And(_) => (" && ", true),
+                    BinOp::Or(_) => (" || ", true),
+                    _ => {
+                        self.failed_to_generate = true;
+                        self.errors.push(Error::new(
+                            (binary.op.clone()).span(),
+                            "expected a comparison or a combination of conditions",
+                        ));
+                        return;
+                    }
+                };
+                if is_logical {
+                    self.visit_condition(&binary.left);
+                    self.body += op;
+                    self.visit_condition(&binary.right);
+                } else if self.is_index_expr(&binary.left) || self.is_index_expr(&binary.right) {
+                    // a comparison of indices (like i < 500 or i & 1 == 0)
+                    self.visit_index(&binary.left);
+                    self.body += op;
+                    self.visit_index(&binary.right);
+                } else {
+                    self.visit_expr(&binary.left);
+                    self.body += op;
+                    self.visit_expr(&binary.right);
+                }
+            }
+            Expr::Unary(unary) if matches!(unary.op, UnOp::Not(_)) => {
+                self.body += "!(";
+                self.visit_condition(&unary.expr);
+                self.body += ")";
+            }
+            Expr::Paren(paren) => {
+                self.body += "(";
+                self.visit_condition(&paren.expr);
+                self.body += ")";
+            }
+            Expr::Lit(ExprLit {
+                lit: Lit::Bool(lit_bool),
+                ..
+            }) => {
+                self.body += if lit_bool.value { "1" } else { "0" };
+            }
+            _ => {
+                self.failed_to_generate = true;
+                self.errors.push(Error::new(
+                    cond.span(),
+                    "expected a comparison or a combination of conditions",
+                ));
+            }
+        }
+    }
+
+    // generates code for the left hand side of an assignment
+    // this must be either an element of an array (data[i]) or a field of an element of an array of structures (particles[i].x)
+    // returns false if it isn't
+    fn visit_assignee(&mut self, left: &Expr) -> bool {
+        let is_assignee = match left {
+            Expr::Index(index) => {
+                // we don't allow 2D arrays so the expr must be an ident
+                if let Expr::Path(_path) = *index.expr.clone() {
+                    self.is_next_ident_array = true;
+                    self.visit_expr(&index.expr); // we now know that the expr must be a path
+                    self.is_next_ident_array = false;
+                    self.record_index(&index.expr, &index.index);
+                    self.body += "[";
+                    self.visit_index(&index.index);
+                    self.body += "]";
+                    true
+                } else {
+                    self.failed_to_generate = true;
+                    self.errors.push(Error::new(
+                        (*index.expr.clone()).span(),
+                        "can only get index of a 1D array",
+                    ));
+                    false
+                }
+            }
+            Expr::Field(field) => {
+                self.visit_struct_array_field(field);
+                true
+            }
+            Expr::Path(path) if self.find_local(path).is_some() => {
+                self.body += &self.find_local(path).unwrap().opencl_name.clone();
+                true
+            }
+            _ => {
+                self.failed_to_generate = true;
+                self.errors.push(Error::new(
+                    (left.clone()).span(),
+                    "only assignment of an array element or a variable declared in launched code is supported",
+                ));
+                false
+            }
+        };
+
+        // the array that is assigned to is written by launched code
+        let array = match left {
+            Expr::Index(index) => Some(&*index.expr),
+            Expr::Field(field) => match &*field.base {
+                Expr::Index(index) => Some(&*index.expr),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(Expr::Path(path)) = array {
+            for param in &mut self.params {
+                if param.is_array && path.path.is_ident(&param.name) {
+                    param.is_written = true;
+                }
+            }
+        }
+
+        is_assignee
+    }
+
+    // generates code for an expression that is a field of an element of an array of structures
+    // particles[i].x becomes emumumu_particles[(emumumu_i) * emumumu_particles__stride + emumumu_particles__x]
+    fn visit_struct_array_field(&mut self, field: &ExprField) {
+        let array = if let Expr::Index(index) = &*field.base {
+            if let Expr::Path(path) = &*index.expr {
+                path.path.get_ident().map(|ident| (ident.to_string(), index))
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+        let member = if let Member::Named(member) = &field.member {
+            Some(member.to_string())
+        } else {
+            None
+        };
+
+        if self.device_fn_params.is_some() {
+            self.failed_to_generate = true;
+            self.errors.push(Error::new(
+                field.span(),
+                "functions called from launched code can't use fields of structures",
+            ));
+        } else if let (Some((name, index)), Some(member)) = (array, member) {
+            // visit the array's identifier (adding it as a parameter if needed) and then mark the field as used
+            self.is_next_ident_array = true;
+            self.visit_expr(&index.expr);
+            self.is_next_ident_array = false;
+            // note that we don't need to check that the array isn't also indexed directly (like particles[i])
+            // that is already a type error that RustC will catch for us
+            if let Some(param) = self.params.iter_mut().find(|param| param.name == name) {
+                if !param.fields.contains(&member) {
+                    param.fields.push(member.clone());
+                }
+            }
+            self.record_index(&index.expr, &index.index);
+            self.body += "[(";
+            self.visit_index(&index.index);
+            self.body += ") * emumumu_";
+            self.body += &name;
+            self.body += "__stride + emumumu_";
+            self.body += &name;
+            self.body += "__";
+            self.body += &member;
+            self.body += "]";
+        } else {
+            self.failed_to_generate = true;
+            self.errors.push(Error::new(
+                field.span(),
+                "can only get a named field of an element of a 1D array of structures",
+            ));
+        }
+    }
 }
 
 impl<'ast> Visit<'ast> for Generator {
@@ -123,76 +1125,85 @@ impl<'ast> Visit<'ast> for Generator {
             self.body += "{\n";
             for (i, global_work_size_dim) in self.global_work_size_dims.iter().enumerate() {
                 match global_work_size_dim {
+                    // if each work-item goes over more than 1 element of the first dimension,
+                    // the variable of that dimension is declared in the grid-stride loop that wraps the statements
+                    Dim::RangeFromZero(_name, _) if i == 0 && self.elements_per_thread > 1 => {
+                        self.body += "\tint emumumu__base_0 = get_global_id(0);\n";
+                        self.body += "\tint emumumu__stride_0 = get_global_size(0);\n";
+                    }
                     Dim::RangeFromZero(name, _) => {
                         self.body += "\t";
                         self.body += "int emumumu_";
                         self.body += &name;
                         self.body += " = get_global_id(";
                         self.body += &i.to_string();
-                        self.body += ");\n"
+                        self.body += ");\n";
+                        // guard against threads past the end of the loop
+                        // the global work size may be rounded up so there could be more threads than iterations
+                        self.body += "\tif (emumumu_";
+                        self.body += &name;
+                        self.body += " >= emumumu__len_";
+                        self.body += &i.to_string();
+                        self.body += ") return;\n";
                     }
                 }
             }
             // compile all statements
-            for stmt in &node.stmts {
-                match stmt {
-                    // for now, only a series of semicolon-ed statements are expected
-                    Stmt::Semi(expr, _) => {
-                        match expr {
-                            // for now, only statement allowed is assign index
-                            Expr::Assign(assign) => {
-                                if let Expr::Index(index) = *assign.left.clone() {
-                                    // we don't allow 2D arrays so the expr must be an ident
-                                    if let Expr::Path(_path) = *index.expr.clone() {
-                                        self.body += "\t";
-                                        self.is_next_ident_array = true;
-                                        self.visit_expr(&index.expr); // we now know that the expr must be a path
-                                        self.is_next_ident_array = false;
-                                        self.body += "[";
-                                        self.visit_expr(&index.index);
-                                        self.body += "] = ";
-                                        self.visit_expr(&assign.right);
-                                        self.body += ";\n";
-                                    } else {
-                                        self.failed_to_generate = true;
-                                        self.errors.push(Error::new(
-                                            (*index.expr.clone()).span(),
-                                            "can only get index of a 1D array",
-                                        ));
-                                    }
-                                } else {
-                                    self.failed_to_generate = true;
-                                    self.errors.push(Error::new(
-                                        (*assign.left.clone()).span(),
-                                        "only assignment of an array element is supported",
-                                    ));
-                                }
-                            }
-                            _ => {
-                                self.failed_to_generate = true;
-                                self.errors.push(Error::new(
-                                    (expr.clone()).span(),
-                                    "only an assignment is a supported statement",
-                                ));
-                            }
-                        }
+            let stmts_start = self.body.len();
+            self.visit_stmts(&node.stmts, 1);
+            // each work-item goes over its elements of the first dimension, a whole grid apart so that neighboring work-items
+            // still access neighboring elements
+            // the statements are copied (each in their own scope) for each element that is unrolled into an iteration
+            if self.elements_per_thread > 1 {
+                let stmts = self.body.split_off(stmts_start);
+                let first_var = match &self.global_work_size_dims[0] {
+                    Dim::RangeFromZero(name, _) => name.clone(),
+                };
+                self.body += &format!(
+                    "\tfor (int emumumu__element = 0; emumumu__element < {}; emumumu__element += {}) {{\n",
+                    self.elements_per_thread, self.unroll
+                );
+                for j in 0..self.unroll {
+                    self.body += "\t\t{\n";
+                    self.body += &format!(
+                        "\t\t\tint emumumu_{} = emumumu__base_0 + (emumumu__element + {}) * emumumu__stride_0;\n",
+                        first_var, j
+                    );
+                    // an element past the end of the loop means the rest of the elements of this work-item are too
+                    if self.elements_per_thread % self.unroll != 0 {
+                        self.body += &format!(
+                            "\t\t\tif (emumumu__element + {} >= {}) return;\n",
+                            j, self.elements_per_thread
+                        );
                     }
-                    _ => {
-                        self.failed_to_generate = true;
-                        self.errors
-                            .push(Error::new((stmt.clone()).span(), "unsupported item"));
+                    self.body += &format!(
+                        "\t\t\tif (emumumu_{} >= emumumu__len_0) return;\n",
+                        first_var
+                    );
+                    for line in stmts.lines() {
+                        self.body += "\t\t";
+                        self.body += line;
+                        self.body += "\n";
                     }
+                    self.body += "\t\t}\n";
                 }
+                self.body += "\t}\n";
             }
+            // the length of each dimension is passed in after all the other parameters
             self.signature += &self
                 .params
                 .iter()
                 .map(|param| param.to_string())
+                .chain(
+                    (0..self.global_work_size_dims.len())
+                        .map(|i| String::from("int emumumu__len_") + &i.to_string()),
+                )
                 .collect::<Vec<_>>()
                 .join(", ");
             self.signature += ") ";
             self.body += "}";
 
+            self.code += &self.functions;
             self.code += &self.signature;
             self.code += &self.body;
         } else {
@@ -203,7 +1214,31 @@ impl<'ast> Visit<'ast> for Generator {
     }
     // this is invoked for all expressions
     fn visit_expr(&mut self, node: &'ast Expr) {
+        // scalar expressions that don't depend on the loop (like (scale - offset).abs()) are evaluated on the host
+        // this lets them use anything Rust can do, not just the subset we can generate code for
+        if self.lift(node) {
+            return;
+        }
+
         match node {
+            Expr::Path(path) if self.find_local(path).is_some() => {
+                self.body += &self.find_local(path).unwrap().opencl_name.clone();
+            }
+            Expr::Path(path)
+                if self.find_local(path).is_none()
+                    && path
+                        .path
+                        .get_ident()
+                        .map_or(false, |ident| self.device_consts.contains_key(&ident.to_string())) =>
+            {
+                let name = path.path.get_ident().unwrap().to_string();
+                if !self.generated_device_consts.contains(&name) {
+                    let device_const = self.device_consts[&name].clone();
+                    self.generate_device_const(&name, &device_const);
+                }
+                self.body += "emumumu_";
+                self.body += &name;
+            }
             Expr::Path(path) => {
                 // we only work with paths that are identifiers
                 if let Some(ident) = path.path.get_ident() {
@@ -244,11 +1279,25 @@ impl<'ast> Visit<'ast> for Generator {
                             is_alread_added = true;
                         }
                     }
+                    // the body of a device function can only use its own parameters
+                    if let Some(device_fn_params) = &self.device_fn_params {
+                        if !device_fn_params.contains(&ident.to_string()) {
+                            self.failed_to_generate = true;
+                            self.errors.push(Error::new(
+                                ident.span(),
+                                "functions called from launched code can only use their parameters and constants",
+                            ));
+                        }
+                        is_already_declared = true;
+                    }
                     // if not yet added and not already declared, add this as a parameter
                     if !is_already_declared && !is_alread_added {
                         self.params.push(Parameter {
                             is_array: self.is_next_ident_array,
                             name: ident.to_string(),
+                            fields: vec![],
+                            host_expr: None,
+                            is_written: false,
                         })
                     }
                 } else {
@@ -265,8 +1314,9 @@ impl<'ast> Visit<'ast> for Generator {
                     self.is_next_ident_array = true;
                     self.visit_expr(&index.expr); // we now know that the expr must be a path
                     self.is_next_ident_array = false;
+                    self.record_index(&index.expr, &index.index);
                     self.body += "[";
-                    self.visit_expr(&index.index);
+                    self.visit_index(&index.index);
                     self.body += "]";
                 } else {
                     self.failed_to_generate = true;
@@ -278,11 +1328,16 @@ impl<'ast> Visit<'ast> for Generator {
             }
             Expr::Lit(lit) => {
                 if let Lit::Float(float) = &lit.lit {
-                    let float_val = float.base10_parse::<f32>();
+                    let float_val = float
+                        .base10_parse::<f32>()
+                        .ok()
+                        .filter(|val| val.is_finite());
 
-                    if float_val.is_ok() {
+                    if let Some(float_val) = float_val {
                         // currently, we only support f32
-                        self.body += &float_val.unwrap().to_string();
+                        // the literal always has a decimal point (or an exponent) and a suffix so that it is never an integer in OpenCL
+                        // otherwise something like 1.0 / 2.0 would become 1 / 2, which is integer division
+                        self.body += &format!("{:?}f", float_val);
                     } else {
                         self.failed_to_generate = true;
                         self.errors.push(Error::new(
@@ -290,6 +1345,16 @@ impl<'ast> Visit<'ast> for Generator {
                             "expected 32-bit floating point number",
                         ));
                     }
+                } else if let (Lit::Int(int), true) = (&lit.lit, self.in_index) {
+                    if let Ok(int_val) = int.base10_parse::<i32>() {
+                        self.body += &int_val.to_string();
+                    } else {
+                        self.failed_to_generate = true;
+                        self.errors.push(Error::new(
+                            (int.clone()).span(),
+                            "expected 32-bit integer",
+                        ));
+                    }
                 } else {
                     self.failed_to_generate = true;
                     self.errors.push(Error::new(
@@ -299,19 +1364,55 @@ impl<'ast> Visit<'ast> for Generator {
                 }
             }
             Expr::Binary(binary) => {
-                // only handle a couple of binops
-                // but adding more is super easy! right?
-                // this should be an easy contribution, I hope
-                match binary.op {
-                    BinOp::Mul(_) => {
+                // the operators are the same in OpenCL except for % of floats, which is fmod
+                // bitwise operators and shifts only work on indices and are parenthesized since they have a different precedence
+                // in OpenCL than in Rust (i & 1 == 0 is (i & 1) == 0 in Rust but i & (1 == 0) in OpenCL)
+                let op = match binary.op {
+                    BinOp::Add(_) => Some(" + "),
+                    BinOp::Sub(_) => Some(" - "),
+                    BinOp::Mul(_) => Some(" * "),
+                    BinOp::Div(_) => Some(" / "),
+                    BinOp::Rem(_) if self.in_index => Some(" % "),
+                    BinOp::Shl(_) if self.in_index => Some(" << "),
+                    BinOp::Shr(_) if self.in_index => Some(" >> "),
+                    BinOp::BitAnd(_) if self.in_index => Some(" & "),
+                    BinOp::BitOr(_) if self.in_index => Some(" | "),
+                    BinOp::BitXor(_) if self.in_index => Some(" ^ "),
+                    _ => None,
+                };
+                match (op, &binary.op) {
+                    (Some(op), BinOp::Add(_))
+                    | (Some(op), BinOp::Sub(_))
+                    | (Some(op), BinOp::Mul(_))
+                    | (Some(op), BinOp::Div(_)) => {
+                        self.visit_expr(&binary.left);
+                        self.body += op;
+                        self.visit_expr(&binary.right);
+                    }
+                    (Some(op), _) => {
+                        self.body += "(";
                         self.visit_expr(&binary.left);
-                        self.body += " * ";
+                        self.body += op;
                         self.visit_expr(&binary.right);
+                        self.body += ")";
                     }
-                    BinOp::Add(_) => {
+                    (None, BinOp::Rem(_)) => {
+                        self.body += "fmod(";
                         self.visit_expr(&binary.left);
-                        self.body += " + ";
+                        self.body += ", ";
                         self.visit_expr(&binary.right);
+                        self.body += ")";
+                    }
+                    (None, BinOp::Shl(_))
+                    | (None, BinOp::Shr(_))
+                    | (None, BinOp::BitAnd(_))
+                    | (None, BinOp::BitOr(_))
+                    | (None, BinOp::BitXor(_)) => {
+                        self.failed_to_generate = true;
+                        self.errors.push(Error::new(
+                            (binary.op.clone()).span(),
+                            "bitwise operators and shifts can only be used in indices",
+                        ));
                     }
                     _ => {
                         self.failed_to_generate = true;
@@ -322,6 +1423,34 @@ impl<'ast> Visit<'ast> for Generator {
                     }
                 }
             }
+            // casts between floats and indices (like i as f32 or x as usize)
+            Expr::Cast(cast) => match DeviceFnParamType::from(&cast.ty) {
+                Some(ty) if ty != DeviceFnParamType::Array => {
+                    self.body += "((";
+                    self.body += ty.to_opencl();
+                    self.body += ")(";
+                    if self.is_index_expr(&cast.expr) {
+                        self.visit_index(&cast.expr);
+                    } else {
+                        self.visit_expr(&cast.expr);
+                    }
+                    self.body += "))";
+                }
+                _ => {
+                    self.failed_to_generate = true;
+                    self.errors.push(Error::new(
+                        cast.ty.span(),
+                        "can only cast to `f32`, `usize`, or `i32`",
+                    ));
+                }
+            },
+            Expr::Unary(unary) if matches!(unary.op, UnOp::Neg(_)) => {
+                self.body += "(-";
+                self.visit_expr(&unary.expr);
+                self.body += ")";
+            }
+            Expr::Field(field) => self.visit_struct_array_field(field),
+            Expr::Call(call) => self.visit_device_fn_call(call),
             Expr::Paren(paren) => {
                 // pretty straightforward...
                 self.body += "(";
@@ -1,5 +1,7 @@
 // for parsing Rust
 extern crate syn;
+use quote::ToTokens;
+use syn::fold::Fold;
 use syn::*;
 
 // this represents a dimension
@@ -17,7 +19,22 @@ use syn::*;
 // like get_global_id(x) where x is the dimension you want to know your position in (either 0 or 1 or 2)
 #[derive(Debug, Clone)]
 pub enum Dim {
-    RangeFromZero(String, i32), // TODO add support for iteration over &mut [f32], [f32], etc.
+    RangeFromZero(String, Size), // TODO add support for iteration over &mut [f32], [f32], etc.
+}
+
+// this represents the size of a dimension
+//
+// the size is either a literal (like 1000), the name of a variable or constant (like width or N) that
+// holds the size at run-time, the length of a variable (like data.len()), or a simple arithmetic
+// expression of constants and variables (like 2 * N) that is evaluated at run-time
+//
+// expressions of only literals (like 2 * 512) are evaluated at compile-time to a literal
+#[derive(Debug, Clone)]
+pub enum Size {
+    Literal(i32),
+    Variable(String),
+    Length(String),
+    Expression(String),
 }
 
 // tries to identify dimensions of global work for for loop and nested for loops
@@ -71,96 +88,92 @@ pub fn get_global_work_size(
     // there are many different kinds of ranges you could have
     // so we try to find one specific kind
     //
-    // this is a giant nested expression which can be intimidating...
-    // but it is really just a bunch of if's to check if this is really the
+    // we look for a range from 0 to either a literal or a variable (like 0..1000 or 0..width)
+    // this is really just a bunch of if's to check if this is really the
     // kind of expr we want
     if let Expr::Range(range) = *i.expr {
-        if let Some(from) = range.from {
-            if let Some(to) = range.to {
-                if let Expr::Lit(from_lit) = *from {
-                    if let Expr::Lit(to_lit) = *to {
-                        if let Lit::Int(from_lit_int) = from_lit.lit {
-                            if let Lit::Int(to_lit_int) = to_lit.lit {
-                                let from_val_raw = from_lit_int.base10_parse::<i32>();
-                                let to_val_raw = to_lit_int.base10_parse::<i32>();
-
-                                if let Ok(from_val) = from_val_raw {
-                                    if let Ok(to_val) = to_val_raw {
-                                        if from_val == 0 && from_val < to_val {
-                                            if let Some(var) = new_global_work_size_var {
-                                                // this is a case of a for loop we can work with
-                                                // so we go ahead and see if further recursion can be done on the for loop body
-
-                                                // add new global work size
-                                                new_global_work_size = Some(to_val - from_val);
-                                                global_work_size.push(Dim::RangeFromZero(
-                                                    var,
-                                                    new_global_work_size.unwrap(),
-                                                ));
-
-                                                // look at body for potential new global work sizes for further recursion
-                                                if i.body.stmts.len() == 1 {
-                                                    match &i.body.stmts[0] {
-                                                        // we should handle both cases of Expr(expr) or Semi(expr, _) exactly the same
-                                                        // either way we check for a for loop inside the passed in for loop
-                                                        // if one exists we return the new global work size and new body
-                                                        // otherwise we return the new global work size (which wouldn't have changed) and the body of the passed in for loop
-                                                        Stmt::Expr(expr) => {
-                                                            if let Expr::ForLoop(for_expr) = expr {
-                                                                let (
-                                                                    new_global_work_size,
-                                                                    block_for_kernel,
-                                                                ) = get_global_work_size(
-                                                                    global_work_size,
-                                                                    for_expr.clone(),
-                                                                );
-                                                                if block_for_kernel.is_none() {
-                                                                    return (
-                                                                        new_global_work_size,
-                                                                        Some(i.body),
-                                                                    );
-                                                                } else {
-                                                                    return (
-                                                                        new_global_work_size,
-                                                                        block_for_kernel,
-                                                                    );
-                                                                }
-                                                            }
-                                                        }
-                                                        Stmt::Semi(expr, _) => {
-                                                            if let Expr::ForLoop(for_expr) = expr {
-                                                                let (
-                                                                    new_global_work_size,
-                                                                    block_for_kernel,
-                                                                ) = get_global_work_size(
-                                                                    global_work_size,
-                                                                    for_expr.clone(),
-                                                                );
-                                                                if block_for_kernel.is_none() {
-                                                                    return (
-                                                                        new_global_work_size,
-                                                                        Some(i.body),
-                                                                    );
-                                                                } else {
-                                                                    return (
-                                                                        new_global_work_size,
-                                                                        block_for_kernel,
-                                                                    );
-                                                                }
-                                                            }
-                                                        }
-                                                        _ => {}
-                                                    }
-                                                }
-
-                                                return (global_work_size, Some(i.body));
-                                            }
-                                        }
+        if let (Some(from), Some(to), RangeLimits::HalfOpen(_)) = (range.from, range.to, range.limits) {
+            // the start must be the literal 0
+            let from_is_zero = if let Expr::Lit(from_lit) = *from {
+                if let Lit::Int(from_lit_int) = from_lit.lit {
+                    from_lit_int.base10_parse::<i32>().ok() == Some(0)
+                } else {
+                    false
+                }
+            } else {
+                false
+            };
+
+            // the end can either be a positive literal, the name of a variable, or an arithmetic expression of those
+            let to_size = match *to {
+                Expr::Lit(to_lit) => {
+                    if let Lit::Int(to_lit_int) = to_lit.lit {
+                        to_lit_int
+                            .base10_parse::<i32>()
+                            .ok()
+                            .filter(|to_val| *to_val > 0)
+                            .map(Size::Literal)
+                    } else {
+                        None
+                    }
+                }
+                Expr::Path(to_path) => to_path
+                    .path
+                    .get_ident()
+                    .map(|to_ident| Size::Variable(to_ident.to_string())),
+                to_expr @ Expr::Binary(_) | to_expr @ Expr::Paren(_) => {
+                    if is_size_expression(&to_expr) {
+                        match evaluate_size_expression(&to_expr) {
+                            Some(to_val) if to_val > 0 => Some(Size::Literal(to_val)),
+                            Some(_) => None,
+                            None => Some(Size::Expression(to_expr.to_token_stream().to_string())),
+                        }
+                    } else {
+                        None
+                    }
+                }
+                Expr::MethodCall(to_call) => {
+                    if to_call.method == "len" && to_call.args.len() == 0 && to_call.turbofish.is_none() {
+                        get_ident_of(&to_call.receiver).map(Size::Length)
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            };
+
+            if from_is_zero {
+                if let (Some(size), Some(var)) = (to_size, new_global_work_size_var) {
+                    // this is a case of a for loop we can work with
+                    // so we go ahead and see if further recursion can be done on the for loop body
+
+                    // add new global work size
+                    new_global_work_size = Some(size);
+                    global_work_size.push(Dim::RangeFromZero(var, new_global_work_size.unwrap()));
+
+                    // look at body for potential new global work sizes for further recursion
+                    if i.body.stmts.len() == 1 {
+                        match &i.body.stmts[0] {
+                            // we should handle both cases of Expr(expr) or Semi(expr, _) exactly the same
+                            // either way we check for a for loop inside the passed in for loop
+                            // if one exists we return the new global work size and new body
+                            // otherwise we return the new global work size (which wouldn't have changed) and the body of the passed in for loop
+                            Stmt::Expr(expr) | Stmt::Semi(expr, _) => {
+                                if let Expr::ForLoop(for_expr) = expr {
+                                    let (new_global_work_size, block_for_kernel) =
+                                        get_global_work_size(global_work_size, for_expr.clone());
+                                    if block_for_kernel.is_none() {
+                                        return (new_global_work_size, Some(i.body));
+                                    } else {
+                                        return (new_global_work_size, block_for_kernel);
                                     }
                                 }
                             }
+                            _ => {}
                         }
                     }
+
+                    return (global_work_size, Some(i.body));
                 }
             }
         }
@@ -171,3 +184,154 @@ pub fn get_global_work_size(
     // in an if statement (or something similar) above this
     (global_work_size, None)
 }
+
+// returns the name of the variable if the expr is just a variable
+fn get_ident_of(expr: &Expr) -> Option<String> {
+    if let Expr::Path(path) = expr {
+        path.path.get_ident().map(|ident| ident.to_string())
+    } else {
+        None
+    }
+}
+
+// returns the name of the variable being iterated over if the expr is data.iter().enumerate() or data.iter_mut().enumerate()
+fn get_enumerated(expr: &Expr) -> Option<String> {
+    if let Expr::MethodCall(enumerate) = expr {
+        if enumerate.method == "enumerate" && enumerate.args.len() == 0 {
+            if let Expr::MethodCall(iter) = &*enumerate.receiver {
+                if (iter.method == "iter" || iter.method == "iter_mut") && iter.args.len() == 0 {
+                    return get_ident_of(&iter.receiver);
+                }
+            }
+        }
+    }
+    None
+}
+
+// returns the names of the index and the element if the pattern is (i, x)
+fn get_index_and_element(pat: &Pat) -> Option<(Ident, String)> {
+    if let Pat::Tuple(tuple) = pat {
+        if tuple.elems.len() == 2 {
+            if let (Pat::Ident(index), Pat::Ident(element)) = (&tuple.elems[0], &tuple.elems[1]) {
+                if index.by_ref.is_none()
+                    && index.mutability.is_none()
+                    && index.subpat.is_none()
+                    && element.by_ref.is_none()
+                    && element.mutability.is_none()
+                    && element.subpat.is_none()
+                {
+                    return Some((index.ident.clone(), element.ident.to_string()));
+                }
+            }
+        }
+    }
+    None
+}
+
+// this replaces uses of the element (like *x or x) with indexing (like data[i])
+struct ElementReplacer {
+    element: String,
+    indexed: Expr,
+}
+
+impl Fold for ElementReplacer {
+    fn fold_expr(&mut self, i: Expr) -> Expr {
+        match &i {
+            Expr::Unary(ExprUnary { op: UnOp::Deref(_), expr, .. })
+                if get_ident_of(expr).as_ref() == Some(&self.element) =>
+            {
+                self.indexed.clone()
+            }
+            Expr::Path(_) if get_ident_of(&i).as_ref() == Some(&self.element) => self.indexed.clone(),
+            _ => fold::fold_expr(self, i),
+        }
+    }
+}
+
+// tries to turn iteration over an enumerated slice into an equivalent for loop over a range of indices
+//
+// this handles both of the following
+// for (i, x) in data.iter_mut().enumerate() { ... }
+// data.iter_mut().enumerate().for_each(|(i, x)| ...)
+// and turns them into the following
+// for i in 0..data.len() { ... }
+// where each *x or x in ... is replaced with data[i]
+//
+// returns None if the expr isn't one of those
+pub fn desugar_enumerate(expr: &Expr) -> Option<ExprForLoop> {
+    let (pat, iterated, body) = match expr {
+        Expr::ForLoop(for_loop) if for_loop.label.is_none() => (
+            &for_loop.pat,
+            &*for_loop.expr,
+            for_loop.body.clone(),
+        ),
+        Expr::MethodCall(for_each) if for_each.method == "for_each" && for_each.args.len() == 1 => {
+            if let Expr::Closure(closure) = &for_each.args[0] {
+                if closure.inputs.len() != 1
+                    || closure.asyncness.is_some()
+                    || closure.movability.is_some()
+                {
+                    return None;
+                }
+                let body = match &*closure.body {
+                    Expr::Block(block) => block.block.clone(),
+                    body => parse_quote! { { #body; } },
+                };
+                (&closure.inputs[0], &*for_each.receiver, body)
+            } else {
+                return None;
+            }
+        }
+        _ => return None,
+    };
+
+    let data = Ident::new(&get_enumerated(iterated)?, proc_macro2::Span::call_site());
+    let (index, element) = get_index_and_element(pat)?;
+
+    let mut replacer = ElementReplacer {
+        element,
+        indexed: parse_quote! { #data[#index] },
+    };
+    let body = replacer.fold_block(body);
+
+    Some(parse_quote! {
+        for #index in 0..#data.len() #body
+    })
+}
+
+// returns whether the expr is an arithmetic expression (+, -, *, /) of integer literals and variables
+fn is_size_expression(expr: &Expr) -> bool {
+    match expr {
+        Expr::Lit(ExprLit { lit: Lit::Int(_), .. }) => true,
+        Expr::Path(path) => path.path.get_ident().is_some(),
+        Expr::Paren(paren) => is_size_expression(&paren.expr),
+        Expr::Binary(binary) => match binary.op {
+            BinOp::Add(_) | BinOp::Sub(_) | BinOp::Mul(_) | BinOp::Div(_) => {
+                is_size_expression(&binary.left) && is_size_expression(&binary.right)
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+// evaluates an arithmetic expression of integer literals
+// returns None if the expression uses variables (and so can only be evaluated at run-time) or overflows
+fn evaluate_size_expression(expr: &Expr) -> Option<i32> {
+    match expr {
+        Expr::Lit(ExprLit { lit: Lit::Int(lit_int), .. }) => lit_int.base10_parse::<i32>().ok(),
+        Expr::Paren(paren) => evaluate_size_expression(&paren.expr),
+        Expr::Binary(binary) => {
+            let left = evaluate_size_expression(&binary.left)?;
+            let right = evaluate_size_expression(&binary.right)?;
+            match binary.op {
+                BinOp::Add(_) => left.checked_add(right),
+                BinOp::Sub(_) => left.checked_sub(right),
+                BinOp::Mul(_) => left.checked_mul(right),
+                BinOp::Div(_) => left.checked_div(right),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
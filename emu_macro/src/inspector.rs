@@ -6,9 +6,11 @@ use proc_macro2::Span;
 // for parsing Rust
 extern crate syn;
 use syn::spanned::Spanned;
+use syn::visit::Visit;
 use syn::*;
 
 // for etc.
+use std::collections::HashMap;
 use std::result::Result;
 
 // this is used for storing info about the functions that
@@ -16,23 +18,46 @@ use std::result::Result;
 pub struct FunctionInfo {
     pub name: Ident,
     pub has_return: bool,
+    pub try_return: Option<TryReturn>,
+}
+
+// what a function returns early with when `?` is used in it
+#[derive(Clone, Copy, PartialEq)]
+pub enum TryReturn {
+    // the function returns a Result so `?` returns the error
+    Result,
+    // the function returns an Option so `?` returns None
+    Option,
+}
+
+// this is used for storing which device the GPU should be created from
+// by default (when this is None), the first device of the first platform is used
+pub enum DeviceSelector {
+    // #[gpu_use(device = 1)] uses the device at the given index in the list of all devices of all platforms
+    Index(usize),
+    // #[gpu_use(device_name = "NVIDIA")] uses the first device whose name contains the given string
+    NameContains(String),
 }
 
 // looks at AttributeArgs in an invocation of #[gpu_use]
-// to see what helper functions are declared
+// to see what helper functions are declared, which device should be used, and whether or not to explain what is done
 //
 // for example, #[gpu_use] should return at empty Vec
 // but, #[gpu_use(multiply, add, subtract)] should return a Vec of length 3
 // containing multiply, add, subtract
 //
+// explain isn't a helper function so #[gpu_use(multiply, explain)] should return a Vec of length 1
+//
 // for more information on what a helper function is, look at the passing.rs module
 // passing is all about passing the GPU around from function to function
 // we need to know what helper functions use the GPU in order to know which ones
 // can and should have the GPU passed to them
 pub fn get_declared_helper_functions(
     attribute_args: AttributeArgs,
-) -> Result<Vec<Ident>, Vec<syn::Error>> {
+) -> Result<(Vec<Ident>, Option<DeviceSelector>, bool), Vec<syn::Error>> {
     let mut declared_helper_functions = vec![];
+    let mut device_selector = None;
+    let mut explain = false;
     let mut errors = vec![];
 
     // note that this is one place where we try to collect as many errors as we can and
@@ -42,7 +67,9 @@ pub fn get_declared_helper_functions(
     for attribute_arg in attribute_args {
         if let NestedMeta::Meta(meta) = attribute_arg {
             if let Meta::Path(path) = meta {
-                if let Some(ident) = path.get_ident() {
+                if path.is_ident("explain") {
+                    explain = true;
+                } else if let Some(ident) = path.get_ident() {
                     // only a helper function declaration if it is an identifier in a list of them
                     declared_helper_functions.push((*ident).clone());
                 } else {
@@ -51,6 +78,14 @@ pub fn get_declared_helper_functions(
                         "expected identifier/name of helper function",
                     ));
                 }
+            } else if let Some(selector) = get_device_selector(&meta) {
+                if device_selector.is_some() {
+                    errors.push(syn::Error::new(
+                        meta.span(),
+                        "device to use is already chosen",
+                    ));
+                }
+                device_selector = Some(selector);
             } else {
                 errors.push(syn::Error::new(
                     meta.span(),
@@ -69,7 +104,26 @@ pub fn get_declared_helper_functions(
         // must be at least 1 error for this Result to be an Err
         Err(errors)
     } else {
-        Ok(declared_helper_functions)
+        Ok((declared_helper_functions, device_selector, explain))
+    }
+}
+
+// looks at an argument like device = 1 or device_name = "NVIDIA" to see which device should be used
+// returns None if the argument isn't one of these
+fn get_device_selector(meta: &Meta) -> Option<DeviceSelector> {
+    if let Meta::NameValue(name_value) = meta {
+        match &name_value.lit {
+            Lit::Int(index) if name_value.path.is_ident("device") => index
+                .base10_parse::<usize>()
+                .ok()
+                .map(DeviceSelector::Index),
+            Lit::Str(name) if name_value.path.is_ident("device_name") => {
+                Some(DeviceSelector::NameContains(name.value()))
+            }
+            _ => None,
+        }
+    } else {
+        None
     }
 }
 
@@ -113,6 +167,7 @@ pub fn get_function_info(input: TokenStream) -> Result<FunctionInfo, Vec<Error>>
             } else {
                 true
             },
+            try_return: get_try_return(&ast.sig.output),
         })
     } else {
         Err(vec![Error::new(
@@ -121,3 +176,64 @@ pub fn get_function_info(input: TokenStream) -> Result<FunctionInfo, Vec<Error>>
         )])
     }
 }
+
+// looks at the return type of a function to see what `?` would return early with
+//
+// we can only look at the name of the type so anything named Result (like io::Result<T>) is assumed to be a Result
+// and anything named Option is assumed to be an Option
+fn get_try_return(output: &ReturnType) -> Option<TryReturn> {
+    if let ReturnType::Type(_, ty) = output {
+        if let Type::Path(path) = &**ty {
+            return match path.path.segments.last() {
+                Some(segment) if segment.ident == "Result" => Some(TryReturn::Result),
+                Some(segment) if segment.ident == "Option" => Some(TryReturn::Option),
+                _ => None,
+            };
+        }
+    }
+    None
+}
+
+// looks through the body of a function tagged with #[gpu_use] for functions defined inside of it
+//
+// these are the functions that can be called from launched code (and from each other)
+// we can only see functions defined here since the macro doesn't have access to anything outside of the tagged function
+pub fn get_device_functions(function: &ItemFn) -> HashMap<String, ItemFn> {
+    let mut collector = DeviceItemCollector {
+        device_functions: HashMap::new(),
+        device_constants: HashMap::new(),
+    };
+    collector.visit_block(&function.block);
+    collector.device_functions
+}
+
+// looks through the body of a function tagged with #[gpu_use] for constants defined inside of it
+//
+// these are the constants that can be used from launched code and from the functions it calls
+pub fn get_device_constants(function: &ItemFn) -> HashMap<String, ItemConst> {
+    let mut collector = DeviceItemCollector {
+        device_functions: HashMap::new(),
+        device_constants: HashMap::new(),
+    };
+    collector.visit_block(&function.block);
+    collector.device_constants
+}
+
+// the body may be wrapped in other blocks (like the boilerplate for creating the GPU) so we look through all of them
+struct DeviceItemCollector {
+    device_functions: HashMap<String, ItemFn>,
+    device_constants: HashMap<String, ItemConst>,
+}
+
+impl<'ast> Visit<'ast> for DeviceItemCollector {
+    // we don't look inside of the functions we find
+    fn visit_item_fn(&mut self, item_fn: &'ast ItemFn) {
+        self.device_functions
+            .insert(item_fn.sig.ident.to_string(), item_fn.clone());
+    }
+
+    fn visit_item_const(&mut self, item_const: &'ast ItemConst) {
+        self.device_constants
+            .insert(item_const.ident.to_string(), item_const.clone());
+    }
+}
@@ -21,11 +21,13 @@ use syn::*;
 mod accelerating; // for looking through code for gpu_do!() and using the GPU appropriately
 mod passing; // for passing around a reference to the GPU from function to function
              // these modules are more linke utilities for Emu
+mod explaining; // for writing reports of what was done with #[gpu_use(explain)]
 mod generator; // for generating OpenCL from Rust
 mod identifier; // for identifying a for loop as potentially something we can work with
 mod inspector; // for inspecting a function for more info
 
 use accelerating::*;
+use explaining::*;
 use inspector::*;
 use passing::*;
 
@@ -33,6 +35,12 @@ use passing::*;
 // let's consider the following where x is of type T
 // gpu_do!(load(x))
 // gpu_do!(read(x))
+// gpu_do!(unload(x))
+// gpu_do!(reserve(x, n))
+// gpu_do!(name(x, "x"))
+// gpu_do!(assert_len(x, n))
+// gpu_do!(launch(elements_per_thread = n, unroll = m))
+// let y = gpu_do!(launch_map(x, |e| e * 2.0))
 // here are the restrictions for what T can be
 // - T must have .as_slice() for reading from slice to GPU
 // - T must have .as_mut_slice() for writing to slice back from GPU
@@ -84,7 +92,7 @@ macro_rules! unwrap_or_return {
 ///
 /// Well, what do I mean by passing? I've talked in other parts of the docs
 /// about the "GPU being in scope" or "passing the GPU around". I literally
-/// mean an identifier `gpu` that references an instance of `Gpu` and holds
+/// mean a variable that holds an instance of `Gpu` and holds
 /// all information about the physical GPU that Emu needs to know. Why do we
 /// need to "pass" this `Gpu` instance from function to function? Well, we
 /// would like only 1 instance of `Gpu` to exist throughout an entire
@@ -96,7 +104,7 @@ macro_rules! unwrap_or_return {
 ///
 /// So, how do I do passing? If you correctly tag your function with
 /// `#[gpu_use]`, it's function signature will be modified and its contents
-/// will be modified so as to allow `gpu`, a `Gpu` instance, to be used in
+/// will be modified so as to allow a `Gpu` instance to be used in
 /// the function body. To correctly tag your function with `#[gpu_use]`, you
 /// must list the "helper functions" of the tagged function.
 /// ```
@@ -163,6 +171,84 @@ macro_rules! unwrap_or_return {
 /// Looking at the above example you should be able to justify each helper
 /// function listed for each function, using the above 2 cases. Note that the `main` function doesn't list itself as a helper function and that is because
 /// it doesn't need the GPU passed to it ever.
+///
+/// The function that doesn't list itself as a helper function is where the GPU is created. By default, the GPU is created from
+/// the first device of the first OpenCL platform. On a machine with more than 1 GPU (like a laptop with both an integrated and a
+/// dedicated GPU), you can choose another device with either its index in [`list_devices`](fn.list_devices.html) or a part of its name.
+/// ```
+/// # extern crate em;
+/// # use em::*;
+/// #[gpu_use(device = 0)] // or #[gpu_use(device_name = "NVIDIA")]
+/// fn main() {
+///     let mut data = vec![0.1; 1000];
+///
+///     gpu_do!(load(data));
+///     gpu_do!(read(data));
+/// }
+/// ```
+///
+/// The variable that holds the `Gpu` has a name that won't collide with your own variables (so you can still have a variable named `gpu`)
+/// and binding that name yourself is an error. If you want to use the `Gpu` directly (to drop down to OpenCL), give it a name with
+/// `gpu_do!(with(name))`.
+/// ```
+/// # extern crate em;
+/// # use em::*;
+/// #[gpu_use]
+/// fn main() {
+///     let gpu = "a variable that has nothing to do with Emu";
+///     let mut data = vec![0.1; 1000];
+///
+///     gpu_do!(load(data));
+///     gpu_do!(with(device));
+///     device.queue.finish().unwrap();
+///     gpu_do!(read(data));
+/// }
+/// ```
+///
+/// Helper functions that return a `Result` or an `Option` can use `?`. The `Gpu` is passed back to the caller whether the helper function
+/// returns normally or returns early with `?` (this includes return statements and `?` in match guards, in loops, and in the arguments of
+/// macros like `println!`). Return statements and `?` in closures and async blocks are left alone since they don't return from the function.
+/// ```
+/// # extern crate em;
+/// # use em::*;
+/// #[gpu_use(parse_and_double)]
+/// fn main() {
+///     assert_eq!(parse_and_double("0.5").unwrap(), vec![1.0; 1000]);
+///     assert!(parse_and_double("half").is_err());
+/// }
+///
+/// #[gpu_use(parse_and_double)]
+/// fn parse_and_double(x: &str) -> Result<Vec<f32>, std::num::ParseFloatError> {
+///     let mut data = vec![x.parse::<f32>()?; 1000];
+///     gpu_do!(load(data));
+///     gpu_do!(launch());
+///     for i in 0..1000 {
+///         data[i] = data[i] * 2.0;
+///     }
+///     gpu_do!(read(data));
+///     Ok(data)
+/// }
+/// ```
+///
+/// To see what Emu did with a function, tag it with `#[gpu_use(explain)]` (alongside any helper functions). When the function is compiled, a
+/// report is written to `target/emu-explain` (or to `$EMU_EXPLAIN_DIR` if that is set). The report lists, in order, where data is loaded to
+/// and read from the GPU, which for loops are launched (along with what is passed to them and the OpenCL they are compiled to), and which
+/// for loops run on the CPU or couldn't be launched. Because of this, a helper function can't be named `explain`.
+/// ```
+/// # extern crate em;
+/// # use em::*;
+/// #[gpu_use(explain)]
+/// fn main() {
+///     let mut data = vec![0.1; 1000];
+///
+///     gpu_do!(load(data));
+///     gpu_do!(launch());
+///     for i in 0..1000 {
+///         data[i] = data[i] * 10.0;
+///     }
+///     gpu_do!(read(data));
+/// }
+/// ```
 #[proc_macro_attribute]
 pub fn gpu_use(metadata: TokenStream, mut input: TokenStream) -> TokenStream {
     // there are 3 parts of Emu's procedural code generation
@@ -173,9 +259,12 @@ pub fn gpu_use(metadata: TokenStream, mut input: TokenStream) -> TokenStream {
 
     // (1) movement of Gpu from function to function
 
+    // the GPU is passed around in a variable the user's code must not shadow
+    unwrap_or_return!(check_gpu_not_shadowed(input.clone()), input);
+
     // find declared helper functions
     let attribute_args = parse_macro_input!(metadata as AttributeArgs);
-    let declared_helper_functions =
+    let (declared_helper_functions, device_selector, explain) =
         unwrap_or_return!(get_declared_helper_functions(attribute_args), input);
 
     // check if current function is a declared helper function
@@ -187,6 +276,28 @@ pub fn gpu_use(metadata: TokenStream, mut input: TokenStream) -> TokenStream {
         }
     }
 
+    // this is what gets explained about passing if we are asked to explain what is done
+    let explained_gpu = if is_declared_helper_function {
+        String::from("the GPU is passed to this function since it is a helper function")
+    } else {
+        match &device_selector {
+            None => String::from("the GPU is created here from the first device of the first platform"),
+            Some(DeviceSelector::Index(index)) => format!(
+                "the GPU is created here from the device at index {} in the list of all devices",
+                index
+            ),
+            Some(DeviceSelector::NameContains(name)) => format!(
+                "the GPU is created here from the first device with a name containing {:?}",
+                name
+            ),
+        }
+    };
+    let explained_helper_functions = declared_helper_functions
+        .iter()
+        .filter(|declared_helper_function| **declared_helper_function != function_info.name)
+        .map(|declared_helper_function| declared_helper_function.to_string())
+        .collect::<Vec<_>>();
+
     // handle all invocations of helper functions
     // GPU must be passed to and back from helper function
     // result of helper function must be used in original way if a result is returned
@@ -198,6 +309,17 @@ pub fn gpu_use(metadata: TokenStream, mut input: TokenStream) -> TokenStream {
     // handle the current function being a declared helper function
     // basically, we need to transform the function so that it can take a GPU as input and return the modified GPU as output
     if is_declared_helper_function {
+        // the device can only be chosen where the GPU is created
+        if device_selector.is_some() {
+            return unwrap_or_return!(
+                Err(vec![Error::new(
+                    Span::call_site().unwrap().into(),
+                    "a device can't be chosen for a helper function since the GPU is passed to it",
+                )]),
+                input
+            );
+        }
+
         // modify signature and returns
         input = unwrap_or_return!(
             modify_signature_for_helper_function(input.clone(), function_info.has_return),
@@ -207,10 +329,16 @@ pub fn gpu_use(metadata: TokenStream, mut input: TokenStream) -> TokenStream {
             modify_return_for_helper_function(input.clone(), function_info.has_return),
             input
         );
-        input = unwrap_or_return!(modify_returns_for_helper_function(input.clone()), input);
+        input = unwrap_or_return!(
+            modify_returns_for_helper_function(input.clone(), function_info.try_return),
+            input
+        );
     } else {
         // modify body by adding boilerplate to create GPU to be passed to helper functions
-        input = unwrap_or_return!(modify_for_not_a_helper_function(input.clone()), input);
+        input = unwrap_or_return!(
+            modify_for_not_a_helper_function(input.clone(), device_selector),
+            input
+        );
     }
 
     // (2) movement of data on Gpu <-> CPU by visit_macro
@@ -223,12 +351,38 @@ pub fn gpu_use(metadata: TokenStream, mut input: TokenStream) -> TokenStream {
     let maybe_ast = syn::parse::<ItemFn>(input.clone());
 
     if maybe_ast.is_ok() {
+        // functions defined inside can be called from launched code
+        let ast = maybe_ast.unwrap();
+        accelerator.device_fns = get_device_functions(&ast);
+        accelerator.device_consts = get_device_constants(&ast);
+
         // transform AST
-        let new_ast = accelerator.fold_item_fn(maybe_ast.unwrap());
+        let new_ast = accelerator.fold_item_fn(ast);
 
         // // print AST
         // println!("{}", new_ast.to_token_stream().to_string());
 
+        // write a report of what was done if we are asked to explain
+        if explain {
+            let explanation = Explanation {
+                function: function_info.name.to_string(),
+                gpu: explained_gpu,
+                helper_functions: explained_helper_functions,
+                notes: accelerator.notes.clone(),
+            };
+            if let Err(error) = write_explanation(&explanation) {
+                accelerator.errors.push(Error::new(
+                    Span::call_site().unwrap().into(),
+                    format!(
+                        "could not write explanation of `{}` to {}: {}",
+                        function_info.name,
+                        get_explain_dir().display(),
+                        error
+                    ),
+                ));
+            }
+        }
+
         let errors = accelerator
             .errors
             .iter()
@@ -10,9 +10,14 @@ use proc_macro2::Span;
 // for parsing Rust
 extern crate syn;
 use syn::fold::Fold;
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
+use syn::visit::Visit;
 use syn::*;
 
 // for etc.
+use crate::inspector::DeviceSelector;
+use crate::inspector::TryReturn;
 use std::result::Result;
 
 // this was copied from standard library source code
@@ -65,6 +70,62 @@ macro_rules! fold_expr_default {
     };
 }
 
+// the name of the variable that holds the GPU and is passed from function to function
+// it is not just `gpu` so that it can't collide with variables of the user's that are named `gpu`
+// the user can still get at the GPU with gpu_do!(with(name))
+pub const GPU_NAME: &str = "__emu_gpu";
+
+// returns the identifier of the variable that holds the GPU
+pub fn gpu_ident() -> Ident {
+    Ident::new(GPU_NAME, Span::call_site())
+}
+
+// looks for anything in a function that is bound to the name of the variable that holds the GPU
+// since that would shadow the GPU
+pub struct GpuShadowFinder {
+    pub errors: Vec<Error>,
+}
+
+impl<'ast> Visit<'ast> for GpuShadowFinder {
+    fn visit_pat_ident(&mut self, i: &'ast PatIdent) {
+        if i.ident == GPU_NAME {
+            self.errors.push(Error::new(
+                i.ident.span(),
+                format!(
+                    "`{}` is the GPU that `#[gpu_use]` passes around so it can't be shadowed (use `gpu_do!(with(name))` to get at the GPU)",
+                    GPU_NAME
+                ),
+            ));
+        }
+        syn::visit::visit_pat_ident(self, i);
+    }
+
+    // don't visit substructures of items
+    // items can't use the GPU of the function the item is in
+    fn visit_item(&mut self, _i: &'ast Item) {}
+}
+
+// checks that nothing in the function shadows the variable that holds the GPU
+pub fn check_gpu_not_shadowed(input: TokenStream) -> Result<(), Vec<Error>> {
+    // parse into function
+    let maybe_ast = syn::parse::<ItemFn>(input.clone());
+
+    if let Ok(ast) = maybe_ast {
+        let mut gpu_shadow_finder = GpuShadowFinder { errors: vec![] };
+        gpu_shadow_finder.visit_item_fn(&ast);
+        if gpu_shadow_finder.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(gpu_shadow_finder.errors)
+        }
+    } else {
+        Err(vec![Error::new(
+            Span::call_site().unwrap().into(),
+            "only functions that are items can be tagged with `#[gpu_use]`",
+        )])
+    }
+}
+
 // what does it mean to be a function that is declared to be a helper function?
 // well, it means that you need to accept the GPU as an argument and return it back to whoever called you
 // the purpose of this module is to transform functions appropriately so this is exactly what happens
@@ -83,11 +144,13 @@ pub fn modify_signature_for_helper_function(
     // (2) modify the output of the function, in order to return the GPU
 
     if let Ok(mut ast) = maybe_ast {
+        let gpu = gpu_ident();
+
         // modify based on whether or not the function returns something already
         if has_return {
             // (1) modify input
             let input: proc_macro::TokenStream = quote! {
-                mut gpu: Gpu
+                mut #gpu: Gpu
             }
             .into();
             ast.sig
@@ -109,7 +172,7 @@ pub fn modify_signature_for_helper_function(
         } else {
             // (1) modify input
             let input = quote! {
-                mut gpu: Gpu
+                mut #gpu: Gpu
             }
             .into();
             ast.sig
@@ -137,12 +200,6 @@ pub fn modify_signature_for_helper_function(
     }
 }
 
-// TODO handle question mark operator
-// we can do one of several things
-// - forbid ?
-// - handle by expanding syntactic sugar
-// - handle by looking for Result in signature and modifying the Ok part of it
-
 // modifies return expression
 // note this doesn't fix up all the return statements only the implicit "last expression is returned" stuff
 // we'll deal with the return statements later
@@ -154,13 +211,21 @@ pub fn modify_return_for_helper_function(
     let maybe_ast = syn::parse::<ItemFn>(input.clone());
 
     if let Ok(mut ast) = maybe_ast {
+        let gpu = gpu_ident();
+
+        // the existing body may never finish (if it ends with a return statement or a loop)
+        // so the code that returns the GPU after it is allowed to be unreachable
+        // only the first unreachable statement is warned about so this doesn't hide warnings about the user's code
         if has_return {
             let existing_body = ast.block;
             // we just change the body so we first evaluate what we normally have there
             // and then we return the GPU
             let body = quote! {
                 {
-                    (#existing_body, gpu)
+                    let result = #existing_body;
+                    #[allow(unreachable_code)]
+                    let result = (result, #gpu);
+                    result
                 }
             };
             ast.block = Box::new(
@@ -173,7 +238,9 @@ pub fn modify_return_for_helper_function(
             let body = quote! {
                 {
                     #existing_body
-                    ((), gpu)
+                    #[allow(unreachable_code)]
+                    let result = ((), #gpu);
+                    result
                 }
             };
             ast.block = Box::new(
@@ -194,30 +261,141 @@ pub fn modify_return_for_helper_function(
 
 // this is what we use to modify the return statements
 // we want to modify the return statements so that they return the GPU
-pub struct HelperFunctionReturnModifier;
+//
+// `?` is a return statement too. so it is expanded into a match that returns the GPU along with the error
+// this is only possible when we know from the signature that the function returns a Result or an Option
+//
+// not every return statement or `?` returns from the function though
+// - in closures and async blocks they return from the closure or the async block
+// - in nested items they return from those items
+// - in try blocks `?` breaks out of the try block (but return statements still return from the function)
+pub struct HelperFunctionReturnModifier {
+    pub try_return: Option<TryReturn>,
+    pub errors: Vec<Error>,
+    // how many try blocks we are in
+    pub try_block_depth: usize,
+    // whether anything has been modified (this lets us leave the tokens of macros alone when they don't need modifying)
+    pub modified: bool,
+}
 
 impl Fold for HelperFunctionReturnModifier {
+    fn fold_expr(&mut self, i: Expr) -> Expr {
+        if let Expr::Try(try_expr) = i {
+            let attrs = try_expr.attrs;
+            let expr = self.fold_expr(*try_expr.expr);
+            let gpu = gpu_ident();
+
+            // `?` in a try block doesn't return from the function
+            if self.try_block_depth > 0 {
+                return Expr::Try(ExprTry {
+                    attrs,
+                    expr: Box::new(expr),
+                    question_token: try_expr.question_token,
+                });
+            }
+            self.modified = true;
+
+            match self.try_return {
+                Some(TryReturn::Result) => parse_quote! {
+                    #(#attrs)*
+                    match #expr {
+                        std::result::Result::Ok(value) => value,
+                        std::result::Result::Err(error) => {
+                            return (std::result::Result::Err(std::convert::From::from(error)), #gpu)
+                        }
+                    }
+                },
+                Some(TryReturn::Option) => parse_quote! {
+                    #(#attrs)*
+                    match #expr {
+                        std::option::Option::Some(value) => value,
+                        std::option::Option::None => return (std::option::Option::None, #gpu),
+                    }
+                },
+                None => {
+                    self.errors.push(Error::new(
+                        try_expr.question_token.span,
+                        "`?` can only be used in a helper function that returns a `Result` or an `Option`",
+                    ));
+                    Expr::Try(ExprTry {
+                        attrs,
+                        expr: Box::new(expr),
+                        question_token: try_expr.question_token,
+                    })
+                }
+            }
+        } else {
+            fold_expr_default!(self, i)
+        }
+    }
+
     fn fold_expr_return(&mut self, i: ExprReturn) -> ExprReturn {
         let attrs = i.attrs;
         let return_token = i.return_token;
-        let expr = i.expr;
+        // the returned expression may use `?` too
+        let expr = i.expr.map(|expr| self.fold_expr(*expr));
+        let gpu = gpu_ident();
+        self.modified = true;
 
-        let new_code = if expr.is_none() {
-            quote! {
+        if expr.is_none() {
+            parse_quote! {
                 #(#attrs)*
-                #return_token ((), gpu)
+                #return_token ((), #gpu)
             }
         } else {
-            quote! {
+            parse_quote! {
                 #(#attrs)*
-                #return_token (#expr, gpu)
+                #return_token (#expr, #gpu)
             }
-        };
+        }
+    }
 
-        let new_ast = syn::parse_str::<ExprReturn>(&new_code.to_string())
-            .expect("could not modify return statements");
+    fn fold_expr_try_block(&mut self, i: ExprTryBlock) -> ExprTryBlock {
+        self.try_block_depth += 1;
+        let new_try_block = syn::fold::fold_expr_try_block(self, i);
+        self.try_block_depth -= 1;
+        new_try_block
+    }
+
+    // the arguments of macros (like println!("{}", x?) or vec![x?; n]) can contain return statements and `?` too
+    // so we try to parse the arguments as expressions and modify them
+    // macros with arguments that aren't expressions are left alone (and so are invocations of gpu_do!())
+    fn fold_macro(&mut self, i: Macro) -> Macro {
+        if i.path.is_ident("gpu_do") {
+            return i;
+        }
 
-        new_ast
+        let was_modified = self.modified;
+        self.modified = false;
+        let tokens = &i.tokens;
+        let new_tokens =
+            if let Ok(args) = Punctuated::<Expr, Token![,]>::parse_terminated.parse2(tokens.clone()) {
+                // arguments separated by commas
+                Some(
+                    args.into_pairs()
+                        .map(|pair| {
+                            let (arg, comma) = pair.into_tuple();
+                            punctuated::Pair::new(self.fold_expr(arg), comma)
+                        })
+                        .collect::<Punctuated<Expr, Token![,]>>()
+                        .to_token_stream(),
+                )
+            } else if let Ok(repeat) = syn::parse2::<ExprRepeat>(quote! { [#tokens] }) {
+                // an expression and how many times to repeat it
+                let expr = self.fold_expr(*repeat.expr);
+                let semi_token = repeat.semi_token;
+                let len = self.fold_expr(*repeat.len);
+                Some(quote! { #expr #semi_token #len })
+            } else {
+                None
+            };
+        let is_modified = self.modified;
+        self.modified = was_modified || is_modified;
+
+        match new_tokens {
+            Some(tokens) if is_modified => Macro { tokens, ..i },
+            _ => i,
+        }
     }
 
     // don't fold on substructures of items
@@ -226,6 +404,11 @@ impl Fold for HelperFunctionReturnModifier {
         i
     }
 
+    // async blocks can't contain return statements that return from this function either
+    fn fold_expr_async(&mut self, i: ExprAsync) -> ExprAsync {
+        i
+    }
+
     // don't fold on substructures of items
     // items can't contain return statements that will return from this function
     fn fold_item(&mut self, i: Item) -> Item {
@@ -236,16 +419,27 @@ impl Fold for HelperFunctionReturnModifier {
 // modifies return statements
 // this mainly just creates an instance of the above "folder" that we defined
 // we then just invoke it's "fold_item_fn" method to fold on the function
-pub fn modify_returns_for_helper_function(input: TokenStream) -> Result<TokenStream, Vec<Error>> {
+pub fn modify_returns_for_helper_function(
+    input: TokenStream,
+    try_return: Option<TryReturn>,
+) -> Result<TokenStream, Vec<Error>> {
     // parse into function
     let maybe_ast = syn::parse::<ItemFn>(input.clone());
 
     if let Ok(ast) = maybe_ast {
         // make helper function return modifier
-        let mut helper_function_return_modifier = HelperFunctionReturnModifier {};
+        let mut helper_function_return_modifier = HelperFunctionReturnModifier {
+            try_return,
+            errors: vec![],
+            try_block_depth: 0,
+            modified: false,
+        };
 
         // transform AST with changes to return statements
         let new_ast = helper_function_return_modifier.fold_item_fn(ast);
+        if !helper_function_return_modifier.errors.is_empty() {
+            return Err(helper_function_return_modifier.errors);
+        }
 
         // return the modified input
         Ok(new_ast.to_token_stream().into())
@@ -268,34 +462,45 @@ pub fn modify_returns_for_helper_function(input: TokenStream) -> Result<TokenStr
 // note that while we don't need to modify it's input and output we must still modify how it
 // invokes all the helper functions it invokes. those invocations must be modified to pass the GPU out
 // and bring it back in
-pub fn modify_for_not_a_helper_function(input: TokenStream) -> Result<TokenStream, Vec<Error>> {
+//
+// the GPU is created from the first device of the first platform unless another device is chosen
+// with #[gpu_use(device = 1)] or #[gpu_use(device_name = "NVIDIA")]
+// creating it reuses the context, queue, and programs of the last GPU created for the device on this thread (see Gpu::new)
+pub fn modify_for_not_a_helper_function(
+    input: TokenStream,
+    device_selector: Option<DeviceSelector>,
+) -> Result<TokenStream, Vec<Error>> {
     // parse into function
     let maybe_ast = syn::parse::<ItemFn>(input.clone());
 
     if let Ok(mut ast) = maybe_ast {
+        let gpu = gpu_ident();
         let existing_body = ast.block;
+        let platform_and_device = match device_selector {
+            None => quote! {
+                let new_platform = ocl::Platform::default();
+                let new_device = ocl::Device::first(new_platform).expect("no GPU found");
+            },
+            Some(DeviceSelector::Index(index)) => quote! {
+                let (new_platform, new_device) = list_devices()
+                    .into_iter()
+                    .nth(#index)
+                    .expect(&format!("no GPU found at index {}", #index));
+            },
+            Some(DeviceSelector::NameContains(name)) => quote! {
+                let (new_platform, new_device) = list_devices()
+                    .into_iter()
+                    .find(|(_, device)| device.name().map_or(false, |device_name| device_name.contains(#name)))
+                    .expect(&format!("no GPU found with name containing \"{}\"", #name));
+            },
+        };
         let body = quote! {
             {
                 use ocl::*;
 
-                let mut gpu = {
-                    let new_platform = ocl::Platform::default();
-                    let new_device = ocl::Device::first(new_platform).expect("no GPU found");
-                    let new_context = ocl::Context::builder()
-                        .platform(new_platform)
-                        .devices(new_device.clone())
-                        .build()
-                        .expect("failed to build context for executing on GPU with OpenCL");
-                    let new_queue = ocl::Queue::new(&new_context, new_device, None)
-                        .expect("failed to create queue of commands to be sent to GPU");
-
-                    Gpu {
-                        device: new_device,
-                        context: new_context,
-                        queue: new_queue,
-                        buffers: std::collections::HashMap::new(),
-                        programs: std::collections::HashMap::new()
-                    }
+                let mut #gpu = {
+                    #platform_and_device
+                    Gpu::new(new_platform, new_device)
                 };
 
                 #existing_body
@@ -338,8 +543,8 @@ impl Fold for HelperFunctionInvocationModifier {
                 }
 
                 if is_helper_function_invocation {
-                    let gpu_ident = quote! {gpu}.to_token_stream();
-                    i.args.insert(0, syn::Expr::Verbatim(gpu_ident));
+                    let gpu = gpu_ident();
+                    i.args.insert(0, syn::Expr::Verbatim(gpu.to_token_stream()));
 
                     let new_code = quote! {
                         {
@@ -347,7 +552,7 @@ impl Fold for HelperFunctionInvocationModifier {
                             let result = #i;
 
                             // update GPU to new state
-                            gpu = result.1;
+                            #gpu = result.1;
 
                             // return result
                             result.0
@@ -0,0 +1,24 @@
+use em::*;
+
+const N: usize = 500;
+
+// this will succeed because the size of a loop can be a constant or an arithmetic expression of constants
+#[gpu_use]
+fn main() {
+	let mut data = vec![0.0; 2 * N];
+
+	gpu_do!(load(data));
+	gpu_do!(launch());
+	for i in 0..N {
+		data[i] = data[i] + 1.0;
+	}
+	gpu_do!(launch());
+	for i in 0..2 * N {
+		data[i] = data[i] + 1.0;
+	}
+	gpu_do!(launch());
+	for i in 0..(4 * 250) {
+		data[i] = data[i] + 1.0;
+	}
+	gpu_do!(read(data));
+}
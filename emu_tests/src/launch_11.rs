@@ -0,0 +1,30 @@
+use em::*;
+
+struct Settings {
+	scale: f32,
+	offset: f32,
+}
+
+// this will succeed because scalar expressions that don't depend on the loop are evaluated before launching
+#[gpu_use]
+fn main() {
+	let mut data = vec![1.0; 1000];
+	let scale = 2.0f32;
+	let offset = 0.5f32;
+	let settings = Settings { scale: 3.0, offset: 1.0 };
+
+	gpu_do!(load(data));
+	gpu_do!(launch());
+	for i in 0..1000 {
+		data[i] = data[i] * (scale + offset);
+	}
+	gpu_do!(launch());
+	for i in 0..1000 {
+		data[i] = data[i] * (scale - offset).abs() + settings.offset;
+	}
+	gpu_do!(launch());
+	for i in 0..1000 {
+		data[i] = data[i] + -settings.scale / data.len() as f32;
+	}
+	gpu_do!(read(data));
+}
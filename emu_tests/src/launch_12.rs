@@ -0,0 +1,27 @@
+use em::*;
+
+// this will succeed because functions defined inside of the tagged function can be called from launched code
+// even with arrays as arguments and even from each other
+#[gpu_use]
+fn main() {
+	let mut data = vec![1.0; 1000];
+	let mut smoothed = vec![0.0; 1000];
+	let scale = 0.5f32;
+
+	fn at(values: &[f32], i: usize) -> f32 {
+		values[i]
+	}
+
+	fn scaled_twice(values: &[f32], i: usize, scale: f32) -> f32 {
+		(at(values, i) + at(values, i)) * scale
+	}
+
+	gpu_do!(load(data));
+	gpu_do!(load(smoothed));
+	gpu_do!(launch());
+	for i in 0..1000 {
+		smoothed[i] = scaled_twice(&data, i, scale) + at(&smoothed, i);
+	}
+	gpu_do!(read(smoothed));
+	assert_eq!(smoothed, vec![1.0; 1000]);
+}
@@ -0,0 +1,16 @@
+use em::*;
+
+// this will succeed because a closure can be launched over data to produce new data
+// without changing the data that is mapped
+#[gpu_use]
+fn main() {
+	let data = vec![1.0; 1000];
+	let scale = 2.0f32;
+
+	gpu_do!(load(data));
+	let scaled = gpu_do!(launch_map(data, |x| x * scale));
+	let mut shifted = gpu_do!(launch_map(scaled, |x: f32| (x + 1.0)));
+	gpu_do!(read(shifted));
+	assert_eq!(shifted, vec![3.0; 1000]);
+	assert_eq!(data, vec![1.0; 1000]);
+}
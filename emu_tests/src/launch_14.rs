@@ -0,0 +1,18 @@
+use em::*;
+
+// this will succeed because each work-item can go over several elements with some of them unrolled
+// even when the number of elements isn't a multiple of the number of elements each work-item goes over
+#[gpu_use]
+fn main() {
+	let mut data = vec![1.0; 1001];
+	gpu_do!(with(gpu));
+	gpu.cpu_threshold = 0;
+
+	gpu_do!(load(data));
+	gpu_do!(launch(elements_per_thread = 5, unroll = 2));
+	for i in 0..data.len() {
+		data[i] = data[i] * 2.0;
+	}
+	gpu_do!(read(data));
+	assert_eq!(data, vec![2.0; 1001]);
+}
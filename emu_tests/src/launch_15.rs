@@ -0,0 +1,13 @@
+use em::*;
+
+// this will fail because more elements can't be unrolled than each work-item goes over
+#[gpu_use]
+fn main() {
+	let mut data = vec![1.0; 1000];
+	gpu_do!(load(data));
+	gpu_do!(launch(elements_per_thread = 2, unroll = 4));
+	for i in 0..1000 {
+		data[i] = data[i] * 2.0;
+	}
+	gpu_do!(read(data));
+}
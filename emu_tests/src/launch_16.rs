@@ -0,0 +1,38 @@
+use em::*;
+
+// this will succeed because launched code can loop until a condition on the data holds and set a flag the CPU polls
+#[gpu_use]
+fn main() {
+	let mut data = vec![100.0; 1000];
+	let mut changed = vec![0.0; 1];
+	gpu_do!(with(gpu));
+	gpu.cpu_threshold = 0;
+
+	gpu_do!(load(data));
+	loop {
+		changed[0] = 0.0;
+		gpu_do!(load(changed));
+		gpu_do!(launch());
+		for i in 0..1000 {
+			while data[i] > 1.0 && !(data[i] == 0.0) {
+				data[i] *= 0.5;
+				changed[0] = 1.0;
+				if data[i] < 10.0 {
+					break;
+				}
+			}
+			loop {
+				if data[i] < 0.0 {
+					continue;
+				}
+				break;
+			}
+		}
+		gpu_do!(read(changed));
+		if changed[0] == 0.0 {
+			break;
+		}
+	}
+	gpu_do!(read(data));
+	assert_eq!(data, vec![0.78125; 1000]);
+}
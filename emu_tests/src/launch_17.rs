@@ -0,0 +1,14 @@
+use em::*;
+
+// this will fail because launched code can't break out of the launched loop itself
+#[gpu_use]
+fn main() {
+	let mut data = vec![1.0; 1000];
+	gpu_do!(load(data));
+	gpu_do!(launch());
+	for i in 0..1000 {
+		data[i] = data[i] * 2.0;
+		break;
+	}
+	gpu_do!(read(data));
+}
@@ -0,0 +1,25 @@
+use em::*;
+
+// this will succeed because the types of variables declared in launched code are inferred
+#[gpu_use]
+fn main() {
+	let mut data = vec![1.0; 1000];
+	let scale = 2.0f32;
+	gpu_do!(with(gpu));
+	gpu.cpu_threshold = 0;
+
+	gpu_do!(load(data));
+	gpu_do!(launch());
+	for i in 0..1000 {
+		let x = data[i] * scale;
+		let mut j = i + 1;
+		j %= 1000;
+		let offset: f32 = 0.5;
+		let x = x + offset + (i % 2) as f32;
+		data[i] = x + data[j] * 0.0;
+	}
+	gpu_do!(read(data));
+	for i in 0..1000 {
+		assert_eq!(data[i], 2.5 + (i % 2) as f32);
+	}
+}
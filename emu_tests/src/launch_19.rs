@@ -0,0 +1,14 @@
+use em::*;
+
+// this will fail because the type of a variable that is neither a float nor an index can't be inferred
+#[gpu_use]
+fn main() {
+	let mut data = vec![1.0; 1000];
+	gpu_do!(load(data));
+	gpu_do!(launch());
+	for i in 0..1000 {
+		let _is_big = data[i] > 1.0;
+		data[i] = 1.0;
+	}
+	gpu_do!(read(data));
+}
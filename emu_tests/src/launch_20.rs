@@ -0,0 +1,36 @@
+use em::*;
+
+// this will succeed because constants and helper functions defined in the tagged function are shared by every launch
+#[gpu_use]
+fn main() {
+	let mut data = vec![-2.0; 1000];
+	let mut activated = vec![0.0; 1000];
+	gpu_do!(with(gpu));
+	gpu.cpu_threshold = 0;
+
+	const ALPHA: f32 = 0.25;
+	const LAST: usize = 999;
+	fn damped(x: f32) -> f32 {
+		let leak = x * ALPHA;
+		x - leak
+	}
+	fn mirrored(values: &[f32], i: usize) -> f32 {
+		let j = LAST - i;
+		values[j]
+	}
+
+	gpu_do!(load(data));
+	gpu_do!(load(activated));
+	gpu_do!(launch());
+	for i in 0..1000 {
+		data[i] = data[i] * ALPHA;
+	}
+	gpu_do!(launch());
+	for i in 0..1000 {
+		activated[i] = damped(mirrored(&data, i));
+	}
+	gpu_do!(read(activated));
+	for i in 0..1000 {
+		assert_eq!(activated[i], damped(-0.5));
+	}
+}
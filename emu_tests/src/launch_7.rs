@@ -0,0 +1,24 @@
+use em::*;
+
+#[repr(C)]
+struct Particle {
+	x: f32,
+	y: f32,
+	velocity: f32,
+}
+
+unsafe impl AsFloats for Particle {}
+
+// this will succeed because fields of structures made up of f32s can be used
+#[gpu_use]
+fn main() {
+	let mut particles = (0..1000).map(|_| Particle { x: 0.0, y: 0.0, velocity: 1.0 }).collect::<Vec<_>>();
+
+	gpu_do!(load(particles));
+	gpu_do!(launch());
+	for i in 0..1000 {
+		particles[i].x += particles[i].velocity;
+		particles[i].y = particles[i].y + particles[i].velocity * 2.0;
+	}
+	gpu_do!(read(particles));
+}
\ No newline at end of file
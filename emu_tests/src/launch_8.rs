@@ -0,0 +1,15 @@
+use em::*;
+
+// this will succeed because the size of a loop can be a variable
+#[gpu_use]
+fn main() {
+	let mut data = vec![0.0; 1000];
+	let len = data.len();
+
+	gpu_do!(load(data));
+	gpu_do!(launch());
+	for i in 0..len {
+		data[i] = data[i] + 1.0;
+	}
+	gpu_do!(read(data));
+}
\ No newline at end of file
@@ -0,0 +1,22 @@
+use em::*;
+
+// this will succeed because enumerated data can be iterated over
+#[gpu_use]
+fn main() {
+	let mut data = vec![0.0; 1000];
+
+	gpu_do!(load(data));
+	gpu_do!(launch());
+	for (i, x) in data.iter_mut().enumerate() {
+		*x = *x + 1.0;
+	}
+	gpu_do!(launch());
+	data.iter_mut().enumerate().for_each(|(i, x)| {
+		*x = *x * 2.0;
+	});
+	gpu_do!(launch());
+	for i in 0..data.len() {
+		data[i] = data[i] + 1.0;
+	}
+	gpu_do!(read(data));
+}
@@ -19,6 +19,17 @@ mod tests {
         t.pass("src/macro_usage_9.rs");
         t.pass("src/macro_usage_10.rs");
         t.pass("src/macro_usage_11.rs");
+        t.pass("src/macro_usage_12.rs");
+        t.pass("src/macro_usage_13.rs");
+        t.compile_fail("src/macro_usage_14.rs");
+        t.pass("src/macro_usage_15.rs");
+        t.compile_fail("src/macro_usage_16.rs");
+        t.pass("src/macro_usage_17.rs");
+        t.pass("src/macro_usage_18.rs");
+        t.pass("src/macro_usage_19.rs");
+        t.compile_fail("src/macro_usage_20.rs");
+        t.compile_fail("src/macro_usage_21.rs");
+        t.compile_fail("src/macro_usage_22.rs");
     }
 
     // this tests that bad usage of load and read macro are detected
@@ -28,6 +39,10 @@ mod tests {
         t.pass("src/load_read_2.rs");
         t.compile_fail("src/load_read_3.rs");
         t.compile_fail("src/load_read_4.rs");
+        t.pass("src/load_read_5.rs");
+        t.pass("src/load_read_6.rs");
+        t.pass("src/load_read_7.rs");
+        t.pass("src/load_read_8.rs");
     }
 
     // this tests that bad usage of launch (like launching things
@@ -40,6 +55,20 @@ mod tests {
         t.compile_fail("src/launch_4.rs");
         t.compile_fail("src/launch_5.rs");
         t.pass("src/launch_6.rs");
+        t.pass("src/launch_7.rs");
+        t.pass("src/launch_8.rs");
+        t.pass("src/launch_9.rs");
+        t.pass("src/launch_10.rs");
+        t.pass("src/launch_11.rs");
+        t.pass("src/launch_12.rs");
+        t.pass("src/launch_13.rs");
+        t.pass("src/launch_14.rs");
+        t.compile_fail("src/launch_15.rs");
+        t.pass("src/launch_16.rs");
+        t.compile_fail("src/launch_17.rs");
+        t.pass("src/launch_18.rs");
+        t.compile_fail("src/launch_19.rs");
+        t.pass("src/launch_20.rs");
     }
 
     // test the compile-time errors
@@ -76,6 +105,103 @@ mod tests {
         assert_eq!(data, vec![100.0; 1000]);
     }
 
+    fn assert_close(result: &[f32], expected: impl Iterator<Item = f32>) {
+        for (i, (result, expected)) in result.iter().zip(expected).enumerate() {
+            assert!(
+                (result - expected).abs() <= 1e-4 * expected.abs().max(1.0),
+                "element {} is {} but should be {}",
+                i,
+                result,
+                expected
+            );
+        }
+    }
+
+    // test that each operator is generated as an operator that computes the same thing
+    #[test]
+    #[gpu_use]
+    fn test_operators() {
+        let x: Vec<f32> = (0..1000).map(|i| i as f32 * 0.25 - 100.0).collect();
+        let mut arithmetic = vec![0.0; 1000];
+        let mut compound = vec![0.0; 1000];
+        let mut indexed = vec![0.0; 1000];
+        gpu_do!(with(gpu));
+        gpu.cpu_threshold = 0;
+
+        gpu_do!(load(x));
+        gpu_do!(load(arithmetic));
+        gpu_do!(load(compound));
+        gpu_do!(load(indexed));
+        gpu_do!(launch());
+        for i in 0..1000 {
+            arithmetic[i] = -x[i] + 1.0 / 2.0 - x[i] * 3.0 + x[i] % 3.5;
+            compound[i] = x[i];
+            compound[i] += 1.5;
+            compound[i] -= 0.25;
+            compound[i] *= 2.0;
+            compound[i] /= 4.0;
+            compound[i] %= 0.75;
+            indexed[i] = x[(i * 3 + 1) % 1000] + x[i >> 1] + x[(i << 1) & 511] + x[i ^ 1] + x[i | 1]
+                - x[i / 2];
+        }
+        gpu_do!(read(arithmetic));
+        gpu_do!(read(compound));
+        gpu_do!(read(indexed));
+
+        assert_close(&arithmetic, x.iter().map(|x| -x + 1.0 / 2.0 - x * 3.0 + x % 3.5));
+        assert_close(&compound, x.iter().map(|x| ((x + 1.5 - 0.25) * 2.0 / 4.0) % 0.75));
+        assert_close(
+            &indexed,
+            (0..1000).map(|i| {
+                x[(i * 3 + 1) % 1000] + x[i >> 1] + x[(i << 1) & 511] + x[i ^ 1] + x[i | 1] - x[i / 2]
+            }),
+        );
+    }
+
+    // launches a kernel and returns how many programs were already built when the GPU was created
+    #[gpu_use]
+    fn launch_and_count_programs() -> usize {
+        let mut data = vec![1.0; 1000];
+        gpu_do!(with(gpu));
+        gpu.cpu_threshold = 0;
+        let programs = gpu.programs.len();
+        gpu_do!(load(data));
+        gpu_do!(launch());
+        for i in 0..1000 {
+            data[i] = data[i] * 2.0;
+        }
+        gpu_do!(read(data));
+        assert_eq!(data, vec![2.0; 1000]);
+        programs
+    }
+
+    // test that a GPU reuses the programs built by an earlier GPU on the same thread
+    #[test]
+    fn test_runtime_reuse() {
+        launch_and_count_programs();
+        assert!(launch_and_count_programs() > 0);
+    }
+
+    #[gpu_use]
+    fn halve_cached(mut data: Vec<f32>) -> Vec<f32> {
+        gpu_do!(load_cached(data));
+        gpu_do!(launch());
+        for i in 0..data.len() {
+            data[i] = data[i] * 0.5;
+        }
+        gpu_do!(read(data));
+        data
+    }
+
+    #[test]
+    fn test_load_cached() {
+        let mut data = vec![1024.0; 1000];
+        for _ in 0..10 {
+            data = halve_cached(data);
+        }
+        assert_eq!(data, vec![1.0; 1000]);
+    }
+
     #[test]
     #[gpu_use]
     #[should_panic(expected = "not loaded to GPU")]
@@ -95,4 +221,176 @@ mod tests {
         let data = vec![1.0; 0];
         gpu_do!(load(data));
     }
+
+    #[test]
+    #[gpu_use]
+    fn test_lifted_expressions() {
+        let mut data = vec![1.0; 1000];
+        let scale = 2.0f32;
+        let offset = 3.0f32;
+        gpu_do!(load(data));
+        gpu_do!(launch());
+        for i in 0..1000 {
+            data[i] = data[i] * (scale - offset).abs() + scale * offset;
+        }
+        gpu_do!(read(data));
+        assert_eq!(data, vec![7.0; 1000]);
+    }
+
+    #[test]
+    #[gpu_use]
+    fn test_launch_on_cpu() {
+        let input = vec![1.0; 1000];
+        let mut middle = vec![0.0; 1000];
+        let mut output = vec![0.0; 1000];
+        gpu_do!(with(gpu));
+        gpu.cpu_threshold = usize::MAX;
+        gpu_do!(load(input));
+        gpu_do!(load(middle));
+        gpu_do!(load(output));
+        gpu_do!(launch());
+        for i in 0..1000 {
+            middle[i] = input[i] * 2.0;
+        }
+        assert_eq!(middle, vec![0.0; 1000]);
+        // middle was written by the last launch so it is read back from the GPU for this launch
+        gpu_do!(launch());
+        for i in 0..1000 {
+            output[i] = middle[i] + 1.0;
+        }
+        gpu_do!(read(middle));
+        gpu_do!(read(output));
+        assert_eq!(middle, vec![2.0; 1000]);
+        assert_eq!(output, vec![3.0; 1000]);
+    }
+
+    // test that a launch uses the data on the GPU even when the data on the CPU has changed since it was loaded
+    // no matter whether it runs on the CPU or the GPU
+    #[test]
+    #[gpu_use]
+    fn test_launch_after_changing_loaded_data() {
+        let mut input = vec![1.0; 1000];
+        let mut output = vec![0.0; 1000];
+        gpu_do!(with(gpu));
+        gpu.cpu_threshold = usize::MAX;
+        gpu_do!(load(input));
+        gpu_do!(load(output));
+        input[0] = 5.0;
+        output[1] = 7.0;
+        gpu_do!(launch());
+        for i in 0..1000 {
+            output[i] = output[i] + input[i] * 2.0;
+        }
+        assert_eq!(input[0], 5.0);
+        assert_eq!(output[1], 7.0);
+        gpu_do!(read(output));
+        assert_eq!(output, vec![2.0; 1000]);
+
+        let mut input_on_gpu = vec![1.0; 1000];
+        let mut output_on_gpu = vec![0.0; 1000];
+        gpu_do!(with(gpu));
+        gpu.cpu_threshold = 0;
+        gpu_do!(load(input_on_gpu));
+        gpu_do!(load(output_on_gpu));
+        input_on_gpu[0] = 5.0;
+        output_on_gpu[1] = 7.0;
+        gpu_do!(launch());
+        for i in 0..1000 {
+            output_on_gpu[i] = output_on_gpu[i] + input_on_gpu[i] * 2.0;
+        }
+        gpu_do!(read(output_on_gpu));
+        assert_eq!(output_on_gpu, vec![2.0; 1000]);
+    }
+
+    #[test]
+    #[gpu_use]
+    fn test_launch_on_gpu() {
+        let mut data = vec![1.0; 10];
+        gpu_do!(with(gpu));
+        gpu.cpu_threshold = 0;
+        gpu_do!(load(data));
+        gpu_do!(launch());
+        for i in 0..10 {
+            data[i] = data[i] * 10.0;
+        }
+        gpu_do!(with(gpu));
+        assert!(!gpu.programs.is_empty());
+        gpu_do!(read(data));
+        assert_eq!(data, vec![10.0; 10]);
+    }
+
+    #[test]
+    #[gpu_use]
+    #[should_panic(expected = "not loaded to GPU")]
+    fn test_panic_what_3() {
+        let mut data = vec![1.0; 1000];
+        gpu_do!(load(data));
+        gpu_do!(sync());
+        gpu_do!(unload(data));
+        gpu_do!(read(data));
+    }
+
+    #[test]
+    #[gpu_use]
+    fn test_reserve() {
+        let input = vec![1.0; 1000];
+        let mut output = vec![0.0; 1000];
+        gpu_do!(load(input));
+        gpu_do!(reserve(output, 1000));
+        gpu_do!(launch());
+        for i in 0..1000 {
+            output[i] = input[i] * 2.0;
+        }
+        gpu_do!(sync());
+        gpu_do!(unload(input));
+        gpu_do!(read(output));
+        assert_eq!(output, vec![2.0; 1000]);
+    }
+
+    #[test]
+    #[gpu_use]
+    fn test_launch_map() {
+        let data = vec![1.0; 1000];
+        let offset = 1.0f32;
+        gpu_do!(with(gpu));
+        gpu.cpu_threshold = 0;
+        gpu_do!(load(data));
+        let doubled = gpu_do!(launch_map(data, |x| x * 2.0));
+        let mut result = gpu_do!(launch_map(doubled, |x: f32| x + offset));
+        // the new data isn't on the CPU until it is read
+        assert_eq!(result, vec![0.0; 1000]);
+        gpu_do!(read(result));
+        assert_eq!(result, vec![3.0; 1000]);
+        assert_eq!(data, vec![1.0; 1000]);
+    }
+
+    #[test]
+    #[gpu_use]
+    #[should_panic(expected = "not loaded to GPU")]
+    fn test_panic_what_5() {
+        let data = vec![1.0; 1000];
+        let _mapped = gpu_do!(launch_map(data, |x| x * 2.0));
+    }
+
+    #[test]
+    #[gpu_use]
+    #[should_panic(expected = "has length 500 but is indexed by `i` which goes up to 1000")]
+    fn test_panic_what_2() {
+        let mut data = vec![1.0; 500];
+        gpu_do!(load(data));
+        gpu_do!(launch());
+        for i in 0..1000 {
+            data[i] = data[i] * 10.0;
+        }
+        gpu_do!(read(data));
+    }
+
+    #[test]
+    #[gpu_use]
+    #[should_panic(expected = "`data` has length 500 but was declared to have length 1000")]
+    fn test_panic_what_4() {
+        let data = vec![1.0; 500];
+        gpu_do!(assert_len(data, 1000));
+        gpu_do!(load(data));
+    }
 }
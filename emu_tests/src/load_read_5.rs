@@ -0,0 +1,18 @@
+use em::*;
+
+// this will succeed because sync, unload, and reserve are all valid commands
+#[gpu_use]
+fn main() {
+	let input = vec![1.0; 1000];
+	let mut output = vec![0.0; 1000];
+
+	gpu_do!(load(input));
+	gpu_do!(reserve(output, 1000));
+	gpu_do!(launch());
+	for i in 0..1000 {
+		output[i] = input[i] * 2.0;
+	}
+	gpu_do!(sync());
+	gpu_do!(unload(input));
+	gpu_do!(read(output));
+}
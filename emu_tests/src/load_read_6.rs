@@ -0,0 +1,21 @@
+use em::*;
+
+// this will succeed because data can be named and then used by name from a helper function
+#[gpu_use(double_weights)]
+fn main() {
+	let weights = vec![1.0; 1000];
+	gpu_do!(load(weights));
+	gpu_do!(name(weights, "weights"));
+	double_weights();
+}
+
+#[gpu_use(double_weights)]
+fn double_weights() {
+	let mut weights = vec![0.0; 1000];
+	gpu_do!(name(weights, "weights"));
+	gpu_do!(launch());
+	for i in 0..1000 {
+		weights[i] = weights[i] * 2.0;
+	}
+	gpu_do!(read(weights));
+}
@@ -0,0 +1,15 @@
+use em::*;
+
+// this will succeed because the loop stays within the declared length of data
+#[gpu_use]
+fn main() {
+	let n = 1000;
+	let mut data = vec![0.0; 1000];
+	gpu_do!(assert_len(data, n));
+	gpu_do!(load(data));
+	gpu_do!(launch());
+	for i in 0..1000 {
+		data[i] = data[i] + 1.0;
+	}
+	gpu_do!(read(data));
+}
@@ -0,0 +1,13 @@
+use em::*;
+
+// this will succeed because data loaded with load_cached is loaded like any other data
+#[gpu_use]
+fn main() {
+	let mut data = vec![0.0; 1000];
+	gpu_do!(load_cached(data));
+	gpu_do!(launch());
+	for i in 0..1000 {
+		data[i] = data[i] + 1.0;
+	}
+	gpu_do!(read(data));
+}
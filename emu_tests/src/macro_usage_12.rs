@@ -0,0 +1,19 @@
+use em::*;
+
+#[gpu_use(multiply)]
+fn multiply(mut data: Vec<f32>) -> Vec<f32> {
+	gpu_do!(launch());
+	for i in 0..1000 {
+		data[i] = data[i] * 2.0;
+	}
+	data
+}
+
+// this will pass because the device to create the GPU from can be chosen alongside helper functions
+#[gpu_use(multiply, device = 0)]
+fn main() {
+	let mut data = vec![0.1; 1000];
+	gpu_do!(load(data));
+	data = multiply(data);
+	gpu_do!(read(data));
+}
@@ -0,0 +1,14 @@
+use em::*;
+
+// this will pass because the GPU that is passed around has a name that does not collide with a variable named gpu
+// and it can still be used directly once it is given a name
+#[gpu_use]
+fn main() {
+	let gpu = vec![0.1; 1000];
+	let mut data = gpu.clone();
+	gpu_do!(load(data));
+	gpu_do!(with(device));
+	device.queue.finish().unwrap();
+	gpu_do!(read(data));
+	assert_eq!(data, gpu);
+}
\ No newline at end of file
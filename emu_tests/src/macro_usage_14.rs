@@ -0,0 +1,7 @@
+use em::*;
+
+// this will fail because the GPU that is passed around cannot be shadowed
+#[gpu_use]
+fn main() {
+	let __emu_gpu = 0;
+}
\ No newline at end of file
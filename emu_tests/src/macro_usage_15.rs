@@ -0,0 +1,31 @@
+use em::*;
+
+// this will pass because `?` in a helper function returns the GPU along with the error or None
+#[gpu_use(parse_and_double, first_positive)]
+fn main() {
+	assert_eq!(parse_and_double("0.5").unwrap(), vec![1.0; 1000]);
+	assert!(parse_and_double("half").is_err());
+	assert_eq!(first_positive(vec![-1.0, 2.0]), Some(2.0));
+	assert_eq!(first_positive(vec![-1.0, -2.0]), None);
+}
+
+#[gpu_use(parse_and_double)]
+fn parse_and_double(x: &str) -> Result<Vec<f32>, std::num::ParseFloatError> {
+	let mut data = vec![x.parse::<f32>()?; 1000];
+	gpu_do!(load(data));
+	gpu_do!(launch());
+	for i in 0..1000 {
+		data[i] = data[i] * 2.0;
+	}
+	gpu_do!(read(data));
+	Ok(data)
+}
+
+#[gpu_use(first_positive)]
+fn first_positive(data: Vec<f32>) -> Option<f32> {
+	let mut data = data;
+	gpu_do!(load(data));
+	gpu_do!(read(data));
+	let first = data.iter().find(|x| **x > 0.0)?;
+	Some(*first)
+}
\ No newline at end of file
@@ -0,0 +1,12 @@
+use em::*;
+
+// this will fail because `?` can only be used in helper functions that return a Result or an Option
+#[gpu_use(parse)]
+fn main() {
+	parse("0.5");
+}
+
+#[gpu_use(parse)]
+fn parse(x: &str) -> f32 {
+	x.parse::<f32>()?
+}
\ No newline at end of file
@@ -0,0 +1,78 @@
+use em::*;
+
+// this will pass because the GPU is returned from helper functions no matter how they return
+#[gpu_use(classify, first_large, parse_all, count_down)]
+fn main() {
+	assert_eq!(classify(vec![1.0; 1000]), 1);
+	assert_eq!(classify(vec![-1.0; 1000]), 0);
+	assert_eq!(classify(vec![]), 2);
+	assert_eq!(first_large(vec![1.0, 20.0, 30.0]), Some(0));
+	assert_eq!(first_large(vec![1.0, 2.0]), None);
+	assert_eq!(parse_all(vec!["1", "2"]).unwrap(), vec![2.0, 4.0]);
+	assert!(parse_all(vec!["1", "two"]).is_err());
+	assert!(parse_all(vec!["-1"]).is_err());
+	count_down(3);
+}
+
+// return statements in match guards, match arms, and nested loops
+#[gpu_use(classify)]
+fn classify(data: Vec<f32>) -> u32 {
+	match data.len() {
+		0 => return 2,
+		_ => {}
+	}
+	let mut data = data;
+	gpu_do!(load(data));
+	gpu_do!(read(data));
+	match data[0] {
+		x if { if x < 0.0 { return 0; } false } => unreachable!(),
+		_ => {}
+	}
+	for _ in 0..2 {
+		loop {
+			return 1;
+		}
+	}
+	unreachable!()
+}
+
+// `?` in loops that are broken out of and in match guards
+#[gpu_use(first_large)]
+fn first_large(data: Vec<f32>) -> Option<usize> {
+	let mut i = 0;
+	let index = loop {
+		match data.get(i) {
+			Some(x) if *data.get(i + 1)? > 10.0 * x => break i,
+			_ => i += 1,
+		}
+	};
+	Some(index)
+}
+
+// `?` in macros, closures, and async blocks that don't return from the function
+#[gpu_use(parse_all)]
+fn parse_all(inputs: Vec<&str>) -> Result<Vec<f32>, String> {
+	let parse = |x: &str| -> Result<f32, String> { x.parse::<f32>().map_err(|e| e.to_string()) };
+	let later = async { return 0; };
+	drop(later);
+	let mut data = vec![parse(inputs[0])?; inputs.len()];
+	for i in 0..inputs.len() {
+		data[i] = parse(inputs[i])?;
+		assert!(data[i] == parse(inputs[i])?, "{} was parsed differently", inputs[i]);
+		if data[i] < 0.0 {
+			return Err(format!("{} is negative", inputs[i]));
+		}
+	}
+	gpu_do!(load(data));
+	gpu_do!(read(data));
+	Ok(data.iter().map(|x| x * 2.0).collect())
+}
+
+// return statements with no value
+#[gpu_use(count_down)]
+fn count_down(n: u32) {
+	if n == 0 {
+		return;
+	}
+	count_down(n - 1);
+}
\ No newline at end of file
@@ -0,0 +1,25 @@
+use em::*;
+
+#[gpu_use(multiply, explain)]
+fn multiply(mut data: Vec<f32>, scale: f32) -> Vec<f32> {
+	gpu_do!(launch());
+	for i in 0..1000 {
+		data[i] = data[i] * (scale * 2.0);
+	}
+	let mut scales = vec![0.0; 10];
+	for i in 0..10 {
+		scales[i] = scale;
+	}
+	assert_eq!(scales, vec![5.0; 10]);
+	data
+}
+
+// this will pass because explaining what was done doesn't change what is done
+#[gpu_use(multiply, explain)]
+fn main() {
+	let mut data = vec![0.1; 1000];
+	gpu_do!(load(data));
+	data = multiply(data, 5.0);
+	gpu_do!(read(data));
+	assert_eq!(data, vec![1.0; 1000]);
+}
\ No newline at end of file
@@ -0,0 +1,49 @@
+use em::*;
+
+// this will pass because only `?` and return statements that return from a helper function return the GPU along with them
+#[gpu_use(first_positive, scaled)]
+fn main() {
+	assert_eq!(first_positive(vec!["-1", "2", "3"]), Some(2.0));
+	assert_eq!(first_positive(vec!["-1", "x"]), None);
+	assert_eq!(first_positive(vec![]), None);
+	assert_eq!(scaled(vec!["1", "2"], 2.0).unwrap(), vec![2.0, 4.0]);
+	assert!(scaled(vec!["1", "two"], 2.0).is_err());
+	assert!(scaled(vec!["1", "inf"], 2.0).is_err());
+}
+
+// `?` and return statements in closures and async blocks return from the closure or the async block
+#[gpu_use(first_positive)]
+fn first_positive(inputs: Vec<&str>) -> Option<f32> {
+	let parse = |x: &str| -> Result<f32, String> {
+		if x.is_empty() {
+			return Err(String::from("empty"));
+		}
+		let parsed = x.parse::<f32>().map_err(|e| e.to_string())?;
+		Ok(parsed)
+	};
+	let later = async {
+		let parsed: f32 = "1".parse().map_err(|_| ())?;
+		return Ok::<f32, ()>(parsed);
+	};
+	drop(later);
+	for input in inputs {
+		let x = parse(input).ok()?;
+		if x > 0.0 {
+			return Some(x);
+		}
+	}
+	None
+}
+
+// `?` and return statements in the arguments of macros return from the helper function
+#[gpu_use(scaled)]
+fn scaled(inputs: Vec<&str>, scale: f32) -> Result<Vec<f32>, String> {
+	let parse = |x: &str| x.parse::<f32>().map_err(|e| e.to_string());
+	let mut data = vec![parse(inputs[0])? * scale; inputs.len()];
+	for i in 0..inputs.len() {
+		data[i] = parse(inputs[i])? * scale;
+	}
+	assert!(data.iter().all(|x| x.is_finite()) || { return Err(String::from("not finite")); });
+	println!("{:?}", parse(inputs[0])?);
+	Ok(data)
+}
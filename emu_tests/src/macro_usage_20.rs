@@ -0,0 +1,13 @@
+use em::*;
+
+// this will fail because `?` in the arguments of a macro returns from the helper function, which does not return a Result or an Option
+#[gpu_use(parse)]
+fn main() {
+	parse("0.5");
+}
+
+#[gpu_use(parse)]
+fn parse(x: &str) -> f32 {
+	assert!(x.parse::<f32>()? > 0.0);
+	0.5
+}
\ No newline at end of file
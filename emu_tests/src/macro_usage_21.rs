@@ -0,0 +1,15 @@
+use em::*;
+
+// this will fail because `?` in a closure returns from the closure (which does not return a Result or an Option) and not from the helper function
+#[gpu_use(parse)]
+fn main() {
+	parse("0.5");
+}
+
+#[gpu_use(parse)]
+fn parse(x: &str) -> Option<f32> {
+	let to_f32 = |x: &str| -> f32 {
+		x.parse::<f32>().ok()?
+	};
+	Some(to_f32(x))
+}
\ No newline at end of file
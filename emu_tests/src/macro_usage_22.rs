@@ -0,0 +1,14 @@
+use em::*;
+
+// this will fail because try blocks are unstable
+// `?` in a try block breaks out of the try block so the helper function does not have to return a Result or an Option
+#[gpu_use(parse)]
+fn main() {
+	parse("0.5");
+}
+
+#[gpu_use(parse)]
+fn parse(x: &str) -> f32 {
+	let parsed: Result<f32, std::num::ParseFloatError> = try { x.parse::<f32>()? };
+	parsed.unwrap_or(0.0)
+}
\ No newline at end of file